@@ -0,0 +1,314 @@
+use crate::drawing_engine::ContentBounds;
+use image::RgbaImage;
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// カメラの拡大率の範囲
+pub const MIN_CAMERA_ZOOM: f32 = 0.05;
+pub const MAX_CAMERA_ZOOM: f32 = 32.0;
+
+/// 縮小時に1画素あたり取る標本数（1辺）の上限
+const MAX_SUPERSAMPLES: u32 = 4;
+
+/// カメラ設定のエラー型
+#[derive(Debug, Clone, PartialEq)]
+pub enum CameraError {
+    InvalidKeyframe(String),
+    KeyframeNotFound(usize),
+}
+
+impl fmt::Display for CameraError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CameraError::InvalidKeyframe(msg) => write!(f, "カメラのキーフレームが不正です: {}", msg),
+            CameraError::KeyframeNotFound(frame) => write!(f, "フレーム {} にカメラのキーフレームがありません", frame),
+        }
+    }
+}
+
+impl Error for CameraError {}
+
+/// 次のキーフレームまでの補間方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CameraEasing {
+    #[default]
+    Linear,
+    /// 動き出しと止まり際をゆっくりにする
+    EaseInOut,
+    /// 次のキーフレームまで動かさない
+    Hold,
+}
+
+/// カメラのキーフレーム（`frame` は `Project::frames` の位置）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CameraKeyframe {
+    pub frame: usize,
+    /// 写す範囲の中心（キャンバス座標）
+    pub center_x: f32,
+    pub center_y: f32,
+    /// 拡大率（1.0 で書き出しの大きさと同じ範囲を写す）
+    pub zoom: f32,
+    #[serde(default)]
+    pub easing: CameraEasing,
+}
+
+/// フレームで写すキャンバス上の範囲
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct CameraRect {
+    pub x: f32,
+    pub y: f32,
+    pub width: f32,
+    pub height: f32,
+}
+
+impl CameraRect {
+    /// キャンバス上の矩形が `width`x`height` に切り出した画像で写る範囲（写らなければ None）
+    ///
+    /// `render_camera_view` の補間でにじむ周囲の半画素を含めて、外側に丸める。
+    pub fn project_bounds(&self, bounds: &ContentBounds, width: u32, height: u32) -> Option<ContentBounds> {
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let scale_x = self.width / width as f32;
+        let scale_y = self.height / height as f32;
+        let left = ((bounds.x as f32 - 0.5 - self.x) / scale_x).floor().max(0.0);
+        let top = ((bounds.y as f32 - 0.5 - self.y) / scale_y).floor().max(0.0);
+        let right = (((bounds.x + bounds.width) as f32 + 0.5 - self.x) / scale_x).ceil().min(width as f32);
+        let bottom = (((bounds.y + bounds.height) as f32 + 0.5 - self.y) / scale_y).ceil().min(height as f32);
+        if !(left < right && top < bottom) {
+            return None;
+        }
+        Some(ContentBounds {
+            x: left as u32,
+            y: top as u32,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+}
+
+/// 仮想カメラ（書き出し・再生時にフレームごとにキャンバスの一部を切り出して拡大縮小する）
+///
+/// レイヤーは変更しないため、大きな背景の上でパンやズームを付けられる。
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CameraTrack {
+    pub enabled: bool,
+    /// フレーム順に並んだキーフレーム（同じフレームには1つだけ）
+    pub keyframes: Vec<CameraKeyframe>,
+}
+
+impl CameraKeyframe {
+    pub fn validate(&self) -> Result<(), CameraError> {
+        if !(self.center_x.is_finite() && self.center_y.is_finite()) {
+            return Err(CameraError::InvalidKeyframe("中心の座標が不正です".to_string()));
+        }
+        if !(MIN_CAMERA_ZOOM..=MAX_CAMERA_ZOOM).contains(&self.zoom) {
+            return Err(CameraError::InvalidKeyframe(format!(
+                "拡大率は {}〜{} で指定してください: {}", MIN_CAMERA_ZOOM, MAX_CAMERA_ZOOM, self.zoom
+            )));
+        }
+        Ok(())
+    }
+}
+
+impl CameraTrack {
+    pub fn validate(&self) -> Result<(), CameraError> {
+        for (index, keyframe) in self.keyframes.iter().enumerate() {
+            keyframe.validate()?;
+            if index > 0 && self.keyframes[index - 1].frame >= keyframe.frame {
+                return Err(CameraError::InvalidKeyframe("キーフレームがフレーム順に並んでいません".to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// キーフレームを置く（同じフレームにあれば置き換える）
+    pub fn set_keyframe(&mut self, keyframe: CameraKeyframe) -> Result<(), CameraError> {
+        keyframe.validate()?;
+        match self.keyframes.binary_search_by_key(&keyframe.frame, |k| k.frame) {
+            Ok(index) => self.keyframes[index] = keyframe,
+            Err(index) => self.keyframes.insert(index, keyframe),
+        }
+        Ok(())
+    }
+
+    pub fn remove_keyframe(&mut self, frame: usize) -> Result<CameraKeyframe, CameraError> {
+        let index = self.keyframes.binary_search_by_key(&frame, |k| k.frame)
+            .map_err(|_| CameraError::KeyframeNotFound(frame))?;
+        Ok(self.keyframes.remove(index))
+    }
+
+    /// フレームで写す範囲（無効かキーフレームがなければ None）
+    ///
+    /// 最初のキーフレームより前と最後より後はその位置で止まる。ズームは比で補間し、
+    /// 一定の速さで寄る・引くように見せる。
+    pub fn rect_at(&self, frame: usize, output_width: u32, output_height: u32) -> Option<CameraRect> {
+        if !self.enabled {
+            return None;
+        }
+        let next = self.keyframes.partition_point(|k| k.frame <= frame);
+        let (center_x, center_y, zoom) = match (next.checked_sub(1).map(|i| &self.keyframes[i]), self.keyframes.get(next)) {
+            (None, None) => return None,
+            (Some(k), None) | (None, Some(k)) => (k.center_x, k.center_y, k.zoom),
+            (Some(a), Some(b)) => {
+                let t = (frame - a.frame) as f32 / (b.frame - a.frame) as f32;
+                let t = match a.easing {
+                    CameraEasing::Linear => t,
+                    CameraEasing::EaseInOut => t * t * (3.0 - 2.0 * t),
+                    CameraEasing::Hold => 0.0,
+                };
+                (
+                    a.center_x + (b.center_x - a.center_x) * t,
+                    a.center_y + (b.center_y - a.center_y) * t,
+                    a.zoom * (b.zoom / a.zoom).powf(t),
+                )
+            }
+        };
+        let width = output_width as f32 / zoom;
+        let height = output_height as f32 / zoom;
+        Some(CameraRect { x: center_x - width / 2.0, y: center_y - height / 2.0, width, height })
+    }
+}
+
+/// キャンバスの `rect` の範囲を `width`x`height` に拡大縮小して切り出す（範囲外は透明）
+///
+/// 透明部分の色がにじまないよう、乗算済みアルファで補間する。縮小時は1画素を複数の標本の平均にする。
+pub fn render_camera_view(canvas: &RgbaImage, rect: CameraRect, width: u32, height: u32) -> RgbaImage {
+    let mut output = RgbaImage::new(width, height);
+    if width == 0 || height == 0 || canvas.width() == 0 || canvas.height() == 0 {
+        return output;
+    }
+    let scale_x = rect.width / width as f32;
+    let scale_y = rect.height / height as f32;
+    let samples = (scale_x.max(scale_y).ceil() as u32).clamp(1, MAX_SUPERSAMPLES);
+
+    output.par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                let mut sum = [0.0f32; 4];
+                for sy in 0..samples {
+                    for sx in 0..samples {
+                        let u = rect.x + (x as f32 + (sx as f32 + 0.5) / samples as f32) * scale_x;
+                        let v = rect.y + (y as f32 + (sy as f32 + 0.5) / samples as f32) * scale_y;
+                        let sample = sample_bilinear(canvas, u - 0.5, v - 0.5);
+                        for (total, value) in sum.iter_mut().zip(sample) {
+                            *total += value;
+                        }
+                    }
+                }
+                let count = (samples * samples) as f32;
+                let alpha = sum[3] / count;
+                if alpha <= 0.0 {
+                    continue;
+                }
+                for channel in 0..3 {
+                    pixel[channel] = (sum[channel] / count / alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+                }
+                pixel[3] = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+            }
+        });
+    output
+}
+
+/// 乗算済みアルファ（0〜1）でのバイリニア補間（画素中心が整数座標）
+fn sample_bilinear(image: &RgbaImage, x: f32, y: f32) -> [f32; 4] {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let mut result = [0.0; 4];
+    for (dy, wy) in [(0, 1.0 - fy), (1, fy)] {
+        for (dx, wx) in [(0, 1.0 - fx), (1, fx)] {
+            let weight = wx * wy;
+            let (px, py) = (x0 as i64 + dx, y0 as i64 + dy);
+            if weight <= 0.0 || px < 0 || py < 0 || px >= image.width() as i64 || py >= image.height() as i64 {
+                continue;
+            }
+            let pixel = image.get_pixel(px as u32, py as u32).0;
+            let alpha = pixel[3] as f32 / 255.0;
+            for channel in 0..3 {
+                result[channel] += pixel[channel] as f32 / 255.0 * alpha * weight;
+            }
+            result[3] += alpha * weight;
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn keyframe(frame: usize, center_x: f32, zoom: f32, easing: CameraEasing) -> CameraKeyframe {
+        CameraKeyframe { frame, center_x, center_y: 50.0, zoom, easing }
+    }
+
+    #[test]
+    fn test_rect_interpolates_between_keyframes() {
+        let mut track = CameraTrack { enabled: true, keyframes: Vec::new() };
+        assert_eq!(track.rect_at(0, 100, 50), None);
+        track.set_keyframe(keyframe(10, 150.0, 4.0, CameraEasing::Linear)).unwrap();
+        track.set_keyframe(keyframe(0, 50.0, 1.0, CameraEasing::Linear)).unwrap();
+        assert!(track.validate().is_ok());
+
+        assert_eq!(track.rect_at(0, 100, 50), Some(CameraRect { x: 0.0, y: 25.0, width: 100.0, height: 50.0 }));
+        // ズームは比で補間する（1倍と4倍の中間は2倍）
+        let middle = track.rect_at(5, 100, 50).unwrap();
+        assert_eq!((middle.x + middle.width / 2.0, middle.width), (100.0, 50.0));
+        // 最後のキーフレーム以降は止まる
+        assert_eq!(track.rect_at(30, 100, 50), track.rect_at(10, 100, 50));
+
+        track.set_keyframe(keyframe(0, 50.0, 1.0, CameraEasing::Hold)).unwrap();
+        assert_eq!(track.rect_at(9, 100, 50), track.rect_at(0, 100, 50));
+        assert!(track.remove_keyframe(3).is_err());
+        assert!(track.set_keyframe(keyframe(3, 0.0, 0.0, CameraEasing::Linear)).is_err());
+
+        track.enabled = false;
+        assert_eq!(track.rect_at(5, 100, 50), None);
+    }
+
+    #[test]
+    fn test_render_camera_view_windows_canvas() {
+        let mut canvas = RgbaImage::new(4, 4);
+        canvas.put_pixel(2, 1, image::Rgba([255, 0, 0, 255]));
+
+        // 等倍で切り出すと画素はそのまま
+        let view = render_camera_view(&canvas, CameraRect { x: 1.0, y: 0.0, width: 2.0, height: 2.0 }, 2, 2);
+        assert_eq!(view.get_pixel(1, 1).0, [255, 0, 0, 255]);
+        assert_eq!(view.get_pixel(0, 0).0[3], 0);
+
+        // 拡大すると縁は透明な周囲と補間されるが、色はにじまない
+        let view = render_camera_view(&canvas, CameraRect { x: 2.0, y: 0.0, width: 2.0, height: 2.0 }, 4, 4);
+        let edge = view.get_pixel(0, 3).0;
+        assert_eq!(&edge[..3], &[255, 0, 0]);
+        assert!(edge[3] > 0 && edge[3] < 255);
+
+        // キャンバスの外まで引くと周りは透明、縮小した点は薄くなるが色は変わらない
+        let view = render_camera_view(&canvas, CameraRect { x: -4.0, y: -4.0, width: 12.0, height: 12.0 }, 3, 3);
+        assert_eq!(view.get_pixel(0, 0).0[3], 0);
+        let center = view.get_pixel(1, 1).0;
+        assert_eq!(&center[..3], &[255, 0, 0]);
+        assert!(center[3] > 0 && center[3] < 255);
+    }
+
+    #[test]
+    fn test_project_bounds_covers_rendered_pixels() {
+        let mut canvas = RgbaImage::new(8, 8);
+        canvas.put_pixel(5, 2, image::Rgba([255, 0, 0, 255]));
+        let content = ContentBounds::from_image(&canvas).unwrap();
+        for rect in [
+            CameraRect { x: 1.0, y: 0.0, width: 6.0, height: 6.0 },
+            CameraRect { x: 4.0, y: 1.0, width: 2.0, height: 2.0 },
+            CameraRect { x: -4.0, y: -4.0, width: 16.0, height: 16.0 },
+        ] {
+            let rendered = ContentBounds::from_image(&render_camera_view(&canvas, rect, 6, 6)).unwrap();
+            let projected = rect.project_bounds(&content, 6, 6).unwrap();
+            assert_eq!(rendered.union(&projected), projected, "{:?}", rect);
+        }
+        // 写す範囲の外
+        assert_eq!(CameraRect { x: 0.0, y: 4.0, width: 4.0, height: 4.0 }.project_bounds(&content, 4, 4), None);
+    }
+}
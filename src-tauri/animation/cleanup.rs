@@ -0,0 +1,239 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use super::Project;
+
+/// 整理の対象（既定ではすべて）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CleanupOptions {
+    /// 完全に透明なレイヤー（フレームの最後の1枚は残す）
+    pub empty_layers: bool,
+    /// どのシーンにも含まれないフレーム（シーンがなければ対象なし）
+    pub unused_frames: bool,
+    /// どのレイヤーからも参照されていないテクスチャ
+    pub orphaned_textures: bool,
+}
+
+impl Default for CleanupOptions {
+    fn default() -> Self {
+        Self { empty_layers: true, unused_frames: true, orphaned_textures: true }
+    }
+}
+
+/// 完全に透明なレイヤー
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct EmptyLayer {
+    pub frame_id: String,
+    pub layer_id: String,
+}
+
+/// 整理で取り除くもの（`removed_textures` は他から参照されていないテクスチャだけ）
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CleanupReport {
+    pub empty_layers: Vec<EmptyLayer>,
+    pub unused_frames: Vec<String>,
+    pub orphaned_textures: Vec<String>,
+    pub removed_textures: Vec<String>,
+    /// 取り除くテクスチャの画素の合計（RGBA 8bit）
+    pub freed_bytes: u64,
+}
+
+impl CleanupReport {
+    pub fn is_empty(&self) -> bool {
+        self.empty_layers.is_empty() && self.unused_frames.is_empty() && self.orphaned_textures.is_empty()
+    }
+}
+
+impl Project {
+    /// どのシーンにも含まれないフレームのID（シーンがなければ全フレームを使うので空）
+    pub fn unused_frame_ids(&self) -> Vec<String> {
+        if self.scenes.is_empty() {
+            return Vec::new();
+        }
+        let used: HashSet<&str> = self.scenes.iter()
+            .flat_map(|scene| scene.frame_ids.iter().map(String::as_str))
+            .collect();
+        self.frames.iter()
+            .filter(|frame| !used.contains(frame.id.as_str()))
+            .map(|frame| frame.id.clone())
+            .collect()
+    }
+
+    /// 整理の計画を立てる
+    ///
+    /// `empty_textures` は完全に透明なテクスチャのID、`textures` はエンジンにあるテクスチャとその大きさ。
+    pub fn plan_cleanup(
+        &self,
+        options: &CleanupOptions,
+        empty_textures: &HashSet<String>,
+        textures: &HashMap<String, (u32, u32)>,
+    ) -> CleanupReport {
+        let mut report = CleanupReport::default();
+        if options.unused_frames {
+            report.unused_frames = self.unused_frame_ids();
+        }
+
+        if options.empty_layers {
+            for frame in self.frames.iter().filter(|frame| !report.unused_frames.contains(&frame.id)) {
+                let empty: Vec<&str> = frame.layers.iter()
+                    .filter(|layer| layer.fill.is_none() && empty_textures.contains(&layer.id))
+                    .map(|layer| layer.id.as_str())
+                    .collect();
+                let keep_last = empty.len() == frame.layers.len();
+                report.empty_layers.extend(
+                    empty.iter()
+                        .take(empty.len() - keep_last as usize)
+                        .map(|layer_id| EmptyLayer { frame_id: frame.id.clone(), layer_id: layer_id.to_string() })
+                );
+            }
+        }
+
+        let referenced: HashSet<String> = self.frames.iter()
+            .flat_map(|frame| frame.texture_ids().map(String::from))
+            .collect();
+        if options.orphaned_textures {
+            report.orphaned_textures = textures.keys()
+                .filter(|id| !referenced.contains(*id))
+                .cloned()
+                .collect();
+            report.orphaned_textures.sort();
+        }
+
+        // 整理後にも参照されるテクスチャは残す（同じレイヤーを複数のフレームで使う場合）
+        let mut cleaned = self.clone();
+        cleaned.apply_cleanup(&report);
+        let remaining: HashSet<String> = cleaned.frames.iter()
+            .flat_map(|frame| frame.texture_ids().map(String::from))
+            .collect();
+        let mut removed: Vec<String> = referenced.into_iter()
+            .filter(|id| !remaining.contains(id))
+            .chain(report.orphaned_textures.iter().cloned())
+            .filter(|id| textures.contains_key(id))
+            .collect();
+        removed.sort();
+        removed.dedup();
+        report.freed_bytes = removed.iter()
+            .map(|id| textures[id])
+            .map(|(width, height)| width as u64 * height as u64 * 4)
+            .sum();
+        report.removed_textures = removed;
+        report
+    }
+
+    /// 計画どおりにフレームとレイヤーを取り除く
+    ///
+    /// 取り除いたフレームのマーカーとカメラのキーフレームは削除し、残りはフレーム番号を詰める。
+    pub fn apply_cleanup(&mut self, report: &CleanupReport) {
+        for frame in &mut self.frames {
+            frame.layers.retain(|layer| {
+                !report.empty_layers.iter().any(|empty| empty.frame_id == frame.id && empty.layer_id == layer.id)
+            });
+        }
+        if report.unused_frames.is_empty() {
+            return;
+        }
+
+        let mut index_map = Vec::with_capacity(self.frames.len());
+        let mut next = 0;
+        for frame in &self.frames {
+            if report.unused_frames.contains(&frame.id) {
+                index_map.push(None);
+            } else {
+                index_map.push(Some(next));
+                next += 1;
+            }
+        }
+        let remap = |frame: usize| index_map.get(frame).copied().flatten();
+        self.frames.retain(|frame| !report.unused_frames.contains(&frame.id));
+        self.markers.remap(remap);
+        self.camera.keyframes.retain_mut(|keyframe| match remap(keyframe.frame) {
+            Some(frame) => {
+                keyframe.frame = frame;
+                true
+            }
+            None => false,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{BlendMode, CameraEasing, CameraKeyframe, Frame, Layer, LayerMask, Scene};
+
+    fn layer(id: &str) -> Layer {
+        Layer {
+            id: id.to_string(),
+            name: id.to_string(),
+            visible: true,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            locked: false,
+            effects: Vec::new(),
+            fill: None,
+            mask: None,
+        }
+    }
+
+    fn frame(id: &str, layers: Vec<Layer>) -> Frame {
+        Frame { id: id.to_string(), layers, duration: 1.0 / 24.0 }
+    }
+
+    #[test]
+    fn test_plan_and_apply_cleanup() {
+        let mut project = Project::new("test".to_string(), 4, 4, 24.0);
+        let masked = Layer { mask: Some(LayerMask { texture_id: "b_mask".to_string(), enabled: true }), ..layer("b") };
+        project.frames = vec![
+            frame("f0", vec![layer("a"), masked]),
+            frame("f1", vec![layer("c")]),
+            frame("f2", vec![layer("d"), layer("a")]),
+        ];
+        project.scenes = vec![Scene {
+            id: "s".to_string(),
+            name: "s".to_string(),
+            frame_ids: vec!["f0".to_string(), "f2".to_string()],
+            width: None,
+            height: None,
+            duration: None,
+        }];
+        project.markers.add(1, "removed", None).unwrap();
+        project.markers.add(2, "kept", None).unwrap();
+        project.camera.keyframes = [0, 2].map(|frame| CameraKeyframe {
+            frame,
+            center_x: 2.0,
+            center_y: 2.0,
+            zoom: 1.0,
+            easing: CameraEasing::Linear,
+        }).to_vec();
+
+        let textures: HashMap<String, (u32, u32)> = ["a", "b", "b_mask", "c", "d", "stale"]
+            .map(|id| (id.to_string(), (4, 4)))
+            .into();
+        let empty: HashSet<String> = ["b", "d", "c"].map(String::from).into();
+        let report = project.plan_cleanup(&CleanupOptions::default(), &empty, &textures);
+
+        assert_eq!(report.unused_frames, vec!["f1".to_string()]);
+        // 最後の1枚ではない空のレイヤーだけ
+        assert_eq!(report.empty_layers, vec![
+            EmptyLayer { frame_id: "f0".to_string(), layer_id: "b".to_string() },
+            EmptyLayer { frame_id: "f2".to_string(), layer_id: "d".to_string() },
+        ]);
+        assert_eq!(report.orphaned_textures, vec!["stale".to_string()]);
+        assert_eq!(report.removed_textures, ["b", "b_mask", "c", "d", "stale"].map(String::from).to_vec());
+        assert_eq!(report.freed_bytes, 5 * 64);
+
+        project.apply_cleanup(&report);
+        let layout: Vec<(&str, Vec<&str>)> = project.frames.iter()
+            .map(|frame| (frame.id.as_str(), frame.layers.iter().map(|layer| layer.id.as_str()).collect()))
+            .collect();
+        assert_eq!(layout, vec![("f0", vec!["a"]), ("f2", vec!["a"])]);
+        let markers: Vec<(&str, usize)> = project.markers.markers().iter()
+            .map(|marker| (marker.name.as_str(), marker.frame))
+            .collect();
+        assert_eq!(markers, vec![("kept", 1)]);
+        assert_eq!(project.camera.keyframes.iter().map(|k| k.frame).collect::<Vec<_>>(), vec![0, 1]);
+
+        let options = CleanupOptions { empty_layers: false, unused_frames: false, orphaned_textures: false };
+        assert!(project.plan_cleanup(&options, &empty, &textures).is_empty());
+    }
+}
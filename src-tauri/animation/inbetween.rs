@@ -0,0 +1,201 @@
+use std::collections::HashSet;
+use crate::history::{HistoryEntry, Operation, StrokePointRecord, StrokeRecord};
+use super::Frame;
+
+/// 一度に作る中割りの上限
+pub const MAX_INBETWEENS: usize = 24;
+
+/// フレームに描かれたベクターのストローク（履歴の順）
+///
+/// フレームのレイヤーへの `DrawStroke` を集める。レイヤーの消去・削除より前のストロークは含めない。
+/// 消しゴム・ブラシ・貼り付けなどのストローク以外の操作は中割りの対象外。
+pub fn frame_strokes(entries: &[HistoryEntry], frame: &Frame) -> Vec<StrokeRecord> {
+    let layer_ids: HashSet<&str> = frame.layers.iter()
+        .filter(|layer| layer.visible)
+        .map(|layer| layer.id.as_str())
+        .collect();
+    let mut strokes: Vec<(&str, &StrokeRecord)> = Vec::new();
+    for entry in entries {
+        match &entry.operation {
+            Operation::DrawStroke { layer_id, stroke } if layer_ids.contains(layer_id.as_str()) => {
+                strokes.push((layer_id, stroke));
+            }
+            Operation::ClearLayer { layer_id } | Operation::RemoveLayer { layer_id } => {
+                strokes.retain(|(id, _)| id != layer_id);
+            }
+            _ => {}
+        }
+    }
+    strokes.into_iter().map(|(_, stroke)| stroke.clone()).collect()
+}
+
+/// 中割りの時刻（両端のキーフレームを除き、等間隔）
+pub fn inbetween_times(count: usize) -> Vec<f32> {
+    (1..=count).map(|i| i as f32 / (count + 1) as f32).collect()
+}
+
+/// 2つのキーフレームのストロークの中割り（`t` = 0 で `a`、1 で `b`）
+///
+/// ストロークは描いた順に対応付ける。対応する相手のないストロークは、`a` 側は消えていくように、
+/// `b` 側は現れるように不透明度を変える。
+pub fn inbetween_strokes(a: &[StrokeRecord], b: &[StrokeRecord], t: f32) -> Vec<StrokeRecord> {
+    let paired = a.len().min(b.len());
+    let mut strokes: Vec<StrokeRecord> = a.iter().zip(b)
+        .map(|(a, b)| morph_stroke(a, b, t))
+        .collect();
+    let fade = |stroke: &StrokeRecord, opacity: f32| {
+        let mut stroke = stroke.clone();
+        stroke.color[3] *= opacity;
+        stroke
+    };
+    strokes.extend(a[paired..].iter().map(|stroke| fade(stroke, 1.0 - t)));
+    strokes.extend(b[paired..].iter().map(|stroke| fade(stroke, t)));
+    strokes.retain(|stroke| !stroke.points.is_empty() && stroke.color[3] > 0.0);
+    strokes
+}
+
+/// 2つのストロークを弧長で点を対応付けて補間
+///
+/// 両方を同じ点の数に弧長で等間隔に並べ直し、位置・筆圧・色・線幅を線形補間する。
+/// 逆向きに描いたストロークは向きを揃えてから対応付ける。
+pub fn morph_stroke(a: &StrokeRecord, b: &StrokeRecord, t: f32) -> StrokeRecord {
+    if a.points.is_empty() || b.points.is_empty() {
+        return if t < 0.5 { a.clone() } else { b.clone() };
+    }
+    let count = a.points.len().max(b.points.len()).max(2);
+    let from = resample(&a.points, count);
+    let mut to = resample(&b.points, count);
+    if is_reversed(&from, &to) {
+        to.reverse();
+    }
+
+    let lerp = |x: f32, y: f32| x + (y - x) * t;
+    let points = from.iter().zip(&to)
+        .map(|(p, q)| StrokePointRecord {
+            x: lerp(p.x, q.x),
+            y: lerp(p.y, q.y),
+            pressure: lerp(p.pressure, q.pressure),
+            time_ms: None,
+        })
+        .collect();
+    StrokeRecord {
+        points,
+        color: std::array::from_fn(|i| lerp(a.color[i], b.color[i])),
+        base_width: lerp(a.base_width, b.base_width),
+    }
+}
+
+/// 逆向きに対応付けた方が端点同士が近いか
+fn is_reversed(a: &[StrokePointRecord], b: &[StrokePointRecord]) -> bool {
+    let distance = |p: &StrokePointRecord, q: &StrokePointRecord| (p.x - q.x).hypot(p.y - q.y);
+    let (a_first, a_last) = (&a[0], &a[a.len() - 1]);
+    let (b_first, b_last) = (&b[0], &b[b.len() - 1]);
+    distance(a_first, b_last) + distance(a_last, b_first) < distance(a_first, b_first) + distance(a_last, b_last)
+}
+
+/// 点列を弧長で等間隔の `count` 点に並べ直す（長さのない点列は最初の点を繰り返す）
+fn resample(points: &[StrokePointRecord], count: usize) -> Vec<StrokePointRecord> {
+    let mut lengths = Vec::with_capacity(points.len());
+    let mut total = 0.0;
+    lengths.push(0.0);
+    for pair in points.windows(2) {
+        total += (pair[1].x - pair[0].x).hypot(pair[1].y - pair[0].y);
+        lengths.push(total);
+    }
+    if total <= 0.0 {
+        return vec![points[0]; count];
+    }
+
+    (0..count)
+        .map(|i| {
+            let target = total * i as f32 / (count - 1).max(1) as f32;
+            // target を含む区間 [k, k + 1]
+            let k = lengths.partition_point(|&length| length < target).clamp(1, points.len() - 1) - 1;
+            let span = lengths[k + 1] - lengths[k];
+            let s = if span > 0.0 { ((target - lengths[k]) / span).clamp(0.0, 1.0) } else { 0.0 };
+            let (p, q) = (&points[k], &points[k + 1]);
+            StrokePointRecord {
+                x: p.x + (q.x - p.x) * s,
+                y: p.y + (q.y - p.y) * s,
+                pressure: p.pressure + (q.pressure - p.pressure) * s,
+                time_ms: None,
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{BlendMode, Layer};
+
+    fn stroke(points: &[(f32, f32)], color: [f32; 4]) -> StrokeRecord {
+        StrokeRecord {
+            points: points.iter().map(|&(x, y)| StrokePointRecord { x, y, pressure: 1.0, time_ms: None }).collect(),
+            color,
+            base_width: 4.0,
+        }
+    }
+
+    #[test]
+    fn test_morph_by_arc_length() {
+        // 点の数と間隔が違っても、弧長で対応付ける
+        let a = stroke(&[(0.0, 0.0), (10.0, 0.0)], [0.0, 0.0, 0.0, 1.0]);
+        let b = stroke(&[(0.0, 20.0), (1.0, 20.0), (10.0, 20.0)], [1.0, 0.0, 0.0, 1.0]);
+        let middle = morph_stroke(&a, &b, 0.5);
+        assert_eq!(middle.points.len(), 3);
+        let xs: Vec<f32> = middle.points.iter().map(|p| p.x).collect();
+        assert_eq!(xs, vec![0.0, 5.0, 10.0]);
+        assert!(middle.points.iter().all(|p| p.y == 10.0));
+        assert_eq!(middle.color, [0.5, 0.0, 0.0, 1.0]);
+
+        // 逆向きに描いたストロークも同じ向きに揃える
+        let reversed = stroke(&[(10.0, 20.0), (0.0, 20.0)], [0.0, 0.0, 0.0, 1.0]);
+        let middle = morph_stroke(&a, &reversed, 0.5);
+        assert_eq!((middle.points[0].x, middle.points[1].x), (0.0, 10.0));
+        assert_eq!(morph_stroke(&a, &b, 0.0).points[2].x, a.points[1].x);
+    }
+
+    #[test]
+    fn test_unpaired_strokes_fade() {
+        let a = vec![stroke(&[(0.0, 0.0), (10.0, 0.0)], [0.0, 0.0, 0.0, 1.0])];
+        let b = vec![
+            stroke(&[(0.0, 10.0), (10.0, 10.0)], [0.0, 0.0, 0.0, 1.0]),
+            stroke(&[(5.0, 5.0), (6.0, 6.0)], [0.0, 0.0, 0.0, 0.8]),
+        ];
+        let strokes = inbetween_strokes(&a, &b, 0.25);
+        assert_eq!(strokes.len(), 2);
+        assert!((strokes[1].color[3] - 0.2).abs() < 1e-6);
+        assert_eq!(inbetween_strokes(&a, &b, 0.0).len(), 1);
+        assert_eq!(inbetween_times(3), vec![0.25, 0.5, 0.75]);
+    }
+
+    #[test]
+    fn test_frame_strokes_follow_history() {
+        let layer = |id: &str| Layer {
+            id: id.to_string(),
+            name: id.to_string(),
+            visible: true,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            locked: false,
+            effects: Vec::new(),
+            fill: None,
+            mask: None,
+        };
+        let frame = Frame { id: "f".to_string(), layers: vec![layer("a"), layer("b")], duration: 1.0 };
+        let draw = |layer_id: &str, x: f32| HistoryEntry {
+            seq: 0,
+            timestamp: 0,
+            operation: Operation::DrawStroke {
+                layer_id: layer_id.to_string(),
+                stroke: stroke(&[(x, 0.0), (x, 10.0)], [0.0; 4]),
+            },
+        };
+        let clear = HistoryEntry { seq: 0, timestamp: 0, operation: Operation::ClearLayer { layer_id: "a".to_string() } };
+        let entries = vec![draw("a", 1.0), draw("b", 2.0), clear, draw("other", 3.0), draw("a", 4.0)];
+
+        let xs: Vec<f32> = frame_strokes(&entries, &frame).iter().map(|s| s.points[0].x).collect();
+        assert_eq!(xs, vec![2.0, 4.0]);
+    }
+}
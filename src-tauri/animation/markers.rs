@@ -0,0 +1,182 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// マーカー名の最大文字数
+pub const MAX_MARKER_NAME_CHARS: usize = 64;
+
+/// マーカーの既定の色（RGB）
+pub const DEFAULT_MARKER_COLOR: [u8; 3] = [255, 196, 0];
+
+/// フレームマーカーのエラー型
+#[derive(Debug, Clone, PartialEq)]
+pub enum MarkerError {
+    InvalidName(String),
+    MarkerNotFound(String),
+}
+
+impl fmt::Display for MarkerError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MarkerError::InvalidName(msg) => write!(f, "マーカー名が不正です: {}", msg),
+            MarkerError::MarkerNotFound(id) => write!(f, "マーカーが見つかりません: {}", id),
+        }
+    }
+}
+
+impl Error for MarkerError {}
+
+/// フレームに付ける名前付きのマーカー（「歩きのサイクル開始」など）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameMarker {
+    pub id: String,
+    /// 0 始まりのフレーム番号
+    pub frame: usize,
+    pub name: String,
+    /// タイムラインでの表示色（RGB）
+    pub color: [u8; 3],
+}
+
+/// プロジェクトのフレームマーカー（常にフレーム順、同じフレームでは追加順に並ぶ）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct FrameMarkers {
+    markers: Vec<FrameMarker>,
+}
+
+impl FrameMarkers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn markers(&self) -> &[FrameMarker] {
+        &self.markers
+    }
+
+    /// フレーム番号が `range` に入るマーカー
+    pub fn in_range(&self, range: std::ops::RangeInclusive<usize>) -> impl Iterator<Item = &FrameMarker> {
+        self.markers.iter().filter(move |marker| range.contains(&marker.frame))
+    }
+
+    pub fn add(&mut self, frame: usize, name: &str, color: Option<[u8; 3]>) -> Result<FrameMarker, MarkerError> {
+        let name = validate_name(name)?;
+        let timestamp = chrono::Utc::now().timestamp_millis();
+        let id = (self.markers.len()..)
+            .map(|n| format!("marker_{}_{}", timestamp, n))
+            .find(|id| self.markers.iter().all(|marker| &marker.id != id))
+            .expect("未使用のマーカーIDが見つかる");
+        let marker = FrameMarker { id, frame, name, color: color.unwrap_or(DEFAULT_MARKER_COLOR) };
+        self.markers.push(marker.clone());
+        self.sort();
+        Ok(marker)
+    }
+
+    /// 名前と色を変更（色を省略すると元の色のまま）
+    pub fn rename(&mut self, marker_id: &str, name: &str, color: Option<[u8; 3]>) -> Result<FrameMarker, MarkerError> {
+        let name = validate_name(name)?;
+        let marker = self.find_mut(marker_id)?;
+        marker.name = name;
+        if let Some(color) = color {
+            marker.color = color;
+        }
+        Ok(marker.clone())
+    }
+
+    /// 別のフレームに移動
+    pub fn move_to(&mut self, marker_id: &str, frame: usize) -> Result<FrameMarker, MarkerError> {
+        let marker = self.find_mut(marker_id)?;
+        marker.frame = frame;
+        let marker = marker.clone();
+        self.sort();
+        Ok(marker)
+    }
+
+    pub fn remove(&mut self, marker_id: &str) -> Result<FrameMarker, MarkerError> {
+        let index = self.markers.iter().position(|marker| marker.id == marker_id)
+            .ok_or_else(|| MarkerError::MarkerNotFound(marker_id.to_string()))?;
+        Ok(self.markers.remove(index))
+    }
+
+    /// フレーム番号を付け直す（`map` が None を返すフレームのマーカーは削除する）
+    pub fn remap(&mut self, map: impl Fn(usize) -> Option<usize>) {
+        self.markers.retain_mut(|marker| match map(marker.frame) {
+            Some(frame) => {
+                marker.frame = frame;
+                true
+            }
+            None => false,
+        });
+        self.sort();
+    }
+
+    /// 読み込んだマーカーの検証（名前を確かめ、フレーム順に並べ直す）
+    pub fn validate(&mut self) -> Result<(), MarkerError> {
+        for marker in &self.markers {
+            validate_name(&marker.name)?;
+        }
+        self.sort();
+        Ok(())
+    }
+
+    fn find_mut(&mut self, marker_id: &str) -> Result<&mut FrameMarker, MarkerError> {
+        self.markers.iter_mut().find(|marker| marker.id == marker_id)
+            .ok_or_else(|| MarkerError::MarkerNotFound(marker_id.to_string()))
+    }
+
+    fn sort(&mut self) {
+        self.markers.sort_by_key(|marker| marker.frame);
+    }
+}
+
+/// 前後の空白を除いた名前（空や長すぎる名前はエラー）
+fn validate_name(name: &str) -> Result<String, MarkerError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(MarkerError::InvalidName("名前が空です".to_string()));
+    }
+    if name.chars().count() > MAX_MARKER_NAME_CHARS {
+        return Err(MarkerError::InvalidName(format!("{} 文字以内で指定してください", MAX_MARKER_NAME_CHARS)));
+    }
+    Ok(name.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_markers_stay_in_frame_order() {
+        let mut markers = FrameMarkers::new();
+        let walk = markers.add(12, " 歩きのサイクル開始 ", None).unwrap();
+        let start = markers.add(0, "カット頭", Some([255, 0, 0])).unwrap();
+        let end = markers.add(12, "止め", None).unwrap();
+        assert_ne!(walk.id, end.id);
+        assert_eq!(walk.name, "歩きのサイクル開始");
+        assert_eq!(walk.color, DEFAULT_MARKER_COLOR);
+        let names: Vec<&str> = markers.markers().iter().map(|m| m.name.as_str()).collect();
+        assert_eq!(names, vec!["カット頭", "歩きのサイクル開始", "止め"]);
+
+        markers.move_to(&start.id, 20).unwrap();
+        assert_eq!(markers.markers().last().unwrap().id, start.id);
+        assert_eq!(markers.in_range(10..=15).count(), 2);
+
+        let renamed = markers.rename(&end.id, "止め（3コマ）", Some([0, 128, 255])).unwrap();
+        assert_eq!((renamed.name.as_str(), renamed.color), ("止め（3コマ）", [0, 128, 255]));
+        assert_eq!(markers.remove(&walk.id).unwrap().frame, 12);
+        assert_eq!(markers.remove(&walk.id), Err(MarkerError::MarkerNotFound(walk.id.clone())));
+    }
+
+    #[test]
+    fn test_marker_names_are_validated() {
+        let mut markers = FrameMarkers::new();
+        assert!(markers.add(0, "   ", None).is_err());
+        assert!(markers.add(0, &"あ".repeat(MAX_MARKER_NAME_CHARS + 1), None).is_err());
+        assert!(markers.add(0, &"あ".repeat(MAX_MARKER_NAME_CHARS), None).is_ok());
+
+        // 保存形式はマーカーの配列そのもの
+        let json = r#"[{"id":"b","frame":5,"name":"後","color":[0,0,0]},{"id":"a","frame":1,"name":"前","color":[0,0,0]}]"#;
+        let mut loaded: FrameMarkers = serde_json::from_str(json).unwrap();
+        loaded.validate().unwrap();
+        assert_eq!(loaded.markers()[0].id, "a");
+    }
+}
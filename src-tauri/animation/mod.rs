@@ -1,5 +1,34 @@
 use serde::{Deserialize, Serialize};
 use chrono;
+use crate::history::OperationLog;
+use crate::timelapse::TimelapseBuffer;
+use crate::guides::GuideSettings;
+use crate::formats::{ExportSettings, LayerEffect, LayerFill};
+use crate::drawing_engine::CanvasRenderSettings;
+
+// キーフレーム間の中割り（実験的）
+pub mod inbetween;
+pub use inbetween::{frame_strokes, inbetween_strokes, inbetween_times, MAX_INBETWEENS};
+
+// 再生範囲・繰り返しの制御
+pub mod player;
+pub use player::{LoopMode, PlaybackOptions, Player};
+
+// フレームのマーカー
+pub mod markers;
+pub use markers::{FrameMarker, FrameMarkers, MarkerError};
+
+// シーン（カット）の構成
+pub mod scenes;
+pub use scenes::{DuplicatedScene, ExportFrame, Scene, SceneError, SceneExportMode, SceneUpdate};
+
+// 仮想カメラ（パン・ズーム）
+pub mod camera;
+pub use camera::{render_camera_view, CameraEasing, CameraError, CameraKeyframe, CameraRect, CameraTrack};
+
+// 空のレイヤー・未使用のフレーム・孤立したテクスチャの整理
+pub mod cleanup;
+pub use cleanup::{CleanupOptions, CleanupReport, EmptyLayer};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Frame {
@@ -16,6 +45,42 @@ pub struct Layer {
     pub opacity: f32,
     pub blend_mode: BlendMode,
     pub locked: bool,
+    /// 非破壊のレイヤー効果（下から順に重ねる）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub effects: Vec<LayerEffect>,
+    /// 塗りつぶしレイヤーの内容（指定すると画素を持たず、合成時に生成する）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub fill: Option<LayerFill>,
+    /// レイヤーマスク
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mask: Option<LayerMask>,
+}
+
+/// レイヤーマスク（グレースケールの画像で、合成時にレイヤーの不透明度に掛ける）
+///
+/// マスクの画像は `texture_id` の別のテクスチャとして持ち、描画APIにこのIDを渡すと
+/// 通常のブラシでマスクを編集できる（白で表示、黒で隠す）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerMask {
+    pub texture_id: String,
+    /// 無効にしたマスクは合成に使わない（画像は残る）
+    pub enabled: bool,
+}
+
+impl Layer {
+    /// 画素を持つテクスチャのID（レイヤー自身、マスクがあればその後にマスク）
+    ///
+    /// .kine にはフレームごとにこの順でテクスチャを格納する。塗りつぶしレイヤーの分は空の画像になる。
+    pub fn texture_ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.id.as_str()).chain(self.mask.as_ref().map(|mask| mask.texture_id.as_str()))
+    }
+}
+
+impl Frame {
+    /// フレームのレイヤーとマスクのテクスチャID（.kine の格納順）
+    pub fn texture_ids(&self) -> impl Iterator<Item = &str> {
+        self.layers.iter().flat_map(Layer::texture_ids)
+    }
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -33,6 +98,30 @@ pub struct Project {
     pub height: u32,
     pub frame_rate: f32,
     pub frames: Vec<Frame>,
+    /// 操作ログ（プロジェクトと共に保存され、読み込み時に再生される）
+    #[serde(default)]
+    pub history: OperationLog,
+    /// 描画過程のタイムラプス（記録していない場合は None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timelapse: Option<TimelapseBuffer>,
+    /// ガイド・グリッド・スナップの設定
+    #[serde(default)]
+    pub guides: GuideSettings,
+    /// 書き出しの設定
+    #[serde(default)]
+    pub export: ExportSettings,
+    /// 描画品質（マルチサンプリング）の設定
+    #[serde(default)]
+    pub render: CanvasRenderSettings,
+    /// フレームに付けた名前付きのマーカー
+    #[serde(default)]
+    pub markers: FrameMarkers,
+    /// シーン（カット）の構成（空なら全フレームで1つのシーン）
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub scenes: Vec<Scene>,
+    /// 書き出し・再生時の仮想カメラ
+    #[serde(default)]
+    pub camera: CameraTrack,
 }
 
 impl Project {
@@ -50,6 +139,14 @@ impl Project {
             height,
             frame_rate,
             frames: vec![initial_frame], // 初期フレームを含める
+            history: OperationLog::new(),
+            timelapse: None,
+            guides: GuideSettings::default(),
+            export: ExportSettings::default(),
+            render: CanvasRenderSettings::default(),
+            markers: FrameMarkers::new(),
+            scenes: Vec::new(),
+            camera: CameraTrack::default(),
         }
     }
 }
\ No newline at end of file
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use std::time::Duration;
+
+/// 再生範囲の端に着いたときの動作
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoopMode {
+    /// 範囲の最初に戻って繰り返す
+    #[default]
+    Loop,
+    /// 端で折り返して往復する（往復で1回）
+    PingPong,
+    /// 範囲の最後で止まる
+    Once,
+}
+
+/// 再生範囲と繰り返しの設定（フレームは 0 始まりの番号）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PlaybackOptions {
+    /// 範囲の最初のフレーム（含む）
+    pub in_frame: usize,
+    /// 範囲の最後のフレーム（含む。None ならタイムラインの最後）
+    pub out_frame: Option<usize>,
+    pub mode: LoopMode,
+    /// 範囲を再生する回数（None なら止めるまで繰り返す。`Once` では使わない）
+    pub loop_count: Option<u32>,
+}
+
+impl PlaybackOptions {
+    pub fn validate(&self, frame_count: usize) -> Result<(), String> {
+        if let Some(out_frame) = self.out_frame {
+            if out_frame < self.in_frame {
+                return Err(format!("再生範囲の終わり({})が始まり({})より前です", out_frame, self.in_frame));
+            }
+            if out_frame >= frame_count {
+                return Err(format!("再生範囲の終わりがフレーム数({})を超えています: {}", frame_count, out_frame));
+            }
+        }
+        if self.in_frame >= frame_count.max(1) {
+            return Err(format!("再生範囲の始まりがフレーム数({})を超えています: {}", frame_count, self.in_frame));
+        }
+        if self.loop_count == Some(0) {
+            return Err("繰り返す回数は 1 以上で指定してください".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// アニメーションの再生位置（タイマーは持たず、呼び出し側が表示時間ごとに `advance` する）
+#[derive(Debug, Clone, Default)]
+pub struct Player {
+    /// 各フレームの表示時間（秒）
+    durations: Vec<f32>,
+    options: PlaybackOptions,
+    current: usize,
+    /// 往復再生で逆向きに進んでいるか
+    reverse: bool,
+    /// 再生を始めてから範囲を再生し終えた回数
+    loops: u32,
+}
+
+impl Player {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// タイムラインのフレーム構成を設定（範囲の設定がはみ出すなら既定に戻す）
+    pub fn set_frames(&mut self, durations: Vec<f32>) {
+        self.durations = durations;
+        if self.options.validate(self.frame_count()).is_err() {
+            self.options = PlaybackOptions::default();
+        }
+        self.current = self.current.min(self.frame_count().saturating_sub(1));
+    }
+
+    pub fn set_options(&mut self, options: PlaybackOptions) -> Result<(), String> {
+        options.validate(self.frame_count())?;
+        self.options = options;
+        Ok(())
+    }
+
+    pub fn options(&self) -> &PlaybackOptions {
+        &self.options
+    }
+
+    pub fn frame_count(&self) -> usize {
+        self.durations.len()
+    }
+
+    pub fn current(&self) -> usize {
+        self.current
+    }
+
+    /// 再生範囲（最初と最後のフレーム。フレームがなければ (0, 0)）
+    pub fn range(&self) -> (usize, usize) {
+        let last = self.frame_count().saturating_sub(1);
+        (self.options.in_frame.min(last), self.options.out_frame.unwrap_or(last).min(last))
+    }
+
+    /// フレームの表示時間（0 以下や不正な値は 1ms とみなす）
+    pub fn frame_duration(&self, frame: usize) -> Duration {
+        let seconds = self.durations.get(frame).copied().unwrap_or(0.0);
+        Duration::from_secs_f32(if seconds.is_finite() { seconds.max(0.001) } else { 0.001 })
+    }
+
+    /// 再生を始める（範囲の外にいれば範囲の最初から）
+    pub fn start(&mut self) {
+        let (first, last) = self.range();
+        if !(first..=last).contains(&self.current) {
+            self.current = first;
+        }
+        self.reverse = false;
+        self.loops = 0;
+    }
+
+    /// 再生中に次のフレームへ進める（再生し終えたら None で、位置は最後のフレームのまま）
+    pub fn advance(&mut self) -> Option<usize> {
+        let (first, last) = self.range();
+        if self.frame_count() == 0 {
+            return None;
+        }
+        let finish_loop = |loops: &mut u32, loop_count: Option<u32>| {
+            *loops += 1;
+            loop_count.is_none_or(|count| *loops < count)
+        };
+
+        let next = match self.options.mode {
+            LoopMode::Once if self.current >= last => return None,
+            LoopMode::Once => self.current + 1,
+            LoopMode::Loop | LoopMode::PingPong if first == last => {
+                if !finish_loop(&mut self.loops, self.options.loop_count) {
+                    return None;
+                }
+                first
+            }
+            LoopMode::Loop if self.current >= last => {
+                if !finish_loop(&mut self.loops, self.options.loop_count) {
+                    return None;
+                }
+                first
+            }
+            LoopMode::Loop => self.current + 1,
+            LoopMode::PingPong if self.reverse && self.current <= first => {
+                if !finish_loop(&mut self.loops, self.options.loop_count) {
+                    return None;
+                }
+                self.reverse = false;
+                first + 1
+            }
+            LoopMode::PingPong if self.reverse => self.current - 1,
+            LoopMode::PingPong if self.current >= last => {
+                self.reverse = true;
+                last - 1
+            }
+            LoopMode::PingPong => self.current + 1,
+        };
+        self.current = next;
+        Some(next)
+    }
+
+    /// 1フレーム進める（範囲の最後からは最初に戻る）
+    pub fn step_forward(&mut self) -> usize {
+        let (first, last) = self.range();
+        self.current = if self.current >= last || self.current < first { first } else { self.current + 1 };
+        self.current
+    }
+
+    /// 1フレーム戻す（範囲の最初からは最後に移る）
+    pub fn step_backward(&mut self) -> usize {
+        let (first, last) = self.range();
+        self.current = if self.current <= first || self.current > last { last } else { self.current - 1 };
+        self.current
+    }
+
+    /// 指定したフレームに移動（範囲の外にも移動できる）
+    pub fn goto(&mut self, frame: usize) -> Result<usize, String> {
+        if frame >= self.frame_count() {
+            return Err(format!("フレームが範囲外です: {} (フレーム数: {})", frame, self.frame_count()));
+        }
+        self.current = frame;
+        Ok(frame)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn player(frames: usize, options: PlaybackOptions) -> Player {
+        let mut player = Player::new();
+        player.set_frames(vec![1.0 / 12.0; frames]);
+        player.set_options(options).unwrap();
+        player.start();
+        player
+    }
+
+    fn play(player: &mut Player, limit: usize) -> Vec<usize> {
+        std::iter::from_fn(|| player.advance()).take(limit).collect()
+    }
+
+    #[test]
+    fn test_loop_within_range() {
+        let options = PlaybackOptions { in_frame: 2, out_frame: Some(4), loop_count: Some(2), ..Default::default() };
+        let mut player = player(10, options);
+        assert_eq!(player.current(), 2);
+        assert_eq!(play(&mut player, 20), vec![3, 4, 2, 3, 4]);
+        assert_eq!(player.current(), 4);
+
+        let mut endless = self::player(3, PlaybackOptions::default());
+        assert_eq!(play(&mut endless, 7), vec![1, 2, 0, 1, 2, 0, 1]);
+    }
+
+    #[test]
+    fn test_ping_pong_and_once() {
+        let options = PlaybackOptions { mode: LoopMode::PingPong, loop_count: Some(2), ..Default::default() };
+        let mut player = player(3, options);
+        assert_eq!(play(&mut player, 20), vec![1, 2, 1, 0, 1, 2, 1, 0]);
+
+        let mut once = self::player(3, PlaybackOptions { mode: LoopMode::Once, ..Default::default() });
+        assert_eq!(play(&mut once, 20), vec![1, 2]);
+
+        // 1フレームの範囲は回数だけ同じフレームを出す
+        let single = PlaybackOptions { in_frame: 1, out_frame: Some(1), mode: LoopMode::PingPong, loop_count: Some(3) };
+        assert_eq!(play(&mut self::player(3, single), 20), vec![1, 1]);
+    }
+
+    #[test]
+    fn test_step_and_goto() {
+        let options = PlaybackOptions { in_frame: 1, out_frame: Some(3), ..Default::default() };
+        let mut player = player(5, options);
+        assert_eq!(player.step_backward(), 3);
+        assert_eq!(player.step_forward(), 1);
+        assert_eq!(player.step_forward(), 2);
+        assert_eq!(player.goto(4), Ok(4));
+        assert_eq!(player.step_forward(), 1);
+        assert!(player.goto(5).is_err());
+
+        assert!(player.set_options(PlaybackOptions { out_frame: Some(5), ..Default::default() }).is_err());
+        assert!(player.set_options(PlaybackOptions { in_frame: 3, out_frame: Some(2), ..Default::default() }).is_err());
+        assert!(player.set_options(PlaybackOptions { loop_count: Some(0), ..Default::default() }).is_err());
+
+        // フレームが減ったら範囲と位置を詰める
+        player.set_options(PlaybackOptions { out_frame: Some(4), ..Default::default() }).unwrap();
+        player.set_frames(vec![0.1; 2]);
+        assert_eq!(player.options(), &PlaybackOptions::default());
+        assert_eq!((player.current(), player.range()), (1, (0, 1)));
+    }
+}
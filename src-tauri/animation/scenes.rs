@@ -0,0 +1,443 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use super::{Frame, Project};
+
+/// シーン名の最大文字数
+pub const MAX_SCENE_NAME_CHARS: usize = 64;
+
+/// シーン操作のエラー型
+#[derive(Debug, Clone, PartialEq)]
+pub enum SceneError {
+    InvalidScene(String),
+    SceneNotFound(String),
+    FrameNotFound(String),
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneError::InvalidScene(msg) => write!(f, "シーンの設定が不正です: {}", msg),
+            SceneError::SceneNotFound(id) => write!(f, "シーンが見つかりません: {}", id),
+            SceneError::FrameNotFound(id) => write!(f, "シーンのフレームが見つかりません: {}", id),
+        }
+    }
+}
+
+impl Error for SceneError {}
+
+/// プロジェクト内のシーン（カット）
+///
+/// フレームの実体は `Project::frames` にあり、シーンはそのIDを再生順に持つ。
+/// `Project::frames` もシーン順に並べておくため、シーンを意識しない処理からは連結したフレーム列に見える。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Scene {
+    pub id: String,
+    pub name: String,
+    pub frame_ids: Vec<String>,
+    /// キャンバスの大きさの上書き（省略時はプロジェクトの大きさ）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    /// シーンの長さ（秒）の上書き（省略時はフレームの表示時間の合計）
+    ///
+    /// フレームの合計より短ければ途中で切り、長ければ最後のフレームを止めて表示する。
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f32>,
+}
+
+/// シーンの設定の変更（省略した項目はそのまま。大きさ・長さは `clear_*` で上書きを解除する）
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct SceneUpdate {
+    pub name: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub duration: Option<f32>,
+    pub clear_size: bool,
+    pub clear_duration: bool,
+}
+
+/// 書き出すフレームの範囲
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum SceneExportMode {
+    /// `Project::frames` をそのまま1枚ずつ書き出す
+    #[default]
+    Frames,
+    /// 1つのシーンを、その大きさと長さで書き出す
+    Scene { scene_id: String },
+    /// すべてのシーンをシーン順に連結し、それぞれの大きさと長さで書き出す
+    Concatenate,
+}
+
+/// 書き出す1枚（`frame_index` は `Project::frames` の位置）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ExportFrame {
+    pub frame_index: usize,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 複製したシーン（複製元のレイヤーIDと新しいレイヤーIDの対応を含む）
+#[derive(Debug, Clone)]
+pub struct DuplicatedScene {
+    pub scene: Scene,
+    pub layer_copies: Vec<(String, String)>,
+}
+
+impl Project {
+    /// シーンの一覧（シーンを作っていないプロジェクトは全フレームを1つのシーンとみなす）
+    pub fn scene_list(&self) -> Vec<Scene> {
+        if self.scenes.is_empty() {
+            vec![self.implicit_scene()]
+        } else {
+            self.scenes.clone()
+        }
+    }
+
+    pub fn scene(&self, scene_id: &str) -> Result<&Scene, SceneError> {
+        self.scenes.iter().find(|scene| scene.id == scene_id)
+            .ok_or_else(|| SceneError::SceneNotFound(scene_id.to_string()))
+    }
+
+    /// シーンのキャンバスの大きさ
+    pub fn scene_size(&self, scene: &Scene) -> (u32, u32) {
+        (scene.width.unwrap_or(self.width), scene.height.unwrap_or(self.height))
+    }
+
+    /// シーンの長さ（秒）
+    pub fn scene_duration(&self, scene: &Scene) -> f32 {
+        scene.duration.unwrap_or_else(|| {
+            scene.frame_ids.iter()
+                .filter_map(|id| self.frames.iter().find(|frame| &frame.id == id))
+                .map(|frame| frame.duration)
+                .sum()
+        })
+    }
+
+    /// 空のフレームを1枚持つシーンを末尾に追加する
+    ///
+    /// 最初のシーンを作るときは、既存のフレームを1つ目のシーンにまとめる。
+    pub fn create_scene(&mut self, name: &str, width: Option<u32>, height: Option<u32>) -> Result<Scene, SceneError> {
+        let name = validate_name(name)?;
+        validate_size(width, height)?;
+        self.materialize_scenes();
+
+        let frame = Frame {
+            id: unique_id("frame", |id| self.frames.iter().any(|frame| frame.id == id)),
+            layers: Vec::new(),
+            duration: 1.0 / self.frame_rate,
+        };
+        let scene = Scene {
+            id: unique_id("scene", |id| self.scenes.iter().any(|scene| scene.id == id)),
+            name,
+            frame_ids: vec![frame.id.clone()],
+            width,
+            height,
+            duration: None,
+        };
+        self.frames.push(frame);
+        self.scenes.push(scene.clone());
+        Ok(scene)
+    }
+
+    pub fn update_scene(&mut self, scene_id: &str, update: SceneUpdate) -> Result<Scene, SceneError> {
+        let name = update.name.as_deref().map(validate_name).transpose()?;
+        self.materialize_scenes();
+        validate_size(update.width, update.height)?;
+        if let Some(duration) = update.duration {
+            if !(duration.is_finite() && duration > 0.0) {
+                return Err(SceneError::InvalidScene(format!("シーンの長さは正の値である必要があります: {}", duration)));
+            }
+        }
+        let scene = self.scenes.iter_mut().find(|scene| scene.id == scene_id)
+            .ok_or_else(|| SceneError::SceneNotFound(scene_id.to_string()))?;
+        if let Some(name) = name {
+            scene.name = name;
+        }
+        if update.clear_size {
+            scene.width = None;
+            scene.height = None;
+        }
+        scene.width = update.width.or(scene.width);
+        scene.height = update.height.or(scene.height);
+        if update.clear_duration {
+            scene.duration = None;
+        }
+        scene.duration = update.duration.or(scene.duration);
+        Ok(scene.clone())
+    }
+
+    /// シーンを並べ替える（`scene_ids` はすべてのシーンIDを新しい順に並べたもの）
+    pub fn reorder_scenes(&mut self, scene_ids: &[String]) -> Result<(), SceneError> {
+        let unique: HashSet<&String> = scene_ids.iter().collect();
+        if scene_ids.len() != self.scenes.len() || unique.len() != scene_ids.len() {
+            return Err(SceneError::InvalidScene("並べ替えにはすべてのシーンを1回ずつ指定してください".to_string()));
+        }
+        let mut reordered = Vec::with_capacity(self.scenes.len());
+        for scene_id in scene_ids {
+            reordered.push(self.scene(scene_id)?.clone());
+        }
+        self.scenes = reordered;
+        self.sort_frames_by_scene();
+        Ok(())
+    }
+
+    /// シーンを複製して直後に置く（フレームとレイヤーは新しいIDで複製する）
+    ///
+    /// レイヤー画像の複製は呼び出し側が `layer_copies` に従って行う。
+    pub fn duplicate_scene(&mut self, scene_id: &str) -> Result<DuplicatedScene, SceneError> {
+        self.materialize_scenes();
+        let position = self.scenes.iter().position(|scene| scene.id == scene_id)
+            .ok_or_else(|| SceneError::SceneNotFound(scene_id.to_string()))?;
+        let source = self.scenes[position].clone();
+
+        let mut taken_ids: HashSet<String> = self.frames.iter()
+            .flat_map(|frame| std::iter::once(frame.id.clone()).chain(frame.layers.iter().map(|layer| layer.id.clone())))
+            .collect();
+        let mut scene = Scene {
+            id: unique_id("scene", |id| self.scenes.iter().any(|scene| scene.id == id)),
+            name: copy_name(&source.name),
+            frame_ids: Vec::with_capacity(source.frame_ids.len()),
+            ..source.clone()
+        };
+        let mut layer_copies = Vec::new();
+        for frame_id in &source.frame_ids {
+            let mut frame = self.frames.iter().find(|frame| &frame.id == frame_id)
+                .ok_or_else(|| SceneError::FrameNotFound(frame_id.clone()))?
+                .clone();
+            frame.id = take_unique_id("frame", &mut taken_ids);
+            for layer in &mut frame.layers {
+                let copy_id = take_unique_id("layer", &mut taken_ids);
+                layer_copies.push((std::mem::replace(&mut layer.id, copy_id), layer.id.clone()));
+            }
+            scene.frame_ids.push(frame.id.clone());
+            self.frames.push(frame);
+        }
+        self.scenes.insert(position + 1, scene.clone());
+        self.sort_frames_by_scene();
+        Ok(DuplicatedScene { scene, layer_copies })
+    }
+
+    /// シーンの検証（各フレームはちょうど1つのシーンに属する）
+    pub fn validate_scenes(&self) -> Result<(), SceneError> {
+        if self.scenes.is_empty() {
+            return Ok(());
+        }
+        let mut seen = HashSet::new();
+        for scene in &self.scenes {
+            validate_name(&scene.name)?;
+            validate_size(scene.width, scene.height)?;
+            if scene.frame_ids.is_empty() {
+                return Err(SceneError::InvalidScene(format!("シーンにフレームがありません: {}", scene.id)));
+            }
+            for frame_id in &scene.frame_ids {
+                if !self.frames.iter().any(|frame| &frame.id == frame_id) {
+                    return Err(SceneError::FrameNotFound(frame_id.clone()));
+                }
+                if !seen.insert(frame_id) {
+                    return Err(SceneError::InvalidScene(format!("フレームが複数のシーンに含まれています: {}", frame_id)));
+                }
+            }
+        }
+        if seen.len() != self.frames.len() {
+            return Err(SceneError::InvalidScene("どのシーンにも含まれないフレームがあります".to_string()));
+        }
+        Ok(())
+    }
+
+    /// 書き出す順にフレームと大きさを並べる
+    pub fn export_plan(&self, mode: &SceneExportMode) -> Result<Vec<ExportFrame>, SceneError> {
+        let scenes = match mode {
+            SceneExportMode::Frames => {
+                return Ok((0..self.frames.len())
+                    .map(|frame_index| ExportFrame { frame_index, width: self.width, height: self.height })
+                    .collect());
+            }
+            SceneExportMode::Scene { scene_id } => match self.scenes.is_empty() {
+                // シーンを作っていないプロジェクトの唯一のシーン
+                true if *scene_id == self.implicit_scene().id => vec![self.implicit_scene()],
+                _ => vec![self.scene(scene_id)?.clone()],
+            },
+            SceneExportMode::Concatenate => self.scene_list(),
+        };
+        self.validate_scenes()?;
+
+        let mut plan = Vec::new();
+        for scene in &scenes {
+            let (width, height) = self.scene_size(scene);
+            let indices = scene.frame_ids.iter()
+                .map(|id| self.frames.iter().position(|frame| &frame.id == id)
+                    .ok_or_else(|| SceneError::FrameNotFound(id.clone())))
+                .collect::<Result<Vec<_>, _>>()?;
+            let indices = match scene.duration {
+                Some(duration) => self.retime(&indices, duration),
+                None => indices,
+            };
+            plan.extend(indices.into_iter().map(|frame_index| ExportFrame { frame_index, width, height }));
+        }
+        Ok(plan)
+    }
+
+    /// 長さを上書きしたシーンのフレーム列（フレームレートで数えた枚数に切り詰め・延長する）
+    fn retime(&self, indices: &[usize], duration: f32) -> Vec<usize> {
+        let total = ((duration * self.frame_rate).round() as usize).max(1);
+        let mut retimed = Vec::with_capacity(total);
+        let mut elapsed = 0.0;
+        for &index in indices {
+            if retimed.len() >= total {
+                break;
+            }
+            elapsed += self.frames[index].duration;
+            // このフレームの表示が終わる時刻までの枚数だけ並べる（最低1枚）
+            let until = ((elapsed * self.frame_rate).round() as usize).clamp(retimed.len() + 1, total);
+            retimed.resize(until, index);
+        }
+        if let Some(&last) = retimed.last() {
+            retimed.resize(total, last);
+        }
+        retimed
+    }
+
+    fn implicit_scene(&self) -> Scene {
+        Scene {
+            id: "scene_main".to_string(),
+            name: "シーン1".to_string(),
+            frame_ids: self.frames.iter().map(|frame| frame.id.clone()).collect(),
+            width: None,
+            height: None,
+            duration: None,
+        }
+    }
+
+    /// シーンを作っていなければ、全フレームを1つ目のシーンにする
+    fn materialize_scenes(&mut self) {
+        if self.scenes.is_empty() {
+            self.scenes.push(self.implicit_scene());
+        }
+    }
+
+    fn sort_frames_by_scene(&mut self) {
+        let order: Vec<&String> = self.scenes.iter().flat_map(|scene| &scene.frame_ids).collect();
+        self.frames.sort_by_key(|frame| order.iter().position(|id| **id == frame.id).unwrap_or(usize::MAX));
+    }
+}
+
+fn validate_name(name: &str) -> Result<String, SceneError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(SceneError::InvalidScene("名前が空です".to_string()));
+    }
+    if name.chars().count() > MAX_SCENE_NAME_CHARS {
+        return Err(SceneError::InvalidScene(format!("名前は {} 文字以内で指定してください", MAX_SCENE_NAME_CHARS)));
+    }
+    Ok(name.to_string())
+}
+
+fn validate_size(width: Option<u32>, height: Option<u32>) -> Result<(), SceneError> {
+    if width == Some(0) || height == Some(0) {
+        return Err(SceneError::InvalidScene("キャンバスの大きさは1以上である必要があります".to_string()));
+    }
+    Ok(())
+}
+
+fn copy_name(name: &str) -> String {
+    let copy = format!("{} のコピー", name);
+    match copy.chars().count() > MAX_SCENE_NAME_CHARS {
+        true => name.to_string(),
+        false => copy,
+    }
+}
+
+fn unique_id(prefix: &str, taken: impl Fn(&str) -> bool) -> String {
+    let timestamp = chrono::Utc::now().timestamp_millis();
+    (0..)
+        .map(|n| format!("{}_{}_{}", prefix, timestamp, n))
+        .find(|id| !taken(id))
+        .expect("未使用のIDが見つかる")
+}
+
+fn take_unique_id(prefix: &str, taken: &mut HashSet<String>) -> String {
+    let id = unique_id(prefix, |id| taken.contains(id));
+    taken.insert(id.clone());
+    id
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{BlendMode, Layer};
+
+    fn project() -> Project {
+        let mut project = Project::new("test".to_string(), 64, 48, 10.0);
+        project.frames[0].layers.push(Layer {
+            id: "layer_a".to_string(),
+            name: "線画".to_string(),
+            visible: true,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            locked: false,
+            effects: Vec::new(),
+            fill: None,
+            mask: None,
+        });
+        project
+    }
+
+    #[test]
+    fn test_create_duplicate_and_reorder_scenes() {
+        let mut project = project();
+        let first_frame = project.frames[0].id.clone();
+        assert_eq!(project.scene_list()[0].frame_ids, vec![first_frame.clone()]);
+
+        let second = project.create_scene("カット2", Some(32), None).unwrap();
+        assert_eq!(project.scenes.len(), 2);
+        assert_eq!(project.scenes[0].frame_ids, vec![first_frame.clone()]);
+        assert_eq!(project.scene_size(&second), (32, 48));
+
+        let main_id = project.scenes[0].id.clone();
+        let copy = project.duplicate_scene(&main_id).unwrap();
+        assert_eq!(copy.scene.name, "シーン1 のコピー");
+        assert_eq!(copy.layer_copies.len(), 1);
+        assert_eq!(copy.layer_copies[0].0, "layer_a");
+        assert_ne!(copy.layer_copies[0].1, "layer_a");
+        assert!(project.validate_scenes().is_ok());
+        // 複製は元のシーンの直後に入り、フレームもシーン順に並ぶ
+        let order: Vec<&str> = project.scenes.iter().map(|scene| scene.id.as_str()).collect();
+        assert_eq!(order, vec![main_id.as_str(), copy.scene.id.as_str(), second.id.as_str()]);
+        assert_eq!(project.frames[1].id, copy.scene.frame_ids[0]);
+        assert_eq!(project.frames[1].layers[0].id, copy.layer_copies[0].1);
+
+        let reversed: Vec<String> = project.scenes.iter().rev().map(|scene| scene.id.clone()).collect();
+        project.reorder_scenes(&reversed).unwrap();
+        assert_eq!(project.frames[0].id, second.frame_ids[0]);
+        assert_eq!(project.frames[2].id, first_frame);
+        assert!(project.reorder_scenes(&reversed[..2]).is_err());
+    }
+
+    #[test]
+    fn test_concatenated_export_uses_scene_size_and_duration() {
+        let mut project = project();
+        let second = project.create_scene("カット2", Some(32), Some(16)).unwrap();
+        // 0.1秒のフレームが1枚のシーンを 0.3 秒に延ばす
+        project.update_scene(&second.id, SceneUpdate { duration: Some(0.3), ..Default::default() }).unwrap();
+
+        let plan = project.export_plan(&SceneExportMode::Concatenate).unwrap();
+        assert_eq!(plan.len(), 4);
+        assert_eq!(plan[0], ExportFrame { frame_index: 0, width: 64, height: 48 });
+        assert!(plan[1..].iter().all(|frame| *frame == ExportFrame { frame_index: 1, width: 32, height: 16 }));
+
+        let scene_only = project.export_plan(&SceneExportMode::Scene { scene_id: second.id.clone() }).unwrap();
+        assert_eq!(scene_only.len(), 3);
+        assert_eq!(project.export_plan(&SceneExportMode::Frames).unwrap().len(), 2);
+
+        // 短くした場合は途中で切る
+        project.update_scene(&second.id, SceneUpdate { duration: Some(0.05), ..Default::default() }).unwrap();
+        assert_eq!(project.export_plan(&SceneExportMode::Concatenate).unwrap().len(), 2);
+        let cleared = project.update_scene(&second.id, SceneUpdate { clear_size: true, ..Default::default() }).unwrap();
+        assert_eq!(project.scene_size(&cleared), (64, 48));
+    }
+}
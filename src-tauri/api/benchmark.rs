@@ -0,0 +1,237 @@
+use crate::benchmark::{
+    synthetic_image, synthetic_layers, synthetic_stroke, BenchmarkCase, BenchmarkKind, BenchmarkOptions,
+    BenchmarkResult, TimingStats, BENCHMARK_LAYER_PREFIX,
+};
+use crate::drawing_engine::DrawingEngine;
+use crate::formats::flatten_layers;
+use super::drawing::DrawingState;
+use log::{info, debug, warn};
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tauri::ipc::Response;
+use tauri::State;
+
+/// IPC 往復計測で返せる最大のバイト数
+const MAX_IPC_PAYLOAD: usize = 64 * 1024 * 1024;
+
+/// フロントエンドで計測した IPC 往復時間
+#[derive(Debug, Deserialize)]
+pub struct IpcBenchmarkSamples {
+    pub payload_bytes: usize,
+    pub samples_ms: Vec<f64>,
+}
+
+fn benchmark_layer_id(name: &str) -> String {
+    format!("{}{}", BENCHMARK_LAYER_PREFIX, name)
+}
+
+/// デバイスの上限を超える解像度を除外
+fn supported_resolutions(engine: &DrawingEngine, options: &BenchmarkOptions) -> Vec<u32> {
+    let max_dimension = engine.canvas_limits().max_dimension();
+    options.resolutions.iter().copied()
+        .filter(|&size| {
+            let supported = size <= max_dimension;
+            if !supported {
+                warn!("[Benchmark API] 上限 {} を超える解像度をスキップ: {}", max_dimension, size);
+            }
+            supported
+        })
+        .collect()
+}
+
+/// ストローク描画（1回ごとにレイヤーを消去し、GPU の完了までを計測する）
+fn bench_stroke_throughput(
+    engine: &mut DrawingEngine,
+    options: &BenchmarkOptions,
+    size: u32,
+) -> Result<BenchmarkCase, String> {
+    let layer_id = benchmark_layer_id("stroke");
+    engine.create_layer_texture(&layer_id, size, size).map_err(|e| e.to_string())?;
+    let stroke = synthetic_stroke(options.stroke_points, size);
+
+    let mut durations = Vec::with_capacity(options.iterations as usize);
+    // 先頭の1回はパイプライン・バッファの準備を含むため計測しない
+    for iteration in 0..=options.iterations {
+        engine.clear_layer_texture(&layer_id, None).map_err(|e| e.to_string())?;
+        engine.wait_idle();
+        let started = Instant::now();
+        engine.draw_stroke_to_layer(&layer_id, &stroke).map_err(|e| e.to_string())?;
+        engine.wait_idle();
+        if iteration > 0 {
+            durations.push(started.elapsed());
+        }
+    }
+
+    let timing = TimingStats::from_durations(&durations);
+    Ok(BenchmarkCase {
+        label: format!("{} 点のストローク", options.stroke_points),
+        width: size,
+        height: size,
+        throughput: timing.per_second(options.stroke_points as f64),
+        throughput_unit: "points/s",
+        timing,
+    })
+}
+
+/// テクスチャの読み出し（GPU からの転送とパディング除去まで）
+async fn bench_readback(
+    engine: &mut DrawingEngine,
+    options: &BenchmarkOptions,
+    size: u32,
+) -> Result<BenchmarkCase, String> {
+    let layer_id = benchmark_layer_id(&format!("readback_{}", size));
+    engine.create_layer_texture(&layer_id, size, size).map_err(|e| e.to_string())?;
+    engine.write_layer_image(&layer_id, 0, 0, &synthetic_image(size, size, 0)).map_err(|e| e.to_string())?;
+    engine.wait_idle();
+
+    let mut durations = Vec::with_capacity(options.iterations as usize);
+    for iteration in 0..=options.iterations {
+        let started = Instant::now();
+        engine.get_layer_texture_data(&layer_id).await.map_err(|e| e.to_string())?;
+        if iteration > 0 {
+            durations.push(started.elapsed());
+        }
+    }
+    engine.remove_layer_texture(&layer_id);
+
+    let timing = TimingStats::from_durations(&durations);
+    let megabytes = size as f64 * size as f64 * 4.0 / 1_000_000.0;
+    Ok(BenchmarkCase {
+        label: format!("{}x{} の読み出し", size, size),
+        width: size,
+        height: size,
+        throughput: timing.per_second(megabytes),
+        throughput_unit: "MB/s",
+        timing,
+    })
+}
+
+/// レイヤー合成（書き出し・サムネイルと同じ CPU の合成処理）
+async fn bench_compositing(options: &BenchmarkOptions, size: u32) -> Result<BenchmarkCase, String> {
+    let iterations = options.iterations;
+    let layer_count = options.layer_count;
+    let durations = tokio::task::spawn_blocking(move || {
+        let layers = synthetic_layers(size, size, layer_count);
+        flatten_layers(&layers, size, size);
+        (0..iterations)
+            .map(|_| {
+                let started = Instant::now();
+                flatten_layers(&layers, size, size);
+                started.elapsed()
+            })
+            .collect::<Vec<Duration>>()
+    })
+    .await
+    .map_err(|e| format!("合成計測タスクエラー: {}", e))?;
+
+    let timing = TimingStats::from_durations(&durations);
+    let megapixels = size as f64 * size as f64 / 1_000_000.0;
+    Ok(BenchmarkCase {
+        label: format!("{}x{} の {} レイヤー合成", size, size, layer_count),
+        width: size,
+        height: size,
+        throughput: timing.per_second(megapixels),
+        throughput_unit: "MP/s",
+        timing,
+    })
+}
+
+/// 標準の負荷でベンチマークを実行し、計測結果を返す
+///
+/// 計測用の一時レイヤーは履歴にもレイヤー一覧にも追加せず、終了時に削除する。
+/// GPU を使う計測中は描画エンジンを占有するため、他の描画操作は計測後まで待たされる。
+/// IPC の往復はフロントエンドで `benchmark_ipc_echo` を呼んで計測し、
+/// `summarize_ipc_benchmark` で同じ形式の結果にまとめる。
+#[tauri::command]
+pub async fn run_benchmark(
+    kind: BenchmarkKind,
+    options: Option<BenchmarkOptions>,
+    state: State<'_, DrawingState>,
+) -> Result<BenchmarkResult, String> {
+    let options = options.unwrap_or_default();
+    options.validate().map_err(|e| e.to_string())?;
+    info!("[Benchmark API] ベンチマーク開始: {:?} ({} 回)", kind, options.iterations);
+
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    let adapter = engine.device_capabilities().map(|capabilities| capabilities.adapter_name);
+    let resolutions = supported_resolutions(engine, &options);
+    if resolutions.is_empty() {
+        return Err("計測できる解像度がありません".to_string());
+    }
+
+    let mut cases = Vec::new();
+    match kind {
+        BenchmarkKind::StrokeThroughput => {
+            let result = bench_stroke_throughput(engine, &options, resolutions[0]);
+            engine.remove_layer_texture(&benchmark_layer_id("stroke"));
+            cases.push(result?);
+        }
+        BenchmarkKind::Readback => {
+            for &size in &resolutions {
+                let result = bench_readback(engine, &options, size).await;
+                if result.is_err() {
+                    engine.remove_layer_texture(&benchmark_layer_id(&format!("readback_{}", size)));
+                }
+                cases.push(result?);
+            }
+        }
+        BenchmarkKind::Compositing => {
+            // CPU だけで完結するため描画エンジンは解放しておく
+            drop(engine_guard);
+            for &size in &resolutions {
+                cases.push(bench_compositing(&options, size).await?);
+            }
+        }
+        BenchmarkKind::IpcRoundTrip => {
+            return Err("IPC の往復はフロントエンドで計測し、summarize_ipc_benchmark で集計してください".to_string());
+        }
+    }
+
+    for case in &cases {
+        debug!("[Benchmark API] {}: 中央値 {:.3} ms / p95 {:.3} ms",
+               case.label, case.timing.median_ms, case.timing.p95_ms);
+    }
+    info!("[Benchmark API] ベンチマーク完了: {:?} ({} 件)", kind, cases.len());
+    Ok(BenchmarkResult { kind, adapter, iterations: options.iterations, cases })
+}
+
+/// IPC 往復計測用に指定サイズのバイト列を返す
+#[tauri::command]
+pub async fn benchmark_ipc_echo(payload_bytes: usize) -> Result<Response, String> {
+    if payload_bytes > MAX_IPC_PAYLOAD {
+        return Err(format!("ペイロードが大きすぎます: {} バイト（上限 {}）", payload_bytes, MAX_IPC_PAYLOAD));
+    }
+    Ok(Response::new(vec![0xA5; payload_bytes]))
+}
+
+/// フロントエンドで計測した IPC 往復時間を他のベンチマークと同じ形式にまとめる
+#[tauri::command]
+pub async fn summarize_ipc_benchmark(
+    cases: Vec<IpcBenchmarkSamples>,
+    state: State<'_, DrawingState>,
+) -> Result<BenchmarkResult, String> {
+    if cases.is_empty() {
+        return Err("計測結果がありません".to_string());
+    }
+    let adapter = state.engine.lock().await.as_ref()
+        .and_then(|engine| engine.device_capabilities())
+        .map(|capabilities| capabilities.adapter_name);
+    let iterations = cases.iter().map(|case| case.samples_ms.len()).max().unwrap_or(0) as u32;
+
+    let cases = cases.into_iter()
+        .map(|samples| {
+            let timing = TimingStats::from_millis(&samples.samples_ms);
+            let megabytes = samples.payload_bytes as f64 / 1_000_000.0;
+            BenchmarkCase {
+                label: format!("{} バイトの往復", samples.payload_bytes),
+                width: 0,
+                height: 0,
+                throughput: timing.per_second(megabytes).filter(|_| samples.payload_bytes > 0),
+                throughput_unit: "MB/s",
+                timing,
+            }
+        })
+        .collect();
+    Ok(BenchmarkResult { kind: BenchmarkKind::IpcRoundTrip, adapter, iterations, cases })
+}
@@ -0,0 +1,108 @@
+use super::drawing::DrawingState;
+use super::history::rebuild_engine_state;
+use futures::FutureExt;
+use log::{info, error};
+use serde::Serialize;
+use std::any::Any;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::atomic::Ordering;
+
+/// コマンド内で起きたパニック（フロントエンドには `kind: "panic"` のオブジェクトとして届く）
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandPanic {
+    pub kind: &'static str,
+    /// パニックしたコマンド名
+    pub command: &'static str,
+    pub message: String,
+    /// 次の描画コマンドの前に描画エンジンを再初期化し、操作履歴からレイヤーを作り直す
+    pub recovery_pending: bool,
+}
+
+/// GPUを使うコマンドのエラー
+///
+/// 通常のエラーはこれまで通り文字列として、パニックは `CommandPanic` として返す。
+#[derive(Debug, Clone, Serialize)]
+#[serde(untagged)]
+pub enum CommandError {
+    Failed(String),
+    Panicked(CommandPanic),
+}
+
+impl From<String> for CommandError {
+    fn from(message: String) -> Self {
+        CommandError::Failed(message)
+    }
+}
+
+impl std::fmt::Display for CommandError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            CommandError::Failed(message) => write!(f, "{}", message),
+            CommandError::Panicked(panic) => write!(f, "{} が異常終了しました: {}", panic.command, panic.message),
+        }
+    }
+}
+
+/// パニックの内容を文字列にする（`panic!` の引数が文字列以外なら固定の文言）
+fn panic_message(payload: &(dyn Any + Send)) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "不明なパニック".to_string()
+    }
+}
+
+/// GPUを使うコマンドの処理を囲み、パニックをエラーとして返す
+///
+/// パニックするとWebViewとのIPCが応答しないままになるため、ここで受け止めて `CommandError::Panicked` にする。
+/// パニックの途中で描画エンジンの状態が壊れている可能性があるため再初期化の印を付け、次にこの境界を通る
+/// コマンドの前に再初期化する（ロックはパニックの巻き戻しで解放される）。
+/// 境界を通ったことはアイドル時のメンテナンスを後回しにする活動として記録する。
+pub(crate) async fn gpu_boundary<T>(
+    state: &DrawingState,
+    command: &'static str,
+    task: impl Future<Output = Result<T, String>>,
+) -> Result<T, CommandError> {
+    state.touch_activity().await;
+    if state.engine_recovery.swap(false, Ordering::AcqRel) {
+        if let Err(e) = recover_engine(state).await {
+            state.engine_recovery.store(true, Ordering::Release);
+            return Err(CommandError::Failed(format!("描画エンジンの復旧に失敗しました: {}", e)));
+        }
+    }
+
+    match AssertUnwindSafe(task).catch_unwind().await {
+        Ok(result) => result.map_err(CommandError::Failed),
+        Err(payload) => {
+            let message = panic_message(payload.as_ref());
+            error!("[Boundary] {} でパニック: {}（次の描画コマンドで描画エンジンを再初期化します）", command, message);
+            state.engine_recovery.store(true, Ordering::Release);
+            Err(CommandError::Panicked(CommandPanic {
+                kind: "panic",
+                command,
+                message,
+                recovery_pending: true,
+            }))
+        }
+    }
+}
+
+/// 描画エンジンを再初期化し、操作履歴からレイヤーを作り直す（描画中のストロークは破棄する）
+async fn recover_engine(state: &DrawingState) -> Result<(), String> {
+    let history_guard = state.history.lock().await;
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let Some(engine) = engine_guard.as_mut() else {
+            // 初期化前にパニックした場合は、初期化時にすべて作られる
+            return Ok(());
+        };
+        engine.reinitialize().await.map_err(|e| e.to_string())?;
+    }
+    state.active_strokes.lock().await.clear();
+    rebuild_engine_state(state, &history_guard).await?;
+    info!("[Boundary] 描画エンジンを再初期化しました");
+    Ok(())
+}
@@ -0,0 +1,204 @@
+use crate::brush::{stamps::select_rasterizer, BrushLibrary, BrushPack, StampBrush};
+use crate::drawing_engine::StampRasterizer;
+use crate::history::{Operation, StrokePointRecord};
+use super::drawing::{layer_size, sanitize_input, DrawingState, StrokePoint};
+use super::paging::ensure_resident;
+use super::settings::SettingsState;
+use log::{info, debug, error};
+use serde::Serialize;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tauri::State;
+use tokio::sync::Mutex;
+
+/// ブラシライブラリの状態管理
+pub struct BrushState {
+    library: Mutex<BrushLibrary>,
+}
+
+impl BrushState {
+    pub fn new() -> Self {
+        Self {
+            library: Mutex::new(BrushLibrary::new()),
+        }
+    }
+}
+
+impl Default for BrushState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// ブラシパック情報
+#[derive(Serialize)]
+pub struct BrushPackInfo {
+    pub id: String,
+    pub name: String,
+    pub author: Option<String>,
+    pub brush_count: usize,
+}
+
+impl From<&BrushPack> for BrushPackInfo {
+    fn from(pack: &BrushPack) -> Self {
+        Self {
+            id: pack.id.clone(),
+            name: pack.name.clone(),
+            author: pack.author.clone(),
+            brush_count: pack.brushes.len(),
+        }
+    }
+}
+
+/// ブラシ一覧の要素（IDは `パックID/ブラシID`）
+#[derive(Serialize)]
+pub struct BrushEntry {
+    pub id: String,
+    pub brush: StampBrush,
+}
+
+/// ブラシストロークの描画結果
+#[derive(Serialize)]
+pub struct BrushStrokeResult {
+    pub stamps: usize,
+    pub rasterizer: StampRasterizer,
+}
+
+/// ブラシパック（JSON）をファイルからインストール
+#[tauri::command]
+pub async fn import_brush_pack(
+    path: String,
+    replace: Option<bool>,
+    brush_state: State<'_, BrushState>,
+) -> Result<BrushPackInfo, String> {
+    info!("[Brush API] ブラシパック読み込み: {}", path);
+
+    let json = tokio::fs::read_to_string(&path).await
+        .map_err(|e| format!("ブラシパックの読み込みに失敗しました: {}", e))?;
+    let pack = BrushPack::from_json(&json).map_err(|e| {
+        error!("[Brush API] {}", e);
+        e.to_string()
+    })?;
+    let info = BrushPackInfo::from(&pack);
+
+    let mut library = brush_state.library.lock().await;
+    library.install(pack, replace.unwrap_or(false)).map_err(|e| e.to_string())?;
+    Ok(info)
+}
+
+/// インストール済みのブラシパック一覧
+#[tauri::command]
+pub async fn list_brush_packs(
+    brush_state: State<'_, BrushState>,
+) -> Result<Vec<BrushPackInfo>, String> {
+    let library = brush_state.library.lock().await;
+    Ok(library.packs().iter().map(BrushPackInfo::from).collect())
+}
+
+/// ブラシパックを削除
+#[tauri::command]
+pub async fn remove_brush_pack(
+    pack_id: String,
+    brush_state: State<'_, BrushState>,
+) -> Result<(), String> {
+    let mut library = brush_state.library.lock().await;
+    library.remove(&pack_id).map(|_| ()).map_err(|e| e.to_string())
+}
+
+/// インストール済みのブラシパックを JSON ファイルに書き出す（`import_brush_pack` で読み込める形式）
+#[tauri::command]
+pub async fn export_brush_pack(
+    pack_id: String,
+    path: String,
+    brush_state: State<'_, BrushState>,
+) -> Result<(), String> {
+    let json = {
+        let library = brush_state.library.lock().await;
+        let pack = library.packs().iter()
+            .find(|pack| pack.id == pack_id)
+            .ok_or_else(|| format!("ブラシパックが見つかりません: {}", pack_id))?;
+        pack.to_json().map_err(|e| e.to_string())?
+    };
+    tokio::fs::write(&path, json).await
+        .map_err(|e| format!("ブラシパックの書き出しに失敗しました: {}", e))?;
+    info!("[Brush API] ブラシパックを書き出し: {} -> {}", pack_id, path);
+    Ok(())
+}
+
+/// 登録済みのスタンプブラシ一覧
+#[tauri::command]
+pub async fn list_brushes(
+    brush_state: State<'_, BrushState>,
+) -> Result<Vec<BrushEntry>, String> {
+    let library = brush_state.library.lock().await;
+    let brushes: Vec<BrushEntry> = library.brushes()
+        .map(|(id, brush)| BrushEntry { id, brush: brush.clone() })
+        .collect();
+    debug!("[Brush API] ブラシ一覧: {} 件", brushes.len());
+    Ok(brushes)
+}
+
+/// スタンプブラシでレイヤーにストロークを描画
+///
+/// スタンプ数が `COMPUTE_STAMP_THRESHOLD` 以上になるブラシ（スプレーなど）は
+/// 自動的にコンピュートシェーダーで描画する。散らす位置の乱数の種は履歴に記録し、再生でも同じ配置にする。
+#[tauri::command]
+pub async fn draw_brush_stroke(
+    layer_id: String,
+    brush_id: String,
+    points: Vec<StrokePoint>,
+    color: [f32; 4],
+    device_id: Option<String>,
+    state: State<'_, DrawingState>,
+    brush_state: State<'_, BrushState>,
+    settings: State<'_, SettingsState>,
+) -> Result<BrushStrokeResult, String> {
+    debug!("[Brush API] ブラシストローク描画: {} {} ({} 点)", layer_id, brush_id, points.len());
+
+    if points.is_empty() {
+        return Err("ストロークの点が空です".to_string());
+    }
+    let brush = {
+        let library = brush_state.library.lock().await;
+        library.get(&brush_id)
+            .ok_or_else(|| format!("ブラシが見つかりません: {}", brush_id))?
+            .stamp_settings()
+            .map_err(|e| e.to_string())?
+    };
+
+    let layer_size = layer_size(&state, &layer_id).await?;
+    // 非有限値や範囲外の点はスタンプの計算に渡す前に取り除く
+    let input = sanitize_input(points.iter().map(StrokePoint::to_input), color, brush.size, layer_size, None)?;
+    let color = input.color;
+    ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+
+    let adjusted = state.guides.lock().await
+        .apply_to_points(&input.points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>());
+    let curve = *settings.get().await.tablet.curve_for(device_id.as_deref());
+    let points: Vec<StrokePointRecord> = input.points.iter().zip(adjusted)
+        .map(|(p, (x, y))| StrokePointRecord { x, y, pressure: curve.apply(p.pressure), time_ms: p.time_ms })
+        .collect();
+
+    let seed = SystemTime::now().duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_nanos() as u64)
+        .unwrap_or_default();
+    let stamps = brush.stamps(&points, seed);
+    let rasterizer = select_rasterizer(stamps.len());
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        engine.draw_stamps_to_layer(&layer_id, &stamps, &brush.shape(color), rasterizer)
+            .map_err(|e| format!("ブラシストローク描画エラー: {}", e))?;
+    }
+
+    state.record_operation(Operation::DrawBrushStroke {
+        layer_id: layer_id.clone(),
+        points,
+        color,
+        brush,
+        seed,
+        brush_id: Some(brush_id),
+    }).await;
+
+    info!("[Brush API] ブラシストローク描画完了: {} ({} スタンプ, {:?})", layer_id, stamps.len(), rasterizer);
+    Ok(BrushStrokeResult { stamps: stamps.len(), rasterizer })
+}
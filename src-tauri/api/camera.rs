@@ -0,0 +1,81 @@
+use crate::animation::{render_camera_view, CameraKeyframe, CameraRect, Project};
+use crate::formats::flatten_layers;
+use crate::drawing_engine::overlay::render_view;
+use crate::drawing_engine::MAX_PIXEL_ZOOM;
+use super::drawing::{CanvasView, DrawingState};
+use super::formats::collect_preview_layers;
+use super::settings::SettingsState;
+use log::debug;
+use tauri::State;
+
+/// カメラのキーフレームを置き（同じフレームにあれば置き換え）、更新したプロジェクトを返す
+#[tauri::command]
+pub async fn set_camera_keyframe(mut project: Project, keyframe: CameraKeyframe) -> Result<Project, String> {
+    let frame = keyframe.frame;
+    project.camera.set_keyframe(keyframe).map_err(|e| e.to_string())?;
+    debug!("[Camera API] キーフレームを設定: フレーム {} ({} 個)", frame, project.camera.keyframes.len());
+    Ok(project)
+}
+
+/// カメラのキーフレームを削除し、更新したプロジェクトを返す
+#[tauri::command]
+pub async fn remove_camera_keyframe(mut project: Project, frame: usize) -> Result<Project, String> {
+    project.camera.remove_keyframe(frame).map_err(|e| e.to_string())?;
+    debug!("[Camera API] キーフレームを削除: フレーム {}", frame);
+    Ok(project)
+}
+
+/// フレームでカメラが写すキャンバス上の範囲（カメラが無効なら None）
+#[tauri::command]
+pub async fn get_camera_rect(project: Project, frame_index: usize) -> Result<Option<CameraRect>, String> {
+    project.camera.validate().map_err(|e| e.to_string())?;
+    Ok(project.camera.rect_at(frame_index, project.width, project.height))
+}
+
+/// 再生用に、カメラを通したフレームの表示画像を取得（カメラが無効ならキャンバス全体）
+///
+/// 出力はプロジェクトの大きさで、設定のオーバーレイとプロジェクトのセーフエリア・画面比のガイドを重ねる。
+#[tauri::command]
+pub async fn render_camera_frame(
+    project: Project,
+    frame_index: usize,
+    zoom: u32,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<CanvasView, String> {
+    if !(1..=MAX_PIXEL_ZOOM).contains(&zoom) {
+        return Err(format!("拡大率は 1〜{} で指定してください: {}", MAX_PIXEL_ZOOM, zoom));
+    }
+    project.camera.validate().map_err(|e| e.to_string())?;
+    project.guides.safe_area.validate().map_err(|e| e.to_string())?;
+    let frame = project.frames.get(frame_index)
+        .ok_or_else(|| format!("フレームが見つかりません: {}", frame_index))?;
+
+    let (width, height) = (project.width, project.height);
+    let canvas = if frame.layers.is_empty() {
+        image::RgbaImage::new(width, height)
+    } else {
+        let (raster_layers, ..) = collect_preview_layers(&state, Some(frame.layers.clone())).await?;
+        flatten_layers(&raster_layers, width, height)
+    };
+    let image = match project.camera.rect_at(frame_index, width, height) {
+        Some(rect) => {
+            debug!("[Camera API] カメラ表示: フレーム {} ({:.1}, {:.1}, {:.1}x{:.1})", frame_index, rect.x, rect.y, rect.width, rect.height);
+            render_camera_view(&canvas, rect, width, height)
+        }
+        None => canvas,
+    };
+    let overlay = settings.get().await.overlay;
+    let mut view = render_view(&image, zoom, &overlay);
+    let guides = &project.guides;
+    if guides.visible && guides.safe_area.is_active() {
+        let border = overlay.border_size();
+        guides.safe_area.draw(&mut view, (border, border, width * zoom, height * zoom));
+    }
+
+    Ok(CanvasView {
+        width: view.width(),
+        height: view.height(),
+        data: view.into_raw(),
+    })
+}
@@ -0,0 +1,85 @@
+use crate::animation::{CleanupOptions, CleanupReport, Project};
+use crate::drawing_engine::ContentCoverage;
+use super::drawing::{remove_layer, DrawingState};
+use super::paging::read_page_blocking;
+use super::selection::QUICK_MASK_TEXTURE_ID;
+use log::{info, debug};
+use serde::Serialize;
+use std::collections::HashSet;
+use tauri::State;
+
+/// 整理の結果
+#[derive(Serialize)]
+pub struct ProjectCleanup {
+    pub report: CleanupReport,
+    /// 整理後のプロジェクト（`remove` が false なら元のまま）
+    pub project: Project,
+}
+
+/// 完全に透明なテクスチャを求める（退避中のものは復帰させずに退避ファイルから調べる）
+async fn empty_textures(state: &DrawingState, texture_ids: &[String]) -> Result<HashSet<String>, String> {
+    let pager = state.pager.lock().await;
+    let (paged, resident): (Vec<String>, Vec<String>) = texture_ids.iter()
+        .cloned()
+        .partition(|id| pager.get(id).is_some());
+
+    let mut empty: HashSet<String> = {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        let coverages = engine.content_coverage(&resident).await
+            .map_err(|e| format!("集計エラー: {}", e))?;
+        resident.into_iter()
+            .zip(coverages)
+            .filter(|(_, coverage)| coverage.pixel_count == 0)
+            .map(|(id, _)| id)
+            .collect()
+    };
+    for id in paged {
+        let page = pager.get(&id).cloned().ok_or_else(|| format!("レイヤーが見つかりません: {}", id))?;
+        if ContentCoverage::from_image(&read_page_blocking(page).await?).pixel_count == 0 {
+            empty.insert(id);
+        }
+    }
+    Ok(empty)
+}
+
+/// 空のレイヤー・どのシーンにも含まれないフレーム・どこからも参照されていないテクスチャを調べ、
+/// `remove` が true ならそれらを取り除く
+///
+/// 取り除いたテクスチャの削除は履歴に記録される。フレームを取り除くとマーカーとカメラの
+/// キーフレームのフレーム番号を詰める。
+#[tauri::command]
+pub async fn cleanup_project(
+    mut project: Project,
+    options: Option<CleanupOptions>,
+    remove: bool,
+    state: State<'_, DrawingState>,
+) -> Result<ProjectCleanup, String> {
+    let options = options.unwrap_or_default();
+    debug!("[Cleanup API] プロジェクトの整理: {:?} (削除: {})", options, remove);
+
+    // クイックマスクは作業中のテクスチャなので対象外
+    let mut textures = state.layers.lock().await.clone();
+    textures.remove(QUICK_MASK_TEXTURE_ID);
+    let mut texture_ids: Vec<String> = textures.keys().cloned().collect();
+    texture_ids.sort();
+    let empty = empty_textures(&state, &texture_ids).await?;
+
+    // マーカーはバックエンドで管理しているものを使う
+    project.markers = state.markers.lock().await.clone();
+    let report = project.plan_cleanup(&options, &empty, &textures);
+    info!("[Cleanup API] 空のレイヤー {} / 未使用のフレーム {} / 孤立したテクスチャ {} ({} bytes)",
+          report.empty_layers.len(), report.unused_frames.len(), report.orphaned_textures.len(), report.freed_bytes);
+    if !remove || report.is_empty() {
+        return Ok(ProjectCleanup { report, project });
+    }
+
+    project.apply_cleanup(&report);
+    for texture_id in &report.removed_textures {
+        remove_layer(texture_id.clone(), state.clone()).await?;
+    }
+    *state.markers.lock().await = project.markers.clone();
+
+    info!("[Cleanup API] 整理完了: テクスチャ {} 枚を削除", report.removed_textures.len());
+    Ok(ProjectCleanup { report, project })
+}
@@ -0,0 +1,324 @@
+use crate::animation::Layer;
+use crate::formats::flatten_layers;
+use crate::history::Operation;
+use crate::selection::{ClipboardImage, Selection};
+use super::formats::collect_raster_layers;
+use super::drawing::DrawingState;
+use super::paging::ensure_resident;
+use super::selection::SelectionState;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::RgbaImage;
+use log::{info, debug, error};
+use serde::Serialize;
+use tauri::State;
+use tokio::sync::Mutex;
+
+/// アプリ内クリップボードの状態管理
+///
+/// 描画状態とは別に保持するため、レイヤー間やプロジェクトを開き直した後でも貼り付けられる。
+pub struct ClipboardState {
+    content: Mutex<Option<ClipboardImage>>,
+}
+
+impl ClipboardState {
+    pub fn new() -> Self {
+        Self {
+            content: Mutex::new(None),
+        }
+    }
+}
+
+impl Default for ClipboardState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// クリップボードの内容の情報
+#[derive(Serialize)]
+pub struct ClipboardInfo {
+    /// コピー元の位置（その場に貼り付けるときの位置）
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl From<&ClipboardImage> for ClipboardInfo {
+    fn from(content: &ClipboardImage) -> Self {
+        Self {
+            x: content.x,
+            y: content.y,
+            width: content.image.width(),
+            height: content.image.height(),
+        }
+    }
+}
+
+/// レイヤーの画像を読み出す（退避中なら復帰させる）
+pub(crate) async fn read_layer_image(state: &DrawingState, layer_id: &str) -> Result<RgbaImage, String> {
+    if !state.layers.lock().await.contains_key(layer_id) {
+        return Err(format!("レイヤーが見つかりません: {}", layer_id));
+    }
+    let layer_id = layer_id.to_string();
+    ensure_resident(state, Some(std::slice::from_ref(&layer_id))).await?;
+
+    let engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+    engine.get_layer_image(&layer_id).await.map_err(|e| {
+        error!("[Clipboard API] レイヤー画像の取得に失敗: {}", e);
+        format!("レイヤー画像取得エラー: {}", e)
+    })
+}
+
+/// 操作を適用して履歴に記録
+async fn apply_operations(state: &DrawingState, operations: Vec<Operation>) -> Result<(), String> {
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        for operation in &operations {
+            operation.apply(engine, &mut layers_guard).map_err(|e| {
+                error!("[Clipboard API] 操作の適用に失敗: {}", e);
+                format!("クリップボード操作エラー: {}", e)
+            })?;
+        }
+    }
+    for operation in operations {
+        state.record_operation(operation).await;
+    }
+    Ok(())
+}
+
+async fn copy_to_clipboard(
+    layer_id: &str,
+    state: &DrawingState,
+    selection_state: &SelectionState,
+    clipboard: &ClipboardState,
+) -> Result<(ClipboardInfo, Selection), String> {
+    if selection_state.is_transforming().await {
+        return Err("選択範囲の変形中です".to_string());
+    }
+    let selection = selection_state.current().await.ok_or("選択範囲がありません")?;
+    let image = read_layer_image(state, layer_id).await?;
+
+    let content = ClipboardImage::from_selection(&image, &selection);
+    let info = ClipboardInfo::from(&content);
+    *clipboard.content.lock().await = Some(content);
+    debug!("[Clipboard API] コピー: {} ({}, {}) {}x{}", layer_id, info.x, info.y, info.width, info.height);
+    Ok((info, selection))
+}
+
+/// 合成した範囲を貼り付け操作にする（重ならなければ None）
+fn paste_operation(layer_id: &str, content: &ClipboardImage, target: &RgbaImage) -> Result<Option<Operation>, String> {
+    match content.composite_onto(target) {
+        Some((x, y, region)) => Operation::paste_image(layer_id, x, y, &region).map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 選択範囲の画素をクリップボードにコピー
+#[tauri::command]
+pub async fn copy_selection(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+    clipboard: State<'_, ClipboardState>,
+) -> Result<ClipboardInfo, String> {
+    let (info, _) = copy_to_clipboard(&layer_id, &state, &selection_state, &clipboard).await?;
+    info!("[Clipboard API] 選択範囲をコピー: {}", layer_id);
+    Ok(info)
+}
+
+/// 選択範囲の画素をクリップボードに移し、レイヤーから消す
+#[tauri::command]
+pub async fn cut_selection(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+    clipboard: State<'_, ClipboardState>,
+) -> Result<ClipboardInfo, String> {
+    let (info, selection) = copy_to_clipboard(&layer_id, &state, &selection_state, &clipboard).await?;
+    apply_operations(&state, vec![Operation::EraseSelection { layer_id: layer_id.clone(), selection }]).await?;
+    info!("[Clipboard API] 選択範囲を切り取り: {}", layer_id);
+    Ok(info)
+}
+
+/// クリップボードの内容を既存のレイヤーのコピー元と同じ位置に貼り付け
+#[tauri::command]
+pub async fn paste_in_place(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+    clipboard: State<'_, ClipboardState>,
+) -> Result<ClipboardInfo, String> {
+    if selection_state.is_transforming().await {
+        return Err("選択範囲の変形中です".to_string());
+    }
+    let content = clipboard.content.lock().await.clone().ok_or("クリップボードが空です")?;
+    let target = read_layer_image(&state, &layer_id).await?;
+
+    let operation = paste_operation(&layer_id, &content, &target)?
+        .ok_or("貼り付け位置がレイヤーの範囲外です")?;
+    apply_operations(&state, vec![operation]).await?;
+
+    let info = ClipboardInfo::from(&content);
+    selection_state.replace(Selection::rect(info.x, info.y, info.width, info.height)).await?;
+    info!("[Clipboard API] その場に貼り付け: {} ({}, {})", layer_id, info.x, info.y);
+    Ok(info)
+}
+
+/// 画像を新しいレイヤーとして貼り付け
+///
+/// レイヤーの大きさは既存のレイヤー（キャンバス）に合わせ、レイヤーがなければ貼り付ける画像の大きさにする。
+async fn paste_new_layer(
+    layer_id: String,
+    mut content: ClipboardImage,
+    state: &DrawingState,
+    selection_state: &SelectionState,
+) -> Result<ClipboardInfo, String> {
+    if layer_id.is_empty() {
+        return Err("レイヤーIDが空です".to_string());
+    }
+    if selection_state.is_transforming().await {
+        return Err("選択範囲の変形中です".to_string());
+    }
+
+    let (width, height) = {
+        let layers_guard = state.layers.lock().await;
+        if layers_guard.contains_key(&layer_id) {
+            return Err(format!("レイヤーが既に存在します: {}", layer_id));
+        }
+        layers_guard.values().fold((0, 0), |(w, h), (lw, lh)| (w.max(*lw), h.max(*lh)))
+    };
+    let (width, height) = if width == 0 || height == 0 {
+        content.x = 0;
+        content.y = 0;
+        content.image.dimensions()
+    } else {
+        (width, height)
+    };
+    state.check_canvas_size(width, height).await?;
+
+    let mut operations = vec![Operation::CreateLayer { layer_id: layer_id.clone(), width, height }];
+    operations.extend(paste_operation(&layer_id, &content, &RgbaImage::new(width, height))?);
+    apply_operations(state, operations).await?;
+
+    let info = ClipboardInfo::from(&content);
+    selection_state.replace(Selection::rect(info.x, info.y, info.width, info.height)).await?;
+    info!("[Clipboard API] 新しいレイヤーに貼り付け: {} ({}x{})", layer_id, width, height);
+    Ok(info)
+}
+
+/// クリップボードの内容を新しいレイヤーとして貼り付け
+#[tauri::command]
+pub async fn paste_as_new_layer(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+    clipboard: State<'_, ClipboardState>,
+) -> Result<ClipboardInfo, String> {
+    let content = clipboard.content.lock().await.clone().ok_or("クリップボードが空です")?;
+    paste_new_layer(layer_id, content, &state, &selection_state).await
+}
+
+/// クリップボードの内容の情報を取得（空なら None）
+#[tauri::command]
+pub async fn get_clipboard_info(clipboard: State<'_, ClipboardState>) -> Result<Option<ClipboardInfo>, String> {
+    Ok(clipboard.content.lock().await.as_ref().map(ClipboardInfo::from))
+}
+
+/// クリップボードの内容を PNG（Base64）で取得（OS のクリップボードへの書き出し用）
+#[tauri::command]
+pub async fn export_clipboard_png(clipboard: State<'_, ClipboardState>) -> Result<String, String> {
+    let content = clipboard.content.lock().await.clone().ok_or("クリップボードが空です")?;
+    let png = tokio::task::spawn_blocking(move || content.to_png())
+        .await
+        .map_err(|e| format!("エンコードタスクエラー: {}", e))?
+        .map_err(|e| e.to_string())?;
+    Ok(STANDARD.encode(png))
+}
+
+/// PNG（Base64）をクリップボードに読み込む（OS のクリップボードからの取り込み用）
+#[tauri::command]
+pub async fn import_clipboard_png(
+    png_base64: String,
+    clipboard: State<'_, ClipboardState>,
+) -> Result<ClipboardInfo, String> {
+    let png = STANDARD.decode(png_base64)
+        .map_err(|e| format!("画像データのデコードに失敗: {}", e))?;
+    let content = tokio::task::spawn_blocking(move || ClipboardImage::from_png(&png))
+        .await
+        .map_err(|e| format!("デコードタスクエラー: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    let info = ClipboardInfo::from(&content);
+    *clipboard.content.lock().await = Some(content);
+    info!("[Clipboard API] PNG をクリップボードに読み込み: {}x{}", info.width, info.height);
+    Ok(info)
+}
+
+/// 合成したキャンバス（選択範囲があればその部分）を OS のクリップボードにコピー
+///
+/// 画像は RGBA で渡し、PNG などへの変換は OS 側のクリップボードの実装に任せる。
+#[tauri::command]
+pub async fn copy_canvas_to_system_clipboard(
+    layers: Option<Vec<Layer>>,
+    selection_only: bool,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<ClipboardInfo, String> {
+    let (raster_layers, width, height) = collect_raster_layers(&state, layers).await?;
+    let composite = flatten_layers(&raster_layers, width, height);
+    let content = match selection_state.current().await {
+        Some(selection) if selection_only => ClipboardImage::from_selection(&composite, &selection),
+        _ => ClipboardImage { x: 0, y: 0, image: composite },
+    };
+    let info = ClipboardInfo::from(&content);
+
+    tokio::task::spawn_blocking(move || {
+        let (width, height) = content.image.dimensions();
+        let mut system = arboard::Clipboard::new()?;
+        system.set_image(arboard::ImageData {
+            width: width as usize,
+            height: height as usize,
+            bytes: content.image.into_raw().into(),
+        })
+    })
+    .await
+    .map_err(|e| format!("クリップボードタスクエラー: {}", e))?
+    .map_err(|e| {
+        error!("[Clipboard API] OS クリップボードへの書き込みに失敗: {}", e);
+        format!("OS クリップボードへの書き込みエラー: {}", e)
+    })?;
+
+    info!("[Clipboard API] OS クリップボードにコピー: {}x{}", info.width, info.height);
+    Ok(info)
+}
+
+/// OS のクリップボードの画像を新しいレイヤーとして貼り付け
+#[tauri::command]
+pub async fn paste_from_system_clipboard(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<ClipboardInfo, String> {
+    let image = tokio::task::spawn_blocking(|| {
+        let mut system = arboard::Clipboard::new()?;
+        let data = system.get_image()?;
+        Ok::<_, arboard::Error>(RgbaImage::from_raw(data.width as u32, data.height as u32, data.bytes.into_owned()))
+    })
+    .await
+    .map_err(|e| format!("クリップボードタスクエラー: {}", e))?
+    .map_err(|e| {
+        debug!("[Clipboard API] OS クリップボードの読み込みに失敗: {}", e);
+        format!("OS クリップボードに画像がありません: {}", e)
+    })?
+    .ok_or("OS クリップボードの画像データが不正です")?;
+
+    if image.width() == 0 || image.height() == 0 {
+        return Err("OS クリップボードの画像が空です".to_string());
+    }
+    // 外部の画像は位置を持たないため左上に置く
+    paste_new_layer(layer_id, ClipboardImage { x: 0, y: 0, image }, &state, &selection_state).await
+}
@@ -0,0 +1,208 @@
+use crate::collaboration::{CollaborationSession, SyncOperation};
+use crate::history::OperationLog;
+use crate::journal::JournalRecord;
+use super::drawing::DrawingState;
+use super::history::{rebuild_engine_state, validate_batch, validate_replay};
+use super::paging::{affected_layers, ensure_resident};
+use log::{info, debug, warn, error};
+use serde::Serialize;
+use tauri::State;
+
+/// 共同編集セッションの状態
+#[derive(Serialize)]
+pub struct CollaborationStatus {
+    pub active: bool,
+    pub site_id: Option<String>,
+    pub lamport_time: u64,
+    pub operation_count: usize,
+}
+
+/// リモート操作適用の結果
+#[derive(Serialize)]
+pub struct RemoteApplyResult {
+    pub applied: usize,
+    pub duplicates: usize,
+    pub rebuilt: bool,
+}
+
+/// 共同編集を開始（既存の履歴はローカル操作として共有対象になる）
+#[tauri::command]
+pub async fn start_collaboration(
+    site_id: Option<String>,
+    state: State<'_, DrawingState>,
+) -> Result<String, String> {
+    Ok(ensure_collaboration_session(&state, site_id).await)
+}
+
+/// 共同編集セッションが無ければ開始し、サイトIDを返す
+pub(crate) async fn ensure_collaboration_session(state: &DrawingState, site_id: Option<String>) -> String {
+    let mut collaboration_guard = state.collaboration.lock().await;
+    if let Some(session) = collaboration_guard.as_ref() {
+        warn!("[Collaboration API] 共同編集は既に開始済み: {}", session.site_id());
+        return session.site_id().to_string();
+    }
+
+    let site_id = site_id.unwrap_or_else(CollaborationSession::generate_site_id);
+    let mut session = CollaborationSession::new(site_id.clone());
+
+    {
+        let history_guard = state.history.lock().await;
+        for entry in history_guard.applied_entries() {
+            session.record_local(entry.operation.clone());
+        }
+        debug!("[Collaboration API] 既存履歴を共有対象に追加: {} 操作", session.len());
+    }
+
+    *collaboration_guard = Some(session);
+    info!("[Collaboration API] 共同編集開始: {}", site_id);
+    site_id
+}
+
+/// 共同編集を終了
+#[tauri::command]
+pub async fn stop_collaboration(
+    state: State<'_, DrawingState>,
+) -> Result<(), String> {
+    let mut collaboration_guard = state.collaboration.lock().await;
+    match collaboration_guard.take() {
+        Some(session) => {
+            info!("[Collaboration API] 共同編集終了: {}", session.site_id());
+            Ok(())
+        }
+        None => Err("共同編集は開始されていません".to_string()),
+    }
+}
+
+/// 共同編集セッションの状態を取得
+#[tauri::command]
+pub async fn get_collaboration_status(
+    state: State<'_, DrawingState>,
+) -> Result<CollaborationStatus, String> {
+    let collaboration_guard = state.collaboration.lock().await;
+    Ok(match collaboration_guard.as_ref() {
+        Some(session) => CollaborationStatus {
+            active: true,
+            site_id: Some(session.site_id().to_string()),
+            lamport_time: session.lamport_time(),
+            operation_count: session.len(),
+        },
+        None => CollaborationStatus {
+            active: false,
+            site_id: None,
+            lamport_time: 0,
+            operation_count: 0,
+        },
+    })
+}
+
+/// 指定したランポート時刻以降のローカル操作を取得（送信用）
+#[tauri::command]
+pub async fn get_local_ops_since(
+    since: u64,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<SyncOperation>, String> {
+    let collaboration_guard = state.collaboration.lock().await;
+    let session = collaboration_guard.as_ref().ok_or("共同編集は開始されていません")?;
+    let ops = session.local_ops_since(since);
+    debug!("[Collaboration API] ローカル操作取得: since={} -> {} 操作", since, ops.len());
+    Ok(ops)
+}
+
+/// リモート操作をマージしてエンジンに反映
+#[tauri::command]
+pub async fn apply_remote_ops(
+    ops: Vec<SyncOperation>,
+    state: State<'_, DrawingState>,
+) -> Result<RemoteApplyResult, String> {
+    merge_remote_operations(&state, ops).await
+}
+
+/// リモート操作をマージしてエンジンと履歴に反映（コマンド・同期サーバー共通）
+///
+/// 取り込んだ操作は適用前に検証し、不正な操作を含む場合は何も取り込まない。適用に失敗した場合は
+/// 失敗した操作以降を取り込み済みから外すため、再送されれば改めて適用され、他のサイトとずれたままにならない。
+pub(crate) async fn merge_remote_operations(
+    state: &DrawingState,
+    ops: Vec<SyncOperation>,
+) -> Result<RemoteApplyResult, String> {
+    debug!("[Collaboration API] リモート操作受信: {} 操作", ops.len());
+
+    let mut collaboration_guard = state.collaboration.lock().await;
+    let session = collaboration_guard.as_mut().ok_or("共同編集は開始されていません")?;
+    let outcome = session.merge_remote(ops);
+
+    if outcome.inserted.is_empty() {
+        return Ok(RemoteApplyResult {
+            applied: 0,
+            duplicates: outcome.duplicates,
+            rebuilt: false,
+        });
+    }
+
+    // 先頭から再生する場合は全操作を、末尾に追加する場合は追加分を現在のレイヤーに照らして検証する
+    let issues = if outcome.requires_rebuild {
+        validate_replay(state, session.operations().iter().map(|op| &op.operation)).await
+    } else {
+        validate_batch(state, outcome.inserted.iter().map(|op| &op.operation)).await
+    };
+    if let Some(issue) = issues.into_iter().next() {
+        session.discard(&outcome.inserted);
+        warn!("[Collaboration API] 不正なリモート操作 #{}: {}", issue.index, issue.message);
+        return Err(format!("リモート操作 #{} が不正です: {}", issue.index, issue.message));
+    }
+
+    // ロック順序: collaboration → history → pager → engine → layers（アンドゥ系コマンドと同じ）
+    let mut history_guard = state.history.lock().await;
+    if outcome.requires_rebuild {
+        // 既存操作の間に挿入されたため、全順序に従って先頭から再生
+        let log = session.to_operation_log();
+        if let Err(e) = rebuild_engine_state(state, &log).await {
+            error!("[Collaboration API] 再構築に失敗: {}", e);
+            session.discard(&outcome.inserted);
+            let _ = rebuild_engine_state(state, &history_guard).await;
+            return Err(format!("リモート操作適用エラー: {}", e));
+        }
+        *history_guard = log;
+        state.save_tracker.lock().await.mark_all();
+        state.write_journal(JournalRecord::Base { log: history_guard.clone() }).await;
+    } else {
+        let layer_ids = affected_layers(outcome.inserted.iter().map(|op| &op.operation));
+        if let Err(e) = ensure_resident(state, layer_ids.as_deref()).await {
+            session.discard(&outcome.inserted);
+            return Err(e);
+        }
+        if let Err((failed, e)) = apply_tail(state, &mut history_guard, &outcome.inserted).await {
+            error!("[Collaboration API] リモート操作の適用に失敗: {}", e);
+            session.discard(&outcome.inserted[failed..]);
+            return Err(format!("リモート操作適用エラー: {}", e));
+        }
+    }
+
+    info!("[Collaboration API] リモート操作適用完了: {} 操作 (再構築: {})",
+          outcome.inserted.len(), outcome.requires_rebuild);
+    Ok(RemoteApplyResult {
+        applied: outcome.inserted.len(),
+        duplicates: outcome.duplicates,
+        rebuilt: outcome.requires_rebuild,
+    })
+}
+
+/// 末尾に追加されたリモート操作を順に適用して履歴に積む（失敗した場合はその操作の位置とエラー）
+async fn apply_tail(
+    state: &DrawingState,
+    history: &mut OperationLog,
+    ops: &[SyncOperation],
+) -> Result<(), (usize, String)> {
+    for (index, op) in ops.iter().enumerate() {
+        {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or((index, "描画エンジンが初期化されていません".to_string()))?;
+            let mut layers_guard = state.layers.lock().await;
+            op.operation.apply(engine, &mut layers_guard).map_err(|e| (index, e.to_string()))?;
+        }
+        history.push(op.operation.clone());
+        state.save_tracker.lock().await.mark_operation(&op.operation);
+        state.write_journal(JournalRecord::Push { operation: op.operation.clone() }).await;
+    }
+    Ok(())
+}
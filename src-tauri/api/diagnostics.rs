@@ -0,0 +1,95 @@
+use crate::drawing_engine::{FrameTimeStats, GpuAdapterInfo, GpuDiagnostics, GpuErrorRecord, GpuPreference, StrokeLatency};
+use crate::memory::MemoryReport;
+use super::drawing::DrawingState;
+use super::gpu::current_adapters;
+use super::memory::{check_memory, MemoryState};
+use super::settings::SettingsState;
+use log::info;
+use serde::Serialize;
+use tauri::{AppHandle, State};
+
+/// 不具合報告に添付する性能診断のレポート
+#[derive(Serialize)]
+pub struct DiagnosticsReport {
+    /// 作成時刻（RFC 3339）
+    pub generated_at: String,
+    pub app_version: &'static str,
+    pub os: &'static str,
+    pub arch: &'static str,
+    /// 合成・書き出しに使うCPUワーカースレッド数
+    pub cpu_workers: usize,
+    /// 使用中のGPU（描画エンジンの初期化前は None）
+    pub gpu: Option<GpuDiagnostics>,
+    /// 利用可能なGPUアダプター
+    pub adapters: Vec<GpuAdapterInfo>,
+    pub gpu_preference: GpuPreference,
+    /// 最近のGPUエラー（古い順）
+    pub gpu_errors: Vec<GpuErrorRecord>,
+    /// ストロークの点を追加して描画するまでの時間の分布（記録がなければ None）
+    pub stroke_frame_times: Option<FrameTimeStats>,
+    /// キャンバス表示画像を作るまでの時間の分布（記録がなければ None）
+    pub view_frame_times: Option<FrameTimeStats>,
+    /// 最近のストロークの入力から表示までの遅延
+    pub recent_strokes: Vec<StrokeLatency>,
+    pub memory: MemoryReport,
+}
+
+/// 性能診断のレポートを作成し、JSON ファイルとして `path` に書き出す
+///
+/// 設定の `collect_diagnostics` を有効にしたセッションでのみ作成できる（フレーム時間はその間だけ記録される）。
+/// レポートはローカルのファイルに書き出すだけで、どこにも送信しない。
+#[tauri::command]
+pub async fn generate_diagnostics_report(
+    path: String,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+    memory: State<'_, MemoryState>,
+) -> Result<DiagnosticsReport, String> {
+    let app_settings = settings.get().await;
+    if !app_settings.collect_diagnostics {
+        return Err("診断情報の記録が無効です（設定の collect_diagnostics を有効にしてください）".to_string());
+    }
+
+    let (gpu, gpu_errors) = match state.engine.lock().await.as_ref() {
+        Some(engine) => (engine.gpu_diagnostics(), engine.gpu_errors().recent()),
+        None => (None, Vec::new()),
+    };
+    let adapters = current_adapters(&state).await;
+    let (stroke_frame_times, view_frame_times) = {
+        let diagnostics = state.diagnostics.lock().await;
+        (diagnostics.stroke_frames.stats(), diagnostics.view_frames.stats())
+    };
+    let report = DiagnosticsReport {
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        app_version: env!("CARGO_PKG_VERSION"),
+        os: std::env::consts::OS,
+        arch: std::env::consts::ARCH,
+        cpu_workers: rayon::current_num_threads(),
+        gpu,
+        adapters,
+        gpu_preference: app_settings.gpu,
+        gpu_errors,
+        stroke_frame_times,
+        view_frame_times,
+        recent_strokes: state.latency.lock().await.recent(),
+        memory: check_memory(&app, &state, &memory).await,
+    };
+
+    let data = serde_json::to_vec_pretty(&report)
+        .map_err(|e| format!("診断レポートの変換に失敗しました: {}", e))?;
+    tokio::fs::write(&path, &data).await
+        .map_err(|e| format!("診断レポートの書き込みに失敗しました: {}", e))?;
+
+    info!("[Diagnostics API] 診断レポートを書き出しました: {} ({} bytes, GPUエラー {} 件)", path, data.len(), report.gpu_errors.len());
+    Ok(report)
+}
+
+/// キャンバス表示画像に性能オーバーレイ（FPS・フレーム時間のグラフ・書き込まれたタイルの枠・メモリ使用量）を
+/// 重ねるかを切り替える（切り替えるとそれまでの記録は捨てる）
+#[tauri::command]
+pub async fn set_perf_overlay(enabled: bool, state: State<'_, DrawingState>) -> Result<bool, String> {
+    state.perf_overlay.lock().await.set_enabled(enabled);
+    info!("[Diagnostics API] 性能オーバーレイ: {}", if enabled { "有効" } else { "無効" });
+    Ok(enabled)
+}
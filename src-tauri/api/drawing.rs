@@ -1,14 +1,95 @@
-use crate::drawing_engine::{DrawingEngine, DrawStroke, Vertex2D};
+use crate::drawing_engine::{DrawingEngine, DefragmentOptions, DefragmentReport, DiagnosticsRecorder, IdleScheduler, Navigator, PerfOverlay, ResizeDebouncer, CanvasRenderSettings, CanvasTransform, CanvasRenderStatus, ContentBounds, DeviceCapabilities, StrokeTessellator, ViewTransform, InputTiming, LatencyTracker, StrokeLatency, MAX_PIXEL_ZOOM, sanitize_stroke, sanitize_style, SanitizedStroke, StrokeInputPoint};
+use crate::drawing_engine::latency::render_latency_graph;
+use crate::drawing_engine::perf_overlay::render_perf_overlay;
+use crate::drawing_engine::coverage::mesh_bounds;
+use crate::drawing_engine::pixel::scale_nearest;
+use crate::drawing_engine::overlay::render_view;
+use crate::drawing_engine::compare::{compose_comparison, difference_heatmap, CompareLayout, DifferenceStats};
+use crate::animation::{FrameMarkers, Layer};
+use crate::history::{HistoryCheckpoints, OperationLog, Operation, StrokePickBuffer, StrokeRecord, StrokePointRecord};
+use crate::collaboration::CollaborationSession;
+use crate::formats::{flatten_layers, SaveTracker};
+use crate::selection::render_quick_mask;
+use crate::timelapse::TimelapseRecorder;
+use crate::guides::GuideSettings;
+use crate::paging::FramePager;
+use crate::journal::{Journal, JournalRecord};
+use crate::tablet::PressureCurve;
+use super::boundary::{gpu_boundary, CommandError};
+use super::clipboard::read_layer_image;
+use super::fill::RegionCache;
+use super::formats::{collect_preview_layers, collect_raster_layers, texture_layer};
+use super::selection::{SelectionState, QUICK_MASK_TEXTURE_ID};
+use super::settings::SettingsState;
+use super::gpu::pipeline_cache_dir;
+use super::memory::measure_memory_usage;
+use super::paging::ensure_resident;
 use log::{info, debug, warn, error, trace};
 use std::collections::HashMap;
+use std::sync::atomic::AtomicBool;
+use std::sync::Arc;
+use std::time::Instant;
 use tokio::sync::Mutex;
-use tauri::State;
+use tauri::{AppHandle, State};
 use serde::{Deserialize, Serialize};
 
 /// 描画エンジンの状態管理
 pub struct DrawingState {
-    engine: Mutex<Option<DrawingEngine>>,
-    layers: Mutex<HashMap<String, (u32, u32)>>, // layer_id -> (width, height)
+    pub(crate) engine: Mutex<Option<DrawingEngine>>,
+    pub(crate) layers: Mutex<HashMap<String, (u32, u32)>>, // layer_id -> (width, height)
+    pub(crate) history: Mutex<OperationLog>,
+    /// 履歴の途中の状態（アンドゥ・リドゥで先頭から再生しないため。ロック順序は history の直後）
+    pub(crate) checkpoints: Mutex<HistoryCheckpoints>,
+    pub(crate) collaboration: Mutex<Option<CollaborationSession>>,
+    pub(crate) timelapse: Mutex<Option<TimelapseRecorder>>,
+    pub(crate) guides: Mutex<GuideSettings>,
+    /// フレームマーカー（保存時にプロジェクトへ書き出す）
+    pub(crate) markers: Mutex<FrameMarkers>,
+    /// 実行中のストローク再生の中断フラグ
+    pub(crate) replay: Mutex<Option<Arc<AtomicBool>>>,
+    /// ディスクへ退避したレイヤー（`layers` には残る）
+    pub(crate) pager: Mutex<FramePager>,
+    /// 確定した操作を書き出すジャーナル（起動処理で開くまでは None）
+    pub(crate) journal: Mutex<Option<Journal>>,
+    /// 表示側と同期している表示の変換（ストロークの点を画面座標からドキュメント座標へ戻す）
+    pub(crate) view: Mutex<ViewTransform>,
+    /// レイヤーごとのストロークのIDバッファ（`pick_object` 用。履歴が変わると作り直す）
+    pub(crate) pick_buffers: Mutex<HashMap<String, StrokePickBuffer>>,
+    /// 線画レイヤー（着色モードのバケツ塗りで範囲を決めるレイヤー）
+    pub(crate) lineart_layer: Mutex<Option<String>>,
+    /// 線画の領域分割（線画が変わるまで使い回す）
+    pub(crate) regions: Mutex<Option<RegionCache>>,
+    /// キャンバス全体の縮小画像（書き込まれたタイルだけ更新する）
+    pub(crate) navigator: Mutex<Navigator>,
+    /// ナビゲーターに最後に指定された大きさとレイヤーの構成（アイドル時に同じ構成で更新する）
+    pub(crate) navigator_source: Mutex<Option<(u32, Option<Vec<Layer>>)>>,
+    /// ストロークごとの入力から表示までの遅延
+    pub(crate) latency: Mutex<LatencyTracker>,
+    /// 性能診断のフレーム時間（設定で有効にしたときだけ記録する）
+    pub(crate) diagnostics: Mutex<DiagnosticsRecorder>,
+    /// 表示画像に重ねる性能オーバーレイ（`set_perf_overlay` で有効にしたときだけ記録する）
+    pub(crate) perf_overlay: Mutex<PerfOverlay>,
+    /// 描画中のストローク（layer_id -> ストローク）
+    pub(crate) active_strokes: Mutex<HashMap<String, ActiveStroke>>,
+    /// 最後に保存してから変更されたレイヤー（追記保存に使う）
+    pub(crate) save_tracker: Mutex<SaveTracker>,
+    /// まだ適用していないキャンバスの解像度変更（`request_canvas_resize` で続けて届いた要求をまとめる）
+    pub(crate) resize: Mutex<ResizeDebouncer>,
+    /// コマンドのパニック後に描画エンジンの再初期化が必要か（`gpu_boundary` が次のコマンドの前に行う）
+    pub(crate) engine_recovery: AtomicBool,
+    /// 最後にストローク・コマンドが届いた時刻と、今回のアイドルで済ませたメンテナンス
+    pub(crate) idle: Mutex<IdleScheduler>,
+    /// 実行中のアイドル時のメンテナンスの停止フラグ
+    pub(crate) maintenance: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+/// 描画中のストローク（追加された線分だけを描画し、終了時に間引いて履歴へ記録する）
+pub(crate) struct ActiveStroke {
+    record: StrokeRecord,
+    curve: PressureCurve,
+    tessellator: StrokeTessellator,
+    /// 消しゴム（確定するまでレイヤーを変えず、表示だけに反映する）
+    erase: bool,
 }
 
 impl DrawingState {
@@ -17,7 +98,109 @@ impl DrawingState {
         Self {
             engine: Mutex::new(None),
             layers: Mutex::new(HashMap::new()),
+            history: Mutex::new(OperationLog::new()),
+            checkpoints: Mutex::new(HistoryCheckpoints::new()),
+            collaboration: Mutex::new(None),
+            timelapse: Mutex::new(None),
+            guides: Mutex::new(GuideSettings::default()),
+            markers: Mutex::new(FrameMarkers::new()),
+            replay: Mutex::new(None),
+            pager: Mutex::new(FramePager::in_temp_dir()),
+            journal: Mutex::new(None),
+            view: Mutex::new(ViewTransform::default()),
+            pick_buffers: Mutex::new(HashMap::new()),
+            lineart_layer: Mutex::new(None),
+            regions: Mutex::new(None),
+            navigator: Mutex::new(Navigator::new()),
+            navigator_source: Mutex::new(None),
+            latency: Mutex::new(LatencyTracker::new()),
+            diagnostics: Mutex::new(DiagnosticsRecorder::new()),
+            perf_overlay: Mutex::new(PerfOverlay::new()),
+            active_strokes: Mutex::new(HashMap::new()),
+            save_tracker: Mutex::new(SaveTracker::new()),
+            resize: Mutex::new(ResizeDebouncer::new()),
+            engine_recovery: AtomicBool::new(false),
+            idle: Mutex::new(IdleScheduler::new(Instant::now())),
+            maintenance: Mutex::new(None),
+        }
+    }
+
+    /// ジャーナルに記録（書き込めなくても編集は続けられるよう、失敗は警告に留める）
+    pub(crate) async fn write_journal(&self, record: JournalRecord) {
+        if let Some(journal) = self.journal.lock().await.as_mut() {
+            if let Err(e) = journal.append(&record) {
+                warn!("[Drawing State] ジャーナルへの書き込みに失敗: {}", e);
+            }
+        }
+    }
+
+    /// 履歴位置の移動で適用・取り消しされた操作（`from`〜`to` の間）のレイヤーを未保存にする
+    pub(crate) async fn mark_unsaved_range(&self, log: &OperationLog, from: usize, to: usize) {
+        let mut tracker = self.save_tracker.lock().await;
+        for entry in &log.entries()[from.min(to)..from.max(to)] {
+            tracker.mark_operation(&entry.operation);
+        }
+    }
+
+    /// ストローク・コマンドが届いたことを記録する（アイドル時のメンテナンスを後回しにする）
+    pub(crate) async fn touch_activity(&self) {
+        self.idle.lock().await.touch(Instant::now());
+    }
+
+    /// 確定した操作を履歴と共同編集セッションに記録
+    pub(crate) async fn record_operation(&self, operation: Operation) {
+        self.touch_activity().await;
+        {
+            let mut collaboration_guard = self.collaboration.lock().await;
+            if let Some(session) = collaboration_guard.as_mut() {
+                session.record_local(operation.clone());
+            }
+        }
+        self.history.lock().await.push(operation.clone());
+        self.save_tracker.lock().await.mark_operation(&operation);
+        self.write_journal(JournalRecord::Push { operation }).await;
+        self.capture_timelapse_frame().await;
+    }
+
+    /// タイムラプス記録中で記録間隔に達していれば、合成画像をフレームとして追加
+    ///
+    /// 合成中は他の操作を妨げないよう、タイムラプスのロックを解放してから画像を読み出す。
+    async fn capture_timelapse_frame(&self) {
+        let now = std::time::Instant::now();
+        let due = match self.timelapse.lock().await.as_mut() {
+            Some(recorder) => recorder.note_operation(now),
+            None => false,
+        };
+        if !due {
+            return;
+        }
+
+        let image = match collect_raster_layers(self, None).await {
+            Ok((layers, width, height)) => flatten_layers(&layers, width, height),
+            Err(e) => {
+                debug!("[Drawing State] タイムラプスのフレームを取得できません: {}", e);
+                return;
+            }
+        };
+
+        if let Some(recorder) = self.timelapse.lock().await.as_mut() {
+            if let Err(e) = recorder.add_frame(&image, now) {
+                warn!("[Drawing State] タイムラプスのフレーム記録に失敗: {}", e);
+            }
+        }
+    }
+
+    /// キャンバス・レイヤーのサイズがデバイスの上限内か確認（初期化前は既定の上限で判定）
+    pub(crate) async fn check_canvas_size(&self, width: u32, height: u32) -> Result<(), String> {
+        let limits = self.engine.lock().await.as_ref()
+            .map(|engine| engine.canvas_limits())
+            .unwrap_or_default();
+        if limits.allows(width, height) {
+            return Ok(());
         }
+        let max = limits.max_dimension();
+        error!("[Drawing State] 解像度上限超過: {}x{} (最大: {}x{})", width, height, max, max);
+        Err(format!("解像度が最大値({}x{})を超えています", max, max))
     }
 
     /// デバッグ用：現在の状態を詳細出力
@@ -40,7 +223,9 @@ impl DrawingState {
 /// 描画エンジンを初期化
 #[tauri::command]
 pub async fn initialize_drawing_engine(
+    app: AppHandle,
     state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
 ) -> Result<String, String> {
     info!("[Drawing API] 描画エンジン初期化開始");
     trace!("[Drawing API] 初期化前の状態確認");
@@ -61,6 +246,10 @@ pub async fn initialize_drawing_engine(
     // 描画エンジン作成
     debug!("[Drawing API] DrawingEngine::new() を呼び出し");
     let mut engine = DrawingEngine::new();
+    let current_settings = settings.get().await;
+    engine.set_gpu_preference(current_settings.gpu);
+    engine.set_stroke_vertex_limit(current_settings.stroke_vertex_limit);
+    engine.set_pipeline_cache_dir(pipeline_cache_dir(&app));
     
     // 初期化実行
     debug!("[Drawing API] engine.initialize() を実行開始");
@@ -110,11 +299,8 @@ pub async fn create_drawing_layer(
         return Err("解像度は1以上である必要があります".to_string());
     }
     
-    // 最大解像度チェック
-    if width > 4096 || height > 4096 {
-        error!("[Drawing API] 解像度上限超過: {}x{} (最大: 4096x4096)", width, height);
-        return Err("解像度が最大値(4096x4096)を超えています".to_string());
-    }
+    // 最大解像度チェック（デバイスの上限に従う）
+    state.check_canvas_size(width, height).await?;
     
     debug!("[Drawing API] 引数バリデーション完了");
     
@@ -132,6 +318,9 @@ pub async fn create_drawing_layer(
         debug!("[Drawing API] 現在のレイヤー数: {}", layers_guard.len());
     }
     
+    // 上書きするレイヤーが退避中なら退避内容を破棄
+    state.pager.lock().await.discard(&layer_id);
+    
     // 描画エンジンでのレイヤー作成
     debug!("[Drawing API] 描画エンジンでレイヤーテクスチャ作成開始");
     {
@@ -148,6 +337,16 @@ pub async fn create_drawing_layer(
                         return Err(format!("レイヤー作成エラー: {}", e));
                     }
                 }
+                // プールから再利用したテクスチャに残る内容を消去（履歴再生と同じ初期状態にする）
+                match engine.clear_layer_texture(&layer_id, Some(wgpu::Color::TRANSPARENT)) {
+                    Ok(_) => {
+                        debug!("[Drawing API] 新規レイヤーの初期クリア完了");
+                    },
+                    Err(e) => {
+                        error!("[Drawing API] clear_layer_texture でエラー: {}", e);
+                        return Err(format!("レイヤー作成エラー: {}", e));
+                    }
+                }
             },
             None => {
                 error!("[Drawing API] 描画エンジンが初期化されていません");
@@ -164,6 +363,12 @@ pub async fn create_drawing_layer(
         debug!("[Drawing API] レイヤー情報保存完了 - 総レイヤー数: {}", layers_guard.len());
     }
     
+    state.record_operation(Operation::CreateLayer {
+        layer_id: layer_id.clone(),
+        width,
+        height,
+    }).await;
+    
     // 最終状態確認
     state.log_detailed_state().await;
     info!("[Drawing API] レイヤー作成完了: {} ({}x{})", layer_id, width, height);
@@ -181,73 +386,79 @@ pub async fn draw_line_on_layer(
     color: [f32; 4],
     width: f32,
     state: State<'_, DrawingState>,
-) -> Result<(), String> {
-    info!("[Drawing API] 線描画開始");
-    debug!("[Drawing API] 線描画パラメータ: layer_id='{}', 開始点=({},{}), 終了点=({},{}), 色={:?}, 幅={}", 
-           layer_id, x1, y1, x2, y2, color, width);
-    
-    // パラメータ検証
-    if layer_id.is_empty() {
-        error!("[Drawing API] レイヤーIDが空です");
-        return Err("レイヤーIDが空です".to_string());
-    }
-    
-    if width <= 0.0 {
-        error!("[Drawing API] 無効な線幅: {}", width);
-        return Err("線幅は0より大きい値である必要があります".to_string());
-    }
-    
-    // レイヤーの存在確認
-    let (layer_width, layer_height) = {
-        let layers_guard = state.layers.lock().await;
-        match layers_guard.get(&layer_id) {
-            Some(dimensions) => {
-                debug!("[Drawing API] レイヤー確認OK: {} ({}x{})", layer_id, dimensions.0, dimensions.1);
-                dimensions.clone()
-            },
-            None => {
-                error!("[Drawing API] レイヤーが見つかりません: {}", layer_id);
-                return Err(format!("レイヤーが見つかりません: {}", layer_id));
-            }
+) -> Result<(), CommandError> {
+    gpu_boundary(&state, "draw_line_on_layer", async {
+        info!("[Drawing API] 線描画開始");
+        debug!("[Drawing API] 線描画パラメータ: layer_id='{}', 開始点=({},{}), 終了点=({},{}), 色={:?}, 幅={}", 
+               layer_id, x1, y1, x2, y2, color, width);
+        
+        // パラメータ検証
+        if layer_id.is_empty() {
+            error!("[Drawing API] レイヤーIDが空です");
+            return Err("レイヤーIDが空です".to_string());
         }
-    };
-    
-    // 線を描画
-    debug!("[Drawing API] 描画エンジンでの線描画処理開始");
-    {
-        let engine_guard = state.engine.lock().await;
-        match engine_guard.as_ref() {
-            Some(engine) => {
-                debug!("[Drawing API] 描画エンジン取得成功");
-                
-                // スクリーン座標を正規化座標に変換
-                debug!("[Drawing API] 座標変換開始");
-                let start_norm = engine.screen_to_normalized((x1, y1), (layer_width, layer_height));
-                let end_norm = engine.screen_to_normalized((x2, y2), (layer_width, layer_height));
-                debug!("[Drawing API] 座標変換完了: ({:.3},{:.3}) -> ({:.3},{:.3})", 
-                       start_norm.0, start_norm.1, end_norm.0, end_norm.1);
-                
-                // 線を描画
-                debug!("[Drawing API] draw_line_to_layer呼び出し");
-                match engine.draw_line_to_layer(&layer_id, start_norm, end_norm, color, width) {
-                    Ok(_) => {
-                        debug!("[Drawing API] draw_line_to_layer成功");
-                    },
-                    Err(e) => {
-                        error!("[Drawing API] draw_line_to_layerでエラー: {}", e);
-                        return Err(format!("線描画エラー: {}", e));
+        
+        // レイヤーの存在確認と、端点・色・線幅の検査（NaN・範囲外の端点は取り除かずに拒否する）
+        let layer_size = layer_size(&state, &layer_id).await?;
+        let endpoints = [(x1, y1), (x2, y2)].into_iter().map(|(x, y)| StrokeInputPoint::new(x, y, 1.0));
+        let input = sanitize_input(endpoints, color, width, layer_size, None)?;
+        if input.report.non_finite > 0 || input.report.out_of_bounds > 0 {
+            error!("[Drawing API] 無効な端点: ({}, {}) - ({}, {})", x1, y1, x2, y2);
+            return Err(format!("線の端点が無効です: ({}, {}) - ({}, {})", x1, y1, x2, y2));
+        }
+        let (color, width) = (input.color, input.width);
+        debug!("[Drawing API] レイヤー確認OK: {} ({}x{})", layer_id, layer_size.0, layer_size.1);
+        
+        // スナップ・透視補正が有効なら端点を補正
+        let ((x1, y1), (x2, y2)) = {
+            let guides_guard = state.guides.lock().await;
+            let adjusted = guides_guard.apply_to_points(&[(x1, y1), (x2, y2)]);
+            (adjusted[0], adjusted[1])
+        };
+        
+        // ディスクへ退避中のレイヤーは復帰させる
+        ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+        
+        // 線を描画
+        debug!("[Drawing API] 描画エンジンでの線描画処理開始");
+        {
+            let mut engine_guard = state.engine.lock().await;
+            match engine_guard.as_mut() {
+                Some(engine) => {
+                    debug!("[Drawing API] 描画エンジン取得成功");
+                    
+                    // 線を描画（座標・線幅はレイヤーのピクセル単位のまま渡す）
+                    debug!("[Drawing API] draw_line_to_layer呼び出し");
+                    match engine.draw_line_to_layer(&layer_id, (x1, y1), (x2, y2), color, width) {
+                        Ok(_) => {
+                            debug!("[Drawing API] draw_line_to_layer成功");
+                        },
+                        Err(e) => {
+                            error!("[Drawing API] draw_line_to_layerでエラー: {}", e);
+                            return Err(format!("線描画エラー: {}", e));
+                        }
                     }
+                },
+                None => {
+                    error!("[Drawing API] 描画エンジンが初期化されていません");
+                    return Err("描画エンジンが初期化されていません".to_string());
                 }
-            },
-            None => {
-                error!("[Drawing API] 描画エンジンが初期化されていません");
-                return Err("描画エンジンが初期化されていません".to_string());
             }
         }
-    }
-    
-    info!("[Drawing API] 線描画完了: {}", layer_id);
-    Ok(())
+        
+        state.record_operation(Operation::DrawLine {
+            layer_id: layer_id.clone(),
+            x1,
+            y1,
+            x2,
+            y2,
+            color,
+            width,
+        }).await;
+        
+        info!("[Drawing API] 線描画完了: {}", layer_id);
+        Ok(())
+    }).await
 }
 
 /// レイヤーにストロークを描画（筆圧対応）
@@ -256,6 +467,40 @@ pub struct StrokePoint {
     pub x: f32,
     pub y: f32,
     pub pressure: f32,
+    /// ストローク開始からの経過時間（ミリ秒。再生用）
+    #[serde(default)]
+    pub time_ms: Option<u32>,
+}
+
+impl StrokePoint {
+    pub(super) fn to_input(&self) -> StrokeInputPoint {
+        StrokeInputPoint { x: self.x, y: self.y, pressure: self.pressure, time_ms: self.time_ms }
+    }
+}
+
+/// レイヤーの大きさ（ストロークの点を受け付ける範囲に使う）
+pub(super) async fn layer_size(state: &DrawingState, layer_id: &str) -> Result<(u32, u32), String> {
+    state.layers.lock().await.get(layer_id).copied()
+        .ok_or_else(|| format!("レイヤーが見つかりません: {}", layer_id))
+}
+
+/// 届いたストロークの入力を `sanitize_stroke` で整え、取り除いた点があればログに残す
+pub(super) fn sanitize_input(
+    points: impl Iterator<Item = StrokeInputPoint>,
+    color: [f32; 4],
+    width: f32,
+    layer_size: (u32, u32),
+    previous: Option<StrokeInputPoint>,
+) -> Result<SanitizedStroke, String> {
+    let points: Vec<StrokeInputPoint> = points.collect();
+    let input = sanitize_stroke(&points, color, width, layer_size, previous).map_err(|e| {
+        warn!("[Drawing API] ストロークの入力を拒否: {}", e);
+        e.to_string()
+    })?;
+    if !input.report.is_clean() {
+        debug!("[Drawing API] ストロークの入力を整えました: {} 点を除外 ({:?})", input.report.dropped(), input.report);
+    }
+    Ok(input)
 }
 
 #[tauri::command]
@@ -263,48 +508,550 @@ pub async fn draw_stroke_on_layer(
     layer_id: String,
     points: Vec<StrokePoint>,
     color: [f32; 4],
+    device_id: Option<String>,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<(), CommandError> {
+    gpu_boundary(&state, "draw_stroke_on_layer", async {
+        debug!("[Drawing API] ストローク描画: {} ({} 点)", layer_id, points.len());
+        
+        if points.is_empty() {
+            return Err("ストロークの点が空です".to_string());
+        }
+        
+        // レイヤーの存在確認
+        let layer_size = layer_size(&state, &layer_id).await?;
+        
+        // NaN・範囲外・重複した点を取り除く
+        let input = sanitize_input(points.iter().map(StrokePoint::to_input), color, 2.0, layer_size, None)?;
+        
+        // ディスクへ退避中のレイヤーは復帰させる
+        ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+        
+        // スナップ・透視補正が有効なら各点を補正
+        let adjusted = {
+            let guides_guard = state.guides.lock().await;
+            guides_guard.apply_to_points(&input.points.iter().map(|p| (p.x, p.y)).collect::<Vec<_>>())
+        };
+        
+        // 筆圧カーブを適用（ブラシの筆圧処理より前に、校正済みの値として記録する）
+        let curve = *settings.get().await.tablet.curve_for(device_id.as_deref());
+        
+        // ベクターとして記録するストローク（筆圧で線幅調整）。見た目が変わらない点は間引いて記録する
+        let record = StrokeRecord {
+            points: input.points.iter().zip(adjusted)
+                .map(|(p, (x, y))| StrokePointRecord { x, y, pressure: curve.apply(p.pressure), time_ms: p.time_ms })
+                .collect(),
+            color: input.color,
+            base_width: input.width, // デフォルト線幅（ピクセル）
+        }.simplified();
+        
+        // ストロークを描画
+        {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+            
+            // レイヤーのピクセル座標のままストロークを作成
+            let stroke = record.to_draw_stroke();
+            
+            // ストロークを描画
+            engine.draw_stroke_to_layer(&layer_id, &stroke)
+                .map_err(|e| format!("ストローク描画エラー: {}", e))?;
+        }
+        
+        state.record_operation(Operation::DrawStroke {
+            layer_id: layer_id.clone(),
+            stroke: record,
+        }).await;
+        
+        info!("[Drawing API] ストローク描画完了: {}", layer_id);
+        Ok(())
+    }).await
+}
+
+/// ストロークの描画を開始（点は `extend_stroke` で追加し、`end_stroke` で確定する）
+///
+/// 描画中は追加された線分だけを三角形分割して被覆率に溜め、描き始める前のレイヤーにストローク全体を
+/// 合成し直す（色のアルファはストローク全体の不透明度で、追加の区切りでも継ぎ目が濃くならない）。
+/// 透視補正は確定時の終点から向きを決めるため、有効な間は `draw_stroke_on_layer` を使う。
+///
+/// `erase` を指定すると消しゴムになり、色のアルファが消す強さになる。消している途中はレイヤーを変えず
+/// `render_canvas_view` の表示だけに反映し、`end_stroke` でレイヤーに確定する（`cancel_stroke` で取り消せる）。
+#[tauri::command]
+pub async fn begin_stroke(
+    layer_id: String,
+    color: [f32; 4],
+    device_id: Option<String>,
+    erase: Option<bool>,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<(), CommandError> {
+    gpu_boundary(&state, "begin_stroke", async {
+        let erase = erase.unwrap_or(false);
+        debug!("[Drawing API] ストローク開始: {} (消しゴム: {})", layer_id, erase);
+        if !state.layers.lock().await.contains_key(&layer_id) {
+            return Err(format!("レイヤーが見つかりません: {}", layer_id));
+        }
+        if state.guides.lock().await.perspective.constrains_strokes() {
+            return Err("透視補正が有効な間は逐次描画できません".to_string());
+        }
+        // 色・線幅は開始時に検査し、点は `extend_stroke` で届くたびに検査する
+        let (color, base_width) = sanitize_style(color, 2.0).map_err(|e| e.to_string())?;
+        ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+        {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+            let result = if erase {
+                engine.begin_erase_stroke(&layer_id)
+            } else {
+                engine.begin_layer_stroke(&layer_id)
+            };
+            result.map_err(|e| format!("ストローク開始エラー: {}", e))?;
+        }
+
+        let curve = *settings.get().await.tablet.curve_for(device_id.as_deref());
+        let stroke = ActiveStroke {
+            record: StrokeRecord { points: Vec::new(), color, base_width },
+            curve,
+            tessellator: StrokeTessellator::new(),
+            erase,
+        };
+        if state.active_strokes.lock().await.insert(layer_id.clone(), stroke).is_some() {
+            warn!("[Drawing API] 終了していないストロークを破棄: {}", layer_id);
+        }
+        Ok(())
+    }).await
+}
+
+/// 描画中のストロークに点を追加し、新しい線分だけを描画
+///
+/// `timing` を渡すと、イベントから描画までの遅延をストロークごとに記録する。
+#[tauri::command]
+pub async fn extend_stroke(
+    layer_id: String,
+    points: Vec<StrokePoint>,
+    timing: Option<InputTiming>,
+    state: State<'_, DrawingState>,
+) -> Result<(), CommandError> {
+    gpu_boundary(&state, "extend_stroke", async {
+        extend_active_stroke(&state, &layer_id, &points, timing).await?;
+        Ok(())
+    }).await
+}
+
+/// 1回のポインター移動でまとめて届いた点（`getCoalescedEvents` の結果）を追加し、描き直した範囲を返す
+///
+/// `packed` は点ごとに x, y, 筆圧, ストローク開始からの経過時間（ミリ秒、負の値は記録しない）を並べたもの。
+/// 高速なストロークでも呼び出しはポインター移動1回につき1度で済む。
+#[tauri::command]
+pub async fn extend_stroke_packed(
+    layer_id: String,
+    packed: Vec<f32>,
+    timing: Option<InputTiming>,
+    state: State<'_, DrawingState>,
+) -> Result<Option<ContentBounds>, CommandError> {
+    gpu_boundary(&state, "extend_stroke_packed", async {
+        let points = unpack_stroke_points(&packed)?;
+        extend_active_stroke(&state, &layer_id, &points, timing).await
+    }).await
+}
+
+/// x, y, 筆圧, 経過時間を並べた配列を点に戻す
+fn unpack_stroke_points(packed: &[f32]) -> Result<Vec<StrokePoint>, String> {
+    if packed.len() % 4 != 0 {
+        return Err(format!("点の配列の長さは4の倍数である必要があります: {}", packed.len()));
+    }
+    Ok(packed.chunks_exact(4)
+        .map(|point| StrokePoint {
+            x: point[0],
+            y: point[1],
+            pressure: point[2],
+            time_ms: (point[3] >= 0.0).then(|| point[3] as u32),
+        })
+        .collect())
+}
+
+/// 描画中のストロークに点を追加して描画し、新しい線分が掛かった範囲を返す
+///
+/// 点は画面座標で受け取り、表示の変換（`set_view_transform`）でドキュメント座標に戻してから
+/// ガイドに吸着させる。表示を回転・反転していてもペンが触れた位置に描かれる。
+async fn extend_active_stroke(
+    state: &DrawingState,
+    layer_id: &str,
+    points: &[StrokePoint],
+    timing: Option<InputTiming>,
+) -> Result<Option<ContentBounds>, String> {
+    let received = std::time::Instant::now();
+    // ロック順序: layers → active_strokes（レイヤーの削除と同じ順）
+    let layer_size = layer_size(state, layer_id).await?;
+    let mut strokes_guard = state.active_strokes.lock().await;
+    let stroke = strokes_guard.get_mut(layer_id)
+        .ok_or(format!("描画中のストロークがありません: {}", layer_id))?;
+
+    {
+        let view = *state.view.lock().await;
+        let previous = stroke.record.points.last().map(|p| StrokeInputPoint::new(p.x, p.y, p.pressure));
+        let input = sanitize_input(
+            points.iter().map(|p| {
+                let (x, y) = view.screen_to_document((p.x, p.y));
+                StrokeInputPoint { x, y, ..p.to_input() }
+            }),
+            stroke.record.color,
+            stroke.record.base_width,
+            layer_size,
+            previous,
+        )?;
+        let guides_guard = state.guides.lock().await;
+        let curve = stroke.curve;
+        stroke.record.points.extend(input.points.iter().map(|p| {
+            let (x, y) = guides_guard.snap_point((p.x, p.y));
+            StrokePointRecord { x, y, pressure: curve.apply(p.pressure), time_ms: p.time_ms }
+        }));
+    }
+
+    let draw_stroke = stroke.record.to_draw_stroke();
+    let mesh = stroke.tessellator.next_mesh(&draw_stroke).with_flow(1.0);
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    engine.extend_layer_stroke(layer_id, &mesh, draw_stroke.color)
+        .map_err(|e| format!("ストローク描画エラー: {}", e))?;
+    let bounds = engine.layer_texture_size(layer_id).and_then(|size| mesh_bounds(&mesh, size));
+    let render_ms = received.elapsed().as_secs_f64() * 1000.0;
+    state.diagnostics.lock().await.record_stroke_frame(render_ms);
+    if let Some(timing) = timing {
+        state.latency.lock().await.record_batch(layer_id, timing, render_ms);
+    }
+    Ok(bounds)
+}
+
+/// 表示側がストロークの描画結果を画面に表示した時刻を報告（入力から表示までの遅延に使う）
+///
+/// 時刻はどちらも `extend_stroke` の `timing` と同じ表示側の時計（ミリ秒）。
+#[tauri::command]
+pub async fn report_stroke_presented(
+    layer_id: String,
+    event_time_ms: f64,
+    presented_time_ms: f64,
     state: State<'_, DrawingState>,
 ) -> Result<(), String> {
-    debug!("[Drawing API] ストローク描画: {} ({} 点)", layer_id, points.len());
-    
-    if points.is_empty() {
-        return Err("ストロークの点が空です".to_string());
+    let total_ms = presented_time_ms - event_time_ms;
+    trace!("[Drawing API] ストロークの表示: {} ({:.1}ms)", layer_id, total_ms);
+    state.latency.lock().await.record_present(&layer_id, total_ms);
+    Ok(())
+}
+
+/// 描画中のストロークを確定し、間引いた点で履歴に記録（記録した点の数を返す）
+#[tauri::command]
+pub async fn end_stroke(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<usize, CommandError> {
+    gpu_boundary(&state, "end_stroke", async {
+        let stroke = state.active_strokes.lock().await.remove(&layer_id)
+            .ok_or(format!("描画中のストロークがありません: {}", layer_id))?;
+        state.latency.lock().await.finish_stroke(&layer_id);
+        if let Some(engine) = state.engine.lock().await.as_mut() {
+            engine.end_layer_stroke(&layer_id)
+                .map_err(|e| format!("ストローク確定エラー: {}", e))?;
+        }
+        if stroke.record.points.is_empty() {
+            return Ok(0);
+        }
+
+        let input_points = stroke.record.points.len();
+        let record = stroke.record.simplified();
+        let recorded_points = record.points.len();
+        let operation = if stroke.erase {
+            Operation::EraseStroke { layer_id: layer_id.clone(), stroke: record }
+        } else {
+            Operation::DrawStroke { layer_id: layer_id.clone(), stroke: record }
+        };
+        state.record_operation(operation).await;
+
+        info!("[Drawing API] ストローク確定: {} ({} → {} 点)", layer_id, input_points, recorded_points);
+        Ok(recorded_points)
+    }).await
+}
+
+/// 描画中のストロークを取り消し、レイヤーを描き始める前の内容に戻す（履歴には記録しない）
+#[tauri::command]
+pub async fn cancel_stroke(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<(), String> {
+    state.active_strokes.lock().await.remove(&layer_id)
+        .ok_or(format!("描画中のストロークがありません: {}", layer_id))?;
+    state.latency.lock().await.finish_stroke(&layer_id);
+    if let Some(engine) = state.engine.lock().await.as_mut() {
+        engine.cancel_layer_stroke(&layer_id)
+            .map_err(|e| format!("ストローク取り消しエラー: {}", e))?;
     }
-    
-    // レイヤーの存在確認
-    let (layer_width, layer_height) = {
-        let layers_guard = state.layers.lock().await;
-        layers_guard.get(&layer_id)
-            .ok_or(format!("レイヤーが見つかりません: {}", layer_id))?
-            .clone()
-    };
-    
-    // ストロークを描画
+    info!("[Drawing API] ストロークを取り消し: {}", layer_id);
+    Ok(())
+}
+
+/// 描画中のすべてのストロークを取り消し、取り消したレイヤーのIDを返す（履歴には記録しない）
+///
+/// Esc キーやパームリジェクションで、どのレイヤーに描いているかを問わず描画を中断するときに使う。
+#[tauri::command]
+pub async fn cancel_all_strokes(
+    state: State<'_, DrawingState>,
+) -> Result<Vec<String>, String> {
+    let layer_ids: Vec<String> = state.active_strokes.lock().await.drain().map(|(layer_id, _)| layer_id).collect();
     {
-        let engine_guard = state.engine.lock().await;
-        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        let mut latency = state.latency.lock().await;
+        for layer_id in &layer_ids {
+            latency.finish_stroke(layer_id);
+        }
+    }
+    let cancelled = match state.engine.lock().await.as_mut() {
+        Some(engine) => engine.cancel_all_layer_strokes()
+            .map_err(|e| format!("ストローク取り消しエラー: {}", e))?,
+        None => Vec::new(),
+    };
+    if !cancelled.is_empty() {
+        info!("[Drawing API] 描画中のストロークをすべて取り消し: {:?}", cancelled);
+    }
+    Ok(cancelled)
+}
+
+/// レイヤーにアンチエイリアスなしの 1px の線を描画（ドット絵用のピクセルブラシ）
+///
+/// 座標はレイヤーのピクセル座標で、小数部は切り捨てられる。
+/// `pixel_perfect` を有効にすると斜めの線に出る L 字の角を取り除く。
+#[tauri::command]
+pub async fn draw_pixel_stroke_on_layer(
+    layer_id: String,
+    points: Vec<(f32, f32)>,
+    color: [f32; 4],
+    pixel_perfect: Option<bool>,
+    state: State<'_, DrawingState>,
+) -> Result<usize, CommandError> {
+    gpu_boundary(&state, "draw_pixel_stroke_on_layer", async {
+        debug!("[Drawing API] ピクセルストローク描画: {} ({} 点)", layer_id, points.len());
+        
+        if points.is_empty() {
+            return Err("ストロークの点が空です".to_string());
+        }
+        
+        // レイヤーの存在確認
+        let layer_size = layer_size(&state, &layer_id).await?;
+        
+        // NaN・範囲外・重複した点を取り除く（範囲外の点で Bresenham の経路が伸びすぎないようにする）
+        let input = sanitize_input(points.iter().map(|&(x, y)| StrokeInputPoint::new(x, y, 1.0)), color, 1.0, layer_size, None)?;
+        let points: Vec<(f32, f32)> = input.points.iter().map(|p| (p.x, p.y)).collect();
+        let color = input.color;
         
-        // スクリーン座標を正規化座標に変換してVertex2Dを作成
-        let vertex_points: Vec<Vertex2D> = points.iter().map(|p| {
-            let norm_pos = engine.screen_to_normalized((p.x, p.y), (layer_width, layer_height));
-            Vertex2D::new(norm_pos.0, norm_pos.1, color, 2.0 * p.pressure) // 筆圧で線幅調整
-        }).collect();
+        // ディスクへ退避中のレイヤーは復帰させる
+        ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+        
+        // スナップ・透視補正が有効なら各点を補正
+        let points = state.guides.lock().await.apply_to_points(&points);
+        let pixel_perfect = pixel_perfect.unwrap_or(true);
+        
+        let written = {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+            engine.draw_pixel_stroke_to_layer(&layer_id, &points, color, pixel_perfect)
+                .map_err(|e| format!("ピクセルストローク描画エラー: {}", e))?
+        };
         
-        // ストロークを作成
-        let stroke = DrawStroke {
-            points: vertex_points,
+        state.record_operation(Operation::DrawPixelStroke {
+            layer_id: layer_id.clone(),
+            points,
             color,
-            base_width: 2.0, // デフォルト線幅
-            is_closed: false, // 通常のストロークは閉じない
+            pixel_perfect,
+        }).await;
+        
+        info!("[Drawing API] ピクセルストローク描画完了: {} ({} px)", layer_id, written);
+        Ok(written)
+    }).await
+}
+
+/// レイヤーの画像を最近傍補間で整数倍に拡大して取得（ドット絵の拡大表示用。RGBA）
+#[tauri::command]
+pub async fn get_layer_image_zoomed(
+    layer_id: String,
+    zoom: u32,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<u8>, CommandError> {
+    gpu_boundary(&state, "get_layer_image_zoomed", async {
+        debug!("[Drawing API] 拡大画像取得: {} x{}", layer_id, zoom);
+        
+        if !(1..=MAX_PIXEL_ZOOM).contains(&zoom) {
+            return Err(format!("拡大率は 1〜{} で指定してください: {}", MAX_PIXEL_ZOOM, zoom));
+        }
+        if !state.layers.lock().await.contains_key(&layer_id) {
+            return Err(format!("レイヤーが見つかりません: {}", layer_id));
+        }
+        
+        // ディスクへ退避中のレイヤーは復帰させる
+        ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+        
+        let image = {
+            let engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+            engine.get_layer_image(&layer_id).await
+                .map_err(|e| format!("画像データ取得エラー: {}", e))?
         };
         
-        // ストロークを描画
-        engine.draw_stroke_to_layer(&layer_id, &stroke)
-            .map_err(|e| format!("ストローク描画エラー: {}", e))?;
+        Ok(scale_nearest(&image, zoom).into_raw())
+    }).await
+}
+
+/// オーバーレイ付きのキャンバス表示画像
+#[derive(Serialize)]
+pub struct CanvasView {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA
+    pub data: Vec<u8>,
+}
+
+/// レイヤーを合成し、設定のオーバーレイ（市松模様・ピクセルグリッド・枠線）を重ねた表示用の画像を取得
+///
+/// `layers` を省略すると全レイヤーを通常合成で重ねる。クイックマスク中は選択されていない部分に色を重ねる。
+/// ガイド設定のセーフエリア・画面比の帯も拡大後の解像度で重ねる（書き出しには含まれない）。
+/// `set_perf_overlay` で有効にすると、FPS・フレーム時間・書き込まれたタイル・メモリ使用量も重ねる。
+#[tauri::command]
+pub async fn render_canvas_view(
+    zoom: u32,
+    layers: Option<Vec<Layer>>,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<CanvasView, CommandError> {
+    gpu_boundary(&state, "render_canvas_view", async {
+        debug!("[Drawing API] キャンバス表示画像の生成: x{}", zoom);
+        
+        if !(1..=MAX_PIXEL_ZOOM).contains(&zoom) {
+            return Err(format!("拡大率は 1〜{} で指定してください: {}", MAX_PIXEL_ZOOM, zoom));
+        }
+        
+        let started = std::time::Instant::now();
+        let settings = settings.get().await;
+        let overlay = settings.overlay;
+        let (raster_layers, width, height) = collect_preview_layers(&state, layers).await?;
+        let mut composite = flatten_layers(&raster_layers, width, height);
+        if selection_state.is_quick_mask().await {
+            let (mask_layers, ..) = collect_preview_layers(&state, Some(vec![texture_layer(QUICK_MASK_TEXTURE_ID)])).await?;
+            if let Some(mask) = mask_layers.into_iter().next() {
+                render_quick_mask(&mut composite, &mask.image, overlay.quick_mask_color);
+            }
+        }
+        let mut view = render_view(&composite, zoom, &overlay);
+        let guides = state.guides.lock().await.clone();
+        if guides.visible && guides.safe_area.is_active() {
+            let border = overlay.border_size();
+            guides.safe_area.draw(&mut view, (border, border, width * zoom, height * zoom));
+        }
+        if overlay.latency_graph {
+            render_latency_graph(&mut view, &state.latency.lock().await.recent());
+        }
+        let frame_ms = started.elapsed().as_secs_f64() * 1000.0;
+        state.diagnostics.lock().await.record_view_frame(frame_ms);
+        draw_perf_overlay(&state, &mut view, zoom, overlay.border_size(), settings.memory.budget_bytes, frame_ms).await;
+        
+        Ok(CanvasView {
+            width: view.width(),
+            height: view.height(),
+            data: view.into_raw(),
+        })
+    }).await
+}
+
+/// 性能オーバーレイが有効なら、このフレームを記録して表示画像に重ねる
+async fn draw_perf_overlay(state: &DrawingState, view: &mut image::RgbaImage, zoom: u32, origin: u32, budget_bytes: u64, frame_ms: f64) {
+    let mut perf = state.perf_overlay.lock().await;
+    if !perf.is_enabled() {
+        return;
     }
+    perf.record_frame(std::time::Instant::now(), frame_ms);
+    let layer_ids: Vec<String> = state.layers.lock().await.keys().cloned().collect();
+    let dirty = match state.engine.lock().await.as_ref() {
+        Some(engine) => {
+            let generations = engine.tile_generations();
+            let since = perf.swap_generation(generations.generation());
+            generations.changed_since(layer_ids.iter().map(String::as_str), since)
+        }
+        None => Vec::new(),
+    };
+    let memory = measure_memory_usage(state).await;
+    render_perf_overlay(view, &perf, &dirty, origin, zoom, &memory, budget_bytes);
+}
+
+/// 2つのキャンバス（同じプロジェクトの2つのフレームなど）を並べる・重ねた比較表示の画像を取得
+///
+/// `primary` が基準、`secondary` が比較対象のレイヤーで、`primary` を省略すると全レイヤーを使う。
+/// レイヤーのない比較対象は基準と同じ大きさの透明な画像になる。オーバーレイはまとめた画像全体に重ねる。
+#[tauri::command]
+pub async fn render_comparison_view(
+    zoom: u32,
+    primary: Option<Vec<Layer>>,
+    secondary: Vec<Layer>,
+    layout: CompareLayout,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<CanvasView, String> {
+    debug!("[Drawing API] 比較表示画像の生成: x{} {:?}", zoom, layout);
     
-    info!("[Drawing API] ストローク描画完了: {}", layer_id);
-    Ok(())
+    if !(1..=MAX_PIXEL_ZOOM).contains(&zoom) {
+        return Err(format!("拡大率は 1〜{} で指定してください: {}", MAX_PIXEL_ZOOM, zoom));
+    }
+    layout.validate()?;
+    
+    let overlay = settings.get().await.overlay;
+    let (raster_layers, width, height) = collect_preview_layers(&state, primary).await?;
+    let primary_image = flatten_layers(&raster_layers, width, height);
+    let secondary_image = if secondary.is_empty() {
+        image::RgbaImage::new(width, height)
+    } else {
+        let (raster_layers, width, height) = collect_preview_layers(&state, Some(secondary)).await?;
+        flatten_layers(&raster_layers, width, height)
+    };
+    let view = render_view(&compose_comparison(&primary_image, &secondary_image, layout), zoom, &overlay);
+    
+    Ok(CanvasView {
+        width: view.width(),
+        height: view.height(),
+        data: view.into_raw(),
+    })
+}
+
+/// 2つのレイヤーの差分
+#[derive(Serialize)]
+pub struct LayerComparison {
+    pub width: u32,
+    pub height: u32,
+    /// 差分のヒートマップ（RGBA。変化のない画素は透明）
+    pub data: Vec<u8>,
+    #[serde(flatten)]
+    pub stats: DifferenceStats,
+}
+
+/// 2つのレイヤー（別々のセルや、フィルターをかける前に複製したレイヤーなど）の差分を取得
+///
+/// 画素ごとの差が `threshold`（省略時は 0）を超えた画素を変化とみなし、ヒートマップと変化した画素の割合を返す。
+#[tauri::command]
+pub async fn compare_layers(
+    a: String,
+    b: String,
+    threshold: Option<u8>,
+    state: State<'_, DrawingState>,
+) -> Result<LayerComparison, String> {
+    debug!("[Drawing API] レイヤーの差分: {} / {}", a, b);
+    
+    let image_a = read_layer_image(&state, &a).await?;
+    let image_b = read_layer_image(&state, &b).await?;
+    let (heatmap, stats) = difference_heatmap(&image_a, &image_b, threshold.unwrap_or(0));
+    debug!("[Drawing API] 変化した画素: {} / {} ({:.2}%)", stats.changed_pixels, stats.total_pixels, stats.changed_percent);
+    
+    Ok(LayerComparison {
+        width: heatmap.width(),
+        height: heatmap.height(),
+        data: heatmap.into_raw(),
+        stats,
+    })
 }
 
 /// レイヤーの画像データを取得
@@ -312,8 +1059,42 @@ pub async fn draw_stroke_on_layer(
 pub async fn get_layer_image_data(
     layer_id: String,
     state: State<'_, DrawingState>,
-) -> Result<Vec<u8>, String> {
-    debug!("[Drawing API] レイヤー画像データ取得: {}", layer_id);
+) -> Result<Vec<u8>, CommandError> {
+    gpu_boundary(&state, "get_layer_image_data", async {
+        debug!("[Drawing API] レイヤー画像データ取得: {}", layer_id);
+        
+        // レイヤーの存在確認
+        {
+            let layers_guard = state.layers.lock().await;
+            if !layers_guard.contains_key(&layer_id) {
+                return Err(format!("レイヤーが見つかりません: {}", layer_id));
+            }
+        }
+        
+        // ディスクへ退避中のレイヤーは復帰させる
+        ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+        
+        // 画像データを取得
+        let image_data = {
+            let engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+            
+            engine.get_layer_texture_data(&layer_id).await
+                .map_err(|e| format!("画像データ取得エラー: {}", e))?
+        };
+        
+        info!("[Drawing API] レイヤー画像データ取得完了: {} ({} バイト)", layer_id, image_data.len());
+        Ok(image_data)
+    }).await
+}
+
+/// レイヤーをクリア
+#[tauri::command]
+pub async fn clear_layer(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<(), String> {
+    debug!("[Drawing API] レイヤークリア: {}", layer_id);
     
     // レイヤーの存在確認
     {
@@ -323,26 +1104,32 @@ pub async fn get_layer_image_data(
         }
     }
     
-    // 画像データを取得
-    let image_data = {
-        let engine_guard = state.engine.lock().await;
-        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+    // ディスクへ退避中のレイヤーは復帰させる
+    ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+    
+    // レイヤーをクリア（透明）
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
         
-        engine.get_layer_texture_data(&layer_id).await
-            .map_err(|e| format!("画像データ取得エラー: {}", e))?
-    };
+        engine.clear_layer_texture(&layer_id, Some(wgpu::Color::TRANSPARENT))
+            .map_err(|e| format!("レイヤークリアエラー: {}", e))?;
+    }
     
-    info!("[Drawing API] レイヤー画像データ取得完了: {} ({} バイト)", layer_id, image_data.len());
-    Ok(image_data)
+    state.record_operation(Operation::ClearLayer { layer_id: layer_id.clone() }).await;
+    
+    info!("[Drawing API] レイヤークリア完了: {}", layer_id);
+    Ok(())
 }
 
-/// レイヤーをクリア
+/// レイヤーを単色で塗りつぶし
 #[tauri::command]
-pub async fn clear_layer(
+pub async fn fill_layer(
     layer_id: String,
+    color: [f32; 4],
     state: State<'_, DrawingState>,
 ) -> Result<(), String> {
-    debug!("[Drawing API] レイヤークリア: {}", layer_id);
+    debug!("[Drawing API] レイヤー塗りつぶし: {} 色={:?}", layer_id, color);
     
     // レイヤーの存在確認
     {
@@ -352,16 +1139,27 @@ pub async fn clear_layer(
         }
     }
     
-    // レイヤーをクリア（透明）
+    // ディスクへ退避中のレイヤーは復帰させる
+    ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+    
+    let fill_color = wgpu::Color {
+        r: color[0] as f64,
+        g: color[1] as f64,
+        b: color[2] as f64,
+        a: color[3] as f64,
+    };
+    
     {
         let mut engine_guard = state.engine.lock().await;
         let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
         
-        engine.clear_layer_texture(&layer_id, Some(wgpu::Color::TRANSPARENT))
-            .map_err(|e| format!("レイヤークリアエラー: {}", e))?;
+        engine.clear_layer_texture(&layer_id, Some(fill_color))
+            .map_err(|e| format!("レイヤー塗りつぶしエラー: {}", e))?;
     }
     
-    info!("[Drawing API] レイヤークリア完了: {}", layer_id);
+    state.record_operation(Operation::FillLayer { layer_id: layer_id.clone(), color }).await;
+    
+    info!("[Drawing API] レイヤー塗りつぶし完了: {}", layer_id);
     Ok(())
 }
 
@@ -373,11 +1171,14 @@ pub async fn remove_layer(
 ) -> Result<(), String> {
     debug!("[Drawing API] レイヤー削除: {}", layer_id);
     
+    // 退避中のレイヤーは復帰させずに退避内容を破棄
+    let was_paged = state.pager.lock().await.discard(&layer_id);
+    
     // レイヤーテクスチャを削除
     let removed = {
         let mut engine_guard = state.engine.lock().await;
         let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
-        engine.remove_layer_texture(&layer_id)
+        engine.remove_layer_texture(&layer_id) || was_paged
     };
     
     if removed {
@@ -386,6 +1187,15 @@ pub async fn remove_layer(
             let mut layers_guard = state.layers.lock().await;
             layers_guard.remove(&layer_id);
         }
+        state.active_strokes.lock().await.remove(&layer_id);
+        {
+            let mut lineart_guard = state.lineart_layer.lock().await;
+            if lineart_guard.as_deref() == Some(layer_id.as_str()) {
+                *lineart_guard = None;
+            }
+        }
+        
+        state.record_operation(Operation::RemoveLayer { layer_id: layer_id.clone() }).await;
         
         info!("[Drawing API] レイヤー削除完了: {}", layer_id);
         Ok(())
@@ -394,6 +1204,82 @@ pub async fn remove_layer(
     }
 }
 
+/// キャンバス変換の結果（変換後のキャンバスサイズ）
+#[derive(Serialize)]
+pub struct CanvasTransformResult {
+    pub width: u32,
+    pub height: u32,
+    pub layers_transformed: usize,
+}
+
+/// キャンバスを90度回転（表示の回転とは異なり、全レイヤーのピクセルを並べ替える）
+#[tauri::command]
+pub async fn rotate_canvas_90(
+    clockwise: Option<bool>,
+    canvas_width: u32,
+    canvas_height: u32,
+    state: State<'_, DrawingState>,
+) -> Result<CanvasTransformResult, String> {
+    let transform = if clockwise.unwrap_or(true) {
+        CanvasTransform::Rotate90Clockwise
+    } else {
+        CanvasTransform::Rotate90CounterClockwise
+    };
+    transform_canvas(transform, (canvas_width, canvas_height), &state).await
+}
+
+/// キャンバスを180度回転
+#[tauri::command]
+pub async fn rotate_canvas_180(
+    canvas_width: u32,
+    canvas_height: u32,
+    state: State<'_, DrawingState>,
+) -> Result<CanvasTransformResult, String> {
+    transform_canvas(CanvasTransform::Rotate180, (canvas_width, canvas_height), &state).await
+}
+
+/// キャンバスを左右反転
+#[tauri::command]
+pub async fn flip_canvas_horizontal(
+    canvas_width: u32,
+    canvas_height: u32,
+    state: State<'_, DrawingState>,
+) -> Result<CanvasTransformResult, String> {
+    transform_canvas(CanvasTransform::FlipHorizontal, (canvas_width, canvas_height), &state).await
+}
+
+async fn transform_canvas(
+    transform: CanvasTransform,
+    canvas_size: (u32, u32),
+    state: &DrawingState,
+) -> Result<CanvasTransformResult, String> {
+    debug!("[Drawing API] キャンバス変換: {:?}", transform);
+
+    ensure_resident(state, None).await?;
+
+    let operation = Operation::TransformCanvas { transform };
+    let layers_transformed = {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        operation.apply(engine, &mut layers_guard).map_err(|e| {
+            error!("[Drawing API] キャンバス変換失敗: {}", e);
+            format!("キャンバス変換エラー: {}", e)
+        })?;
+        layers_guard.len()
+    };
+
+    state.record_operation(operation).await;
+
+    let (width, height) = transform.output_size(canvas_size);
+    info!("[Drawing API] キャンバス変換完了: {:?} -> {}x{} ({} レイヤー)", transform, width, height, layers_transformed);
+    Ok(CanvasTransformResult {
+        width,
+        height,
+        layers_transformed,
+    })
+}
+
 /// 描画エンジンの統計情報を取得
 #[derive(Serialize)]
 pub struct DrawingStats {
@@ -402,6 +1288,8 @@ pub struct DrawingStats {
     pub memory_limit: u64,
     pub active_textures: usize,
     pub total_textures: usize,
+    /// 最近のストロークの入力から表示までの遅延（古い順）
+    pub stroke_latency: Vec<StrokeLatency>,
 }
 
 #[tauri::command]
@@ -425,6 +1313,7 @@ pub async fn get_drawing_stats(
         memory_limit,
         active_textures,
         total_textures,
+        stroke_latency: state.latency.lock().await.recent(),
     })
 }
 
@@ -445,6 +1334,130 @@ pub async fn cleanup_textures(
     Ok("テクスチャクリーンアップが完了しました".to_string())
 }
 
+/// テクスチャプールを整理し、使われそうにないテクスチャを解放（長く使ったセッションのメンテナンス）
+///
+/// アイドル時のメンテナンス（`start_idle_maintenance`）でも描画が途切れたときに自動で行う。
+#[tauri::command]
+pub async fn defragment_textures(
+    options: Option<DefragmentOptions>,
+    state: State<'_, DrawingState>,
+) -> Result<DefragmentReport, String> {
+    let options = options.unwrap_or_default();
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    let report = engine.defragment(&options);
+    info!("[Drawing API] テクスチャプールを整理: {} 枚解放 ({} bytes), タイル {} 枚",
+          report.pool.released_textures, report.pool.freed_bytes + report.scratch_freed_bytes, report.compacted_tiles);
+    Ok(report)
+}
+
+/// GPUデバイスの情報とキャンバスサイズの上限を取得
+#[tauri::command]
+pub async fn get_device_capabilities(
+    state: State<'_, DrawingState>,
+) -> Result<DeviceCapabilities, String> {
+    let engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+    engine.device_capabilities().ok_or_else(|| "GPUデバイスが初期化されていません".to_string())
+}
+
+/// キャンバスの最大辺を設定（省略時はデバイスの上限まで）
+///
+/// デバイスの上限を超える値を指定してもデバイスの上限に制限される。
+/// 既存のレイヤーには影響しない。
+#[tauri::command]
+pub async fn set_max_canvas_size(
+    max_dimension: Option<u32>,
+    state: State<'_, DrawingState>,
+) -> Result<DeviceCapabilities, String> {
+    if max_dimension == Some(0) {
+        return Err("最大辺は1以上である必要があります".to_string());
+    }
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    engine.set_max_canvas_dimension(max_dimension);
+    engine.device_capabilities().ok_or_else(|| "GPUデバイスが初期化されていません".to_string())
+}
+
+/// キャンバスの描画品質の設定と、デバイスで実際に使える値を取得
+#[tauri::command]
+pub async fn get_canvas_render_settings(
+    state: State<'_, DrawingState>,
+) -> Result<CanvasRenderStatus, String> {
+    let engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+    Ok(engine.render_status())
+}
+
+/// キャンバスの描画品質を設定（プロジェクト読み込み時など）
+///
+/// デバイスが対応していないサンプル数は対応する中で最も近い少ない数に下げる。
+/// sRGB での合成に対応していないデバイスで `srgb` を指定するとエラーになる。
+/// 以降に描くストロークと、操作ログからの再描画に適用される（描画済みの内容は変わらない）。
+#[tauri::command]
+pub async fn set_canvas_render_settings(
+    settings: CanvasRenderSettings,
+    state: State<'_, DrawingState>,
+) -> Result<CanvasRenderStatus, String> {
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    engine.set_msaa_mode(settings.msaa).map_err(|e| e.to_string())?;
+    engine.set_blend_space(settings.blend_space).map_err(|e| e.to_string())?;
+    Ok(engine.render_status())
+}
+
+/// 合成の色空間の比較画像の書き出し結果
+#[derive(Serialize)]
+pub struct BlendComparisonResult {
+    pub files: Vec<String>,
+    /// 画素ごとの RGB の差の平均（0〜255）
+    pub mean_difference: f64,
+}
+
+/// 同じ図柄を線形・sRGB の両方で合成した画像を書き出す（色の確認用）
+///
+/// `directory` に `blend_linear.png` と `blend_srgb.png` を保存する。キャンバスの内容は変わらない。
+#[tauri::command]
+pub async fn export_blend_comparison(
+    directory: String,
+    width: Option<u32>,
+    height: Option<u32>,
+    state: State<'_, DrawingState>,
+) -> Result<BlendComparisonResult, String> {
+    let (width, height) = (width.unwrap_or(512), height.unwrap_or(256));
+    info!("[Drawing API] 合成の比較画像を書き出し: {} ({}x{})", directory, width, height);
+
+    let images = {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        engine.render_blend_comparison(width, height).await
+            .map_err(|e| format!("比較画像の描画エラー: {}", e))?
+    };
+
+    let dir = std::path::PathBuf::from(directory);
+    std::fs::create_dir_all(&dir).map_err(|e| format!("ディレクトリを作成できません: {}", e))?;
+    let mut files = Vec::new();
+    for (blend_space, image) in &images {
+        let path = dir.join(format!("blend_{}.png", blend_space.name()));
+        image.save(&path).map_err(|e| {
+            error!("[Drawing API] PNG書き出し失敗: {} - {}", path.display(), e);
+            format!("PNG書き出しエラー: {}", e)
+        })?;
+        files.push(path.to_string_lossy().to_string());
+    }
+
+    let [(_, linear), (_, srgb)] = images.as_slice() else {
+        return Err("比較画像の数が不正です".to_string());
+    };
+    let total: u64 = linear.pixels().zip(srgb.pixels())
+        .map(|(a, b)| (0..3).map(|i| a[i].abs_diff(b[i]) as u64).sum::<u64>())
+        .sum();
+    let mean_difference = total as f64 / (width as f64 * height as f64 * 3.0);
+    debug!("[Drawing API] 合成の比較: 平均の差 {:.2}", mean_difference);
+
+    Ok(BlendComparisonResult { files, mean_difference })
+}
+
 /// デバッグ用：描画エンジンの詳細状態を取得
 #[derive(Serialize)]
 pub struct DetailedEngineState {
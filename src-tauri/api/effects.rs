@@ -0,0 +1,97 @@
+use crate::animation::Layer;
+use crate::formats::{apply_effects, LayerEffect};
+use crate::history::Operation;
+use super::drawing::DrawingState;
+use super::formats::collect_raster_layers;
+use super::paging::ensure_resident;
+use log::{info, debug};
+use tauri::State;
+
+fn effect_at(layer: &mut Layer, index: usize) -> Result<&mut LayerEffect, String> {
+    let count = layer.effects.len();
+    layer.effects.get_mut(index)
+        .ok_or_else(|| format!("レイヤー効果が見つかりません: {} ({} 個中)", index, count))
+}
+
+/// レイヤー効果をまとめて置き換え、更新したレイヤーを返す
+#[tauri::command]
+pub async fn set_layer_effects(mut layer: Layer, effects: Vec<LayerEffect>) -> Result<Layer, String> {
+    for effect in &effects {
+        effect.validate().map_err(|e| e.to_string())?;
+    }
+    layer.effects = effects;
+    debug!("[Effect API] レイヤー効果を設定: {} ({} 個)", layer.id, layer.effects.len());
+    Ok(layer)
+}
+
+/// レイヤー効果を一番上に追加し、更新したレイヤーを返す
+#[tauri::command]
+pub async fn add_layer_effect(mut layer: Layer, effect: LayerEffect) -> Result<Layer, String> {
+    effect.validate().map_err(|e| e.to_string())?;
+    layer.effects.push(effect);
+    debug!("[Effect API] レイヤー効果を追加: {} ({} 個)", layer.id, layer.effects.len());
+    Ok(layer)
+}
+
+/// `index` 番目のレイヤー効果のパラメータを変更し、更新したレイヤーを返す
+#[tauri::command]
+pub async fn update_layer_effect(mut layer: Layer, index: usize, effect: LayerEffect) -> Result<Layer, String> {
+    effect.validate().map_err(|e| e.to_string())?;
+    *effect_at(&mut layer, index)? = effect;
+    Ok(layer)
+}
+
+/// `index` 番目のレイヤー効果の有効・無効を切り替え、更新したレイヤーを返す
+#[tauri::command]
+pub async fn set_layer_effect_enabled(mut layer: Layer, index: usize, enabled: bool) -> Result<Layer, String> {
+    effect_at(&mut layer, index)?.enabled = enabled;
+    Ok(layer)
+}
+
+/// `index` 番目のレイヤー効果を削除し、更新したレイヤーを返す
+#[tauri::command]
+pub async fn remove_layer_effect(mut layer: Layer, index: usize) -> Result<Layer, String> {
+    effect_at(&mut layer, index)?;
+    layer.effects.remove(index);
+    Ok(layer)
+}
+
+/// 有効なレイヤー効果をレイヤーの画像に焼き込み、効果を外したレイヤーを返す
+///
+/// レイヤーの範囲からはみ出す部分は切り捨てる。焼き込みは画像の貼り付けとして履歴に記録する。
+#[tauri::command]
+pub async fn rasterize_layer_effects(mut layer: Layer, state: State<'_, DrawingState>) -> Result<Layer, String> {
+    if !layer.effects.iter().any(|effect| effect.enabled) {
+        layer.effects.clear();
+        return Ok(layer);
+    }
+    ensure_resident(&state, Some(std::slice::from_ref(&layer.id))).await?;
+    // マスクは焼き込まず、合成時に引き続き掛ける
+    let source = Layer { visible: true, opacity: 1.0, mask: None, ..layer.clone() };
+    let (mut raster_layers, ..) = collect_raster_layers(&state, Some(vec![source])).await?;
+    let raster = raster_layers.pop().ok_or("画像データ取得エラー: 読み出し結果が不足しています")?;
+
+    let (width, height) = raster.image.dimensions();
+    let baked = tokio::task::spawn_blocking(move || {
+        apply_effects(&raster).map(|applied| {
+            let (x, y) = ((-applied.offset.0) as u32, (-applied.offset.1) as u32);
+            image::imageops::crop_imm(&applied.image, x, y, width, height).to_image()
+        })
+    })
+    .await
+    .map_err(|e| format!("焼き込みタスクエラー: {}", e))?;
+
+    if let Some(image) = baked {
+        let operation = Operation::paste_image(&layer.id, 0, 0, &image)?;
+        {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+            operation.apply(engine, &mut *state.layers.lock().await)?;
+        }
+        state.record_operation(operation).await;
+    }
+
+    info!("[Effect API] レイヤー効果を焼き込み: {} ({} 個)", layer.id, layer.effects.len());
+    layer.effects.clear();
+    Ok(layer)
+}
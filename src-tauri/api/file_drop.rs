@@ -0,0 +1,128 @@
+use crate::animation::Project;
+use crate::formats::FileKind;
+use crate::jobs::JobError;
+use super::drawing::DrawingState;
+use super::formats::{run_import_image_layer, run_import_project, run_load_project, ImportedLayer};
+use super::jobs::{progress_listener, to_job_result, JobState};
+use log::{info, debug, warn};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// 読み込めなかったファイル
+#[derive(Debug, Clone, Serialize)]
+pub struct SkippedFile {
+    pub path: String,
+    pub reason: String,
+}
+
+/// ドロップされたファイルの読み込み結果（ジョブの結果）
+#[derive(Serialize)]
+pub struct FileDropResult {
+    /// 開いたプロジェクト（プロジェクトファイルがなければ None）
+    pub project: Option<Project>,
+    pub layers: Vec<ImportedLayer>,
+    pub skipped: Vec<SkippedFile>,
+}
+
+/// ドロップされたファイルの振り分け
+struct DropPlan {
+    /// 開くプロジェクト（.kine / PSD / OpenRaster）
+    project: Option<(FileKind, String)>,
+    /// 新しいレイヤーとして読み込む画像
+    images: Vec<String>,
+    skipped: Vec<SkippedFile>,
+}
+
+impl DropPlan {
+    /// プロジェクトは先頭の1つだけを開き、画像はその後でレイヤーとして追加する
+    fn new(paths: Vec<PathBuf>) -> Self {
+        let mut plan = DropPlan { project: None, images: Vec::new(), skipped: Vec::new() };
+        for path in paths {
+            let kind = FileKind::from_path(&path);
+            let path = path.to_string_lossy().to_string();
+            match kind {
+                Some(FileKind::Image) => plan.images.push(path),
+                Some(kind) if plan.project.is_none() => plan.project = Some((kind, path)),
+                Some(_) => plan.skipped.push(SkippedFile {
+                    path,
+                    reason: "一度に開けるプロジェクトは1つです".to_string(),
+                }),
+                None => plan.skipped.push(SkippedFile {
+                    path,
+                    reason: "未対応のファイル形式です".to_string(),
+                }),
+            }
+        }
+        plan
+    }
+
+    fn step_count(&self) -> usize {
+        self.project.is_some() as usize + self.images.len()
+    }
+}
+
+/// ドロップされたファイルを種類ごとに読み込むジョブを登録し、ジョブIDを返す
+///
+/// 画像（PNG / JPEG）は新しいレイヤーに、プロジェクト（.kine / PSD / OpenRaster）は開いて現在の内容と置き換える。
+/// 進捗は `job:progress`、作成したレイヤーは `file-drop:layer-created`、
+/// 開いたプロジェクトは `file-drop:project-opened` イベントで通知する。
+pub(crate) fn handle_file_drop(app: &AppHandle, paths: Vec<PathBuf>) -> Option<u64> {
+    let plan = DropPlan::new(paths);
+    for skipped in &plan.skipped {
+        warn!("[File Drop] 読み込みをスキップ: {} ({})", skipped.path, skipped.reason);
+    }
+    if plan.step_count() == 0 {
+        if let Err(e) = app.emit("file-drop:skipped", &plan.skipped) {
+            warn!("[File Drop] イベントの送信に失敗: {}", e);
+        }
+        return None;
+    }
+
+    let handle = app.clone();
+    let id = app.state::<JobState>().queue().submit("file_drop", progress_listener(app), move |context| async move {
+        let state = handle.state::<DrawingState>();
+        let steps = plan.step_count() as f32;
+        let mut result = FileDropResult { project: None, layers: Vec::new(), skipped: plan.skipped };
+
+        if let Some((kind, path)) = plan.project {
+            let step = context.subrange(0.0, 1.0 / steps);
+            let project = match kind {
+                FileKind::Project => run_load_project(&state, path, &step).await?,
+                _ => run_import_project(&state, path, &step).await?,
+            };
+            if let Err(e) = handle.emit("file-drop:project-opened", &project) {
+                warn!("[File Drop] イベントの送信に失敗: {}", e);
+            }
+            result.project = Some(project);
+        }
+
+        let offset = result.project.is_some() as usize;
+        let id_prefix = chrono::Utc::now().timestamp_millis();
+        for (index, path) in plan.images.into_iter().enumerate() {
+            let start = (offset + index) as f32 / steps;
+            let step = context.subrange(start, start + 1.0 / steps);
+            let layer_id = format!("layer_{}_{}", id_prefix, index);
+            match run_import_image_layer(&state, path.clone(), Some(layer_id), &step).await {
+                Ok(layer) => {
+                    if let Err(e) = handle.emit("file-drop:layer-created", &layer) {
+                        warn!("[File Drop] イベントの送信に失敗: {}", e);
+                    }
+                    result.layers.push(layer);
+                }
+                Err(JobError::Cancelled) => return Err(JobError::Cancelled),
+                // 1つの画像が読めなくても残りの画像は読み込む
+                Err(e) => {
+                    warn!("[File Drop] 画像の読み込みに失敗: {} ({})", path, e);
+                    result.skipped.push(SkippedFile { path, reason: e.to_string() });
+                }
+            }
+        }
+
+        info!("[File Drop] 読み込み完了: プロジェクト {} / レイヤー {} / スキップ {}",
+              result.project.is_some(), result.layers.len(), result.skipped.len());
+        to_job_result(result)
+    });
+    debug!("[File Drop] ジョブ #{} を登録", id);
+    Some(id)
+}
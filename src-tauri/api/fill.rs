@@ -0,0 +1,369 @@
+use crate::animation::{BlendMode, Frame, Layer};
+use crate::formats::flood::{fill_selection, flood_fill_selection};
+use crate::formats::{FloodFillOptions, LayerFill, RegionInfo, RegionMap};
+use crate::history::Operation;
+use super::clipboard::read_layer_image;
+use super::drawing::DrawingState;
+use image::RgbaImage;
+use log::{info, debug};
+use serde::{Deserialize, Serialize};
+use tauri::State;
+
+/// 塗りつぶしレイヤーを作る（画素を持たないため、描画エンジンにはテクスチャを作らない）
+///
+/// フレームへの追加は呼び出し側で行う。
+#[tauri::command]
+pub async fn create_fill_layer(name: String, fill: LayerFill) -> Result<Layer, String> {
+    fill.validate().map_err(|e| e.to_string())?;
+    let layer = Layer {
+        id: format!("fill_{}", chrono::Utc::now().timestamp_millis()),
+        name,
+        visible: true,
+        opacity: 1.0,
+        blend_mode: BlendMode::Normal,
+        locked: false,
+        effects: Vec::new(),
+        fill: Some(fill),
+        mask: None,
+    };
+    info!("[Fill API] 塗りつぶしレイヤーを作成: {} ({})", layer.name, layer.id);
+    Ok(layer)
+}
+
+/// 塗りつぶしレイヤーの内容（色・グラデーション・模様）を変更し、更新したレイヤーを返す
+#[tauri::command]
+pub async fn set_layer_fill(mut layer: Layer, fill: LayerFill) -> Result<Layer, String> {
+    if layer.fill.is_none() {
+        return Err(format!("塗りつぶしレイヤーではありません: {}", layer.id));
+    }
+    fill.validate().map_err(|e| e.to_string())?;
+    layer.fill = Some(fill);
+    debug!("[Fill API] 塗りつぶしを変更: {}", layer.id);
+    Ok(layer)
+}
+
+/// 塗りつぶしレイヤーを同じIDの通常のレイヤーに変換する（キャンバスの大きさで生成した画像を貼り付ける）
+///
+/// レイヤーの作成と貼り付けは履歴に記録される。
+#[tauri::command]
+pub async fn rasterize_fill_layer(mut layer: Layer, state: State<'_, DrawingState>) -> Result<Layer, String> {
+    let fill = layer.fill.take().ok_or_else(|| format!("塗りつぶしレイヤーではありません: {}", layer.id))?;
+    fill.validate().map_err(|e| e.to_string())?;
+    let (width, height) = {
+        let layers_guard = state.layers.lock().await;
+        if layers_guard.contains_key(&layer.id) {
+            return Err(format!("レイヤーが既に存在します: {}", layer.id));
+        }
+        layers_guard.values().fold((0, 0), |(w, h), (lw, lh)| (w.max(*lw), h.max(*lh)))
+    };
+    if width == 0 || height == 0 {
+        return Err("キャンバスの大きさを決めるレイヤーがありません".to_string());
+    }
+
+    let image = tokio::task::spawn_blocking(move || fill.render(width, height))
+        .await
+        .map_err(|e| format!("塗りつぶし生成タスクエラー: {}", e))?;
+    let operations = vec![
+        Operation::CreateLayer { layer_id: layer.id.clone(), width, height },
+        Operation::paste_image(&layer.id, 0, 0, &image)?,
+    ];
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        for operation in &operations {
+            operation.apply(engine, &mut layers_guard)?;
+        }
+    }
+    for operation in operations {
+        state.record_operation(operation).await;
+    }
+
+    info!("[Fill API] 塗りつぶしレイヤーを画素のレイヤーに変換: {} ({}x{})", layer.id, width, height);
+    Ok(layer)
+}
+
+/// バケツ塗りの結果（塗った範囲）
+#[derive(Serialize)]
+pub struct BucketFillResult {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// レイヤー座標の位置から続く同じ色の範囲を塗る（バケツ）
+///
+/// `reference_layer_id` を指定するとそのレイヤー（線画など）の色で範囲を決め、`layer_id` に塗る。
+/// 着色モード（`options.colorize`）で指定がなければ `set_lineart_layer` で設定した線画レイヤーを使う。
+/// `options.gap_closing` で線画の小さな隙間を閉じて塗る。塗った画素は貼り付けとして履歴に記録される。
+/// 塗れる範囲がなければ None。
+#[tauri::command]
+pub async fn bucket_fill(
+    layer_id: String,
+    x: f32,
+    y: f32,
+    color: [f32; 4],
+    options: Option<FloodFillOptions>,
+    reference_layer_id: Option<String>,
+    state: State<'_, DrawingState>,
+) -> Result<Option<BucketFillResult>, String> {
+    let options = options.unwrap_or_default();
+    options.validate().map_err(|e| e.to_string())?;
+    debug!("[Fill API] バケツ塗り: {} ({}, {}) {:?}", layer_id, x, y, options);
+    if !(x >= 0.0 && y >= 0.0) {
+        return Ok(None);
+    }
+
+    // 着色モードでは指定がなければ線画レイヤーで範囲を決める
+    let reference_layer_id = match reference_layer_id {
+        None if options.colorize => Some(state.lineart_layer.lock().await.clone().ok_or("線画レイヤーが設定されていません")?),
+        reference_layer_id => reference_layer_id,
+    };
+    if options.colorize && reference_layer_id.as_deref() == Some(layer_id.as_str()) {
+        return Err("着色モードでは線画レイヤー以外のレイヤーに塗ってください".to_string());
+    }
+
+    let color = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    let result = flood_fill_layer(&state, &layer_id, (x as u32, y as u32), color, options, reference_layer_id.as_deref()).await?;
+    match &result {
+        Some(result) => info!("[Fill API] バケツ塗り完了: {} ({}, {}) {}x{}", layer_id, result.x, result.y, result.width, result.height),
+        None => debug!("[Fill API] 塗れる範囲がありません: {}", layer_id),
+    }
+    Ok(result)
+}
+
+/// 起点から続く範囲をレイヤーに塗り、履歴に記録する（塗れる範囲がなければ None）
+async fn flood_fill_layer(
+    state: &DrawingState,
+    layer_id: &str,
+    seed: (u32, u32),
+    color: [u8; 4],
+    options: FloodFillOptions,
+    reference_layer_id: Option<&str>,
+) -> Result<Option<BucketFillResult>, String> {
+    let target = read_layer_image(state, layer_id).await?;
+    let reference = match reference_layer_id {
+        Some(reference_layer_id) if reference_layer_id != layer_id => Some(read_layer_image(state, reference_layer_id).await?),
+        _ => None,
+    };
+    let filled = tokio::task::spawn_blocking(move || {
+        let selection = flood_fill_selection(reference.as_ref().unwrap_or(&target), seed, &options)?;
+        fill_selection(&target, &selection, color)
+    })
+    .await
+    .map_err(|e| format!("塗りつぶしタスクエラー: {}", e))?;
+    match filled {
+        Some((x, y, region)) => apply_fill(state, layer_id, x, y, &region).await.map(Some),
+        None => Ok(None),
+    }
+}
+
+/// 塗った範囲の画像を貼り付けとしてレイヤーに反映し、履歴に記録する
+async fn apply_fill(state: &DrawingState, layer_id: &str, x: u32, y: u32, region: &RgbaImage) -> Result<BucketFillResult, String> {
+    let operation = Operation::paste_image(layer_id, x, y, region)?;
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        operation.apply(engine, &mut layers_guard)?;
+    }
+    state.record_operation(operation).await;
+    Ok(BucketFillResult { x, y, width: region.width(), height: region.height() })
+}
+
+/// 着色モードのバケツ塗りで範囲を決める線画レイヤーを設定（None で解除）
+#[tauri::command]
+pub async fn set_lineart_layer(layer_id: Option<String>, state: State<'_, DrawingState>) -> Result<(), String> {
+    if let Some(layer_id) = &layer_id {
+        if !state.layers.lock().await.contains_key(layer_id) {
+            return Err(format!("レイヤーが見つかりません: {}", layer_id));
+        }
+    }
+    info!("[Fill API] 線画レイヤーを設定: {:?}", layer_id);
+    *state.lineart_layer.lock().await = layer_id;
+    Ok(())
+}
+
+/// 設定中の線画レイヤー
+#[tauri::command]
+pub async fn get_lineart_layer(state: State<'_, DrawingState>) -> Result<Option<String>, String> {
+    Ok(state.lineart_layer.lock().await.clone())
+}
+
+/// 領域分割のキャッシュ（分割した線画レイヤーと設定）
+pub(crate) struct RegionCache {
+    layer_id: String,
+    options: FloodFillOptions,
+    map: RegionMap,
+}
+
+/// 線画レイヤーの内容・大きさと設定から領域分割の世代を作る
+async fn region_revision(state: &DrawingState, layer_id: &str, options: &FloodFillOptions) -> Result<u64, String> {
+    use std::hash::{Hash, Hasher};
+    let size = *state.layers.lock().await.get(layer_id)
+        .ok_or(format!("レイヤーが見つかりません: {}", layer_id))?;
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    state.history.lock().await.layer_revision(layer_id).hash(&mut hasher);
+    size.hash(&mut hasher);
+    options.hash(&mut hasher);
+    Ok(hasher.finish())
+}
+
+/// 線画レイヤーを閉じた領域に分割し、領域の一覧を返す
+///
+/// `layer_id` を省略すると `set_lineart_layer` で設定した線画レイヤーを使う。
+/// 分割結果は線画（と設定）が変わるまで保持し、同じ線画ならすぐに返す。
+#[tauri::command]
+pub async fn segment_regions(
+    layer_id: Option<String>,
+    options: Option<FloodFillOptions>,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<RegionInfo>, String> {
+    let options = options.unwrap_or_default();
+    options.validate().map_err(|e| e.to_string())?;
+    let layer_id = match layer_id {
+        Some(layer_id) => layer_id,
+        None => state.lineart_layer.lock().await.clone().ok_or("線画レイヤーが設定されていません")?,
+    };
+    let revision = region_revision(&state, &layer_id, &options).await?;
+    if let Some(cache) = state.regions.lock().await.as_ref() {
+        if cache.layer_id == layer_id && cache.map.is_current(revision) {
+            return Ok(cache.map.regions().to_vec());
+        }
+    }
+
+    let lineart = read_layer_image(&state, &layer_id).await?;
+    let map = tokio::task::spawn_blocking(move || RegionMap::segment(&lineart, &options, revision))
+        .await
+        .map_err(|e| format!("領域分割タスクエラー: {}", e))?;
+    let regions = map.regions().to_vec();
+    info!("[Fill API] 線画を領域に分割: {} ({} 領域)", layer_id, regions.len());
+    *state.regions.lock().await = Some(RegionCache { layer_id, options, map });
+    Ok(regions)
+}
+
+/// 保持している領域分割を返す（線画が分割後に変わっていればエラー）
+async fn current_regions<'a>(
+    state: &'a DrawingState,
+) -> Result<tokio::sync::MutexGuard<'a, Option<RegionCache>>, String> {
+    let regions_guard = state.regions.lock().await;
+    let cache = regions_guard.as_ref().ok_or("線画が領域に分割されていません")?;
+    let revision = region_revision(state, &cache.layer_id, &cache.options).await?;
+    if !cache.map.is_current(revision) {
+        return Err("線画が変更されています。領域を分割し直してください".to_string());
+    }
+    Ok(regions_guard)
+}
+
+/// レイヤー座標の位置の領域ID（線の上なら None）
+#[tauri::command]
+pub async fn get_region_at(x: f32, y: f32, state: State<'_, DrawingState>) -> Result<Option<u32>, String> {
+    let regions_guard = current_regions(&state).await?;
+    Ok(regions_guard.as_ref().and_then(|cache| cache.map.region_at(x, y)))
+}
+
+/// 領域全体を色で塗る（線の下まで1ピクセル広げる。塗った画素は貼り付けとして履歴に記録される）
+#[tauri::command]
+pub async fn fill_region(
+    region_id: u32,
+    color: [f32; 4],
+    layer_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<BucketFillResult, String> {
+    let selection = {
+        let regions_guard = current_regions(&state).await?;
+        let cache = regions_guard.as_ref().ok_or("線画が領域に分割されていません")?;
+        if cache.layer_id == layer_id {
+            return Err("線画レイヤー以外のレイヤーに塗ってください".to_string());
+        }
+        cache.map.selection(region_id).ok_or(format!("領域が見つかりません: {}", region_id))?
+    };
+
+    let target = read_layer_image(&state, &layer_id).await?;
+    let color = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    let (x, y, region) = fill_selection(&target, &selection, color)
+        .ok_or(format!("領域がレイヤーの範囲外です: {}", region_id))?;
+    let result = apply_fill(&state, &layer_id, x, y, &region).await?;
+
+    debug!("[Fill API] 領域を塗りつぶし: {} 領域 {} ({}, {}) {}x{}", layer_id, region_id, result.x, result.y, result.width, result.height);
+    Ok(result)
+}
+
+/// 複数フレームのバケツ塗りの起点
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BatchFillSeed {
+    /// レイヤー座標の位置
+    Point { x: f32, y: f32 },
+    /// 分割済みの線画の領域（領域の中の1画素を各フレームの起点にする）
+    Region { region_id: u32 },
+}
+
+/// フレームごとのバケツ塗りの結果
+#[derive(Serialize)]
+pub struct FrameFillResult {
+    pub frame_id: String,
+    /// 塗った範囲（対応するレイヤーがない・塗れる範囲がないフレームは None）
+    pub filled: Option<BucketFillResult>,
+}
+
+/// 複数のフレームの対応するレイヤーに同じ起点からバケツ塗りをする
+///
+/// フレームごとに名前が `layer_name` のレイヤーに塗り、`lineart_layer_name` を指定すると
+/// 同じフレームのその名前のレイヤーで範囲を決める（着色モードでは必須）。
+/// 塗りはフレームごとに1つの操作として履歴に記録される。
+#[tauri::command]
+pub async fn batch_bucket_fill(
+    frames: Vec<Frame>,
+    layer_name: String,
+    lineart_layer_name: Option<String>,
+    seed: BatchFillSeed,
+    color: [f32; 4],
+    options: Option<FloodFillOptions>,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<FrameFillResult>, String> {
+    let options = options.unwrap_or_default();
+    options.validate().map_err(|e| e.to_string())?;
+    if options.colorize && lineart_layer_name.is_none() {
+        return Err("着色モードでは線画レイヤーの名前を指定してください".to_string());
+    }
+    if lineart_layer_name.as_deref() == Some(layer_name.as_str()) {
+        return Err("線画レイヤー以外のレイヤーに塗ってください".to_string());
+    }
+    let seed = match seed {
+        BatchFillSeed::Point { x, y } if x >= 0.0 && y >= 0.0 => (x as u32, y as u32),
+        BatchFillSeed::Point { x, y } => return Err(format!("起点がレイヤーの範囲外です: ({}, {})", x, y)),
+        BatchFillSeed::Region { region_id } => {
+            let regions_guard = current_regions(&state).await?;
+            let cache = regions_guard.as_ref().ok_or("線画が領域に分割されていません")?;
+            cache.map.seed_point(region_id).ok_or(format!("領域が見つかりません: {}", region_id))?
+        }
+    };
+    debug!("[Fill API] 複数フレームのバケツ塗り: {} フレーム, {} ({}, {})", frames.len(), layer_name, seed.0, seed.1);
+
+    let color = color.map(|channel| (channel.clamp(0.0, 1.0) * 255.0).round() as u8);
+    let find_layer = |frame: &Frame, name: &str| frame.layers.iter()
+        .find(|layer| layer.name == name && layer.fill.is_none())
+        .map(|layer| layer.id.clone());
+    let mut results = Vec::with_capacity(frames.len());
+    for frame in &frames {
+        let target = find_layer(frame, &layer_name);
+        let reference = lineart_layer_name.as_deref().map(|name| find_layer(frame, name));
+        let filled = match (target, reference) {
+            (Some(layer_id), None) => flood_fill_layer(&state, &layer_id, seed, color, options, None).await?,
+            (Some(layer_id), Some(Some(reference_id))) => {
+                flood_fill_layer(&state, &layer_id, seed, color, options, Some(&reference_id)).await?
+            }
+            _ => {
+                debug!("[Fill API] 対応するレイヤーがないフレームを飛ばします: {}", frame.id);
+                None
+            }
+        };
+        results.push(FrameFillResult { frame_id: frame.id.clone(), filled });
+    }
+
+    let filled = results.iter().filter(|result| result.filled.is_some()).count();
+    info!("[Fill API] 複数フレームのバケツ塗り完了: {} / {} フレーム", filled, frames.len());
+    Ok(results)
+}
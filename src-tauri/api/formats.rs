@@ -0,0 +1,1280 @@
+use crate::animation::{render_camera_view, BlendMode, CameraRect, ExportFrame, FrameMarkers, Layer, Project, SceneExportMode};
+use crate::formats::psd::{self, PsdWriteOptions};
+use crate::formats::{self as file_formats, apply_mask, apply_matte, flatten_layers_on, kine, sequence, ExportPreset, ExportSettings, FrameSequenceOptions, RasterLayer};
+use crate::formats::dirty::SaveSnapshot;
+use crate::formats::presets::{scaled_size, MAX_EXPORT_SCALE};
+use crate::drawing_engine::resample::resample;
+use crate::drawing_engine::{ContentBounds, ContentCoverage, ResampleFilter};
+use crate::jobs::{JobContext, JobError};
+use crate::history::{Operation, OperationLog};
+use crate::journal::JournalRecord;
+use super::drawing::DrawingState;
+use super::history::{rebuild_engine_state, replace_engine_images};
+use super::paging::{ensure_resident, read_page_blocking};
+use super::selection::QUICK_MASK_TEXTURE_ID;
+use super::settings::SettingsState;
+use log::{info, debug, warn, error};
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use tauri::ipc::Response;
+use tauri::State;
+use tokio::sync::Semaphore;
+
+/// 読み込んだプロジェクトのフレームレート（元ファイルに情報がないため固定）
+const IMPORTED_PROJECT_FRAME_RATE: f32 = 24.0;
+
+/// PSD書き出し結果
+#[derive(Serialize)]
+pub struct PsdExportResult {
+    pub path: String,
+    pub width: u32,
+    pub height: u32,
+    pub layer_count: usize,
+    pub flattened: bool,
+}
+
+/// レイヤーをPSDファイルとして書き出す
+///
+/// `layers` はプロジェクトのレイヤー情報（先頭が最背面）。省略時はエンジン上の
+/// 全レイヤーをID順に通常レイヤーとして書き出す。
+/// `settings.trim_to_content` が有効な場合は表示レイヤーの不透明部分に切り詰める。
+/// `settings.background` がマット色の場合はその色で塗った背景レイヤーを最背面に加える。
+/// 進捗表示や中断が必要な場合は `submit_job` から実行する。
+#[tauri::command]
+pub async fn export_psd(
+    path: String,
+    layers: Option<Vec<Layer>>,
+    flatten: Option<bool>,
+    settings: Option<ExportSettings>,
+    state: State<'_, DrawingState>,
+) -> Result<PsdExportResult, String> {
+    run_export_psd(&state, path, layers, flatten, settings, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// PSD書き出しの本体（ジョブとしても実行される）
+pub(crate) async fn run_export_psd(
+    state: &DrawingState,
+    path: String,
+    layers: Option<Vec<Layer>>,
+    flatten: Option<bool>,
+    settings: Option<ExportSettings>,
+    context: &JobContext,
+) -> Result<PsdExportResult, JobError> {
+    info!("[Format API] PSD書き出し開始: {}", path);
+
+    let settings = settings.unwrap_or_default();
+    settings.background.validate().map_err(|e| e.to_string())?;
+    let trim_layer_ids = settings.trim_to_content.then(|| {
+        layers.as_ref().map(|layers| layers.iter().filter(|l| l.visible).map(|l| l.id.clone()).collect())
+    });
+
+    context.report(0.0, "レイヤーを読み出しています");
+    let (mut raster_layers, mut width, mut height) = collect_raster_layers(state, layers).await?;
+    context.check_cancelled()?;
+
+    if let Some(layer_ids) = trim_layer_ids {
+        context.report(0.3, "不透明範囲を計算しています");
+        match content_bounds(state, layer_ids, settings.trim_padding).await? {
+            Some(bounds) => {
+                debug!("[Format API] 不透明範囲に切り詰め: {:?}", bounds);
+                file_formats::crop_layers(&mut raster_layers, bounds);
+                (width, height) = (bounds.width, bounds.height);
+            }
+            None => debug!("[Format API] 不透明なピクセルがないため切り詰めません"),
+        }
+        context.check_cancelled()?;
+    }
+    if let Some(matte) = settings.background.matte() {
+        raster_layers.insert(0, RasterLayer::new("背景", RgbaImage::from_pixel(width, height, matte)));
+    }
+    let options = PsdWriteOptions { flatten: flatten.unwrap_or(false) };
+    let flattened = options.flatten || psd::requires_flatten(&raster_layers);
+
+    // エンコードはCPU負荷が高いため別スレッドで行う
+    context.report(0.4, "PSDをエンコードしています");
+    let layer_count = raster_layers.len();
+    let data = tokio::task::spawn_blocking(move || psd::encode_psd(&raster_layers, width, height, &options))
+        .await
+        .map_err(|e| format!("書き出しタスクエラー: {}", e))?
+        .map_err(|e| {
+            error!("[Format API] PSDエンコード失敗: {}", e);
+            e.to_string()
+        })?;
+    context.check_cancelled()?;
+
+    context.report(0.9, "ファイルに書き込んでいます");
+    tokio::fs::write(&path, data).await
+        .map_err(|e| format!("PSDファイルの書き込みに失敗しました: {}", e))?;
+
+    info!("[Format API] PSD書き出し完了: {} ({}x{}, {} レイヤー)", path, width, height, layer_count);
+    Ok(PsdExportResult {
+        path,
+        width,
+        height,
+        layer_count: if flattened { 1 } else { layer_count },
+        flattened,
+    })
+}
+
+/// 指定レイヤー（省略時は全レイヤー）を合成した画像を PNG のバイト列で取得
+///
+/// ファイルには書き出さず、バイナリのまま返す（フロントエンドでは `ArrayBuffer` として受け取り、
+/// `Blob` にしてダウンロードやクリップボードに使う）。`layers` は先頭が最背面。
+/// `scale` を指定するとキャンバスと違う大きさに `filter`（省略時は Lanczos3）で拡大・縮小して書き出す。
+/// `settings.background` にマット色を指定するとアルファを残さず、その色の上に合成して書き出す。
+/// `settings.trim_to_content` が有効な場合は表示レイヤーの不透明部分に切り詰めてから拡大・縮小する。
+#[tauri::command]
+pub async fn export_canvas_png(
+    layers: Option<Vec<Layer>>,
+    scale: Option<f32>,
+    filter: Option<ResampleFilter>,
+    settings: Option<ExportSettings>,
+    state: State<'_, DrawingState>,
+) -> Result<Response, String> {
+    let scale = scale.unwrap_or(1.0);
+    if !(scale > 0.0 && scale <= MAX_EXPORT_SCALE) {
+        return Err(format!("拡大率は 0 より大きく {} 以下で指定してください: {}", MAX_EXPORT_SCALE, scale));
+    }
+    let settings = settings.unwrap_or_default();
+    settings.background.validate().map_err(|e| e.to_string())?;
+    let matte = settings.background.matte();
+    let trim_layer_ids = settings.trim_to_content.then(|| {
+        layers.as_ref().map(|layers| layers.iter().filter(|l| l.visible).map(|l| l.id.clone()).collect())
+    });
+    let (mut raster_layers, mut width, mut height) = collect_raster_layers(&state, layers).await?;
+    if let Some(layer_ids) = trim_layer_ids {
+        match content_bounds(&state, layer_ids, settings.trim_padding).await? {
+            Some(bounds) => {
+                debug!("[Format API] 不透明範囲に切り詰め: {:?}", bounds);
+                file_formats::crop_layers(&mut raster_layers, bounds);
+                (width, height) = (bounds.width, bounds.height);
+            }
+            None => debug!("[Format API] 不透明なピクセルがないため切り詰めません"),
+        }
+    }
+    let mut image = tokio::task::spawn_blocking(move || flatten_layers_on(&raster_layers, width, height, matte))
+        .await
+        .map_err(|e| format!("合成タスクエラー: {}", e))?;
+    let size = scaled_size((width, height), scale);
+    if size != (width, height) {
+        image = scale_for_export(&state, &image, size, filter.unwrap_or_default()).await?;
+    }
+    let png = tokio::task::spawn_blocking(move || sequence::encode_png(&image))
+        .await
+        .map_err(|e| format!("エンコードタスクエラー: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    info!("[Format API] キャンバスを PNG で書き出し: {}x{} ({} bytes)", size.0, size.1, png.len());
+    Ok(Response::new(png))
+}
+
+/// 指定レイヤー（省略時は全レイヤー）の不透明部分を合わせた範囲を取得
+///
+/// 複数フレームにまたがる範囲は、それらのフレームのレイヤーIDをまとめて指定する。
+/// すべて透明な場合は None を返す。
+#[tauri::command]
+pub async fn get_content_bounds(
+    layer_ids: Option<Vec<String>>,
+    padding: Option<u32>,
+    state: State<'_, DrawingState>,
+) -> Result<Option<ContentBounds>, String> {
+    content_bounds(&state, layer_ids, padding.unwrap_or(0)).await
+}
+
+/// レイヤーの不透明部分の集計
+#[derive(Serialize)]
+pub struct LayerStats {
+    pub layer_id: String,
+    pub width: u32,
+    pub height: u32,
+    #[serde(flatten)]
+    pub coverage: ContentCoverage,
+}
+
+impl LayerStats {
+    /// 完全に透明なレイヤーか
+    pub fn is_empty(&self) -> bool {
+        self.coverage.pixel_count == 0
+    }
+}
+
+/// 指定レイヤー（省略時は全レイヤー）それぞれの不透明なピクセル数と不透明部分の範囲を取得
+///
+/// GPU でまとめて集計し、レイヤーの画像は読み出さない。空のレイヤーの整理などに使う。
+#[tauri::command]
+pub async fn get_canvas_stats(
+    layer_ids: Option<Vec<String>>,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<LayerStats>, String> {
+    let sizes = state.layers.lock().await.clone();
+    let layer_ids = layer_ids.unwrap_or_else(|| {
+        let mut ids: Vec<String> = sizes.keys().filter(|id| *id != QUICK_MASK_TEXTURE_ID).cloned().collect();
+        ids.sort();
+        ids
+    });
+    if let Some(missing) = layer_ids.iter().find(|id| !sizes.contains_key(*id)) {
+        return Err(format!("レイヤーが見つかりません: {}", missing));
+    }
+
+    ensure_resident(&state, Some(&layer_ids)).await?;
+    let coverages = {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        engine.content_coverage(&layer_ids).await
+            .map_err(|e| format!("集計エラー: {}", e))?
+    };
+
+    let stats: Vec<LayerStats> = layer_ids.into_iter()
+        .zip(coverages)
+        .map(|(layer_id, coverage)| {
+            let (width, height) = sizes[&layer_id];
+            LayerStats { layer_id, width, height, coverage }
+        })
+        .collect();
+    debug!("[Format API] レイヤー集計: {} レイヤー（空 {}）",
+           stats.len(), stats.iter().filter(|stats| stats.is_empty()).count());
+    Ok(stats)
+}
+
+/// GPU で不透明範囲を求め、余白を加える
+async fn content_bounds(
+    state: &DrawingState,
+    layer_ids: Option<Vec<String>>,
+    padding: u32,
+) -> Result<Option<ContentBounds>, String> {
+    let sizes = state.layers.lock().await.clone();
+    let layer_ids = layer_ids.unwrap_or_else(|| {
+        let mut ids: Vec<String> = sizes.keys().cloned().collect();
+        ids.sort();
+        ids
+    });
+    if let Some(missing) = layer_ids.iter().find(|id| !sizes.contains_key(*id)) {
+        return Err(format!("レイヤーが見つかりません: {}", missing));
+    }
+    let canvas_size = layer_ids.iter()
+        .filter_map(|id| sizes.get(id))
+        .fold((0, 0), |(w, h), &(lw, lh)| (w.max(lw), h.max(lh)));
+
+    ensure_resident(state, Some(&layer_ids)).await?;
+    let bounds = {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        engine.content_bounds(&layer_ids).await
+            .map_err(|e| format!("範囲計算エラー: {}", e))?
+    };
+
+    debug!("[Format API] 不透明範囲: {:?} ({} レイヤー)", bounds, layer_ids.len());
+    Ok(bounds.map(|bounds| bounds.padded(padding, canvas_size)))
+}
+
+/// 連番・一括書き出しで全フレームに共通の切り詰め範囲（書き出す画像の座標）
+///
+/// `project.export.trim_to_content` が無効か、どのフレームも透明なら None。
+/// カメラを使うフレームは、キャンバス上の不透明範囲を切り出した画像の座標に写して合わせる。
+async fn sequence_trim_bounds(
+    state: &DrawingState,
+    project: &Project,
+    plan: &[ExportFrame],
+) -> Result<Option<ContentBounds>, String> {
+    if !project.export.trim_to_content {
+        return Ok(None);
+    }
+    let mut union: Option<ContentBounds> = None;
+    let mut output_size = (0, 0);
+    let mut measured = std::collections::HashSet::new();
+    for planned in plan {
+        output_size = (output_size.0.max(planned.width), output_size.1.max(planned.height));
+        // 同じフレームを同じ大きさで繰り返し書き出す場合は1度だけ測る
+        if !measured.insert((planned.frame_index, planned.width, planned.height)) {
+            continue;
+        }
+        let layer_ids: Vec<String> = project.frames[planned.frame_index].layers.iter()
+            .filter(|layer| layer.visible)
+            .map(|layer| layer.id.clone())
+            .collect();
+        if layer_ids.is_empty() {
+            continue;
+        }
+        let Some(bounds) = content_bounds(state, Some(layer_ids), 0).await? else {
+            continue;
+        };
+        let bounds = match project.camera.rect_at(planned.frame_index, planned.width, planned.height) {
+            Some(rect) => rect.project_bounds(&bounds, planned.width, planned.height),
+            None => bounds.clipped(planned.width, planned.height),
+        };
+        union = match (union, bounds) {
+            (Some(union), Some(bounds)) => Some(union.union(&bounds)),
+            (union, bounds) => union.or(bounds),
+        };
+    }
+    let bounds = union.map(|bounds| bounds.padded(project.export.trim_padding, output_size));
+    debug!("[Format API] 書き出すフレームの不透明範囲: {:?} ({} フレーム)", bounds, measured.len());
+    Ok(bounds)
+}
+
+/// 書き出す画像を切り詰め範囲で切り抜く（範囲が画像からはみ出す部分は含めない）
+fn crop_to_bounds(image: RgbaImage, bounds: Option<ContentBounds>) -> RgbaImage {
+    match bounds {
+        Some(bounds) => image::imageops::crop_imm(&image, bounds.x, bounds.y, bounds.width, bounds.height).to_image(),
+        None => image,
+    }
+}
+
+/// PSD / OpenRaster ファイルを新しいプロジェクトとして読み込む
+///
+/// 現在のレイヤーは破棄され、読み込んだレイヤーの作成と画像の貼り付けが
+/// 新しい履歴として記録される。
+#[tauri::command]
+pub async fn import_project(
+    path: String,
+    state: State<'_, DrawingState>,
+) -> Result<Project, String> {
+    run_import_project(&state, path, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// プロジェクト読み込みの本体（ジョブとしても実行される）
+///
+/// 中断できるのはエンジンの状態を置き換える前まで。
+pub(crate) async fn run_import_project(
+    state: &DrawingState,
+    path: String,
+    context: &JobContext,
+) -> Result<Project, JobError> {
+    info!("[Format API] プロジェクト読み込み開始: {}", path);
+
+    if state.collaboration.lock().await.is_some() {
+        return Err(JobError::Failed("共同編集中はプロジェクトを読み込めません".to_string()));
+    }
+
+    context.report(0.0, "ファイルを読み込んでいます");
+
+    let data = tokio::fs::read(&path).await
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+    let name = Path::new(&path).file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Imported".to_string());
+    context.check_cancelled()?;
+
+    context.report(0.2, "レイヤーをデコードしています");
+    // デコードとレイヤー画像のPNG化はCPU負荷が高いため別スレッドで行う
+    let (mut project, operations) = tokio::task::spawn_blocking(move || {
+        let document = file_formats::decode_layered_document(&data).map_err(|e| {
+            error!("[Format API] 読み込み失敗: {}", e);
+            e.to_string()
+        })?;
+        build_imported_project(name, document)
+    })
+    .await
+    .map_err(|e| format!("読み込みタスクエラー: {}", e))??;
+    context.check_cancelled()?;
+
+    context.report(0.7, "レイヤーを再構築しています");
+    let log = OperationLog::from_operations(operations);
+    {
+        let mut history_guard = state.history.lock().await;
+        rebuild_engine_state(state, &log).await?;
+        *history_guard = log.clone();
+        state.save_tracker.lock().await.mark_all();
+        state.write_journal(JournalRecord::Base { log: log.clone() }).await;
+    }
+    project.history = log;
+    *state.markers.lock().await = project.markers.clone();
+
+    info!("[Format API] プロジェクト読み込み完了: {} ({}x{}, {} レイヤー)",
+          project.name, project.width, project.height, project.frames[0].layers.len());
+    Ok(project)
+}
+
+/// 画像ファイルから作成したレイヤー
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedLayer {
+    pub path: String,
+    pub layer_id: String,
+    /// ファイル名から付けたレイヤー名
+    pub name: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// PNG / JPEG ファイルを現在のプロジェクトに新しいレイヤーとして読み込む
+///
+/// レイヤーの大きさは既存のレイヤー（キャンバス）に合わせ、画像は左上に置く（はみ出した部分は切り捨て）。
+/// レイヤーがなければ画像の大きさのレイヤーを作る。`layer_id` を省略した場合は自動で付ける。
+#[tauri::command]
+pub async fn import_image_layer(
+    path: String,
+    layer_id: Option<String>,
+    state: State<'_, DrawingState>,
+) -> Result<ImportedLayer, String> {
+    run_import_image_layer(&state, path, layer_id, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// 画像レイヤー読み込みの本体（ジョブとしても実行される）
+pub(crate) async fn run_import_image_layer(
+    state: &DrawingState,
+    path: String,
+    layer_id: Option<String>,
+    context: &JobContext,
+) -> Result<ImportedLayer, JobError> {
+    info!("[Format API] 画像レイヤー読み込み開始: {}", path);
+
+    context.report(0.0, "ファイルを読み込んでいます");
+    let data = tokio::fs::read(&path).await
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+    let name = Path::new(&path).file_stem()
+        .map(|stem| stem.to_string_lossy().to_string())
+        .unwrap_or_else(|| "Image".to_string());
+    context.check_cancelled()?;
+
+    context.report(0.3, "画像をデコードしています");
+    let image = tokio::task::spawn_blocking(move || file_formats::decode_image(&data))
+        .await
+        .map_err(|e| format!("読み込みタスクエラー: {}", e))?
+        .map_err(|e| {
+            error!("[Format API] 画像の読み込み失敗: {}", e);
+            e.to_string()
+        })?;
+    context.check_cancelled()?;
+
+    let layer_id = layer_id.unwrap_or_else(|| format!("layer_{}", chrono::Utc::now().timestamp_millis()));
+    let (width, height) = {
+        let layers_guard = state.layers.lock().await;
+        if layers_guard.contains_key(&layer_id) {
+            return Err(JobError::Failed(format!("レイヤーが既に存在します: {}", layer_id)));
+        }
+        layers_guard.values().fold((0, 0), |(w, h), (lw, lh)| (w.max(*lw), h.max(*lh)))
+    };
+    let (width, height) = if width == 0 || height == 0 { image.dimensions() } else { (width, height) };
+    state.check_canvas_size(width, height).await?;
+
+    context.report(0.7, "レイヤーを作成しています");
+    let image = if image.width() > width || image.height() > height {
+        image::imageops::crop_imm(&image, 0, 0, width, height).to_image()
+    } else {
+        image
+    };
+    let operations = vec![
+        Operation::CreateLayer { layer_id: layer_id.clone(), width, height },
+        Operation::paste_image(&layer_id, 0, 0, &image)?,
+    ];
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut()
+            .ok_or_else(|| JobError::Failed("描画エンジンが初期化されていません".to_string()))?;
+        let mut layers_guard = state.layers.lock().await;
+        for operation in &operations {
+            operation.apply(engine, &mut layers_guard)?;
+        }
+    }
+    for operation in operations {
+        state.record_operation(operation).await;
+    }
+
+    info!("[Format API] 画像レイヤー読み込み完了: {} -> {} ({}x{})", path, layer_id, width, height);
+    Ok(ImportedLayer { path, layer_id, name, width, height })
+}
+
+/// 連番画像書き出し結果
+#[derive(Serialize)]
+pub struct FrameSequenceExportResult {
+    pub directory: String,
+    /// 書き出したファイル名（フレーム順）
+    pub files: Vec<String>,
+    pub bytes: u64,
+    /// 同時に処理したフレーム数
+    pub frames_in_flight: usize,
+}
+
+/// アニメーションの全フレームを合成し、連番PNGとして書き出す
+///
+/// `project.export.trim_to_content` が有効な場合は、全フレームの不透明部分を合わせた同じ範囲に切り詰める。
+/// 進捗表示や中断が必要な場合は `submit_job` から実行する（フレームが書き出されるたびに進捗を報告する）。
+#[tauri::command]
+pub async fn export_frame_sequence(
+    directory: String,
+    project: Project,
+    options: Option<FrameSequenceOptions>,
+    state: State<'_, DrawingState>,
+) -> Result<FrameSequenceExportResult, String> {
+    run_export_frame_sequence(&state, directory, project, options, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// 連番画像書き出しの本体（ジョブとしても実行される）
+///
+/// レイヤーの読み出しは描画エンジンを使うためフレーム順に行い、合成とPNGエンコードは
+/// ワーカースレッドで複数フレームを並行して行う。同時に処理するフレーム数はメモリ上限から決める。
+/// ファイル名はフレーム番号で決まるため、完了の順序によらず出力の順序は保たれる。
+pub(crate) async fn run_export_frame_sequence(
+    state: &DrawingState,
+    directory: String,
+    project: Project,
+    options: Option<FrameSequenceOptions>,
+    context: &JobContext,
+) -> Result<FrameSequenceExportResult, JobError> {
+    let options = options.unwrap_or_default();
+    options.validate().map_err(|e| e.to_string())?;
+    let plan = project.export_plan(&options.scenes).map_err(|e| e.to_string())?;
+    project.camera.validate().map_err(|e| e.to_string())?;
+    let frame_count = plan.len();
+    if frame_count == 0 {
+        return Err(JobError::Failed("書き出すフレームがありません".to_string()));
+    }
+    tokio::fs::create_dir_all(&directory).await
+        .map_err(|e| format!("書き出し先フォルダの作成に失敗しました: {}", e))?;
+    let trim = sequence_trim_bounds(state, &project, &plan).await?;
+    context.check_cancelled()?;
+
+    let max_layers = project.frames.iter().map(|frame| frame.layers.len()).max().unwrap_or(0);
+    // カメラを使う場合はキャンバス全体を合成してから切り出す
+    let canvas_size = (project.width, project.height);
+    let (max_width, max_height) = plan.iter()
+        .fold(canvas_size, |(w, h), frame| (w.max(frame.width), h.max(frame.height)));
+    let workers = std::thread::available_parallelism().map(usize::from).unwrap_or(1);
+    let frames_in_flight = options.frames_in_flight(max_width, max_height, max_layers, workers);
+    let matte = options.background.matte();
+    info!("[Format API] 連番書き出し開始: {} ({} フレーム, 同時 {} フレーム)", directory, frame_count, frames_in_flight);
+
+    let slots = Arc::new(Semaphore::new(frames_in_flight));
+    let completed = Arc::new(AtomicUsize::new(0));
+    let mut files = Vec::with_capacity(frame_count);
+    let mut tasks = Vec::with_capacity(frame_count);
+    for (index, planned) in plan.iter().enumerate() {
+        let (frame, width, height) = (&project.frames[planned.frame_index], planned.width, planned.height);
+        let camera = project.camera.rect_at(planned.frame_index, width, height);
+        // 空きが出るまで次のフレームを読み出さない（読み出した画像がメモリ上限を超えないようにする）
+        let slot = slots.clone().acquire_owned().await
+            .map_err(|e| format!("書き出しワーカーの待機に失敗: {}", e))?;
+        context.check_cancelled()?;
+
+        let layers = if frame.layers.is_empty() {
+            Vec::new()
+        } else {
+            collect_raster_layers(state, Some(frame.layers.clone())).await?.0
+        };
+        let file_name = options.file_name(index, frame_count);
+        let path = Path::new(&directory).join(&file_name);
+        files.push(file_name);
+
+        let (context, completed) = (context.clone(), completed.clone());
+        tasks.push(tokio::task::spawn_blocking(move || {
+            let _slot = slot;
+            context.check_cancelled()?;
+            let image = crop_to_bounds(composite_frame(&layers, canvas_size, camera, (width, height), matte), trim);
+            let png = sequence::encode_png(&image).map_err(|e| e.to_string())?;
+            std::fs::write(&path, &png)
+                .map_err(|e| format!("フレームの書き込みに失敗しました: {}: {}", path.display(), e))?;
+
+            let done = completed.fetch_add(1, Ordering::Relaxed) + 1;
+            context.report(done as f32 / frame_count as f32, format!("フレーム {}/{} を書き出しました", done, frame_count));
+            Ok::<_, JobError>(png.len() as u64)
+        }));
+    }
+
+    let mut bytes = 0;
+    for task in tasks {
+        bytes += task.await.map_err(|e| format!("書き出しタスクエラー: {}", e))??;
+    }
+
+    info!("[Format API] 連番書き出し完了: {} ({} フレーム, {} bytes)", directory, frame_count, bytes);
+    Ok(FrameSequenceExportResult { directory, files, bytes, frames_in_flight })
+}
+
+/// 1つのプリセットの書き出し結果
+#[derive(Serialize)]
+pub struct PresetExportResult {
+    pub preset: String,
+    pub directory: String,
+    /// 書き出したファイル名（フレーム順）
+    pub files: Vec<String>,
+    pub bytes: u64,
+}
+
+/// 一括書き出し結果
+#[derive(Serialize)]
+pub struct BatchExportResult {
+    pub directory: String,
+    pub presets: Vec<PresetExportResult>,
+}
+
+/// 保存済みの書き出しプリセットをまとめて実行する
+///
+/// `presets` はプリセット名で、`directory` の下にプリセット名のフォルダを作って書き出す。
+/// フレームの読み出しと合成は1度だけ行い、同じ合成結果を各プリセットで拡大・縮小してエンコードする。
+/// `project.export.trim_to_content` が有効な場合は、書き出す全フレームに共通の範囲に切り詰めてから拡大・縮小する。
+/// 進捗表示や中断が必要な場合は `submit_job` から実行する。
+#[tauri::command]
+pub async fn batch_export(
+    directory: String,
+    project: Project,
+    presets: Vec<String>,
+    scenes: Option<SceneExportMode>,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<BatchExportResult, String> {
+    let presets = resolve_presets(&settings, &presets).await?;
+    run_batch_export(&state, directory, project, presets, scenes.unwrap_or_default(), &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// プリセット名から保存済みのプリセットを探す（指定した順）
+pub(crate) async fn resolve_presets(settings: &SettingsState, names: &[String]) -> Result<Vec<ExportPreset>, String> {
+    let saved = settings.get().await.export_presets;
+    names.iter()
+        .map(|name| saved.iter().find(|preset| preset.name == *name).cloned()
+            .ok_or_else(|| format!("書き出しプリセットが見つかりません: {}", name)))
+        .collect()
+}
+
+/// 一括書き出しの本体（ジョブとしても実行される）
+pub(crate) async fn run_batch_export(
+    state: &DrawingState,
+    directory: String,
+    project: Project,
+    presets: Vec<ExportPreset>,
+    scenes: SceneExportMode,
+    context: &JobContext,
+) -> Result<BatchExportResult, JobError> {
+    if presets.is_empty() {
+        return Err(JobError::Failed("書き出しプリセットが指定されていません".to_string()));
+    }
+    file_formats::presets::validate_presets(&presets).map_err(|e| e.to_string())?;
+    let plan = project.export_plan(&scenes).map_err(|e| e.to_string())?;
+    project.camera.validate().map_err(|e| e.to_string())?;
+    let ranges: Vec<_> = presets.iter().map(|preset| preset.frame_indices(plan.len())).collect();
+    let frame_count = ranges.iter().map(|range| range.end).max().unwrap_or(0);
+    let first_frame = ranges.iter().filter(|range| !range.is_empty()).map(|range| range.start).min().unwrap_or(frame_count);
+    if first_frame >= frame_count {
+        return Err(JobError::Failed("書き出すフレームがありません".to_string()));
+    }
+
+    let mut results = Vec::with_capacity(presets.len());
+    for preset in &presets {
+        let preset_directory = Path::new(&directory).join(preset.name.trim());
+        tokio::fs::create_dir_all(&preset_directory).await
+            .map_err(|e| format!("書き出し先フォルダの作成に失敗しました: {}", e))?;
+        results.push(PresetExportResult {
+            preset: preset.name.clone(),
+            directory: preset_directory.to_string_lossy().into_owned(),
+            files: Vec::new(),
+            bytes: 0,
+        });
+    }
+    info!("[Format API] 一括書き出し開始: {} ({} プリセット, {} フレーム)", directory, presets.len(), frame_count - first_frame);
+    let trim = sequence_trim_bounds(state, &project, &plan[first_frame..frame_count]).await?;
+
+    let canvas_size = (project.width, project.height);
+    let presets = Arc::new(presets);
+    for (index, planned) in plan.iter().enumerate().take(frame_count).skip(first_frame) {
+        context.check_cancelled()?;
+        let targets: Vec<usize> = (0..presets.len()).filter(|&i| ranges[i].contains(&index)).collect();
+        if targets.is_empty() {
+            continue;
+        }
+        let (frame, width, height) = (&project.frames[planned.frame_index], planned.width, planned.height);
+        let camera = project.camera.rect_at(planned.frame_index, width, height);
+        let layers = if frame.layers.is_empty() {
+            Vec::new()
+        } else {
+            collect_raster_layers(state, Some(frame.layers.clone())).await?.0
+        };
+
+        // 合成はマット色ごとに1度だけ行い、同じ大きさとフィルターのプリセットは拡大・縮小した画像も共有する
+        let layers = Arc::new(layers);
+        let mut composites: HashMap<Option<[u8; 4]>, Arc<RgbaImage>> = HashMap::new();
+        let mut scaled: HashMap<(Option<[u8; 4]>, (u32, u32), ResampleFilter), Arc<RgbaImage>> = HashMap::new();
+        let mut outputs: Vec<(usize, String, PathBuf, Arc<RgbaImage>)> = Vec::with_capacity(targets.len());
+        for &i in &targets {
+            let preset = &presets[i];
+            let matte = preset.matte();
+            let key = matte.map(|color| color.0);
+            let image = match composites.get(&key) {
+                Some(image) => image.clone(),
+                None => {
+                    let layers = layers.clone();
+                    let image = Arc::new(tokio::task::spawn_blocking(move || {
+                        crop_to_bounds(composite_frame(&layers, canvas_size, camera, (width, height), matte), trim)
+                    })
+                    .await
+                    .map_err(|e| format!("合成タスクエラー: {}", e))?);
+                    composites.insert(key, image.clone());
+                    image
+                }
+            };
+            let size = preset.output_size(image.width(), image.height());
+            let output = if size == image.dimensions() {
+                image
+            } else if let Some(output) = scaled.get(&(key, size, preset.filter)) {
+                output.clone()
+            } else {
+                let output = Arc::new(scale_for_export(state, &image, size, preset.filter).await?);
+                scaled.insert((key, size, preset.filter), output.clone());
+                output
+            };
+            let range = &ranges[i];
+            let file_name = presets[i].file_name(index - range.start, range.len());
+            let path = Path::new(&results[i].directory).join(&file_name);
+            outputs.push((i, file_name, path, output));
+        }
+
+        // プリセットごとのエンコードはワーカースレッドで並行して行う
+        let task_presets = presets.clone();
+        let written = tokio::task::spawn_blocking(move || {
+            outputs.into_par_iter()
+                .map(|(i, file_name, path, image)| {
+                    let data = task_presets[i].encode(&image).map_err(|e| e.to_string())?;
+                    std::fs::write(&path, &data)
+                        .map_err(|e| format!("フレームの書き込みに失敗しました: {}: {}", path.display(), e))?;
+                    Ok::<_, String>((i, file_name, data.len() as u64))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        })
+        .await
+        .map_err(|e| format!("書き出しタスクエラー: {}", e))??;
+        for (i, file_name, bytes) in written {
+            results[i].files.push(file_name);
+            results[i].bytes += bytes;
+        }
+
+        let done = index + 1 - first_frame;
+        context.report(done as f32 / (frame_count - first_frame) as f32, format!("フレーム {}/{} を書き出しました", done, frame_count - first_frame));
+    }
+
+    info!("[Format API] 一括書き出し完了: {} ({} bytes)", directory, results.iter().map(|result| result.bytes).sum::<u64>());
+    Ok(BatchExportResult { directory, presets: results })
+}
+
+/// 書き出す1フレームを合成する（カメラがあればキャンバス全体を合成してから切り出す）
+///
+/// マット色はレイヤーの下地として合成し、カメラの切り出しでキャンバスの外になった部分も同じ色で埋める。
+fn composite_frame(
+    layers: &[RasterLayer],
+    canvas_size: (u32, u32),
+    camera: Option<CameraRect>,
+    (width, height): (u32, u32),
+    matte: Option<Rgba<u8>>,
+) -> RgbaImage {
+    match camera {
+        Some(rect) => {
+            let mut image = render_camera_view(&flatten_layers_on(layers, canvas_size.0, canvas_size.1, matte), rect, width, height);
+            if let Some(matte) = matte {
+                apply_matte(&mut image, matte);
+            }
+            image
+        }
+        None => flatten_layers_on(layers, width, height, matte),
+    }
+}
+
+/// 書き出す画像を GPU で拡大・縮小する（GPU で扱えない大きさなどで失敗したときは CPU で行う）
+pub(crate) async fn scale_for_export(
+    state: &DrawingState,
+    image: &RgbaImage,
+    (width, height): (u32, u32),
+    filter: ResampleFilter,
+) -> Result<RgbaImage, String> {
+    {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        match engine.resample_image(image, width, height, filter).await {
+            Ok(scaled) => return Ok(scaled),
+            Err(e) => warn!("[Format API] GPU での拡大・縮小に失敗したため CPU で行います: {}", e),
+        }
+    }
+    let image = image.clone();
+    tokio::task::spawn_blocking(move || resample(&image, width, height, filter))
+        .await
+        .map_err(|e| format!("拡大・縮小タスクエラー: {}", e))
+}
+
+/// .kine 保存結果
+#[derive(Serialize)]
+pub struct ProjectSaveResult {
+    pub path: String,
+    pub frame_count: usize,
+    pub layer_count: usize,
+    /// 保存後のファイルサイズ
+    pub bytes: u64,
+    /// 変更されたレイヤーだけを追記したか
+    pub incremental: bool,
+    /// 書き込んだフレーム・レイヤー数（全体を書き直した場合は全フレーム・全レイヤー）
+    pub frames_written: usize,
+    pub layers_written: usize,
+}
+
+/// プロジェクトを .kine ファイルとして保存
+///
+/// 前回と同じファイルに保存する場合は、保存後に変更されたレイヤーだけを末尾に追記する。
+/// 初めての保存やキャンバス全体の変更後、追記で不要な領域が増えた場合はファイル全体を書き直す。
+/// 書き直す際は各フレームのレイヤー画像を直前のフレームとの差分として LZ4 で圧縮し、
+/// `keyframe_interval` フレームごとに差分を使わないキーフレームを置く（省略時は 12）。
+#[tauri::command]
+pub async fn save_project(
+    path: String,
+    project: Project,
+    keyframe_interval: Option<u32>,
+    state: State<'_, DrawingState>,
+) -> Result<ProjectSaveResult, String> {
+    run_save_project(&state, path, project, keyframe_interval, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// .kine 保存の本体（ジョブとしても実行される）
+pub(crate) async fn run_save_project(
+    state: &DrawingState,
+    path: String,
+    mut project: Project,
+    keyframe_interval: Option<u32>,
+    context: &JobContext,
+) -> Result<ProjectSaveResult, JobError> {
+    info!("[Format API] プロジェクト保存開始: {} ({} フレーム)", path, project.frames.len());
+    project.markers = state.markers.lock().await.clone();
+    // 開き直してもアンドゥ・リドゥできるよう、操作ログも保存する
+    project.history = state.history.lock().await.clone();
+
+    // 保存中に記録された変更は次の保存の対象になる
+    let (base, snapshot) = {
+        let mut tracker = state.save_tracker.lock().await;
+        (tracker.incremental_base(Path::new(&path)).cloned(), tracker.begin_save())
+    };
+    let saved_index = match base {
+        Some(base) => open_saved_index(&path, base.len).await,
+        None => None,
+    };
+    let result = match saved_index {
+        Some(index) => append_project(state, &path, project, index, &snapshot, context).await,
+        None => write_project(state, &path, project, keyframe_interval, context).await,
+    };
+    {
+        let mut tracker = state.save_tracker.lock().await;
+        match &result {
+            Ok(saved) => tracker.finish_save(&path, saved.bytes),
+            Err(_) => tracker.abort_save(snapshot),
+        }
+    }
+    let saved = result?;
+    // 保存した内容より前の記録は不要になるため、ジャーナルを圧縮する
+    state.write_journal(JournalRecord::Saved { path: path.clone() }).await;
+
+    info!("[Format API] プロジェクト保存完了: {} ({} フレーム, {} レイヤー中 {} レイヤーを書き込み, {} bytes, 追記: {})",
+          path, saved.frame_count, saved.layer_count, saved.layers_written, saved.bytes, saved.incremental);
+    Ok(saved)
+}
+
+/// 前回保存したファイルの索引を読む（追記できない場合は None）
+async fn open_saved_index(path: &str, expected_len: u64) -> Option<kine::KineIndex> {
+    let path = path.to_string();
+    let index = tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::File::open(&path)?;
+        kine::read_kine_index(&mut file)
+    })
+    .await;
+
+    match index {
+        Ok(Ok(index)) if index.file_len != expected_len => {
+            info!("[Format API] 前回の保存後にファイルが変更されたため全体を書き直します");
+            None
+        }
+        Ok(Ok(index)) if index.version != kine::KINE_VERSION => {
+            info!("[Format API] 古い形式のファイルのため全体を書き直します: v{}", index.version);
+            None
+        }
+        Ok(Ok(index)) if index.should_compact() => {
+            info!("[Format API] 不要な領域が増えたため全体を書き直します: {} / {} bytes", index.live_bytes(), index.file_len);
+            None
+        }
+        Ok(Ok(index)) => Some(index),
+        Ok(Err(e)) => {
+            info!("[Format API] 追記保存できないため全体を書き直します: {}", e);
+            None
+        }
+        Err(e) => {
+            error!("[Format API] 索引読み込みタスクエラー: {}", e);
+            None
+        }
+    }
+}
+
+/// 変更されたレイヤーだけを保存済みファイルに追記
+async fn append_project(
+    state: &DrawingState,
+    path: &str,
+    project: Project,
+    index: kine::KineIndex,
+    snapshot: &SaveSnapshot,
+    context: &JobContext,
+) -> Result<ProjectSaveResult, JobError> {
+    let frame_count = project.frames.len();
+    let layer_count = project.frames.iter().map(|frame| frame.layers.len()).sum();
+    let layers = snapshot.layers_to_write(&project, |layer_id| index.layer_chunk(layer_id).is_some());
+    debug!("[Format API] 追記保存: 変更されたフレーム {:?}, 書き込むレイヤー {} 件",
+           snapshot.dirty_frames(&project), layers.len());
+
+    context.report(0.0, "変更されたレイヤーを読み出しています");
+    let layer_ids: Vec<String> = layers.iter().map(|layer| layer.id.clone()).collect();
+    let changed: HashMap<String, RgbaImage> = layer_ids.into_iter()
+        .zip(read_saved_images(state, layers).await?)
+        .collect();
+    context.check_cancelled()?;
+
+    context.report(0.6, "変更されたフレームを書き込んでいます");
+    let file_path = path.to_string();
+    let stats = tokio::task::spawn_blocking(move || {
+        let mut file = std::fs::OpenOptions::new().write(true).open(&file_path)?;
+        let result = kine::append_kine(&mut file, &index, &project, &changed)
+            .and_then(|stats| { file.sync_all()?; Ok(stats) });
+        if result.is_err() {
+            // 書きかけの末尾を取り除き、前回保存した状態に戻す
+            if let Err(e) = file.set_len(index.file_len) {
+                error!("[Format API] 追記に失敗したファイルを元に戻せません: {}", e);
+            }
+        }
+        result
+    })
+    .await
+    .map_err(|e| format!("保存タスクエラー: {}", e))?
+    .map_err(|e| {
+        error!("[Format API] プロジェクトの追記失敗: {}", e);
+        e.to_string()
+    })?;
+
+    Ok(ProjectSaveResult {
+        path: path.to_string(),
+        frame_count,
+        layer_count,
+        bytes: stats.file_len,
+        incremental: true,
+        frames_written: stats.frames_rewritten,
+        layers_written: stats.chunks_written,
+    })
+}
+
+/// 保存するレイヤーとマスクの画像を `Layer::texture_ids` の順に読み出す
+///
+/// マスクは掛けずにそのまま読み、塗りつぶしレイヤーは画素を持たないため空の画像にする。
+async fn read_saved_images(state: &DrawingState, layers: Vec<Layer>) -> Result<Vec<RgbaImage>, String> {
+    let textures: Vec<Layer> = layers.iter()
+        .flat_map(|layer| layer.texture_ids().filter(move |id| layer.fill.is_none() || *id != layer.id))
+        .map(texture_layer)
+        .collect();
+    let mut images = if textures.is_empty() {
+        Vec::new()
+    } else {
+        collect_raster_layers(state, Some(textures)).await?.0.into_iter().map(|layer| layer.image).collect()
+    }
+    .into_iter();
+    let mut saved = Vec::new();
+    for layer in &layers {
+        for texture_id in layer.texture_ids() {
+            saved.push(match layer.fill {
+                Some(_) if texture_id == layer.id => RgbaImage::new(0, 0),
+                _ => images.next().ok_or("画像データ取得エラー: 読み出し結果が不足しています")?,
+            });
+        }
+    }
+    Ok(saved)
+}
+
+/// テクスチャ（レイヤーやマスク）をそのまま読み出すためのレイヤー情報
+pub(crate) fn texture_layer(texture_id: &str) -> Layer {
+    Layer {
+        id: texture_id.to_string(),
+        name: texture_id.to_string(),
+        visible: true,
+        opacity: 1.0,
+        blend_mode: BlendMode::Normal,
+        locked: false,
+        effects: Vec::new(),
+        fill: None,
+        mask: None,
+    }
+}
+
+/// 全フレームを読み出してファイル全体を書き直す
+async fn write_project(
+    state: &DrawingState,
+    path: &str,
+    project: Project,
+    keyframe_interval: Option<u32>,
+    context: &JobContext,
+) -> Result<ProjectSaveResult, JobError> {
+    let mut frames = Vec::with_capacity(project.frames.len());
+    for (index, frame) in project.frames.iter().enumerate() {
+        context.report(0.6 * index as f32 / project.frames.len() as f32, "レイヤーを読み出しています");
+        frames.push(read_saved_images(state, frame.layers.clone()).await?);
+        context.check_cancelled()?;
+    }
+
+    context.report(0.6, "フレームを圧縮しています");
+    let options = kine::KineWriteOptions {
+        keyframe_interval: keyframe_interval.unwrap_or(kine::DEFAULT_KEYFRAME_INTERVAL),
+    };
+    let frame_count = frames.len();
+    let layer_count = frames.iter().map(Vec::len).sum();
+    let data = tokio::task::spawn_blocking(move || kine::encode_kine(&project, &frames, &options))
+        .await
+        .map_err(|e| format!("保存タスクエラー: {}", e))?
+        .map_err(|e| {
+            error!("[Format API] プロジェクトのエンコード失敗: {}", e);
+            e.to_string()
+        })?;
+    context.check_cancelled()?;
+
+    context.report(0.9, "ファイルに書き込んでいます");
+    let bytes = data.len() as u64;
+    tokio::fs::write(path, data).await
+        .map_err(|e| format!("プロジェクトファイルの書き込みに失敗しました: {}", e))?;
+
+    Ok(ProjectSaveResult {
+        path: path.to_string(),
+        frame_count,
+        layer_count,
+        bytes,
+        incremental: false,
+        frames_written: frame_count,
+        layers_written: layer_count,
+    })
+}
+
+/// .kine ファイルを開く
+///
+/// 現在のレイヤーは破棄され、保存されていたレイヤー画像がそのままエンジンに読み込まれる。
+/// 保存されていた操作ログはアンドゥ・リドゥの履歴としてだけ使い、ない場合や読めない場合は
+/// レイヤーの作成と画像の貼り付けが新しい履歴として記録される。
+#[tauri::command]
+pub async fn load_project(
+    path: String,
+    state: State<'_, DrawingState>,
+) -> Result<Project, String> {
+    run_load_project(&state, path, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// .kine 読み込みの本体（ジョブとしても実行される）
+pub(crate) async fn run_load_project(
+    state: &DrawingState,
+    path: String,
+    context: &JobContext,
+) -> Result<Project, JobError> {
+    info!("[Format API] プロジェクトを開く: {}", path);
+
+    if state.collaboration.lock().await.is_some() {
+        return Err(JobError::Failed("共同編集中はプロジェクトを読み込めません".to_string()));
+    }
+
+    context.report(0.0, "ファイルを読み込んでいます");
+    let data = tokio::fs::read(&path).await
+        .map_err(|e| format!("ファイルの読み込みに失敗しました: {}", e))?;
+    let file_len = data.len() as u64;
+    context.check_cancelled()?;
+
+    context.report(0.2, "フレームを展開しています");
+    let (mut project, images, log) = tokio::task::spawn_blocking(move || {
+        let mut document = kine::decode_kine(&data).map_err(|e| {
+            error!("[Format API] 読み込み失敗: {}", e);
+            e.to_string()
+        })?;
+        let mut images = Vec::new();
+        for (frame, frame_images) in document.project.frames.iter().zip(document.frames) {
+            // 塗りつぶしレイヤーは画素を持たない
+            let textures = frame.layers.iter()
+                .flat_map(|layer| layer.texture_ids().map(move |id| (id, layer.fill.is_some() && id == layer.id)));
+            for ((texture_id, _), image) in textures.zip(frame_images).filter(|((_, procedural), _)| !procedural) {
+                images.push((texture_id.to_string(), image));
+            }
+        }
+
+        // 保存された操作ログはアンドゥ用の履歴にだけ使い、なければレイヤー画像から作る
+        let log = match std::mem::take(&mut document.project.history).restored() {
+            Ok(log) if !log.is_empty() => log,
+            result => {
+                if let Err(e) = result {
+                    warn!("[Format API] 操作ログを読めないため、レイヤー画像から履歴を作り直します: {}", e);
+                }
+                let mut operations = Vec::with_capacity(images.len() * 2);
+                for (texture_id, image) in &images {
+                    operations.push(Operation::CreateLayer {
+                        layer_id: texture_id.clone(),
+                        width: image.width(),
+                        height: image.height(),
+                    });
+                    operations.push(Operation::paste_image(texture_id, 0, 0, image)?);
+                }
+                OperationLog::from_operations(operations)
+            }
+        };
+        Ok::<_, String>((document.project, images, log))
+    })
+    .await
+    .map_err(|e| format!("読み込みタスクエラー: {}", e))??;
+    context.check_cancelled()?;
+
+    context.report(0.7, "レイヤーを読み込んでいます");
+    {
+        let mut history_guard = state.history.lock().await;
+        replace_engine_images(state, &images).await?;
+        *history_guard = log.clone();
+        project.history = log;
+        // 開いたファイルが以降のジャーナルと追記保存の起点になる
+        state.save_tracker.lock().await.mark_clean(&path, file_len);
+        state.write_journal(JournalRecord::Saved { path: path.clone() }).await;
+    }
+    // 不正なマーカーは読み込み全体を失敗させず、破棄する
+    if let Err(e) = project.markers.validate() {
+        warn!("[Format API] マーカーを読み込めないため破棄: {}", e);
+        project.markers = FrameMarkers::new();
+    }
+    if let Err(e) = project.validate_scenes() {
+        warn!("[Format API] シーン構成を読み込めないため、全フレームを1つのシーンとして扱います: {}", e);
+        project.scenes.clear();
+    }
+    *state.markers.lock().await = project.markers.clone();
+
+    info!("[Format API] プロジェクトを開きました: {} ({}x{}, {} フレーム)",
+          project.name, project.width, project.height, project.frames.len());
+    Ok(project)
+}
+
+/// 読み込んだレイヤー構成からプロジェクトと再生用の操作列を作る
+fn build_imported_project(
+    name: String,
+    document: file_formats::LayeredDocument,
+) -> Result<(Project, Vec<Operation>), String> {
+    let mut project = Project::new(name, document.width, document.height, IMPORTED_PROJECT_FRAME_RATE);
+    let mut operations = Vec::with_capacity(document.layers.len() * 2);
+    let id_prefix = chrono::Utc::now().timestamp_millis();
+
+    for (index, layer) in document.layers.into_iter().enumerate() {
+        let layer_id = format!("layer_{}_{}", id_prefix, index);
+        operations.push(Operation::CreateLayer {
+            layer_id: layer_id.clone(),
+            width: document.width,
+            height: document.height,
+        });
+        operations.push(Operation::paste_image(&layer_id, 0, 0, &layer.canvas_image(document.width, document.height))?);
+        debug!("[Format API] レイヤー取り込み: {} -> {}", layer.name, layer_id);
+
+        project.frames[0].layers.push(Layer {
+            id: layer_id,
+            name: layer.name,
+            visible: layer.visible,
+            opacity: layer.opacity,
+            blend_mode: layer.blend_mode,
+            locked: false,
+            effects: Vec::new(),
+            fill: None,
+            mask: None,
+        });
+    }
+
+    Ok((project, operations))
+}
+
+/// エンジンからレイヤー画像を読み出し、キャンバスサイズと共に返す
+pub(crate) async fn collect_raster_layers(
+    state: &DrawingState,
+    layers: Option<Vec<Layer>>,
+) -> Result<(Vec<RasterLayer>, u32, u32), String> {
+    read_raster_layers(state, layers, false).await
+}
+
+/// 表示用にレイヤー画像を読み出す（消しゴムで消している途中のレイヤーは確定前のプレビューを使う）
+pub(crate) async fn collect_preview_layers(
+    state: &DrawingState,
+    layers: Option<Vec<Layer>>,
+) -> Result<(Vec<RasterLayer>, u32, u32), String> {
+    read_raster_layers(state, layers, true).await
+}
+
+async fn read_raster_layers(
+    state: &DrawingState,
+    layers: Option<Vec<Layer>>,
+    preview: bool,
+) -> Result<(Vec<RasterLayer>, u32, u32), String> {
+    let sizes = state.layers.lock().await.clone();
+    let layers = layers.unwrap_or_else(|| {
+        let mut ids: Vec<&String> = sizes.keys().filter(|id| *id != QUICK_MASK_TEXTURE_ID).collect();
+        ids.sort();
+        ids.into_iter().map(|id| texture_layer(id)).collect()
+    });
+    if layers.is_empty() {
+        return Err("書き出すレイヤーがありません".to_string());
+    }
+
+    // 退避中のレイヤーは復帰させずに退避ファイルから読む（ロック順序: pager → engine）
+    let pager = state.pager.lock().await;
+    let engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+
+    // 塗りつぶしレイヤーは画素を持たず、キャンバス（最も大きいレイヤー）の大きさで生成する。
+    // 有効なマスクはレイヤーの直後に読み出し、レイヤーの不透明度に掛ける
+    let texture_ids: Vec<String> = layers.iter()
+        .flat_map(|layer| {
+            let own = layer.fill.is_none().then(|| layer.id.clone());
+            let mask = layer.mask.as_ref().filter(|mask| mask.enabled).map(|mask| mask.texture_id.clone());
+            own.into_iter().chain(mask)
+        })
+        .collect();
+    if let Some(missing) = texture_ids.iter().find(|id| !sizes.contains_key(*id)) {
+        return Err(format!("レイヤーが見つかりません: {}", missing));
+    }
+    let canvas_size = sizes.values().fold((0, 0), |(w, h), &(lw, lh)| (w.max(lw), h.max(lh)));
+
+    // GPU 上のレイヤーは読み出しを1回の送信にまとめる
+    let resident_ids: Vec<String> = texture_ids.iter()
+        .filter(|id| pager.get(id).is_none())
+        .cloned()
+        .collect();
+    let resident_images = if preview {
+        engine.get_layer_preview_images(&resident_ids).await
+    } else {
+        engine.get_layer_images(&resident_ids).await
+    };
+    let mut resident_images = resident_images
+        .map_err(|e| format!("画像データ取得エラー: {}", e))?
+        .into_iter();
+    let mut textures = Vec::with_capacity(texture_ids.len());
+    for texture_id in &texture_ids {
+        textures.push(match pager.get(texture_id) {
+            Some(page) => read_page_blocking(page.clone()).await?,
+            None => resident_images.next().ok_or("画像データ取得エラー: 読み出し結果が不足しています")?,
+        });
+    }
+    let mut textures = textures.into_iter();
+
+    let mut raster_layers = Vec::with_capacity(layers.len());
+    let (mut width, mut height) = (0, 0);
+    for layer in layers {
+        let mut image = match &layer.fill {
+            Some(fill) => fill.render(canvas_size.0, canvas_size.1),
+            None => textures.next().ok_or("画像データ取得エラー: 読み出し結果が不足しています")?,
+        };
+        if layer.mask.as_ref().is_some_and(|mask| mask.enabled) {
+            let mask = textures.next().ok_or("画像データ取得エラー: 読み出し結果が不足しています")?;
+            apply_mask(&mut image, &mask);
+        }
+        width = width.max(image.width());
+        height = height.max(image.height());
+        debug!("[Format API] レイヤー読み出し: {} ({}x{})", layer.id, image.width(), image.height());
+
+        raster_layers.push(RasterLayer {
+            name: layer.name,
+            visible: layer.visible,
+            opacity: layer.opacity,
+            blend_mode: layer.blend_mode,
+            offset: (0, 0),
+            image,
+            effects: layer.effects,
+        });
+    }
+
+    Ok((raster_layers, width, height))
+}
@@ -0,0 +1,98 @@
+use crate::drawing_engine::{DrawingEngine, GpuAdapterInfo, GpuPowerPreference, GpuPreference};
+use crate::settings::AppSettings;
+use super::drawing::DrawingState;
+use super::history::rebuild_engine_state;
+use super::settings::SettingsState;
+use log::{info, debug, warn, error};
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State};
+
+/// コンパイル済みパイプラインのキャッシュを置くディレクトリ（取得できなければキャッシュを使わない）
+pub(crate) fn pipeline_cache_dir(app: &AppHandle) -> Option<PathBuf> {
+    match app.path().app_cache_dir() {
+        Ok(dir) => Some(dir.join("pipeline_cache")),
+        Err(e) => {
+            warn!("[GPU API] パイプラインキャッシュを使用しません: {}", e);
+            None
+        }
+    }
+}
+
+/// 利用可能なアダプターを列挙（初期化前でも一覧は取得できる）
+pub(super) async fn current_adapters(state: &DrawingState) -> Vec<GpuAdapterInfo> {
+    match state.engine.lock().await.as_ref() {
+        Some(engine) => engine.list_adapters(),
+        None => DrawingEngine::new().list_adapters(),
+    }
+}
+
+/// 利用可能なGPUアダプターの一覧を取得
+#[tauri::command]
+pub async fn list_gpu_adapters(
+    state: State<'_, DrawingState>,
+) -> Result<Vec<GpuAdapterInfo>, String> {
+    let adapters = current_adapters(&state).await;
+    debug!("[GPU API] アダプター一覧: {} 件", adapters.len());
+    Ok(adapters)
+}
+
+/// 保存されているGPU設定を取得
+#[tauri::command]
+pub async fn get_gpu_preference(settings: State<'_, SettingsState>) -> Result<GpuPreference, String> {
+    Ok(settings.get().await.gpu)
+}
+
+/// 使用するGPUアダプターと電力設定を選択して保存
+///
+/// `adapter_index` は `list_gpu_adapters` の `index`（省略時は電力設定で自動選択）。
+/// 描画エンジンが初期化済みの場合は新しいアダプターで作り直し、操作履歴から
+/// レイヤーを再構築する。
+#[tauri::command]
+pub async fn select_gpu_adapter(
+    adapter_index: Option<usize>,
+    power_preference: Option<GpuPowerPreference>,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<GpuPreference, String> {
+    info!("[GPU API] アダプター選択: {:?} ({:?})", adapter_index, power_preference);
+
+    let adapter = match adapter_index {
+        Some(index) => {
+            let info = current_adapters(&state).await.into_iter().find(|info| info.index == index)
+                .ok_or_else(|| format!("アダプターが見つかりません: {}", index))?;
+            Some(info.id)
+        }
+        None => None,
+    };
+    let preference = GpuPreference {
+        adapter,
+        power_preference: power_preference.unwrap_or_default(),
+    };
+    let updated = settings.update(&app, |current| Ok(AppSettings { gpu: preference.clone(), ..current.clone() })).await?;
+
+    let history_guard = state.history.lock().await;
+    let configured_max = match state.engine.lock().await.as_ref() {
+        Some(engine) => engine.canvas_limits().configured_max,
+        None => {
+            info!("[GPU API] GPU設定を保存（次回の初期化時に適用）");
+            return Ok(preference);
+        }
+    };
+
+    let mut engine = DrawingEngine::new();
+    engine.set_gpu_preference(preference.clone());
+    engine.set_pipeline_cache_dir(pipeline_cache_dir(&app));
+    engine.initialize().await.map_err(|e| {
+        error!("[GPU API] 新しいアダプターでの初期化に失敗: {}", e);
+        format!("初期化エラー: {}", e)
+    })?;
+    engine.set_max_canvas_dimension(configured_max);
+    engine.set_stroke_vertex_limit(updated.stroke_vertex_limit);
+    *state.engine.lock().await = Some(engine);
+
+    rebuild_engine_state(&state, &history_guard).await?;
+
+    info!("[GPU API] 描画エンジンを新しいアダプターで再初期化しました");
+    Ok(preference)
+}
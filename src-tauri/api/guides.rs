@@ -0,0 +1,121 @@
+use crate::guides::{Guide, GuideOrientation, GuideSettings, PerspectiveGuide, SafeAreaGuide};
+use super::drawing::DrawingState;
+use log::{info, debug};
+use tauri::State;
+
+/// 現在のガイド設定を取得
+#[tauri::command]
+pub async fn get_guides(state: State<'_, DrawingState>) -> Result<GuideSettings, String> {
+    Ok(state.guides.lock().await.clone())
+}
+
+/// ガイド設定を置き換える（プロジェクト読み込み時など）
+#[tauri::command]
+pub async fn set_guides(
+    settings: GuideSettings,
+    state: State<'_, DrawingState>,
+) -> Result<GuideSettings, String> {
+    settings.validate().map_err(|e| e.to_string())?;
+    *state.guides.lock().await = settings.clone();
+    info!("[Guide API] ガイド設定を更新: {} 本のガイド", settings.guides.len());
+    Ok(settings)
+}
+
+/// ガイドを追加
+#[tauri::command]
+pub async fn add_guide(
+    orientation: GuideOrientation,
+    position: f32,
+    state: State<'_, DrawingState>,
+) -> Result<Guide, String> {
+    let guide = state.guides.lock().await.add_guide(orientation, position)
+        .map_err(|e| e.to_string())?;
+    debug!("[Guide API] ガイド追加: {} {:?} {}", guide.id, guide.orientation, guide.position);
+    Ok(guide)
+}
+
+/// ガイドを削除
+#[tauri::command]
+pub async fn remove_guide(
+    guide_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<Guide, String> {
+    let guide = state.guides.lock().await.remove_guide(&guide_id)
+        .map_err(|e| e.to_string())?;
+    debug!("[Guide API] ガイド削除: {}", guide_id);
+    Ok(guide)
+}
+
+/// 透視ガイド（消失点）を設定
+#[tauri::command]
+pub async fn set_perspective_guide(
+    perspective: PerspectiveGuide,
+    state: State<'_, DrawingState>,
+) -> Result<GuideSettings, String> {
+    perspective.validate().map_err(|e| e.to_string())?;
+    let mut guides_guard = state.guides.lock().await;
+    guides_guard.perspective = perspective;
+    info!("[Guide API] 透視ガイドを更新: {} 点透視", guides_guard.perspective.vanishing_points.len());
+    Ok(guides_guard.clone())
+}
+
+/// セーフエリア（アクションセーフ・タイトルセーフ）と画面比の帯の表示を設定
+///
+/// キャンバス表示とカメラ再生の表示画像、ガイドのオーバーレイにだけ描かれ、書き出しには含まれない。
+#[tauri::command]
+pub async fn set_safe_area_guide(
+    safe_area: SafeAreaGuide,
+    state: State<'_, DrawingState>,
+) -> Result<GuideSettings, String> {
+    safe_area.validate().map_err(|e| e.to_string())?;
+    let mut guides_guard = state.guides.lock().await;
+    guides_guard.safe_area = safe_area;
+    info!(
+        "[Guide API] セーフエリアを更新: アクション {} / タイトル {} / 画面比 {:?}",
+        guides_guard.safe_area.action_safe, guides_guard.safe_area.title_safe, guides_guard.safe_area.aspect_ratio
+    );
+    Ok(guides_guard.clone())
+}
+
+/// ストロークの点列にスナップと透視補正を適用した結果を取得（描画前のプレビュー用）
+#[tauri::command]
+pub async fn preview_stroke_constraint(
+    points: Vec<(f32, f32)>,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<(f32, f32)>, String> {
+    Ok(state.guides.lock().await.apply_to_points(&points))
+}
+
+/// 座標をスナップ設定に従って吸着させる（カーソル表示用）
+#[tauri::command]
+pub async fn snap_point(
+    x: f32,
+    y: f32,
+    state: State<'_, DrawingState>,
+) -> Result<(f32, f32), String> {
+    Ok(state.guides.lock().await.snap_point((x, y)))
+}
+
+/// ガイド・グリッドのオーバーレイ画像（RGBA）を取得
+///
+/// オーバーレイが非表示の場合は None を返す。
+#[tauri::command]
+pub async fn get_guide_overlay(
+    canvas_width: u32,
+    canvas_height: u32,
+    state: State<'_, DrawingState>,
+) -> Result<Option<Vec<u8>>, String> {
+    if canvas_width == 0 || canvas_height == 0 {
+        return Err(format!("無効なキャンバスサイズです: {}x{}", canvas_width, canvas_height));
+    }
+
+    let settings = state.guides.lock().await.clone();
+    if !settings.visible {
+        return Ok(None);
+    }
+
+    let overlay = tokio::task::spawn_blocking(move || settings.render_overlay(canvas_width, canvas_height))
+        .await
+        .map_err(|e| format!("オーバーレイ描画タスクエラー: {}", e))?;
+    Ok(Some(overlay.into_raw()))
+}
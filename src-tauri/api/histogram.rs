@@ -0,0 +1,59 @@
+use crate::animation::Layer;
+use crate::drawing_engine::{Histogram, HistogramRegion};
+use crate::formats::flatten_layers;
+use super::drawing::DrawingState;
+use super::formats::collect_raster_layers;
+use super::paging::ensure_resident;
+use log::debug;
+use serde::Deserialize;
+use tauri::State;
+
+/// ヒストグラムを求める対象
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum HistogramTarget {
+    /// レイヤーテクスチャ（GPU で集計する）
+    Layer { layer_id: String },
+    /// レイヤーを合成した画像（`layers` を省略すると全レイヤー）
+    Composite {
+        #[serde(default)]
+        layers: Option<Vec<Layer>>,
+    },
+}
+
+/// レイヤーまたは合成画像の範囲（省略時は全体）の赤・緑・青・輝度・アルファのヒストグラムを取得
+///
+/// 画像そのものは返さず、256 段階の集計結果だけを返す。
+/// 合成画像は CPU で合成するため、そのまま CPU で集計する。
+#[tauri::command]
+pub async fn get_histogram(
+    target: HistogramTarget,
+    region: Option<HistogramRegion>,
+    state: State<'_, DrawingState>,
+) -> Result<Histogram, String> {
+    debug!("[Histogram API] ヒストグラム計算: {:?} {:?}", target, region);
+
+    let histogram = match target {
+        HistogramTarget::Layer { layer_id } => {
+            if !state.layers.lock().await.contains_key(&layer_id) {
+                return Err(format!("レイヤーが見つかりません: {}", layer_id));
+            }
+            ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+            let engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+            engine.histogram(&layer_id, region).await
+                .map_err(|e| format!("ヒストグラム計算エラー: {}", e))?
+        }
+        HistogramTarget::Composite { layers } => {
+            let (raster_layers, width, height) = collect_raster_layers(&state, layers).await?;
+            tokio::task::spawn_blocking(move || {
+                Histogram::from_image(&flatten_layers(&raster_layers, width, height), region)
+            })
+            .await
+            .map_err(|e| format!("ヒストグラム計算タスクエラー: {}", e))?
+        }
+    };
+
+    debug!("[Histogram API] ヒストグラム計算完了: {} ピクセル", histogram.pixel_count);
+    Ok(histogram)
+}
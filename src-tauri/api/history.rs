@@ -0,0 +1,713 @@
+use crate::drawing_engine::{CanvasLimits, ContentBounds, PendingResize, DEFAULT_RESIZE_QUIET_PERIOD, MAX_RESIZE_QUIET_PERIOD};
+use crate::history::{self, Operation, OperationLog, HistoryInfo, ReplayFrame, ReplayOptions, StrokeInfo, ValidationIssue};
+use crate::journal::JournalRecord;
+use crate::selection::Selection;
+use super::boundary::{gpu_boundary, CommandError};
+use super::drawing::DrawingState;
+use super::paging::{affected_layers, ensure_resident, page_out_layers};
+use super::settings::SettingsState;
+use image::RgbaImage;
+use log::{info, debug, error};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, State};
+
+/// 再生中に発行するフレームイベント（フロントエンドはレイヤー画像を取得して表示する）
+#[derive(Clone, Serialize)]
+pub struct ReplayFrameEvent {
+    pub layer_id: String,
+    pub frame_index: usize,
+    pub frame_count: usize,
+    pub at_ms: u64,
+}
+
+/// ストローク再生の結果
+#[derive(Serialize)]
+pub struct ReplayResult {
+    pub frame_count: usize,
+    pub duration_ms: u64,
+    pub cancelled: bool,
+}
+
+/// 再ラスタライズの結果
+#[derive(Serialize)]
+pub struct RerasterizeResult {
+    pub width: u32,
+    pub height: u32,
+    /// 新しい解像度で描き直したストローク・線の数
+    pub vector_operations: usize,
+    /// 画素の拡大縮小で済ませたレイヤー（貼り付け画像を含むもの）
+    pub raster_layers: Vec<String>,
+}
+
+/// 操作ログの適用済み部分を再生してエンジン状態を再構築
+///
+/// 渡されたログは今の履歴と同じ `seq` のまま操作を書き換えたもの（解像度変更・投げ縄消去など）の
+/// ことがあるため、チェックポイントは破棄して先頭から再生する。
+pub(crate) async fn rebuild_engine_state(state: &DrawingState, log: &OperationLog) -> Result<(), String> {
+    {
+        let mut checkpoints = state.checkpoints.lock().await;
+        checkpoints.clear(state.engine.lock().await.as_mut());
+    }
+    replay_engine_state(state, log).await
+}
+
+/// 履歴位置だけを動かした操作ログの状態にエンジンを戻す（アンドゥ・リドゥ・履歴の移動）
+///
+/// 移動先以前のチェックポイントから残りの操作だけを再生する。
+pub(crate) async fn restore_engine_state(state: &DrawingState, log: &OperationLog) -> Result<(), String> {
+    replay_engine_state(state, log).await
+}
+
+/// 既存のレイヤーを破棄し、渡された画像（レイヤーID → 画像）だけのエンジン状態にする
+///
+/// 保存したファイルを開くときに使う。画像が正なので操作ログは再生せず、チェックポイントと退避中のレイヤーも破棄する。
+/// ロック順序: checkpoints → pager → engine → layers
+pub(crate) async fn replace_engine_images(state: &DrawingState, images: &[(String, RgbaImage)]) -> Result<(), String> {
+    let mut checkpoints = state.checkpoints.lock().await;
+    let mut pager = state.pager.lock().await;
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    let mut layers_guard = state.layers.lock().await;
+
+    checkpoints.clear(Some(&mut *engine));
+    pager.clear();
+    OperationLog::clear_engine_state(engine, &mut layers_guard);
+    for (layer_id, image) in images {
+        engine.create_layer_texture(layer_id, image.width(), image.height())
+            .and_then(|_| engine.write_layer_image(layer_id, 0, 0, image))
+            .map_err(|e| {
+                error!("[History API] レイヤー画像の書き込みに失敗: {} ({})", layer_id, e);
+                format!("レイヤー画像の書き込みに失敗: {}", e)
+            })?;
+        layers_guard.insert(layer_id.clone(), image.dimensions());
+    }
+
+    info!("[History API] レイヤー画像からエンジン状態を作成: {} レイヤー", images.len());
+    Ok(())
+}
+
+/// 操作ログの適用済み部分をチェックポイントから再生する
+///
+/// 退避中だったレイヤーは再生で作り直した内容を改めて退避し、再構築の後も退避したままにする。
+/// ロック順序: history → checkpoints → pager → engine → layers
+async fn replay_engine_state(state: &DrawingState, log: &OperationLog) -> Result<(), String> {
+    // 退避ファイルは再生前の内容なので破棄し、どのレイヤーが退避中だったかだけ覚えておく
+    let paged = {
+        let mut pager = state.pager.lock().await;
+        let paged = pager.paged_layer_ids();
+        pager.clear();
+        paged
+    };
+
+    let paged: Vec<String> = {
+        let mut checkpoints = state.checkpoints.lock().await;
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+
+        let replayed = checkpoints.rebuild(log, engine, &mut layers_guard).map_err(|e| {
+            error!("[History API] 状態再構築に失敗: {}", e);
+            format!("履歴再生エラー: {}", e)
+        })?;
+        debug!("[History API] 状態再構築: {} / {} 操作を再生", replayed, log.position());
+        paged.into_iter().filter(|layer_id| layers_guard.contains_key(layer_id)).collect()
+    };
+
+    if !paged.is_empty() {
+        let paged_out = page_out_layers(state, &paged).await?;
+        debug!("[History API] 退避中だったレイヤーを再び退避: {} レイヤー", paged_out);
+    }
+    Ok(())
+}
+
+/// キャンバス・レイヤーのサイズ上限（エンジン初期化前は既定値）
+async fn canvas_limits(state: &DrawingState) -> CanvasLimits {
+    state.engine.lock().await.as_ref()
+        .map(|engine| engine.canvas_limits())
+        .unwrap_or_default()
+}
+
+/// 操作列を現在のレイヤーとキャンバスの上限に照らして検証（GPUには触れない）
+pub(crate) async fn validate_batch<'a>(
+    state: &DrawingState,
+    operations: impl IntoIterator<Item = &'a Operation>,
+) -> Vec<ValidationIssue> {
+    let limits = canvas_limits(state).await;
+    let layers = state.layers.lock().await;
+    history::validate_operations(operations, &layers, &limits)
+}
+
+/// 空の状態から再生する操作列を検証（操作ログの読み込み・共同編集の再構築で使う）
+pub(crate) async fn validate_replay<'a>(
+    state: &DrawingState,
+    operations: impl IntoIterator<Item = &'a Operation>,
+) -> Vec<ValidationIssue> {
+    history::validate_operations(operations, &HashMap::new(), &canvas_limits(state).await)
+}
+
+/// 直前の操作を取り消す
+#[tauri::command]
+pub async fn undo(
+    state: State<'_, DrawingState>,
+    settings: State<'_, SettingsState>,
+) -> Result<HistoryInfo, String> {
+    debug!("[History API] アンドゥ");
+
+    // 取り消しは他のサイトへ送られないため、共同編集中に行うと文書がずれる
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中は取り消しできません".to_string());
+    }
+
+    let undo_depth = settings.get().await.undo_depth;
+    let mut history_guard = state.history.lock().await;
+    // 取り消し済みの操作数が設定の上限に達していれば、それ以上は戻れない
+    if undo_depth > 0 && history_guard.len() - history_guard.position() >= undo_depth {
+        return Err(format!("アンドゥの上限（{} 回）に達しました", undo_depth));
+    }
+    let seq = history_guard.undo()
+        .map(|entry| entry.seq)
+        .ok_or("取り消せる操作がありません")?;
+
+    if let Err(e) = restore_engine_state(&state, &history_guard).await {
+        history_guard.redo();
+        return Err(e);
+    }
+
+    let position = history_guard.position();
+    state.mark_unsaved_range(&history_guard, position, position + 1).await;
+    state.write_journal(JournalRecord::Seek { offset: -1 }).await;
+    info!("[History API] アンドゥ完了: 操作 #{}", seq);
+    Ok(history_guard.info())
+}
+
+/// 取り消した操作をやり直す
+#[tauri::command]
+pub async fn redo(
+    state: State<'_, DrawingState>,
+) -> Result<HistoryInfo, String> {
+    debug!("[History API] リドゥ");
+
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中はやり直しできません".to_string());
+    }
+
+    let mut history_guard = state.history.lock().await;
+    let seq = history_guard.redo()
+        .map(|entry| entry.seq)
+        .ok_or("やり直せる操作がありません")?;
+
+    if let Err(e) = restore_engine_state(&state, &history_guard).await {
+        history_guard.undo();
+        return Err(e);
+    }
+
+    let position = history_guard.position();
+    state.mark_unsaved_range(&history_guard, position - 1, position).await;
+    state.write_journal(JournalRecord::Seek { offset: 1 }).await;
+    info!("[History API] リドゥ完了: 操作 #{}", seq);
+    Ok(history_guard.info())
+}
+
+/// 任意の履歴位置へ移動
+#[tauri::command]
+pub async fn seek_history(
+    position: usize,
+    state: State<'_, DrawingState>,
+) -> Result<HistoryInfo, String> {
+    debug!("[History API] 履歴位置へ移動: {}", position);
+
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中は履歴を移動できません".to_string());
+    }
+
+    let mut history_guard = state.history.lock().await;
+    let previous = history_guard.position();
+    history_guard.seek(position).map_err(|e| e.to_string())?;
+
+    if let Err(e) = restore_engine_state(&state, &history_guard).await {
+        let _ = history_guard.seek(previous);
+        return Err(e);
+    }
+
+    state.mark_unsaved_range(&history_guard, previous, position).await;
+    state.write_journal(JournalRecord::Seek { offset: position as i64 - previous as i64 }).await;
+    info!("[History API] 履歴位置移動完了: {} -> {}", previous, position);
+    Ok(history_guard.info())
+}
+
+/// 履歴の状態を取得
+#[tauri::command]
+pub async fn get_history_info(
+    state: State<'_, DrawingState>,
+) -> Result<HistoryInfo, String> {
+    let history_guard = state.history.lock().await;
+    Ok(history_guard.info())
+}
+
+/// カーソル位置にあるストローク
+#[derive(Serialize)]
+pub struct PickedObject {
+    /// 履歴のエントリの `seq`
+    pub seq: u64,
+    pub operation: Operation,
+}
+
+/// レイヤー座標の位置に一番上に描かれているストロークを取得（なければ None）
+///
+/// 履歴からストロークのIDバッファを作ってレイヤーごとに保持し、履歴が変わるまでは作り直さない。
+/// ストロークを選んで移動・削除する操作の起点に使う。
+#[tauri::command]
+pub async fn pick_object(
+    layer_id: String,
+    x: f32,
+    y: f32,
+    state: State<'_, DrawingState>,
+) -> Result<Option<PickedObject>, String> {
+    let size = *state.layers.lock().await.get(&layer_id)
+        .ok_or(format!("レイヤーが見つかりません: {}", layer_id))?;
+    let history_guard = state.history.lock().await;
+    let mut buffers = state.pick_buffers.lock().await;
+    if !buffers.get(&layer_id).is_some_and(|buffer| buffer.is_current(&history_guard, size)) {
+        debug!("[History API] ストロークのIDバッファを作成: {} ({}x{})", layer_id, size.0, size.1);
+        buffers.insert(layer_id.clone(), history_guard.stroke_pick_buffer(&layer_id, size));
+    }
+    Ok(buffers[&layer_id].pick(x, y)
+        .and_then(|seq| history_guard.applied_entry(seq))
+        .map(|entry| PickedObject { seq: entry.seq, operation: entry.operation.clone() }))
+}
+
+/// レイヤーの確定済みのストロークの一覧を取得（`region` を指定するとその範囲に掛かるものだけ）
+#[tauri::command]
+pub async fn list_strokes(
+    layer_id: String,
+    region: Option<ContentBounds>,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<StrokeInfo>, String> {
+    let size = *state.layers.lock().await.get(&layer_id)
+        .ok_or(format!("レイヤーが見つかりません: {}", layer_id))?;
+    let history_guard = state.history.lock().await;
+    let strokes = history_guard.list_strokes(&layer_id, size, region.as_ref());
+    debug!("[History API] ストローク一覧: {} ({} 本)", layer_id, strokes.len());
+    Ok(strokes)
+}
+
+/// 確定済みのストロークを1本だけ取り除く（ストローク単位の消しゴム）
+///
+/// 履歴からそのストロークの操作を取り除いて全操作を再生するため、後から描いた内容はそのまま残る。
+/// 履歴を書き換えるので、取り除いたこと自体はアンドゥできない。
+#[tauri::command]
+pub async fn delete_stroke(
+    stroke_id: u64,
+    state: State<'_, DrawingState>,
+) -> Result<HistoryInfo, String> {
+    info!("[History API] ストローク削除: #{}", stroke_id);
+
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中はストロークを削除できません".to_string());
+    }
+
+    let mut history_guard = state.history.lock().await;
+    let log = history_guard.without_stroke(stroke_id).map_err(|e| e.to_string())?;
+    if let Err(e) = rebuild_engine_state(&state, &log).await {
+        // 失敗した場合は削除前の状態に戻す
+        let _ = rebuild_engine_state(&state, &history_guard).await;
+        return Err(e);
+    }
+    *history_guard = log;
+    state.save_tracker.lock().await.mark_all();
+    state.write_journal(JournalRecord::Base { log: history_guard.clone() }).await;
+
+    info!("[History API] ストローク削除完了: 位置 {}", history_guard.position());
+    Ok(history_guard.info())
+}
+
+/// 投げ縄消去の結果
+#[derive(Serialize)]
+pub struct LassoEraseResult {
+    pub history: HistoryInfo,
+    pub split_strokes: usize,
+    pub removed_strokes: usize,
+    /// 描き直したレイヤー上の範囲（表示側はこの範囲のタイルを取り直す）
+    pub bounds: Option<ContentBounds>,
+}
+
+/// レイヤーの投げ縄（多角形。レイヤー座標）の内側を消す
+///
+/// ストロークは境界で分割して外側の部分を残し、ブラシや貼り付け画像などは画素を消す。
+/// 履歴を書き換えて再生するため、消去自体はアンドゥできない。
+#[tauri::command]
+pub async fn lasso_erase(
+    layer_id: String,
+    polygon: Vec<(f32, f32)>,
+    state: State<'_, DrawingState>,
+) -> Result<LassoEraseResult, String> {
+    info!("[History API] 投げ縄消去: {} ({} 点)", layer_id, polygon.len());
+
+    let (width, height) = *state.layers.lock().await.get(&layer_id)
+        .ok_or(format!("レイヤーが見つかりません: {}", layer_id))?;
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中は投げ縄で消去できません".to_string());
+    }
+
+    let mut history_guard = state.history.lock().await;
+    let (log, summary) = history_guard.lasso_erased(&layer_id, &polygon)
+        .ok_or("投げ縄の範囲が空です")?;
+    if let Err(e) = rebuild_engine_state(&state, &log).await {
+        // 失敗した場合は消去前の状態に戻す
+        let _ = rebuild_engine_state(&state, &history_guard).await;
+        return Err(e);
+    }
+    *history_guard = log;
+    state.save_tracker.lock().await.mark_all();
+    state.write_journal(JournalRecord::Base { log: history_guard.clone() }).await;
+
+    let bounds = Selection::from_polygon(&polygon).and_then(|selection| {
+        let x = selection.x.max(0) as u32;
+        let y = selection.y.max(0) as u32;
+        let right = ((selection.x + selection.width as i32).max(0) as u32).min(width);
+        let bottom = ((selection.y + selection.height as i32).max(0) as u32).min(height);
+        (x < right && y < bottom).then(|| ContentBounds { x, y, width: right - x, height: bottom - y })
+    });
+    info!("[History API] 投げ縄消去完了: 分割 {} 本, 削除 {} 本", summary.split_strokes, summary.removed_strokes);
+    Ok(LassoEraseResult {
+        history: history_guard.info(),
+        split_strokes: summary.split_strokes,
+        removed_strokes: summary.removed_strokes,
+        bounds,
+    })
+}
+
+/// 操作ログ全体を取得（プロジェクト保存用）
+#[tauri::command]
+pub async fn get_operation_log(
+    state: State<'_, DrawingState>,
+) -> Result<OperationLog, String> {
+    let history_guard = state.history.lock().await;
+    debug!("[History API] 操作ログ取得: {} 操作", history_guard.len());
+    Ok(history_guard.clone())
+}
+
+/// 保存された操作ログを読み込み、指定位置（省略時はログの位置）まで再生
+#[tauri::command]
+pub async fn load_operation_log(
+    log: OperationLog,
+    position: Option<usize>,
+    state: State<'_, DrawingState>,
+) -> Result<HistoryInfo, String> {
+    info!("[History API] 操作ログ読み込み: {} 操作", log.len());
+
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中は操作ログを読み込めません".to_string());
+    }
+
+    let mut log = log.restored().map_err(|e| e.to_string())?;
+    if let Some(position) = position {
+        log.seek(position).map_err(|e| e.to_string())?;
+    }
+    // やり直しで適用される取り消し済みの操作も含めて検証する
+    let operations = log.entries().iter().map(|entry| &entry.operation);
+    if let Some(issue) = validate_replay(&state, operations).await.into_iter().next() {
+        error!("[History API] 操作ログに不正な操作 #{}: {}", issue.index, issue.message);
+        return Err(format!("操作ログの操作 #{} が不正です: {}", issue.index, issue.message));
+    }
+
+    rebuild_engine_state(&state, &log).await?;
+
+    let mut history_guard = state.history.lock().await;
+    *history_guard = log;
+    state.save_tracker.lock().await.mark_all();
+    state.write_journal(JournalRecord::Base { log: history_guard.clone() }).await;
+
+    info!("[History API] 操作ログ読み込み完了: 位置 {}", history_guard.position());
+    Ok(history_guard.info())
+}
+
+/// 操作をまとめて適用した結果
+#[derive(Serialize)]
+pub struct OperationBatchResult {
+    /// 検証で見つかった問題（1つでもあれば何も適用しない）
+    pub issues: Vec<ValidationIssue>,
+    /// 適用した操作の数（dry run・問題があった場合は 0）
+    pub operations_applied: usize,
+}
+
+/// 操作列を検証してからまとめて適用（`dry_run` を指定すると検証だけ行う）
+///
+/// 検証はGPUに触れずに行い、問題が1つでもあれば何も適用せずに問題の一覧を返す。
+/// 適用した操作は描画コマンドと同じく1操作ずつ履歴・共同編集へ記録される。
+#[tauri::command]
+pub async fn apply_operations(
+    operations: Vec<Operation>,
+    dry_run: Option<bool>,
+    state: State<'_, DrawingState>,
+) -> Result<OperationBatchResult, CommandError> {
+    let dry_run = dry_run.unwrap_or(false);
+    let issues = validate_batch(&state, &operations).await;
+    if dry_run || !issues.is_empty() {
+        debug!("[History API] 操作の検証: {} 操作 / 問題 {} 件 (dry run: {})", operations.len(), issues.len(), dry_run);
+        return Ok(OperationBatchResult { issues, operations_applied: 0 });
+    }
+
+    gpu_boundary(&state, "apply_operations", async {
+        let operations_applied = operations.len();
+        ensure_resident(&state, affected_layers(&operations).as_deref()).await?;
+        for operation in operations {
+            {
+                let mut engine_guard = state.engine.lock().await;
+                let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+                let mut layers_guard = state.layers.lock().await;
+                operation.apply(engine, &mut layers_guard).map_err(|e| {
+                    error!("[History API] 操作の適用に失敗: {}", e);
+                    format!("操作適用エラー: {}", e)
+                })?;
+            }
+            state.record_operation(operation).await;
+        }
+        info!("[History API] 操作をまとめて適用: {} 操作", operations_applied);
+        Ok(OperationBatchResult { issues, operations_applied })
+    }).await
+}
+
+/// キャンバスの解像度を変更し、ストロークを新しい解像度で描き直す
+///
+/// 画素を拡大縮小する代わりに操作ログの座標を変換して全操作を再生するため、
+/// 拡大してもストロークはぼやけない。貼り付けた画像を含むレイヤーのみ画素の拡大縮小になる。
+#[tauri::command]
+pub async fn rerasterize_canvas(
+    canvas_width: u32,
+    canvas_height: u32,
+    new_width: u32,
+    new_height: u32,
+    state: State<'_, DrawingState>,
+) -> Result<RerasterizeResult, String> {
+    check_resize(&state, (canvas_width, canvas_height), (new_width, new_height)).await?;
+    // 待っている解像度変更の要求は、この変更で古くなるため破棄する
+    state.resize.lock().await.take();
+    rerasterize(&state, (canvas_width, canvas_height), (new_width, new_height)).await
+}
+
+/// 解像度の変更を始められるか確認
+async fn check_resize(
+    state: &DrawingState,
+    (canvas_width, canvas_height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+) -> Result<(), String> {
+    if canvas_width == 0 || canvas_height == 0 || new_width == 0 || new_height == 0 {
+        return Err(format!("無効なキャンバスサイズです: {}x{} -> {}x{}", canvas_width, canvas_height, new_width, new_height));
+    }
+    state.check_canvas_size(new_width, new_height).await?;
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中は解像度を変更できません".to_string());
+    }
+    Ok(())
+}
+
+/// 操作ログの座標を変換して全操作を再生し、キャンバスの解像度を変更
+async fn rerasterize(
+    state: &DrawingState,
+    (canvas_width, canvas_height): (u32, u32),
+    (new_width, new_height): (u32, u32),
+) -> Result<RerasterizeResult, String> {
+    info!("[History API] 再ラスタライズ開始: {}x{} -> {}x{}", canvas_width, canvas_height, new_width, new_height);
+
+    let scale = (new_width as f32 / canvas_width as f32, new_height as f32 / canvas_height as f32);
+    let mut history_guard = state.history.lock().await;
+    let log = history_guard.clone();
+    let (rescaled, summary) = tokio::task::spawn_blocking(move || log.rescaled(scale))
+        .await
+        .map_err(|e| format!("解像度変換タスクエラー: {}", e))?
+        .map_err(|e| e.to_string())?;
+
+    if let Err(e) = rebuild_engine_state(state, &rescaled).await {
+        // 失敗した場合は元の解像度の状態に戻す
+        let _ = rebuild_engine_state(state, &history_guard).await;
+        return Err(e);
+    }
+    *history_guard = rescaled;
+    state.save_tracker.lock().await.mark_all();
+    state.write_journal(JournalRecord::Base { log: history_guard.clone() }).await;
+
+    info!("[History API] 再ラスタライズ完了: ベクター操作 {} 件, 画素拡大縮小レイヤー {:?}",
+          summary.vector_operations, summary.raster_layers);
+    Ok(RerasterizeResult {
+        width: new_width,
+        height: new_height,
+        vector_operations: summary.vector_operations,
+        raster_layers: summary.raster_layers,
+    })
+}
+
+/// キャンバスの解像度変更を要求し、要求が途切れてから適用する（`rerasterize_canvas` の間引き版）
+///
+/// リサイズハンドルのドラッグ中など続けて呼ばれた場合は、最後の要求から `quiet_ms`（既定 250ms）の間
+/// 次の要求がなければ最後のサイズだけを適用する。後から要求が届いた呼び出しと、`commit_canvas_resize`・
+/// `cancel_canvas_resize` で片付けられた呼び出しは何もせずに None を返す。
+#[tauri::command]
+pub async fn request_canvas_resize(
+    canvas_width: u32,
+    canvas_height: u32,
+    new_width: u32,
+    new_height: u32,
+    quiet_ms: Option<u64>,
+    state: State<'_, DrawingState>,
+) -> Result<Option<RerasterizeResult>, String> {
+    check_resize(&state, (canvas_width, canvas_height), (new_width, new_height)).await?;
+    let quiet = quiet_ms.map(Duration::from_millis).unwrap_or(DEFAULT_RESIZE_QUIET_PERIOD).min(MAX_RESIZE_QUIET_PERIOD);
+    let generation = state.resize.lock().await.request((canvas_width, canvas_height), (new_width, new_height));
+    debug!("[History API] 解像度変更を要求: {}x{} (世代 {})", new_width, new_height, generation);
+
+    tokio::time::sleep(quiet).await;
+    let Some(pending) = state.resize.lock().await.take_if_latest(generation) else {
+        return Ok(None);
+    };
+    apply_pending_resize(&state, pending).await.map(Some)
+}
+
+/// 待っている解像度変更をすぐに適用する（リサイズハンドルを離したときなど。待っている要求がなければ None）
+#[tauri::command]
+pub async fn commit_canvas_resize(
+    state: State<'_, DrawingState>,
+) -> Result<Option<RerasterizeResult>, String> {
+    let Some(pending) = state.resize.lock().await.take() else {
+        return Ok(None);
+    };
+    apply_pending_resize(&state, pending).await.map(Some)
+}
+
+/// 待っている解像度変更を破棄する（破棄した要求があれば true）
+#[tauri::command]
+pub async fn cancel_canvas_resize(
+    state: State<'_, DrawingState>,
+) -> Result<bool, String> {
+    let cancelled = state.resize.lock().await.take().is_some();
+    if cancelled {
+        info!("[History API] 解像度変更の要求を破棄");
+    }
+    Ok(cancelled)
+}
+
+/// まとめた解像度変更の要求を適用（元のサイズに戻っていればテクスチャを作り直さない）
+async fn apply_pending_resize(state: &DrawingState, pending: PendingResize) -> Result<RerasterizeResult, String> {
+    info!("[History API] 解像度変更の要求 {} 件をまとめて適用: {:?} -> {:?}", pending.requests, pending.from, pending.to);
+    if pending.from == pending.to {
+        return Ok(RerasterizeResult {
+            width: pending.to.0,
+            height: pending.to.1,
+            vector_operations: 0,
+            raster_layers: Vec::new(),
+        });
+    }
+    // 待っている間に共同編集が始まっていることがあるため確認し直す
+    check_resize(state, pending.from, pending.to).await?;
+    rerasterize(state, pending.from, pending.to).await
+}
+
+/// レイヤーの描画過程を記録時のタイミングで再描画する
+///
+/// レイヤーをクリアしてから操作をフレームごとに適用し、`replay:frame` イベントを発行する。
+/// 再生は履歴に記録されず、完了（または中断）時のレイヤーは再生前と同じ内容になる。
+#[tauri::command]
+pub async fn replay_strokes(
+    layer_id: String,
+    options: Option<ReplayOptions>,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+) -> Result<ReplayResult, String> {
+    info!("[History API] ストローク再生開始: {}", layer_id);
+
+    if !state.layers.lock().await.contains_key(&layer_id) {
+        return Err(format!("レイヤーが見つかりません: {}", layer_id));
+    }
+    ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+
+    let frames = {
+        let history_guard = state.history.lock().await;
+        history::build_replay_plan(history_guard.applied_entries(), &layer_id, &options.unwrap_or_default())
+            .map_err(|e| e.to_string())?
+    };
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    {
+        let mut replay_guard = state.replay.lock().await;
+        if replay_guard.is_some() {
+            return Err("既に再生中です".to_string());
+        }
+        *replay_guard = Some(cancel.clone());
+    }
+
+    let result = play_frames(&app, &state, &layer_id, &frames, &cancel).await;
+    *state.replay.lock().await = None;
+
+    let result = result?;
+    info!("[History API] ストローク再生完了: {} ({} フレーム, 中断: {})", layer_id, result.frame_count, result.cancelled);
+    Ok(result)
+}
+
+/// 再生を中断する（残りの操作は即座に適用される）
+#[tauri::command]
+pub async fn cancel_replay(
+    state: State<'_, DrawingState>,
+) -> Result<bool, String> {
+    match state.replay.lock().await.as_ref() {
+        Some(cancel) => {
+            cancel.store(true, Ordering::SeqCst);
+            debug!("[History API] ストローク再生の中断を要求");
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+async fn play_frames(
+    app: &AppHandle,
+    state: &DrawingState,
+    layer_id: &str,
+    frames: &[ReplayFrame],
+    cancel: &AtomicBool,
+) -> Result<ReplayResult, String> {
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        engine.clear_layer_texture(layer_id, Some(wgpu::Color::TRANSPARENT))
+            .map_err(|e| format!("レイヤークリアエラー: {}", e))?;
+    }
+
+    let started = Instant::now();
+    for (frame_index, frame) in frames.iter().enumerate() {
+        let cancelled = cancel.load(Ordering::SeqCst);
+        if !cancelled {
+            tokio::time::sleep_until((started + Duration::from_millis(frame.at_ms)).into()).await;
+        }
+
+        {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+            let mut layers_guard = state.layers.lock().await;
+            for operation in &frame.operations {
+                operation.apply(engine, &mut layers_guard).map_err(|e| {
+                    error!("[History API] 再生中の操作適用に失敗: {}", e);
+                    format!("ストローク再生エラー: {}", e)
+                })?;
+            }
+        }
+
+        if !cancelled {
+            let _ = app.emit("replay:frame", ReplayFrameEvent {
+                layer_id: layer_id.to_string(),
+                frame_index,
+                frame_count: frames.len(),
+                at_ms: frame.at_ms,
+            });
+        }
+    }
+
+    Ok(ReplayResult {
+        frame_count: frames.len(),
+        duration_ms: started.elapsed().as_millis() as u64,
+        cancelled: cancel.load(Ordering::SeqCst),
+    })
+}
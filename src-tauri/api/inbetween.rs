@@ -0,0 +1,71 @@
+use crate::animation::{frame_strokes, inbetween_strokes, inbetween_times, Frame, MAX_INBETWEENS};
+use super::drawing::DrawingState;
+use log::{info, debug};
+use serde::Serialize;
+use tauri::State;
+
+/// 中割りのプレビュー画像
+#[derive(Serialize)]
+pub struct InbetweenFrame {
+    /// `frame_a` から `frame_b` までの位置（0〜1）
+    pub t: f32,
+    pub width: u32,
+    pub height: u32,
+    /// RGBA（背景は透明）
+    pub data: Vec<u8>,
+}
+
+/// 2つのキーフレームのベクターのストロークを補間し、中割りのプレビュー画像を作る（実験的）
+///
+/// ストロークは描いた順に対応付け、点は弧長で対応付けて補間する。プレビューはレイヤーにも履歴にも残らない。
+#[tauri::command]
+pub async fn generate_inbetweens(
+    frame_a: Frame,
+    frame_b: Frame,
+    count: usize,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<InbetweenFrame>, String> {
+    debug!("[Inbetween API] 中割り生成: {} → {} ({} 枚)", frame_a.id, frame_b.id, count);
+
+    if !(1..=MAX_INBETWEENS).contains(&count) {
+        return Err(format!("中割りの枚数は 1〜{} で指定してください: {}", MAX_INBETWEENS, count));
+    }
+    let (strokes_a, strokes_b) = {
+        let history = state.history.lock().await;
+        let entries = history.applied_entries();
+        (frame_strokes(entries, &frame_a), frame_strokes(entries, &frame_b))
+    };
+    if strokes_a.is_empty() && strokes_b.is_empty() {
+        return Err("キーフレームにベクターのストロークがありません".to_string());
+    }
+
+    let times = inbetween_times(count);
+    let frames: Vec<_> = times.iter()
+        .map(|&t| {
+            inbetween_strokes(&strokes_a, &strokes_b, t).iter()
+                .map(|stroke| stroke.to_draw_stroke())
+                .collect()
+        })
+        .collect();
+
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    let (width, height) = {
+        let layers = state.layers.lock().await;
+        frame_a.layers.iter().chain(&frame_b.layers)
+            .find_map(|layer| layers.get(&layer.id).copied())
+            .ok_or("キーフレームのレイヤーが見つかりません")?
+    };
+    let images = engine.render_stroke_frames(width, height, &frames).await
+        .map_err(|e| format!("中割りの描画エラー: {}", e))?;
+
+    info!("[Inbetween API] 中割り生成完了: {} 枚 ({} / {} 本のストローク)", images.len(), strokes_a.len(), strokes_b.len());
+    Ok(times.into_iter().zip(images)
+        .map(|(t, image)| InbetweenFrame {
+            t,
+            width: image.width(),
+            height: image.height(),
+            data: image.into_raw(),
+        })
+        .collect())
+}
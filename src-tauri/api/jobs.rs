@@ -0,0 +1,185 @@
+use crate::animation::{Layer, Project, SceneExportMode};
+use crate::formats::{ExportSettings, FrameSequenceOptions};
+use crate::jobs::{JobContext, JobError, JobInfo, JobListener, JobQueue};
+use crate::timelapse::TimelapseBuffer;
+use super::drawing::DrawingState;
+use super::formats::{resolve_presets, run_batch_export, run_export_frame_sequence, run_export_psd, run_import_image_layer, run_import_project, run_load_project, run_save_project};
+use super::settings::SettingsState;
+use super::timelapse::run_export_timelapse;
+use log::{info, debug, warn};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// バックグラウンドジョブの状態管理
+pub struct JobState {
+    queue: JobQueue,
+}
+
+impl JobState {
+    pub fn new() -> Self {
+        Self {
+            queue: JobQueue::default(),
+        }
+    }
+
+    pub(crate) fn queue(&self) -> &JobQueue {
+        &self.queue
+    }
+}
+
+impl Default for JobState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// バックグラウンドで実行できる処理（引数は対応するコマンドと同じ）
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobRequest {
+    ExportPsd {
+        path: String,
+        layers: Option<Vec<Layer>>,
+        flatten: Option<bool>,
+        settings: Option<ExportSettings>,
+    },
+    ExportFrameSequence {
+        directory: String,
+        project: Box<Project>,
+        options: Option<FrameSequenceOptions>,
+    },
+    BatchExport {
+        directory: String,
+        project: Box<Project>,
+        presets: Vec<String>,
+        scenes: Option<SceneExportMode>,
+    },
+    ExportTimelapse {
+        path: String,
+        fps: Option<u32>,
+        buffer: Option<TimelapseBuffer>,
+    },
+    ImportProject {
+        path: String,
+    },
+    ImportImageLayer {
+        path: String,
+        layer_id: Option<String>,
+    },
+    SaveProject {
+        path: String,
+        project: Box<Project>,
+        keyframe_interval: Option<u32>,
+    },
+    LoadProject {
+        path: String,
+    },
+}
+
+impl JobRequest {
+    fn kind(&self) -> &'static str {
+        match self {
+            JobRequest::ExportPsd { .. } => "export_psd",
+            JobRequest::ExportFrameSequence { .. } => "export_frame_sequence",
+            JobRequest::BatchExport { .. } => "batch_export",
+            JobRequest::ExportTimelapse { .. } => "export_timelapse",
+            JobRequest::ImportProject { .. } => "import_project",
+            JobRequest::ImportImageLayer { .. } => "import_image_layer",
+            JobRequest::SaveProject { .. } => "save_project",
+            JobRequest::LoadProject { .. } => "load_project",
+        }
+    }
+
+    async fn run(self, state: &DrawingState, settings: &SettingsState, context: &JobContext) -> Result<serde_json::Value, JobError> {
+        match self {
+            JobRequest::ExportPsd { path, layers, flatten, settings } => {
+                to_job_result(run_export_psd(state, path, layers, flatten, settings, context).await?)
+            }
+            JobRequest::ExportFrameSequence { directory, project, options } => {
+                to_job_result(run_export_frame_sequence(state, directory, *project, options, context).await?)
+            }
+            JobRequest::BatchExport { directory, project, presets, scenes } => {
+                let presets = resolve_presets(settings, &presets).await?;
+                to_job_result(run_batch_export(state, directory, *project, presets, scenes.unwrap_or_default(), context).await?)
+            }
+            JobRequest::ExportTimelapse { path, fps, buffer } => {
+                to_job_result(run_export_timelapse(state, path, fps, buffer, context).await?)
+            }
+            JobRequest::ImportProject { path } => {
+                to_job_result(run_import_project(state, path, context).await?)
+            }
+            JobRequest::ImportImageLayer { path, layer_id } => {
+                to_job_result(run_import_image_layer(state, path, layer_id, context).await?)
+            }
+            JobRequest::SaveProject { path, project, keyframe_interval } => {
+                to_job_result(run_save_project(state, path, *project, keyframe_interval, context).await?)
+            }
+            JobRequest::LoadProject { path } => {
+                to_job_result(run_load_project(state, path, context).await?)
+            }
+        }
+    }
+}
+
+pub(crate) fn to_job_result(result: impl Serialize) -> Result<serde_json::Value, JobError> {
+    serde_json::to_value(result).map_err(|e| JobError::Failed(format!("結果のシリアライズに失敗: {}", e)))
+}
+
+/// 状態や進捗を `job:progress` イベントとして発行するリスナー
+pub(crate) fn progress_listener(app: &AppHandle) -> JobListener {
+    let emitter = app.clone();
+    Arc::new(move |info: &JobInfo| {
+        if let Err(e) = emitter.emit("job:progress", info) {
+            warn!("[Job API] 進捗イベントの送信に失敗: {}", e);
+        }
+    })
+}
+
+/// 時間のかかる処理をバックグラウンドジョブとして登録し、ジョブIDを返す
+///
+/// 状態や進捗が変わるたびに `job:progress` イベント（JobInfo）を発行する。
+/// 終了後の結果は `take_job_result` で取得する。
+#[tauri::command]
+pub async fn submit_job(
+    job: JobRequest,
+    app: AppHandle,
+    jobs: State<'_, JobState>,
+) -> Result<u64, String> {
+    let kind = job.kind();
+    debug!("[Job API] ジョブ登録: {}", kind);
+
+    let id = jobs.queue.submit(kind, progress_listener(&app), move |context| async move {
+        let state = app.state::<DrawingState>();
+        let settings = app.state::<SettingsState>();
+        job.run(&state, &settings, &context).await
+    });
+    info!("[Job API] ジョブ #{} を登録: {}", id, kind);
+    Ok(id)
+}
+
+/// ジョブの状態を取得
+#[tauri::command]
+pub async fn get_job(job_id: u64, jobs: State<'_, JobState>) -> Result<JobInfo, String> {
+    jobs.queue.info(job_id).ok_or_else(|| JobError::JobNotFound(job_id).to_string())
+}
+
+/// 全ジョブの状態を取得
+#[tauri::command]
+pub async fn list_jobs(jobs: State<'_, JobState>) -> Result<Vec<JobInfo>, String> {
+    Ok(jobs.queue.list())
+}
+
+/// ジョブの中断を要求
+#[tauri::command]
+pub async fn cancel_job(job_id: u64, jobs: State<'_, JobState>) -> Result<JobInfo, String> {
+    let info = jobs.queue.cancel(job_id).map_err(|e| e.to_string())?;
+    info!("[Job API] ジョブ #{} の中断を要求", job_id);
+    Ok(info)
+}
+
+/// 終了したジョブの結果を取得（取得したジョブは一覧から取り除かれる）
+#[tauri::command]
+pub async fn take_job_result(job_id: u64, jobs: State<'_, JobState>) -> Result<serde_json::Value, String> {
+    jobs.queue.take_result(job_id).map_err(|e| e.to_string())
+}
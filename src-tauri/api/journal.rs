@@ -0,0 +1,158 @@
+use crate::animation::Project;
+use crate::history::{HistoryInfo, OperationLog};
+use crate::jobs::JobContext;
+use crate::journal::{Journal, JournalBase, JournalContents, JournalRecord, JOURNAL_FILE_NAME, RECOVERED_JOURNAL_FILE_NAME};
+use super::drawing::DrawingState;
+use super::formats::run_load_project;
+use super::history::rebuild_engine_state;
+use log::{info, debug, warn, error};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager, State};
+
+/// ジャーナルを置くディレクトリ
+fn journal_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    app.path().app_data_dir()
+        .map(|dir| dir.join("journal"))
+        .map_err(|e| format!("データディレクトリを取得できません: {}", e))
+}
+
+/// 起動時にこのセッションのジャーナルを開く
+///
+/// 前回のセッションのジャーナルが残っていれば（正常に保存されずに終了した場合など）、
+/// 復元できるように別名で退避してから新しいジャーナルを作る。
+pub fn open_session_journal(app: &AppHandle) {
+    let dir = match journal_dir(app) {
+        Ok(dir) => dir,
+        Err(e) => {
+            warn!("[Journal API] ジャーナルを使用しません: {}", e);
+            return;
+        }
+    };
+    let path = dir.join(JOURNAL_FILE_NAME);
+    if path.exists() {
+        if let Err(e) = std::fs::rename(&path, dir.join(RECOVERED_JOURNAL_FILE_NAME)) {
+            error!("[Journal API] 前回のジャーナルを退避できません: {}", e);
+        }
+    }
+
+    match Journal::create(&path) {
+        Ok(journal) => *app.state::<DrawingState>().journal.blocking_lock() = Some(journal),
+        Err(e) => error!("[Journal API] ジャーナルを作成できません: {}", e),
+    }
+}
+
+/// 前回のセッションのジャーナルを読み込む（復元できる変更がなければ None）
+async fn read_recovered(app: &AppHandle) -> Result<Option<(PathBuf, JournalContents)>, String> {
+    let path = journal_dir(app)?.join(RECOVERED_JOURNAL_FILE_NAME);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let read_path = path.clone();
+    let contents = tokio::task::spawn_blocking(move || Journal::read(&read_path))
+        .await
+        .map_err(|e| format!("ジャーナル読み込みタスクエラー: {}", e))?
+        .map_err(|e| e.to_string())?;
+    Ok(contents.has_changes().then_some((path, contents)))
+}
+
+/// 復元できる前回のセッションの情報
+#[derive(Serialize)]
+pub struct RecoverableSession {
+    /// 最後に保存した（または開いた）プロジェクトファイル（未保存なら None）
+    pub saved_path: Option<String>,
+    /// 保存後に記録された操作・履歴移動の数
+    pub record_count: usize,
+    /// 最後の記録が書き込み途中で欠けていた
+    pub truncated: bool,
+}
+
+/// 復元の結果
+#[derive(Serialize)]
+pub struct RecoveredSession {
+    /// 起点として開いたプロジェクト（保存したことがなければ None）
+    pub project: Option<Project>,
+    /// 適用した記録の数
+    pub applied: usize,
+    pub history: HistoryInfo,
+}
+
+/// 前回のセッションに復元できる変更があるか確認
+#[tauri::command]
+pub async fn get_recoverable_session(app: AppHandle) -> Result<Option<RecoverableSession>, String> {
+    let Some((_, contents)) = read_recovered(&app).await? else {
+        return Ok(None);
+    };
+    Ok(Some(RecoverableSession {
+        saved_path: match &contents.base {
+            JournalBase::Saved(path) => Some(path.clone()),
+            _ => None,
+        },
+        record_count: contents.records.len(),
+        truncated: contents.truncated,
+    }))
+}
+
+/// 前回のセッションを復元（最後に保存した状態から、ジャーナルの記録を順に適用する）
+#[tauri::command]
+pub async fn recover_session(
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+) -> Result<RecoveredSession, String> {
+    let (path, contents) = read_recovered(&app).await?.ok_or("復元できるセッションがありません")?;
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中はセッションを復元できません".to_string());
+    }
+    info!("[Journal API] セッション復元開始: {} 件の記録", contents.records.len());
+
+    let (project, mut log) = match &contents.base {
+        JournalBase::Saved(saved_path) => {
+            // 開いたファイルが新しいジャーナルの起点として記録される
+            let project = run_load_project(&state, saved_path.clone(), &JobContext::detached()).await
+                .map_err(|e| format!("保存したプロジェクトを開けません: {}", e))?;
+            let log = state.history.lock().await.clone();
+            (Some(project), log)
+        }
+        JournalBase::Log(log) => (None, log.clone()),
+        JournalBase::Empty => (None, OperationLog::new()),
+    };
+    if !matches!(contents.base, JournalBase::Saved(_)) {
+        state.write_journal(JournalRecord::Base { log: log.clone() }).await;
+    }
+    let applied = contents.apply_to(&mut log);
+    // 旧バージョンで記録されたジャーナルなら線幅の単位を変換する
+    log.upgrade();
+
+    let history = {
+        let mut history_guard = state.history.lock().await;
+        rebuild_engine_state(&state, &log).await?;
+        *history_guard = log;
+        // 保存後の記録を適用したため、保存済みファイルとの差分はレイヤー単位では分からない
+        state.save_tracker.lock().await.mark_all();
+        history_guard.info()
+    };
+    // 復元した記録を新しいセッションのジャーナルにも書き写し、再度のクラッシュに備える
+    for record in contents.records {
+        state.write_journal(record).await;
+    }
+
+    if let Err(e) = tokio::fs::remove_file(&path).await {
+        warn!("[Journal API] 復元済みのジャーナルを削除できません: {}", e);
+    }
+    info!("[Journal API] セッション復元完了: {} 件を適用", applied);
+    Ok(RecoveredSession { project, applied, history })
+}
+
+/// 前回のセッションを復元せずに破棄
+#[tauri::command]
+pub async fn discard_recoverable_session(app: AppHandle) -> Result<(), String> {
+    let path = journal_dir(&app)?.join(RECOVERED_JOURNAL_FILE_NAME);
+    match tokio::fs::remove_file(&path).await {
+        Ok(()) => {
+            debug!("[Journal API] 前回のジャーナルを破棄");
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(format!("ジャーナルの削除に失敗: {}", e)),
+    }
+}
@@ -0,0 +1,140 @@
+use crate::drawing_engine::{DefragmentOptions, MaintenanceTask};
+use crate::journal::JournalRecord;
+use crate::settings::AppSettings;
+use super::drawing::DrawingState;
+use super::settings::SettingsState;
+use super::viewport::refresh_navigator;
+use log::{info, debug, warn};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager, State};
+
+/// アイドルかどうかを確かめる間隔
+const MAINTENANCE_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// アイドル時にジャーナルを圧縮する記録の数
+pub const JOURNAL_COMPACT_RECORDS: usize = 256;
+
+/// `maintenance:autosave` イベントの内容（保存はプロジェクトを持つフロントエンドが行う）
+#[derive(Debug, Clone, Serialize)]
+pub struct AutosaveRequest {
+    /// 最後のストローク・コマンドからの経過時間（ミリ秒）
+    pub idle_ms: u64,
+}
+
+/// 作業を1つ行い、実際に何かを行ったかを返す
+async fn run_task(app: &AppHandle, state: &DrawingState, settings: &AppSettings, task: MaintenanceTask) -> Result<bool, String> {
+    match task {
+        MaintenanceTask::Thumbnail => {
+            let Some((max_size, layers)) = state.navigator_source.lock().await.clone() else {
+                return Ok(false);
+            };
+            let mut navigator = state.navigator.lock().await;
+            refresh_navigator(state, &mut navigator, max_size, layers).await
+        }
+        MaintenanceTask::PoolCleanup => {
+            let mut engine_guard = state.engine.lock().await;
+            let Some(engine) = engine_guard.as_mut() else { return Ok(false) };
+            let report = engine.defragment(&DefragmentOptions { compact_tiles: true, ..DefragmentOptions::default() });
+            info!("[Maintenance API] テクスチャプールを整理: {} 枚解放 ({} bytes), タイル {} 枚",
+                  report.pool.released_textures, report.pool.freed_bytes + report.scratch_freed_bytes, report.compacted_tiles);
+            Ok(report.pool.released_textures > 0 || report.compacted_tiles > 0)
+        }
+        MaintenanceTask::Autosave => {
+            let interval = Duration::from_secs(settings.autosave_interval_secs as u64);
+            if interval.is_zero() || !state.save_tracker.lock().await.is_dirty() {
+                return Ok(false);
+            }
+            let idle_ms = {
+                let idle = state.idle.lock().await;
+                let now = Instant::now();
+                if idle.since_last_run(MaintenanceTask::Autosave, now) < interval {
+                    return Ok(false);
+                }
+                idle.idle_for(now).as_millis() as u64
+            };
+            app.emit("maintenance:autosave", &AutosaveRequest { idle_ms }).map_err(|e| e.to_string())?;
+            info!("[Maintenance API] 自動保存を要求");
+            Ok(true)
+        }
+        MaintenanceTask::JournalCompaction => {
+            // ロック順序: history → journal
+            let history_guard = state.history.lock().await;
+            let mut journal_guard = state.journal.lock().await;
+            let Some(journal) = journal_guard.as_mut() else { return Ok(false) };
+            if journal.has_saved_base() || journal.record_count() < JOURNAL_COMPACT_RECORDS {
+                return Ok(false);
+            }
+            let records = journal.record_count();
+            journal.compact(&JournalRecord::Base { log: history_guard.clone() }).map_err(|e| e.to_string())?;
+            info!("[Maintenance API] ジャーナルを圧縮: {} 件の記録", records);
+            Ok(true)
+        }
+    }
+}
+
+/// アイドルが続いていれば、まだ行っていない作業を1つ行う
+async fn run_next_task(app: &AppHandle, state: &DrawingState, settings: &AppSettings) {
+    let started = Instant::now();
+    let maintenance = &settings.maintenance;
+    let Some(task) = state.idle.lock().await.next_task(started, maintenance.idle_threshold(), &maintenance.tasks) else {
+        return;
+    };
+    // ペンを止めたまま押さえている間も描画中とみなす
+    if !state.active_strokes.lock().await.is_empty() {
+        return;
+    }
+
+    debug!("[Maintenance API] {:?} を実行", task);
+    let ran = match run_task(app, state, settings, task).await {
+        Ok(ran) => ran,
+        Err(e) => {
+            warn!("[Maintenance API] {:?} に失敗: {}", task, e);
+            false
+        }
+    };
+    state.idle.lock().await.finish(task, ran, started, Instant::now());
+}
+
+/// ストローク・コマンドが途切れている間だけ、優先度の低い作業（ナビゲーターの更新・テクスチャプールの整理・
+/// 自動保存の要求・ジャーナルの圧縮）を行う
+///
+/// 作業は設定の時間アイドルが続いたときに1回の確認につき1つだけ行い、描画が再開すると残りは次のアイドルまで待つ。
+#[tauri::command]
+pub async fn start_idle_maintenance(
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+) -> Result<(), String> {
+    let mut maintenance_guard = state.maintenance.lock().await;
+    if maintenance_guard.is_some() {
+        return Err("アイドル時のメンテナンスは既に実行中です".to_string());
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    *maintenance_guard = Some(stop.clone());
+
+    tokio::spawn(async move {
+        info!("[Maintenance API] アイドル時のメンテナンス開始");
+        while !stop.load(Ordering::Relaxed) {
+            tokio::time::sleep(MAINTENANCE_POLL_INTERVAL).await;
+            let state = app.state::<DrawingState>();
+            let settings = app.state::<SettingsState>().get().await;
+            run_next_task(&app, &state, &settings).await;
+        }
+        info!("[Maintenance API] アイドル時のメンテナンス終了");
+    });
+    Ok(())
+}
+
+/// アイドル時のメンテナンスを停止（実行していなかった場合は false）
+#[tauri::command]
+pub async fn stop_idle_maintenance(state: State<'_, DrawingState>) -> Result<bool, String> {
+    match state.maintenance.lock().await.take() {
+        Some(stop) => {
+            stop.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
@@ -0,0 +1,109 @@
+use crate::animation::{Layer, LayerMask};
+use crate::formats::apply_mask;
+use crate::history::Operation;
+use super::drawing::{remove_layer, DrawingState};
+use super::formats::{collect_raster_layers, texture_layer};
+use super::paging::ensure_resident;
+use log::{info, debug};
+use tauri::State;
+
+/// 操作をエンジンに適用してから履歴に記録する
+async fn apply_operations(state: &DrawingState, operations: Vec<Operation>) -> Result<(), String> {
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        for operation in &operations {
+            operation.apply(engine, &mut layers_guard)?;
+        }
+    }
+    for operation in operations {
+        state.record_operation(operation).await;
+    }
+    Ok(())
+}
+
+/// レイヤーに白（すべて表示）で塗ったマスクを追加し、更新したレイヤーを返す
+///
+/// マスクは `<レイヤーID>_mask` のテクスチャとして作られ、作成は履歴に記録される。
+/// マスクを編集するには、描画APIにレイヤーIDの代わりに `mask.texture_id` を渡す。
+#[tauri::command]
+pub async fn add_layer_mask(mut layer: Layer, state: State<'_, DrawingState>) -> Result<Layer, String> {
+    if layer.mask.is_some() {
+        return Err(format!("レイヤーには既にマスクがあります: {}", layer.id));
+    }
+    let texture_id = format!("{}_mask", layer.id);
+    let (width, height) = {
+        let layers_guard = state.layers.lock().await;
+        if layers_guard.contains_key(&texture_id) {
+            return Err(format!("レイヤーが既に存在します: {}", texture_id));
+        }
+        match (layers_guard.get(&layer.id), &layer.fill) {
+            (Some(size), _) => *size,
+            // 塗りつぶしレイヤーはキャンバス（最も大きいレイヤー）の大きさ
+            (None, Some(_)) => layers_guard.values().fold((0, 0), |(w, h), (lw, lh)| (w.max(*lw), h.max(*lh))),
+            (None, None) => return Err(format!("レイヤーが見つかりません: {}", layer.id)),
+        }
+    };
+    if width == 0 || height == 0 {
+        return Err("マスクの大きさを決めるレイヤーがありません".to_string());
+    }
+
+    apply_operations(&state, vec![
+        Operation::CreateLayer { layer_id: texture_id.clone(), width, height },
+        Operation::FillLayer { layer_id: texture_id.clone(), color: [1.0, 1.0, 1.0, 1.0] },
+    ]).await?;
+
+    info!("[Mask API] マスクを追加: {} ({}x{})", texture_id, width, height);
+    layer.mask = Some(LayerMask { texture_id, enabled: true });
+    Ok(layer)
+}
+
+/// マスクをテクスチャごと削除し（履歴に記録される）、更新したレイヤーを返す
+#[tauri::command]
+pub async fn remove_layer_mask(mut layer: Layer, state: State<'_, DrawingState>) -> Result<Layer, String> {
+    let mask = layer.mask.take().ok_or_else(|| format!("レイヤーにマスクがありません: {}", layer.id))?;
+    remove_layer(mask.texture_id.clone(), state).await?;
+    info!("[Mask API] マスクを削除: {}", mask.texture_id);
+    Ok(layer)
+}
+
+/// マスクの有効・無効を切り替え、更新したレイヤーを返す（無効にしてもマスクの画像は残る）
+#[tauri::command]
+pub async fn set_layer_mask_enabled(mut layer: Layer, enabled: bool) -> Result<Layer, String> {
+    let mask = layer.mask.as_mut().ok_or_else(|| format!("レイヤーにマスクがありません: {}", layer.id))?;
+    mask.enabled = enabled;
+    debug!("[Mask API] マスクを{}: {}", if enabled { "有効化" } else { "無効化" }, mask.texture_id);
+    Ok(layer)
+}
+
+/// マスクをレイヤーの不透明度に焼き込んでからマスクを削除し、更新したレイヤーを返す
+///
+/// 焼き込みは画像の貼り付けとして、マスクの削除と共に履歴に記録される。
+#[tauri::command]
+pub async fn apply_layer_mask(mut layer: Layer, state: State<'_, DrawingState>) -> Result<Layer, String> {
+    if layer.fill.is_some() {
+        return Err(format!("塗りつぶしレイヤーにはマスクを焼き込めません（先に画素のレイヤーに変換してください）: {}", layer.id));
+    }
+    let mask = layer.mask.take().ok_or_else(|| format!("レイヤーにマスクがありません: {}", layer.id))?;
+    ensure_resident(&state, Some(std::slice::from_ref(&layer.id))).await?;
+
+    let sources = vec![texture_layer(&layer.id), texture_layer(&mask.texture_id)];
+    let (raster_layers, ..) = collect_raster_layers(&state, Some(sources)).await?;
+    let [source, mask_source]: [_; 2] = raster_layers
+        .try_into()
+        .map_err(|_| "画像データ取得エラー: 読み出し結果が不足しています".to_string())?;
+    let image = tokio::task::spawn_blocking(move || {
+        let mut image = source.image;
+        apply_mask(&mut image, &mask_source.image);
+        image
+    })
+    .await
+    .map_err(|e| format!("マスク焼き込みタスクエラー: {}", e))?;
+
+    apply_operations(&state, vec![Operation::paste_image(&layer.id, 0, 0, &image)?]).await?;
+    remove_layer(mask.texture_id.clone(), state).await?;
+
+    info!("[Mask API] マスクを焼き込み: {} -> {}", mask.texture_id, layer.id);
+    Ok(layer)
+}
@@ -0,0 +1,186 @@
+use crate::memory::{DowngradeAction, MemoryConfig, MemoryMonitor, MemoryReport, MemoryUsage};
+use crate::paging::PRESSURE_KEEP_RADIUS;
+use super::drawing::DrawingState;
+use crate::settings::AppSettings;
+use super::paging::apply_paging;
+use super::settings::SettingsState;
+use log::{info, debug, warn};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+/// メモリ監視の状態管理
+pub struct MemoryState {
+    monitor: Mutex<MemoryMonitor>,
+    /// 実行中の定期監視の停止フラグ
+    polling: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl MemoryState {
+    pub fn new() -> Self {
+        Self {
+            monitor: Mutex::new(MemoryMonitor::default()),
+            polling: Mutex::new(None),
+        }
+    }
+}
+
+impl MemoryState {
+    /// 監視の設定を変更
+    pub(crate) async fn set_config(&self, config: MemoryConfig) -> Result<(), String> {
+        self.monitor.lock().await.set_config(config).map_err(|e| e.to_string())
+    }
+
+    /// 非同期ランタイム外（起動時）から監視の設定を変更
+    pub(crate) fn set_config_blocking(&self, config: MemoryConfig) -> Result<(), String> {
+        self.monitor.blocking_lock().set_config(config).map_err(|e| e.to_string())
+    }
+}
+
+impl Default for MemoryState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// テクスチャ（描画の作業用を含む）・ステージングバッファ・CPU側のレイヤー画像の使用量を集計
+pub(super) async fn measure_memory_usage(state: &DrawingState) -> MemoryUsage {
+    let (texture_bytes, staging_bytes) = match state.engine.lock().await.as_ref() {
+        Some(engine) => (
+            engine.get_texture_memory_stats().map(|(used, ..)| used).unwrap_or(0)
+                + engine.scratch_memory_size(),
+            engine.get_staging_memory_usage(),
+        ),
+        None => (0, 0),
+    };
+    let history_bytes = state.history.lock().await.raster_data_bytes();
+    let timelapse_bytes = state.timelapse.lock().await.as_ref()
+        .map(|recorder| recorder.buffer().bytes_used())
+        .unwrap_or(0);
+
+    MemoryUsage {
+        texture_bytes,
+        staging_bytes,
+        cpu_layer_bytes: history_bytes + timelapse_bytes,
+    }
+}
+
+/// 品質の引き下げを適用
+async fn apply_downgrade(state: &DrawingState, actions: &[DowngradeAction]) {
+    for action in actions {
+        match action {
+            DowngradeAction::ReleaseTexturePool => {
+                // 履歴のチェックポイントだけが使っているテクスチャも手放す（次のアンドゥで作り直される）
+                let mut checkpoints = state.checkpoints.lock().await;
+                if let Some(engine) = state.engine.lock().await.as_mut() {
+                    checkpoints.clear(Some(&mut *engine));
+                    let freed = engine.release_pooled_textures();
+                    info!("[Memory API] 未使用テクスチャを解放: {} bytes", freed);
+                }
+            }
+            DowngradeAction::ReducePreviewResolution { scale } => {
+                if let Some(recorder) = state.timelapse.lock().await.as_mut() {
+                    recorder.set_resolution_scale(*scale);
+                }
+                info!("[Memory API] プレビュー解像度を {:.0}% に縮小", scale * 100.0);
+            }
+            // フレーム構成が `page_frames` で通知済みなら、常駐範囲を狭めて退避
+            DowngradeAction::EvictColdFrames => {
+                if state.pager.lock().await.layout().0.is_empty() {
+                    debug!("[Memory API] フレーム構成が未通知のため退避をスキップ");
+                    continue;
+                }
+                match apply_paging(state, PRESSURE_KEEP_RADIUS).await {
+                    Ok(result) => info!("[Memory API] 非表示フレームを退避: {} レイヤー ({} bytes)",
+                                        result.paged_out, result.swapped_bytes),
+                    Err(e) => warn!("[Memory API] フレームの退避に失敗: {}", e),
+                }
+            }
+        }
+    }
+}
+
+/// 使用量を評価し、必要なら品質を下げて `memory:pressure` イベントを発行
+pub(super) async fn check_memory(app: &AppHandle, state: &DrawingState, memory: &MemoryState) -> MemoryReport {
+    let usage = measure_memory_usage(state).await;
+    let (report, changed) = memory.monitor.lock().await.evaluate(usage);
+
+    apply_downgrade(state, &report.actions).await;
+    if changed {
+        if let Err(e) = app.emit("memory:pressure", &report) {
+            warn!("[Memory API] メモリ警告イベントの送信に失敗: {}", e);
+        }
+    }
+    report
+}
+
+/// 現在のメモリ使用量を評価して取得
+#[tauri::command]
+pub async fn get_memory_report(
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    memory: State<'_, MemoryState>,
+) -> Result<MemoryReport, String> {
+    Ok(check_memory(&app, &state, &memory).await)
+}
+
+/// メモリ監視の設定を取得
+#[tauri::command]
+pub async fn get_memory_config(memory: State<'_, MemoryState>) -> Result<MemoryConfig, String> {
+    Ok(memory.monitor.lock().await.config().clone())
+}
+
+/// メモリ監視の設定（予算・しきい値）を変更して設定に保存
+#[tauri::command]
+pub async fn set_memory_config(
+    config: MemoryConfig,
+    app: AppHandle,
+    memory: State<'_, MemoryState>,
+    settings: State<'_, SettingsState>,
+) -> Result<MemoryConfig, String> {
+    memory.set_config(config.clone()).await?;
+    settings.update(&app, |current| Ok(AppSettings { memory: config.clone(), ..current.clone() })).await?;
+    Ok(config)
+}
+
+/// 設定の間隔でメモリ使用量を定期的に監視する
+#[tauri::command]
+pub async fn start_memory_monitor(
+    app: AppHandle,
+    memory: State<'_, MemoryState>,
+) -> Result<(), String> {
+    let mut polling_guard = memory.polling.lock().await;
+    if polling_guard.is_some() {
+        return Err("メモリ監視は既に実行中です".to_string());
+    }
+    let stop = Arc::new(AtomicBool::new(false));
+    *polling_guard = Some(stop.clone());
+
+    tokio::spawn(async move {
+        info!("[Memory API] メモリ監視開始");
+        while !stop.load(Ordering::Relaxed) {
+            let state = app.state::<DrawingState>();
+            let memory = app.state::<MemoryState>();
+            check_memory(&app, &state, &memory).await;
+
+            let interval = memory.monitor.lock().await.config().poll_interval_ms;
+            tokio::time::sleep(Duration::from_millis(interval as u64)).await;
+        }
+        info!("[Memory API] メモリ監視終了");
+    });
+    Ok(())
+}
+
+/// 定期監視を停止（実行していなかった場合は false）
+#[tauri::command]
+pub async fn stop_memory_monitor(memory: State<'_, MemoryState>) -> Result<bool, String> {
+    match memory.polling.lock().await.take() {
+        Some(stop) => {
+            stop.store(true, Ordering::Relaxed);
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
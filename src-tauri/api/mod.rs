@@ -1,6 +1,6 @@
 use tauri::State;
 use serde::{Deserialize, Serialize};
-use crate::drawing_engine::{DrawingEngine, DrawStroke};
+use crate::drawing_engine::{DrawingEngine, DrawStroke, DrawUniforms};
 use crate::animation::Project;
 use log::{info, error, debug, warn};
 
@@ -8,6 +8,164 @@ use log::{info, error, debug, warn};
 pub mod drawing;
 pub use drawing::*;
 
+// コマンドのパニック境界
+pub mod boundary;
+pub use boundary::*;
+
+// 履歴API
+pub mod history;
+pub use history::*;
+
+// 共同編集API
+pub mod collaboration;
+pub use collaboration::*;
+
+// ブラシAPI
+pub mod brush;
+pub use brush::*;
+
+// ファイル形式API
+pub mod formats;
+pub use formats::*;
+
+// タイムラプスAPI
+pub mod timelapse;
+pub use timelapse::*;
+
+// ガイドAPI
+pub mod guides;
+pub use guides::*;
+
+// バックグラウンドジョブAPI
+pub mod jobs;
+pub use jobs::*;
+
+// メモリ監視API
+pub mod memory;
+pub use memory::*;
+
+// アイドル時のメンテナンスAPI
+pub mod maintenance;
+pub use maintenance::*;
+
+// コマンドの記録・再生API
+pub mod recording;
+pub use recording::*;
+
+// フレームページングAPI
+pub mod paging;
+pub use paging::*;
+
+// バイナリ転送API
+pub mod transfer;
+pub use transfer::*;
+
+// GPU選択API
+pub mod gpu;
+pub use gpu::*;
+
+// 設定API
+pub mod settings;
+pub use settings::*;
+
+// ショートカットAPI
+pub mod shortcuts;
+pub use shortcuts::*;
+
+// ペンタブレットAPI
+pub mod tablet;
+pub use tablet::*;
+
+// 無限キャンバスAPI
+pub mod viewport;
+pub use viewport::*;
+
+// 選択範囲API
+pub mod selection;
+pub use selection::*;
+
+// クリップボードAPI
+pub mod clipboard;
+pub use clipboard::*;
+
+// ファイルのドラッグ&ドロップ
+pub mod file_drop;
+pub use file_drop::*;
+
+// セッション復元API
+pub mod journal;
+pub use journal::*;
+
+// ベンチマークAPI
+pub mod benchmark;
+pub use benchmark::*;
+
+// 中割りAPI
+pub mod inbetween;
+pub use inbetween::*;
+
+// アニメーション再生API
+pub mod player;
+pub use player::*;
+
+// タイムラインAPI
+pub mod timeline;
+pub use timeline::*;
+
+// シーンAPI
+pub mod scenes;
+pub use scenes::*;
+
+// カメラAPI
+pub mod camera;
+pub use camera::*;
+
+// レイヤー効果API
+pub mod effects;
+pub use effects::*;
+
+// 塗りつぶしレイヤーAPI
+pub mod fill;
+pub use fill::*;
+
+// レイヤーマスクAPI
+pub mod masks;
+pub use masks::*;
+
+// ヒストグラムAPI
+pub mod histogram;
+pub use histogram::*;
+
+// パレット抽出API
+pub mod palette;
+pub use palette::*;
+
+// プロジェクト整理API
+pub mod cleanup;
+pub use cleanup::*;
+
+// 診断レポートAPI
+pub mod diagnostics;
+pub use diagnostics::*;
+
+// シェーダーのホットリロードAPI（開発ビルドのみ）
+#[cfg(debug_assertions)]
+pub mod shaders;
+#[cfg(debug_assertions)]
+pub use shaders::*;
+
+// LAN同期API（collab-server フィーチャー）
+#[cfg(feature = "collab-server")]
+pub mod sync;
+#[cfg(feature = "collab-server")]
+pub use sync::*;
+
+// スクリプトAPI（scripting フィーチャー）
+#[cfg(feature = "scripting")]
+pub mod scripting;
+#[cfg(feature = "scripting")]
+pub use scripting::*;
+
 #[derive(Deserialize)]
 pub struct CreateProjectArgs {
     pub name: String,
@@ -43,6 +201,9 @@ pub struct DrawStrokeArgs {
     pub points: Vec<DrawStrokePoint>,
     pub color: [f32; 4],
     pub base_width: f32,
+    /// 入力デバイスのID（筆圧カーブのプロファイル選択用）
+    #[serde(default)]
+    pub device_id: Option<String>,
     pub canvas_width: u32,
     pub canvas_height: u32,
 }
@@ -136,6 +297,17 @@ pub async fn create_layer(
     }
 }
 
+/// スクリーン座標（キャンバス要素のピクセル）からレイヤーのピクセル座標への表示行列
+fn screen_view(engine: &DrawingEngine, layer_id: &str, canvas_size: (u32, u32)) -> [[f32; 4]; 4] {
+    match engine.layer_texture_size(layer_id) {
+        Some((width, height)) if canvas_size.0 > 0 && canvas_size.1 > 0 => DrawUniforms::scale_translate(
+            (width as f32 / canvas_size.0 as f32, height as f32 / canvas_size.1 as f32),
+            (0.0, 0.0),
+        ),
+        _ => DrawUniforms::IDENTITY,
+    }
+}
+
 #[tauri::command]
 pub async fn draw_line(
     args: DrawLineArgs,
@@ -146,19 +318,14 @@ pub async fn draw_line(
            args.start_x, args.start_y, args.end_x, args.end_y, args.color, args.width);
     
     let engine_arc = drawing_engine.inner();
-    let engine = engine_arc.lock().await;
+    let mut engine = engine_arc.lock().await;
     
-    // スクリーン座標を正規化座標に変換
-    let start = engine.screen_to_normalized(
-        (args.start_x, args.start_y), 
-        (args.canvas_width, args.canvas_height)
-    );
-    let end = engine.screen_to_normalized(
-        (args.end_x, args.end_y), 
-        (args.canvas_width, args.canvas_height)
-    );
+    let mut stroke = DrawStroke::new(args.color, args.width);
+    stroke.add_point(args.start_x, args.start_y, 1.0);
+    stroke.add_point(args.end_x, args.end_y, 1.0);
+    let view = screen_view(&engine, &args.layer_id, (args.canvas_width, args.canvas_height));
     
-    match engine.draw_line_to_layer(&args.layer_id, start, end, args.color, args.width) {
+    match engine.draw_mesh_to_layer_with_view(&args.layer_id, &stroke.to_mesh(), view) {
         Ok(_) => {
             info!("[API] 線描画成功: {}", args.layer_id);
             Ok(DrawResult {
@@ -177,24 +344,24 @@ pub async fn draw_line(
 pub async fn draw_stroke(
     args: DrawStrokeArgs,
     drawing_engine: State<'_, std::sync::Arc<tokio::sync::Mutex<DrawingEngine>>>,
+    settings: State<'_, SettingsState>,
 ) -> Result<DrawResult, String> {
     info!("[API] draw_stroke コマンド呼び出し: {} ({} 点)", args.layer_id, args.points.len());
     
+    let curve = *settings.get().await.tablet.curve_for(args.device_id.as_deref());
+    
     let engine_arc = drawing_engine.inner();
-    let engine = engine_arc.lock().await;
+    let mut engine = engine_arc.lock().await;
     
     // ストロークを作成
     let mut stroke = DrawStroke::new(args.color, args.base_width);
     
     for point in args.points {
-        let norm_pos = engine.screen_to_normalized(
-            (point.x, point.y), 
-            (args.canvas_width, args.canvas_height)
-        );
-        stroke.add_point(norm_pos.0, norm_pos.1, point.pressure);
+        stroke.add_point(point.x, point.y, curve.apply(point.pressure));
     }
+    let view = screen_view(&engine, &args.layer_id, (args.canvas_width, args.canvas_height));
     
-    match engine.draw_stroke_to_layer(&args.layer_id, &stroke) {
+    match engine.draw_mesh_to_layer_with_view(&args.layer_id, &stroke.to_mesh(), view) {
         Ok(_) => {
             info!("[API] ストローク描画成功: {}", args.layer_id);
             Ok(DrawResult {
@@ -0,0 +1,162 @@
+use crate::history::Operation;
+use crate::paging::{self, FrameLayers, PagedLayer, DEFAULT_KEEP_RADIUS};
+use super::drawing::DrawingState;
+use log::{info, debug, warn};
+use serde::Serialize;
+use tauri::State;
+
+/// フレームページングの結果
+#[derive(Debug, Serialize)]
+pub struct PagingResult {
+    pub paged_out: usize,
+    pub paged_in: usize,
+    /// 退避ファイルの合計サイズ
+    pub swapped_bytes: u64,
+    pub paged_layers: Vec<String>,
+}
+
+/// 操作が対象とするレイヤー（キャンバス全体への操作を含む場合は None = 全レイヤー）
+pub(crate) fn affected_layers<'a>(operations: impl IntoIterator<Item = &'a Operation>) -> Option<Vec<String>> {
+    operations.into_iter()
+        .map(|operation| operation.layer_id().map(str::to_string))
+        .collect()
+}
+
+/// 退避中のレイヤーをGPUテクスチャに復帰させる（None は退避中の全レイヤー）
+///
+/// 描画・読み出しの前に呼ぶことで、退避はAPIの呼び出し側から見えなくなる。
+/// ロック順序: history → pager → engine
+pub(crate) async fn ensure_resident(state: &DrawingState, layer_ids: Option<&[String]>) -> Result<usize, String> {
+    let mut pager = state.pager.lock().await;
+    let targets: Vec<String> = match layer_ids {
+        Some(ids) => ids.iter().filter(|id| pager.is_paged(id)).cloned().collect(),
+        None => pager.paged_layer_ids(),
+    };
+
+    for layer_id in &targets {
+        let page = pager.take(layer_id).map_err(|e| e.to_string())?;
+        let image = match read_page_blocking(page.clone()).await {
+            Ok(image) => image,
+            Err(e) => {
+                // 読めなかった退避ファイルは残しておき、再試行できるようにする
+                pager.insert(layer_id, page);
+                return Err(e);
+            }
+        };
+
+        let uploaded = match state.engine.lock().await.as_mut() {
+            Some(engine) => engine.create_layer_texture(layer_id, page.width, page.height)
+                .and_then(|_| engine.write_layer_image(layer_id, 0, 0, &image))
+                .map_err(|e| format!("レイヤー復帰エラー: {}", e)),
+            None => Err("描画エンジンが初期化されていません".to_string()),
+        };
+        if let Err(e) = uploaded {
+            pager.insert(layer_id, page);
+            return Err(e);
+        }
+        pager.release(&page);
+        debug!("[Paging API] レイヤー復帰: {} ({}x{})", layer_id, page.width, page.height);
+    }
+    Ok(targets.len())
+}
+
+/// レイヤーの画像をディスクへ退避し、GPUテクスチャを解放する
+///
+/// 退避中のレイヤーは `layers` に残るため、存在確認やレイヤー一覧には影響しない。
+/// 履歴のチェックポイントが共有しているテクスチャは解放されないため、退避する前に破棄する。
+pub(crate) async fn page_out_layers(state: &DrawingState, layer_ids: &[String]) -> Result<usize, String> {
+    let sizes = state.layers.lock().await.clone();
+    if layer_ids.iter().any(|layer_id| sizes.contains_key(layer_id)) {
+        let mut checkpoints = state.checkpoints.lock().await;
+        checkpoints.clear(state.engine.lock().await.as_mut());
+    }
+    let mut pager = state.pager.lock().await;
+
+    let mut paged_out = 0;
+    for layer_id in layer_ids {
+        if pager.is_paged(layer_id) || !sizes.contains_key(layer_id) {
+            continue;
+        }
+        // 読み出しから解放まで engine を保持し、その間の描画が失われないようにする
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let image = engine.get_layer_image(layer_id).await
+            .map_err(|e| format!("画像データ取得エラー: {}", e))?;
+
+        let path = pager.allocate_path().map_err(|e| e.to_string())?;
+        let (width, height) = image.dimensions();
+        let write_path = path.clone();
+        let bytes = tokio::task::spawn_blocking(move || paging::write_page(&write_path, &image))
+            .await
+            .map_err(|e| format!("退避タスクエラー: {}", e))?
+            .map_err(|e| e.to_string())?;
+
+        engine.remove_layer_texture(layer_id);
+        pager.insert(layer_id, PagedLayer { path, width, height, bytes });
+        paged_out += 1;
+    }
+    Ok(paged_out)
+}
+
+/// 退避ファイルを読み込む（CPU側で画像が必要なだけのときは復帰させずに使う）
+pub(crate) async fn read_page_blocking(page: PagedLayer) -> Result<image::RgbaImage, String> {
+    tokio::task::spawn_blocking(move || paging::read_page(&page))
+        .await
+        .map_err(|e| format!("退避ファイル読み込みタスクエラー: {}", e))?
+        .map_err(|e| e.to_string())
+}
+
+/// 記録済みのフレーム構成に従い、再生位置から `keep_radius` フレームより遠いレイヤーを退避
+pub(crate) async fn apply_paging(state: &DrawingState, keep_radius: usize) -> Result<PagingResult, String> {
+    let plan = {
+        let pager = state.pager.lock().await;
+        let (frames, playhead) = pager.layout();
+        paging::plan_paging(frames, playhead, keep_radius)
+    };
+
+    let paged_in = ensure_resident(state, Some(&plan.warm)).await?;
+    let paged_out = page_out_layers(state, &plan.cold).await?;
+
+    let pager = state.pager.lock().await;
+    Ok(PagingResult {
+        paged_out,
+        paged_in,
+        swapped_bytes: pager.swapped_bytes(),
+        paged_layers: pager.paged_layer_ids(),
+    })
+}
+
+/// フレーム構成と再生位置を通知し、遠いフレームのレイヤーをディスクへ退避する
+///
+/// `frames` はフレーム順のレイヤーID一覧。再生位置の近くに戻ったレイヤーは復帰させる。
+/// 退避中のレイヤーも描画・読み出しの際に自動で復帰するため、この呼び出しは最適化のみに影響する。
+#[tauri::command]
+pub async fn page_frames(
+    frames: Vec<FrameLayers>,
+    playhead: usize,
+    keep_radius: Option<usize>,
+    state: State<'_, DrawingState>,
+) -> Result<PagingResult, String> {
+    let keep_radius = keep_radius.unwrap_or(DEFAULT_KEEP_RADIUS);
+    debug!("[Paging API] フレームページング: {} フレーム, 再生位置 {}, 範囲 ±{}", frames.len(), playhead, keep_radius);
+    if playhead >= frames.len() && !frames.is_empty() {
+        return Err(format!("再生位置がフレーム数を超えています: {} >= {}", playhead, frames.len()));
+    }
+
+    state.pager.lock().await.set_layout(frames, playhead);
+    let result = apply_paging(&state, keep_radius).await?;
+    info!("[Paging API] フレームページング完了: 退避 {} / 復帰 {} ({} bytes)",
+          result.paged_out, result.paged_in, result.swapped_bytes);
+    Ok(result)
+}
+
+/// 退避中のレイヤーをすべて復帰させる
+#[tauri::command]
+pub async fn restore_paged_layers(state: State<'_, DrawingState>) -> Result<usize, String> {
+    let restored = ensure_resident(&state, None).await.map_err(|e| {
+        warn!("[Paging API] レイヤー復帰に失敗: {}", e);
+        e
+    })?;
+    info!("[Paging API] 全レイヤー復帰: {} レイヤー", restored);
+    Ok(restored)
+}
@@ -0,0 +1,32 @@
+use crate::animation::Layer;
+use crate::formats::flatten_layers;
+use crate::palette::{self, PaletteColor, PALETTE_SAMPLE_SIZE};
+use super::drawing::DrawingState;
+use super::formats::collect_raster_layers;
+use log::{info, debug};
+use tauri::State;
+
+/// 合成した画像（`layers` を省略すると全レイヤー）から主な色を最大 `n_colors` 色抽出する
+///
+/// 長辺 256 ピクセルに縮小してから数えるため、大きなキャンバスでも一定の時間で終わる。
+/// 結果は画像に占める割合の大きい順。
+#[tauri::command]
+pub async fn extract_palette(
+    n_colors: usize,
+    layers: Option<Vec<Layer>>,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<PaletteColor>, String> {
+    debug!("[Palette API] パレット抽出: {} 色", n_colors);
+
+    let (raster_layers, width, height) = collect_raster_layers(&state, layers).await?;
+    let palette = tokio::task::spawn_blocking(move || {
+        let composite = flatten_layers(&raster_layers, width, height);
+        palette::extract_palette(&palette::downsample(&composite, PALETTE_SAMPLE_SIZE), n_colors)
+    })
+    .await
+    .map_err(|e| format!("パレット抽出タスクエラー: {}", e))?
+    .map_err(|e| e.to_string())?;
+
+    info!("[Palette API] パレット抽出完了: {} 色", palette.len());
+    Ok(palette)
+}
@@ -0,0 +1,224 @@
+use crate::animation::{PlaybackOptions, Player};
+use log::{info, debug};
+use serde::Serialize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+/// アニメーションの再生状態
+pub struct PlayerState {
+    player: Mutex<Player>,
+    /// 再生中のタスクの停止フラグ
+    playing: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl PlayerState {
+    pub fn new() -> Self {
+        Self {
+            player: Mutex::new(Player::new()),
+            playing: Mutex::new(None),
+        }
+    }
+
+    /// 再生中なら止める（止めた場合は true）
+    async fn stop(&self) -> bool {
+        match self.playing.lock().await.take() {
+            Some(stop) => {
+                stop.store(true, Ordering::SeqCst);
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub(crate) async fn status(&self) -> PlayerStatus {
+        let playing = self.playing.lock().await.is_some();
+        let player = self.player.lock().await;
+        PlayerStatus::new(&player, playing)
+    }
+}
+
+impl Default for PlayerState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 再生位置と設定（`player:frame-changed` イベントの内容）
+#[derive(Debug, Clone, Serialize)]
+pub struct PlayerStatus {
+    pub frame: usize,
+    pub frame_count: usize,
+    pub playing: bool,
+    pub options: PlaybackOptions,
+}
+
+impl PlayerStatus {
+    fn new(player: &Player, playing: bool) -> Self {
+        Self {
+            frame: player.current(),
+            frame_count: player.frame_count(),
+            playing,
+            options: player.options().clone(),
+        }
+    }
+}
+
+fn emit_frame_changed(app: &AppHandle, status: &PlayerStatus) {
+    if let Err(e) = app.emit("player:frame-changed", status) {
+        debug!("[Player API] フレーム変更イベントの発行に失敗: {}", e);
+    }
+}
+
+/// タイムラインのフレーム構成（各フレームの表示時間・秒）を設定する
+///
+/// フレームを追加・削除したら呼び直す。再生範囲がはみ出す場合は既定の範囲に戻す。
+#[tauri::command]
+pub async fn set_player_frames(
+    durations: Vec<f32>,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    debug!("[Player API] フレーム構成を設定: {} フレーム", durations.len());
+    player.player.lock().await.set_frames(durations);
+    Ok(player.status().await)
+}
+
+/// 再生範囲（イン・アウト点）・往復・繰り返し回数を設定する
+#[tauri::command]
+pub async fn set_playback_options(
+    options: PlaybackOptions,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    player.player.lock().await.set_options(options)?;
+    info!("[Player API] 再生設定を更新");
+    Ok(player.status().await)
+}
+
+#[tauri::command]
+pub async fn get_player_status(player: State<'_, PlayerState>) -> Result<PlayerStatus, String> {
+    Ok(player.status().await)
+}
+
+/// 再生範囲を再生する（フレームが変わるたびに `player:frame-changed` イベントを発行する）
+///
+/// 再生し終えると `playing: false` のイベントを発行して止まる。再生中に呼ぶと最初からやり直す。
+#[tauri::command]
+pub async fn start_playback(
+    app: AppHandle,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    let stop = Arc::new(AtomicBool::new(false));
+    {
+        let mut playing_guard = player.playing.lock().await;
+        let mut player_guard = player.player.lock().await;
+        if player_guard.frame_count() == 0 {
+            return Err("再生するフレームがありません".to_string());
+        }
+        if let Some(previous) = playing_guard.replace(stop.clone()) {
+            previous.store(true, Ordering::SeqCst);
+        }
+        player_guard.start();
+    }
+    let status = player.status().await;
+    emit_frame_changed(&app, &status);
+
+    info!("[Player API] 再生開始: フレーム {} ({:?})", status.frame, status.options.mode);
+    tokio::spawn(run_playback(app, stop));
+    Ok(status)
+}
+
+async fn run_playback(app: AppHandle, stop: Arc<AtomicBool>) {
+    let state = app.state::<PlayerState>();
+    let mut deadline = tokio::time::Instant::now();
+    loop {
+        deadline += {
+            let player = state.player.lock().await;
+            player.frame_duration(player.current())
+        };
+        // 表示時間は前のフレームの予定時刻から数え、処理の遅れを溜めない
+        tokio::time::sleep_until(deadline).await;
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+
+        let mut playing_guard = state.playing.lock().await;
+        // 止められた後や、新しい再生に置き換わった後は何もしない
+        if stop.load(Ordering::SeqCst) {
+            break;
+        }
+        let mut player = state.player.lock().await;
+        let advanced = player.advance();
+        if advanced.is_none() {
+            playing_guard.take();
+        }
+        let status = PlayerStatus::new(&player, advanced.is_some());
+        drop(player);
+        drop(playing_guard);
+
+        emit_frame_changed(&app, &status);
+        if advanced.is_none() {
+            info!("[Player API] 再生終了: フレーム {}", status.frame);
+            break;
+        }
+    }
+}
+
+/// 再生を止める（位置はそのまま。再生していなかった場合は playing: false のまま返す）
+#[tauri::command]
+pub async fn stop_playback(
+    app: AppHandle,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    let stopped = player.stop().await;
+    let status = player.status().await;
+    if stopped {
+        info!("[Player API] 再生停止: フレーム {}", status.frame);
+        emit_frame_changed(&app, &status);
+    }
+    Ok(status)
+}
+
+/// 1フレーム進める（再生中なら止める。範囲の最後からは最初に戻る）
+#[tauri::command]
+pub async fn step_forward(
+    app: AppHandle,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    move_to(&app, &player, |player| Ok(player.step_forward())).await
+}
+
+/// 1フレーム戻す（再生中なら止める。範囲の最初からは最後に移る）
+#[tauri::command]
+pub async fn step_backward(
+    app: AppHandle,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    move_to(&app, &player, |player| Ok(player.step_backward())).await
+}
+
+/// 指定したフレームに移動する（再生中なら止める）
+#[tauri::command]
+pub async fn goto_frame(
+    frame: usize,
+    app: AppHandle,
+    player: State<'_, PlayerState>,
+) -> Result<PlayerStatus, String> {
+    move_to(&app, &player, |player| player.goto(frame)).await
+}
+
+async fn move_to(
+    app: &AppHandle,
+    player: &PlayerState,
+    step: impl FnOnce(&mut Player) -> Result<usize, String>,
+) -> Result<PlayerStatus, String> {
+    if player.player.lock().await.frame_count() == 0 {
+        return Err("フレームがありません".to_string());
+    }
+    player.stop().await;
+    let frame = step(&mut *player.player.lock().await)?;
+    debug!("[Player API] フレーム移動: {}", frame);
+    let status = player.status().await;
+    emit_frame_changed(app, &status);
+    Ok(status)
+}
@@ -0,0 +1,264 @@
+use crate::history::OperationLog;
+use crate::ipc::recording::{is_recorded_command, should_record, CommandRecorder, RecordedCommand, Recording};
+use crate::journal::JournalRecord;
+use super::drawing::{
+    begin_stroke, cancel_all_strokes, cancel_stroke, cleanup_textures, clear_layer, create_drawing_layer,
+    defragment_textures, draw_line_on_layer, draw_pixel_stroke_on_layer, draw_stroke_on_layer, end_stroke,
+    extend_stroke, extend_stroke_packed, fill_layer, flip_canvas_horizontal, get_layer_image_data,
+    get_layer_image_zoomed, initialize_drawing_engine, remove_layer, render_canvas_view, rotate_canvas_180,
+    rotate_canvas_90, DrawingState,
+};
+use super::fill::bucket_fill;
+use super::history::{rebuild_engine_state, redo, undo};
+use super::paging::{page_frames, restore_paged_layers};
+use super::selection::SelectionState;
+use super::settings::SettingsState;
+use log::{info, debug, warn, error};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::ipc::{Invoke, InvokeBody};
+use tauri::{AppHandle, Manager, Runtime, State};
+
+/// 描画コマンドの記録の状態管理
+///
+/// 記録はコマンドの実行前に IPC のスレッドから同期的に書くため、非同期のロックは使わない。
+pub struct RecordingState {
+    recorder: Mutex<Option<CommandRecorder>>,
+}
+
+impl RecordingState {
+    pub fn new() -> Self {
+        Self {
+            recorder: Mutex::new(None),
+        }
+    }
+}
+
+impl Default for RecordingState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 届いたコマンドが記録対象で、記録中なら書き出す
+///
+/// 再生できないコマンドも、記録を再生できないものとして印を付けるために書き出す
+/// （バイナリの引数は記録せず null にする）。
+fn record_invoke<R: Runtime>(invoke: &Invoke<R>) {
+    let command = invoke.message.command();
+    if !should_record(command) {
+        return;
+    }
+    let payload = match invoke.message.payload() {
+        InvokeBody::Json(payload) => payload,
+        _ if is_recorded_command(command) => return,
+        _ => &Value::Null,
+    };
+    let Some(recording) = invoke.message.webview_ref().try_state::<RecordingState>() else { return };
+    let Ok(mut recorder_guard) = recording.recorder.lock() else { return };
+    let Some(recorder) = recorder_guard.as_mut() else { return };
+    if let Err(e) = recorder.record(command, payload) {
+        // 書けなくなった記録は再現に使えないため止める
+        error!("[Recording API] コマンドの記録に失敗したため記録を停止: {}", e);
+        *recorder_guard = None;
+    }
+}
+
+/// コマンドのハンドラーを包み、記録中は状態を変えるコマンドを実行前に書き出す（`invoke_handler` に渡す）
+pub fn record_commands<R: Runtime, F>(handler: F) -> impl Fn(Invoke<R>) -> bool + Send + Sync + 'static
+where
+    F: Fn(Invoke<R>) -> bool + Send + Sync + 'static,
+{
+    move |invoke| {
+        record_invoke(&invoke);
+        handler(invoke)
+    }
+}
+
+/// 記録の結果
+#[derive(Serialize)]
+pub struct RecordingSummary {
+    pub path: String,
+    /// 記録したコマンドの数
+    pub commands: u64,
+}
+
+/// 以降に届く状態を変えるコマンドを、時刻・引数のハッシュとともに `path` へ記録する（不具合の再現用）
+#[tauri::command]
+pub async fn start_command_recording(
+    path: String,
+    recording: State<'_, RecordingState>,
+) -> Result<(), String> {
+    let mut recorder_guard = recording.recorder.lock().map_err(|e| e.to_string())?;
+    if recorder_guard.is_some() {
+        return Err("コマンドは既に記録中です".to_string());
+    }
+    *recorder_guard = Some(CommandRecorder::create(Path::new(&path)).map_err(|e| e.to_string())?);
+    Ok(())
+}
+
+/// 記録を停止（記録していなかった場合は None）
+#[tauri::command]
+pub async fn stop_command_recording(
+    recording: State<'_, RecordingState>,
+) -> Result<Option<RecordingSummary>, String> {
+    let recorder = recording.recorder.lock().map_err(|e| e.to_string())?.take();
+    Ok(recorder.map(|recorder| {
+        info!("[Recording API] コマンドの記録終了: {:?} ({} 件)", recorder.path(), recorder.count());
+        RecordingSummary {
+            path: recorder.path().to_string_lossy().into_owned(),
+            commands: recorder.count(),
+        }
+    }))
+}
+
+/// 再生に失敗したコマンド
+#[derive(Serialize)]
+pub struct ReplayFailure {
+    pub sequence: u64,
+    pub command: String,
+    pub error: String,
+}
+
+/// 再生の結果
+#[derive(Serialize)]
+pub struct ReplayResult {
+    /// 再生したコマンドの数（失敗したものを含む）
+    pub replayed: usize,
+    /// 失敗したコマンド（記録時にも失敗していたものを含む）
+    pub failures: Vec<ReplayFailure>,
+    /// 記録の最後の行が書き込み途中で欠けていた
+    pub truncated: bool,
+}
+
+/// 記録した引数から1つ取り出す（省略された引数は null として読む）
+fn arg<T: DeserializeOwned>(payload: &Value, key: &str) -> Result<T, String> {
+    serde_json::from_value(payload.get(key).cloned().unwrap_or(Value::Null))
+        .map_err(|e| format!("引数 {} を読めません: {}", key, e))
+}
+
+/// 記録したコマンドを1件実行する（引数のキーはフロントエンドから届いたままの camelCase）
+async fn replay_command(app: &AppHandle, recorded: &RecordedCommand) -> Result<(), String> {
+    let p = &recorded.payload;
+    let state = || app.state::<DrawingState>();
+    let settings = || app.state::<SettingsState>();
+    match recorded.command.as_str() {
+        // 再生の前に描画エンジンは作り直してあるため、初期化は既に済んでいるものとして扱われる
+        "initialize_drawing_engine" => initialize_drawing_engine(app.clone(), state(), settings()).await.map(drop),
+        "create_drawing_layer" => create_drawing_layer(arg(p, "layerId")?, arg(p, "width")?, arg(p, "height")?, state()).await.map(drop),
+        "remove_layer" => remove_layer(arg(p, "layerId")?, state()).await,
+        "clear_layer" => clear_layer(arg(p, "layerId")?, state()).await,
+        "fill_layer" => fill_layer(arg(p, "layerId")?, arg(p, "color")?, state()).await,
+        "draw_line_on_layer" => draw_line_on_layer(
+            arg(p, "layerId")?, arg(p, "x1")?, arg(p, "y1")?, arg(p, "x2")?, arg(p, "y2")?, arg(p, "color")?, arg(p, "width")?, state(),
+        ).await.map_err(|e| e.to_string()),
+        "draw_stroke_on_layer" => draw_stroke_on_layer(
+            arg(p, "layerId")?, arg(p, "points")?, arg(p, "color")?, arg(p, "deviceId")?, state(), settings(),
+        ).await.map_err(|e| e.to_string()),
+        "draw_pixel_stroke_on_layer" => draw_pixel_stroke_on_layer(
+            arg(p, "layerId")?, arg(p, "points")?, arg(p, "color")?, arg(p, "pixelPerfect")?, state(),
+        ).await.map(drop).map_err(|e| e.to_string()),
+        "begin_stroke" => begin_stroke(
+            arg(p, "layerId")?, arg(p, "color")?, arg(p, "deviceId")?, arg(p, "erase")?, state(), settings(),
+        ).await.map_err(|e| e.to_string()),
+        "extend_stroke" => extend_stroke(arg(p, "layerId")?, arg(p, "points")?, arg(p, "timing")?, state())
+            .await.map_err(|e| e.to_string()),
+        "extend_stroke_packed" => extend_stroke_packed(arg(p, "layerId")?, arg(p, "packed")?, arg(p, "timing")?, state())
+            .await.map(drop).map_err(|e| e.to_string()),
+        "end_stroke" => end_stroke(arg(p, "layerId")?, state()).await.map(drop).map_err(|e| e.to_string()),
+        "cancel_stroke" => cancel_stroke(arg(p, "layerId")?, state()).await,
+        "cancel_all_strokes" => cancel_all_strokes(state()).await.map(drop),
+        "rotate_canvas_90" => rotate_canvas_90(arg(p, "clockwise")?, arg(p, "canvasWidth")?, arg(p, "canvasHeight")?, state())
+            .await.map(drop),
+        "rotate_canvas_180" => rotate_canvas_180(arg(p, "canvasWidth")?, arg(p, "canvasHeight")?, state()).await.map(drop),
+        "flip_canvas_horizontal" => flip_canvas_horizontal(arg(p, "canvasWidth")?, arg(p, "canvasHeight")?, state())
+            .await.map(drop),
+        "undo" => undo(state(), settings()).await.map(drop),
+        "redo" => redo(state()).await.map(drop),
+        "render_canvas_view" => render_canvas_view(arg(p, "zoom")?, arg(p, "layers")?, state(), settings(), app.state::<SelectionState>())
+            .await.map(drop).map_err(|e| e.to_string()),
+        "get_layer_image_data" => get_layer_image_data(arg(p, "layerId")?, state()).await.map(drop).map_err(|e| e.to_string()),
+        "get_layer_image_zoomed" => get_layer_image_zoomed(arg(p, "layerId")?, arg(p, "zoom")?, state())
+            .await.map(drop).map_err(|e| e.to_string()),
+        "cleanup_textures" => cleanup_textures(state()).await.map(drop),
+        "defragment_textures" => defragment_textures(arg(p, "options")?, state()).await.map(drop),
+        "bucket_fill" => bucket_fill(
+            arg(p, "layerId")?, arg(p, "x")?, arg(p, "y")?, arg(p, "color")?, arg(p, "options")?, arg(p, "referenceLayerId")?, state(),
+        ).await.map(drop),
+        "page_frames" => page_frames(arg(p, "frames")?, arg(p, "playhead")?, arg(p, "keepRadius")?, state()).await.map(drop),
+        "restore_paged_layers" => restore_paged_layers(state()).await.map(drop),
+        other => Err(format!("再生できないコマンドです: {}", other)),
+    }
+}
+
+/// 描画エンジンを作り直し、空のキャンバスに戻す（描画中のストロークと操作履歴は破棄する）
+async fn reset_for_replay(state: &DrawingState) -> Result<(), String> {
+    let mut history_guard = state.history.lock().await;
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        engine.reinitialize().await.map_err(|e| e.to_string())?;
+    }
+    state.active_strokes.lock().await.clear();
+    *history_guard = OperationLog::new();
+    rebuild_engine_state(state, &history_guard).await?;
+    state.save_tracker.lock().await.mark_all();
+    state.write_journal(JournalRecord::Base { log: history_guard.clone() }).await;
+    Ok(())
+}
+
+/// 記録した描画コマンドを、作り直した描画エンジンに順に流して再現する
+///
+/// 再生できないコマンド（`CommandKind::Unreplayable`）を含む記録は、キャンバスを破棄する前に拒否する。
+/// 現在のキャンバスと操作履歴は破棄する。`realtime` を指定すると記録時の間隔を空けて実行する
+/// （解像度変更の待ち合わせなど、時間に依存する不具合の再現用）。失敗したコマンドは記録して続ける。
+#[tauri::command]
+pub async fn replay_command_recording(
+    path: String,
+    realtime: Option<bool>,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    recording: State<'_, RecordingState>,
+) -> Result<ReplayResult, String> {
+    if recording.recorder.lock().map_err(|e| e.to_string())?.is_some() {
+        return Err("コマンドの記録中は再生できません".to_string());
+    }
+    if state.collaboration.lock().await.is_some() {
+        return Err("共同編集中は再生できません".to_string());
+    }
+    let stream = Recording::read(Path::new(&path)).map_err(|e| e.to_string())?;
+    if !stream.is_replayable() {
+        warn!("[Recording API] 再生できないコマンドを含むため再生しません: {:?}", stream.unreplayable);
+        return Err(format!("再生できないコマンドを含む記録です: {}", stream.unreplayable.join(", ")));
+    }
+    info!("[Recording API] コマンドの再生開始: {} ({} 件)", path, stream.commands.len());
+    reset_for_replay(&state).await?;
+
+    let realtime = realtime.unwrap_or(false);
+    let mut failures = Vec::new();
+    let mut previous_ms = stream.commands.first().map(|command| command.elapsed_ms).unwrap_or(0.0);
+    for command in &stream.commands {
+        if realtime {
+            let wait_ms = (command.elapsed_ms - previous_ms).max(0.0);
+            tokio::time::sleep(Duration::from_secs_f64(wait_ms / 1000.0)).await;
+        }
+        previous_ms = command.elapsed_ms;
+
+        debug!("[Recording API] #{} {}", command.sequence, command.command);
+        if let Err(error) = replay_command(&app, command).await {
+            warn!("[Recording API] #{} {} に失敗: {}", command.sequence, command.command, error);
+            failures.push(ReplayFailure { sequence: command.sequence, command: command.command.clone(), error });
+        }
+    }
+
+    info!("[Recording API] コマンドの再生完了: {} 件 (失敗 {} 件)", stream.commands.len(), failures.len());
+    Ok(ReplayResult {
+        replayed: stream.commands.len(),
+        failures,
+        truncated: stream.truncated,
+    })
+}
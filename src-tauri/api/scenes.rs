@@ -0,0 +1,100 @@
+use crate::animation::{Project, Scene, SceneUpdate};
+use crate::history::Operation;
+use super::drawing::DrawingState;
+use super::formats::collect_raster_layers;
+use log::{info, debug};
+use tauri::State;
+
+/// シーンの一覧（シーンを作っていないプロジェクトは全フレームを1つのシーンとして返す）
+#[tauri::command]
+pub async fn get_scenes(project: Project) -> Result<Vec<Scene>, String> {
+    Ok(project.scene_list())
+}
+
+/// 空のフレームを1枚持つシーンを末尾に追加し、更新したプロジェクトを返す
+///
+/// `width` / `height` を指定するとシーンのキャンバスの大きさを上書きする。
+#[tauri::command]
+pub async fn create_scene(
+    mut project: Project,
+    name: String,
+    width: Option<u32>,
+    height: Option<u32>,
+) -> Result<Project, String> {
+    let scene = project.create_scene(&name, width, height).map_err(|e| e.to_string())?;
+    info!("[Scene API] シーンを追加: {} ({} シーン)", scene.name, project.scenes.len());
+    Ok(project)
+}
+
+/// シーンの名前・大きさ・長さを変更し、更新したプロジェクトを返す
+#[tauri::command]
+pub async fn update_scene(
+    mut project: Project,
+    scene_id: String,
+    update: SceneUpdate,
+) -> Result<Project, String> {
+    let scene = project.update_scene(&scene_id, update).map_err(|e| e.to_string())?;
+    debug!("[Scene API] シーンを更新: {} ({:?}x{:?}, {:?} 秒)", scene.name, scene.width, scene.height, scene.duration);
+    Ok(project)
+}
+
+/// シーンを並べ替え、更新したプロジェクトを返す（フレームの並びもシーン順に揃える）
+#[tauri::command]
+pub async fn reorder_scenes(mut project: Project, scene_ids: Vec<String>) -> Result<Project, String> {
+    project.reorder_scenes(&scene_ids).map_err(|e| e.to_string())?;
+    debug!("[Scene API] シーンを並べ替え: {:?}", scene_ids);
+    Ok(project)
+}
+
+/// シーンを複製して直後に置き、更新したプロジェクトを返す
+///
+/// 複製したフレームのレイヤーは新しいIDで作り、元の画像を貼り付けて履歴に記録する。
+/// 常駐している元のレイヤーとはテクスチャを共有し、どちらかに描き込むまで複製しない。
+#[tauri::command]
+pub async fn duplicate_scene(
+    mut project: Project,
+    scene_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<Project, String> {
+    let duplicated = project.duplicate_scene(&scene_id).map_err(|e| e.to_string())?;
+
+    let sources: Vec<_> = project.frames.iter()
+        .flat_map(|frame| &frame.layers)
+        .filter(|layer| duplicated.layer_copies.iter().any(|(source, _)| *source == layer.id))
+        .cloned()
+        .collect();
+    let mut operations = Vec::with_capacity(duplicated.layer_copies.len() * 2);
+    let mut copies = Vec::with_capacity(duplicated.layer_copies.len());
+    if !sources.is_empty() {
+        let (images, ..) = collect_raster_layers(&state, Some(sources.clone())).await?;
+        for (source, raster) in sources.iter().zip(images) {
+            let (_, copy_id) = duplicated.layer_copies.iter()
+                .find(|(id, _)| *id == source.id)
+                .ok_or("複製するレイヤーが見つかりません")?;
+            let (width, height) = raster.image.dimensions();
+            operations.push(Operation::CreateLayer { layer_id: copy_id.clone(), width, height });
+            operations.push(Operation::paste_image(copy_id, 0, 0, &raster.image)?);
+            copies.push((source.id.as_str(), copy_id.as_str()));
+        }
+    }
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        for ((source_id, copy_id), pair) in copies.iter().zip(operations.chunks(2)) {
+            let [create, paste] = pair else { continue };
+            create.apply(engine, &mut layers_guard)?;
+            // 退避中の元のレイヤーはテクスチャがないので、画像を貼り付ける
+            if engine.share_layer_texture(source_id, copy_id).is_err() {
+                paste.apply(engine, &mut layers_guard)?;
+            }
+        }
+    }
+    for operation in operations {
+        state.record_operation(operation).await;
+    }
+
+    info!("[Scene API] シーンを複製: {} -> {} ({} レイヤー)",
+          scene_id, duplicated.scene.id, duplicated.layer_copies.len());
+    Ok(project)
+}
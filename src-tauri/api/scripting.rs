@@ -0,0 +1,102 @@
+use crate::scripting::{self, ScriptLimits};
+use super::drawing::DrawingState;
+use super::history::validate_batch;
+use super::paging::{affected_layers, ensure_resident};
+use log::{info, debug, error};
+use serde::Serialize;
+use std::path::PathBuf;
+use tauri::State;
+
+/// スクリプト実行結果
+#[derive(Serialize)]
+pub struct ScriptRunResult {
+    pub operations_applied: usize,
+    pub exported_files: Vec<String>,
+    pub logs: Vec<String>,
+}
+
+/// 自動化スクリプトを実行
+///
+/// スクリプトが発行した操作は通常の描画コマンドと同様に履歴・共同編集へ記録される。
+/// PNG書き出しは `export_dir` 直下にのみ行える。
+#[tauri::command]
+pub async fn run_script(
+    source: String,
+    export_dir: Option<String>,
+    state: State<'_, DrawingState>,
+) -> Result<ScriptRunResult, String> {
+    info!("[Script API] スクリプト実行開始");
+
+    let layers = state.layers.lock().await.clone();
+    let output = tokio::task::spawn_blocking(move || {
+        scripting::run_script(&source, &layers, ScriptLimits::default())
+    })
+    .await
+    .map_err(|e| format!("スクリプト実行タスクエラー: {}", e))?
+    .map_err(|e| {
+        error!("[Script API] {}", e);
+        e.to_string()
+    })?;
+
+    let export_dir = match (&export_dir, output.exports.is_empty()) {
+        (Some(dir), _) => {
+            let dir = PathBuf::from(dir);
+            if !dir.is_dir() {
+                return Err(format!("書き出し先ディレクトリが存在しません: {}", dir.display()));
+            }
+            Some(dir)
+        }
+        (None, true) => None,
+        (None, false) => return Err("書き出し先ディレクトリが指定されていません".to_string()),
+    };
+
+    // 途中まで適用してから失敗しないよう、GPUに触れる前に全操作を検証
+    if let Some(issue) = validate_batch(&state, &output.operations).await.into_iter().next() {
+        error!("[Script API] 不正な操作 #{}: {}", issue.index, issue.message);
+        return Err(format!("スクリプトの操作 #{} が不正です: {}", issue.index, issue.message));
+    }
+
+    // 操作をエンジンに適用し、描画コマンドと同じく1操作ずつ記録
+    let operations_applied = output.operations.len();
+    let mut touched = affected_layers(&output.operations);
+    if let Some(layer_ids) = touched.as_mut() {
+        layer_ids.extend(output.exports.iter().map(|request| request.layer_id.clone()));
+    }
+    ensure_resident(&state, touched.as_deref()).await?;
+    for operation in output.operations {
+        {
+            let mut engine_guard = state.engine.lock().await;
+            let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+            let mut layers_guard = state.layers.lock().await;
+            operation.apply(engine, &mut layers_guard).map_err(|e| {
+                error!("[Script API] 操作の適用に失敗: {}", e);
+                format!("スクリプト操作適用エラー: {}", e)
+            })?;
+        }
+        state.record_operation(operation).await;
+    }
+
+    let mut exported_files = Vec::new();
+    if let Some(dir) = export_dir {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        for request in &output.exports {
+            let image = engine.get_layer_image(&request.layer_id).await
+                .map_err(|e| format!("画像データ取得エラー: {}", e))?;
+            let path = dir.join(&request.file_name);
+            image.save(&path).map_err(|e| {
+                error!("[Script API] PNG書き出し失敗: {} - {}", path.display(), e);
+                format!("PNG書き出しエラー: {}", e)
+            })?;
+            debug!("[Script API] PNG書き出し: {} -> {}", request.layer_id, path.display());
+            exported_files.push(path.to_string_lossy().to_string());
+        }
+    }
+
+    info!("[Script API] スクリプト実行完了: 操作 {} / 書き出し {}", operations_applied, exported_files.len());
+    Ok(ScriptRunResult {
+        operations_applied,
+        exported_files,
+        logs: output.logs,
+    })
+}
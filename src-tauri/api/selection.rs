@@ -0,0 +1,334 @@
+use crate::history::Operation;
+use crate::selection::{apply_selection_transform, Selection, SelectionTransform};
+use super::drawing::{remove_layer, DrawingState};
+use super::formats::{collect_raster_layers, texture_layer};
+use super::paging::ensure_resident;
+use image::RgbaImage;
+use log::{info, debug, error};
+use serde::Serialize;
+use tauri::State;
+use tokio::sync::Mutex;
+
+/// 変形中の選択範囲（プレビューはレイヤーテクスチャに直接書き込み、確定時に履歴へ記録する）
+struct FloatingSelection {
+    layer_id: String,
+    selection: Selection,
+    /// 変形開始時のレイヤー画像（プレビューの元画像と取り消し用）
+    original: RgbaImage,
+    transform: SelectionTransform,
+}
+
+/// クイックマスクのテクスチャ（レイヤーの一覧には含めず、描画APIにこのIDを渡すと選択範囲を塗れる）
+pub(crate) const QUICK_MASK_TEXTURE_ID: &str = "__quick_mask";
+
+/// 選択範囲の状態管理
+pub struct SelectionState {
+    selection: Mutex<Option<Selection>>,
+    floating: Mutex<Option<FloatingSelection>>,
+    quick_mask: Mutex<bool>,
+}
+
+impl SelectionState {
+    pub fn new() -> Self {
+        Self {
+            selection: Mutex::new(None),
+            floating: Mutex::new(None),
+            quick_mask: Mutex::new(false),
+        }
+    }
+
+    pub(crate) async fn current(&self) -> Option<Selection> {
+        self.selection.lock().await.clone()
+    }
+
+    /// 選択範囲を置き換える（変形中は変更できない）
+    pub(crate) async fn replace(&self, selection: Selection) -> Result<(), String> {
+        selection.validate().map_err(|e| e.to_string())?;
+        if self.is_transforming().await {
+            return Err("選択範囲の変形中です".to_string());
+        }
+        *self.selection.lock().await = Some(selection);
+        Ok(())
+    }
+
+    /// 選択範囲の変形中か（変形中のレイヤーは確定まで他の操作で書き換えない）
+    pub(crate) async fn is_transforming(&self) -> bool {
+        self.floating.lock().await.is_some()
+    }
+
+    /// クイックマスク中か（表示に選択範囲の色を重ねる）
+    pub(crate) async fn is_quick_mask(&self) -> bool {
+        *self.quick_mask.lock().await
+    }
+}
+
+impl Default for SelectionState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 変形プレビューの結果
+#[derive(Serialize)]
+pub struct SelectionPreview {
+    pub layer_id: String,
+    /// 変形後の選択範囲を覆う矩形
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// 確定後の選択範囲（移動だけならマスクを保ち、それ以外は変形後の外接矩形）
+fn transformed_selection(selection: &Selection, transform: &SelectionTransform) -> Selection {
+    let is_translation = transform.scale_x == 1.0
+        && transform.scale_y == 1.0
+        && transform.rotation_degrees.rem_euclid(360.0) == 0.0
+        && transform.translate_x.fract() == 0.0
+        && transform.translate_y.fract() == 0.0;
+    if is_translation {
+        return Selection {
+            x: selection.x + transform.translate_x as i32,
+            y: selection.y + transform.translate_y as i32,
+            ..selection.clone()
+        };
+    }
+    let (x, y, width, height) = transform.bounds(selection);
+    Selection::rect(x, y, width, height)
+}
+
+/// 選択範囲を設定
+#[tauri::command]
+pub async fn set_selection(
+    selection: Selection,
+    selection_state: State<'_, SelectionState>,
+) -> Result<(), String> {
+    debug!("[Selection API] 選択範囲設定: ({}, {}) {}x{}",
+           selection.x, selection.y, selection.width, selection.height);
+    selection_state.replace(selection).await
+}
+
+/// 選択を解除
+#[tauri::command]
+pub async fn clear_selection(selection_state: State<'_, SelectionState>) -> Result<(), String> {
+    if selection_state.floating.lock().await.is_some() {
+        return Err("選択範囲の変形中です".to_string());
+    }
+    *selection_state.selection.lock().await = None;
+    Ok(())
+}
+
+/// 現在の選択範囲を取得
+#[tauri::command]
+pub async fn get_selection(selection_state: State<'_, SelectionState>) -> Result<Option<Selection>, String> {
+    Ok(selection_state.current().await)
+}
+
+/// 選択範囲の変形を開始（レイヤーの現在の画像を変形の元として保持する）
+#[tauri::command]
+pub async fn begin_selection_transform(
+    layer_id: String,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<SelectionPreview, String> {
+    let selection = selection_state.current().await.ok_or("選択範囲がありません")?;
+    let mut floating_guard = selection_state.floating.lock().await;
+    if floating_guard.is_some() {
+        return Err("選択範囲の変形中です".to_string());
+    }
+    if !state.layers.lock().await.contains_key(&layer_id) {
+        return Err(format!("レイヤーが見つかりません: {}", layer_id));
+    }
+
+    ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+    let original = {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        engine.get_layer_image(&layer_id).await.map_err(|e| {
+            error!("[Selection API] レイヤー画像の取得に失敗: {}", e);
+            format!("レイヤー画像取得エラー: {}", e)
+        })?
+    };
+
+    info!("[Selection API] 選択範囲の変形を開始: {}", layer_id);
+    let preview = SelectionPreview {
+        layer_id: layer_id.clone(),
+        x: selection.x,
+        y: selection.y,
+        width: selection.width,
+        height: selection.height,
+    };
+    *floating_guard = Some(FloatingSelection {
+        layer_id,
+        selection,
+        original,
+        transform: SelectionTransform::default(),
+    });
+    Ok(preview)
+}
+
+/// 変形をプレビュー（履歴には記録せず、レイヤーに結果を表示する）
+///
+/// 変形は常に開始時の画像から計算するため、繰り返し呼んでも画質は劣化しない。
+#[tauri::command]
+pub async fn preview_selection_transform(
+    transform: SelectionTransform,
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<SelectionPreview, String> {
+    transform.validate().map_err(|e| e.to_string())?;
+    let mut floating_guard = selection_state.floating.lock().await;
+    let floating = floating_guard.as_mut().ok_or("選択範囲の変形が開始されていません")?;
+
+    let result = apply_selection_transform(&floating.original, &floating.selection, &transform);
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        engine.write_layer_image(&floating.layer_id, 0, 0, &result).map_err(|e| {
+            error!("[Selection API] プレビューの書き込みに失敗: {}", e);
+            format!("プレビューエラー: {}", e)
+        })?;
+    }
+    floating.transform = transform;
+
+    let (x, y, width, height) = transform.bounds(&floating.selection);
+    Ok(SelectionPreview { layer_id: floating.layer_id.clone(), x, y, width, height })
+}
+
+/// 変形を確定して履歴に 1 つの操作として記録
+#[tauri::command]
+pub async fn commit_selection_transform(
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<Selection, String> {
+    let floating = selection_state.floating.lock().await.take()
+        .ok_or("選択範囲の変形が開始されていません")?;
+
+    // プレビューと同じ結果を元画像から書き込み直す（最後のプレビュー以降に変わっていても確定内容と一致させる）
+    let result = apply_selection_transform(&floating.original, &floating.selection, &floating.transform);
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        engine.write_layer_image(&floating.layer_id, 0, 0, &result).map_err(|e| {
+            error!("[Selection API] 変形結果の書き込みに失敗: {}", e);
+            format!("変形エラー: {}", e)
+        })?;
+    }
+    state.record_operation(Operation::TransformSelection {
+        layer_id: floating.layer_id.clone(),
+        selection: floating.selection.clone(),
+        transform: floating.transform,
+    }).await;
+
+    let selection = transformed_selection(&floating.selection, &floating.transform);
+    *selection_state.selection.lock().await = Some(selection.clone());
+    info!("[Selection API] 選択範囲の変形を確定: {}", floating.layer_id);
+    Ok(selection)
+}
+
+/// 変形を取り消してレイヤーを開始時の画像に戻す
+#[tauri::command]
+pub async fn cancel_selection_transform(
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<(), String> {
+    let Some(floating) = selection_state.floating.lock().await.take() else {
+        return Ok(());
+    };
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    engine.write_layer_image(&floating.layer_id, 0, 0, &floating.original).map_err(|e| {
+        error!("[Selection API] 元画像の復元に失敗: {}", e);
+        format!("変形の取り消しエラー: {}", e)
+    })?;
+    info!("[Selection API] 選択範囲の変形を取り消し: {}", floating.layer_id);
+    Ok(())
+}
+
+/// クイックマスクのテクスチャ
+#[derive(Serialize)]
+pub struct QuickMask {
+    pub texture_id: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+/// クイックマスクを開始（現在の選択範囲をキャンバスの大きさのマスクに書き込む）
+///
+/// マスクは白で塗ると選択、消しゴムで消すと選択外になる。作成と描画は履歴に記録される。
+#[tauri::command]
+pub async fn enter_quick_mask(
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<QuickMask, String> {
+    let mut quick_mask_guard = selection_state.quick_mask.lock().await;
+    if *quick_mask_guard {
+        return Err("既にクイックマスク中です".to_string());
+    }
+    if selection_state.is_transforming().await {
+        return Err("選択範囲の変形中です".to_string());
+    }
+    let (width, height) = {
+        let layers_guard = state.layers.lock().await;
+        if layers_guard.contains_key(QUICK_MASK_TEXTURE_ID) {
+            return Err(format!("レイヤーが既に存在します: {}", QUICK_MASK_TEXTURE_ID));
+        }
+        layers_guard.values().fold((0, 0), |(w, h), (lw, lh)| (w.max(*lw), h.max(*lh)))
+    };
+    if width == 0 || height == 0 {
+        return Err("キャンバスの大きさを決めるレイヤーがありません".to_string());
+    }
+
+    let mut operations = vec![Operation::CreateLayer { layer_id: QUICK_MASK_TEXTURE_ID.to_string(), width, height }];
+    if let Some(selection) = selection_state.current().await {
+        operations.push(Operation::paste_image(QUICK_MASK_TEXTURE_ID, 0, 0, &selection.to_mask_image(width, height))?);
+    }
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        for operation in &operations {
+            operation.apply(engine, &mut layers_guard)?;
+        }
+    }
+    for operation in operations {
+        state.record_operation(operation).await;
+    }
+
+    *quick_mask_guard = true;
+    info!("[Selection API] クイックマスクを開始: {}x{}", width, height);
+    Ok(QuickMask { texture_id: QUICK_MASK_TEXTURE_ID.to_string(), width, height })
+}
+
+/// クイックマスクを終了し、塗った内容を選択範囲に変換する（何も塗られていなければ選択を解除）
+#[tauri::command]
+pub async fn exit_quick_mask(
+    state: State<'_, DrawingState>,
+    selection_state: State<'_, SelectionState>,
+) -> Result<Option<Selection>, String> {
+    let mut quick_mask_guard = selection_state.quick_mask.lock().await;
+    if !*quick_mask_guard {
+        return Err("クイックマスク中ではありません".to_string());
+    }
+    // 取り消しでマスクの作成まで戻っていた場合は選択範囲を変えずに終了する
+    if !state.layers.lock().await.contains_key(QUICK_MASK_TEXTURE_ID) {
+        *quick_mask_guard = false;
+        return Ok(selection_state.current().await);
+    }
+
+    let (raster_layers, ..) = collect_raster_layers(&state, Some(vec![texture_layer(QUICK_MASK_TEXTURE_ID)])).await?;
+    let image = raster_layers.into_iter().next().ok_or("画像データ取得エラー: 読み出し結果が不足しています")?.image;
+    let selection = tokio::task::spawn_blocking(move || Selection::from_mask_image(&image))
+        .await
+        .map_err(|e| format!("選択範囲変換タスクエラー: {}", e))?;
+    remove_layer(QUICK_MASK_TEXTURE_ID.to_string(), state).await?;
+
+    *selection_state.selection.lock().await = selection.clone();
+    *quick_mask_guard = false;
+    match &selection {
+        Some(selection) => info!("[Selection API] クイックマスクを選択範囲に変換: ({}, {}) {}x{}",
+                                 selection.x, selection.y, selection.width, selection.height),
+        None => info!("[Selection API] クイックマスクを終了: 選択なし"),
+    }
+    Ok(selection)
+}
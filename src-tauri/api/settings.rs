@@ -0,0 +1,176 @@
+use crate::formats::ExportPreset;
+use crate::settings::{AppSettings, SETTINGS_FILE_NAME};
+use super::drawing::DrawingState;
+use super::memory::MemoryState;
+use log::{info, debug, warn, error};
+use serde_json::Value;
+use std::path::PathBuf;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+/// アプリケーション設定の状態管理
+pub struct SettingsState {
+    settings: Mutex<AppSettings>,
+    /// 保存先（設定ディレクトリを取得できない場合は保存しない）
+    path: Option<PathBuf>,
+}
+
+impl SettingsState {
+    /// アプリの設定ディレクトリから設定を読み込む（読み込めない場合は既定値）
+    pub fn load(app: &AppHandle) -> Self {
+        let path = match app.path().app_config_dir() {
+            Ok(dir) => Some(dir.join(SETTINGS_FILE_NAME)),
+            Err(e) => {
+                warn!("[Settings API] 設定ディレクトリを取得できません: {}", e);
+                None
+            }
+        };
+        let settings = match path.as_deref().map(AppSettings::load) {
+            Some(Ok(settings)) => settings,
+            Some(Err(e)) => {
+                error!("[Settings API] 設定を読み込めないため既定値を使用: {}", e);
+                AppSettings::default()
+            }
+            None => AppSettings::default(),
+        };
+        Self {
+            settings: Mutex::new(settings),
+            path,
+        }
+    }
+
+    /// 現在の設定を取得
+    pub async fn get(&self) -> AppSettings {
+        self.settings.lock().await.clone()
+    }
+
+    /// 設定を変更して保存し、`settings:changed` イベントを送る
+    pub(crate) async fn update<F>(&self, app: &AppHandle, change: F) -> Result<AppSettings, String>
+    where
+        F: FnOnce(&AppSettings) -> Result<AppSettings, String>,
+    {
+        let mut settings_guard = self.settings.lock().await;
+        let settings = change(&settings_guard)?;
+        if let Some(path) = &self.path {
+            settings.save(path).map_err(|e| e.to_string())?;
+        }
+        *settings_guard = settings.clone();
+        drop(settings_guard);
+
+        if let Err(e) = app.emit("settings:changed", &settings) {
+            warn!("[Settings API] 設定変更イベントの送信に失敗: {}", e);
+        }
+        Ok(settings)
+    }
+}
+
+/// 描画エンジンに関わる設定を反映（初期化前なら初期化時に反映される）
+async fn apply_engine_settings(drawing: &DrawingState, settings: &AppSettings) {
+    if let Some(engine) = drawing.engine.lock().await.as_mut() {
+        engine.set_stroke_vertex_limit(settings.stroke_vertex_limit);
+    }
+    drawing.diagnostics.lock().await.set_enabled(settings.collect_diagnostics);
+}
+
+/// 起動時に保存済みの設定を読み込んで登録し、各サブシステムに反映
+pub fn manage_settings(app: &AppHandle) {
+    let settings_state = SettingsState::load(app);
+    let (memory_config, collect_diagnostics) = {
+        let settings = settings_state.settings.blocking_lock();
+        (settings.memory.clone(), settings.collect_diagnostics)
+    };
+    if let Err(e) = app.state::<MemoryState>().set_config_blocking(memory_config) {
+        warn!("[Settings API] メモリ設定を反映できません: {}", e);
+    }
+    app.state::<DrawingState>().diagnostics.blocking_lock().set_enabled(collect_diagnostics);
+    app.manage(settings_state);
+}
+
+/// 設定を取得
+#[tauri::command]
+pub async fn get_settings(settings: State<'_, SettingsState>) -> Result<AppSettings, String> {
+    Ok(settings.get().await)
+}
+
+/// 設定を部分的に変更して保存
+///
+/// `patch` に含まれる項目だけが変更される（入れ子のオブジェクトも項目ごとにマージ）。
+/// メモリ設定・頂点バッファの上限・診断情報の記録は即座に反映され、GPU設定は次回の描画エンジン初期化時に使われる。
+#[tauri::command]
+pub async fn set_settings(
+    patch: Value,
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+    memory: State<'_, MemoryState>,
+    drawing: State<'_, DrawingState>,
+) -> Result<AppSettings, String> {
+    let updated = settings.update(&app, |current| {
+        current.merged(&patch).map_err(|e| e.to_string())
+    }).await?;
+    memory.set_config(updated.memory.clone()).await?;
+    apply_engine_settings(&drawing, &updated).await;
+
+    info!("[Settings API] 設定を更新しました");
+    Ok(updated)
+}
+
+/// 設定を既定値に戻す
+#[tauri::command]
+pub async fn reset_settings(
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+    memory: State<'_, MemoryState>,
+    drawing: State<'_, DrawingState>,
+) -> Result<AppSettings, String> {
+    let updated = settings.update(&app, |_| Ok(AppSettings::default())).await?;
+    memory.set_config(updated.memory.clone()).await?;
+    apply_engine_settings(&drawing, &updated).await;
+
+    info!("[Settings API] 設定を既定値に戻しました");
+    Ok(updated)
+}
+
+/// 書き出しプリセットの一覧を取得
+#[tauri::command]
+pub async fn list_export_presets(settings: State<'_, SettingsState>) -> Result<Vec<ExportPreset>, String> {
+    Ok(settings.get().await.export_presets)
+}
+
+/// 書き出しプリセットを保存（同じ名前のプリセットは置き換える）
+#[tauri::command]
+pub async fn save_export_preset(
+    preset: ExportPreset,
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+) -> Result<Vec<ExportPreset>, String> {
+    preset.validate().map_err(|e| e.to_string())?;
+    debug!("[Settings API] 書き出しプリセットの保存: {}", preset.name);
+    let updated = settings.update(&app, |current| {
+        let mut updated = current.clone();
+        match updated.export_presets.iter_mut().find(|saved| saved.name == preset.name) {
+            Some(saved) => *saved = preset.clone(),
+            None => updated.export_presets.push(preset.clone()),
+        }
+        updated.validate().map_err(|e| e.to_string())?;
+        Ok(updated)
+    }).await?;
+    Ok(updated.export_presets)
+}
+
+/// 書き出しプリセットを削除
+#[tauri::command]
+pub async fn delete_export_preset(
+    name: String,
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+) -> Result<Vec<ExportPreset>, String> {
+    let updated = settings.update(&app, |current| {
+        let mut updated = current.clone();
+        let index = updated.export_presets.iter().position(|preset| preset.name == name)
+            .ok_or_else(|| format!("書き出しプリセットが見つかりません: {}", name))?;
+        updated.export_presets.remove(index);
+        Ok(updated)
+    }).await?;
+    info!("[Settings API] 書き出しプリセットを削除しました: {}", name);
+    Ok(updated.export_presets)
+}
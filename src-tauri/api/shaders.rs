@@ -0,0 +1,131 @@
+use crate::drawing_engine::{ShaderId, ShaderWatcher};
+use super::drawing::DrawingState;
+use log::{info, debug, warn};
+use serde::Serialize;
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::Mutex;
+
+/// 既定の監視ディレクトリ（開発時のビルドディレクトリ内。ソースの shaders/ はモジュール集）
+const DEFAULT_SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/target/shaders");
+
+/// 変更を確認する間隔
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// シェーダーのホットリロードの状態（開発ビルドのみ）
+pub struct ShaderReloadState {
+    /// 実行中の監視の停止フラグ
+    watching: Mutex<Option<Arc<AtomicBool>>>,
+}
+
+impl ShaderReloadState {
+    pub fn new() -> Self {
+        Self {
+            watching: Mutex::new(None),
+        }
+    }
+}
+
+impl Default for ShaderReloadState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 監視の開始結果
+#[derive(Serialize)]
+pub struct ShaderReloadStatus {
+    pub directory: String,
+    /// 埋め込みのソースから書き出したファイル数
+    pub exported: usize,
+}
+
+/// シェーダーの差し替えに成功したときのイベント
+#[derive(Clone, Serialize)]
+pub struct ShaderReloadedEvent {
+    pub shader: ShaderId,
+    pub file: String,
+}
+
+/// シェーダーの読み込み・コンパイルに失敗したときのイベント（元のパイプラインを使い続ける）
+#[derive(Clone, Serialize)]
+pub struct ShaderErrorEvent {
+    pub shader: ShaderId,
+    pub file: String,
+    pub message: String,
+}
+
+/// shaders/ ディレクトリの WGSL を監視し、変更されたらパイプラインを作り直す
+///
+/// ディレクトリにないシェーダーは埋め込みのソースから書き出す。
+/// 差し替えると `shader:reloaded`、失敗すると `shader:error` イベントを発行する。
+#[tauri::command]
+pub async fn start_shader_hot_reload(
+    directory: Option<String>,
+    app: AppHandle,
+    reload: State<'_, ShaderReloadState>,
+) -> Result<ShaderReloadStatus, String> {
+    let dir = PathBuf::from(directory.unwrap_or_else(|| DEFAULT_SHADER_DIR.to_string()));
+    info!("[Shader API] シェーダーの監視開始: {}", dir.display());
+
+    let mut watcher = ShaderWatcher::new(&dir);
+    let exported = watcher.export_missing()
+        .map_err(|e| format!("シェーダーを書き出せません: {}", e))?;
+
+    let cancel = Arc::new(AtomicBool::new(false));
+    if let Some(previous) = reload.watching.lock().await.replace(cancel.clone()) {
+        previous.store(true, Ordering::SeqCst);
+    }
+    tokio::spawn(watch_shaders(app, watcher, cancel));
+
+    Ok(ShaderReloadStatus {
+        directory: dir.display().to_string(),
+        exported,
+    })
+}
+
+/// シェーダーの監視を停止する（差し替えたシェーダーはそのまま使う）
+#[tauri::command]
+pub async fn stop_shader_hot_reload(reload: State<'_, ShaderReloadState>) -> Result<bool, String> {
+    match reload.watching.lock().await.take() {
+        Some(cancel) => {
+            cancel.store(true, Ordering::SeqCst);
+            info!("[Shader API] シェーダーの監視を停止");
+            Ok(true)
+        }
+        None => Ok(false),
+    }
+}
+
+async fn watch_shaders(app: AppHandle, mut watcher: ShaderWatcher, cancel: Arc<AtomicBool>) {
+    while !cancel.load(Ordering::SeqCst) {
+        for (shader, source) in watcher.poll() {
+            let file = watcher.dir().join(shader.file_name()).display().to_string();
+            let result = match source {
+                Ok(source) => reload_shader(&app, shader, source).await,
+                Err(e) => Err(e),
+            };
+            match result {
+                Ok(()) => {
+                    let _ = app.emit("shader:reloaded", ShaderReloadedEvent { shader, file });
+                }
+                Err(message) => {
+                    warn!("[Shader API] シェーダーを差し替えられません: {}: {}", file, message);
+                    let _ = app.emit("shader:error", ShaderErrorEvent { shader, file, message });
+                }
+            }
+        }
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+    debug!("[Shader API] 監視タスク終了: {}", watcher.dir().display());
+}
+
+async fn reload_shader(app: &AppHandle, shader: ShaderId, source: String) -> Result<(), String> {
+    let state = app.state::<DrawingState>();
+    let mut engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+    engine.reload_shader(shader, source).await.map_err(|e| e.to_string())
+}
@@ -0,0 +1,79 @@
+use crate::settings::AppSettings;
+use crate::shortcuts::{KeyChord, ShortcutAction, ShortcutConflict, ShortcutMap};
+use super::settings::SettingsState;
+use log::{info, debug};
+use tauri::{AppHandle, State};
+
+/// 現在のショートカット割り当てを取得
+#[tauri::command]
+pub async fn get_shortcuts(settings: State<'_, SettingsState>) -> Result<ShortcutMap, String> {
+    Ok(settings.get().await.shortcuts)
+}
+
+/// 操作のショートカットを変更して設定に保存
+///
+/// 他の操作に割り当て済みのキーを指定した場合はエラーになる（先に相手側の割り当てを外す）。
+#[tauri::command]
+pub async fn set_shortcut(
+    action: ShortcutAction,
+    chords: Vec<String>,
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+) -> Result<ShortcutMap, String> {
+    let chords = chords.iter()
+        .map(|chord| chord.parse::<KeyChord>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+
+    let updated = settings.update(&app, |current| {
+        let mut shortcuts = current.shortcuts.clone();
+        shortcuts.bind(action, chords.clone()).map_err(|e| e.to_string())?;
+        Ok(AppSettings { shortcuts, ..current.clone() })
+    }).await?;
+
+    info!(
+        "[Shortcut API] {} の割り当てを変更: {:?}",
+        action.as_str(),
+        chords.iter().map(|chord| chord.to_string()).collect::<Vec<_>>()
+    );
+    Ok(updated.shortcuts)
+}
+
+/// ショートカットを既定の割り当てに戻す
+#[tauri::command]
+pub async fn reset_shortcuts(
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+) -> Result<ShortcutMap, String> {
+    let updated = settings.update(&app, |current| {
+        Ok(AppSettings { shortcuts: ShortcutMap::default(), ..current.clone() })
+    }).await?;
+    info!("[Shortcut API] ショートカットを既定値に戻しました");
+    Ok(updated.shortcuts)
+}
+
+/// キー入力に対応する操作を取得（割り当てがなければ None）
+#[tauri::command]
+pub async fn resolve_shortcut(
+    chord: String,
+    settings: State<'_, SettingsState>,
+) -> Result<Option<ShortcutAction>, String> {
+    let chord = chord.parse::<KeyChord>().map_err(|e| e.to_string())?;
+    let action = settings.get().await.shortcuts.resolve(&chord);
+    debug!("[Shortcut API] {} -> {:?}", chord, action);
+    Ok(action)
+}
+
+/// 指定した割り当てに変更した場合に重複するキーを取得（設定画面での事前確認用）
+#[tauri::command]
+pub async fn get_shortcut_conflicts(
+    action: ShortcutAction,
+    chords: Vec<String>,
+    settings: State<'_, SettingsState>,
+) -> Result<Vec<ShortcutConflict>, String> {
+    let chords = chords.iter()
+        .map(|chord| chord.parse::<KeyChord>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| e.to_string())?;
+    Ok(settings.get().await.shortcuts.conflicts_with(action, &chords))
+}
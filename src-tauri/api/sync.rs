@@ -0,0 +1,248 @@
+use crate::collaboration::server::{self, PeerInfo, SyncEvent, SyncHandle};
+use super::collaboration::{ensure_collaboration_session, merge_remote_operations};
+use super::drawing::DrawingState;
+use log::{info, debug, warn, error};
+use serde::Serialize;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager, State};
+use tokio::sync::{mpsc, Mutex};
+use tokio::task::JoinHandle;
+
+/// 同期サーバーのデフォルトポート
+pub const DEFAULT_SYNC_PORT: u16 = 48620;
+
+/// ローカル操作を送信する間隔
+const SYNC_PUSH_INTERVAL: Duration = Duration::from_millis(30);
+
+/// 同期接続の状態管理
+pub struct SyncState {
+    link: Mutex<Option<SyncLink>>,
+}
+
+struct SyncLink {
+    role: &'static str,
+    address: String,
+    /// ホストが発行したセッショントークン（参加側は None）
+    token: Option<String>,
+    peers: Arc<Mutex<Vec<PeerInfo>>>,
+    pump: JoinHandle<()>,
+}
+
+impl Drop for SyncLink {
+    fn drop(&mut self) {
+        // ポンプタスクが所有する SyncHandle も破棄され、接続が閉じられる
+        self.pump.abort();
+    }
+}
+
+impl SyncState {
+    pub fn new() -> Self {
+        Self {
+            link: Mutex::new(None),
+        }
+    }
+}
+
+impl Default for SyncState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 同期セッション情報
+#[derive(Serialize)]
+pub struct SyncSessionInfo {
+    pub role: String,
+    pub address: String,
+    /// 参加に必要なセッショントークン（ホストのみ。参加する相手に伝える）
+    pub token: Option<String>,
+    pub site_id: String,
+    pub peers: Vec<PeerInfo>,
+}
+
+/// LAN同期セッションを作成（このマシンがホストになる）
+///
+/// 既定ではこのマシン（127.0.0.1）からの接続だけを受け付け、`allow_lan` を指定すると LAN 内の全アドレスで待ち受ける。
+/// 参加するにはこのコマンドが返すセッショントークンが必要になる。
+#[tauri::command]
+pub async fn create_sync_session(
+    port: Option<u16>,
+    name: Option<String>,
+    allow_lan: Option<bool>,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    sync_state: State<'_, SyncState>,
+) -> Result<SyncSessionInfo, String> {
+    let mut link_guard = sync_state.link.lock().await;
+    if link_guard.is_some() {
+        return Err("同期セッションは既に開始されています".to_string());
+    }
+
+    let site_id = ensure_collaboration_session(&state, None).await;
+    let local_peer = PeerInfo {
+        site_id: site_id.clone(),
+        name: name.unwrap_or_else(|| "host".to_string()),
+    };
+    let ip = if allow_lan.unwrap_or(false) { Ipv4Addr::UNSPECIFIED } else { Ipv4Addr::LOCALHOST };
+    let addr = SocketAddr::from((ip, port.unwrap_or(DEFAULT_SYNC_PORT)));
+    let token = server::generate_token().map_err(|e| e.to_string())?;
+
+    let (handle, events) = server::host(addr, local_peer.clone(), token.clone()).await
+        .map_err(|e| {
+            error!("[Sync API] 同期サーバー起動失敗: {}", e);
+            e.to_string()
+        })?;
+    let address = handle.local_addr().map(|a| a.to_string()).unwrap_or_default();
+
+    let peers = Arc::new(Mutex::new(vec![local_peer]));
+    let pump = tokio::spawn(run_sync_pump(app, handle, events, peers.clone()));
+    *link_guard = Some(SyncLink { role: "host", address: address.clone(), token: Some(token.clone()), peers: peers.clone(), pump });
+
+    info!("[Sync API] 同期セッション作成: {} (site={})", address, site_id);
+    let peers = peers.lock().await.clone();
+    Ok(SyncSessionInfo { role: "host".to_string(), address, token: Some(token), site_id, peers })
+}
+
+/// 他マシンの同期セッションに参加（`token` はホストから伝えられたセッショントークン）
+#[tauri::command]
+pub async fn join_sync_session(
+    url: String,
+    token: String,
+    name: Option<String>,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    sync_state: State<'_, SyncState>,
+) -> Result<SyncSessionInfo, String> {
+    let mut link_guard = sync_state.link.lock().await;
+    if link_guard.is_some() {
+        return Err("同期セッションは既に開始されています".to_string());
+    }
+
+    let site_id = ensure_collaboration_session(&state, None).await;
+    let local_peer = PeerInfo {
+        site_id: site_id.clone(),
+        name: name.unwrap_or_else(|| "guest".to_string()),
+    };
+
+    let (handle, events) = server::join(&url, local_peer, token).await
+        .map_err(|e| {
+            error!("[Sync API] 同期セッション参加失敗: {}", e);
+            e.to_string()
+        })?;
+
+    let peers = Arc::new(Mutex::new(Vec::new()));
+    let pump = tokio::spawn(run_sync_pump(app, handle, events, peers.clone()));
+    *link_guard = Some(SyncLink { role: "guest", address: url.clone(), token: None, peers, pump });
+
+    info!("[Sync API] 同期セッション参加: {} (site={})", url, site_id);
+    Ok(SyncSessionInfo { role: "guest".to_string(), address: url, token: None, site_id, peers: Vec::new() })
+}
+
+/// 同期セッションから退出（ホストの場合はサーバーを停止）
+#[tauri::command]
+pub async fn leave_sync_session(
+    sync_state: State<'_, SyncState>,
+) -> Result<(), String> {
+    let mut link_guard = sync_state.link.lock().await;
+    match link_guard.take() {
+        Some(link) => {
+            info!("[Sync API] 同期セッション終了: {} ({})", link.address, link.role);
+            Ok(())
+        }
+        None => Err("同期セッションは開始されていません".to_string()),
+    }
+}
+
+/// 同期セッションの状態を取得
+#[tauri::command]
+pub async fn get_sync_session(
+    state: State<'_, DrawingState>,
+    sync_state: State<'_, SyncState>,
+) -> Result<Option<SyncSessionInfo>, String> {
+    let link_guard = sync_state.link.lock().await;
+    let Some(link) = link_guard.as_ref() else {
+        return Ok(None);
+    };
+
+    let site_id = {
+        let collaboration_guard = state.collaboration.lock().await;
+        collaboration_guard.as_ref().map(|s| s.site_id().to_string()).unwrap_or_default()
+    };
+    let peers = link.peers.lock().await.clone();
+    Ok(Some(SyncSessionInfo {
+        role: link.role.to_string(),
+        address: link.address.clone(),
+        token: link.token.clone(),
+        site_id,
+        peers,
+    }))
+}
+
+/// 同期イベントの処理とローカル操作の送信を行うタスク
+async fn run_sync_pump(
+    app: AppHandle,
+    handle: SyncHandle,
+    mut events: mpsc::UnboundedReceiver<SyncEvent>,
+    peers: Arc<Mutex<Vec<PeerInfo>>>,
+) {
+    let state = app.state::<DrawingState>();
+    let mut last_sent = 0u64;
+    let mut ticker = tokio::time::interval(SYNC_PUSH_INTERVAL);
+
+    loop {
+        tokio::select! {
+            event = events.recv() => match event {
+                Some(SyncEvent::RemoteOps(ops)) => {
+                    match merge_remote_operations(&state, ops).await {
+                        Ok(result) if result.applied > 0 => {
+                            let _ = app.emit("sync:ops-applied", &result);
+                        }
+                        Ok(_) => {}
+                        Err(e) => warn!("[Sync API] リモート操作の適用に失敗: {}", e),
+                    }
+                }
+                Some(SyncEvent::PeerJoined(peer)) => {
+                    // 新しいピアには現在の全操作を送る（重複は受信側で除外される）
+                    let snapshot = {
+                        let collaboration_guard = state.collaboration.lock().await;
+                        collaboration_guard.as_ref().map(|s| s.operations().to_vec()).unwrap_or_default()
+                    };
+                    debug!("[Sync API] ピア参加: {} - {} 操作を送信", peer.site_id, snapshot.len());
+                    if let Err(e) = handle.send_ops(snapshot) {
+                        warn!("[Sync API] スナップショット送信に失敗: {}", e);
+                    }
+                    let _ = app.emit("sync:peer-joined", &peer);
+                }
+                Some(SyncEvent::Presence(list)) => {
+                    *peers.lock().await = list.clone();
+                    let _ = app.emit("sync:presence", &list);
+                }
+                Some(SyncEvent::Disconnected(reason)) => {
+                    let _ = app.emit("sync:disconnected", &reason);
+                    break;
+                }
+                None => break,
+            },
+            _ = ticker.tick() => {
+                let ops = {
+                    let collaboration_guard = state.collaboration.lock().await;
+                    match collaboration_guard.as_ref() {
+                        Some(session) => session.local_ops_since(last_sent),
+                        None => Vec::new(),
+                    }
+                };
+                if let Some(last) = ops.last() {
+                    last_sent = last.id.lamport;
+                    if let Err(e) = handle.send_ops(ops) {
+                        warn!("[Sync API] ローカル操作の送信に失敗: {}", e);
+                        break;
+                    }
+                }
+            }
+        }
+    }
+
+    info!("[Sync API] 同期タスク終了");
+}
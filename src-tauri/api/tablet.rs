@@ -0,0 +1,142 @@
+use crate::settings::AppSettings;
+use crate::tablet::{InputAction, InputClassifier, MIN_CALIBRATION_SAMPLES, PointerInput, PressureCalibration, PressureCurve, TabletSettings};
+use super::settings::SettingsState;
+use log::{info, debug, trace};
+use serde::Serialize;
+use tauri::{AppHandle, State};
+use tokio::sync::Mutex;
+
+/// 進行中の筆圧校正
+struct CalibrationSession {
+    /// 校正対象のデバイス（None は全体のカーブ）
+    device_id: Option<String>,
+    calibration: PressureCalibration,
+}
+
+/// ペンタブレット設定の状態管理
+pub struct TabletState {
+    session: Mutex<Option<CalibrationSession>>,
+    /// ペンと指の入力の分類（触れているポインターを覚えておく）
+    classifier: Mutex<InputClassifier>,
+}
+
+impl TabletState {
+    pub fn new() -> Self {
+        Self {
+            session: Mutex::new(None),
+            classifier: Mutex::new(InputClassifier::new()),
+        }
+    }
+}
+
+impl Default for TabletState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 校正の進捗
+#[derive(Serialize)]
+pub struct CalibrationProgress {
+    pub device_id: Option<String>,
+    pub sample_count: usize,
+    pub required_samples: usize,
+}
+
+/// ペンタブレット設定（筆圧カーブとデバイスごとのプロファイル）を取得
+#[tauri::command]
+pub async fn get_tablet_settings(settings: State<'_, SettingsState>) -> Result<TabletSettings, String> {
+    Ok(settings.get().await.tablet)
+}
+
+/// 筆圧の校正を開始（進行中の校正は破棄される）
+#[tauri::command]
+pub async fn start_pressure_calibration(
+    device_id: Option<String>,
+    tablet: State<'_, TabletState>,
+) -> Result<CalibrationProgress, String> {
+    info!("[Tablet API] 筆圧の校正を開始: {:?}", device_id);
+    *tablet.session.lock().await = Some(CalibrationSession {
+        device_id: device_id.clone(),
+        calibration: PressureCalibration::new(),
+    });
+    Ok(CalibrationProgress {
+        device_id,
+        sample_count: 0,
+        required_samples: MIN_CALIBRATION_SAMPLES,
+    })
+}
+
+/// 校正用の生の筆圧サンプルを追加
+#[tauri::command]
+pub async fn record_pressure_samples(
+    samples: Vec<f32>,
+    tablet: State<'_, TabletState>,
+) -> Result<CalibrationProgress, String> {
+    let mut session_guard = tablet.session.lock().await;
+    let session = session_guard.as_mut().ok_or("筆圧の校正が開始されていません")?;
+    let sample_count = session.calibration.record(&samples).map_err(|e| e.to_string())?;
+    debug!("[Tablet API] 筆圧サンプル追加: {} 件（計 {} 件）", samples.len(), sample_count);
+    Ok(CalibrationProgress {
+        device_id: session.device_id.clone(),
+        sample_count,
+        required_samples: MIN_CALIBRATION_SAMPLES,
+    })
+}
+
+/// 集めたサンプルからカーブを推定して設定に保存
+#[tauri::command]
+pub async fn finish_pressure_calibration(
+    app: AppHandle,
+    tablet: State<'_, TabletState>,
+    settings: State<'_, SettingsState>,
+) -> Result<PressureCurve, String> {
+    let mut session_guard = tablet.session.lock().await;
+    let session = session_guard.as_ref().ok_or("筆圧の校正が開始されていません")?;
+    let curve = session.calibration.fit().map_err(|e| e.to_string())?;
+    let device_id = session.device_id.clone();
+
+    settings.update(&app, |current| {
+        let mut tablet_settings = current.tablet.clone();
+        tablet_settings.set_curve(device_id.as_deref(), curve);
+        Ok(AppSettings { tablet: tablet_settings, ..current.clone() })
+    }).await?;
+    *session_guard = None;
+
+    info!(
+        "[Tablet API] 筆圧カーブを保存: {:?} 入力 {:.3}〜{:.3} ガンマ {:.3}",
+        device_id, curve.input_min, curve.input_max, curve.gamma
+    );
+    Ok(curve)
+}
+
+/// 筆圧カーブを初期状態に戻す（デバイスIDを指定した場合はそのプロファイルを削除）
+#[tauri::command]
+pub async fn reset_pressure_curve(
+    device_id: Option<String>,
+    app: AppHandle,
+    settings: State<'_, SettingsState>,
+) -> Result<TabletSettings, String> {
+    let updated = settings.update(&app, |current| {
+        let mut tablet_settings = current.tablet.clone();
+        tablet_settings.reset_curve(device_id.as_deref());
+        Ok(AppSettings { tablet: tablet_settings, ..current.clone() })
+    }).await?;
+    info!("[Tablet API] 筆圧カーブをリセット: {:?}", device_id);
+    Ok(updated.tablet)
+}
+
+/// ポインターイベントを分類（描く・手のひらとして捨てる・2本指ジェスチャー）
+///
+/// `BeginGesture` が返ったら、1本目の指で描き始めたストロークは取り消す。
+#[tauri::command]
+pub async fn classify_pointer_input(
+    input: PointerInput,
+    tablet: State<'_, TabletState>,
+    settings: State<'_, SettingsState>,
+) -> Result<InputAction, String> {
+    let touch = settings.get().await.tablet.touch;
+    let action = tablet.classifier.lock().await.classify(&input, &touch);
+    trace!("[Tablet API] 入力を分類: {:?} {:?} {} → {:?}", input.kind, input.phase, input.pointer_id, action);
+    Ok(action)
+}
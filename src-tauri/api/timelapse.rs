@@ -0,0 +1,133 @@
+use crate::timelapse::{self, TimelapseBuffer, TimelapseConfig, TimelapseRecorder, TimelapseStatus};
+use crate::jobs::{JobContext, JobError};
+use super::drawing::DrawingState;
+use log::{info, error};
+use serde::Serialize;
+use std::path::Path;
+use tauri::State;
+
+/// 書き出し時のデフォルトのフレームレート
+const DEFAULT_TIMELAPSE_FPS: u32 = 12;
+
+/// タイムラプス書き出し結果
+#[derive(Serialize)]
+pub struct TimelapseExportResult {
+    pub path: String,
+    pub frame_count: usize,
+    pub fps: u32,
+}
+
+/// タイムラプスの記録を開始
+///
+/// `buffer` にプロジェクトに保存されていたタイムラプスを渡すと、その続きから記録する。
+#[tauri::command]
+pub async fn start_timelapse(
+    config: Option<TimelapseConfig>,
+    buffer: Option<TimelapseBuffer>,
+    state: State<'_, DrawingState>,
+) -> Result<TimelapseStatus, String> {
+    let mut timelapse_guard = state.timelapse.lock().await;
+    if timelapse_guard.is_some() {
+        return Err("タイムラプスは既に記録中です".to_string());
+    }
+
+    let recorder = TimelapseRecorder::new(config.unwrap_or_default(), buffer.unwrap_or_default())
+        .map_err(|e| e.to_string())?;
+    let status = recorder.status();
+    *timelapse_guard = Some(recorder);
+
+    info!("[Timelapse API] 記録開始");
+    Ok(status)
+}
+
+/// タイムラプスの記録を一時停止
+#[tauri::command]
+pub async fn pause_timelapse(state: State<'_, DrawingState>) -> Result<TimelapseStatus, String> {
+    let mut timelapse_guard = state.timelapse.lock().await;
+    let recorder = timelapse_guard.as_mut().ok_or("タイムラプスを記録していません")?;
+    recorder.pause();
+    info!("[Timelapse API] 記録一時停止");
+    Ok(recorder.status())
+}
+
+/// タイムラプスの記録を再開
+#[tauri::command]
+pub async fn resume_timelapse(state: State<'_, DrawingState>) -> Result<TimelapseStatus, String> {
+    let mut timelapse_guard = state.timelapse.lock().await;
+    let recorder = timelapse_guard.as_mut().ok_or("タイムラプスを記録していません")?;
+    recorder.resume();
+    info!("[Timelapse API] 記録再開");
+    Ok(recorder.status())
+}
+
+/// タイムラプスの記録を終了し、プロジェクトに保存するバッファを返す
+#[tauri::command]
+pub async fn stop_timelapse(state: State<'_, DrawingState>) -> Result<TimelapseBuffer, String> {
+    let recorder = state.timelapse.lock().await.take().ok_or("タイムラプスを記録していません")?;
+    let buffer = recorder.into_buffer();
+    info!("[Timelapse API] 記録終了: {} フレーム", buffer.frames.len());
+    Ok(buffer)
+}
+
+/// 記録状態を取得（記録していない場合は None）
+#[tauri::command]
+pub async fn get_timelapse_status(state: State<'_, DrawingState>) -> Result<Option<TimelapseStatus>, String> {
+    Ok(state.timelapse.lock().await.as_ref().map(|recorder| recorder.status()))
+}
+
+/// タイムラプスをGIFアニメーションとして書き出す
+///
+/// `buffer` を省略した場合は記録中のバッファを書き出す。動画形式には対応していない。
+#[tauri::command]
+pub async fn export_timelapse(
+    path: String,
+    fps: Option<u32>,
+    buffer: Option<TimelapseBuffer>,
+    state: State<'_, DrawingState>,
+) -> Result<TimelapseExportResult, String> {
+    run_export_timelapse(&state, path, fps, buffer, &JobContext::detached()).await
+        .map_err(|e| e.to_string())
+}
+
+/// タイムラプス書き出しの本体（ジョブとしても実行される）
+pub(crate) async fn run_export_timelapse(
+    state: &DrawingState,
+    path: String,
+    fps: Option<u32>,
+    buffer: Option<TimelapseBuffer>,
+    context: &JobContext,
+) -> Result<TimelapseExportResult, JobError> {
+    let is_gif = Path::new(&path).extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("gif"));
+    if !is_gif {
+        return Err(JobError::Failed("タイムラプスはGIF形式（.gif）でのみ書き出せます".to_string()));
+    }
+
+    let buffer = match buffer {
+        Some(buffer) => buffer,
+        None => state.timelapse.lock().await.as_ref()
+            .map(|recorder| recorder.buffer().clone())
+            .ok_or_else(|| JobError::Failed("書き出すタイムラプスがありません".to_string()))?,
+    };
+    let fps = fps.unwrap_or(DEFAULT_TIMELAPSE_FPS).clamp(1, 100);
+    info!("[Timelapse API] GIF書き出し開始: {} ({} フレーム)", path, buffer.frames.len());
+    context.check_cancelled()?;
+
+    // GIFの減色は重いため別スレッドで行う
+    context.report(0.1, "GIFをエンコードしています");
+    let frame_count = buffer.frames.len();
+    let output_path = path.clone();
+    tokio::task::spawn_blocking(move || {
+        let file = std::fs::File::create(&output_path)
+            .map_err(|e| format!("ファイルの作成に失敗しました: {}", e))?;
+        timelapse::encode_gif(&buffer, fps, std::io::BufWriter::new(file)).map_err(|e| {
+            error!("[Timelapse API] GIF書き出し失敗: {}", e);
+            e.to_string()
+        })
+    })
+    .await
+    .map_err(|e| format!("書き出しタスクエラー: {}", e))??;
+
+    info!("[Timelapse API] GIF書き出し完了: {}", path);
+    Ok(TimelapseExportResult { path, frame_count, fps })
+}
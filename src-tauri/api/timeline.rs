@@ -0,0 +1,90 @@
+use crate::animation::{FrameMarker, FrameMarkers};
+use super::drawing::DrawingState;
+use super::player::{PlayerState, PlayerStatus};
+use log::{info, debug};
+use serde::Serialize;
+use tauri::State;
+
+/// タイムラインの表示に使う情報
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelineMetadata {
+    /// フレーム順に並んだマーカー
+    pub markers: Vec<FrameMarker>,
+    /// 再生位置・再生範囲（`set_player_frames` で通知されたフレーム構成に基づく）
+    pub playback: PlayerStatus,
+}
+
+/// マーカーと再生状態をまとめて取得
+#[tauri::command]
+pub async fn get_timeline_metadata(
+    state: State<'_, DrawingState>,
+    player: State<'_, PlayerState>,
+) -> Result<TimelineMetadata, String> {
+    let markers = state.markers.lock().await.markers().to_vec();
+    Ok(TimelineMetadata { markers, playback: player.status().await })
+}
+
+/// マーカーを置き換える（プロジェクトを新規作成したときなど）
+#[tauri::command]
+pub async fn set_frame_markers(
+    mut markers: FrameMarkers,
+    state: State<'_, DrawingState>,
+) -> Result<Vec<FrameMarker>, String> {
+    markers.validate().map_err(|e| e.to_string())?;
+    *state.markers.lock().await = markers.clone();
+    info!("[Timeline API] マーカーを更新: {} 個", markers.markers().len());
+    Ok(markers.markers().to_vec())
+}
+
+/// フレームにマーカーを付ける（色を省略すると既定の色）
+#[tauri::command]
+pub async fn add_frame_marker(
+    frame: usize,
+    name: String,
+    color: Option<[u8; 3]>,
+    state: State<'_, DrawingState>,
+) -> Result<FrameMarker, String> {
+    let marker = state.markers.lock().await.add(frame, &name, color)
+        .map_err(|e| e.to_string())?;
+    info!("[Timeline API] マーカーを追加: {} (フレーム {})", marker.name, marker.frame);
+    Ok(marker)
+}
+
+/// マーカーの名前（と色）を変更
+#[tauri::command]
+pub async fn rename_frame_marker(
+    marker_id: String,
+    name: String,
+    color: Option<[u8; 3]>,
+    state: State<'_, DrawingState>,
+) -> Result<FrameMarker, String> {
+    let marker = state.markers.lock().await.rename(&marker_id, &name, color)
+        .map_err(|e| e.to_string())?;
+    debug!("[Timeline API] マーカー名を変更: {} -> {}", marker_id, marker.name);
+    Ok(marker)
+}
+
+/// マーカーを別のフレームへ移動
+#[tauri::command]
+pub async fn move_frame_marker(
+    marker_id: String,
+    frame: usize,
+    state: State<'_, DrawingState>,
+) -> Result<FrameMarker, String> {
+    let marker = state.markers.lock().await.move_to(&marker_id, frame)
+        .map_err(|e| e.to_string())?;
+    debug!("[Timeline API] マーカーを移動: {} -> フレーム {}", marker_id, frame);
+    Ok(marker)
+}
+
+/// マーカーを削除
+#[tauri::command]
+pub async fn remove_frame_marker(
+    marker_id: String,
+    state: State<'_, DrawingState>,
+) -> Result<FrameMarker, String> {
+    let marker = state.markers.lock().await.remove(&marker_id)
+        .map_err(|e| e.to_string())?;
+    info!("[Timeline API] マーカーを削除: {}", marker.name);
+    Ok(marker)
+}
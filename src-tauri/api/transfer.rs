@@ -0,0 +1,147 @@
+use crate::ipc::binary::{BinaryTransfer, TransferDictionary, TransferError, TransferOptions};
+use super::drawing::DrawingState;
+use super::formats::collect_raster_layers;
+use super::paging::ensure_resident;
+use log::{info, debug, error};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU32, Ordering};
+use tauri::ipc::Response;
+use tauri::{AppHandle, Emitter, State};
+use tokio::sync::Mutex;
+
+/// バイナリ転送の状態管理
+pub struct TransferState {
+    /// 学習済みの zstd 辞書
+    dictionary: Mutex<Option<TransferDictionary>>,
+    /// 次の転送に付ける連番
+    next_sequence: AtomicU32,
+}
+
+impl TransferState {
+    pub fn new() -> Self {
+        Self {
+            dictionary: Mutex::new(None),
+            next_sequence: AtomicU32::new(0),
+        }
+    }
+}
+
+impl Default for TransferState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 学習した辞書の情報
+#[derive(Serialize)]
+pub struct TransferDictionaryInfo {
+    pub id: u32,
+    pub size: usize,
+    pub sample_layers: usize,
+}
+
+/// 転送データの検証に失敗したことを通知するイベント（`transfer:error`）
+#[derive(Clone, Serialize)]
+pub struct TransferErrorEvent {
+    pub sequence: u32,
+    pub layer_id: String,
+    pub message: String,
+}
+
+/// レイヤー画像を圧縮して取得
+///
+/// 戻り値は `BinaryTransfer` のヘッダー付きバイト列。圧縮方式はリクエストごとに指定でき、
+/// 省略時は LZ4。zstd で辞書を使う場合は `get_transfer_dictionary` で辞書を取得しておく。
+/// デバッグビルドでは送信前にバイト列を展開してチェックサムを検証し、不一致なら
+/// `transfer:error` イベントを送ってエラーを返す。
+#[tauri::command]
+pub async fn get_layer_image_transfer(
+    layer_id: String,
+    options: Option<TransferOptions>,
+    app: AppHandle,
+    state: State<'_, DrawingState>,
+    transfer_state: State<'_, TransferState>,
+) -> Result<Response, String> {
+    let options = options.unwrap_or_default();
+    debug!("[Transfer API] レイヤー画像転送: {} ({:?})", layer_id, options.codec);
+
+    let (width, height) = state.layers.lock().await.get(&layer_id).copied()
+        .ok_or_else(|| format!("レイヤーが見つかりません: {}", layer_id))?;
+    ensure_resident(&state, Some(std::slice::from_ref(&layer_id))).await?;
+
+    let raw = {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        engine.get_layer_texture_data(&layer_id).await
+            .map_err(|e| format!("画像データ取得エラー: {}", e))?
+    };
+    let dictionary = transfer_state.dictionary.lock().await.clone();
+    let sequence = transfer_state.next_sequence.fetch_add(1, Ordering::Relaxed);
+
+    let bytes = tokio::task::spawn_blocking(move || {
+        let bytes = BinaryTransfer::encode(sequence, width, height, &raw, &options, dictionary.as_ref())?
+            .to_bytes();
+        if cfg!(debug_assertions) {
+            BinaryTransfer::from_bytes(&bytes)?.decode(dictionary.as_ref())?;
+        }
+        Ok::<_, TransferError>(bytes)
+    })
+    .await
+    .map_err(|e| format!("圧縮タスクエラー: {}", e))?;
+
+    match bytes {
+        Ok(bytes) => Ok(Response::new(bytes)),
+        Err(e) => {
+            error!("[Transfer API] 転送データ #{} の作成に失敗: {}", sequence, e);
+            let _ = app.emit("transfer:error", TransferErrorEvent {
+                sequence,
+                layer_id,
+                message: e.to_string(),
+            });
+            Err(e.to_string())
+        }
+    }
+}
+
+/// 現在のレイヤー画像から zstd 辞書を学習
+///
+/// 学習した辞書は以降の `use_dictionary` 指定の転送で使われ、以前の辞書は置き換えられる。
+#[tauri::command]
+pub async fn train_transfer_dictionary(
+    state: State<'_, DrawingState>,
+    transfer_state: State<'_, TransferState>,
+) -> Result<TransferDictionaryInfo, String> {
+    info!("[Transfer API] 転送用辞書の学習開始");
+
+    let (layers, ..) = collect_raster_layers(&state, None).await?;
+    let sample_layers = layers.len();
+    let dictionary = tokio::task::spawn_blocking(move || {
+        let images: Vec<_> = layers.iter().map(|layer| &layer.image).collect();
+        TransferDictionary::train(&images)
+    })
+    .await
+    .map_err(|e| format!("辞書学習タスクエラー: {}", e))?
+    .map_err(|e| {
+        error!("[Transfer API] 辞書の学習に失敗: {}", e);
+        e.to_string()
+    })?;
+
+    let info = TransferDictionaryInfo {
+        id: dictionary.id,
+        size: dictionary.data.len(),
+        sample_layers,
+    };
+    info!("[Transfer API] 転送用辞書を学習: ID {} ({} bytes, {} レイヤー)", info.id, info.size, sample_layers);
+    *transfer_state.dictionary.lock().await = Some(dictionary);
+    Ok(info)
+}
+
+/// 学習済みの zstd 辞書を取得（フロントエンドでの展開用）
+#[tauri::command]
+pub async fn get_transfer_dictionary(
+    transfer_state: State<'_, TransferState>,
+) -> Result<Response, String> {
+    let dictionary_guard = transfer_state.dictionary.lock().await;
+    let dictionary = dictionary_guard.as_ref().ok_or("転送用辞書が学習されていません")?;
+    Ok(Response::new(dictionary.data.clone()))
+}
@@ -0,0 +1,285 @@
+use crate::animation::Layer;
+use crate::drawing_engine::{Navigator, TileCoord, ViewTransform, Viewport, MAX_NAVIGATOR_SIZE, TILE_SIZE};
+use crate::formats::flatten_layers;
+use crate::history::Operation;
+use super::drawing::DrawingState;
+use super::formats::collect_preview_layers;
+use super::paging::ensure_resident;
+use log::{info, debug, error};
+use serde::Serialize;
+use std::hash::{Hash, Hasher};
+use tauri::State;
+
+/// 無限キャンバスの表示範囲の情報
+#[derive(Serialize)]
+pub struct ViewportInfo {
+    pub enabled: bool,
+    /// 表示範囲（大きさはレイヤーテクスチャの大きさ）
+    pub viewport: Viewport,
+    /// 表示範囲と重なるタイル
+    pub visible_tiles: Vec<TileCoord>,
+    /// 保持しているタイルの枚数（全レイヤー）
+    pub tile_count: usize,
+    pub tile_memory_bytes: u64,
+    /// タイルに保持している内容の範囲（表示範囲の内容は含まない）
+    pub tile_bounds: Option<Viewport>,
+}
+
+async fn viewport_info(state: &DrawingState) -> Result<ViewportInfo, String> {
+    let (width, height) = state.layers.lock().await.values()
+        .fold((0, 0), |(w, h), (lw, lh)| (w.max(*lw), h.max(*lh)));
+
+    let engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+    let Some(canvas) = engine.infinite_canvas() else {
+        return Ok(ViewportInfo {
+            enabled: false,
+            viewport: Viewport { x: 0, y: 0, width, height },
+            visible_tiles: Vec::new(),
+            tile_count: 0,
+            tile_memory_bytes: 0,
+            tile_bounds: None,
+        });
+    };
+
+    let viewport = Viewport { x: canvas.origin.0, y: canvas.origin.1, width, height };
+    let tile_bounds = canvas.layers.values()
+        .filter_map(|tiles| tiles.tile_bounds())
+        .reduce(|a, b| {
+            let left = a.x.min(b.x);
+            let top = a.y.min(b.y);
+            let right = (a.x as i64 + a.width as i64).max(b.x as i64 + b.width as i64);
+            let bottom = (a.y as i64 + a.height as i64).max(b.y as i64 + b.height as i64);
+            Viewport { x: left, y: top, width: (right - left as i64) as u32, height: (bottom - top as i64) as u32 }
+        });
+    Ok(ViewportInfo {
+        enabled: true,
+        viewport,
+        visible_tiles: viewport.visible_tiles(),
+        tile_count: canvas.tile_count(),
+        tile_memory_bytes: canvas.memory_bytes(),
+        tile_bounds,
+    })
+}
+
+/// 表示範囲に関わる操作を適用して履歴に記録
+async fn apply_viewport_operation(state: &DrawingState, operation: Operation) -> Result<ViewportInfo, String> {
+    // 表示範囲の移動は全レイヤーの内容を読み書きするため、退避中のレイヤーを復帰させる
+    ensure_resident(state, None).await?;
+
+    {
+        let mut engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_mut().ok_or("描画エンジンが初期化されていません")?;
+        let mut layers_guard = state.layers.lock().await;
+        operation.apply(engine, &mut layers_guard).map_err(|e| {
+            error!("[Viewport API] 表示範囲の変更に失敗: {}", e);
+            format!("表示範囲の変更エラー: {}", e)
+        })?;
+    }
+    state.record_operation(operation).await;
+
+    viewport_info(state).await
+}
+
+/// 無限キャンバスの状態を取得
+#[tauri::command]
+pub async fn get_viewport(state: State<'_, DrawingState>) -> Result<ViewportInfo, String> {
+    viewport_info(&state).await
+}
+
+/// 無限キャンバスモードを切り替える
+///
+/// 有効にすると現在のキャンバスが原点の表示範囲になり、表示範囲を動かして描き足せる。
+/// 無効にすると表示範囲外の内容は破棄される。
+#[tauri::command]
+pub async fn set_infinite_canvas(
+    enabled: bool,
+    state: State<'_, DrawingState>,
+) -> Result<ViewportInfo, String> {
+    let current = viewport_info(&state).await?;
+    if current.enabled == enabled {
+        return Ok(current);
+    }
+    let info = apply_viewport_operation(&state, Operation::SetInfiniteCanvas { enabled }).await?;
+    info!("[Viewport API] 無限キャンバス: {}", if enabled { "有効" } else { "無効" });
+    Ok(info)
+}
+
+/// 表示範囲の左上をキャンバス座標で指定して移動
+#[tauri::command]
+pub async fn set_viewport(
+    x: i32,
+    y: i32,
+    state: State<'_, DrawingState>,
+) -> Result<ViewportInfo, String> {
+    debug!("[Viewport API] 表示範囲移動: ({}, {})", x, y);
+    apply_viewport_operation(&state, Operation::SetViewport { x, y }).await
+}
+
+/// 表示範囲を相対的にスクロール
+#[tauri::command]
+pub async fn scroll_viewport(
+    dx: i32,
+    dy: i32,
+    state: State<'_, DrawingState>,
+) -> Result<ViewportInfo, String> {
+    let current = viewport_info(&state).await?;
+    if !current.enabled {
+        return Err("無限キャンバスが有効ではありません".to_string());
+    }
+    let x = current.viewport.x.checked_add(dx).ok_or("表示範囲が大きすぎます")?;
+    let y = current.viewport.y.checked_add(dy).ok_or("表示範囲が大きすぎます")?;
+    debug!("[Viewport API] スクロール: ({}, {}) -> ({}, {})", dx, dy, x, y);
+    apply_viewport_operation(&state, Operation::SetViewport { x, y }).await
+}
+
+/// 前回の表示以降に書き込まれたタイル
+#[derive(Serialize)]
+pub struct ChangedTiles {
+    /// 現在の世代（次の問い合わせの `since` に渡す）
+    pub generation: u64,
+    pub tile_size: u32,
+    /// 変わったタイル（レイヤーテクスチャのピクセル座標をタイルの大きさで割ったもの。行優先）
+    pub tiles: Vec<TileCoord>,
+}
+
+/// 世代 `since` より後に書き込まれたタイルを取得（`layer_ids` を省略すると全レイヤー）
+///
+/// 表示側は前回受け取った `generation` を渡し、返ったタイルだけを読み直して表示を更新する。
+/// 初回は `since` に 0 を渡すと、書き込まれたことのあるタイルがすべて返る。
+#[tauri::command]
+pub async fn get_changed_tiles(
+    layer_ids: Option<Vec<String>>,
+    since: u64,
+    state: State<'_, DrawingState>,
+) -> Result<ChangedTiles, String> {
+    let layer_ids = match layer_ids {
+        Some(layer_ids) => layer_ids,
+        None => state.layers.lock().await.keys().cloned().collect(),
+    };
+    let engine_guard = state.engine.lock().await;
+    let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+    let generations = engine.tile_generations();
+    let tiles = generations.changed_since(layer_ids.iter().map(String::as_str), since);
+    debug!("[Viewport API] 世代 {} 以降に変わったタイル: {} 枚", since, tiles.len());
+    Ok(ChangedTiles { generation: generations.generation(), tile_size: TILE_SIZE, tiles })
+}
+
+/// 表示の変換（拡大・回転・反転・移動）を取得
+#[tauri::command]
+pub async fn get_view_transform(state: State<'_, DrawingState>) -> Result<ViewTransform, String> {
+    Ok(*state.view.lock().await)
+}
+
+/// 表示の変換を表示側と同期する
+///
+/// 以降にストロークへ追加する点はこの変換でドキュメント座標に戻される。表示だけの状態のため履歴には記録しない。
+#[tauri::command]
+pub async fn set_view_transform(
+    transform: ViewTransform,
+    state: State<'_, DrawingState>,
+) -> Result<ViewTransform, String> {
+    transform.validate()?;
+    debug!("[Viewport API] 表示の変換: {:?}", transform);
+    *state.view.lock().await = transform;
+    Ok(transform)
+}
+
+/// ナビゲーター（ミニマップ）の画像
+#[derive(Serialize)]
+pub struct NavigatorImage {
+    pub width: u32,
+    pub height: u32,
+    /// RGBA（表示範囲の枠を描き込み済み）
+    pub data: Vec<u8>,
+    /// キャンバスのピクセルに対する縮小率（クリック位置をキャンバス座標に戻すのに使う）
+    pub scale: f32,
+    /// 表示範囲の四隅のキャンバス座標（画面の左上から時計回り）
+    pub viewport: [(f32, f32); 4],
+}
+
+/// ナビゲーターの縮小画像を前回から書き込まれたタイルの範囲だけ更新し、更新したかを返す
+///
+/// `layers`（省略時は全レイヤー）の構成や `max_size` が変わったときは全体を作り直す。
+pub(crate) async fn refresh_navigator(
+    state: &DrawingState,
+    navigator: &mut Navigator,
+    max_size: u32,
+    layers: Option<Vec<Layer>>,
+) -> Result<bool, String> {
+    // レイヤーの表示・順序・塗りの設定が変わったら全体を作り直す
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    serde_json::to_string(&layers).map_err(|e| e.to_string())?.hash(&mut hasher);
+    let source = hasher.finish();
+
+    let (layer_ids, canvas_size) = {
+        let layers_guard = state.layers.lock().await;
+        let canvas_size = layers_guard.values().fold((0, 0), |(w, h), &(lw, lh)| (w.max(lw), h.max(lh)));
+        (layers_guard.keys().cloned().collect::<Vec<_>>(), canvas_size)
+    };
+
+    let rebuild = navigator.needs_rebuild(canvas_size, max_size, source);
+    let (generation, tiles) = {
+        let engine_guard = state.engine.lock().await;
+        let engine = engine_guard.as_ref().ok_or("描画エンジンが初期化されていません")?;
+        let generations = engine.tile_generations();
+        let tiles = if rebuild {
+            Vec::new()
+        } else {
+            generations.changed_since(layer_ids.iter().map(String::as_str), navigator.generation())
+        };
+        (generations.generation(), tiles)
+    };
+
+    let changed = rebuild || !tiles.is_empty();
+    if changed {
+        let (raster_layers, width, height) = collect_preview_layers(state, layers).await?;
+        let composite = flatten_layers(&raster_layers, width, height);
+        if rebuild || composite.dimensions() != canvas_size {
+            debug!("[Viewport API] ナビゲーターを作り直す: {}x{} -> 長辺 {}", width, height, max_size);
+            navigator.rebuild(&composite, max_size, source, generation);
+        } else {
+            debug!("[Viewport API] ナビゲーターの更新: {} タイル", tiles.len());
+            navigator.update_tiles(&composite, &tiles, generation);
+        }
+    }
+    Ok(changed)
+}
+
+/// キャンバス全体の縮小画像に、現在の表示範囲の枠を描いたナビゲーターの画像を取得
+///
+/// `screen_width`・`screen_height` は表示領域の画面上の大きさで、表示の変換（`set_view_transform`）で
+/// キャンバス座標に戻して枠を描く。縮小画像は前回から書き込まれたタイルの範囲だけ作り直し、
+/// `layers`（省略時は全レイヤー）の構成や `max_size` が変わったときは全体を作り直す。
+/// 最後に指定した `max_size` と `layers` は、アイドル時のメンテナンスで縮小画像を先に更新しておくのに使う。
+#[tauri::command]
+pub async fn get_navigator_image(
+    max_size: u32,
+    screen_width: f32,
+    screen_height: f32,
+    layers: Option<Vec<Layer>>,
+    state: State<'_, DrawingState>,
+) -> Result<NavigatorImage, String> {
+    if !(1..=MAX_NAVIGATOR_SIZE).contains(&max_size) {
+        return Err(format!("ナビゲーターの大きさは 1〜{} で指定してください: {}", MAX_NAVIGATOR_SIZE, max_size));
+    }
+    if !(screen_width > 0.0 && screen_height > 0.0 && screen_width.is_finite() && screen_height.is_finite()) {
+        return Err("表示領域の大きさは正の値で指定してください".to_string());
+    }
+
+    *state.navigator_source.lock().await = Some((max_size, layers.clone()));
+    let mut navigator = state.navigator.lock().await;
+    refresh_navigator(&state, &mut navigator, max_size, layers).await?;
+
+    let view = *state.view.lock().await;
+    let viewport = [(0.0, 0.0), (screen_width, 0.0), (screen_width, screen_height), (0.0, screen_height)]
+        .map(|corner| view.screen_to_document(corner));
+    let image = navigator.render(&viewport);
+    Ok(NavigatorImage {
+        width: image.width(),
+        height: image.height(),
+        data: image.into_raw(),
+        scale: navigator.scale(),
+        viewport,
+    })
+}
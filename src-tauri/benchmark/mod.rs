@@ -0,0 +1,228 @@
+use crate::drawing_engine::DrawStroke;
+use crate::formats::RasterLayer;
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::time::Duration;
+
+/// ベンチマーク用の一時レイヤーIDの接頭辞（通常のレイヤーと衝突しないようにする）
+pub const BENCHMARK_LAYER_PREFIX: &str = "__benchmark_";
+
+/// 1回のベンチマークで計測できる最大の反復回数
+pub const MAX_ITERATIONS: u32 = 1000;
+
+/// ベンチマークのエラー型
+#[derive(Debug)]
+pub enum BenchmarkError {
+    InvalidOptions(String),
+}
+
+impl fmt::Display for BenchmarkError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BenchmarkError::InvalidOptions(msg) => write!(f, "無効なベンチマーク設定です: {}", msg),
+        }
+    }
+}
+
+impl Error for BenchmarkError {}
+
+/// ベンチマークの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BenchmarkKind {
+    /// ストロークの描画（1ストロークあたりの時間と点/秒）
+    StrokeThroughput,
+    /// レイヤー合成（解像度ごとの時間とメガピクセル/秒）
+    Compositing,
+    /// テクスチャの読み出し（解像度ごとの時間と MB/秒）
+    Readback,
+    /// フロントエンドとの往復（計測はフロントエンドで行い、結果の集計だけをバックエンドで行う）
+    IpcRoundTrip,
+}
+
+/// ベンチマークの設定（省略した項目は標準の値を使う）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BenchmarkOptions {
+    /// 計測の反復回数（ウォームアップの1回は含まない）
+    pub iterations: u32,
+    /// 計測する正方形キャンバスの一辺（ストローク描画は先頭の解像度のみ）
+    pub resolutions: Vec<u32>,
+    /// 1ストロークの点の数
+    pub stroke_points: usize,
+    /// 合成するレイヤーの数
+    pub layer_count: usize,
+}
+
+impl Default for BenchmarkOptions {
+    fn default() -> Self {
+        Self {
+            iterations: 20,
+            resolutions: vec![512, 1024, 2048, 4096],
+            stroke_points: 500,
+            layer_count: 4,
+        }
+    }
+}
+
+impl BenchmarkOptions {
+    pub fn validate(&self) -> Result<(), BenchmarkError> {
+        if self.iterations == 0 || self.iterations > MAX_ITERATIONS {
+            return Err(BenchmarkError::InvalidOptions(format!(
+                "反復回数は 1〜{} で指定してください: {}", MAX_ITERATIONS, self.iterations
+            )));
+        }
+        if self.resolutions.is_empty() || self.resolutions.contains(&0) {
+            return Err(BenchmarkError::InvalidOptions("解像度を1つ以上指定してください".to_string()));
+        }
+        if self.stroke_points < 2 {
+            return Err(BenchmarkError::InvalidOptions("ストロークには2点以上必要です".to_string()));
+        }
+        if self.layer_count == 0 {
+            return Err(BenchmarkError::InvalidOptions("レイヤーを1つ以上指定してください".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 計測時間の統計（ミリ秒）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimingStats {
+    pub samples: usize,
+    pub min_ms: f64,
+    pub mean_ms: f64,
+    pub median_ms: f64,
+    pub p95_ms: f64,
+    pub max_ms: f64,
+}
+
+impl TimingStats {
+    pub fn from_durations(durations: &[Duration]) -> Self {
+        let millis: Vec<f64> = durations.iter().map(|d| d.as_secs_f64() * 1000.0).collect();
+        Self::from_millis(&millis)
+    }
+
+    /// ミリ秒の計測値から統計を計算（負の値・非有限の値は除外する）
+    pub fn from_millis(millis: &[f64]) -> Self {
+        let mut sorted: Vec<f64> = millis.iter().copied().filter(|ms| ms.is_finite() && *ms >= 0.0).collect();
+        if sorted.is_empty() {
+            return Self { samples: 0, min_ms: 0.0, mean_ms: 0.0, median_ms: 0.0, p95_ms: 0.0, max_ms: 0.0 };
+        }
+        sorted.sort_by(f64::total_cmp);
+
+        let count = sorted.len();
+        let percentile = |p: f64| sorted[((count - 1) as f64 * p).round() as usize];
+        Self {
+            samples: count,
+            min_ms: sorted[0],
+            mean_ms: sorted.iter().sum::<f64>() / count as f64,
+            median_ms: percentile(0.5),
+            p95_ms: percentile(0.95),
+            max_ms: sorted[count - 1],
+        }
+    }
+
+    /// 中央値を基準にした1秒あたりの処理量
+    pub fn per_second(&self, amount: f64) -> Option<f64> {
+        (self.median_ms > 0.0).then(|| amount / (self.median_ms / 1000.0))
+    }
+}
+
+/// 1つの計測条件の結果
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkCase {
+    pub label: String,
+    pub width: u32,
+    pub height: u32,
+    pub timing: TimingStats,
+    /// 中央値から求めた処理量（単位は `throughput_unit`）
+    pub throughput: Option<f64>,
+    pub throughput_unit: &'static str,
+}
+
+/// ベンチマークの結果
+#[derive(Debug, Clone, Serialize)]
+pub struct BenchmarkResult {
+    pub kind: BenchmarkKind,
+    /// 計測に使ったGPUアダプター名
+    pub adapter: Option<String>,
+    pub iterations: u32,
+    pub cases: Vec<BenchmarkCase>,
+}
+
+/// 標準のストローク（一辺 `size` ピクセルのキャンバス全体に広がる渦巻き。毎回同じ形になる）
+pub fn synthetic_stroke(points: usize, size: u32) -> DrawStroke {
+    let mut stroke = DrawStroke::new([0.1, 0.2, 0.8, 1.0], 4.0);
+    let turns = 8.0;
+    let center = size as f32 * 0.5;
+    for i in 0..points {
+        let t = i as f32 / (points - 1).max(1) as f32;
+        let angle = t * turns * std::f32::consts::TAU;
+        let radius = (0.05 + t * 0.9) * center;
+        let pressure = 0.5 + 0.5 * (t * std::f32::consts::PI).sin();
+        stroke.add_point(center + radius * angle.cos(), center + radius * angle.sin(), pressure);
+    }
+    stroke
+}
+
+/// 標準のレイヤー画像（レイヤーごとに異なる半透明のグラデーション）
+pub fn synthetic_image(width: u32, height: u32, index: usize) -> RgbaImage {
+    let shift = (index as u32 * 61) % 256;
+    RgbaImage::from_fn(width, height, |x, y| {
+        let u = x * 255 / width.max(1);
+        let v = y * 255 / height.max(1);
+        Rgba([
+            ((u + shift) % 256) as u8,
+            ((v + shift) % 256) as u8,
+            ((u + v) / 2) as u8,
+            (96 + (u ^ v) % 160) as u8,
+        ])
+    })
+}
+
+/// 標準の合成用レイヤー一式
+pub fn synthetic_layers(width: u32, height: u32, count: usize) -> Vec<RasterLayer> {
+    (0..count)
+        .map(|index| {
+            let mut layer = RasterLayer::new(format!("{}{}", BENCHMARK_LAYER_PREFIX, index), synthetic_image(width, height, index));
+            layer.opacity = 0.8;
+            layer
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timing_stats() {
+        let stats = TimingStats::from_millis(&[4.0, 1.0, 3.0, 2.0, f64::NAN, 5.0]);
+        assert_eq!(stats.samples, 5);
+        assert_eq!(stats.min_ms, 1.0);
+        assert_eq!(stats.median_ms, 3.0);
+        assert_eq!(stats.p95_ms, 5.0);
+        assert_eq!(stats.max_ms, 5.0);
+        assert_eq!(stats.mean_ms, 3.0);
+        assert_eq!(stats.per_second(6.0), Some(2000.0));
+
+        assert_eq!(TimingStats::from_millis(&[]).samples, 0);
+        assert_eq!(TimingStats::from_millis(&[]).per_second(1.0), None);
+    }
+
+    #[test]
+    fn test_options_and_workloads() {
+        assert!(BenchmarkOptions::default().validate().is_ok());
+        let options = BenchmarkOptions { iterations: 0, ..Default::default() };
+        assert!(options.validate().is_err());
+        let options = BenchmarkOptions { resolutions: vec![0], ..Default::default() };
+        assert!(options.validate().is_err());
+
+        let stroke = synthetic_stroke(100, 512);
+        assert_eq!(stroke.points.len(), 100);
+        assert!(stroke.points.iter().all(|p| p.position.iter().all(|c| (0.0..=512.0).contains(c))));
+        assert_eq!(synthetic_image(16, 8, 1), synthetic_image(16, 8, 1));
+    }
+}
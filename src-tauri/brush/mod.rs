@@ -0,0 +1,570 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use log::{info, debug, warn};
+
+// ストロークからスタンプ列の生成
+pub mod stamps;
+pub use stamps::{StampSettings, COMPUTE_STAMP_THRESHOLD};
+
+/// ブラシパックのフォーマット識別子
+pub const BRUSH_PACK_FORMAT: &str = "kinegraph-brush-pack";
+
+/// ブラシパックのフォーマットバージョン
+pub const BRUSH_PACK_VERSION: u32 = 1;
+
+/// 画像先端の最大サイズ（ピクセル）
+pub const MAX_TIP_SIZE: u32 = 1024;
+
+/// ブラシ直径の上限（ピクセル）
+pub const MAX_BRUSH_SIZE: f32 = 4096.0;
+
+/// 1回の間隔で打つスタンプ数の上限
+pub const MAX_STAMP_COUNT: u32 = 64;
+
+/// ブラシライブラリのエラー型
+#[derive(Debug)]
+pub enum BrushError {
+    ParseFailed(String),
+    UnsupportedFormat(String),
+    UnsupportedVersion(u32),
+    InvalidParameter(String, String),
+    InvalidTip(String, String),
+    DuplicateBrush(String),
+    PackAlreadyInstalled(String),
+    PackNotFound(String),
+}
+
+impl fmt::Display for BrushError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            BrushError::ParseFailed(msg) => write!(f, "ブラシパックの解析に失敗しました: {}", msg),
+            BrushError::UnsupportedFormat(format) => write!(f, "未対応のブラシパック形式です: {}", format),
+            BrushError::UnsupportedVersion(version) => write!(f, "未対応のブラシパックバージョンです: {}", version),
+            BrushError::InvalidParameter(brush, msg) => write!(f, "ブラシ '{}' のパラメータが不正です: {}", brush, msg),
+            BrushError::InvalidTip(brush, msg) => write!(f, "ブラシ '{}' の先端画像が不正です: {}", brush, msg),
+            BrushError::DuplicateBrush(brush) => write!(f, "ブラシIDが重複しています: {}", brush),
+            BrushError::PackAlreadyInstalled(pack) => write!(f, "ブラシパックは既にインストールされています: {}", pack),
+            BrushError::PackNotFound(pack) => write!(f, "ブラシパックが見つかりません: {}", pack),
+        }
+    }
+}
+
+impl Error for BrushError {}
+
+/// 筆圧の適用先
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PressureDynamics {
+    #[serde(default = "default_true")]
+    pub size: bool,
+    #[serde(default)]
+    pub opacity: bool,
+}
+
+impl Default for PressureDynamics {
+    fn default() -> Self {
+        Self { size: true, opacity: false }
+    }
+}
+
+/// ブラシ先端の定義（JSON上の表現）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum TipDefinition {
+    Round {
+        #[serde(default = "default_hardness")]
+        hardness: f32,
+    },
+    Image {
+        png_base64: String,
+    },
+}
+
+impl Default for TipDefinition {
+    fn default() -> Self {
+        TipDefinition::Round { hardness: default_hardness() }
+    }
+}
+
+/// ブラシ定義（JSON上の表現）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BrushDefinition {
+    id: String,
+    name: String,
+    #[serde(default = "default_size")]
+    size: f32,
+    #[serde(default = "default_spacing")]
+    spacing: f32,
+    #[serde(default = "default_one")]
+    opacity: f32,
+    #[serde(default = "default_one")]
+    flow: f32,
+    #[serde(default)]
+    angle: f32,
+    #[serde(default = "default_one")]
+    roundness: f32,
+    #[serde(default)]
+    scatter: f32,
+    #[serde(default = "default_count")]
+    count: u32,
+    #[serde(default)]
+    pressure: PressureDynamics,
+    #[serde(default)]
+    tip: TipDefinition,
+}
+
+/// ブラシパック（JSON上の表現）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct BrushPackDefinition {
+    format: String,
+    version: u32,
+    id: String,
+    name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    author: Option<String>,
+    brushes: Vec<BrushDefinition>,
+}
+
+/// スタンプブラシの先端形状
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum BrushTip {
+    /// 円形先端（hardness: 0.0=ぼかし最大, 1.0=くっきり）
+    Round { hardness: f32 },
+    /// 画像先端（8bitアルファマスク）
+    Image {
+        width: u32,
+        height: u32,
+        #[serde(skip)]
+        alpha: Vec<u8>,
+    },
+}
+
+/// 登録済みのスタンプブラシ
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct StampBrush {
+    pub id: String,
+    pub name: String,
+    /// 直径（ピクセル）
+    pub size: f32,
+    /// スタンプ間隔（直径に対する比率）
+    pub spacing: f32,
+    pub opacity: f32,
+    pub flow: f32,
+    /// 先端の回転角（度）
+    pub angle: f32,
+    /// 先端の縦横比（1.0=真円）
+    pub roundness: f32,
+    /// スタンプを散らす範囲（直径に対する比率。0.0=散らさない）
+    pub scatter: f32,
+    /// 1回の間隔で打つスタンプ数（スプレーなど）
+    pub count: u32,
+    pub pressure: PressureDynamics,
+    pub tip: BrushTip,
+}
+
+/// インストール済みのブラシパック
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BrushPack {
+    pub id: String,
+    pub name: String,
+    pub author: Option<String>,
+    pub brushes: Vec<StampBrush>,
+}
+
+impl BrushPack {
+    /// JSONのブラシパックを解析・検証
+    ///
+    /// 形式は次の通り（`version` は現在 1 のみ対応）:
+    ///
+    /// ```json
+    /// {
+    ///   "format": "kinegraph-brush-pack",
+    ///   "version": 1,
+    ///   "id": "inking",
+    ///   "name": "Inking Pack",
+    ///   "author": "someone",
+    ///   "brushes": [
+    ///     {
+    ///       "id": "g-pen",
+    ///       "name": "Gペン",
+    ///       "size": 8.0,
+    ///       "spacing": 0.1,
+    ///       "opacity": 1.0,
+    ///       "flow": 1.0,
+    ///       "angle": 0.0,
+    ///       "roundness": 1.0,
+    ///       "scatter": 0.0,
+    ///       "count": 1,
+    ///       "pressure": { "size": true, "opacity": false },
+    ///       "tip": { "type": "round", "hardness": 0.9 }
+    ///     },
+    ///     {
+    ///       "id": "chalk",
+    ///       "name": "チョーク",
+    ///       "tip": { "type": "image", "png_base64": "iVBORw0KGgo..." }
+    ///     }
+    ///   ]
+    /// }
+    /// ```
+    ///
+    /// 省略したパラメータはデフォルト値になる。`image` 先端のPNGは輝度をアルファとして
+    /// 扱い、白い部分が描画される。
+    pub fn from_json(json: &str) -> Result<Self, BrushError> {
+        let definition: BrushPackDefinition = serde_json::from_str(json)
+            .map_err(|e| BrushError::ParseFailed(e.to_string()))?;
+
+        if definition.format != BRUSH_PACK_FORMAT {
+            return Err(BrushError::UnsupportedFormat(definition.format));
+        }
+        if definition.version > BRUSH_PACK_VERSION {
+            return Err(BrushError::UnsupportedVersion(definition.version));
+        }
+        if definition.id.trim().is_empty() || definition.id.contains('/') {
+            return Err(BrushError::ParseFailed(format!("無効なパックIDです: '{}'", definition.id)));
+        }
+
+        let mut brushes: Vec<StampBrush> = Vec::with_capacity(definition.brushes.len());
+        for brush in definition.brushes {
+            if brushes.iter().any(|b| b.id == brush.id) {
+                return Err(BrushError::DuplicateBrush(brush.id));
+            }
+            brushes.push(StampBrush::from_definition(brush)?);
+        }
+
+        debug!("[BrushLibrary] ブラシパック解析完了: {} ({} ブラシ)", definition.id, brushes.len());
+        Ok(Self {
+            id: definition.id,
+            name: definition.name,
+            author: definition.author,
+            brushes,
+        })
+    }
+
+    /// `from_json` で読み込める形式の JSON に書き出す（他の環境とブラシ定義を共有する）
+    ///
+    /// すべてのパラメータを省略せずに書き出し、画像先端はアルファをそのまま輝度にした
+    /// グレースケールの PNG にする（読み込むと同じマスクに戻る）。
+    pub fn to_json(&self) -> Result<String, BrushError> {
+        let definition = BrushPackDefinition {
+            format: BRUSH_PACK_FORMAT.to_string(),
+            version: BRUSH_PACK_VERSION,
+            id: self.id.clone(),
+            name: self.name.clone(),
+            author: self.author.clone(),
+            brushes: self.brushes.iter().map(StampBrush::to_definition).collect::<Result<_, _>>()?,
+        };
+        serde_json::to_string_pretty(&definition)
+            .map_err(|e| BrushError::ParseFailed(e.to_string()))
+    }
+}
+
+impl StampBrush {
+    fn to_definition(&self) -> Result<BrushDefinition, BrushError> {
+        let tip = match &self.tip {
+            BrushTip::Round { hardness } => TipDefinition::Round { hardness: *hardness },
+            BrushTip::Image { width, height, alpha } => TipDefinition::Image {
+                png_base64: encode_tip_image(&self.id, *width, *height, alpha)?,
+            },
+        };
+        Ok(BrushDefinition {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            size: self.size,
+            spacing: self.spacing,
+            opacity: self.opacity,
+            flow: self.flow,
+            angle: self.angle,
+            roundness: self.roundness,
+            scatter: self.scatter,
+            count: self.count,
+            pressure: self.pressure,
+            tip,
+        })
+    }
+
+    fn from_definition(definition: BrushDefinition) -> Result<Self, BrushError> {
+        let id = definition.id;
+        let invalid = |msg: String| BrushError::InvalidParameter(id.clone(), msg);
+
+        if id.trim().is_empty() || id.contains('/') {
+            return Err(invalid(format!("無効なブラシIDです: '{}'", id)));
+        }
+        if !(definition.size > 0.0 && definition.size <= MAX_BRUSH_SIZE) {
+            return Err(invalid(format!("size は 0 より大きく {} 以下である必要があります: {}", MAX_BRUSH_SIZE, definition.size)));
+        }
+        if !(definition.spacing > 0.0 && definition.spacing <= 10.0) {
+            return Err(invalid(format!("spacing は 0 より大きく 10 以下である必要があります: {}", definition.spacing)));
+        }
+        for (name, value) in [("opacity", definition.opacity), ("flow", definition.flow)] {
+            if !(0.0..=1.0).contains(&value) {
+                return Err(invalid(format!("{} は 0.0〜1.0 の範囲で指定してください: {}", name, value)));
+            }
+        }
+        if !(definition.roundness > 0.0 && definition.roundness <= 1.0) {
+            return Err(invalid(format!("roundness は 0 より大きく 1.0 以下である必要があります: {}", definition.roundness)));
+        }
+        if !definition.angle.is_finite() {
+            return Err(invalid("angle が数値ではありません".to_string()));
+        }
+        if !(0.0..=10.0).contains(&definition.scatter) {
+            return Err(invalid(format!("scatter は 0.0〜10.0 の範囲で指定してください: {}", definition.scatter)));
+        }
+        if !(1..=MAX_STAMP_COUNT).contains(&definition.count) {
+            return Err(invalid(format!("count は 1〜{} の範囲で指定してください: {}", MAX_STAMP_COUNT, definition.count)));
+        }
+
+        let tip = match definition.tip {
+            TipDefinition::Round { hardness } => {
+                if !(0.0..=1.0).contains(&hardness) {
+                    return Err(invalid(format!("hardness は 0.0〜1.0 の範囲で指定してください: {}", hardness)));
+                }
+                BrushTip::Round { hardness }
+            }
+            TipDefinition::Image { png_base64 } => decode_tip_image(&id, &png_base64)?,
+        };
+
+        Ok(Self {
+            id,
+            name: definition.name,
+            size: definition.size,
+            spacing: definition.spacing,
+            opacity: definition.opacity,
+            flow: definition.flow,
+            angle: definition.angle,
+            roundness: definition.roundness,
+            scatter: definition.scatter,
+            count: definition.count,
+            pressure: definition.pressure,
+            tip,
+        })
+    }
+}
+
+/// Base64のPNGを先端用アルファマスクに変換
+fn decode_tip_image(brush_id: &str, png_base64: &str) -> Result<BrushTip, BrushError> {
+    let invalid = |msg: String| BrushError::InvalidTip(brush_id.to_string(), msg);
+
+    let bytes = STANDARD.decode(png_base64.trim())
+        .map_err(|e| invalid(format!("Base64のデコードに失敗: {}", e)))?;
+    let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+        .map_err(|e| invalid(format!("PNGのデコードに失敗: {}", e)))?;
+
+    let (width, height) = (image.width(), image.height());
+    if width == 0 || height == 0 || width > MAX_TIP_SIZE || height > MAX_TIP_SIZE {
+        return Err(invalid(format!("先端画像のサイズが範囲外です: {}x{} (最大 {})", width, height, MAX_TIP_SIZE)));
+    }
+
+    // 透過PNGはアルファとの積、不透明PNGは輝度をそのまま使う
+    let rgba = image.to_rgba8();
+    let alpha = rgba.pixels().map(|p| {
+        let luma = (p[0] as u32 * 299 + p[1] as u32 * 587 + p[2] as u32 * 114) / 1000;
+        (luma * p[3] as u32 / 255) as u8
+    }).collect();
+
+    Ok(BrushTip::Image { width, height, alpha })
+}
+
+/// 先端用アルファマスクを Base64 の PNG（グレースケール）に変換
+fn encode_tip_image(brush_id: &str, width: u32, height: u32, alpha: &[u8]) -> Result<String, BrushError> {
+    let invalid = |msg: String| BrushError::InvalidTip(brush_id.to_string(), msg);
+
+    let image = image::GrayImage::from_raw(width, height, alpha.to_vec())
+        .ok_or_else(|| invalid(format!("先端画像のサイズとマスクが一致しません: {}x{}", width, height)))?;
+    let mut bytes = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut bytes, image::ImageFormat::Png)
+        .map_err(|e| invalid(format!("PNGのエンコードに失敗: {}", e)))?;
+    Ok(STANDARD.encode(bytes.into_inner()))
+}
+
+/// インストール済みブラシパックの管理
+#[derive(Debug, Default)]
+pub struct BrushLibrary {
+    packs: Vec<BrushPack>,
+}
+
+impl BrushLibrary {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// ブラシパックをインストール（同じIDのパックは replace 指定時のみ置き換え）
+    pub fn install(&mut self, pack: BrushPack, replace: bool) -> Result<(), BrushError> {
+        match self.packs.iter().position(|p| p.id == pack.id) {
+            Some(index) if replace => {
+                warn!("[BrushLibrary] ブラシパックを置き換え: {}", pack.id);
+                self.packs[index] = pack;
+            }
+            Some(_) => return Err(BrushError::PackAlreadyInstalled(pack.id)),
+            None => {
+                info!("[BrushLibrary] ブラシパックをインストール: {} ({} ブラシ)", pack.id, pack.brushes.len());
+                self.packs.push(pack);
+            }
+        }
+        Ok(())
+    }
+
+    /// ブラシパックを削除
+    pub fn remove(&mut self, pack_id: &str) -> Result<BrushPack, BrushError> {
+        let index = self.packs.iter().position(|p| p.id == pack_id)
+            .ok_or_else(|| BrushError::PackNotFound(pack_id.to_string()))?;
+        info!("[BrushLibrary] ブラシパックを削除: {}", pack_id);
+        Ok(self.packs.remove(index))
+    }
+
+    pub fn packs(&self) -> &[BrushPack] {
+        &self.packs
+    }
+
+    /// 登録済みの全ブラシ（`パックID/ブラシID` 形式のIDと共に）
+    pub fn brushes(&self) -> impl Iterator<Item = (String, &StampBrush)> {
+        self.packs.iter().flat_map(|pack| {
+            pack.brushes.iter().map(move |brush| (format!("{}/{}", pack.id, brush.id), brush))
+        })
+    }
+
+    /// `パックID/ブラシID` でブラシを取得
+    pub fn get(&self, qualified_id: &str) -> Option<&StampBrush> {
+        let (pack_id, brush_id) = qualified_id.split_once('/')?;
+        self.packs.iter()
+            .find(|p| p.id == pack_id)?
+            .brushes.iter()
+            .find(|b| b.id == brush_id)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn default_one() -> f32 {
+    1.0
+}
+
+fn default_size() -> f32 {
+    10.0
+}
+
+fn default_spacing() -> f32 {
+    0.1
+}
+
+fn default_hardness() -> f32 {
+    1.0
+}
+
+fn default_count() -> u32 {
+    1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pack_json(brushes: &str) -> String {
+        format!(r#"{{
+            "format": "kinegraph-brush-pack",
+            "version": 1,
+            "id": "test-pack",
+            "name": "Test Pack",
+            "brushes": [{}]
+        }}"#, brushes)
+    }
+
+    fn tip_png_base64() -> String {
+        let mut tip = image::RgbaImage::new(4, 2);
+        tip.put_pixel(0, 0, image::Rgba([255, 255, 255, 255]));
+        tip.put_pixel(1, 0, image::Rgba([255, 255, 255, 128]));
+        let mut bytes = std::io::Cursor::new(Vec::new());
+        tip.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        STANDARD.encode(bytes.into_inner())
+    }
+
+    #[test]
+    fn test_parse_defaults() {
+        let pack = BrushPack::from_json(&pack_json(r#"{"id": "pen", "name": "Pen"}"#)).unwrap();
+        let brush = &pack.brushes[0];
+        assert_eq!(brush.size, 10.0);
+        assert_eq!(brush.spacing, 0.1);
+        assert_eq!((brush.scatter, brush.count), (0.0, 1));
+        assert_eq!(brush.pressure, PressureDynamics::default());
+        assert_eq!(brush.tip, BrushTip::Round { hardness: 1.0 });
+    }
+
+    #[test]
+    fn test_parse_image_tip() {
+        let json = pack_json(&format!(
+            r#"{{"id": "chalk", "name": "Chalk", "size": 24, "tip": {{"type": "image", "png_base64": "{}"}}}}"#,
+            tip_png_base64()
+        ));
+        let pack = BrushPack::from_json(&json).unwrap();
+        match &pack.brushes[0].tip {
+            BrushTip::Image { width, height, alpha } => {
+                assert_eq!((*width, *height), (4, 2));
+                assert_eq!(alpha[0], 255);
+                assert_eq!(alpha[1], 128);
+                assert_eq!(alpha[2], 0);
+            }
+            other => panic!("予期しない先端: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let json = pack_json(&format!(
+            r#"{{"id": "pen", "name": "Pen", "size": 6, "flow": 0.5, "pressure": {{"size": false, "opacity": true}},
+                "tip": {{"type": "round", "hardness": 0.25}}}},
+               {{"id": "chalk", "name": "Chalk", "angle": 30, "tip": {{"type": "image", "png_base64": "{}"}}}}"#,
+            tip_png_base64()
+        ));
+        let pack = BrushPack::from_json(&json).unwrap();
+        let exported = pack.to_json().unwrap();
+        assert!(exported.contains(BRUSH_PACK_FORMAT));
+        assert_eq!(BrushPack::from_json(&exported).unwrap(), pack);
+    }
+
+    #[test]
+    fn test_rejects_invalid_definitions() {
+        assert!(matches!(
+            BrushPack::from_json(&pack_json(r#"{"id": "pen", "name": "Pen", "opacity": 1.5}"#)),
+            Err(BrushError::InvalidParameter(_, _))
+        ));
+        assert!(matches!(
+            BrushPack::from_json(&pack_json(r#"{"id": "a", "name": "A"}, {"id": "a", "name": "B"}"#)),
+            Err(BrushError::DuplicateBrush(_))
+        ));
+        assert!(matches!(
+            BrushPack::from_json(&pack_json(r#"{"id": "bad", "name": "Bad", "tip": {"type": "image", "png_base64": "!!"}}"#)),
+            Err(BrushError::InvalidTip(_, _))
+        ));
+        assert!(matches!(
+            BrushPack::from_json(r#"{"format": "other", "version": 1, "id": "x", "name": "x", "brushes": []}"#),
+            Err(BrushError::UnsupportedFormat(_))
+        ));
+        assert!(matches!(
+            BrushPack::from_json(r#"{"format": "kinegraph-brush-pack", "version": 99, "id": "x", "name": "x", "brushes": []}"#),
+            Err(BrushError::UnsupportedVersion(99))
+        ));
+    }
+
+    #[test]
+    fn test_library_install_and_remove() {
+        let mut library = BrushLibrary::new();
+        let pack = BrushPack::from_json(&pack_json(r#"{"id": "pen", "name": "Pen"}"#)).unwrap();
+
+        library.install(pack.clone(), false).unwrap();
+        assert!(matches!(library.install(pack.clone(), false), Err(BrushError::PackAlreadyInstalled(_))));
+        library.install(pack, true).unwrap();
+        assert_eq!(library.packs().len(), 1);
+
+        let ids: Vec<String> = library.brushes().map(|(id, _)| id).collect();
+        assert_eq!(ids, vec!["test-pack/pen"]);
+        assert_eq!(library.get("test-pack/pen").unwrap().name, "Pen");
+        assert!(library.get("pen").is_none());
+
+        library.remove("test-pack").unwrap();
+        assert!(library.packs().is_empty());
+        assert!(matches!(library.remove("test-pack"), Err(BrushError::PackNotFound(_))));
+    }
+}
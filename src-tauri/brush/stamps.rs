@@ -0,0 +1,254 @@
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use crate::drawing_engine::{Stamp, StampShape, StampRasterizer};
+use crate::history::StrokePointRecord;
+use super::{BrushError, BrushTip, PressureDynamics, StampBrush};
+
+/// この数以上のスタンプはコンピュートシェーダーで描画する
+///
+/// 少ないスタンプは三角形の方が準備が軽く、スプレーのように数千個を重ねる場合は
+/// 1スタンプあたり数十の三角形を作るより画素ごとに合成する方が速い。
+pub const COMPUTE_STAMP_THRESHOLD: usize = 2048;
+
+/// 1ストロークで生成するスタンプ数の上限
+pub const MAX_STROKE_STAMPS: usize = 1 << 20;
+
+/// スタンプ間隔の下限（ピクセル）
+const MIN_STAMP_STEP: f32 = 0.5;
+
+/// スタンプの生成に使うブラシのパラメータ（履歴に記録して再生に使う）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StampSettings {
+    /// 直径（ピクセル）
+    pub size: f32,
+    /// スタンプ間隔（直径に対する比率）
+    pub spacing: f32,
+    pub opacity: f32,
+    pub flow: f32,
+    /// 先端の回転角（度）
+    pub angle: f32,
+    /// 先端の縦横比（1.0=真円）
+    pub roundness: f32,
+    pub hardness: f32,
+    /// スタンプを散らす範囲（直径に対する比率）
+    pub scatter: f32,
+    /// 1回の間隔で打つスタンプ数
+    pub count: u32,
+    pub pressure: PressureDynamics,
+}
+
+impl StampBrush {
+    /// スタンプ描画用のパラメータ（画像先端は未対応）
+    pub fn stamp_settings(&self) -> Result<StampSettings, BrushError> {
+        let hardness = match &self.tip {
+            BrushTip::Round { hardness } => *hardness,
+            BrushTip::Image { .. } => {
+                return Err(BrushError::InvalidTip(self.id.clone(), "画像先端のスタンプ描画には未対応です".to_string()));
+            }
+        };
+        Ok(StampSettings {
+            size: self.size,
+            spacing: self.spacing,
+            opacity: self.opacity,
+            flow: self.flow,
+            angle: self.angle,
+            roundness: self.roundness,
+            hardness,
+            scatter: self.scatter,
+            count: self.count,
+            pressure: self.pressure,
+        })
+    }
+}
+
+impl StampSettings {
+    /// 描画色を指定したスタンプの形状（色のアルファにブラシの不透明度を掛け、ストローク全体に1回だけ適用する）
+    pub fn shape(&self, color: [f32; 4]) -> StampShape {
+        StampShape {
+            color: [color[0], color[1], color[2], color[3] * self.opacity],
+            hardness: self.hardness,
+            angle: self.angle,
+            roundness: self.roundness,
+        }
+    }
+
+    /// ストロークの点列に沿ってスタンプを並べる
+    ///
+    /// 間隔はその位置の直径（筆圧を反映）に比例し、点の間隔とは無関係に一定の距離ごとに打つ。
+    /// 散らす位置は `seed` から決まるため、同じ引数なら再生しても同じスタンプ列になる。
+    /// スタンプの不透明度は flow と筆圧の積（重なるほど濃くなる）。opacity は `shape` の色に掛ける。
+    pub fn stamps(&self, points: &[StrokePointRecord], seed: u64) -> Vec<Stamp> {
+        let mut stamps = Vec::new();
+        let mut random = SplitMix64(seed);
+        let mut emit = |x: f32, y: f32, pressure: f32, stamps: &mut Vec<Stamp>| -> f32 {
+            let pressure = pressure.clamp(0.0, 1.0);
+            let diameter = if self.pressure.size { self.size * pressure } else { self.size };
+            let opacity = self.flow * if self.pressure.opacity { pressure } else { 1.0 };
+            if diameter > 0.0 && opacity > 0.0 {
+                for _ in 0..self.count {
+                    let (dx, dy) = if self.scatter > 0.0 {
+                        // 円内に一様に散らす
+                        let radius = self.scatter * diameter * random.next_f32().sqrt();
+                        let angle = random.next_f32() * std::f32::consts::TAU;
+                        (radius * angle.cos(), radius * angle.sin())
+                    } else {
+                        (0.0, 0.0)
+                    };
+                    stamps.push(Stamp { center: [x + dx, y + dy], radius: diameter * 0.5, opacity });
+                }
+            }
+            (diameter * self.spacing).max(MIN_STAMP_STEP)
+        };
+
+        match points {
+            [] => {}
+            [point] => {
+                emit(point.x, point.y, point.pressure, &mut stamps);
+            }
+            _ => {
+                // 次のスタンプまでの残り距離（区間をまたいで持ち越す）
+                let mut next = 0.0;
+                for pair in points.windows(2) {
+                    let (a, b) = (&pair[0], &pair[1]);
+                    let length = ((b.x - a.x).powi(2) + (b.y - a.y).powi(2)).sqrt();
+                    let mut distance = next;
+                    while distance <= length {
+                        if stamps.len() >= MAX_STROKE_STAMPS {
+                            warn!("[BrushStamps] スタンプ数が上限 {} に達したため打ち切り", MAX_STROKE_STAMPS);
+                            return stamps;
+                        }
+                        let t = if length > 0.0 { distance / length } else { 0.0 };
+                        distance += emit(
+                            a.x + (b.x - a.x) * t,
+                            a.y + (b.y - a.y) * t,
+                            a.pressure + (b.pressure - a.pressure) * t,
+                            &mut stamps,
+                        );
+                    }
+                    next = distance - length;
+                }
+            }
+        }
+
+        debug!("[BrushStamps] {} 点から {} 個のスタンプを生成", points.len(), stamps.len());
+        stamps
+    }
+}
+
+/// スタンプ数に応じて描画方法を選ぶ
+pub fn select_rasterizer(stamp_count: usize) -> StampRasterizer {
+    if stamp_count >= COMPUTE_STAMP_THRESHOLD {
+        StampRasterizer::Compute
+    } else {
+        StampRasterizer::Triangles
+    }
+}
+
+/// 散らす位置に使う擬似乱数（SplitMix64。環境によらず同じ列になる）
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// 0.0 以上 1.0 未満
+    fn next_f32(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> StampSettings {
+        StampSettings {
+            size: 10.0,
+            spacing: 0.5,
+            opacity: 1.0,
+            flow: 0.5,
+            angle: 0.0,
+            roundness: 1.0,
+            hardness: 1.0,
+            scatter: 0.0,
+            count: 1,
+            pressure: PressureDynamics::default(),
+        }
+    }
+
+    fn point(x: f32, y: f32, pressure: f32) -> StrokePointRecord {
+        StrokePointRecord { x, y, pressure, time_ms: None }
+    }
+
+    #[test]
+    fn test_stamps_follow_spacing_across_segments() {
+        // 直径 10、間隔 0.5 なら 5px ごと（区間の境目でも間隔は変わらない）
+        let points = [point(0.0, 0.0, 1.0), point(7.0, 0.0, 1.0), point(20.0, 0.0, 1.0)];
+        let stamps = settings().stamps(&points, 0);
+        let xs: Vec<f32> = stamps.iter().map(|s| s.center[0]).collect();
+        assert_eq!(xs, vec![0.0, 5.0, 10.0, 15.0, 20.0]);
+        assert!(stamps.iter().all(|s| s.radius == 5.0 && s.opacity == 0.5));
+
+        // 筆圧で直径と間隔が小さくなる
+        let light = settings().stamps(&[point(0.0, 0.0, 0.5), point(20.0, 0.0, 0.5)], 0);
+        assert_eq!(light.len(), 9);
+        assert_eq!(light[0].radius, 2.5);
+
+        assert_eq!(settings().stamps(&[point(3.0, 4.0, 1.0)], 0).len(), 1);
+
+        // 不透明度はスタンプではなくストロークの色に掛ける
+        let translucent = StampSettings { opacity: 0.4, ..settings() };
+        assert!(translucent.stamps(&points, 0).iter().all(|s| s.opacity == 0.5));
+        assert_eq!(translucent.shape([1.0, 0.0, 0.0, 0.5]).color, [1.0, 0.0, 0.0, 0.2]);
+    }
+
+    #[test]
+    fn test_scatter_is_deterministic_and_bounded() {
+        let spray = StampSettings { scatter: 2.0, count: 16, ..settings() };
+        let points = [point(50.0, 50.0, 1.0), point(100.0, 50.0, 1.0)];
+        let stamps = spray.stamps(&points, 42);
+        assert_eq!(stamps.len(), 11 * 16);
+        assert_eq!(stamps, spray.stamps(&points, 42));
+        assert_ne!(stamps, spray.stamps(&points, 43));
+        // 散らす範囲は直径の2倍以内
+        assert!(stamps.iter().all(|s| (s.center[1] - 50.0).abs() <= 20.0));
+
+        assert_eq!(select_rasterizer(stamps.len()), StampRasterizer::Triangles);
+        assert_eq!(select_rasterizer(COMPUTE_STAMP_THRESHOLD), StampRasterizer::Compute);
+    }
+
+    #[test]
+    fn test_sanitized_hostile_input_gives_finite_stamps() {
+        use crate::drawing_engine::stroke_input::{sanitize_stroke, StrokeInputPoint, STROKE_BOUNDS_MARGIN};
+
+        // ブラシの描画コマンドと同じく、sanitize_stroke を通した点だけをスタンプにする
+        const HOSTILE: [f32; 6] = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, f32::MAX, f32::MIN, 1e9];
+        let mut random = SplitMix64(7);
+        let spray = StampSettings { scatter: 1.0, count: 4, ..settings() };
+        for _ in 0..500 {
+            let mut value = |range: f32| match random.next_u64() % 8 {
+                0 => HOSTILE[(random.next_u64() % HOSTILE.len() as u64) as usize],
+                _ => random.next_f32() * range * 2.0 - range / 2.0,
+            };
+            let points: Vec<StrokeInputPoint> = (0..8)
+                .map(|_| StrokeInputPoint::new(value(128.0), value(96.0), value(1.5)))
+                .collect();
+            let Ok(input) = sanitize_stroke(&points, [0.0, 0.0, 0.0, 1.0], spray.size, (128, 96), None) else {
+                continue;
+            };
+            let records: Vec<StrokePointRecord> = input.points.iter()
+                .map(|p| StrokePointRecord { x: p.x, y: p.y, pressure: p.pressure, time_ms: p.time_ms })
+                .collect();
+            let limit = STROKE_BOUNDS_MARGIN + 128.0 + spray.size * spray.scatter;
+            for stamp in spray.stamps(&records, 1) {
+                assert!(stamp.center.iter().all(|c| c.is_finite() && c.abs() <= limit), "{:?}", stamp);
+                assert!(stamp.radius.is_finite() && stamp.opacity.is_finite(), "{:?}", stamp);
+            }
+        }
+    }
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::collections::HashSet;
+use log::{info, debug};
+use crate::history::{Operation, OperationLog};
+
+// LAN同期サーバー（WebSocket）
+#[cfg(feature = "collab-server")]
+pub mod server;
+
+/// ランポート時計
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LamportClock {
+    time: u64,
+}
+
+impl LamportClock {
+    /// ローカルイベント用に時刻を進める
+    pub fn tick(&mut self) -> u64 {
+        self.time += 1;
+        self.time
+    }
+
+    /// リモートの時刻を観測して時計を同期
+    pub fn observe(&mut self, remote: u64) {
+        self.time = self.time.max(remote);
+    }
+
+    pub fn time(&self) -> u64 {
+        self.time
+    }
+}
+
+/// 操作の一意ID（ランポート時刻 → サイトIDの順で全順序を定義）
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct OpId {
+    pub lamport: u64,
+    pub site_id: String,
+}
+
+impl Ord for OpId {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.lamport.cmp(&other.lamport)
+            .then_with(|| self.site_id.cmp(&other.site_id))
+    }
+}
+
+impl PartialOrd for OpId {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// 同期用の操作エントリ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SyncOperation {
+    pub id: OpId,
+    pub operation: Operation,
+}
+
+/// リモート操作マージの結果
+#[derive(Debug, Clone, Default)]
+pub struct MergeOutcome {
+    /// 新たに取り込んだ操作（全順序順）
+    pub inserted: Vec<SyncOperation>,
+    /// 既に取り込み済みで無視した操作数
+    pub duplicates: usize,
+    /// 既存操作の間に挿入されたため先頭からの再生が必要か
+    pub requires_rebuild: bool,
+}
+
+/// 共同編集セッション（操作ベースのCRDT）
+///
+/// 全サイトの操作を OpId の全順序で並べたものが文書状態になるため、
+/// 同じ操作集合を受け取ったサイトは受信順に関係なく同じ結果に収束する。
+#[derive(Debug, Clone)]
+pub struct CollaborationSession {
+    site_id: String,
+    clock: LamportClock,
+    operations: Vec<SyncOperation>,
+    seen: HashSet<OpId>,
+}
+
+impl CollaborationSession {
+    pub fn new(site_id: String) -> Self {
+        info!("[Collaboration] セッション作成: site={}", site_id);
+        Self {
+            site_id,
+            clock: LamportClock::default(),
+            operations: Vec::new(),
+            seen: HashSet::new(),
+        }
+    }
+
+    /// 衝突しにくいサイトIDを生成
+    pub fn generate_site_id() -> String {
+        let nanos = chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default();
+        format!("site_{}_{}", nanos, std::process::id())
+    }
+
+    pub fn site_id(&self) -> &str {
+        &self.site_id
+    }
+
+    pub fn lamport_time(&self) -> u64 {
+        self.clock.time()
+    }
+
+    pub fn len(&self) -> usize {
+        self.operations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.operations.is_empty()
+    }
+
+    /// 全順序で並んだ操作
+    pub fn operations(&self) -> &[SyncOperation] {
+        &self.operations
+    }
+
+    /// ローカル操作を記録
+    pub fn record_local(&mut self, operation: Operation) -> SyncOperation {
+        let id = OpId {
+            lamport: self.clock.tick(),
+            site_id: self.site_id.clone(),
+        };
+        let sync_op = SyncOperation { id, operation };
+        // ローカル時刻は単調増加なので常に末尾に追加される
+        self.insert(sync_op.clone());
+        sync_op
+    }
+
+    /// リモート操作を取り込む
+    pub fn merge_remote(&mut self, remote_ops: Vec<SyncOperation>) -> MergeOutcome {
+        let mut outcome = MergeOutcome::default();
+
+        for op in remote_ops {
+            if self.seen.contains(&op.id) {
+                outcome.duplicates += 1;
+                continue;
+            }
+            self.clock.observe(op.id.lamport);
+
+            let is_tail = self.operations.last().is_none_or(|last| last.id < op.id);
+            if !is_tail {
+                outcome.requires_rebuild = true;
+            }
+            self.insert(op.clone());
+            outcome.inserted.push(op);
+        }
+
+        outcome.inserted.sort_by(|a, b| a.id.cmp(&b.id));
+        debug!("[Collaboration] リモート操作マージ: 追加 {} / 重複 {} / 再構築 {}",
+               outcome.inserted.len(), outcome.duplicates, outcome.requires_rebuild);
+        outcome
+    }
+
+    /// 取り込んだリモート操作を取り除く（検証・適用できなかった操作を取り込み済みとして残さない）
+    ///
+    /// 取り除いた操作は取り込む前と同じく未受信の扱いになり、再送されれば改めて取り込まれる。
+    pub fn discard(&mut self, ops: &[SyncOperation]) {
+        for op in ops {
+            if !self.seen.remove(&op.id) {
+                continue;
+            }
+            if let Ok(index) = self.operations.binary_search_by(|existing| existing.id.cmp(&op.id)) {
+                self.operations.remove(index);
+            }
+        }
+        debug!("[Collaboration] リモート操作を取り消し: {} 操作", ops.len());
+    }
+
+    /// 指定したランポート時刻より後のローカル操作
+    pub fn local_ops_since(&self, since: u64) -> Vec<SyncOperation> {
+        self.operations.iter()
+            .filter(|op| op.id.site_id == self.site_id && op.id.lamport > since)
+            .cloned()
+            .collect()
+    }
+
+    /// 全操作を再生するための操作ログを作成
+    pub fn to_operation_log(&self) -> OperationLog {
+        OperationLog::from_operations(self.operations.iter().map(|op| op.operation.clone()))
+    }
+
+    fn insert(&mut self, op: SyncOperation) {
+        let index = self.operations.partition_point(|existing| existing.id < op.id);
+        self.seen.insert(op.id.clone());
+        self.operations.insert(index, op);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear_op(layer_id: &str) -> Operation {
+        Operation::ClearLayer { layer_id: layer_id.to_string() }
+    }
+
+    #[test]
+    fn test_lamport_clock() {
+        let mut clock = LamportClock::default();
+        assert_eq!(clock.tick(), 1);
+        clock.observe(10);
+        assert_eq!(clock.tick(), 11);
+        clock.observe(3);
+        assert_eq!(clock.time(), 11);
+    }
+
+    #[test]
+    fn test_op_id_total_order() {
+        let a = OpId { lamport: 1, site_id: "b".to_string() };
+        let b = OpId { lamport: 2, site_id: "a".to_string() };
+        let c = OpId { lamport: 2, site_id: "b".to_string() };
+        assert!(a < b);
+        assert!(b < c);
+    }
+
+    #[test]
+    fn test_sessions_converge() {
+        let mut site_a = CollaborationSession::new("a".to_string());
+        let mut site_b = CollaborationSession::new("b".to_string());
+
+        let a1 = site_a.record_local(clear_op("a1"));
+        let a2 = site_a.record_local(clear_op("a2"));
+        let b1 = site_b.record_local(clear_op("b1"));
+
+        // 受信順が異なっても同じ順序に収束する
+        site_a.merge_remote(vec![b1.clone()]);
+        site_b.merge_remote(vec![a2, a1]);
+
+        assert_eq!(site_a.operations(), site_b.operations());
+        assert_eq!(site_a.len(), 3);
+    }
+
+    #[test]
+    fn test_merge_detects_duplicates_and_rebuild() {
+        let mut site_a = CollaborationSession::new("a".to_string());
+        let mut site_b = CollaborationSession::new("b".to_string());
+
+        site_a.record_local(clear_op("a1"));
+        site_a.record_local(clear_op("a2"));
+        let b1 = site_b.record_local(clear_op("b1"));
+
+        // b1 (lamport 1) は a2 (lamport 2) より前に入るため再生し直しが必要
+        let outcome = site_a.merge_remote(vec![b1.clone()]);
+        assert_eq!(outcome.inserted.len(), 1);
+        assert!(outcome.requires_rebuild);
+
+        let outcome = site_a.merge_remote(vec![b1]);
+        assert_eq!(outcome.duplicates, 1);
+        assert!(outcome.inserted.is_empty());
+    }
+
+    #[test]
+    fn test_merge_tail_does_not_require_rebuild() {
+        let mut site_a = CollaborationSession::new("a".to_string());
+        let mut site_b = CollaborationSession::new("b".to_string());
+
+        let a1 = site_a.record_local(clear_op("a1"));
+        site_b.merge_remote(vec![a1]);
+        let b2 = site_b.record_local(clear_op("b2"));
+
+        let outcome = site_a.merge_remote(vec![b2]);
+        assert!(!outcome.requires_rebuild);
+        assert_eq!(site_a.lamport_time(), 2);
+    }
+
+    #[test]
+    fn test_discarded_ops_can_be_merged_again() {
+        let mut site_a = CollaborationSession::new("a".to_string());
+        let mut site_b = CollaborationSession::new("b".to_string());
+
+        let a1 = site_a.record_local(clear_op("a1"));
+        let b1 = site_b.record_local(clear_op("b1"));
+        let b2 = site_b.record_local(clear_op("b2"));
+
+        // 適用できずに取り除いた操作は重複扱いにならず、再送で取り込み直せる
+        let outcome = site_a.merge_remote(vec![b1.clone(), b2.clone()]);
+        site_a.discard(&outcome.inserted[1..]);
+        assert_eq!(site_a.operations(), &[a1.clone(), b1.clone()][..]);
+
+        let outcome = site_a.merge_remote(vec![b1.clone(), b2.clone()]);
+        assert_eq!((outcome.inserted.len(), outcome.duplicates), (1, 1));
+        assert_eq!(site_a.operations(), &[a1, b1, b2][..]);
+    }
+
+    #[test]
+    fn test_local_ops_since() {
+        let mut site_a = CollaborationSession::new("a".to_string());
+        let mut site_b = CollaborationSession::new("b".to_string());
+
+        site_a.record_local(clear_op("a1"));
+        let b1 = site_b.record_local(clear_op("b1"));
+        site_a.merge_remote(vec![b1]);
+        site_a.record_local(clear_op("a2"));
+
+        let since_zero = site_a.local_ops_since(0);
+        assert_eq!(since_zero.len(), 2);
+        assert!(since_zero.iter().all(|op| op.id.site_id == "a"));
+
+        let since_one = site_a.local_ops_since(1);
+        assert_eq!(since_one.len(), 1);
+        assert_eq!(since_one[0].operation, clear_op("a2"));
+    }
+}
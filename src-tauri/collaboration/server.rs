@@ -0,0 +1,484 @@
+use super::SyncOperation;
+use futures::{SinkExt, StreamExt};
+use log::{info, debug, warn};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::{broadcast, mpsc, watch, Mutex};
+use tokio_tungstenite::tungstenite::protocol::WebSocketConfig;
+use tokio_tungstenite::tungstenite::Message;
+
+/// 1メッセージの大きさの上限（貼り付け画像を含む操作も送れる大きさ）
+pub const MAX_MESSAGE_SIZE: usize = 64 << 20;
+
+/// 1フレームの大きさの上限
+pub const MAX_FRAME_SIZE: usize = 16 << 20;
+
+/// 1メッセージに含める操作の数の上限（超える分は分けて送る）
+pub const MAX_OPS_PER_MESSAGE: usize = 256;
+
+/// 接続してから `Hello` を受け取るまでの待ち時間
+const HELLO_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 同期サーバー・クライアントのエラー型
+#[derive(Debug)]
+pub enum SyncError {
+    BindFailed(String),
+    ConnectFailed(String),
+    ProtocolError(String),
+}
+
+impl fmt::Display for SyncError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncError::BindFailed(msg) => {
+                write!(f, "同期サーバーの待ち受けに失敗しました: {}", msg)
+            }
+            SyncError::ConnectFailed(msg) => {
+                write!(f, "同期サーバーへの接続に失敗しました: {}", msg)
+            }
+            SyncError::ProtocolError(msg) => {
+                write!(f, "同期プロトコルエラー: {}", msg)
+            }
+        }
+    }
+}
+
+impl Error for SyncError {}
+
+/// 接続中のピア情報
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PeerInfo {
+    pub site_id: String,
+    pub name: String,
+}
+
+/// ピア間でやり取りするメッセージ（JSONテキストフレーム）
+///
+/// クライアントは接続直後に、ホストが発行したセッショントークンを付けた `Hello` を送る。
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum PeerMessage {
+    Hello { peer: PeerInfo, token: String },
+    Ops { ops: Vec<SyncOperation> },
+    Presence { peers: Vec<PeerInfo> },
+}
+
+impl PeerMessage {
+    fn to_message(&self) -> Result<Message, SyncError> {
+        serde_json::to_string(self)
+            .map(Message::text)
+            .map_err(|e| SyncError::ProtocolError(e.to_string()))
+    }
+
+    fn from_message(message: &Message) -> Result<Option<Self>, SyncError> {
+        match message {
+            Message::Text(text) => serde_json::from_str(text.as_str())
+                .map(Some)
+                .map_err(|e| SyncError::ProtocolError(e.to_string())),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// アプリ側に通知される同期イベント
+#[derive(Debug, Clone)]
+pub enum SyncEvent {
+    RemoteOps(Vec<SyncOperation>),
+    PeerJoined(PeerInfo),
+    Presence(Vec<PeerInfo>),
+    Disconnected(String),
+}
+
+/// ホスト内部で配信するメッセージ（送信元サイトへは送り返さない）
+#[derive(Debug, Clone)]
+struct Envelope {
+    origin: Option<String>,
+    message: PeerMessage,
+}
+
+/// 送受信する WebSocket の設定（メッセージ・フレームの大きさを制限する）
+fn websocket_config() -> WebSocketConfig {
+    let mut config = WebSocketConfig::default();
+    config.max_message_size = Some(MAX_MESSAGE_SIZE);
+    config.max_frame_size = Some(MAX_FRAME_SIZE);
+    config
+}
+
+/// 推測できないセッショントークンを生成（128bit を16進数で表す）
+pub fn generate_token() -> Result<String, SyncError> {
+    let mut bytes = [0u8; 16];
+    getrandom::getrandom(&mut bytes)
+        .map_err(|e| SyncError::BindFailed(format!("トークンを生成できません: {}", e)))?;
+    Ok(bytes.iter().map(|byte| format!("{:02x}", byte)).collect())
+}
+
+/// トークンの比較（一致するまでの時間から推測されないよう、長さが同じなら全体を比べる）
+fn token_matches(received: &str, expected: &str) -> bool {
+    received.len() == expected.len()
+        && received.bytes().zip(expected.bytes()).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// クライアントから届いた操作を中継してよいか（数の上限と、送信元のサイトの操作であること）
+fn check_incoming_ops(ops: &[SyncOperation], site_id: &str) -> Result<(), String> {
+    if ops.len() > MAX_OPS_PER_MESSAGE {
+        return Err(format!("操作が多すぎます: {}（上限 {}）", ops.len(), MAX_OPS_PER_MESSAGE));
+    }
+    if let Some(op) = ops.iter().find(|op| op.id.site_id != site_id) {
+        return Err(format!("他のサイトの操作が含まれています: {}", op.id.site_id));
+    }
+    Ok(())
+}
+
+/// 起動中の同期接続（ホストまたはクライアント）へのハンドル
+pub struct SyncHandle {
+    outgoing: mpsc::UnboundedSender<Vec<SyncOperation>>,
+    shutdown: watch::Sender<bool>,
+    local_addr: Option<SocketAddr>,
+}
+
+impl SyncHandle {
+    /// ローカル操作をピアへ送信（`MAX_OPS_PER_MESSAGE` ごとに分けて送る）
+    pub fn send_ops(&self, ops: Vec<SyncOperation>) -> Result<(), SyncError> {
+        for chunk in ops.chunks(MAX_OPS_PER_MESSAGE) {
+            self.outgoing.send(chunk.to_vec())
+                .map_err(|_| SyncError::ProtocolError("同期接続は既に終了しています".to_string()))?;
+        }
+        Ok(())
+    }
+
+    /// ホストとして待ち受けているアドレス
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    /// 全タスクを停止
+    pub fn shutdown(&self) {
+        let _ = self.shutdown.send(true);
+    }
+}
+
+impl Drop for SyncHandle {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// 同期サーバーを起動（ホスト自身もピアとしてプレゼンスに含まれる）
+///
+/// `token` と同じトークンの `Hello` を送ってきた接続だけを参加させ、それまでは操作を送受信しない。
+pub async fn host(
+    addr: SocketAddr,
+    local_peer: PeerInfo,
+    token: String,
+) -> Result<(SyncHandle, mpsc::UnboundedReceiver<SyncEvent>), SyncError> {
+    let listener = TcpListener::bind(addr).await
+        .map_err(|e| SyncError::BindFailed(e.to_string()))?;
+    let local_addr = listener.local_addr()
+        .map_err(|e| SyncError::BindFailed(e.to_string()))?;
+    info!("[SyncServer] 待ち受け開始: {}", local_addr);
+
+    let (outgoing_tx, mut outgoing_rx) = mpsc::unbounded_channel::<Vec<SyncOperation>>();
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, shutdown_rx) = watch::channel(false);
+    let (broadcast_tx, _) = broadcast::channel::<Envelope>(1024);
+    let peers = Arc::new(Mutex::new(vec![local_peer]));
+    let token = Arc::new(token);
+
+    // ホストのローカル操作を全クライアントへ配信
+    {
+        let broadcast_tx = broadcast_tx.clone();
+        let mut shutdown_rx = shutdown_rx.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    ops = outgoing_rx.recv() => match ops {
+                        Some(ops) => {
+                            let _ = broadcast_tx.send(Envelope { origin: None, message: PeerMessage::Ops { ops } });
+                        }
+                        None => break,
+                    },
+                    _ = shutdown_rx.changed() => break,
+                }
+            }
+        });
+    }
+
+    // 接続受け付けループ
+    {
+        let mut shutdown_rx = shutdown_rx.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    accepted = listener.accept() => match accepted {
+                        Ok((stream, remote)) => {
+                            debug!("[SyncServer] 接続受け付け: {}", remote);
+                            tokio::spawn(serve_connection(
+                                stream,
+                                token.clone(),
+                                broadcast_tx.clone(),
+                                event_tx.clone(),
+                                peers.clone(),
+                                shutdown_rx.clone(),
+                            ));
+                        }
+                        Err(e) => warn!("[SyncServer] 接続受け付けに失敗: {}", e),
+                    },
+                    _ = shutdown_rx.changed() => break,
+                }
+            }
+            info!("[SyncServer] 待ち受け終了");
+        });
+    }
+
+    Ok((
+        SyncHandle { outgoing: outgoing_tx, shutdown: shutdown_tx, local_addr: Some(local_addr) },
+        event_rx,
+    ))
+}
+
+/// ホスト側の1接続を処理
+async fn serve_connection(
+    stream: TcpStream,
+    token: Arc<String>,
+    broadcast_tx: broadcast::Sender<Envelope>,
+    event_tx: mpsc::UnboundedSender<SyncEvent>,
+    peers: Arc<Mutex<Vec<PeerInfo>>>,
+    mut shutdown_rx: watch::Receiver<bool>,
+) {
+    let ws = match tokio_tungstenite::accept_async_with_config(stream, Some(websocket_config())).await {
+        Ok(ws) => ws,
+        Err(e) => {
+            warn!("[SyncServer] WebSocket ハンドシェイク失敗: {}", e);
+            return;
+        }
+    };
+    let (mut sink, mut source) = ws.split();
+
+    // 最初のメッセージでトークンを確認するまでは参加させず、配信も購読しない
+    let hello = match tokio::time::timeout(HELLO_TIMEOUT, source.next()).await {
+        Ok(Some(Ok(message))) => PeerMessage::from_message(&message).ok().flatten(),
+        _ => None,
+    };
+    let peer = match hello {
+        Some(PeerMessage::Hello { peer, token: received }) if token_matches(&received, &token) => peer,
+        _ => {
+            warn!("[SyncServer] 認証されていない接続を切断");
+            let _ = sink.send(Message::Close(None)).await;
+            return;
+        }
+    };
+
+    info!("[SyncServer] ピア参加: {} ({})", peer.name, peer.site_id);
+    let site_id = peer.site_id.clone();
+    let mut broadcast_rx = broadcast_tx.subscribe();
+    let list = {
+        let mut peers_guard = peers.lock().await;
+        peers_guard.retain(|p| p.site_id != peer.site_id);
+        peers_guard.push(peer.clone());
+        peers_guard.clone()
+    };
+    let _ = event_tx.send(SyncEvent::PeerJoined(peer));
+    let _ = event_tx.send(SyncEvent::Presence(list.clone()));
+    let _ = broadcast_tx.send(Envelope { origin: None, message: PeerMessage::Presence { peers: list } });
+
+    loop {
+        tokio::select! {
+            incoming = source.next() => {
+                let message = match incoming {
+                    Some(Ok(message)) => message,
+                    _ => break,
+                };
+                if message.is_close() {
+                    break;
+                }
+                match PeerMessage::from_message(&message) {
+                    Ok(Some(PeerMessage::Ops { ops })) => {
+                        // 内容の検証は各サイトが適用する前に行う。ここでは数と送信元だけを確かめる
+                        if let Err(e) = check_incoming_ops(&ops, &site_id) {
+                            warn!("[SyncServer] 操作を破棄: {} ({})", e, site_id);
+                            continue;
+                        }
+                        let _ = event_tx.send(SyncEvent::RemoteOps(ops.clone()));
+                        let _ = broadcast_tx.send(Envelope { origin: Some(site_id.clone()), message: PeerMessage::Ops { ops } });
+                    }
+                    Ok(Some(PeerMessage::Hello { .. })) | Ok(Some(PeerMessage::Presence { .. })) | Ok(None) => {}
+                    Err(e) => warn!("[SyncServer] 不正なメッセージを無視: {}", e),
+                }
+            }
+            envelope = broadcast_rx.recv() => {
+                let envelope = match envelope {
+                    Ok(envelope) => envelope,
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("[SyncServer] 配信遅延で {} メッセージを破棄", skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                };
+                if envelope.origin.as_deref() == Some(site_id.as_str()) {
+                    continue;
+                }
+                let sent = match envelope.message.to_message() {
+                    Ok(message) => sink.send(message).await.is_ok(),
+                    Err(e) => {
+                        warn!("[SyncServer] メッセージ変換に失敗: {}", e);
+                        true
+                    }
+                };
+                if !sent {
+                    break;
+                }
+            }
+            _ = shutdown_rx.changed() => {
+                let _ = sink.send(Message::Close(None)).await;
+                break;
+            }
+        }
+    }
+
+    info!("[SyncServer] ピア離脱: {}", site_id);
+    let list = {
+        let mut peers_guard = peers.lock().await;
+        peers_guard.retain(|p| p.site_id != site_id);
+        peers_guard.clone()
+    };
+    let _ = event_tx.send(SyncEvent::Presence(list.clone()));
+    let _ = broadcast_tx.send(Envelope { origin: None, message: PeerMessage::Presence { peers: list } });
+}
+
+/// 他マシンの同期サーバーへ参加（`token` はホストが発行したセッショントークン）
+pub async fn join(
+    url: &str,
+    local_peer: PeerInfo,
+    token: String,
+) -> Result<(SyncHandle, mpsc::UnboundedReceiver<SyncEvent>), SyncError> {
+    let (ws, _) = tokio_tungstenite::connect_async_with_config(url, Some(websocket_config()), false).await
+        .map_err(|e| SyncError::ConnectFailed(e.to_string()))?;
+    info!("[SyncClient] 接続完了: {}", url);
+
+    let (mut sink, mut source) = ws.split();
+    sink.send(PeerMessage::Hello { peer: local_peer, token }.to_message()?).await
+        .map_err(|e| SyncError::ConnectFailed(e.to_string()))?;
+
+    let (outgoing_tx, mut outgoing_rx) = mpsc::unbounded_channel::<Vec<SyncOperation>>();
+    let (event_tx, event_rx) = mpsc::unbounded_channel();
+    let (shutdown_tx, mut shutdown_rx) = watch::channel(false);
+
+    tokio::spawn(async move {
+        let reason = loop {
+            tokio::select! {
+                incoming = source.next() => {
+                    let message = match incoming {
+                        Some(Ok(message)) => message,
+                        Some(Err(e)) => break e.to_string(),
+                        None => break "接続が閉じられました".to_string(),
+                    };
+                    if message.is_close() {
+                        break "ホストが接続を終了しました".to_string();
+                    }
+                    match PeerMessage::from_message(&message) {
+                        Ok(Some(PeerMessage::Ops { ops })) => {
+                            let _ = event_tx.send(SyncEvent::RemoteOps(ops));
+                        }
+                        Ok(Some(PeerMessage::Presence { peers })) => {
+                            let _ = event_tx.send(SyncEvent::Presence(peers));
+                        }
+                        Ok(Some(PeerMessage::Hello { .. })) | Ok(None) => {}
+                        Err(e) => warn!("[SyncClient] 不正なメッセージを無視: {}", e),
+                    }
+                }
+                ops = outgoing_rx.recv() => {
+                    let Some(ops) = ops else {
+                        break "送信チャネルが閉じられました".to_string();
+                    };
+                    let message = match (PeerMessage::Ops { ops }).to_message() {
+                        Ok(message) => message,
+                        Err(e) => {
+                            warn!("[SyncClient] メッセージ変換に失敗: {}", e);
+                            continue;
+                        }
+                    };
+                    if let Err(e) = sink.send(message).await {
+                        break e.to_string();
+                    }
+                }
+                _ = shutdown_rx.changed() => {
+                    let _ = sink.send(Message::Close(None)).await;
+                    break "セッションから退出しました".to_string();
+                }
+            }
+        };
+        info!("[SyncClient] 切断: {}", reason);
+        let _ = event_tx.send(SyncEvent::Disconnected(reason));
+    });
+
+    Ok((
+        SyncHandle { outgoing: outgoing_tx, shutdown: shutdown_tx, local_addr: None },
+        event_rx,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::collaboration::CollaborationSession;
+    use crate::history::Operation;
+
+    fn peer(site_id: &str) -> PeerInfo {
+        PeerInfo { site_id: site_id.to_string(), name: site_id.to_string() }
+    }
+
+    #[tokio::test]
+    async fn test_host_relays_ops_and_presence() {
+        let (host_handle, mut host_events) = host("127.0.0.1:0".parse().unwrap(), peer("host"), "secret".to_string()).await.unwrap();
+        let url = format!("ws://{}", host_handle.local_addr().unwrap());
+
+        let (client_handle, mut client_events) = join(&url, peer("client"), "secret".to_string()).await.unwrap();
+
+        // ホスト側に参加とプレゼンスが通知される
+        assert!(matches!(host_events.recv().await, Some(SyncEvent::PeerJoined(p)) if p.site_id == "client"));
+        assert!(matches!(host_events.recv().await, Some(SyncEvent::Presence(peers)) if peers.len() == 2));
+        assert!(matches!(client_events.recv().await, Some(SyncEvent::Presence(peers)) if peers.len() == 2));
+
+        // クライアントの操作がホストに届く
+        let mut session = CollaborationSession::new("client".to_string());
+        let op = session.record_local(Operation::ClearLayer { layer_id: "layer1".to_string() });
+        client_handle.send_ops(vec![op.clone()]).unwrap();
+        assert!(matches!(host_events.recv().await, Some(SyncEvent::RemoteOps(ops)) if ops == vec![op.clone()]));
+
+        // ホストの操作がクライアントに届く
+        host_handle.send_ops(vec![op.clone()]).unwrap();
+        assert!(matches!(client_events.recv().await, Some(SyncEvent::RemoteOps(ops)) if ops == vec![op]));
+    }
+
+    #[tokio::test]
+    async fn test_host_rejects_wrong_token_and_foreign_ops() {
+        let token = generate_token().unwrap();
+        assert_eq!(token.len(), 32);
+        assert_ne!(token, generate_token().unwrap());
+
+        let (host_handle, mut host_events) = host("127.0.0.1:0".parse().unwrap(), peer("host"), token.clone()).await.unwrap();
+        let url = format!("ws://{}", host_handle.local_addr().unwrap());
+
+        // トークンが違う接続は参加できずに切断される
+        let (_intruder, mut intruder_events) = join(&url, peer("intruder"), "guess".to_string()).await.unwrap();
+        assert!(matches!(intruder_events.recv().await, Some(SyncEvent::Disconnected(_))));
+
+        let (client_handle, _client_events) = join(&url, peer("client"), token).await.unwrap();
+        assert!(matches!(host_events.recv().await, Some(SyncEvent::PeerJoined(p)) if p.site_id == "client"));
+        assert!(matches!(host_events.recv().await, Some(SyncEvent::Presence(_))));
+
+        // 他のサイトを名乗る操作は中継せず、自分のサイトの操作だけが届く
+        let mut other = CollaborationSession::new("host".to_string());
+        let spoofed = other.record_local(Operation::ClearLayer { layer_id: "layer1".to_string() });
+        client_handle.send_ops(vec![spoofed]).unwrap();
+        let mut session = CollaborationSession::new("client".to_string());
+        let op = session.record_local(Operation::ClearLayer { layer_id: "layer1".to_string() });
+        client_handle.send_ops(vec![op.clone()]).unwrap();
+        assert!(matches!(host_events.recv().await, Some(SyncEvent::RemoteOps(ops)) if ops == vec![op]));
+    }
+}
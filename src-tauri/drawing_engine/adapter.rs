@@ -0,0 +1,89 @@
+use serde::{Deserialize, Serialize};
+use wgpu::{Adapter, AdapterInfo, Backends, Instance, PowerPreference};
+
+/// GPUの電力設定
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GpuPowerPreference {
+    /// 外部GPUなど高性能なアダプターを優先
+    #[default]
+    HighPerformance,
+    /// ノートPCの内蔵GPUなど省電力のアダプターを優先
+    LowPower,
+}
+
+impl From<GpuPowerPreference> for PowerPreference {
+    fn from(preference: GpuPowerPreference) -> Self {
+        match preference {
+            GpuPowerPreference::HighPerformance => PowerPreference::HighPerformance,
+            GpuPowerPreference::LowPower => PowerPreference::LowPower,
+        }
+    }
+}
+
+/// アダプターの識別情報（列挙順は起動ごとに変わりうるため、名前とIDで照合する）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpuAdapterId {
+    pub name: String,
+    pub vendor: u32,
+    pub device: u32,
+    pub backend: String,
+}
+
+impl GpuAdapterId {
+    pub fn from_info(info: &AdapterInfo) -> Self {
+        Self {
+            name: info.name.clone(),
+            vendor: info.vendor,
+            device: info.device,
+            backend: format!("{:?}", info.backend),
+        }
+    }
+}
+
+/// 使用するGPUの設定
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GpuPreference {
+    /// 使用するアダプター（None または見つからない場合は電力設定で自動選択）
+    pub adapter: Option<GpuAdapterId>,
+    pub power_preference: GpuPowerPreference,
+}
+
+/// フロントエンドに公開するアダプター情報
+#[derive(Debug, Clone, Serialize)]
+pub struct GpuAdapterInfo {
+    /// `list_gpu_adapters` の結果内での位置（選択時に使う）
+    pub index: usize,
+    pub id: GpuAdapterId,
+    pub device_type: String,
+    pub driver: String,
+    /// 現在の描画エンジンが使用しているアダプターか
+    pub in_use: bool,
+}
+
+/// 利用可能なアダプターを列挙（ソフトウェア実装を含む）
+pub fn enumerate_adapters(instance: &Instance) -> Vec<Adapter> {
+    instance.enumerate_adapters(Backends::all())
+}
+
+/// アダプター一覧をフロントエンド向けの情報に変換
+pub fn describe_adapters(adapters: &[Adapter], in_use: Option<&GpuAdapterId>) -> Vec<GpuAdapterInfo> {
+    adapters.iter().enumerate().map(|(index, adapter)| {
+        let info = adapter.get_info();
+        let id = GpuAdapterId::from_info(&info);
+        GpuAdapterInfo {
+            index,
+            in_use: in_use == Some(&id),
+            id,
+            device_type: format!("{:?}", info.device_type),
+            driver: info.driver,
+        }
+    }).collect()
+}
+
+/// 設定で指定されたアダプターを一覧から取り出す
+pub fn take_preferred_adapter(adapters: Vec<Adapter>, preference: &GpuPreference) -> Option<Adapter> {
+    let wanted = preference.adapter.as_ref()?;
+    adapters.into_iter().find(|adapter| GpuAdapterId::from_info(&adapter.get_info()) == *wanted)
+}
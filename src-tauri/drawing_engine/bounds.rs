@@ -0,0 +1,397 @@
+use wgpu::*;
+use log::{info, debug};
+use crate::shaders;
+use serde::{Deserialize, Serialize};
+use super::pipeline::PipelineError;
+use super::texture::{ManagedTexture, TextureError};
+
+/// コンピュートシェーダーのワークグループの大きさ（1辺）
+const WORKGROUP_SIZE: u32 = 8;
+
+/// 不透明なピクセルを含む最小の矩形
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentBounds {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl ContentBounds {
+    /// 画像の不透明部分の矩形（CPUで計算。完全に透明なら None）
+    pub fn from_image(image: &image::RgbaImage) -> Option<Self> {
+        let mut extent: Option<(u32, u32, u32, u32)> = None;
+        for (x, y, _) in image.enumerate_pixels().filter(|(_, _, p)| p[3] > 0) {
+            extent = Some(match extent {
+                None => (x, y, x, y),
+                Some((min_x, min_y, max_x, max_y)) => (min_x.min(x), min_y.min(y), max_x.max(x), max_y.max(y)),
+            });
+        }
+        extent.map(Self::from_extent)
+    }
+
+    /// 両端を含む座標範囲から作成
+    fn from_extent((min_x, min_y, max_x, max_y): (u32, u32, u32, u32)) -> Self {
+        Self {
+            x: min_x,
+            y: min_y,
+            width: max_x - min_x + 1,
+            height: max_y - min_y + 1,
+        }
+    }
+
+    /// 両方を含む最小の矩形
+    pub fn union(&self, other: &Self) -> Self {
+        let (x, y) = (self.x.min(other.x), self.y.min(other.y));
+        let right = (self.x + self.width).max(other.x + other.width);
+        let bottom = (self.y + self.height).max(other.y + other.height);
+        Self { x, y, width: right - x, height: bottom - y }
+    }
+
+    /// `width`x`height` の画像に収まる部分（重ならなければ None）
+    pub fn clipped(&self, width: u32, height: u32) -> Option<Self> {
+        let right = (self.x + self.width).min(width);
+        let bottom = (self.y + self.height).min(height);
+        (self.x < right && self.y < bottom)
+            .then(|| Self { x: self.x, y: self.y, width: right - self.x, height: bottom - self.y })
+    }
+
+    /// 余白を加えた矩形（キャンバスの外にははみ出さない）
+    pub fn padded(&self, padding: u32, (canvas_width, canvas_height): (u32, u32)) -> Self {
+        let x = self.x.saturating_sub(padding);
+        let y = self.y.saturating_sub(padding);
+        let right = (self.x + self.width).saturating_add(padding).min(canvas_width.max(self.x + self.width));
+        let bottom = (self.y + self.height).saturating_add(padding).min(canvas_height.max(self.y + self.height));
+        Self {
+            x,
+            y,
+            width: right - x,
+            height: bottom - y,
+        }
+    }
+}
+
+/// テクスチャ1枚の不透明部分の集計
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContentCoverage {
+    /// 不透明度が 0 より大きいピクセルの数
+    pub pixel_count: u32,
+    /// 不透明部分の矩形（すべて透明なら None）
+    pub bounds: Option<ContentBounds>,
+}
+
+impl ContentCoverage {
+    /// 画像の不透明部分の集計（CPUで計算）
+    pub fn from_image(image: &image::RgbaImage) -> Self {
+        Self {
+            pixel_count: image.pixels().filter(|p| p[3] > 0).count() as u32,
+            bounds: ContentBounds::from_image(image),
+        }
+    }
+}
+
+/// レイヤーテクスチャの不透明部分の矩形とピクセル数を求めるリダクションパイプライン
+///
+/// ワークグループ内で共有メモリに最小・最大座標とピクセル数を集約してから、
+/// ワークグループごとに1回だけ結果バッファへ atomicMin / atomicMax / atomicAdd で書き込む。
+/// 結果はテクスチャごとに別の位置に書き込む。
+pub struct ContentBoundsPipeline {
+    compute_pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl ContentBoundsPipeline {
+    pub fn new(device: &Device, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        Self::with_shader(device, cache, Self::shader_source())
+    }
+
+    pub fn with_shader(device: &Device, cache: Option<&PipelineCache>, source: &str) -> Result<Self, PipelineError> {
+        info!("[ContentBoundsPipeline] パイプライン作成開始");
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Content Bounds Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(source)?.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Content Bounds Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Content Bounds Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Content Bounds Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache,
+        });
+
+        info!("[ContentBoundsPipeline] パイプライン作成完了");
+        Ok(Self {
+            compute_pipeline,
+            bind_group_layout,
+        })
+    }
+
+    /// 全テクスチャの不透明部分を合わせた矩形を求める（すべて透明なら None）
+    pub async fn compute(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        textures: &[&ManagedTexture],
+    ) -> Result<Option<ContentBounds>, TextureError> {
+        let coverages = self.compute_each(device, queue, textures).await?;
+        let bounds = coverages.iter()
+            .filter_map(|coverage| coverage.bounds)
+            .reduce(|a, b| a.union(&b));
+        debug!("[ContentBoundsPipeline] 範囲計算完了: {:?}", bounds);
+        Ok(bounds)
+    }
+
+    /// テクスチャごとの不透明部分の矩形とピクセル数を求める（1回の送信にまとめる）
+    pub async fn compute_each(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        textures: &[&ManagedTexture],
+    ) -> Result<Vec<ContentCoverage>, TextureError> {
+        debug!("[ContentBoundsPipeline] 範囲計算: {} テクスチャ", textures.len());
+        if textures.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // テクスチャごとに [min_x, min_y, max_x, max_y, pixel_count] を
+        // ストレージバッファのオフセットの境界に揃えて並べる
+        let initial: [u32; 5] = [u32::MAX, u32::MAX, 0, 0, 0];
+        let entry_size = std::mem::size_of_val(&initial) as u64;
+        let stride = entry_size.next_multiple_of(device.limits().min_storage_buffer_offset_alignment as u64);
+        let result_size = stride * (textures.len() as u64 - 1) + entry_size;
+        let result_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Content Bounds Result Buffer"),
+            size: result_size,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        for index in 0..textures.len() as u64 {
+            queue.write_buffer(&result_buffer, index * stride, bytemuck::cast_slice(&initial));
+        }
+
+        let read_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Content Bounds Read Buffer"),
+            size: result_size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Content Bounds Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Content Bounds Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.compute_pipeline);
+
+            for (index, texture) in textures.iter().enumerate() {
+                let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                    label: Some("Content Bounds Bind Group"),
+                    layout: &self.bind_group_layout,
+                    entries: &[
+                        BindGroupEntry {
+                            binding: 0,
+                            resource: BindingResource::TextureView(&texture.view),
+                        },
+                        BindGroupEntry {
+                            binding: 1,
+                            resource: BindingResource::Buffer(BufferBinding {
+                                buffer: &result_buffer,
+                                offset: index as u64 * stride,
+                                size: BufferSize::new(entry_size),
+                            }),
+                        },
+                    ],
+                });
+                compute_pass.set_bind_group(0, &bind_group, &[]);
+                compute_pass.dispatch_workgroups(
+                    texture.spec.width.div_ceil(WORKGROUP_SIZE),
+                    texture.spec.height.div_ceil(WORKGROUP_SIZE),
+                    1,
+                );
+            }
+        }
+        encoder.copy_buffer_to_buffer(&result_buffer, 0, &read_buffer, 0, result_size);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = read_buffer.slice(..);
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        buffer_slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+
+        receiver.await
+            .map_err(|_| TextureError::BufferReadFailed("バッファマップ待機に失敗".to_string()))?
+            .map_err(|e| TextureError::BufferReadFailed(format!("バッファマップに失敗: {:?}", e)))?;
+
+        let data = buffer_slice.get_mapped_range();
+        let coverages = (0..textures.len())
+            .map(|index| {
+                let offset = index * stride as usize;
+                let entry: [u32; 5] = bytemuck::pod_read_unaligned(&data[offset..offset + entry_size as usize]);
+                let [min_x, min_y, max_x, max_y, pixel_count] = entry;
+                let bounds = (min_x <= max_x && min_y <= max_y)
+                    .then(|| ContentBounds::from_extent((min_x, min_y, max_x, max_y)));
+                ContentCoverage { pixel_count, bounds }
+            })
+            .collect();
+        drop(data);
+        read_buffer.unmap();
+        Ok(coverages)
+    }
+
+    /// 不透明部分の範囲を求めるシェーダー（WGSL）
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        @group(0) @binding(0) var source: texture_2d<f32>;
+        // [min_x, min_y, max_x, max_y, pixel_count]
+        @group(0) @binding(1) var<storage, read_write> bounds: array<atomic<u32>, 5>;
+
+        var<workgroup> local_bounds: array<atomic<u32>, 5>;
+
+        @compute @workgroup_size(8, 8)
+        fn cs_main(
+            @builtin(global_invocation_id) id: vec3<u32>,
+            @builtin(local_invocation_index) local_index: u32,
+        ) {
+            if local_index == 0u {
+                atomicStore(&local_bounds[0], 0xffffffffu);
+                atomicStore(&local_bounds[1], 0xffffffffu);
+                atomicStore(&local_bounds[2], 0u);
+                atomicStore(&local_bounds[3], 0u);
+                atomicStore(&local_bounds[4], 0u);
+            }
+            workgroupBarrier();
+
+            let size = textureDimensions(source);
+            if id.x < size.x && id.y < size.y {
+                let alpha = textureLoad(source, vec2<i32>(id.xy), 0).a;
+                if alpha > 0.0 {
+                    atomicMin(&local_bounds[0], id.x);
+                    atomicMin(&local_bounds[1], id.y);
+                    atomicMax(&local_bounds[2], id.x);
+                    atomicMax(&local_bounds[3], id.y);
+                    atomicAdd(&local_bounds[4], 1u);
+                }
+            }
+            workgroupBarrier();
+
+            if local_index == 0u {
+                let min_x = atomicLoad(&local_bounds[0]);
+                let max_x = atomicLoad(&local_bounds[2]);
+                if min_x <= max_x {
+                    atomicMin(&bounds[0], min_x);
+                    atomicMin(&bounds[1], atomicLoad(&local_bounds[1]));
+                    atomicMax(&bounds[2], max_x);
+                    atomicMax(&bounds[3], atomicLoad(&local_bounds[3]));
+                    atomicAdd(&bounds[4], atomicLoad(&local_bounds[4]));
+                }
+            }
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    #[test]
+    fn test_from_image() {
+        let mut image = RgbaImage::new(10, 8);
+        assert_eq!(ContentBounds::from_image(&image), None);
+
+        image.put_pixel(2, 5, Rgba([0, 0, 0, 1]));
+        image.put_pixel(6, 3, Rgba([255, 0, 0, 255]));
+        assert_eq!(
+            ContentBounds::from_image(&image),
+            Some(ContentBounds { x: 2, y: 3, width: 5, height: 3 })
+        );
+    }
+
+    #[test]
+    fn test_padded_is_clamped_to_canvas() {
+        let bounds = ContentBounds { x: 2, y: 3, width: 5, height: 3 };
+        assert_eq!(bounds.padded(1, (10, 8)), ContentBounds { x: 1, y: 2, width: 7, height: 5 });
+        assert_eq!(bounds.padded(4, (10, 8)), ContentBounds { x: 0, y: 0, width: 10, height: 8 });
+
+        assert_eq!(bounds.clipped(5, 8), Some(ContentBounds { x: 2, y: 3, width: 3, height: 3 }));
+        assert_eq!(bounds.clipped(2, 8), None);
+    }
+
+    #[tokio::test]
+    async fn test_gpu_bounds_match_cpu() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = crate::drawing_engine::DrawingEngine::new();
+        engine.initialize().await?;
+
+        let mut first = RgbaImage::new(40, 20);
+        first.put_pixel(9, 17, Rgba([0, 0, 255, 255]));
+        let mut second = RgbaImage::new(40, 20);
+        second.put_pixel(33, 4, Rgba([255, 0, 0, 64]));
+        for (id, image) in [("first", &first), ("second", &second)] {
+            engine.create_layer_texture(id, 40, 20)?;
+            engine.write_layer_image(id, 0, 0, image)?;
+        }
+
+        let ids = ["first".to_string(), "second".to_string()];
+        assert_eq!(engine.content_bounds(&ids[..1]).await?, ContentBounds::from_image(&first));
+        assert_eq!(
+            engine.content_bounds(&ids).await?,
+            Some(ContentBounds { x: 9, y: 4, width: 25, height: 14 })
+        );
+
+        engine.create_layer_texture("empty", 40, 20)?;
+        assert_eq!(engine.content_bounds(&["empty".to_string()]).await?, None);
+
+        let ids = ["first".to_string(), "empty".to_string(), "second".to_string()];
+        assert_eq!(
+            engine.content_coverage(&ids).await?,
+            vec![
+                ContentCoverage::from_image(&first),
+                ContentCoverage { pixel_count: 0, bounds: None },
+                ContentCoverage::from_image(&second),
+            ]
+        );
+        Ok(())
+    }
+}
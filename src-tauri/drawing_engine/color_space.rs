@@ -0,0 +1,84 @@
+use serde::{Deserialize, Serialize};
+use super::stamp::{Stamp, StampShape};
+
+/// ストロークを合成する色空間
+///
+/// レイヤーは sRGB のテクスチャで、既定では sRGB のビューに描くためハードウェアが
+/// 読み書きのたびに変換し、合成は線形の値で行われる。`Srgb` はレイヤーを非 sRGB のビューで
+/// 描画先にし、ブラシの色をシェーダーで手動で sRGB に変換してから、保存されている値のまま合成する
+/// （一般的な 2D ペイントソフトと同じ合成。半透明の縁は線形より暗く見える）。
+/// 不透明に塗った画素は両方で同じ値になり、違いはぼかし・半透明の部分だけに出る。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BlendSpace {
+    /// 線形の値で合成（既定）
+    #[default]
+    Linear,
+    /// sRGB の値のまま合成
+    Srgb,
+}
+
+impl BlendSpace {
+    pub const ALL: [BlendSpace; 2] = [BlendSpace::Linear, BlendSpace::Srgb];
+
+    /// 比較用の書き出しのファイル名などに使う名前
+    pub fn name(self) -> &'static str {
+        match self {
+            BlendSpace::Linear => "linear",
+            BlendSpace::Srgb => "srgb",
+        }
+    }
+}
+
+/// sRGB で合成できるか（レイヤーを非 sRGB のビューで描画先にするため、ビューの形式の変更に対応している必要がある）
+pub fn srgb_blend_supported(adapter: &wgpu::Adapter) -> bool {
+    adapter.get_downlevel_capabilities().flags.contains(wgpu::DownlevelFlags::VIEW_FORMATS)
+}
+
+/// 合成の色空間の比較に使う図柄（背景色と、ぼかしたスタンプの列）
+///
+/// 色相の離れた彩度の高い色を半分ずつ重ね、線形と sRGB で縁の明るさの差が出やすくする。
+pub fn blend_audit_pattern(width: u32, height: u32) -> (wgpu::Color, Vec<(Vec<Stamp>, StampShape)>) {
+    const COLORS: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 1.0],
+        [0.0, 1.0, 0.0, 1.0],
+        [0.0, 0.0, 1.0, 1.0],
+        [1.0, 1.0, 0.0, 1.0],
+    ];
+    let (w, h) = (width as f32, height as f32);
+    let radius = (w.min(h) / 10.0).max(2.0);
+    let strokes = COLORS.iter().enumerate().map(|(row, &color)| {
+        let y = h * (row as f32 + 0.5) / COLORS.len() as f32;
+        // 行ごとに右へずらして、前の行の色と重なるようにする
+        let shift = radius * 0.8 * (row % 2) as f32;
+        let stamps = (0..24)
+            .map(|i| Stamp {
+                center: [radius + shift + (w - 2.0 * radius - shift) * i as f32 / 23.0, y + radius * 0.6 * (row % 2) as f32],
+                radius,
+                opacity: 0.35,
+            })
+            .collect();
+        (stamps, StampShape { color, hardness: 0.0, angle: 0.0, roundness: 1.0 })
+    }).collect();
+    (wgpu::Color::WHITE, strokes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_audit_pattern_stays_inside_canvas() {
+        let (background, strokes) = blend_audit_pattern(256, 128);
+        assert_eq!(background, wgpu::Color::WHITE);
+        assert_eq!(strokes.len(), 4);
+        for (stamps, shape) in &strokes {
+            assert_eq!(shape.hardness, 0.0);
+            for stamp in stamps {
+                assert!(stamp.center[0] - stamp.radius >= -0.01 && stamp.center[0] + stamp.radius <= 256.01);
+                assert!(stamp.center[1] >= 0.0 && stamp.center[1] <= 128.0);
+            }
+        }
+        assert_eq!(serde_json::to_string(&BlendSpace::Srgb).unwrap(), "\"srgb\"");
+    }
+}
@@ -0,0 +1,185 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::formats::blend_over;
+
+/// 並べるときの隙間の上限（ピクセル）
+pub const MAX_COMPARE_GAP: u32 = 256;
+
+/// 2つの合成済みキャンバス（別々のレイヤーの組や、同じプロジェクトの2つのフレーム）の並べ方
+///
+/// 清書で前後のフレームを見比べるための表示。1枚目が基準、2枚目が比較対象。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum CompareLayout {
+    /// 左右に並べる（間に `gap` ピクセルの透明な隙間を空ける）
+    SideBySide { gap: u32 },
+    /// 上下に並べる
+    Stacked { gap: u32 },
+    /// 基準の上に比較対象を `opacity` の不透明度で重ねる（左上を揃える）
+    Overlay { opacity: f32 },
+}
+
+impl Default for CompareLayout {
+    fn default() -> Self {
+        CompareLayout::SideBySide { gap: 8 }
+    }
+}
+
+impl CompareLayout {
+    pub fn validate(&self) -> Result<(), String> {
+        match *self {
+            CompareLayout::SideBySide { gap } | CompareLayout::Stacked { gap } if gap > MAX_COMPARE_GAP => {
+                Err(format!("隙間は {} ピクセル以下で指定してください: {}", MAX_COMPARE_GAP, gap))
+            }
+            CompareLayout::Overlay { opacity } if !(0.0..=1.0).contains(&opacity) => {
+                Err(format!("不透明度は 0〜1 で指定してください: {}", opacity))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// 比較対象の左上の位置（出力画像上のピクセル）
+    pub fn secondary_origin(&self, primary: (u32, u32)) -> (u32, u32) {
+        match *self {
+            CompareLayout::SideBySide { gap } => (primary.0 + gap, 0),
+            CompareLayout::Stacked { gap } => (0, primary.1 + gap),
+            CompareLayout::Overlay { .. } => (0, 0),
+        }
+    }
+}
+
+/// 2つの合成済みキャンバスを1枚の表示用の画像にまとめる（オーバーレイは呼び出し側で重ねる）
+pub fn compose_comparison(primary: &RgbaImage, secondary: &RgbaImage, layout: CompareLayout) -> RgbaImage {
+    let (x, y) = layout.secondary_origin(primary.dimensions());
+    let width = primary.width().max(x + secondary.width());
+    let height = primary.height().max(y + secondary.height());
+    let opacity = match layout {
+        CompareLayout::Overlay { opacity } => opacity.clamp(0.0, 1.0),
+        _ => 1.0,
+    };
+
+    let mut output = RgbaImage::new(width, height);
+    image::imageops::replace(&mut output, primary, 0, 0);
+    for (sx, sy, pixel) in secondary.enumerate_pixels() {
+        let alpha = (pixel[3] as f32 * opacity).round() as u8;
+        let target = output.get_pixel_mut(x + sx, y + sy);
+        *target = blend_over(*target, Rgba([pixel[0], pixel[1], pixel[2], alpha]));
+    }
+    output
+}
+
+/// 2枚の画像の差分の集計
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct DifferenceStats {
+    /// 差が `threshold` を超えた画素数
+    pub changed_pixels: u64,
+    pub total_pixels: u64,
+    /// 変化した画素の割合（0〜100）
+    pub changed_percent: f32,
+    /// 画素ごとの差の最大（0〜255）
+    pub max_difference: u8,
+}
+
+/// 画素の差（乗算済みアルファにした各チャンネルの差の最大。透明な画素の色は無視される）
+pub(super) fn pixel_difference(a: Rgba<u8>, b: Rgba<u8>) -> u8 {
+    let premultiplied = |pixel: Rgba<u8>| {
+        let alpha = pixel[3] as u32;
+        [0, 1, 2].map(|channel| ((pixel[channel] as u32 * alpha + 127) / 255) as u8)
+    };
+    let (pa, pb) = (premultiplied(a), premultiplied(b));
+    pa.iter().zip(&pb)
+        .map(|(a, b)| a.abs_diff(*b))
+        .fold(a[3].abs_diff(b[3]), u8::max)
+}
+
+/// 2枚の画像の差分をヒートマップにする（差が `threshold` 以下の画素は透明）
+///
+/// 差が小さいほど黄色く薄く、大きいほど赤く濃くなる。大きさの違う画像は大きい方に合わせ、
+/// はみ出した部分は透明な画素と比べる。セル同士や、フィルターの前後で意図しない変化を探すための表示。
+pub fn difference_heatmap(a: &RgbaImage, b: &RgbaImage, threshold: u8) -> (RgbaImage, DifferenceStats) {
+    let width = a.width().max(b.width());
+    let height = a.height().max(b.height());
+    let pixel_at = |image: &RgbaImage, x: u32, y: u32| {
+        if x < image.width() && y < image.height() { *image.get_pixel(x, y) } else { Rgba([0; 4]) }
+    };
+
+    let mut changed_pixels = 0u64;
+    let mut max_difference = 0u8;
+    let heatmap = RgbaImage::from_fn(width, height, |x, y| {
+        let difference = pixel_difference(pixel_at(a, x, y), pixel_at(b, x, y));
+        max_difference = max_difference.max(difference);
+        if difference <= threshold {
+            return Rgba([0; 4]);
+        }
+        changed_pixels += 1;
+        let t = difference as f32 / 255.0;
+        Rgba([255, (255.0 * (1.0 - t)).round() as u8, 0, (96.0 + 159.0 * t).round() as u8])
+    });
+
+    let total_pixels = width as u64 * height as u64;
+    let changed_percent = if total_pixels == 0 { 0.0 } else { changed_pixels as f32 * 100.0 / total_pixels as f32 };
+    (heatmap, DifferenceStats { changed_pixels, total_pixels, changed_percent, max_difference })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_side_by_side_and_stacked() {
+        let primary = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]));
+        let secondary = RgbaImage::from_pixel(3, 3, Rgba([0, 0, 255, 255]));
+
+        let view = compose_comparison(&primary, &secondary, CompareLayout::SideBySide { gap: 2 });
+        assert_eq!(view.dimensions(), (9, 3));
+        assert_eq!(view.get_pixel(3, 1), &Rgba([255, 0, 0, 255]));
+        assert_eq!(view.get_pixel(5, 0), &Rgba([0, 0, 0, 0])); // 隙間
+        assert_eq!(view.get_pixel(6, 2), &Rgba([0, 0, 255, 255]));
+        assert_eq!(view.get_pixel(0, 2), &Rgba([0, 0, 0, 0])); // 背の低い方の下
+
+        let view = compose_comparison(&primary, &secondary, CompareLayout::Stacked { gap: 0 });
+        assert_eq!(view.dimensions(), (4, 5));
+        assert_eq!(view.get_pixel(0, 2), &Rgba([0, 0, 255, 255]));
+    }
+
+    #[test]
+    fn test_overlay_opacity() {
+        let primary = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]));
+        let mut secondary = RgbaImage::new(2, 2);
+        secondary.put_pixel(1, 1, Rgba([0, 0, 0, 255]));
+
+        let view = compose_comparison(&primary, &secondary, CompareLayout::Overlay { opacity: 0.5 });
+        assert_eq!(view.dimensions(), (2, 2));
+        assert_eq!(view.get_pixel(0, 0), &Rgba([255, 255, 255, 255]));
+        assert_eq!(view.get_pixel(1, 1), &Rgba([127, 127, 127, 255]));
+
+        let hidden = compose_comparison(&primary, &secondary, CompareLayout::Overlay { opacity: 0.0 });
+        assert_eq!(hidden, primary);
+
+        assert!(CompareLayout::Overlay { opacity: 1.5 }.validate().is_err());
+        assert!(CompareLayout::SideBySide { gap: MAX_COMPARE_GAP + 1 }.validate().is_err());
+        assert!(CompareLayout::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_difference_heatmap() {
+        let before = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 255]));
+        let mut after = before.clone();
+        after.put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        after.put_pixel(1, 0, Rgba([250, 0, 0, 255]));
+
+        let (heatmap, stats) = difference_heatmap(&before, &after, 8);
+        assert_eq!(heatmap.dimensions(), (4, 2));
+        assert_eq!(heatmap.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(heatmap.get_pixel(1, 0), &Rgba([0, 0, 0, 0])); // しきい値以下
+        assert_eq!(stats, DifferenceStats { changed_pixels: 1, total_pixels: 8, changed_percent: 12.5, max_difference: 255 });
+
+        // 透明な画素同士は色が違っても同じ、大きさの違いは透明と比べる
+        let clear = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 0]));
+        let (heatmap, stats) = difference_heatmap(&clear, &RgbaImage::new(3, 2), 0);
+        assert_eq!(heatmap.dimensions(), (3, 2));
+        assert_eq!(stats.changed_pixels, 0);
+        let (_, stats) = difference_heatmap(&before, &RgbaImage::new(2, 2), 0);
+        assert_eq!(stats.changed_pixels, 8);
+    }
+}
@@ -0,0 +1,514 @@
+use wgpu::*;
+use log::{info, debug};
+use crate::shaders;
+use super::bounds::ContentBounds;
+use super::pipeline::{PipelineError, StrokeMesh, Vertex2D};
+
+/// 被覆率バッファの形式（1チャンネル）
+pub const COVERAGE_FORMAT: TextureFormat = TextureFormat::R8Unorm;
+
+/// 合成するストロークの色（`color[3]` はストローク全体に1回だけ掛ける不透明度）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+struct CompositeUniforms {
+    color: [f32; 4],
+}
+
+/// 溜めた被覆率の合成方法
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CoverageBlend {
+    /// 色を掛けて重ねる（`color[3]` はストローク全体の不透明度）
+    Paint([f32; 4]),
+    /// 被覆率に強さを掛けた分だけアルファを削る（消しゴム。色はそのまま残す）
+    Erase(f32),
+}
+
+/// ストロークの被覆率を溜めるテクスチャ
+///
+/// マルチサンプリングする場合は解決前のテクスチャも持ち、点の追加ごとの描画をまたいで溜め続ける。
+pub struct CoverageBuffer {
+    texture: Texture,
+    view: TextureView,
+    multisampled: Option<(Texture, TextureView)>,
+    /// 次の描画の前に 0 で消すか
+    needs_clear: bool,
+    /// 描き込んだ範囲（ピクセル）
+    bounds: Option<ContentBounds>,
+}
+
+impl CoverageBuffer {
+    pub fn new(device: &Device, size: (u32, u32), sample_count: u32) -> Self {
+        debug!("[CoverageBuffer] 作成: {}x{} ({}x)", size.0, size.1, sample_count);
+        let create = |label, sample_count, usage| {
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some(label),
+                size: Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count,
+                dimension: TextureDimension::D2,
+                format: COVERAGE_FORMAT,
+                usage,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            (texture, view)
+        };
+        let (texture, view) = create("Stroke Coverage Texture", 1, TextureUsages::RENDER_ATTACHMENT | TextureUsages::TEXTURE_BINDING);
+        let multisampled = (sample_count > 1)
+            .then(|| create("Stroke Coverage MSAA Texture", sample_count, TextureUsages::RENDER_ATTACHMENT));
+        Self { texture, view, multisampled, needs_clear: true, bounds: None }
+    }
+
+    pub fn size(&self) -> (u32, u32) {
+        (self.texture.width(), self.texture.height())
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.multisampled.as_ref().map_or(1, |(texture, _)| texture.sample_count())
+    }
+
+    /// 描き込んだ範囲（まだ何も描いていなければ None）
+    pub fn bounds(&self) -> Option<ContentBounds> {
+        self.bounds
+    }
+
+    /// メモリ使用量（バイト）
+    pub fn memory_size(&self) -> u64 {
+        let (width, height) = self.size();
+        width as u64 * height as u64 * (1 + self.multisampled.as_ref().map_or(0, |_| self.sample_count() as u64))
+    }
+
+    /// 次のストロークのために空にする（テクスチャは次の描画の最初に消す）
+    pub fn reset(&mut self) {
+        self.needs_clear = true;
+        self.bounds = None;
+    }
+}
+
+/// メッシュが掛かるレイヤー上の範囲（線の端のアンチエイリアス分の余白を含む。レイヤーの外なら None）
+pub fn mesh_bounds(mesh: &StrokeMesh, layer_size: (u32, u32)) -> Option<ContentBounds> {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+    for vertex in &mesh.vertices {
+        min_x = min_x.min(vertex.position[0]);
+        min_y = min_y.min(vertex.position[1]);
+        max_x = max_x.max(vertex.position[0]);
+        max_y = max_y.max(vertex.position[1]);
+    }
+    let x = (min_x.floor() - 1.0).max(0.0) as u32;
+    let y = (min_y.floor() - 1.0).max(0.0) as u32;
+    let right = ((max_x.ceil() + 1.0).max(0.0) as u32).min(layer_size.0);
+    let bottom = ((max_y.ceil() + 1.0).max(0.0) as u32).min(layer_size.1);
+    (right > x && bottom > y).then(|| ContentBounds { x, y, width: right - x, height: bottom - y })
+}
+
+fn union(a: Option<ContentBounds>, b: ContentBounds) -> ContentBounds {
+    a.map_or(b, |a| a.union(&b))
+}
+
+/// ストロークを被覆率バッファに描いてから、レイヤーに1回だけ合成する描画
+///
+/// 三角形をレイヤーに直接合成すると、半透明の線の継ぎ目やスタンプの重なりがその分だけ濃くなる。
+/// 被覆率を `c + a(1 - c)` で溜めてから色と不透明度を掛けて合成すると、頂点のアルファ（流量）は
+/// 重なるほど濃くなり、不透明度はストローク全体の上限になる。不透明度が 1 ならレイヤーに直接
+/// 重ねたときと同じ結果になる。
+pub struct StrokeCoverage {
+    coverage_pipeline: RenderPipeline,
+    composite_pipeline: RenderPipeline,
+    erase_pipeline: RenderPipeline,
+    composite_bind_group_layout: BindGroupLayout,
+    composite_uniform_buffer: Buffer,
+    sample_count: u32,
+    /// 1回で描き終えるストロークに使い回す被覆率バッファ
+    scratch: Option<CoverageBuffer>,
+}
+
+impl StrokeCoverage {
+    /// `vertex_shader` と `layout` はストローク描画と同じもの（頂点の形式とユニフォームを共有する）
+    pub fn new(
+        device: &Device,
+        vertex_shader: &ShaderModule,
+        layout: &PipelineLayout,
+        target_format: TextureFormat,
+        sample_count: u32,
+        encode_srgb: bool,
+        cache: Option<&PipelineCache>,
+    ) -> Result<Self, PipelineError> {
+        debug!("[StrokeCoverage] 作成開始: {:?} {}x", target_format, sample_count);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Stroke Coverage Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(Self::shader_source())?.into()),
+        });
+
+        // 被覆率を c' = a(1 - c) + c で溜める（重なるほど 1 に近づき、1 を超えない）
+        let accumulate = BlendComponent {
+            src_factor: BlendFactor::OneMinusDst,
+            dst_factor: BlendFactor::One,
+            operation: BlendOperation::Add,
+        };
+        let coverage_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Stroke Coverage Pipeline"),
+            layout: Some(layout),
+            vertex: VertexState {
+                module: vertex_shader,
+                entry_point: Some("vs_main"),
+                buffers: &[Vertex2D::desc()],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_coverage"),
+                targets: &[Some(ColorTargetState {
+                    format: COVERAGE_FORMAT,
+                    blend: Some(BlendState { color: accumulate, alpha: accumulate }),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache,
+        });
+
+        let composite_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Stroke Composite Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let composite_layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Stroke Composite Pipeline Layout"),
+            bind_group_layouts: &[&composite_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let constants: &[(&str, f64)] = if encode_srgb { &[("encode_srgb", 1.0)] } else { &[] };
+        let composite_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Stroke Composite Pipeline"),
+            layout: Some(&composite_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_composite"),
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_composite"),
+                targets: &[Some(ColorTargetState {
+                    format: target_format,
+                    // ストロークの描画パイプラインと同じアルファブレンド
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::SrcAlpha,
+                            dst_factor: BlendFactor::OneMinusSrcAlpha,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent {
+                            src_factor: BlendFactor::One,
+                            dst_factor: BlendFactor::OneMinusSrcAlpha,
+                            operation: BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+        // アルファだけを (1 - 被覆率 × 強さ) 倍にする（選択範囲の消去と同じく色は残す）
+        let erase_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Stroke Erase Pipeline"),
+            layout: Some(&composite_layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_composite"),
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_erase"),
+                targets: &[Some(ColorTargetState {
+                    format: target_format,
+                    blend: Some(BlendState {
+                        color: BlendComponent {
+                            src_factor: BlendFactor::Zero,
+                            dst_factor: BlendFactor::One,
+                            operation: BlendOperation::Add,
+                        },
+                        alpha: BlendComponent {
+                            src_factor: BlendFactor::Zero,
+                            dst_factor: BlendFactor::OneMinusSrcAlpha,
+                            operation: BlendOperation::Add,
+                        },
+                    }),
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+        let composite_uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Stroke Composite Uniform Buffer"),
+            size: std::mem::size_of::<CompositeUniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        info!("[StrokeCoverage] 作成完了: {}x", sample_count);
+        Ok(Self {
+            coverage_pipeline,
+            composite_pipeline,
+            erase_pipeline,
+            composite_bind_group_layout,
+            composite_uniform_buffer,
+            sample_count,
+            scratch: None,
+        })
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// 使い回している被覆率バッファを取り出す（大きさが違えば作り直す）。使い終わったら `put_scratch` で戻す
+    pub fn take_scratch(&mut self, device: &Device, size: (u32, u32)) -> CoverageBuffer {
+        match self.scratch.take() {
+            Some(mut buffer) if buffer.size() == size => {
+                buffer.reset();
+                buffer
+            }
+            _ => CoverageBuffer::new(device, size, self.sample_count),
+        }
+    }
+
+    pub fn put_scratch(&mut self, buffer: CoverageBuffer) {
+        self.scratch = Some(buffer);
+    }
+
+    /// 使い回している被覆率バッファのメモリ使用量（バイト）
+    pub fn scratch_memory_size(&self) -> u64 {
+        self.scratch.as_ref().map_or(0, CoverageBuffer::memory_size)
+    }
+
+    /// 使い回している被覆率バッファを解放し、解放したバイト数を返す
+    pub fn release_scratch(&mut self) -> u64 {
+        self.scratch.take().map_or(0, |buffer| buffer.memory_size())
+    }
+
+    /// メッシュの被覆率を溜めるパスを記録（頂点・ユニフォームは書き込み済みのバッファを使う）
+    pub fn record_accumulate(
+        &self,
+        encoder: &mut CommandEncoder,
+        buffer: &mut CoverageBuffer,
+        uniform_bind_group: &BindGroup,
+        vertex_buffer: &Buffer,
+        index_buffer: &Buffer,
+        mesh: &StrokeMesh,
+    ) -> Result<(), PipelineError> {
+        if buffer.sample_count() != self.sample_count {
+            return Err(PipelineError::RenderingFailed(format!(
+                "被覆率バッファのサンプル数が描画の設定と異なります: {}x != {}x", buffer.sample_count(), self.sample_count
+            )));
+        }
+        let load = if buffer.needs_clear { LoadOp::Clear(Color::TRANSPARENT) } else { LoadOp::Load };
+        let attachment = match &buffer.multisampled {
+            Some((_, view)) => RenderPassColorAttachment {
+                view,
+                resolve_target: Some(&buffer.view),
+                // 点の追加ごとの描画をまたいで溜めるため、解決前のサンプルも残す
+                ops: Operations { load, store: StoreOp::Store },
+            },
+            None => RenderPassColorAttachment {
+                view: &buffer.view,
+                resolve_target: None,
+                ops: Operations { load, store: StoreOp::Store },
+            },
+        };
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Stroke Coverage Pass"),
+            color_attachments: &[Some(attachment)],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.coverage_pipeline);
+        render_pass.set_bind_group(0, uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.indices.len() as u32, 0, 0..1);
+        drop(render_pass);
+
+        buffer.needs_clear = false;
+        if let Some(bounds) = mesh_bounds(mesh, buffer.size()) {
+            buffer.bounds = Some(union(buffer.bounds, bounds));
+        }
+        Ok(())
+    }
+
+    /// 溜めた被覆率を `target` に合成するパスを記録（描き込んだ範囲だけを描く）
+    ///
+    /// ユニフォームは送信前にまとめて書き込まれるため、1回の送信で合成できるのは1回だけ。
+    pub fn record_composite(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        buffer: &CoverageBuffer,
+        target: &TextureView,
+        blend: CoverageBlend,
+    ) {
+        let Some(bounds) = buffer.bounds else { return };
+        let (pipeline, color) = match blend {
+            CoverageBlend::Paint(color) => (&self.composite_pipeline, color),
+            CoverageBlend::Erase(strength) => (&self.erase_pipeline, [0.0, 0.0, 0.0, strength]),
+        };
+        queue.write_buffer(&self.composite_uniform_buffer, 0, bytemuck::bytes_of(&CompositeUniforms { color }));
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Stroke Composite Bind Group"),
+            layout: &self.composite_bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&buffer.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.composite_uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Stroke Composite Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Load,
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.set_scissor_rect(bounds.x, bounds.y, bounds.width, bounds.height);
+        render_pass.draw(0..3, 0..1);
+    }
+
+    /// 被覆率の蓄積と合成のシェーダー（WGSL。頂点シェーダーはストローク描画のものを使う）
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        #import color
+        #import fullscreen
+
+        // 非 sRGB のビューに合成するときは、ストロークの色を手動で変換する
+        override encode_srgb: bool = false;
+
+        struct FragmentInput {
+            @location(0) color: vec4<f32>,
+            @location(1) line_width: f32,
+        }
+
+        // 頂点のアルファ（流量）を被覆率として出力する
+        @fragment
+        fn fs_coverage(in: FragmentInput) -> @location(0) vec4<f32> {
+            var flow = in.color.a;
+            // 1ピクセルより細い線は幅に応じて薄くする（描画パイプラインと同じ）
+            if (in.line_width < 1.0) {
+                flow = flow * in.line_width;
+            }
+            return vec4<f32>(flow, 0.0, 0.0, flow);
+        }
+
+        struct CompositeUniforms {
+            color: vec4<f32>,
+        }
+
+        @group(0) @binding(0) var coverage: texture_2d<f32>;
+        @group(0) @binding(1) var<uniform> paint: CompositeUniforms;
+
+        @vertex
+        fn vs_composite(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            return fullscreen_triangle(index);
+        }
+
+        @fragment
+        fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            let c = textureLoad(coverage, vec2<i32>(position.xy), 0).r;
+            var rgb = paint.color.rgb;
+            if encode_srgb {
+                rgb = linear_to_srgb(rgb);
+            }
+            return vec4<f32>(rgb, c * paint.color.a);
+        }
+
+        // 消す量をアルファとして出力する（色はブレンドで捨てる）
+        @fragment
+        fn fs_erase(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            let c = textureLoad(coverage, vec2<i32>(position.xy), 0).r;
+            return vec4<f32>(0.0, 0.0, 0.0, c * paint.color.a);
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mesh_bounds() {
+        let mut mesh = StrokeMesh::default();
+        let vertex = |x, y| Vertex2D::new(x, y, [0.0, 0.0, 0.0, 1.0], 1.0);
+        mesh.push_quad([vertex(10.5, 20.0), vertex(10.5, 24.0), vertex(30.0, 20.0), vertex(30.0, 24.0)]);
+        assert_eq!(mesh_bounds(&mesh, (100, 100)), Some(ContentBounds { x: 9, y: 19, width: 22, height: 6 }));
+        // レイヤーの端で切り詰める
+        assert_eq!(mesh_bounds(&mesh, (25, 22)), Some(ContentBounds { x: 9, y: 19, width: 16, height: 3 }));
+        assert_eq!(mesh_bounds(&mesh, (5, 5)), None);
+        assert_eq!(mesh_bounds(&StrokeMesh::default(), (100, 100)), None);
+
+        let a = ContentBounds { x: 10, y: 10, width: 5, height: 5 };
+        let b = ContentBounds { x: 2, y: 12, width: 3, height: 10 };
+        assert_eq!(union(Some(a), b), ContentBounds { x: 2, y: 10, width: 13, height: 12 });
+        assert_eq!(union(None, b), b);
+    }
+}
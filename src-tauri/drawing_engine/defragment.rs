@@ -0,0 +1,32 @@
+use serde::{Deserialize, Serialize};
+use super::texture::PoolDefragment;
+
+/// テクスチャプールの整理の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DefragmentOptions {
+    /// 使用中のレイヤーと同じ仕様の未使用テクスチャを何枚まで残すか
+    pub keep_per_spec: usize,
+    /// 無限キャンバスのタイルも詰める
+    pub compact_tiles: bool,
+}
+
+impl Default for DefragmentOptions {
+    fn default() -> Self {
+        Self {
+            keep_per_spec: 1,
+            compact_tiles: false,
+        }
+    }
+}
+
+/// テクスチャプールの整理の結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct DefragmentReport {
+    #[serde(flatten)]
+    pub pool: PoolDefragment,
+    /// 描画の作業用のテクスチャを解放したバイト数
+    pub scratch_freed_bytes: u64,
+    /// 破棄した無限キャンバスのタイル数（`compact_tiles` が false なら 0）
+    pub compacted_tiles: usize,
+}
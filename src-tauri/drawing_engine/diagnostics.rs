@@ -0,0 +1,228 @@
+use serde::Serialize;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use wgpu::Limits;
+use super::limits::DeviceCapabilities;
+
+/// 記録しておく最近のフレーム時間の数（種類ごと）
+pub const MAX_FRAME_SAMPLES: usize = 1024;
+
+/// 記録しておく最近のGPUエラーの数
+pub const MAX_GPU_ERRORS: usize = 32;
+
+/// GPUで発生したエラー（エラースコープで捕まえたもの・捕まえられなかったもの）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct GpuErrorRecord {
+    /// 発生時刻（RFC 3339）
+    pub time: String,
+    /// 発生した処理（`uncaptured` はエラースコープの外で起きたもの）
+    pub source: String,
+    pub message: String,
+}
+
+/// 最近のGPUエラーの記録（デバイスのエラーハンドラーからも書き込むため共有する）
+#[derive(Debug, Clone, Default)]
+pub struct GpuErrorLog {
+    records: Arc<Mutex<VecDeque<GpuErrorRecord>>>,
+}
+
+impl GpuErrorLog {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&self, source: impl Into<String>, message: impl Into<String>) {
+        let Ok(mut records) = self.records.lock() else { return };
+        if records.len() == MAX_GPU_ERRORS {
+            records.pop_front();
+        }
+        records.push_back(GpuErrorRecord {
+            time: chrono::Utc::now().to_rfc3339(),
+            source: source.into(),
+            message: message.into(),
+        });
+    }
+
+    /// 最近のエラー（古い順）
+    pub fn recent(&self) -> Vec<GpuErrorRecord> {
+        self.records.lock().map(|records| records.iter().cloned().collect()).unwrap_or_default()
+    }
+}
+
+/// フレーム時間の分布（ミリ秒）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct FrameTimeStats {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub p50_ms: f64,
+    pub p90_ms: f64,
+    pub p99_ms: f64,
+    pub max_ms: f64,
+}
+
+impl FrameTimeStats {
+    /// 記録から分布を求める（記録がなければ None）。百分位は最近順位法で求める
+    pub fn from_samples(samples: &[f64]) -> Option<Self> {
+        if samples.is_empty() {
+            return None;
+        }
+        let mut sorted = samples.to_vec();
+        sorted.sort_by(f64::total_cmp);
+        let percentile = |p: f64| sorted[((p / 100.0 * sorted.len() as f64).ceil() as usize).clamp(1, sorted.len()) - 1];
+        Some(Self {
+            count: sorted.len(),
+            mean_ms: sorted.iter().sum::<f64>() / sorted.len() as f64,
+            p50_ms: percentile(50.0),
+            p90_ms: percentile(90.0),
+            p99_ms: percentile(99.0),
+            max_ms: sorted[sorted.len() - 1],
+        })
+    }
+}
+
+/// 最近のフレーム時間の記録
+#[derive(Debug, Clone, Default)]
+pub struct FrameTimeRecorder {
+    samples: VecDeque<f64>,
+}
+
+impl FrameTimeRecorder {
+    pub fn record(&mut self, ms: f64) {
+        if self.samples.len() == MAX_FRAME_SAMPLES {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(ms.max(0.0));
+    }
+
+    pub fn stats(&self) -> Option<FrameTimeStats> {
+        FrameTimeStats::from_samples(&self.samples.iter().copied().collect::<Vec<_>>())
+    }
+
+    pub fn clear(&mut self) {
+        self.samples.clear();
+    }
+}
+
+/// 性能診断のためのセッション中の記録（設定で有効にしたときだけ記録する）
+#[derive(Debug, Clone, Default)]
+pub struct DiagnosticsRecorder {
+    enabled: bool,
+    /// ストロークの点を追加して描画するまでの時間（描画中の1フレーム分の処理）
+    pub stroke_frames: FrameTimeRecorder,
+    /// オーバーレイ付きのキャンバス表示画像を作るまでの時間
+    pub view_frames: FrameTimeRecorder,
+}
+
+impl DiagnosticsRecorder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// 記録の有効・無効を切り替える（無効にすると記録を捨てる）
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+        if !enabled {
+            self.stroke_frames.clear();
+            self.view_frames.clear();
+        }
+    }
+
+    pub fn record_stroke_frame(&mut self, ms: f64) {
+        if self.enabled {
+            self.stroke_frames.record(ms);
+        }
+    }
+
+    pub fn record_view_frame(&mut self, ms: f64) {
+        if self.enabled {
+            self.view_frames.record(ms);
+        }
+    }
+}
+
+/// 診断に使うデバイスの上限（主なもの）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct DeviceLimitsSummary {
+    pub max_texture_dimension_2d: u32,
+    pub max_buffer_size: u64,
+    pub max_storage_buffer_binding_size: u32,
+    pub max_uniform_buffer_binding_size: u32,
+    pub max_bind_groups: u32,
+    pub max_compute_workgroup_size_x: u32,
+    pub max_compute_invocations_per_workgroup: u32,
+    pub max_compute_workgroups_per_dimension: u32,
+}
+
+impl From<&Limits> for DeviceLimitsSummary {
+    fn from(limits: &Limits) -> Self {
+        Self {
+            max_texture_dimension_2d: limits.max_texture_dimension_2d,
+            max_buffer_size: limits.max_buffer_size,
+            max_storage_buffer_binding_size: limits.max_storage_buffer_binding_size,
+            max_uniform_buffer_binding_size: limits.max_uniform_buffer_binding_size,
+            max_bind_groups: limits.max_bind_groups,
+            max_compute_workgroup_size_x: limits.max_compute_workgroup_size_x,
+            max_compute_invocations_per_workgroup: limits.max_compute_invocations_per_workgroup,
+            max_compute_workgroups_per_dimension: limits.max_compute_workgroups_per_dimension,
+        }
+    }
+}
+
+/// 診断レポートに載せる使用中のGPUの情報
+#[derive(Debug, Clone, Serialize)]
+pub struct GpuDiagnostics {
+    #[serde(flatten)]
+    pub capabilities: DeviceCapabilities,
+    pub driver: String,
+    pub driver_info: String,
+    pub limits: DeviceLimitsSummary,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_time_percentiles() {
+        assert_eq!(FrameTimeStats::from_samples(&[]), None);
+
+        let samples: Vec<f64> = (1..=100).rev().map(f64::from).collect();
+        let stats = FrameTimeStats::from_samples(&samples).unwrap();
+        assert_eq!(stats.count, 100);
+        assert_eq!((stats.p50_ms, stats.p90_ms, stats.p99_ms, stats.max_ms), (50.0, 90.0, 99.0, 100.0));
+        assert_eq!(stats.mean_ms, 50.5);
+
+        let single = FrameTimeStats::from_samples(&[4.0]).unwrap();
+        assert_eq!((single.p50_ms, single.p99_ms), (4.0, 4.0));
+    }
+
+    #[test]
+    fn test_recorder_opt_in_and_capacity() {
+        let mut recorder = DiagnosticsRecorder::new();
+        recorder.record_stroke_frame(5.0);
+        assert_eq!(recorder.stroke_frames.stats(), None);
+
+        recorder.set_enabled(true);
+        for i in 0..MAX_FRAME_SAMPLES + 10 {
+            recorder.record_stroke_frame(i as f64);
+        }
+        let stats = recorder.stroke_frames.stats().unwrap();
+        assert_eq!(stats.count, MAX_FRAME_SAMPLES);
+        assert_eq!(stats.max_ms, (MAX_FRAME_SAMPLES + 9) as f64);
+
+        recorder.set_enabled(false);
+        assert_eq!(recorder.stroke_frames.stats(), None);
+
+        let log = GpuErrorLog::new();
+        for i in 0..MAX_GPU_ERRORS + 1 {
+            log.clone().push("uncaptured", format!("error {}", i));
+        }
+        let recent = log.recent();
+        assert_eq!(recent.len(), MAX_GPU_ERRORS);
+        assert_eq!(recent[0].message, "error 1");
+    }
+}
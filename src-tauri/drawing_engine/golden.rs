@@ -0,0 +1,212 @@
+use super::compare::{compose_comparison, difference_heatmap, pixel_difference, CompareLayout, DifferenceStats};
+use image::RgbaImage;
+use std::path::{Path, PathBuf};
+
+/// 設定すると比較の代わりに基準画像を書き直す環境変数（シェーダー・三角形分割を意図して変えたとき）
+pub const UPDATE_GOLDEN_ENV: &str = "KINEGRAPH_UPDATE_GOLDEN";
+
+/// 基準画像と比べるときの許容範囲
+///
+/// フォールバックアダプターでも実装やバージョンによって境界の丸め方が少し違うため、
+/// 色の小さな差と、縁のアンチエイリアスが1画素ずれた程度の差は見逃す。
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GoldenTolerance {
+    /// 画素ごとの差（乗算済みアルファの各チャンネルの差の最大）がこれ以下なら同じとみなす
+    pub threshold: u8,
+    /// 差のある画素でも、相手の画像のこの距離（画素）以内に近い画素があれば縁のずれとみなす
+    pub neighbor_radius: u32,
+    /// 見逃せない差のある画素の割合（0〜100）がこれ以下なら一致とみなす
+    pub max_mismatch_percent: f32,
+    /// 縁のずれとして見逃す画素の、描かれた画素に対する割合（0〜100）の上限
+    ///
+    /// 丸め方の違いは縁のところどころに出るだけなので、縁全体がずれる（線が1画素太る）変化はここで見つける。
+    pub max_shifted_percent: f32,
+}
+
+impl Default for GoldenTolerance {
+    fn default() -> Self {
+        Self {
+            threshold: 8,
+            neighbor_radius: 1,
+            max_mismatch_percent: 0.05,
+            max_shifted_percent: 2.0,
+        }
+    }
+}
+
+/// 基準画像との比較の結果
+#[derive(Debug, Clone)]
+pub struct GoldenComparison {
+    /// 単純な画素ごとの差
+    pub stats: DifferenceStats,
+    /// 縁のずれとしても説明できない差のある画素の数
+    pub mismatched_pixels: u64,
+    pub mismatched_percent: f32,
+    /// 縁のずれとして見逃した画素の、描かれた画素（どちらかが透明でない画素）に対する割合
+    pub shifted_percent: f32,
+    /// 大きさが同じか（違う場合は差がなくても一致とみなさない）
+    pub same_size: bool,
+    /// 差のヒートマップ
+    pub heatmap: RgbaImage,
+}
+
+impl GoldenComparison {
+    pub fn passes(&self, tolerance: &GoldenTolerance) -> bool {
+        self.same_size
+            && self.mismatched_percent <= tolerance.max_mismatch_percent
+            && self.shifted_percent <= tolerance.max_shifted_percent
+    }
+}
+
+/// `image` の `(x, y)` の周囲に `pixel` と近い画素があるか（範囲外は透明な画素として扱う）
+fn has_close_neighbor(image: &RgbaImage, x: u32, y: u32, pixel: image::Rgba<u8>, tolerance: &GoldenTolerance) -> bool {
+    let radius = tolerance.neighbor_radius as i64;
+    (-radius..=radius).any(|dy| {
+        (-radius..=radius).any(|dx| {
+            let (nx, ny) = (x as i64 + dx, y as i64 + dy);
+            let neighbor = if nx >= 0 && ny >= 0 && (nx as u32) < image.width() && (ny as u32) < image.height() {
+                *image.get_pixel(nx as u32, ny as u32)
+            } else {
+                image::Rgba([0; 4])
+            };
+            pixel_difference(pixel, neighbor) <= tolerance.threshold
+        })
+    })
+}
+
+/// 描画結果を基準画像と比べる（大きさが違う場合は、はみ出した部分を透明な画素と比べる）
+///
+/// 差のある画素は、お互いの周囲にどちらも近い画素があるときだけ縁のずれとして見逃す。
+/// 線が太った・細った・消えたといった変化は片側に近い画素がないため見逃さない。
+pub fn compare_golden(actual: &RgbaImage, golden: &RgbaImage, tolerance: &GoldenTolerance) -> GoldenComparison {
+    let (heatmap, stats) = difference_heatmap(actual, golden, tolerance.threshold);
+    let pixel_at = |image: &RgbaImage, x: u32, y: u32| {
+        if x < image.width() && y < image.height() { *image.get_pixel(x, y) } else { image::Rgba([0; 4]) }
+    };
+
+    let mut mismatched_pixels = 0u64;
+    let mut shifted_pixels = 0u64;
+    let mut drawn_pixels = 0u64;
+    for (x, y, cell) in heatmap.enumerate_pixels() {
+        let (a, g) = (pixel_at(actual, x, y), pixel_at(golden, x, y));
+        if a[3] > 0 || g[3] > 0 {
+            drawn_pixels += 1;
+        }
+        if cell[3] == 0 {
+            continue;
+        }
+        if has_close_neighbor(golden, x, y, a, tolerance) && has_close_neighbor(actual, x, y, g, tolerance) {
+            shifted_pixels += 1;
+        } else {
+            mismatched_pixels += 1;
+        }
+    }
+    let mismatched_percent = if stats.total_pixels == 0 {
+        0.0
+    } else {
+        mismatched_pixels as f32 * 100.0 / stats.total_pixels as f32
+    };
+    let shifted_percent = if drawn_pixels == 0 { 0.0 } else { shifted_pixels as f32 * 100.0 / drawn_pixels as f32 };
+    GoldenComparison {
+        stats,
+        mismatched_pixels,
+        mismatched_percent,
+        shifted_percent,
+        same_size: actual.dimensions() == golden.dimensions(),
+        heatmap,
+    }
+}
+
+/// 基準画像を置くディレクトリ（リポジトリに含める）
+pub fn golden_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests").join("golden")
+}
+
+/// 一致しなかったときに描画結果と差分を書き出すディレクトリ
+fn failure_dir() -> PathBuf {
+    std::env::temp_dir().join("kinegraph-golden")
+}
+
+/// 描画結果を `name` の基準画像と比べ、一致しなければ差分を書き出して内容を返す
+///
+/// `KINEGRAPH_UPDATE_GOLDEN=1` のときは比較せずに基準画像を書き直す。
+/// 差分は「基準・描画結果」を並べた画像と、差のヒートマップの2枚。
+pub fn check_golden(name: &str, actual: &RgbaImage, tolerance: &GoldenTolerance) -> Result<(), String> {
+    let path = golden_dir().join(format!("{}.png", name));
+    if std::env::var_os(UPDATE_GOLDEN_ENV).is_some_and(|value| value != "0") {
+        std::fs::create_dir_all(golden_dir()).map_err(|e| e.to_string())?;
+        actual.save(&path).map_err(|e| e.to_string())?;
+        println!("- 基準画像を更新: {:?}", path);
+        return Ok(());
+    }
+    if !path.exists() {
+        return Err(format!("基準画像がありません: {:?} ({}=1 で作成)", path, UPDATE_GOLDEN_ENV));
+    }
+    let golden = image::open(&path).map_err(|e| format!("基準画像を読めません: {:?}: {}", path, e))?.to_rgba8();
+
+    let comparison = compare_golden(actual, &golden, tolerance);
+    if comparison.passes(tolerance) {
+        return Ok(());
+    }
+
+    let dir = failure_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let side_by_side = dir.join(format!("{}.compare.png", name));
+    let heatmap = dir.join(format!("{}.diff.png", name));
+    compose_comparison(&golden, actual, CompareLayout::SideBySide { gap: 4 })
+        .save(&side_by_side)
+        .map_err(|e| e.to_string())?;
+    comparison.heatmap.save(&heatmap).map_err(|e| e.to_string())?;
+
+    Err(format!(
+        "基準画像と一致しません: {} - 差のある画素 {:.3}% (許容 {:.3}%), 縁のずれ {:.2}% (許容 {:.2}%), 画素ごとの差の最大 {}, 大きさ {:?} / 基準 {:?}\n  並べた画像: {:?}\n  差分: {:?}",
+        name,
+        comparison.mismatched_percent,
+        tolerance.max_mismatch_percent,
+        comparison.shifted_percent,
+        tolerance.max_shifted_percent,
+        comparison.stats.max_difference,
+        actual.dimensions(),
+        golden.dimensions(),
+        side_by_side,
+        heatmap,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_edge_shift_is_tolerated_but_missing_line_is_not() {
+        let line_at = |x0: u32| RgbaImage::from_fn(16, 16, |x, _| {
+            if (x0..x0 + 3).contains(&x) { Rgba([0, 0, 0, 255]) } else { Rgba([0; 4]) }
+        });
+        let tolerance = GoldenTolerance { max_mismatch_percent: 0.0, max_shifted_percent: 100.0, ..GoldenTolerance::default() };
+        let golden = line_at(5);
+
+        // 色の小さな差は見逃す
+        let mut faint = golden.clone();
+        faint.put_pixel(6, 3, Rgba([4, 4, 4, 255]));
+        assert!(compare_golden(&faint, &golden, &tolerance).passes(&tolerance));
+
+        // 1画素ずれた縁は、差はあっても見逃す（縁全体がずれた場合は割合の上限で見つける）
+        let shifted = compare_golden(&line_at(6), &golden, &tolerance);
+        assert!(shifted.stats.changed_pixels > 0);
+        assert!(shifted.passes(&tolerance));
+        assert!(shifted.shifted_percent > 2.0 && !shifted.passes(&GoldenTolerance::default()));
+
+        // 線が太った・消えた・大きさが違う場合は見逃さない
+        let mut thick = golden.clone();
+        for y in 0..16 {
+            for x in 8..12 {
+                thick.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+            }
+        }
+        assert!(!compare_golden(&thick, &golden, &tolerance).passes(&tolerance));
+        let empty = RgbaImage::new(16, 16);
+        assert_eq!(compare_golden(&empty, &golden, &tolerance).mismatched_pixels, 48);
+        assert!(!compare_golden(&RgbaImage::new(16, 20), &empty, &tolerance).passes(&tolerance));
+    }
+}
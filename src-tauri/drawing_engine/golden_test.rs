@@ -0,0 +1,126 @@
+use super::*;
+use super::golden::{check_golden, GoldenTolerance};
+use crate::animation::BlendMode;
+use crate::formats::{flatten_layers, RasterLayer};
+
+/// 基準画像のキャンバスの大きさ
+const GOLDEN_SIZE: u32 = 128;
+
+/// 基準画像の描画結果の回帰テスト
+///
+/// 代表的なストローク・合成をフォールバックアダプター（ソフトウェア実装）で描き、
+/// `tests/golden` の基準画像と比べる。シェーダーや三角形分割の変更で描画結果が変わると失敗し、
+/// 並べた画像と差分を一時ディレクトリに書き出す。意図した変更なら `KINEGRAPH_UPDATE_GOLDEN=1` で書き直す。
+async fn fallback_engine() -> Option<DrawingEngine> {
+    let instance = Instance::new(&InstanceDescriptor::default());
+    let options = RequestAdapterOptions { force_fallback_adapter: true, ..RequestAdapterOptions::default() };
+    if instance.request_adapter(&options).await.is_err() {
+        println!("- フォールバックアダプターがないためスキップ");
+        return None;
+    }
+    let mut engine = DrawingEngine::new();
+    engine.set_force_fallback_adapter(true);
+    match engine.initialize().await {
+        Ok(()) => Some(engine),
+        Err(e) => {
+            println!("- 描画エンジンを初期化できないためスキップ: {}", e);
+            None
+        }
+    }
+}
+
+/// 描いたレイヤーを読み出して基準画像と比べる
+async fn assert_golden(engine: &DrawingEngine, layer_id: &str, name: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let image = engine.get_layer_image(layer_id).await?;
+    check_golden(name, &image, &GoldenTolerance::default())?;
+    Ok(())
+}
+
+fn stroke(color: [f32; 4], width: f32, points: &[(f32, f32, f32)]) -> DrawStroke {
+    let mut stroke = DrawStroke::new(color, width);
+    for &(x, y, pressure) in points {
+        stroke.add_point(x, y, pressure);
+    }
+    stroke
+}
+
+#[tokio::test]
+async fn test_golden_lines() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut engine) = fallback_engine().await else { return Ok(()) };
+    engine.create_layer_texture("golden", GOLDEN_SIZE, GOLDEN_SIZE)?;
+
+    // 太さ・向きの違う線（縦横・斜め・細線）
+    engine.draw_line_to_layer("golden", (10.0, 10.0), (118.0, 10.0), [1.0, 0.0, 0.0, 1.0], 1.0)?;
+    engine.draw_line_to_layer("golden", (10.0, 24.0), (118.0, 24.0), [0.0, 0.6, 0.0, 1.0], 4.0)?;
+    engine.draw_line_to_layer("golden", (10.0, 40.0), (118.0, 118.0), [0.0, 0.0, 1.0, 1.0], 6.0)?;
+    engine.draw_line_to_layer("golden", (118.0, 40.0), (40.0, 118.0), [0.2, 0.2, 0.2, 1.0], 2.5)?;
+    engine.draw_line_to_layer("golden", (20.0, 50.0), (20.0, 118.0), [1.0, 0.5, 0.0, 1.0], 9.0)?;
+
+    assert_golden(&engine, "golden", "lines").await?;
+    println!("✓ 線の基準画像のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_golden_pressure_strokes() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut engine) = fallback_engine().await else { return Ok(()) };
+    engine.create_layer_texture("golden", GOLDEN_SIZE, GOLDEN_SIZE)?;
+
+    // 筆圧で太さの変わるサイン波と、鋭く折り返すストローク（継ぎ目と角の三角形分割）
+    let wave: Vec<_> = (0..32)
+        .map(|i| {
+            let t = i as f32 / 31.0;
+            let pressure = 0.2 + 0.8 * (t * std::f32::consts::PI).sin();
+            (12.0 + t * 104.0, 40.0 + (t * std::f32::consts::PI * 2.0).sin() * 20.0, pressure)
+        })
+        .collect();
+    engine.draw_stroke_to_layer("golden", &stroke([0.0, 0.3, 0.8, 1.0], 10.0, &wave))?;
+    let zigzag = [(16.0, 112.0, 1.0), (40.0, 76.0, 0.6), (64.0, 112.0, 1.0), (88.0, 76.0, 0.4), (112.0, 112.0, 1.0)];
+    engine.draw_stroke_to_layer("golden", &stroke([0.7, 0.1, 0.1, 1.0], 6.0, &zigzag))?;
+
+    assert_golden(&engine, "golden", "pressure_strokes").await?;
+    println!("✓ 筆圧のあるストロークの基準画像のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_golden_translucent_and_erase() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut engine) = fallback_engine().await else { return Ok(()) };
+    engine.create_layer_texture("golden", GOLDEN_SIZE, GOLDEN_SIZE)?;
+
+    // 重なる半透明のストローク・自己交差・スタンプの流量・消しゴム
+    let cross = [(16.0, 16.0, 1.0), (112.0, 64.0, 1.0), (16.0, 64.0, 1.0), (112.0, 16.0, 1.0)];
+    engine.draw_stroke_to_layer("golden", &stroke([1.0, 0.0, 0.0, 0.5], 12.0, &cross))?;
+    engine.draw_stroke_to_layer("golden", &stroke([0.0, 0.0, 1.0, 0.5], 12.0, &[(64.0, 8.0, 1.0), (64.0, 120.0, 1.0)]))?;
+    let stamps: Vec<Stamp> = (0..12)
+        .map(|i| Stamp { center: [20.0 + i as f32 * 8.0, 96.0], radius: 10.0, opacity: 0.4 })
+        .collect();
+    let shape = StampShape { color: [0.0, 0.6, 0.2, 0.8], hardness: 0.5, angle: 0.0, roundness: 1.0 };
+    engine.draw_stamps_to_layer("golden", &stamps, &shape, StampRasterizer::Compute)?;
+    engine.erase_stroke_from_layer("golden", &stroke([0.0, 0.0, 0.0, 1.0], 8.0, &[(8.0, 40.0, 1.0), (120.0, 40.0, 1.0)]))?;
+
+    assert_golden(&engine, "golden", "translucent_and_erase").await?;
+    println!("✓ 半透明の重なりと消しゴムの基準画像のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_golden_layer_composite() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut engine) = fallback_engine().await else { return Ok(()) };
+
+    // GPUで描いたレイヤーを不透明度・合成モードを変えて重ねる
+    engine.create_layer_texture("base", GOLDEN_SIZE, GOLDEN_SIZE)?;
+    engine.create_layer_texture("top", GOLDEN_SIZE, GOLDEN_SIZE)?;
+    engine.draw_line_to_layer("base", (16.0, 64.0), (112.0, 64.0), [0.9, 0.7, 0.1, 1.0], 40.0)?;
+    engine.draw_stroke_to_layer("top", &stroke([0.1, 0.4, 0.9, 1.0], 24.0, &[(64.0, 16.0, 1.0), (64.0, 112.0, 0.5)]))?;
+
+    let mut base = RasterLayer::new("base", engine.get_layer_image("base").await?);
+    base.opacity = 0.8;
+    let mut top = RasterLayer::new("top", engine.get_layer_image("top").await?);
+    top.blend_mode = BlendMode::Multiply;
+    let composite = flatten_layers(&[base, top], GOLDEN_SIZE, GOLDEN_SIZE);
+
+    check_golden("layer_composite", &composite, &GoldenTolerance::default())?;
+    println!("✓ レイヤー合成の基準画像のテスト成功");
+    Ok(())
+}
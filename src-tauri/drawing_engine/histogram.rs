@@ -0,0 +1,384 @@
+use wgpu::*;
+use log::{info, debug};
+use crate::shaders;
+use serde::{Deserialize, Serialize};
+use super::pipeline::PipelineError;
+use super::texture::{ManagedTexture, TextureError};
+
+/// コンピュートシェーダーのワークグループの大きさ（1辺）
+const WORKGROUP_SIZE: u32 = 16;
+/// 1チャンネルのビン数
+pub const HISTOGRAM_BINS: usize = 256;
+/// 結果バッファのチャンネル数（赤・緑・青・輝度・アルファ）
+const HISTOGRAM_CHANNELS: usize = 5;
+
+/// ヒストグラムを求める範囲（レイヤー左上からの矩形）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct HistogramRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl HistogramRegion {
+    /// 画像の範囲に収めた矩形（重ならなければ None）
+    pub fn clamped(&self, (width, height): (u32, u32)) -> Option<Self> {
+        let right = self.x.saturating_add(self.width).min(width);
+        let bottom = self.y.saturating_add(self.height).min(height);
+        (right > self.x && bottom > self.y).then(|| Self {
+            x: self.x,
+            y: self.y,
+            width: right - self.x,
+            height: bottom - self.y,
+        })
+    }
+}
+
+/// チャンネルごとの 256 段階のヒストグラム
+///
+/// 色と輝度は不透明度が 0 より大きいピクセルだけを数え、アルファは範囲内の全ピクセルを数える。
+/// 輝度は sRGB の値から Rec.601 の係数で求める。
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Histogram {
+    pub red: Vec<u32>,
+    pub green: Vec<u32>,
+    pub blue: Vec<u32>,
+    pub luminance: Vec<u32>,
+    pub alpha: Vec<u32>,
+    /// 色と輝度で数えたピクセル数
+    pub pixel_count: u32,
+}
+
+impl Histogram {
+    /// 結果バッファ（チャンネルごとに 256 個ずつ並べたもの）から作成
+    fn from_bins(bins: &[u32]) -> Self {
+        let channel = |index: usize| bins[index * HISTOGRAM_BINS..(index + 1) * HISTOGRAM_BINS].to_vec();
+        let red = channel(0);
+        Self {
+            pixel_count: red.iter().sum(),
+            red,
+            green: channel(1),
+            blue: channel(2),
+            luminance: channel(3),
+            alpha: channel(4),
+        }
+    }
+
+    /// 画像のヒストグラム（CPUで計算。範囲を省略すると画像全体）
+    pub fn from_image(image: &image::RgbaImage, region: Option<HistogramRegion>) -> Self {
+        let mut bins = vec![0u32; HISTOGRAM_CHANNELS * HISTOGRAM_BINS];
+        let full = HistogramRegion { x: 0, y: 0, width: image.width(), height: image.height() };
+        if let Some(region) = region.unwrap_or(full).clamped(image.dimensions()) {
+            for y in region.y..region.y + region.height {
+                for x in region.x..region.x + region.width {
+                    let [r, g, b, a] = image.get_pixel(x, y).0;
+                    bins[4 * HISTOGRAM_BINS + a as usize] += 1;
+                    if a > 0 {
+                        bins[r as usize] += 1;
+                        bins[HISTOGRAM_BINS + g as usize] += 1;
+                        bins[2 * HISTOGRAM_BINS + b as usize] += 1;
+                        bins[3 * HISTOGRAM_BINS + luminance(r, g, b) as usize] += 1;
+                    }
+                }
+            }
+        }
+        Self::from_bins(&bins)
+    }
+}
+
+/// 輝度（GPU と結果が一致するようにシェーダーと同じ整数演算で求める）
+fn luminance(r: u8, g: u8, b: u8) -> u8 {
+    ((299 * r as u32 + 587 * g as u32 + 114 * b as u32 + 500) / 1000) as u8
+}
+
+/// レイヤーテクスチャのヒストグラムを求めるリダクションパイプライン
+///
+/// ワークグループ内で共有メモリのビンに数えてから、
+/// ワークグループごとに 0 でないビンだけを結果バッファへ atomicAdd で足し込む。
+pub struct HistogramPipeline {
+    compute_pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl HistogramPipeline {
+    pub fn new(device: &Device, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        Self::with_shader(device, cache, Self::shader_source())
+    }
+
+    pub fn with_shader(device: &Device, cache: Option<&PipelineCache>, source: &str) -> Result<Self, PipelineError> {
+        info!("[HistogramPipeline] パイプライン作成開始");
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Histogram Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(source)?.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Histogram Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Histogram Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Histogram Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache,
+        });
+
+        info!("[HistogramPipeline] パイプライン作成完了");
+        Ok(Self {
+            compute_pipeline,
+            bind_group_layout,
+        })
+    }
+
+    /// テクスチャの範囲（省略時は全体）のヒストグラムを求める
+    pub async fn compute(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        texture: &ManagedTexture,
+        region: Option<HistogramRegion>,
+    ) -> Result<Histogram, TextureError> {
+        let size = (texture.spec.width, texture.spec.height);
+        let full = HistogramRegion { x: 0, y: 0, width: size.0, height: size.1 };
+        let Some(region) = region.unwrap_or(full).clamped(size) else {
+            return Ok(Histogram::from_bins(&[0; HISTOGRAM_CHANNELS * HISTOGRAM_BINS]));
+        };
+        debug!("[HistogramPipeline] ヒストグラム計算: {:?}", region);
+
+        let result_size = (HISTOGRAM_CHANNELS * HISTOGRAM_BINS * std::mem::size_of::<u32>()) as u64;
+        let result_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram Result Buffer"),
+            size: result_size,
+            // wgpu のバッファは 0 で初期化される
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+
+        let region_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram Region Buffer"),
+            size: 16,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let region_data: [u32; 4] = [region.x, region.y, region.width, region.height];
+        queue.write_buffer(&region_buffer, 0, bytemuck::cast_slice(&region_data));
+
+        let read_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Histogram Read Buffer"),
+            size: result_size,
+            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Histogram Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&texture.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: result_buffer.as_entire_binding(),
+                },
+                BindGroupEntry {
+                    binding: 2,
+                    resource: region_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Histogram Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Histogram Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(
+                region.width.div_ceil(WORKGROUP_SIZE),
+                region.height.div_ceil(WORKGROUP_SIZE),
+                1,
+            );
+        }
+        encoder.copy_buffer_to_buffer(&result_buffer, 0, &read_buffer, 0, result_size);
+        queue.submit(std::iter::once(encoder.finish()));
+
+        let buffer_slice = read_buffer.slice(..);
+        let (sender, receiver) = futures::channel::oneshot::channel();
+        buffer_slice.map_async(MapMode::Read, move |result| {
+            sender.send(result).unwrap();
+        });
+
+        let _ = device.poll(wgpu::MaintainBase::Wait);
+
+        receiver.await
+            .map_err(|_| TextureError::BufferReadFailed("バッファマップ待機に失敗".to_string()))?
+            .map_err(|e| TextureError::BufferReadFailed(format!("バッファマップに失敗: {:?}", e)))?;
+
+        let data = buffer_slice.get_mapped_range();
+        let bins: Vec<u32> = bytemuck::pod_collect_to_vec(&data);
+        drop(data);
+        read_buffer.unmap();
+
+        let histogram = Histogram::from_bins(&bins);
+        debug!("[HistogramPipeline] ヒストグラム計算完了: {} ピクセル", histogram.pixel_count);
+        Ok(histogram)
+    }
+
+    /// ヒストグラムを求めるシェーダー（WGSL）
+    ///
+    /// レイヤーテクスチャは sRGB 形式で読み出すと線形の値になるため、sRGB に戻してからビンに分ける。
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        struct Region {
+            origin: vec2<u32>,
+            size: vec2<u32>,
+        }
+
+        @group(0) @binding(0) var source: texture_2d<f32>;
+        @group(0) @binding(1) var<storage, read_write> bins: array<atomic<u32>, 1280>;
+        @group(0) @binding(2) var<uniform> region: Region;
+
+        // 赤・緑・青・輝度・アルファの順に 256 個ずつ
+        var<workgroup> local_bins: array<atomic<u32>, 1280>;
+
+        fn to_srgb(linear: f32) -> f32 {
+            if linear <= 0.0031308 {
+                return linear * 12.92;
+            }
+            return 1.055 * pow(linear, 1.0 / 2.4) - 0.055;
+        }
+
+        fn to_bin(value: f32) -> u32 {
+            return u32(round(clamp(value, 0.0, 1.0) * 255.0));
+        }
+
+        @compute @workgroup_size(16, 16)
+        fn cs_main(
+            @builtin(global_invocation_id) id: vec3<u32>,
+            @builtin(local_invocation_index) local_index: u32,
+        ) {
+            for (var i = local_index; i < 1280u; i += 256u) {
+                atomicStore(&local_bins[i], 0u);
+            }
+            workgroupBarrier();
+
+            if id.x < region.size.x && id.y < region.size.y {
+                let texel = textureLoad(source, vec2<i32>(region.origin + id.xy), 0);
+                atomicAdd(&local_bins[1024u + to_bin(texel.a)], 1u);
+                if texel.a > 0.0 {
+                    let r = to_bin(to_srgb(texel.r));
+                    let g = to_bin(to_srgb(texel.g));
+                    let b = to_bin(to_srgb(texel.b));
+                    let luminance = (299u * r + 587u * g + 114u * b + 500u) / 1000u;
+                    atomicAdd(&local_bins[r], 1u);
+                    atomicAdd(&local_bins[256u + g], 1u);
+                    atomicAdd(&local_bins[512u + b], 1u);
+                    atomicAdd(&local_bins[768u + luminance], 1u);
+                }
+            }
+            workgroupBarrier();
+
+            for (var i = local_index; i < 1280u; i += 256u) {
+                let count = atomicLoad(&local_bins[i]);
+                if count > 0u {
+                    atomicAdd(&bins[i], count);
+                }
+            }
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn test_image() -> RgbaImage {
+        // 左半分が不透明な赤、右上が半透明の灰色、右下が透明
+        RgbaImage::from_fn(40, 20, |x, y| match (x < 20, y < 10) {
+            (true, _) => Rgba([255, 0, 0, 255]),
+            (false, true) => Rgba([128, 128, 128, 64]),
+            (false, false) => Rgba([0, 0, 0, 0]),
+        })
+    }
+
+    #[test]
+    fn test_from_image() {
+        let histogram = Histogram::from_image(&test_image(), None);
+        assert_eq!(histogram.pixel_count, 600);
+        assert_eq!((histogram.red[255], histogram.red[128], histogram.red[0]), (400, 200, 0));
+        assert_eq!(histogram.luminance[76], 400);
+        assert_eq!(histogram.luminance[128], 200);
+        assert_eq!((histogram.alpha[255], histogram.alpha[64], histogram.alpha[0]), (400, 200, 200));
+
+        let region = HistogramRegion { x: 30, y: 5, width: 100, height: 10 };
+        let histogram = Histogram::from_image(&test_image(), Some(region));
+        assert_eq!(histogram.pixel_count, 50);
+        assert_eq!(histogram.alpha[0], 50);
+        assert_eq!(HistogramRegion { x: 40, ..region }.clamped((40, 20)), None);
+    }
+
+    #[tokio::test]
+    async fn test_gpu_histogram_matches_cpu() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = crate::drawing_engine::DrawingEngine::new();
+        engine.initialize().await?;
+
+        let image = RgbaImage::from_fn(40, 20, |x, y| Rgba([(x * 6) as u8, (y * 12) as u8, 200, if x == 0 { 0 } else { 255 }]));
+        engine.create_layer_texture("layer", 40, 20)?;
+        engine.write_layer_image("layer", 0, 0, &image)?;
+
+        assert_eq!(engine.histogram("layer", None).await?, Histogram::from_image(&image, None));
+        let region = HistogramRegion { x: 3, y: 17, width: 50, height: 50 };
+        assert_eq!(engine.histogram("layer", Some(region)).await?, Histogram::from_image(&image, Some(region)));
+        Ok(())
+    }
+}
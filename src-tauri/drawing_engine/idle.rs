@@ -0,0 +1,166 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::time::{Duration, Instant};
+
+/// アイドルとみなすまでの時間の下限（ミリ秒）
+pub const MIN_IDLE_MS: u32 = 100;
+
+/// アイドルとみなすまでの時間の上限（ミリ秒）
+pub const MAX_IDLE_MS: u32 = 10 * 60 * 1000;
+
+/// 描画が途切れている間に行う優先度の低い作業（並びが実行する順番）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MaintenanceTask {
+    /// ナビゲーターの縮小画像を書き込まれた分だけ更新する
+    Thumbnail,
+    /// テクスチャプールの整理
+    PoolCleanup,
+    /// 未保存の変更があれば自動保存を要求する
+    Autosave,
+    /// ジャーナルの記録が溜まっていれば起点の記録に置き換える
+    JournalCompaction,
+}
+
+impl MaintenanceTask {
+    pub const ALL: [MaintenanceTask; 4] = [
+        MaintenanceTask::Thumbnail,
+        MaintenanceTask::PoolCleanup,
+        MaintenanceTask::Autosave,
+        MaintenanceTask::JournalCompaction,
+    ];
+}
+
+/// アイドル時のメンテナンスの設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MaintenanceSettings {
+    /// ストローク・コマンドがこの時間（ミリ秒）途切れたらアイドルとみなす
+    pub idle_ms: u32,
+    /// 行う作業（実行する順番は `MaintenanceTask::ALL` の並び）
+    pub tasks: Vec<MaintenanceTask>,
+}
+
+impl Default for MaintenanceSettings {
+    fn default() -> Self {
+        Self {
+            idle_ms: 2000,
+            tasks: MaintenanceTask::ALL.to_vec(),
+        }
+    }
+}
+
+impl MaintenanceSettings {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(MIN_IDLE_MS..=MAX_IDLE_MS).contains(&self.idle_ms) {
+            return Err(format!("アイドルとみなす時間は {}〜{}ms で指定してください: {}", MIN_IDLE_MS, MAX_IDLE_MS, self.idle_ms));
+        }
+        Ok(())
+    }
+
+    pub fn idle_threshold(&self) -> Duration {
+        Duration::from_millis(self.idle_ms as u64)
+    }
+}
+
+/// ストロークやコマンドが途切れたことを検出し、メンテナンスの作業を1つずつ割り当てる
+///
+/// 作業はアイドルに入るたびにそれぞれ1回だけ割り当て、1つ終えるごとに次を尋ねてもらう。
+/// 途中で描画が再開すると（`touch`）残りの作業は次のアイドルまで待つため、描画と競合しない。
+#[derive(Debug, Clone)]
+pub struct IdleScheduler {
+    started: Instant,
+    last_activity: Instant,
+    /// 今回のアイドルで済ませた作業
+    done: HashSet<MaintenanceTask>,
+    /// 作業を最後に実際に行った時刻（何もせずに済んだ場合は記録しない）
+    last_run: HashMap<MaintenanceTask, Instant>,
+}
+
+impl IdleScheduler {
+    pub fn new(now: Instant) -> Self {
+        Self {
+            started: now,
+            last_activity: now,
+            done: HashSet::new(),
+            last_run: HashMap::new(),
+        }
+    }
+
+    /// ストローク・コマンドが届いたことを記録する
+    pub fn touch(&mut self, now: Instant) {
+        self.last_activity = now;
+        self.done.clear();
+    }
+
+    /// 最後の活動からの経過時間
+    pub fn idle_for(&self, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_activity)
+    }
+
+    /// アイドルが `threshold` 以上続いていれば、今回まだ行っていない作業のうち最初のものを返す
+    pub fn next_task(&self, now: Instant, threshold: Duration, enabled: &[MaintenanceTask]) -> Option<MaintenanceTask> {
+        if self.idle_for(now) < threshold {
+            return None;
+        }
+        MaintenanceTask::ALL.into_iter()
+            .find(|task| enabled.contains(task) && !self.done.contains(task))
+    }
+
+    /// `started` に始めた作業を終えたことを記録する（`ran` は実際に何かを行ったか）
+    ///
+    /// 作業の途中で活動があった場合は、次のアイドルでもう一度行う。
+    pub fn finish(&mut self, task: MaintenanceTask, ran: bool, started: Instant, now: Instant) {
+        if self.last_activity <= started {
+            self.done.insert(task);
+        }
+        if ran {
+            self.last_run.insert(task, now);
+        }
+    }
+
+    /// 作業を最後に行ってからの経過時間（まだ行っていなければ作成してからの時間）
+    pub fn since_last_run(&self, task: MaintenanceTask, now: Instant) -> Duration {
+        now.saturating_duration_since(self.last_run.get(&task).copied().unwrap_or(self.started))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tasks_run_once_per_idle_period() {
+        let start = Instant::now();
+        let at = |ms: u64| start + Duration::from_millis(ms);
+        let threshold = Duration::from_millis(1000);
+        let enabled = [MaintenanceTask::JournalCompaction, MaintenanceTask::Thumbnail];
+        let mut scheduler = IdleScheduler::new(start);
+
+        assert_eq!(scheduler.next_task(at(999), threshold, &enabled), None);
+        assert_eq!(scheduler.next_task(at(1000), threshold, &enabled), Some(MaintenanceTask::Thumbnail));
+        scheduler.finish(MaintenanceTask::Thumbnail, true, at(1000), at(1000));
+        assert_eq!(scheduler.next_task(at(1100), threshold, &enabled), Some(MaintenanceTask::JournalCompaction));
+        scheduler.finish(MaintenanceTask::JournalCompaction, false, at(1100), at(1100));
+        assert_eq!(scheduler.next_task(at(5000), threshold, &enabled), None);
+
+        // 描画が再開すると次のアイドルでまた行う
+        scheduler.touch(at(6000));
+        assert_eq!(scheduler.next_task(at(6500), threshold, &enabled), None);
+        assert_eq!(scheduler.next_task(at(7000), threshold, &enabled), Some(MaintenanceTask::Thumbnail));
+
+        assert_eq!(scheduler.since_last_run(MaintenanceTask::Thumbnail, at(7000)), Duration::from_millis(6000));
+
+        // 作業中に描画が再開した作業は終わっていないものとして扱う
+        scheduler.touch(at(7200));
+        scheduler.finish(MaintenanceTask::Thumbnail, true, at(7000), at(7300));
+        assert_eq!(scheduler.next_task(at(8200), threshold, &enabled), Some(MaintenanceTask::Thumbnail));
+        assert_eq!(scheduler.since_last_run(MaintenanceTask::JournalCompaction, at(8200)), Duration::from_millis(8200));
+    }
+
+    #[test]
+    fn test_settings_validation() {
+        assert!(MaintenanceSettings::default().validate().is_ok());
+        assert!(MaintenanceSettings { idle_ms: 10, ..MaintenanceSettings::default() }.validate().is_err());
+    }
+}
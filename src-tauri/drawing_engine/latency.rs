@@ -0,0 +1,206 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, VecDeque};
+use crate::formats::blend_over;
+
+/// 記録しておく最近のストロークの数
+pub const MAX_LATENCY_STROKES: usize = 32;
+
+/// 遅延グラフで「十分速い」「遅い」とみなす境目（ミリ秒。60Hz と 30Hz の1フレーム）
+pub(super) const GRAPH_THRESHOLDS_MS: (f64, f64) = (16.7, 33.3);
+/// 遅延グラフの棒の幅と、高さの上限（表示上のピクセル。1ミリ秒が1ピクセル）
+const GRAPH_BAR_WIDTH: u32 = 3;
+const GRAPH_MAX_HEIGHT: u32 = 64;
+
+/// 表示側の時計で測った、点の入力とコマンド呼び出しの時刻（`performance.now()` のミリ秒）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct InputTiming {
+    /// 追加する点のうち最も古いイベントの時刻
+    pub event_time_ms: f64,
+    /// コマンドを呼び出した時刻
+    pub sent_time_ms: f64,
+}
+
+/// 遅延の平均と最大
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct LatencyStat {
+    pub count: usize,
+    pub mean_ms: f64,
+    pub max_ms: f64,
+}
+
+impl LatencyStat {
+    fn add(&mut self, ms: f64) {
+        self.count += 1;
+        self.mean_ms += (ms - self.mean_ms) / self.count as f64;
+        self.max_ms = self.max_ms.max(ms);
+    }
+}
+
+/// 1本のストロークの遅延
+///
+/// `input` はイベントからコマンド呼び出しまで（表示側の待ち）、`render` はコマンドを受け取ってから
+/// 描画命令を送るまで、`total` はイベントから画面に表示されるまで（表示側が報告したものだけ）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeLatency {
+    pub layer_id: String,
+    pub input: LatencyStat,
+    pub render: LatencyStat,
+    pub total: LatencyStat,
+}
+
+impl StrokeLatency {
+    fn new(layer_id: &str) -> Self {
+        Self {
+            layer_id: layer_id.to_string(),
+            input: LatencyStat::default(),
+            render: LatencyStat::default(),
+            total: LatencyStat::default(),
+        }
+    }
+
+    /// グラフに使う遅延（表示までの遅延がなければ入力と描画の合計）
+    pub fn representative_ms(&self) -> f64 {
+        if self.total.count > 0 {
+            self.total.mean_ms
+        } else {
+            self.input.mean_ms + self.render.mean_ms
+        }
+    }
+}
+
+/// ストロークごとの入力 → 描画 → 表示の遅延を集計する
+#[derive(Debug, Clone, Default)]
+pub struct LatencyTracker {
+    active: HashMap<String, StrokeLatency>,
+    completed: VecDeque<StrokeLatency>,
+}
+
+impl LatencyTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 描画中のストロークに点を追加したときの遅延を記録
+    pub fn record_batch(&mut self, layer_id: &str, timing: InputTiming, render_ms: f64) {
+        let stroke = self.active.entry(layer_id.to_string()).or_insert_with(|| StrokeLatency::new(layer_id));
+        stroke.input.add((timing.sent_time_ms - timing.event_time_ms).max(0.0));
+        stroke.render.add(render_ms);
+    }
+
+    /// 表示側が画面に表示したときの、イベントからの遅延を記録
+    ///
+    /// ストロークの確定後に届いた報告は、そのレイヤーの直前のストロークに加える。
+    pub fn record_present(&mut self, layer_id: &str, total_ms: f64) {
+        let stroke = match self.active.get_mut(layer_id) {
+            Some(stroke) => Some(stroke),
+            None => self.completed.back_mut().filter(|stroke| stroke.layer_id == layer_id),
+        };
+        if let Some(stroke) = stroke {
+            stroke.total.add(total_ms.max(0.0));
+        }
+    }
+
+    /// ストロークの終了（確定・取り消し）で集計を締める
+    pub fn finish_stroke(&mut self, layer_id: &str) {
+        let Some(stroke) = self.active.remove(layer_id) else { return };
+        if stroke.input.count == 0 {
+            return;
+        }
+        if self.completed.len() == MAX_LATENCY_STROKES {
+            self.completed.pop_front();
+        }
+        self.completed.push_back(stroke);
+    }
+
+    /// 最近のストロークの遅延（古い順）
+    pub fn recent(&self) -> Vec<StrokeLatency> {
+        self.completed.iter().cloned().collect()
+    }
+}
+
+/// 最近のストロークの遅延を棒グラフとして表示画像の左上に重ねる（古い順に左から）
+///
+/// 棒は上端から `GRAPH_MAX_HEIGHT` の位置を底に上へ伸び、高さは1ミリ秒が1ピクセル。
+/// 1フレーム（60Hz）以内は緑、2フレーム以内は黄、それより遅いと赤。
+pub fn render_latency_graph(view: &mut RgbaImage, strokes: &[StrokeLatency]) {
+    for (index, stroke) in strokes.iter().enumerate() {
+        let ms = stroke.representative_ms();
+        let color = if ms <= GRAPH_THRESHOLDS_MS.0 {
+            Rgba([0, 200, 0, 192])
+        } else if ms <= GRAPH_THRESHOLDS_MS.1 {
+            Rgba([230, 200, 0, 192])
+        } else {
+            Rgba([230, 0, 0, 192])
+        };
+        let baseline = GRAPH_MAX_HEIGHT.min(view.height());
+        let height = (ms.ceil() as u32).clamp(1, baseline.max(1));
+        let left = index as u32 * GRAPH_BAR_WIDTH;
+        for x in left..(left + GRAPH_BAR_WIDTH - 1).min(view.width()) {
+            for y in baseline.saturating_sub(height)..baseline {
+                let pixel = view.get_pixel_mut(x, y);
+                *pixel = blend_over(*pixel, color);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn timing(event_time_ms: f64, sent_time_ms: f64) -> InputTiming {
+        InputTiming { event_time_ms, sent_time_ms }
+    }
+
+    #[test]
+    fn test_track_stroke_latency() {
+        let mut tracker = LatencyTracker::new();
+        tracker.record_batch("a", timing(100.0, 104.0), 2.0);
+        tracker.record_batch("a", timing(110.0, 112.0), 4.0);
+        tracker.record_present("a", 20.0);
+        // 描画中でないレイヤーの報告は捨てる
+        tracker.record_present("b", 50.0);
+        assert!(tracker.recent().is_empty());
+
+        tracker.finish_stroke("a");
+        tracker.record_present("a", 30.0);
+        let recent = tracker.recent();
+        assert_eq!(recent.len(), 1);
+        assert_eq!(recent[0].input, LatencyStat { count: 2, mean_ms: 3.0, max_ms: 4.0 });
+        assert_eq!(recent[0].render, LatencyStat { count: 2, mean_ms: 3.0, max_ms: 4.0 });
+        assert_eq!(recent[0].total, LatencyStat { count: 2, mean_ms: 25.0, max_ms: 30.0 });
+
+        // 点のないストロークは記録せず、古いストロークから捨てる
+        tracker.finish_stroke("b");
+        for _ in 0..MAX_LATENCY_STROKES {
+            tracker.record_batch("c", timing(0.0, 1.0), 1.0);
+            tracker.finish_stroke("c");
+        }
+        assert_eq!(tracker.recent().len(), MAX_LATENCY_STROKES);
+        assert!(tracker.recent().iter().all(|stroke| stroke.layer_id == "c"));
+    }
+
+    #[test]
+    fn test_latency_graph_colors() {
+        let mut tracker = LatencyTracker::new();
+        for (layer, render_ms) in [("fast", 5.0), ("slow", 50.0)] {
+            tracker.record_batch(layer, timing(0.0, 0.0), render_ms);
+            tracker.finish_stroke(layer);
+        }
+        let mut view = RgbaImage::from_pixel(10, 80, Rgba([255, 255, 255, 255]));
+        render_latency_graph(&mut view, &tracker.recent());
+
+        // 速いストロークは短い緑、遅いストロークは長い赤（底は上端から 64 ピクセル）
+        let white = Rgba([255, 255, 255, 255]);
+        let fast = view.get_pixel(0, 63);
+        assert!(fast[1] > fast[0] && fast[1] > fast[2]);
+        assert_eq!(view.get_pixel(0, 58), &white);
+        let slow = view.get_pixel(3, 14);
+        assert!(slow[0] > slow[1] && slow[0] > slow[2]);
+        assert_eq!(view.get_pixel(3, 13), &white);
+        assert_eq!(view.get_pixel(3, 64), &white);
+        // 棒の間は1ピクセル空ける
+        assert_eq!(view.get_pixel(2, 63), &white);
+    }
+}
@@ -0,0 +1,115 @@
+use serde::Serialize;
+use wgpu::{Adapter, Limits};
+
+/// デバイスの上限を取得する前（エンジン初期化前）に使うキャンバスの最大辺
+pub const DEFAULT_MAX_CANVAS_DIMENSION: u32 = 4096;
+
+/// キャンバス・レイヤーのサイズ上限
+///
+/// デバイスの上限と、ユーザーが設定した上限（省略可）の小さい方を使う。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CanvasLimits {
+    /// デバイスで作成・読み出しできる最大辺
+    pub device_max: u32,
+    /// ユーザーが設定した最大辺
+    pub configured_max: Option<u32>,
+}
+
+impl CanvasLimits {
+    /// デバイスの上限から作成
+    ///
+    /// テクスチャの最大辺に加え、レイヤー全体を読み出すステージングバッファが
+    /// `max_buffer_size` に収まる大きさに制限する。
+    pub fn from_device_limits(limits: &Limits) -> Self {
+        let readback_max = ((limits.max_buffer_size / 4) as f64).sqrt() as u32;
+        let readback_max = readback_max / wgpu::COPY_BYTES_PER_ROW_ALIGNMENT * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        Self {
+            device_max: limits.max_texture_dimension_2d.min(readback_max),
+            configured_max: None,
+        }
+    }
+
+    /// 現在有効な最大辺
+    pub fn max_dimension(&self) -> u32 {
+        match self.configured_max {
+            Some(configured) => configured.min(self.device_max),
+            None => self.device_max,
+        }
+    }
+
+    /// 幅・高さが上限内か
+    pub fn allows(&self, width: u32, height: u32) -> bool {
+        let max = self.max_dimension();
+        width > 0 && height > 0 && width <= max && height <= max
+    }
+}
+
+impl Default for CanvasLimits {
+    fn default() -> Self {
+        Self {
+            device_max: DEFAULT_MAX_CANVAS_DIMENSION,
+            configured_max: None,
+        }
+    }
+}
+
+/// フロントエンドに公開するデバイス情報
+#[derive(Debug, Clone, Serialize)]
+pub struct DeviceCapabilities {
+    pub adapter_name: String,
+    pub backend: String,
+    pub device_type: String,
+    /// デバイスが扱えるテクスチャの最大辺
+    pub max_texture_dimension_2d: u32,
+    pub max_buffer_size: u64,
+    /// キャンバス・レイヤーに使える最大辺（設定値とデバイス上限の小さい方）
+    pub max_canvas_dimension: u32,
+    /// ユーザーが設定した最大辺（None はデバイス上限まで）
+    pub configured_max_canvas_dimension: Option<u32>,
+}
+
+impl DeviceCapabilities {
+    pub fn new(adapter: &Adapter, limits: &Limits, canvas_limits: &CanvasLimits) -> Self {
+        let info = adapter.get_info();
+        Self {
+            adapter_name: info.name,
+            backend: format!("{:?}", info.backend),
+            device_type: format!("{:?}", info.device_type),
+            max_texture_dimension_2d: limits.max_texture_dimension_2d,
+            max_buffer_size: limits.max_buffer_size,
+            max_canvas_dimension: canvas_limits.max_dimension(),
+            configured_max_canvas_dimension: canvas_limits.configured_max,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_limits_from_device() {
+        // 既定の上限（8192, 256MiB）ではテクスチャの最大辺がそのまま使える
+        let limits = CanvasLimits::from_device_limits(&Limits::default());
+        assert_eq!(limits.max_dimension(), 8192);
+
+        // バッファが小さいデバイスでは読み出せる大きさに制限される
+        let small_buffer = Limits { max_buffer_size: 64 * 1024 * 1024, ..Limits::default() };
+        assert_eq!(CanvasLimits::from_device_limits(&small_buffer).max_dimension(), 4096);
+
+        let downlevel = CanvasLimits::from_device_limits(&Limits::downlevel_webgl2_defaults());
+        assert_eq!(downlevel.max_dimension(), 2048);
+    }
+
+    #[test]
+    fn test_configured_max() {
+        let mut limits = CanvasLimits { device_max: 8192, configured_max: Some(3000) };
+        assert!(limits.allows(3000, 2000));
+        assert!(!limits.allows(3001, 2000));
+        assert!(!limits.allows(0, 10));
+
+        // 設定値はデバイス上限を超えられない
+        limits.configured_max = Some(20000);
+        assert_eq!(limits.max_dimension(), 8192);
+    }
+}
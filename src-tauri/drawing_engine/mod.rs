@@ -1,16 +1,121 @@
 
 use wgpu::*;
-use log::{info, error, debug};
+use log::{info, error, debug, warn};
+use std::collections::HashMap;
 
 pub mod renderer;
 pub mod texture;
 pub mod pipeline;
+pub mod pipeline_cache;
+pub mod msaa;
+pub mod coverage;
+pub mod color_space;
+pub mod render_settings;
+pub mod shader_reload;
+pub mod transform;
+pub mod bounds;
+pub mod histogram;
+pub mod resample;
+pub mod stamp;
+pub mod submit;
+pub mod limits;
+pub mod adapter;
+pub mod pixel;
+pub mod tiles;
+pub mod overlay;
+pub mod compare;
+pub mod navigator;
+pub mod view;
+pub mod latency;
+pub mod diagnostics;
+pub mod resize;
+pub mod defragment;
+pub mod idle;
+pub mod perf_overlay;
+pub mod stroke_input;
 
 #[cfg(test)]
 mod pipeline_test;
+#[cfg(test)]
+mod golden;
+#[cfg(test)]
+mod golden_test;
 pub use renderer::{OffscreenRenderer, OffscreenRenderError};
-pub use texture::{TextureManager, TextureError, TextureSpec, ManagedTexture};
-pub use pipeline::{BasicDrawPipeline, PipelineError, DrawStroke, Vertex2D};
+pub use texture::{TextureManager, TextureError, TextureSpec, ManagedTexture, PoolDefragment};
+pub use pipeline::{BasicDrawPipeline, PipelineError, DrawStroke, DrawUniforms, StrokeMesh, StrokeTessellator, Vertex2D, DEFAULT_VERTEX_LIMIT, MIN_VERTEX_LIMIT, MAX_VERTEX_LIMIT};
+pub use transform::{CanvasTransform, CanvasTransformPipeline};
+pub use bounds::{ContentBounds, ContentBoundsPipeline, ContentCoverage};
+pub use histogram::{Histogram, HistogramPipeline, HistogramRegion};
+pub use resample::{ResampleFilter, ResamplePipeline};
+pub use stamp::{Stamp, StampShape, StampRasterizer, StampComputePipeline};
+pub use submit::{submit_parallel, SubmissionFence};
+pub use pipeline_cache::PipelineCacheStore;
+pub use msaa::MsaaMode;
+pub use coverage::{CoverageBlend, CoverageBuffer};
+pub use color_space::BlendSpace;
+pub use render_settings::{CanvasRenderSettings, CanvasRenderStatus};
+pub use shader_reload::{ShaderId, ShaderOverrides, ShaderWatcher};
+pub use limits::{CanvasLimits, DeviceCapabilities, DEFAULT_MAX_CANVAS_DIMENSION};
+pub use adapter::{GpuAdapterId, GpuAdapterInfo, GpuPowerPreference, GpuPreference};
+pub use pixel::MAX_PIXEL_ZOOM;
+pub use overlay::OverlaySettings;
+pub use compare::{compose_comparison, difference_heatmap, CompareLayout, DifferenceStats};
+pub use tiles::{InfiniteCanvas, TileCoord, TileGenerations, TileStore, Viewport, TILE_SIZE};
+pub use view::ViewTransform;
+pub use navigator::{Navigator, MAX_NAVIGATOR_SIZE};
+pub use latency::{InputTiming, LatencyStat, LatencyTracker, StrokeLatency};
+pub use diagnostics::{DiagnosticsRecorder, FrameTimeStats, GpuDiagnostics, GpuErrorLog, GpuErrorRecord};
+pub use resize::{PendingResize, ResizeDebouncer, DEFAULT_RESIZE_QUIET_PERIOD, MAX_RESIZE_QUIET_PERIOD};
+pub use defragment::{DefragmentOptions, DefragmentReport};
+pub use idle::{IdleScheduler, MaintenanceSettings, MaintenanceTask};
+pub use perf_overlay::{PerfOverlay, PERF_OVERLAY_FRAMES};
+pub use stroke_input::{sanitize_stroke, sanitize_style, SanitizeReport, SanitizedStroke, StrokeInputError, StrokeInputPoint, MAX_STROKE_WIDTH};
+
+/// 描画中のストローク（点を追加するたびに、ストローク全体を合成し直す）
+struct LayerStroke {
+    coverage: CoverageBuffer,
+    target: StrokeTarget,
+}
+
+/// 描画中のストロークの合成先
+enum StrokeTarget {
+    /// レイヤーに直接描く（描き始める前のレイヤーの内容へ合成し直す）
+    Layer { snapshot: Texture },
+    /// 消しゴム（確定までレイヤーは変えず、レイヤーから消した結果をプレビューに合成する）
+    Preview { preview: ManagedTexture, strength: f32 },
+}
+
+impl LayerStroke {
+    /// 被覆率バッファと保存したレイヤーの内容のメモリ使用量（バイト）
+    fn memory_size(&self) -> u64 {
+        let texture = match &self.target {
+            StrokeTarget::Layer { snapshot } => snapshot,
+            StrokeTarget::Preview { preview, .. } => &preview.texture,
+        };
+        self.coverage.memory_size() + texture.width() as u64 * texture.height() as u64 * 4
+    }
+}
+
+/// 頂点バッファの上限を超えるメッシュを分割する
+fn split_mesh(mesh: &StrokeMesh, vertex_limit: usize) -> std::borrow::Cow<'_, [StrokeMesh]> {
+    if mesh.vertices.len() > vertex_limit {
+        let chunks = mesh.chunks(vertex_limit);
+        debug!("[DrawingEngine] {} 頂点を {} 回に分けて描画", mesh.vertices.len(), chunks.len());
+        chunks.into()
+    } else {
+        std::slice::from_ref(mesh).into()
+    }
+}
+
+/// シェーダーの差し替えで作り直したパイプライン
+enum ReloadedPipeline {
+    Draw(Box<BasicDrawPipeline>),
+    Transform(CanvasTransformPipeline),
+    Bounds(ContentBoundsPipeline),
+    Stamp(StampComputePipeline),
+    Histogram(HistogramPipeline),
+    Resample(ResamplePipeline),
+}
 
 pub struct DrawingEngine {
     instance: Instance,
@@ -20,6 +125,35 @@ pub struct DrawingEngine {
     pub queue: Option<Queue>,
     pub texture_manager: Option<TextureManager>,
     pub draw_pipeline: Option<BasicDrawPipeline>,
+    pub transform_pipeline: Option<CanvasTransformPipeline>,
+    pub bounds_pipeline: Option<ContentBoundsPipeline>,
+    pub stamp_pipeline: Option<StampComputePipeline>,
+    pub histogram_pipeline: Option<HistogramPipeline>,
+    pub resample_pipeline: Option<ResamplePipeline>,
+    /// キャンバス・レイヤーのサイズ上限（初期化時にデバイスの上限から決まる）
+    canvas_limits: CanvasLimits,
+    /// 初期化時に使うアダプターの設定
+    gpu_preference: GpuPreference,
+    /// 自動選択でソフトウェア実装のアダプター（フォールバックアダプター）だけを使う
+    force_fallback_adapter: bool,
+    /// 無限キャンバスモード（None は通常の固定サイズのキャンバス）
+    infinite_canvas: Option<InfiniteCanvas>,
+    /// ストローク描画の頂点バッファの上限
+    stroke_vertex_limit: usize,
+    /// コンパイル済みパイプラインのキャッシュを保存するディレクトリ（None なら保存しない）
+    pipeline_cache_dir: Option<std::path::PathBuf>,
+    /// 初期化時に作成したパイプラインキャッシュと保存先
+    pipeline_cache: Option<(PipelineCache, PipelineCacheStore)>,
+    /// 埋め込みの代わりに使うシェーダー（開発時の差し替え用。再初期化後も引き継ぐ）
+    shader_overrides: ShaderOverrides,
+    /// 描画品質の設定（マルチサンプリングはデバイスが対応するサンプル数に合わせて使う）
+    render_settings: CanvasRenderSettings,
+    /// 描画中のストローク（レイヤーID -> ストローク）
+    layer_strokes: HashMap<String, LayerStroke>,
+    /// レイヤーのタイルごとの更新世代（表示側が変わったタイルだけを読み直すため）
+    tile_generations: TileGenerations,
+    /// 最近のGPUエラー（診断レポート用。再初期化後も引き継ぐ）
+    gpu_errors: GpuErrorLog,
 }
 
 impl DrawingEngine {
@@ -42,6 +176,23 @@ impl DrawingEngine {
             queue: None,
             texture_manager: None,
             draw_pipeline: None,
+            transform_pipeline: None,
+            bounds_pipeline: None,
+            stamp_pipeline: None,
+            histogram_pipeline: None,
+            resample_pipeline: None,
+            canvas_limits: CanvasLimits::default(),
+            gpu_preference: GpuPreference::default(),
+            force_fallback_adapter: false,
+            infinite_canvas: None,
+            stroke_vertex_limit: DEFAULT_VERTEX_LIMIT,
+            pipeline_cache_dir: None,
+            pipeline_cache: None,
+            shader_overrides: ShaderOverrides::default(),
+            render_settings: CanvasRenderSettings::default(),
+            layer_strokes: HashMap::new(),
+            tile_generations: TileGenerations::new(),
+            gpu_errors: GpuErrorLog::new(),
         };
         
         info!("[DrawingEngine] DrawingEngine インスタンス作成完了");
@@ -52,26 +203,42 @@ impl DrawingEngine {
         info!("[DrawingEngine] 初期化開始");
         
         debug!("[DrawingEngine] 利用可能なアダプターを検索中...");
-        let adapter = self
-            .instance
-            .request_adapter(&RequestAdapterOptions {
-                power_preference: PowerPreference::HighPerformance,
-                compatible_surface: self.surface.as_ref(),
-                force_fallback_adapter: false,
-            })
-            .await
-            .map_err(|e| format!("Failed to find an appropriate adapter: {:?}", e))?;
+        let adapters = adapter::enumerate_adapters(&self.instance);
+        for info in adapter::describe_adapters(&adapters, None) {
+            debug!("[DrawingEngine] アダプター候補 {}: {} ({}, {})",
+                   info.index, info.id.name, info.id.backend, info.device_type);
+        }
+        let adapter = match adapter::take_preferred_adapter(adapters, &self.gpu_preference) {
+            Some(adapter) => {
+                info!("[DrawingEngine] 設定されたアダプターを使用");
+                adapter
+            }
+            None => {
+                if let Some(wanted) = &self.gpu_preference.adapter {
+                    warn!("[DrawingEngine] 設定されたアダプターが見つかりません: {} - 自動選択します", wanted.name);
+                }
+                self.instance
+                    .request_adapter(&RequestAdapterOptions {
+                        power_preference: self.gpu_preference.power_preference.into(),
+                        compatible_surface: self.surface.as_ref(),
+                        force_fallback_adapter: self.force_fallback_adapter,
+                    })
+                    .await
+                    .map_err(|e| format!("Failed to find an appropriate adapter: {:?}", e))?
+            }
+        };
             
         info!("[DrawingEngine] アダプター検索成功");
         debug!("[DrawingEngine] アダプター情報: {:?}", adapter.get_info());
 
         debug!("[DrawingEngine] デバイスとキューをリクエスト中...");
+        // テクスチャの最大サイズはアダプターの上限まで引き上げる
         let device_result = adapter
             .request_device(
                 &DeviceDescriptor {
                     label: Some("Kinegraph Drawing Device"),
-                    required_features: Features::empty(),
-                    required_limits: Limits::default(),
+                    required_features: pipeline_cache::required_features(&adapter) | msaa::required_features(&adapter),
+                    required_limits: Limits::default().using_resolution(adapter.limits()),
                     ..Default::default()
                 },
             )
@@ -89,32 +256,106 @@ impl DrawingEngine {
             }
         };
 
+        // エラースコープの外で起きた検証エラーはパニックさせずに記録する（診断レポート用）
+        let gpu_errors = self.gpu_errors.clone();
+        device.on_uncaptured_error(Box::new(move |e| {
+            error!("[DrawingEngine] GPUエラー: {}", e);
+            gpu_errors.push("uncaptured", e.to_string());
+        }));
+
         debug!("[DrawingEngine] DrawingEngine 状態を更新中...");
         self.adapter = Some(adapter);
+        self.canvas_limits = CanvasLimits {
+            configured_max: self.canvas_limits.configured_max,
+            ..CanvasLimits::from_device_limits(&device.limits())
+        };
+        info!("[DrawingEngine] キャンバスの最大辺: {} (デバイス上限 {})",
+              self.canvas_limits.max_dimension(), self.canvas_limits.device_max);
         
+        // 前回保存したパイプラインキャッシュがあれば、パイプラインのコンパイルに再利用する
+        let cache_store = self.pipeline_cache_dir.as_deref()
+            .zip(self.adapter.as_ref())
+            .and_then(|(dir, adapter)| PipelineCacheStore::for_adapter(dir, &adapter.get_info()));
+        let cache = cache_store.as_ref()
+            .and_then(|store| pipeline_cache::create_pipeline_cache(&device, store.load().as_deref()));
+        let pipeline_started = std::time::Instant::now();
+
         // 描画パイプラインを初期化（deviceを使用する前に）
         debug!("[DrawingEngine] BasicDrawPipeline 初期化中...");
-        let pipeline = BasicDrawPipeline::new(&device, TextureFormat::Rgba8UnormSrgb)
-            .map_err(|e| format!("描画パイプライン初期化失敗: {}", e))?;
+        let shaders = &self.shader_overrides;
+        let mut pipeline = BasicDrawPipeline::with_shaders(
+            &device,
+            TextureFormat::Rgba8UnormSrgb,
+            cache.as_ref(),
+            shaders.source(ShaderId::DrawVertex),
+            shaders.source(ShaderId::DrawFragment),
+        ).map_err(|e| format!("描画パイプライン初期化失敗: {}", e))?;
+        pipeline.set_vertex_limit(&device, self.stroke_vertex_limit);
+        let sample_count = self.negotiate_sample_count(&device);
+        pipeline.set_sample_count(&device, sample_count, cache.as_ref())
+            .and_then(|()| pipeline.set_blend_space(&device, self.effective_blend_space(), cache.as_ref()))
+            .map_err(|e| format!("描画品質の設定の適用失敗: {}", e))?;
         self.draw_pipeline = Some(pipeline);
+        let transform_pipeline = CanvasTransformPipeline::with_shader(&device, TextureFormat::Rgba8UnormSrgb, cache.as_ref(), shaders.source(ShaderId::CanvasTransform))
+            .map_err(|e| format!("変換パイプライン初期化失敗: {}", e))?;
+        self.transform_pipeline = Some(transform_pipeline);
+        let bounds_pipeline = ContentBoundsPipeline::with_shader(&device, cache.as_ref(), shaders.source(ShaderId::ContentBounds))
+            .map_err(|e| format!("範囲計算パイプライン初期化失敗: {}", e))?;
+        self.bounds_pipeline = Some(bounds_pipeline);
+        let stamp_pipeline = StampComputePipeline::with_shader(&device, cache.as_ref(), shaders.source(ShaderId::StampCompute))
+            .map_err(|e| format!("スタンプ合成パイプライン初期化失敗: {}", e))?;
+        self.stamp_pipeline = Some(stamp_pipeline);
+        let histogram_pipeline = HistogramPipeline::with_shader(&device, cache.as_ref(), shaders.source(ShaderId::Histogram))
+            .map_err(|e| format!("ヒストグラムパイプライン初期化失敗: {}", e))?;
+        self.histogram_pipeline = Some(histogram_pipeline);
+        let resample_pipeline = ResamplePipeline::with_shader(&device, cache.as_ref(), shaders.source(ShaderId::Resample))
+            .map_err(|e| format!("拡大・縮小パイプライン初期化失敗: {}", e))?;
+        self.resample_pipeline = Some(resample_pipeline);
+        info!("[DrawingEngine] パイプライン作成完了: {:?} (キャッシュ: {})",
+              pipeline_started.elapsed(), if cache.is_some() { "使用" } else { "なし" });
+        self.pipeline_cache = cache.zip(cache_store);
+        self.save_pipeline_cache();
         
-        // deviceとqueueを保存
+        // deviceとqueueを保存（前のデバイスで描画中だったストロークは破棄する）
+        self.layer_strokes.clear();
         self.device = Some(device);
         self.queue = Some(queue);
         
         // TextureManagerを初期化
         debug!("[DrawingEngine] TextureManager 初期化中...");
-        self.texture_manager = Some(TextureManager::new());
+        let mut texture_manager = TextureManager::new();
+        texture_manager.set_max_dimension(self.canvas_limits.max_dimension());
+        texture_manager.set_encoded_views(self.srgb_blend_supported());
+        self.texture_manager = Some(texture_manager);
         
         info!("[DrawingEngine] 初期化正常完了");
         Ok(())
     }
 
+    /// デバイスを作り直す（パニックなどで状態が壊れたときの復旧用）
+    ///
+    /// レイヤーのテクスチャと描画中のストロークは失われるため、呼び出し側で操作履歴から作り直す。
+    /// アダプターの設定・頂点バッファの上限・描画品質・差し替えたシェーダーは引き継ぐ。
+    pub async fn reinitialize(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        warn!("[DrawingEngine] デバイスを作り直します");
+        self.texture_manager = None;
+        self.draw_pipeline = None;
+        self.transform_pipeline = None;
+        self.bounds_pipeline = None;
+        self.stamp_pipeline = None;
+        self.histogram_pipeline = None;
+        self.resample_pipeline = None;
+        self.queue = None;
+        self.device = None;
+        self.adapter = None;
+        self.initialize().await
+    }
+
     /// オフスクリーンレンダラーを作成
     pub fn create_offscreen_renderer(&self, width: u32, height: u32) -> Result<OffscreenRenderer, OffscreenRenderError> {
         debug!("[DrawingEngine] オフスクリーンレンダラー作成開始: {}x{}", width, height);
         
-        let mut renderer = OffscreenRenderer::new(width, height)?;
+        let mut renderer = OffscreenRenderer::with_max_dimension(width, height, self.canvas_limits.max_dimension())?;
         
         if let Some(device) = &self.device {
             renderer.initialize(device)?;
@@ -140,6 +381,231 @@ impl DrawingEngine {
         Ok(result)
     }
 
+    /// パイプラインキャッシュの保存先を設定（次回の初期化から有効）
+    pub fn set_pipeline_cache_dir(&mut self, dir: Option<std::path::PathBuf>) {
+        self.pipeline_cache_dir = dir;
+    }
+
+    /// パイプラインキャッシュの内容をディスクに保存（失敗しても描画には影響しないため警告に留める）
+    pub fn save_pipeline_cache(&self) {
+        let Some((cache, store)) = &self.pipeline_cache else {
+            return;
+        };
+        match cache.get_data() {
+            Some(data) => {
+                if let Err(e) = store.save(&data) {
+                    warn!("[DrawingEngine] パイプラインキャッシュを保存できません: {}", e);
+                }
+            }
+            None => debug!("[DrawingEngine] 保存するパイプラインキャッシュのデータがありません"),
+        }
+    }
+
+    /// シェーダーを差し替えて、それを使うパイプラインを作り直す（開発時のホットリロード用）
+    ///
+    /// コンパイル・検証に失敗した場合は現在のパイプラインをそのまま使い続ける。
+    pub async fn reload_shader(&mut self, id: ShaderId, source: String) -> Result<(), PipelineError> {
+        let device = self.device.as_ref().ok_or(PipelineError::DeviceNotAvailable)?;
+        let mut shaders = self.shader_overrides.clone();
+        shaders.set(id, source);
+        let cache = self.pipeline_cache.as_ref().map(|(cache, _)| cache);
+        let format = TextureFormat::Rgba8UnormSrgb;
+
+        // 不正な WGSL はパニックさせずにエラーとして受け取る
+        device.push_error_scope(ErrorFilter::Validation);
+        let created = match id {
+            ShaderId::DrawVertex | ShaderId::DrawFragment => BasicDrawPipeline::with_shaders(
+                device, format, cache, shaders.source(ShaderId::DrawVertex), shaders.source(ShaderId::DrawFragment),
+            ).and_then(|mut pipeline| {
+                pipeline.set_vertex_limit(device, self.stroke_vertex_limit);
+                pipeline.set_sample_count(device, self.negotiate_sample_count(device), cache)?;
+                pipeline.set_blend_space(device, self.effective_blend_space(), cache)?;
+                Ok(ReloadedPipeline::Draw(Box::new(pipeline)))
+            }),
+            ShaderId::CanvasTransform => CanvasTransformPipeline::with_shader(device, format, cache, shaders.source(id))
+                .map(ReloadedPipeline::Transform),
+            ShaderId::ContentBounds => ContentBoundsPipeline::with_shader(device, cache, shaders.source(id))
+                .map(ReloadedPipeline::Bounds),
+            ShaderId::StampCompute => StampComputePipeline::with_shader(device, cache, shaders.source(id))
+                .map(ReloadedPipeline::Stamp),
+            ShaderId::Histogram => HistogramPipeline::with_shader(device, cache, shaders.source(id))
+                .map(ReloadedPipeline::Histogram),
+            ShaderId::Resample => ResamplePipeline::with_shader(device, cache, shaders.source(id))
+                .map(ReloadedPipeline::Resample),
+        };
+        if let Some(error) = device.pop_error_scope().await {
+            warn!("[DrawingEngine] シェーダー {:?} のコンパイルに失敗: {}", id, error);
+            self.gpu_errors.push(format!("reload_shader:{}", id.file_name()), error.to_string());
+            return Err(PipelineError::ShaderCompilationFailed(error.to_string()));
+        }
+
+        match created? {
+            ReloadedPipeline::Draw(pipeline) => self.draw_pipeline = Some(*pipeline),
+            ReloadedPipeline::Transform(pipeline) => self.transform_pipeline = Some(pipeline),
+            ReloadedPipeline::Bounds(pipeline) => self.bounds_pipeline = Some(pipeline),
+            ReloadedPipeline::Stamp(pipeline) => self.stamp_pipeline = Some(pipeline),
+            ReloadedPipeline::Histogram(pipeline) => self.histogram_pipeline = Some(pipeline),
+            ReloadedPipeline::Resample(pipeline) => self.resample_pipeline = Some(pipeline),
+        }
+        self.shader_overrides = shaders;
+        info!("[DrawingEngine] シェーダーを差し替えました: {:?}", id);
+        Ok(())
+    }
+
+    /// 初期化時に使うアダプターの設定（初期化後に変更しても現在のデバイスは変わらない）
+    pub fn set_gpu_preference(&mut self, preference: GpuPreference) {
+        self.gpu_preference = preference;
+    }
+
+    /// 自動選択でフォールバックアダプターを使うか（環境によらず同じ描画結果が要る基準画像のテスト用）
+    pub fn set_force_fallback_adapter(&mut self, force: bool) {
+        self.force_fallback_adapter = force;
+    }
+
+    /// 利用可能なアダプターの一覧を取得
+    pub fn list_adapters(&self) -> Vec<GpuAdapterInfo> {
+        let in_use = self.adapter.as_ref().map(|adapter| GpuAdapterId::from_info(&adapter.get_info()));
+        adapter::describe_adapters(&adapter::enumerate_adapters(&self.instance), in_use.as_ref())
+    }
+
+    /// キャンバス・レイヤーのサイズ上限を取得
+    pub fn canvas_limits(&self) -> CanvasLimits {
+        self.canvas_limits
+    }
+
+    /// キャンバスの最大辺を設定（None でデバイスの上限まで）
+    ///
+    /// 既存のレイヤーには影響せず、以降のレイヤー・レンダラーの作成に適用される。
+    pub fn set_max_canvas_dimension(&mut self, max_dimension: Option<u32>) {
+        self.canvas_limits.configured_max = max_dimension;
+        let max_dimension = self.canvas_limits.max_dimension();
+        if let Some(texture_manager) = self.texture_manager.as_mut() {
+            texture_manager.set_max_dimension(max_dimension);
+        }
+        info!("[DrawingEngine] キャンバスの最大辺を変更: {}", max_dimension);
+    }
+
+    /// デバイス情報を取得（初期化前は None）
+    pub fn device_capabilities(&self) -> Option<DeviceCapabilities> {
+        let adapter = self.adapter.as_ref()?;
+        let device = self.device.as_ref()?;
+        Some(DeviceCapabilities::new(adapter, &device.limits(), &self.canvas_limits))
+    }
+
+    /// 診断レポート用の使用中のGPUの情報（初期化前は None）
+    pub fn gpu_diagnostics(&self) -> Option<GpuDiagnostics> {
+        let adapter = self.adapter.as_ref()?;
+        let limits = self.device.as_ref()?.limits();
+        let info = adapter.get_info();
+        Some(GpuDiagnostics {
+            capabilities: DeviceCapabilities::new(adapter, &limits, &self.canvas_limits),
+            driver: info.driver,
+            driver_info: info.driver_info,
+            limits: (&limits).into(),
+        })
+    }
+
+    /// 最近のGPUエラー
+    pub fn gpu_errors(&self) -> &GpuErrorLog {
+        &self.gpu_errors
+    }
+
+    /// ストローク描画のマルチサンプリングを設定し、実際に使うサンプル数を返す
+    ///
+    /// デバイスが対応していないサンプル数は対応する中で最も近い少ない数に下げる。
+    /// 初期化前に設定した場合は初期化時に適用する。
+    pub fn set_msaa_mode(&mut self, mode: MsaaMode) -> Result<u32, PipelineError> {
+        self.render_settings.msaa = mode;
+        let Some(device) = self.device.as_ref() else {
+            return Ok(1);
+        };
+        let sample_count = self.negotiate_sample_count(device);
+        let cache = self.pipeline_cache.as_ref().map(|(cache, _)| cache);
+        if let Some(pipeline) = self.draw_pipeline.as_mut() {
+            pipeline.set_sample_count(device, sample_count, cache)?;
+        }
+        info!("[DrawingEngine] マルチサンプリング: {:?} → {}x", mode, sample_count);
+        Ok(sample_count)
+    }
+
+    /// ストロークを合成する色空間を設定（初期化前に設定した場合は初期化時に適用する）
+    ///
+    /// sRGB での合成に対応していないデバイスで `Srgb` を設定するとエラーになる。
+    /// 保存されていた設定で初期化したときは、対応していなければ線形で合成する。
+    pub fn set_blend_space(&mut self, blend_space: BlendSpace) -> Result<(), PipelineError> {
+        if blend_space == BlendSpace::Srgb && self.adapter.is_some() && !self.srgb_blend_supported() {
+            return Err(PipelineError::PipelineCreationFailed("このデバイスは sRGB での合成に対応していません".to_string()));
+        }
+        self.render_settings.blend_space = blend_space;
+        let cache = self.pipeline_cache.as_ref().map(|(cache, _)| cache);
+        if let (Some(device), Some(pipeline)) = (self.device.as_ref(), self.draw_pipeline.as_mut()) {
+            pipeline.set_blend_space(device, blend_space, cache)?;
+        }
+        info!("[DrawingEngine] ストロークの合成: {:?}", blend_space);
+        Ok(())
+    }
+
+    /// 描画品質の設定と、実際に使っているサンプル数・デバイスで使えるサンプル数
+    pub fn render_status(&self) -> CanvasRenderStatus {
+        CanvasRenderStatus {
+            settings: self.render_settings,
+            sample_count: self.draw_pipeline.as_ref().map_or(1, |pipeline| pipeline.sample_count()),
+            supported_sample_counts: self.supported_sample_counts(),
+            blend_space: self.effective_blend_space(),
+            srgb_blend_supported: self.srgb_blend_supported(),
+        }
+    }
+
+    fn srgb_blend_supported(&self) -> bool {
+        self.adapter.as_ref().is_some_and(color_space::srgb_blend_supported)
+    }
+
+    /// 実際に使う合成の色空間（デバイスが sRGB での合成に対応していなければ線形）
+    fn effective_blend_space(&self) -> BlendSpace {
+        match self.render_settings.blend_space {
+            BlendSpace::Srgb if !self.srgb_blend_supported() => BlendSpace::Linear,
+            blend_space => blend_space,
+        }
+    }
+
+    fn supported_sample_counts(&self) -> Vec<u32> {
+        match (self.adapter.as_ref(), self.device.as_ref()) {
+            (Some(adapter), Some(device)) => Self::stroke_sample_counts(adapter, device),
+            _ => vec![1],
+        }
+    }
+
+    /// レイヤーと被覆率バッファの両方の形式で使えるサンプル数
+    fn stroke_sample_counts(adapter: &Adapter, device: &Device) -> Vec<u32> {
+        let coverage = msaa::supported_sample_counts(adapter, device, coverage::COVERAGE_FORMAT);
+        msaa::supported_sample_counts(adapter, device, TextureFormat::Rgba8UnormSrgb)
+            .into_iter()
+            .filter(|count| coverage.contains(count))
+            .collect()
+    }
+
+    fn negotiate_sample_count(&self, device: &Device) -> u32 {
+        let supported = self.adapter.as_ref()
+            .map(|adapter| Self::stroke_sample_counts(adapter, device))
+            .unwrap_or_default();
+        self.render_settings.msaa.negotiate(&supported)
+    }
+
+    /// ストローク描画の頂点バッファの上限を設定（超えるストロークは複数回に分けて描画する）
+    pub fn set_stroke_vertex_limit(&mut self, limit: usize) {
+        self.stroke_vertex_limit = limit;
+        if let (Some(device), Some(pipeline)) = (&self.device, self.draw_pipeline.as_mut()) {
+            pipeline.set_vertex_limit(device, limit);
+        }
+    }
+
+    /// 送信済みのGPUコマンドがすべて完了するまで待つ（計測用）
+    pub fn wait_idle(&self) {
+        if let Some(device) = &self.device {
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+        }
+    }
+
     /// TextureManagerの参照を取得
     pub fn texture_manager(&self) -> Option<&TextureManager> {
         self.texture_manager.as_ref()
@@ -160,9 +626,28 @@ impl DrawingEngine {
             .ok_or(TextureError::DeviceNotInitialized)?;
 
         texture_manager.create_layer_texture(device, layer_id, width, height)?;
+        self.mark_layer_written(layer_id);
         Ok(())
     }
 
+    /// レイヤーのタイルごとの更新世代
+    pub fn tile_generations(&self) -> &TileGenerations {
+        &self.tile_generations
+    }
+
+    /// レイヤー全体に書き込んだことを記録
+    fn mark_layer_written(&mut self, layer_id: &str) {
+        if let Some((width, height)) = self.layer_texture_size(layer_id) {
+            self.tile_generations.mark(layer_id, Viewport { x: 0, y: 0, width, height });
+        }
+    }
+
+    /// レイヤーの範囲に書き込んだことを記録
+    fn mark_layer_region(&mut self, layer_id: &str, bounds: ContentBounds) {
+        let region = Viewport { x: bounds.x as i32, y: bounds.y as i32, width: bounds.width, height: bounds.height };
+        self.tile_generations.mark(layer_id, region);
+    }
+
     /// レイヤーテクスチャのピクセルデータを取得
     pub async fn get_layer_texture_data(&self, layer_id: &str) -> Result<Vec<u8>, TextureError> {
         debug!("[DrawingEngine] レイヤーテクスチャデータ取得: {}", layer_id);
@@ -177,6 +662,62 @@ impl DrawingEngine {
         texture_manager.get_texture_data(device, queue, layer_id).await
     }
 
+    /// レイヤーを画像として取得（行パディングを除去したRGBA）
+    pub async fn get_layer_image(&self, layer_id: &str) -> Result<image::RgbaImage, TextureError> {
+        let mut images = self.get_layer_images(&[layer_id.to_string()]).await?;
+        Ok(images.remove(0))
+    }
+
+    /// 複数のレイヤーを画像としてまとめて取得（書き出し・合成用）
+    ///
+    /// 読み出しのコピーを1回の送信にまとめ、GPU の完了を待つのも1度だけにする。
+    pub async fn get_layer_images(&self, layer_ids: &[String]) -> Result<Vec<image::RgbaImage>, TextureError> {
+        self.read_layer_images(layer_ids, false).await
+    }
+
+    /// 表示用に複数のレイヤーを画像として取得（消しゴムで消している途中のレイヤーはプレビューを読む）
+    pub async fn get_layer_preview_images(&self, layer_ids: &[String]) -> Result<Vec<image::RgbaImage>, TextureError> {
+        self.read_layer_images(layer_ids, true).await
+    }
+
+    async fn read_layer_images(&self, layer_ids: &[String], with_previews: bool) -> Result<Vec<image::RgbaImage>, TextureError> {
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+
+        let textures = layer_ids.iter()
+            .map(|layer_id| {
+                let erasing = self.layer_strokes.get(layer_id)
+                    .filter(|_| with_previews)
+                    .and_then(|stroke| match &stroke.target {
+                        StrokeTarget::Preview { preview, .. } => Some(preview),
+                        StrokeTarget::Layer { .. } => None,
+                    });
+                erasing.or_else(|| texture_manager.get_layer_texture(layer_id))
+                    .map(|texture| (layer_id.as_str(), texture))
+                    .ok_or_else(|| TextureError::TextureNotFound(layer_id.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let data = texture_manager.read_textures_data(device, queue, &textures).await?;
+
+        textures.iter().zip(data)
+            .map(|((_, texture), data)| {
+                let (width, height) = (texture.spec.width, texture.spec.height);
+                let unpadded_bytes_per_row = (width * 4) as usize;
+                let padded_bytes_per_row = data.len() / height.max(1) as usize;
+                let mut pixels = Vec::with_capacity(unpadded_bytes_per_row * height as usize);
+                for row in data.chunks(padded_bytes_per_row).take(height as usize) {
+                    pixels.extend_from_slice(&row[..unpadded_bytes_per_row]);
+                }
+                image::RgbaImage::from_raw(width, height, pixels)
+                    .ok_or_else(|| TextureError::BufferReadFailed("画像データのサイズが一致しません".to_string()))
+            })
+            .collect()
+    }
+
     /// レイヤーテクスチャをクリア
     pub fn clear_layer_texture(&mut self, layer_id: &str, clear_color: Option<wgpu::Color>) -> Result<(), TextureError> {
         debug!("[DrawingEngine] レイヤーテクスチャクリア: {}", layer_id);
@@ -188,11 +729,245 @@ impl DrawingEngine {
         let texture_manager = self.texture_manager.as_mut()
             .ok_or(TextureError::DeviceNotInitialized)?;
 
-        texture_manager.clear_texture(device, queue, layer_id, clear_color)
+        texture_manager.clear_texture(device, queue, layer_id, clear_color)?;
+        self.mark_layer_written(layer_id);
+        Ok(())
+    }
+
+    /// レイヤーテクスチャに画像を書き込む
+    ///
+    /// 左上から書き込む画像はレイヤー全体の内容として扱い、同じ内容のレイヤーとテクスチャを共有する。
+    pub fn write_layer_image(&mut self, layer_id: &str, x: u32, y: u32, image: &image::RgbaImage) -> Result<(), TextureError> {
+        debug!("[DrawingEngine] レイヤー画像書き込み: {}", layer_id);
+
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let texture_manager = self.texture_manager.as_mut()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+
+        if (x, y) == (0, 0) {
+            texture_manager.write_layer_content(device, queue, layer_id, image.dimensions(), image.as_raw())?;
+        } else {
+            texture_manager.prepare_layer_write(device, queue, layer_id)?;
+            texture_manager.write_texture_region(queue, layer_id, (x, y), image.dimensions(), image.as_raw())?;
+        }
+        let (width, height) = image.dimensions();
+        self.mark_layer_region(layer_id, ContentBounds { x, y, width, height });
+        Ok(())
+    }
+
+    /// レイヤーのテクスチャを別のレイヤーと共有する（どちらかに書き込むときに複製される）
+    pub fn share_layer_texture(&mut self, source_layer: &str, target_layer: &str) -> Result<(), TextureError> {
+        self.texture_manager.as_mut()
+            .ok_or(TextureError::DeviceNotInitialized)?
+            .share_layer_texture(source_layer, target_layer)?;
+        self.mark_layer_written(target_layer);
+        Ok(())
+    }
+
+    /// GPU でレイヤーに書き込む前に、共有中のテクスチャをこのレイヤーだけのものにする
+    fn prepare_layer_write(&mut self, layer_id: &str) -> Result<(), TextureError> {
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        self.texture_manager.as_mut()
+            .ok_or(TextureError::DeviceNotInitialized)?
+            .prepare_layer_write(device, queue, layer_id)
+    }
+
+    /// レイヤーテクスチャにアンチエイリアスなしの 1px の線を描画し、書き込んだピクセル数を返す
+    ///
+    /// 線上のピクセルは色をそのまま書き込む（下の色とは合成しない）。
+    pub fn draw_pixel_stroke_to_layer(
+        &mut self,
+        layer_id: &str,
+        points: &[(f32, f32)],
+        color: [f32; 4],
+        pixel_perfect: bool,
+    ) -> Result<usize, TextureError> {
+        debug!("[DrawingEngine] レイヤーにピクセルストローク描画: {} ({} 点)", layer_id, points.len());
+
+        let size = self.texture_manager.as_ref()
+            .and_then(|tm| tm.get_layer_texture(layer_id))
+            .map(|texture| (texture.spec.width, texture.spec.height))
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+
+        let path = pixel::pixel_stroke_path(points, pixel_perfect);
+        let runs = pixel::pixel_runs(&path, size);
+        let rgba = pixel::color_to_rgba8(color);
+        let mut written = 0;
+        for (x, y, length) in runs {
+            let run = image::RgbaImage::from_pixel(length, 1, rgba);
+            self.write_layer_image(layer_id, x, y, &run)?;
+            written += length as usize;
+        }
+
+        debug!("[DrawingEngine] ピクセルストローク描画完了: {} ({} px)", layer_id, written);
+        Ok(written)
+    }
+
+    /// レイヤーテクスチャを回転・反転し、変換後のサイズを返す
+    pub fn transform_layer_texture(&mut self, layer_id: &str, transform: CanvasTransform) -> Result<(u32, u32), TextureError> {
+        debug!("[DrawingEngine] レイヤーテクスチャ変換: {} {:?}", layer_id, transform);
+
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let pipeline = self.transform_pipeline.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let texture_manager = self.texture_manager.as_mut()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+
+        let source = texture_manager.get_layer_texture(layer_id)
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+        let size = transform.output_size((source.spec.width, source.spec.height));
+
+        texture_manager.replace_layer_texture(device, layer_id, size, |source, target| {
+            pipeline.transform(device, queue, source, target, transform);
+        })?;
+        self.mark_layer_written(layer_id);
+        Ok(size)
+    }
+
+    /// 無限キャンバスの状態（無効なら None）
+    pub fn infinite_canvas(&self) -> Option<&InfiniteCanvas> {
+        self.infinite_canvas.as_ref()
+    }
+
+    /// 無限キャンバスモードを切り替える
+    ///
+    /// 有効にすると現在のレイヤーテクスチャが原点 (0, 0) の表示範囲になる。
+    /// 無効にすると表示範囲の内容だけが残り、範囲外のタイルは破棄される。
+    pub fn set_infinite_canvas(&mut self, enabled: bool) {
+        if enabled == self.infinite_canvas.is_some() {
+            return;
+        }
+        self.infinite_canvas = enabled.then(InfiniteCanvas::new);
+        info!("[DrawingEngine] 無限キャンバス: {}", if enabled { "有効" } else { "無効" });
+    }
+
+    /// 無限キャンバスで表示範囲外に保持しているレイヤーの内容を破棄
+    pub fn clear_layer_tiles(&mut self, layer_id: &str) {
+        if let Some(canvas) = self.infinite_canvas.as_mut() {
+            canvas.layers.remove(layer_id);
+        }
+    }
+
+    /// 無限キャンバスの表示範囲を移動する
+    ///
+    /// 各レイヤーテクスチャ（現在の表示範囲）の内容をタイルに書き戻してから、
+    /// 新しい表示範囲に重なるタイルだけを合成してテクスチャに読み込む。
+    pub fn scroll_infinite_canvas(&mut self, layer_ids: &[String], origin: (i32, i32)) -> Result<(), Box<dyn std::error::Error>> {
+        let previous = self.infinite_canvas.as_ref()
+            .ok_or("無限キャンバスが有効ではありません")?
+            .origin;
+        if previous == origin {
+            return Ok(());
+        }
+        debug!("[DrawingEngine] 表示範囲移動: {:?} -> {:?} ({} レイヤー)", previous, origin, layer_ids.len());
+
+        for layer_id in layer_ids {
+            // テクスチャの読み出しは poll(Wait) で完了するため、同期的に待っても詰まらない
+            let window = pollster::block_on(self.get_layer_image(layer_id))?;
+            let (width, height) = window.dimensions();
+
+            let canvas = self.infinite_canvas.as_mut().ok_or("無限キャンバスが有効ではありません")?;
+            let tiles = canvas.layers.entry(layer_id.clone()).or_default();
+            tiles.store_region(previous, &window);
+            let visible = tiles.read_region(Viewport { x: origin.0, y: origin.1, width, height });
+
+            self.write_layer_image(layer_id, 0, 0, &visible)?;
+        }
+
+        let canvas = self.infinite_canvas.as_mut().ok_or("無限キャンバスが有効ではありません")?;
+        canvas.origin = origin;
+        debug!("[DrawingEngine] 表示範囲移動完了: タイル {} 枚", canvas.tile_count());
+        Ok(())
+    }
+
+    /// 指定レイヤーの不透明部分を合わせた矩形を GPU で求める（すべて透明なら None）
+    pub async fn content_bounds(&self, layer_ids: &[String]) -> Result<Option<ContentBounds>, TextureError> {
+        debug!("[DrawingEngine] 不透明範囲計算: {:?}", layer_ids);
+
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let pipeline = self.bounds_pipeline.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+
+        let textures = layer_ids.iter()
+            .map(|id| texture_manager.get_layer_texture(id).ok_or_else(|| TextureError::TextureNotFound(id.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        pipeline.compute(device, queue, &textures).await
+    }
+
+    /// 指定レイヤーそれぞれの不透明部分の矩形とピクセル数を GPU で求める（指定順）
+    pub async fn content_coverage(&self, layer_ids: &[String]) -> Result<Vec<ContentCoverage>, TextureError> {
+        debug!("[DrawingEngine] 不透明部分の集計: {:?}", layer_ids);
+
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let pipeline = self.bounds_pipeline.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+
+        let textures = layer_ids.iter()
+            .map(|id| texture_manager.get_layer_texture(id).ok_or_else(|| TextureError::TextureNotFound(id.clone())))
+            .collect::<Result<Vec<_>, _>>()?;
+        pipeline.compute_each(device, queue, &textures).await
+    }
+
+    /// レイヤーの範囲（省略時は全体）のヒストグラムを GPU で求める
+    pub async fn histogram(&self, layer_id: &str, region: Option<HistogramRegion>) -> Result<Histogram, TextureError> {
+        debug!("[DrawingEngine] ヒストグラム計算: {} {:?}", layer_id, region);
+
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let pipeline = self.histogram_pipeline.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let texture = self.texture_manager.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?
+            .get_layer_texture(layer_id)
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+        pipeline.compute(device, queue, texture, region).await
+    }
+
+    /// 画像を GPU で `width`x`height` に拡大・縮小する（作業中のキャンバスと違う大きさで書き出す）
+    pub async fn resample_image(
+        &self,
+        image: &image::RgbaImage,
+        width: u32,
+        height: u32,
+        filter: ResampleFilter,
+    ) -> Result<image::RgbaImage, TextureError> {
+        let device = self.device.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let queue = self.queue.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        let pipeline = self.resample_pipeline.as_ref()
+            .ok_or(TextureError::DeviceNotInitialized)?;
+        pipeline.resample(device, queue, image, width, height, filter).await
     }
 
     /// レイヤーテクスチャを削除
     pub fn remove_layer_texture(&mut self, layer_id: &str) -> bool {
+        self.layer_strokes.remove(layer_id);
+        self.tile_generations.remove_layer(layer_id);
+        if let Some(canvas) = self.infinite_canvas.as_mut() {
+            canvas.layers.remove(layer_id);
+        }
         if let Some(texture_manager) = self.texture_manager.as_mut() {
             texture_manager.remove_layer_texture(layer_id)
         } else {
@@ -207,14 +982,55 @@ impl DrawingEngine {
         }
     }
 
+    /// 未使用のプール内テクスチャ（と描画の作業用のテクスチャ）をすぐに解放し、解放したバイト数を返す
+    pub fn release_pooled_textures(&mut self) -> u64 {
+        let scratch = self.draw_pipeline.as_mut()
+            .map(|pipeline| pipeline.release_scratch_textures())
+            .unwrap_or(0);
+        scratch + self.texture_manager.as_mut()
+            .map(|tm| tm.release_pooled_textures())
+            .unwrap_or(0)
+    }
+
+    /// 長く使ったセッションで溜まったテクスチャプールを整理する（手動・アイドル時のメンテナンス）
+    ///
+    /// プールから仕様の合わないテクスチャと余分なテクスチャを解放し、描画の作業用のテクスチャも解放する
+    /// （次の描画で作り直される）。`compact_tiles` を指定すると無限キャンバスのタイルも詰める。
+    pub fn defragment(&mut self, options: &DefragmentOptions) -> DefragmentReport {
+        let pool = self.texture_manager.as_mut()
+            .map(|tm| tm.defragment_pool(options.keep_per_spec))
+            .unwrap_or_default();
+        let scratch_freed_bytes = self.draw_pipeline.as_mut()
+            .map(|pipeline| pipeline.release_scratch_textures())
+            .unwrap_or(0);
+        let compacted_tiles = match self.infinite_canvas.as_mut() {
+            Some(canvas) if options.compact_tiles => canvas.compact(),
+            _ => 0,
+        };
+        DefragmentReport { pool, scratch_freed_bytes, compacted_tiles }
+    }
+
+    /// 描画の作業用のテクスチャ（マルチサンプリング・被覆率・描画中のストローク）のメモリ使用量
+    pub fn scratch_memory_size(&self) -> u64 {
+        let strokes: u64 = self.layer_strokes.values().map(LayerStroke::memory_size).sum();
+        strokes + self.draw_pipeline.as_ref().map_or(0, |pipeline| pipeline.scratch_memory_size())
+    }
+
+    /// 読み出し中のステージングバッファの合計サイズを取得
+    pub fn get_staging_memory_usage(&self) -> u64 {
+        self.texture_manager.as_ref()
+            .map(|tm| tm.get_staging_memory_usage())
+            .unwrap_or(0)
+    }
+
     /// メモリ使用量統計を取得
     pub fn get_texture_memory_stats(&self) -> Option<(u64, u64, usize, usize)> {
         self.texture_manager.as_ref().map(|tm| tm.get_memory_stats())
     }
 
-    /// レイヤーテクスチャに線を描画
+    /// レイヤーテクスチャに線を描画（座標・線幅はレイヤーのピクセル単位）
     pub fn draw_line_to_layer(
-        &self,
+        &mut self,
         layer_id: &str,
         start: (f32, f32),
         end: (f32, f32),
@@ -222,86 +1038,526 @@ impl DrawingEngine {
         width: f32,
     ) -> Result<(), Box<dyn std::error::Error>> {
         debug!("[DrawingEngine] レイヤーに線描画: {} {:?} -> {:?}", layer_id, start, end);
+
+        let mut stroke = DrawStroke::new(color, width);
+        stroke.add_point(start.0, start.1, 1.0);
+        stroke.add_point(end.0, end.1, 1.0);
+        self.composite_mesh_to_layer(layer_id, &stroke.to_mesh().with_flow(1.0), color)?;
+
+        info!("[DrawingEngine] レイヤーに線描画完了: {}", layer_id);
+        Ok(())
+    }
+
+    /// レイヤーテクスチャにストロークを描画
+    ///
+    /// 色のアルファはストローク全体の不透明度として1回だけ掛ける（線の継ぎ目が濃くならない）。
+    pub fn draw_stroke_to_layer(
+        &mut self,
+        layer_id: &str,
+        stroke: &DrawStroke,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("[DrawingEngine] レイヤーにストローク描画: {} ({} 点)", layer_id, stroke.points.len());
         
+        self.composite_mesh_to_layer(layer_id, &stroke.to_mesh().with_flow(1.0), stroke.color)?;
+
+        info!("[DrawingEngine] レイヤーにストローク描画完了: {}", layer_id);
+        Ok(())
+    }
+
+    /// レイヤーテクスチャからストロークの形に消す（消しゴム。色のアルファが消す強さ）
+    pub fn erase_stroke_from_layer(
+        &mut self,
+        layer_id: &str,
+        stroke: &DrawStroke,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("[DrawingEngine] レイヤーからストロークを消去: {} ({} 点)", layer_id, stroke.points.len());
+        self.erase_mesh_from_layer(layer_id, &stroke.to_mesh().with_flow(1.0), stroke.color[3])
+    }
+
+    /// 三角形分割済みのメッシュをレイヤーテクスチャに直接描画（三角形ごとに合成する）
+    pub fn draw_mesh_to_layer(
+        &mut self,
+        layer_id: &str,
+        mesh: &StrokeMesh,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.draw_mesh_to_layer_with_view(layer_id, mesh, DrawUniforms::IDENTITY)
+    }
+
+    /// 表示行列を指定してメッシュをレイヤーテクスチャに描画
+    ///
+    /// 頂点はドキュメントのピクセル座標で、表示行列を掛けた結果をレイヤーのピクセル座標として扱う。
+    /// 頂点バッファの上限を超えるメッシュは分割し、チャンクごとに送信して描画する。
+    pub fn draw_mesh_to_layer_with_view(
+        &mut self,
+        layer_id: &str,
+        mesh: &StrokeMesh,
+        view: [[f32; 4]; 4],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if mesh.is_empty() {
+            return Ok(());
+        }
+        self.prepare_layer_write(layer_id)?;
+        let queue = self.queue.as_ref()
+            .ok_or("Queue が初期化されていません")?;
         let device = self.device.as_ref()
             .ok_or("Device が初期化されていません")?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or("TextureManager が初期化されていません")?;
+        let pipeline = self.draw_pipeline.as_mut()
+            .ok_or("DrawPipeline が初期化されていません")?;
+
+        let managed_texture = texture_manager.get_layer_texture(layer_id)
+            .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+        let uniforms = DrawUniforms::with_view((managed_texture.spec.width, managed_texture.spec.height), view);
+
+        for chunk in split_mesh(mesh, pipeline.vertex_limit()).iter() {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Draw Mesh Encoder"),
+            });
+            pipeline.draw_mesh_to_texture(device, queue, &mut encoder, managed_texture, &uniforms, chunk)?;
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.mark_layer_written(layer_id);
+
+        debug!("[DrawingEngine] レイヤーに {} 頂点を描画: {}", mesh.vertices.len(), layer_id);
+        Ok(())
+    }
+
+    /// メッシュの被覆率を溜めてから、色を掛けてレイヤーテクスチャに1回だけ合成
+    ///
+    /// 頂点のアルファは流量として重なるほど濃くなり、`color[3]` はストローク全体の不透明度になる。
+    /// 頂点バッファの上限を超えるメッシュは分割して溜め、合成は最後に1回だけ行う。
+    pub fn composite_mesh_to_layer(
+        &mut self,
+        layer_id: &str,
+        mesh: &StrokeMesh,
+        color: [f32; 4],
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.composite_coverage_to_layer(layer_id, mesh, CoverageBlend::Paint(color))
+    }
+
+    /// メッシュの被覆率を溜めてから、レイヤーテクスチャのアルファを1回だけ削る（消しゴム）
+    ///
+    /// `strength` はストローク全体の消す強さ（1 で完全に透明にする）。色の値はそのまま残る。
+    pub fn erase_mesh_from_layer(
+        &mut self,
+        layer_id: &str,
+        mesh: &StrokeMesh,
+        strength: f32,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        self.composite_coverage_to_layer(layer_id, mesh, CoverageBlend::Erase(strength))
+    }
+
+    fn composite_coverage_to_layer(
+        &mut self,
+        layer_id: &str,
+        mesh: &StrokeMesh,
+        blend: CoverageBlend,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        if mesh.is_empty() {
+            return Ok(());
+        }
+        self.prepare_layer_write(layer_id)?;
         let queue = self.queue.as_ref()
             .ok_or("Queue が初期化されていません")?;
+        let device = self.device.as_ref()
+            .ok_or("Device が初期化されていません")?;
         let texture_manager = self.texture_manager.as_ref()
             .ok_or("TextureManager が初期化されていません")?;
-        let pipeline = self.draw_pipeline.as_ref()
+        let pipeline = self.draw_pipeline.as_mut()
             .ok_or("DrawPipeline が初期化されていません")?;
+        let managed_texture = texture_manager.get_layer_texture(layer_id)
+            .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+        let size = (managed_texture.spec.width, managed_texture.spec.height);
+        let uniforms = DrawUniforms::new(size);
+
+        let mut coverage = pipeline.take_coverage_buffer(device, size);
+        let result = split_mesh(mesh, pipeline.vertex_limit()).iter().try_for_each(|chunk| {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Stroke Coverage Encoder"),
+            });
+            pipeline.accumulate_coverage(device, queue, &mut encoder, &mut coverage, &uniforms, chunk)?;
+            queue.submit(std::iter::once(encoder.finish()));
+            Ok::<_, PipelineError>(())
+        });
+        if result.is_ok() {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Stroke Composite Encoder"),
+            });
+            pipeline.composite_coverage(device, queue, &mut encoder, managed_texture, &coverage, blend);
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        let bounds = coverage.bounds();
+        pipeline.put_coverage_buffer(coverage);
+        result?;
+        if let Some(bounds) = bounds {
+            self.mark_layer_region(layer_id, bounds);
+        }
 
-        // レイヤーテクスチャを取得
+        debug!("[DrawingEngine] 被覆率を合成: {} ({} 頂点, {:?})", layer_id, mesh.vertices.len(), blend);
+        Ok(())
+    }
+
+    /// 点を追加しながら描くストロークを開始（描き始める前のレイヤーの内容を保存する）
+    ///
+    /// 追加分は `extend_layer_stroke` で被覆率に溜めて合成し直すため、区切りの継ぎ目も濃くならない。
+    /// 同じレイヤーで描画中のストロークがあれば、そのときの内容のまま破棄する。
+    pub fn begin_layer_stroke(&mut self, layer_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.start_layer_stroke(layer_id, false)
+    }
+
+    /// 点を追加しながら消す消しゴムのストロークを開始
+    ///
+    /// 描画中はレイヤーのテクスチャを変えず、消した結果はプレビュー（`get_layer_preview_images`）
+    /// にだけ反映する。`end_layer_stroke` でレイヤーに確定し、`cancel_layer_stroke` で取り消せる。
+    pub fn begin_erase_stroke(&mut self, layer_id: &str) -> Result<(), Box<dyn std::error::Error>> {
+        self.start_layer_stroke(layer_id, true)
+    }
+
+    fn start_layer_stroke(&mut self, layer_id: &str, erase: bool) -> Result<(), Box<dyn std::error::Error>> {
+        let queue = self.queue.as_ref()
+            .ok_or("Queue が初期化されていません")?;
+        let device = self.device.as_ref()
+            .ok_or("Device が初期化されていません")?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or("TextureManager が初期化されていません")?;
+        let pipeline = self.draw_pipeline.as_ref()
+            .ok_or("DrawPipeline が初期化されていません")?;
         let managed_texture = texture_manager.get_layer_texture(layer_id)
             .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+        let size = (managed_texture.spec.width, managed_texture.spec.height);
 
-        // コマンドエンコーダーを作成
+        let usage = if erase {
+            TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::COPY_DST
+        } else {
+            TextureUsages::COPY_SRC | TextureUsages::COPY_DST
+        };
+        let copy = device.create_texture(&TextureDescriptor {
+            label: Some(if erase { "Erase Preview Texture" } else { "Stroke Snapshot Texture" }),
+            size: managed_texture.texture.size(),
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: managed_texture.spec.format,
+            usage,
+            view_formats: &[],
+        });
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Draw Line Encoder"),
+            label: Some("Stroke Snapshot Encoder"),
         });
-
-        // 線を描画
-        pipeline.draw_line(
-            device,
-            queue,
-            &mut encoder,
-            &managed_texture.view,
-            start,
-            end,
-            color,
-            width,
-        )?;
-
-        // コマンドを送信
+        encoder.copy_texture_to_texture(
+            managed_texture.texture.as_image_copy(),
+            copy.as_image_copy(),
+            managed_texture.texture.size(),
+        );
         queue.submit(std::iter::once(encoder.finish()));
 
-        info!("[DrawingEngine] レイヤーに線描画完了: {}", layer_id);
+        let target = if erase {
+            let spec = TextureSpec { usage, ..managed_texture.spec.clone() };
+            StrokeTarget::Preview { preview: ManagedTexture::new(copy, spec), strength: 1.0 }
+        } else {
+            StrokeTarget::Layer { snapshot: copy }
+        };
+        let stroke = LayerStroke {
+            coverage: pipeline.create_coverage_buffer(device, size),
+            target,
+        };
+        if self.layer_strokes.insert(layer_id.to_string(), stroke).is_some() {
+            warn!("[DrawingEngine] 描画中のストロークを破棄: {}", layer_id);
+        }
+        debug!("[DrawingEngine] ストローク開始: {} ({}x{}, 消しゴム: {})", layer_id, size.0, size.1, erase);
         Ok(())
     }
 
-    /// レイヤーテクスチャにストロークを描画
-    pub fn draw_stroke_to_layer(
-        &self,
+    /// 描画中のストロークにメッシュを追加し、ストローク全体を合成し直す
+    ///
+    /// 合成し直すのはストロークが掛かった範囲だけ。`color[3]` はストローク全体の不透明度
+    /// （消しゴムでは消す強さで、色は使わない）。消しゴムはレイヤーの代わりにプレビューに合成する。
+    pub fn extend_layer_stroke(
+        &mut self,
         layer_id: &str,
-        stroke: &DrawStroke,
+        mesh: &StrokeMesh,
+        color: [f32; 4],
     ) -> Result<(), Box<dyn std::error::Error>> {
-        debug!("[DrawingEngine] レイヤーにストローク描画: {} ({} 点)", layer_id, stroke.points.len());
-        
+        if mesh.is_empty() {
+            return Ok(());
+        }
+        // 消しゴムはプレビューにだけ合成するため、レイヤーは確定するまで複製しない
+        if matches!(self.layer_strokes.get(layer_id).map(|stroke| &stroke.target), Some(StrokeTarget::Layer { .. })) {
+            self.prepare_layer_write(layer_id)?;
+        }
+        let queue = self.queue.as_ref()
+            .ok_or("Queue が初期化されていません")?;
         let device = self.device.as_ref()
             .ok_or("Device が初期化されていません")?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or("TextureManager が初期化されていません")?;
+        let pipeline = self.draw_pipeline.as_mut()
+            .ok_or("DrawPipeline が初期化されていません")?;
+        let stroke = self.layer_strokes.get_mut(layer_id)
+            .ok_or(format!("描画中のストロークがありません: {}", layer_id))?;
+        let managed_texture = texture_manager.get_layer_texture(layer_id)
+            .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+        let size = (managed_texture.spec.width, managed_texture.spec.height);
+        if stroke.coverage.size() != size {
+            return Err(format!("描画中にレイヤーの大きさが変わりました: {}", layer_id).into());
+        }
+        let uniforms = DrawUniforms::new(size);
+
+        for chunk in split_mesh(mesh, pipeline.vertex_limit()).iter() {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Stroke Coverage Encoder"),
+            });
+            pipeline.accumulate_coverage(device, queue, &mut encoder, &mut stroke.coverage, &uniforms, chunk)?;
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        let Some(bounds) = stroke.coverage.bounds() else { return Ok(()) };
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Stroke Composite Encoder"),
+        });
+        let origin = Origin3d { x: bounds.x, y: bounds.y, z: 0 };
+        let extent = Extent3d { width: bounds.width, height: bounds.height, depth_or_array_layers: 1 };
+        match &mut stroke.target {
+            StrokeTarget::Layer { snapshot } => {
+                encoder.copy_texture_to_texture(
+                    TexelCopyTextureInfo { texture: snapshot, mip_level: 0, origin, aspect: TextureAspect::All },
+                    TexelCopyTextureInfo { texture: &managed_texture.texture, mip_level: 0, origin, aspect: TextureAspect::All },
+                    extent,
+                );
+                pipeline.composite_coverage(device, queue, &mut encoder, managed_texture, &stroke.coverage, CoverageBlend::Paint(color));
+            }
+            StrokeTarget::Preview { preview, strength } => {
+                *strength = color[3];
+                encoder.copy_texture_to_texture(
+                    TexelCopyTextureInfo { texture: &managed_texture.texture, mip_level: 0, origin, aspect: TextureAspect::All },
+                    TexelCopyTextureInfo { texture: &preview.texture, mip_level: 0, origin, aspect: TextureAspect::All },
+                    extent,
+                );
+                pipeline.composite_coverage(device, queue, &mut encoder, preview, &stroke.coverage, CoverageBlend::Erase(*strength));
+            }
+        }
+        queue.submit(std::iter::once(encoder.finish()));
+        // 消しゴムのプレビューも表示が変わる
+        self.mark_layer_region(layer_id, bounds);
+        Ok(())
+    }
+
+    /// 描画中のストロークを確定（描画中でなければ false）
+    ///
+    /// 描き込みはレイヤーに合成済みのため、そのまま残す。消しゴムはここで初めてレイヤーから消す。
+    pub fn end_layer_stroke(&mut self, layer_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(stroke) = self.layer_strokes.remove(layer_id) else { return Ok(false) };
+        let StrokeTarget::Preview { strength, .. } = stroke.target else { return Ok(true) };
+        self.prepare_layer_write(layer_id)?;
+
         let queue = self.queue.as_ref()
             .ok_or("Queue が初期化されていません")?;
+        let device = self.device.as_ref()
+            .ok_or("Device が初期化されていません")?;
         let texture_manager = self.texture_manager.as_ref()
             .ok_or("TextureManager が初期化されていません")?;
         let pipeline = self.draw_pipeline.as_ref()
             .ok_or("DrawPipeline が初期化されていません")?;
-
-        // レイヤーテクスチャを取得
         let managed_texture = texture_manager.get_layer_texture(layer_id)
             .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+        if stroke.coverage.size() != (managed_texture.spec.width, managed_texture.spec.height) {
+            return Err(format!("描画中にレイヤーの大きさが変わりました: {}", layer_id).into());
+        }
 
-        // コマンドエンコーダーを作成
         let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Draw Stroke Encoder"),
+            label: Some("Erase Commit Encoder"),
         });
+        pipeline.composite_coverage(device, queue, &mut encoder, managed_texture, &stroke.coverage, CoverageBlend::Erase(strength));
+        queue.submit(std::iter::once(encoder.finish()));
+        if let Some(bounds) = stroke.coverage.bounds() {
+            self.mark_layer_region(layer_id, bounds);
+        }
+        debug!("[DrawingEngine] 消しゴムを確定: {} (強さ {})", layer_id, strength);
+        Ok(true)
+    }
 
-        // ストロークを描画
-        pipeline.draw_stroke(
-            device,
-            queue,
-            &mut encoder,
-            &managed_texture.view,
-            stroke,
-        )?;
+    /// 描画中のストロークを取り消し、レイヤーを描き始める前の内容に戻す（描画中でなければ false）
+    ///
+    /// 戻すのはストロークが掛かった範囲だけ（まだ何も描いていなければレイヤーに触れない）。
+    pub fn cancel_layer_stroke(&mut self, layer_id: &str) -> Result<bool, Box<dyn std::error::Error>> {
+        let Some(stroke) = self.layer_strokes.remove(layer_id) else { return Ok(false) };
+        let Some(bounds) = stroke.coverage.bounds() else {
+            debug!("[DrawingEngine] 描き込む前のストロークを取り消し: {}", layer_id);
+            return Ok(true);
+        };
+        // 消しゴムはレイヤーを変えていないので、プレビューを捨てるだけでよい
+        self.mark_layer_region(layer_id, bounds);
+        let StrokeTarget::Layer { snapshot } = stroke.target else { return Ok(true) };
+        self.prepare_layer_write(layer_id)?;
 
-        // コマンドを送信
+        let queue = self.queue.as_ref()
+            .ok_or("Queue が初期化されていません")?;
+        let device = self.device.as_ref()
+            .ok_or("Device が初期化されていません")?;
+        let texture_manager = self.texture_manager.as_ref()
+            .ok_or("TextureManager が初期化されていません")?;
+        let managed_texture = texture_manager.get_layer_texture(layer_id)
+            .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+        if snapshot.size() != managed_texture.texture.size() {
+            return Err(format!("描画中にレイヤーの大きさが変わりました: {}", layer_id).into());
+        }
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Stroke Cancel Encoder"),
+        });
+        let origin = Origin3d { x: bounds.x, y: bounds.y, z: 0 };
+        encoder.copy_texture_to_texture(
+            TexelCopyTextureInfo { texture: &snapshot, mip_level: 0, origin, aspect: TextureAspect::All },
+            TexelCopyTextureInfo { texture: &managed_texture.texture, mip_level: 0, origin, aspect: TextureAspect::All },
+            Extent3d { width: bounds.width, height: bounds.height, depth_or_array_layers: 1 },
+        );
         queue.submit(std::iter::once(encoder.finish()));
+        debug!("[DrawingEngine] ストロークを取り消し: {} ({:?})", layer_id, bounds);
+        Ok(true)
+    }
 
-        info!("[DrawingEngine] レイヤーにストローク描画完了: {}", layer_id);
+    /// 描画中のすべてのストロークを取り消し、取り消したレイヤーのIDを返す
+    pub fn cancel_all_layer_strokes(&mut self) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+        let mut layer_ids: Vec<String> = self.layer_strokes.keys().cloned().collect();
+        layer_ids.sort();
+        for layer_id in &layer_ids {
+            self.cancel_layer_stroke(layer_id)?;
+        }
+        Ok(layer_ids)
+    }
+
+    /// ブラシのスタンプをレイヤーテクスチャに描画
+    ///
+    /// 三角形での描画はスタンプの被覆率を溜めてから1回だけ合成し、コンピュートシェーダーでの描画は
+    /// スタンプを直接画素に合成する。どちらを使うかは呼び出し側で決める。
+    /// スタンプの不透明度は流量として重なるほど濃くなり、`shape.color[3]` はストローク全体の不透明度になる
+    /// （1 ならどちらの描画も同じ結果になる）。
+    pub fn draw_stamps_to_layer(
+        &mut self,
+        layer_id: &str,
+        stamps: &[Stamp],
+        shape: &StampShape,
+        rasterizer: StampRasterizer,
+    ) -> Result<(), Box<dyn std::error::Error>> {
+        debug!("[DrawingEngine] スタンプ描画: {} ({} 個, {:?})", layer_id, stamps.len(), rasterizer);
+        if stamps.is_empty() {
+            return Ok(());
+        }
+
+        // コンピュートシェーダーは線形で、スタンプごとに合成するため、sRGB で合成する設定と
+        // ストロークの不透明度が 1 未満のときは三角形で描く
+        let rasterizer = match self.effective_blend_space() {
+            BlendSpace::Srgb => StampRasterizer::Triangles,
+            BlendSpace::Linear if shape.color[3] < 1.0 => StampRasterizer::Triangles,
+            BlendSpace::Linear => rasterizer,
+        };
+        match rasterizer {
+            StampRasterizer::Triangles => {
+                let flow_shape = StampShape { color: [shape.color[0], shape.color[1], shape.color[2], 1.0], ..*shape };
+                self.composite_mesh_to_layer(layer_id, &stamp::stamp_mesh(stamps, &flow_shape), shape.color)?;
+            }
+            StampRasterizer::Compute => {
+                self.prepare_layer_write(layer_id)?;
+                let device = self.device.as_ref()
+                    .ok_or("Device が初期化されていません")?;
+                let queue = self.queue.as_ref()
+                    .ok_or("Queue が初期化されていません")?;
+                let texture_manager = self.texture_manager.as_ref()
+                    .ok_or("TextureManager が初期化されていません")?;
+                let pipeline = self.stamp_pipeline.as_mut()
+                    .ok_or("StampComputePipeline が初期化されていません")?;
+                let managed_texture = texture_manager.get_layer_texture(layer_id)
+                    .ok_or(format!("レイヤーテクスチャが見つかりません: {}", layer_id))?;
+                let region = stamp::StampRegion::covering(stamps, (managed_texture.spec.width, managed_texture.spec.height));
+                let pixels = pipeline.draw(device, queue, managed_texture, stamps, shape)?;
+                debug!("[DrawingEngine] コンピュートシェーダーで {} 画素を合成: {}", pixels, layer_id);
+                if let Some(region) = region {
+                    self.mark_layer_region(layer_id, ContentBounds { x: region.x, y: region.y, width: region.width, height: region.height });
+                }
+            }
+        }
         Ok(())
     }
 
+    /// 同じ図柄を線形・sRGB の両方の合成で描き、比較用の画像を返す（色空間の確認用）
+    ///
+    /// 作業用のレイヤーに描いて読み出したあとは削除し、合成の設定も元に戻す。
+    pub async fn render_blend_comparison(&mut self, width: u32, height: u32) -> Result<Vec<(BlendSpace, image::RgbaImage)>, Box<dyn std::error::Error>> {
+        let original = self.render_settings.blend_space;
+        let mut images = Vec::new();
+        for blend_space in BlendSpace::ALL {
+            let layer_id = format!("__blend_audit_{}", blend_space.name());
+            let rendered = self.render_blend_audit_layer(&layer_id, blend_space, width, height).await;
+            self.remove_layer_texture(&layer_id);
+            match rendered {
+                Ok(image) => images.push((blend_space, image)),
+                Err(e) => {
+                    self.set_blend_space(original)?;
+                    return Err(e);
+                }
+            }
+        }
+        self.set_blend_space(original)?;
+        debug!("[DrawingEngine] 合成の比較画像を作成: {}x{}", width, height);
+        Ok(images)
+    }
+
+    async fn render_blend_audit_layer(
+        &mut self,
+        layer_id: &str,
+        blend_space: BlendSpace,
+        width: u32,
+        height: u32,
+    ) -> Result<image::RgbaImage, Box<dyn std::error::Error>> {
+        let (background, strokes) = color_space::blend_audit_pattern(width, height);
+        self.set_blend_space(blend_space)?;
+        self.create_layer_texture(layer_id, width, height)?;
+        self.clear_layer_texture(layer_id, Some(background))?;
+        for (stamps, shape) in &strokes {
+            self.draw_stamps_to_layer(layer_id, stamps, shape, StampRasterizer::Triangles)?;
+        }
+        Ok(self.get_layer_image(layer_id).await?)
+    }
+
+    /// ストロークの組ごとに透明な画像に描く（中割りのプレビューなど、レイヤーとして残さない描画）
+    pub async fn render_stroke_frames(
+        &mut self,
+        width: u32,
+        height: u32,
+        frames: &[Vec<DrawStroke>],
+    ) -> Result<Vec<image::RgbaImage>, Box<dyn std::error::Error>> {
+        let layer_id = "__stroke_preview";
+        self.create_layer_texture(layer_id, width, height)?;
+        let rendered = self.render_stroke_frames_to(layer_id, frames).await;
+        self.remove_layer_texture(layer_id);
+        debug!("[DrawingEngine] ストロークのプレビューを作成: {} 枚 ({}x{})", frames.len(), width, height);
+        rendered
+    }
+
+    async fn render_stroke_frames_to(
+        &mut self,
+        layer_id: &str,
+        frames: &[Vec<DrawStroke>],
+    ) -> Result<Vec<image::RgbaImage>, Box<dyn std::error::Error>> {
+        let mut images = Vec::with_capacity(frames.len());
+        for strokes in frames {
+            self.clear_layer_texture(layer_id, Some(Color::TRANSPARENT))?;
+            for stroke in strokes {
+                self.draw_stroke_to_layer(layer_id, stroke)?;
+            }
+            images.push(self.get_layer_image(layer_id).await?);
+        }
+        Ok(images)
+    }
+
+    /// レイヤーテクスチャの大きさ（ピクセル）
+    pub fn layer_texture_size(&self, layer_id: &str) -> Option<(u32, u32)> {
+        self.texture_manager.as_ref()?
+            .get_layer_texture(layer_id)
+            .map(|texture| (texture.spec.width, texture.spec.height))
+    }
+
     /// スクリーン座標を正規化座標に変換（描画用）
     pub fn screen_to_normalized(&self, screen_pos: (f32, f32), screen_size: (u32, u32)) -> (f32, f32) {
         BasicDrawPipeline::screen_to_normalized(screen_pos, screen_size)
@@ -0,0 +1,242 @@
+use wgpu::*;
+use log::{info, debug};
+use serde::{Deserialize, Serialize};
+use crate::shaders;
+use super::pipeline::PipelineError;
+
+/// ストローク描画のマルチサンプリング（品質と速度の設定）
+///
+/// サンプル数が多いほど斜めの線のギザギザが減るが、描画ごとにレイヤーと同じ大きさの
+/// マルチサンプルテクスチャ（サンプル数倍のメモリ）への転写と解決が加わる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MsaaMode {
+    /// マルチサンプリングなし（最速）
+    #[default]
+    Off,
+    /// 2x（デバイスが対応していなければ 1x）
+    X2,
+    /// 4x（最高品質）
+    X4,
+}
+
+impl MsaaMode {
+    /// 要求するサンプル数
+    pub fn requested_samples(self) -> u32 {
+        match self {
+            MsaaMode::Off => 1,
+            MsaaMode::X2 => 2,
+            MsaaMode::X4 => 4,
+        }
+    }
+
+    /// デバイスが対応するサンプル数のうち、要求以下で最大のものを選ぶ（最低 1）
+    pub fn negotiate(self, supported: &[u32]) -> u32 {
+        supported.iter()
+            .copied()
+            .filter(|&count| count <= self.requested_samples())
+            .max()
+            .unwrap_or(1)
+    }
+}
+
+/// アダプターが 2x などの WebGPU の保証外のサンプル数に対応していれば、デバイスに要求する機能を返す
+pub fn required_features(adapter: &Adapter) -> Features {
+    adapter.features() & Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES
+}
+
+/// デバイスで使えるサンプル数（1, 2, 4 のうち対応しているもの）
+pub fn supported_sample_counts(adapter: &Adapter, device: &Device, format: TextureFormat) -> Vec<u32> {
+    let features = if device.features().contains(Features::TEXTURE_ADAPTER_SPECIFIC_FORMAT_FEATURES) {
+        adapter.get_texture_format_features(format)
+    } else {
+        format.guaranteed_format_features(device.features())
+    };
+    features.flags.supported_sample_counts()
+        .into_iter()
+        .filter(|&count| count <= MsaaMode::X4.requested_samples())
+        .collect()
+}
+
+/// マルチサンプルの描画先
+///
+/// 描画のたびにレイヤーの内容をマルチサンプルテクスチャに転写してからストロークを描き、
+/// レイヤーのテクスチャに解決する（描かれていない画素は元の値のまま戻る）。
+/// マルチサンプルテクスチャは直前に使った大きさのものを使い回す。
+pub struct MsaaTarget {
+    sample_count: u32,
+    format: TextureFormat,
+    copy_pipeline: RenderPipeline,
+    copy_bind_group_layout: BindGroupLayout,
+    texture: Option<(Texture, TextureView)>,
+}
+
+impl MsaaTarget {
+    pub fn new(device: &Device, format: TextureFormat, sample_count: u32, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        debug!("[MsaaTarget] 作成開始: {}x", sample_count);
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("MSAA Copy Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(Self::shader_source())?.into()),
+        });
+
+        let copy_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("MSAA Copy Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::FRAGMENT,
+                ty: BindingType::Texture {
+                    sample_type: TextureSampleType::Float { filterable: false },
+                    view_dimension: TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("MSAA Copy Pipeline Layout"),
+            bind_group_layouts: &[&copy_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let copy_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("MSAA Copy Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState {
+                count: sample_count,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+            cache,
+        });
+
+        info!("[MsaaTarget] 作成完了: {}x", sample_count);
+        Ok(Self {
+            sample_count,
+            format,
+            copy_pipeline,
+            copy_bind_group_layout,
+            texture: None,
+        })
+    }
+
+    pub fn sample_count(&self) -> u32 {
+        self.sample_count
+    }
+
+    /// 使い回しているマルチサンプルテクスチャのメモリ使用量（バイト）
+    pub fn memory_size(&self) -> u64 {
+        self.texture.as_ref().map_or(0, |(texture, _)| {
+            texture.width() as u64 * texture.height() as u64 * 4 * self.sample_count as u64
+        })
+    }
+
+    /// レイヤーの内容（`source` はこの描画先と同じ形式のビュー）をマルチサンプルテクスチャに転写し、
+    /// ストロークを描くビューを返す
+    pub fn prepare(&mut self, device: &Device, encoder: &mut CommandEncoder, source: &TextureView, size: (u32, u32)) -> &TextureView {
+        let reusable = self.texture.as_ref()
+            .is_some_and(|(texture, _)| (texture.width(), texture.height()) == size);
+        if !reusable {
+            debug!("[MsaaTarget] マルチサンプルテクスチャを作成: {}x{} ({}x)", size.0, size.1, self.sample_count);
+            let texture = device.create_texture(&TextureDescriptor {
+                label: Some("MSAA Stroke Texture"),
+                size: Extent3d { width: size.0, height: size.1, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: self.sample_count,
+                dimension: TextureDimension::D2,
+                format: self.format,
+                usage: TextureUsages::RENDER_ATTACHMENT,
+                view_formats: &[],
+            });
+            let view = texture.create_view(&TextureViewDescriptor::default());
+            self.texture = Some((texture, view));
+        }
+        let (_, view) = self.texture.as_ref().expect("マルチサンプルテクスチャは作成済み");
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("MSAA Copy Bind Group"),
+            layout: &self.copy_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: BindingResource::TextureView(source),
+            }],
+        });
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("MSAA Copy Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.copy_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+        view
+    }
+
+    /// 使い回しているマルチサンプルテクスチャを解放
+    pub fn release_texture(&mut self) {
+        self.texture = None;
+    }
+
+    /// レイヤーの画素をそのまま全サンプルに書き込むシェーダー（WGSL）
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        #import fullscreen
+
+        @group(0) @binding(0) var source: texture_2d<f32>;
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            return fullscreen_triangle(index);
+        }
+
+        @fragment
+        fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            return textureLoad(source, vec2<i32>(position.xy), 0);
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_negotiate_sample_count() {
+        // WebGPU で保証されるのは 1x と 4x
+        assert_eq!(MsaaMode::X4.negotiate(&[1, 4]), 4);
+        assert_eq!(MsaaMode::X2.negotiate(&[1, 4]), 1);
+        assert_eq!(MsaaMode::X2.negotiate(&[1, 2, 4]), 2);
+        assert_eq!(MsaaMode::X4.negotiate(&[1, 2]), 2);
+        assert_eq!(MsaaMode::Off.negotiate(&[1, 2, 4]), 1);
+        assert_eq!(MsaaMode::X4.negotiate(&[]), 1);
+    }
+}
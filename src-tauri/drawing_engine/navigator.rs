@@ -0,0 +1,190 @@
+use image::{Rgba, RgbaImage};
+use super::tiles::{TileCoord, TILE_SIZE};
+use crate::formats::blend_over;
+
+/// ナビゲーターの縮小画像の長辺の上限（ピクセル）
+pub const MAX_NAVIGATOR_SIZE: u32 = 1024;
+
+/// 縮小画像に描く表示範囲の枠の色
+pub const NAVIGATOR_FRAME_COLOR: Rgba<u8> = Rgba([255, 64, 64, 255]);
+
+/// キャンバス全体の縮小画像（ナビゲーター）
+///
+/// 合成済みのキャンバスを長辺が `max_size` に収まるよう縮小して保持し、タイルの更新世代を
+/// 見て書き込まれたタイルの範囲だけ縮小し直す。キャンバスの大きさ・上限・合成するレイヤーの
+/// 組が変わったときは全体を作り直す。
+#[derive(Debug, Clone, Default)]
+pub struct Navigator {
+    overview: RgbaImage,
+    canvas_size: (u32, u32),
+    max_size: u32,
+    /// 合成したレイヤーの組（表示・順序の変更を検出する）
+    source: u64,
+    /// 最後に反映したタイルの更新世代
+    generation: u64,
+}
+
+impl Navigator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// キャンバスの大きさに対する縮小率（拡大はしない）
+    pub fn scale_for(canvas_size: (u32, u32), max_size: u32) -> f32 {
+        let longest = canvas_size.0.max(canvas_size.1).max(1);
+        (max_size as f32 / longest as f32).min(1.0)
+    }
+
+    pub fn scale(&self) -> f32 {
+        Self::scale_for(self.canvas_size, self.max_size)
+    }
+
+    /// 最後に反映したタイルの更新世代（この世代より後に書き込まれたタイルを `update_tiles` に渡す）
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// 全体を作り直す必要があるか
+    pub fn needs_rebuild(&self, canvas_size: (u32, u32), max_size: u32, source: u64) -> bool {
+        self.overview.width() == 0 || self.canvas_size != canvas_size || self.max_size != max_size || self.source != source
+    }
+
+    /// 合成済みのキャンバス全体から縮小画像を作り直す
+    pub fn rebuild(&mut self, composite: &RgbaImage, max_size: u32, source: u64, generation: u64) {
+        let canvas_size = composite.dimensions();
+        let scale = Self::scale_for(canvas_size, max_size);
+        let width = ((canvas_size.0 as f32 * scale).ceil() as u32).max(1);
+        let height = ((canvas_size.1 as f32 * scale).ceil() as u32).max(1);
+        self.overview = RgbaImage::new(width, height);
+        self.canvas_size = canvas_size;
+        self.max_size = max_size;
+        self.source = source;
+        self.generation = generation;
+        resample(&mut self.overview, composite, scale, (0, 0, width, height));
+    }
+
+    /// 書き込まれたタイルの範囲だけ縮小し直す（`composite` は同じ大きさの合成済みキャンバス）
+    pub fn update_tiles(&mut self, composite: &RgbaImage, tiles: &[TileCoord], generation: u64) {
+        let scale = self.scale();
+        let (width, height) = self.overview.dimensions();
+        for tile in tiles {
+            let (x, y) = tile.origin();
+            let left = ((x.max(0) as f32 * scale).floor() as u32).min(width);
+            let top = ((y.max(0) as f32 * scale).floor() as u32).min(height);
+            let right = ((((x + TILE_SIZE as i64).max(0)) as f32 * scale).ceil() as u32).min(width);
+            let bottom = ((((y + TILE_SIZE as i64).max(0)) as f32 * scale).ceil() as u32).min(height);
+            if left < right && top < bottom {
+                resample(&mut self.overview, composite, scale, (left, top, right, bottom));
+            }
+        }
+        self.generation = generation;
+    }
+
+    /// 縮小画像に表示範囲の枠（キャンバス座標の四隅を順につないだ四角形）を描いた画像
+    ///
+    /// 表示を回転していると枠も傾く。キャンバスの外にはみ出した部分は描かない。
+    pub fn render(&self, corners: &[(f32, f32); 4]) -> RgbaImage {
+        let mut image = self.overview.clone();
+        let scale = self.scale();
+        let (width, height) = image.dimensions();
+        for (index, &(ax, ay)) in corners.iter().enumerate() {
+            let (bx, by) = corners[(index + 1) % corners.len()];
+            let (ax, ay, bx, by) = (ax * scale, ay * scale, bx * scale, by * scale);
+            let steps = ((bx - ax).abs().max((by - ay).abs()).ceil() as u32).clamp(1, 4 * MAX_NAVIGATOR_SIZE);
+            for step in 0..=steps {
+                let t = step as f32 / steps as f32;
+                let (x, y) = ((ax + (bx - ax) * t).floor(), (ay + (by - ay) * t).floor());
+                if x >= 0.0 && y >= 0.0 && (x as u32) < width && (y as u32) < height {
+                    let pixel = image.get_pixel_mut(x as u32, y as u32);
+                    *pixel = blend_over(*pixel, NAVIGATOR_FRAME_COLOR);
+                }
+            }
+        }
+        image
+    }
+}
+
+/// 縮小画像の範囲 `(left, top, right, bottom)` を、対応するキャンバスの画素の平均で塗り直す
+///
+/// 半透明の画素の色が透明な画素に引っ張られないよう、乗算済みアルファで平均する。
+fn resample(overview: &mut RgbaImage, composite: &RgbaImage, scale: f32, (left, top, right, bottom): (u32, u32, u32, u32)) {
+    let (canvas_width, canvas_height) = composite.dimensions();
+    let source_range = |position: u32, limit: u32| {
+        let start = ((position as f32 / scale).floor() as u32).min(limit.saturating_sub(1));
+        let end = (((position + 1) as f32 / scale).floor() as u32).clamp(start + 1, limit);
+        start..end
+    };
+    for y in top..bottom {
+        let rows = source_range(y, canvas_height);
+        for x in left..right {
+            let columns = source_range(x, canvas_width);
+            let mut sum = [0u64; 4];
+            let mut count = 0u64;
+            for sy in rows.clone() {
+                for sx in columns.clone() {
+                    let pixel = composite.get_pixel(sx, sy);
+                    let alpha = pixel[3] as u64;
+                    for channel in 0..3 {
+                        sum[channel] += pixel[channel] as u64 * alpha;
+                    }
+                    sum[3] += alpha;
+                    count += 1;
+                }
+            }
+            let pixel = if sum[3] == 0 {
+                Rgba([0; 4])
+            } else {
+                let color = [0, 1, 2].map(|channel| ((sum[channel] + sum[3] / 2) / sum[3]) as u8);
+                Rgba([color[0], color[1], color[2], ((sum[3] + count / 2) / count) as u8])
+            };
+            overview.put_pixel(x, y, pixel);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rebuild_and_update_tiles() {
+        let mut canvas = RgbaImage::from_pixel(1024, 512, Rgba([0, 0, 255, 255]));
+        let mut navigator = Navigator::new();
+        assert!(navigator.needs_rebuild((1024, 512), 256, 1));
+        navigator.rebuild(&canvas, 256, 1, 3);
+        assert_eq!(navigator.scale(), 0.25);
+        assert_eq!(navigator.generation(), 3);
+        assert!(!navigator.needs_rebuild((1024, 512), 256, 1));
+        assert!(navigator.needs_rebuild((1024, 512), 256, 2));
+
+        // 書き込まれたタイルの範囲だけ縮小し直す
+        for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+            if x >= 256 && y < 256 {
+                *pixel = Rgba([255, 0, 0, 255]);
+            }
+        }
+        navigator.update_tiles(&canvas, &[TileCoord { x: 1, y: 0 }], 5);
+        let image = navigator.render(&[(-10.0, -10.0); 4]);
+        assert_eq!(image.dimensions(), (256, 128));
+        assert_eq!(image.get_pixel(70, 10), &Rgba([255, 0, 0, 255]));
+        // 渡していないタイルは古いまま
+        assert_eq!(image.get_pixel(140, 10), &Rgba([0, 0, 255, 255]));
+        assert_eq!(navigator.generation(), 5);
+    }
+
+    #[test]
+    fn test_render_viewport_frame() {
+        let mut canvas = RgbaImage::new(64, 32);
+        canvas.put_pixel(0, 0, Rgba([255, 255, 255, 128]));
+        canvas.put_pixel(1, 0, Rgba([0, 0, 0, 0]));
+        let mut navigator = Navigator::new();
+        navigator.rebuild(&canvas, 32, 0, 0);
+        let image = navigator.render(&[(8.0, 8.0), (24.0, 8.0), (24.0, 24.0), (8.0, 24.0)]);
+        assert_eq!(image.dimensions(), (32, 16));
+        // 透明な画素と平均しても色は薄まらない
+        assert_eq!(&image.get_pixel(0, 0).0[..3], &[255, 255, 255]);
+        assert_eq!(image.get_pixel(8, 4), &NAVIGATOR_FRAME_COLOR);
+        assert_eq!(image.get_pixel(12, 12), &NAVIGATOR_FRAME_COLOR);
+        assert_eq!(image.get_pixel(8, 8), &Rgba([0, 0, 0, 0]));
+    }
+}
@@ -0,0 +1,139 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::formats::blend_over;
+
+/// 表示用オーバーレイの設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OverlaySettings {
+    /// 透明部分に市松模様を表示
+    pub checkerboard: bool,
+    /// 市松模様のマス目の大きさ（表示上のピクセル）
+    pub checker_size: u32,
+    pub checker_colors: [[u8; 3]; 2],
+    /// 拡大率が `grid_min_zoom` 以上のときにピクセルグリッドを表示
+    pub pixel_grid: bool,
+    pub grid_min_zoom: u32,
+    /// RGBA（アルファで下の画像と合成）
+    pub grid_color: [u8; 4],
+    /// キャンバスの外周に枠線を表示（出力はその分だけ大きくなる）
+    pub border: bool,
+    pub border_width: u32,
+    pub border_color: [u8; 4],
+    /// クイックマスク中に選択されていない部分へ重ねる色（RGBA）
+    pub quick_mask_color: [u8; 4],
+    /// 最近のストロークの入力から表示までの遅延を棒グラフで表示（遅い環境の診断用）
+    pub latency_graph: bool,
+}
+
+impl Default for OverlaySettings {
+    fn default() -> Self {
+        Self {
+            checkerboard: true,
+            checker_size: 8,
+            checker_colors: [[255, 255, 255], [204, 204, 204]],
+            pixel_grid: true,
+            grid_min_zoom: 8,
+            grid_color: [0, 0, 0, 48],
+            border: true,
+            border_width: 1,
+            border_color: [96, 96, 96, 255],
+            quick_mask_color: [255, 0, 0, 128],
+            latency_graph: false,
+        }
+    }
+}
+
+impl OverlaySettings {
+    /// 枠線の太さ（無効なら 0）
+    pub fn border_size(&self) -> u32 {
+        if self.border { self.border_width } else { 0 }
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if self.checker_size == 0 {
+            return Err("市松模様のマス目は 1 以上である必要があります".to_string());
+        }
+        if self.grid_min_zoom == 0 {
+            return Err("グリッドを表示する拡大率は 1 以上である必要があります".to_string());
+        }
+        Ok(())
+    }
+}
+
+/// 合成済みのキャンバス画像にオーバーレイを重ねた表示用の画像を作る
+///
+/// 画像は最近傍補間で `zoom` 倍に拡大し、下に市松模様、上にピクセルグリッドを描き、
+/// 外周に枠線を付ける。
+pub fn render_view(composite: &RgbaImage, zoom: u32, settings: &OverlaySettings) -> RgbaImage {
+    let zoom = zoom.max(1);
+    let border = settings.border_size();
+    let (width, height) = (composite.width() * zoom, composite.height() * zoom);
+    let show_grid = settings.pixel_grid && zoom >= settings.grid_min_zoom;
+    let checker_size = settings.checker_size.max(1);
+
+    RgbaImage::from_fn(width + border * 2, height + border * 2, |x, y| {
+        if x < border || y < border || x >= width + border || y >= height + border {
+            return Rgba(settings.border_color);
+        }
+        let (vx, vy) = (x - border, y - border);
+
+        let background = if settings.checkerboard {
+            let [r, g, b] = settings.checker_colors[((vx / checker_size + vy / checker_size) % 2) as usize];
+            Rgba([r, g, b, 255])
+        } else {
+            Rgba([0, 0, 0, 0])
+        };
+        let mut pixel = blend_over(background, *composite.get_pixel(vx / zoom, vy / zoom));
+
+        // 各ピクセルの左端・上端に線を引く（右端・下端は枠線または隣のピクセルの線になる）
+        if show_grid && (vx % zoom == 0 || vy % zoom == 0) {
+            pixel = blend_over(pixel, Rgba(settings.grid_color));
+        }
+        pixel
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checkerboard_and_border() {
+        let mut composite = RgbaImage::new(4, 2);
+        composite.put_pixel(3, 1, Rgba([255, 0, 0, 255]));
+        let settings = OverlaySettings { checker_size: 2, ..OverlaySettings::default() };
+
+        let view = render_view(&composite, 1, &settings);
+        assert_eq!(view.dimensions(), (6, 4));
+        assert_eq!(view.get_pixel(0, 0), &Rgba(settings.border_color));
+        // 透明部分は市松模様（2px ごとに色が変わる）
+        assert_eq!(view.get_pixel(1, 1), &Rgba([255, 255, 255, 255]));
+        assert_eq!(view.get_pixel(3, 1), &Rgba([204, 204, 204, 255]));
+        assert_eq!(view.get_pixel(4, 2), &Rgba([255, 0, 0, 255]));
+
+        let plain = render_view(&composite, 1, &OverlaySettings { checkerboard: false, border: false, ..settings });
+        assert_eq!(plain, composite);
+    }
+
+    #[test]
+    fn test_pixel_grid_above_threshold() {
+        let composite = RgbaImage::from_pixel(2, 2, Rgba([255, 255, 255, 255]));
+        let settings = OverlaySettings {
+            checkerboard: false,
+            border: false,
+            grid_min_zoom: 4,
+            grid_color: [0, 0, 0, 255],
+            ..OverlaySettings::default()
+        };
+
+        let below = render_view(&composite, 2, &settings);
+        assert!(below.pixels().all(|p| p == &Rgba([255, 255, 255, 255])));
+
+        let view = render_view(&composite, 4, &settings);
+        assert_eq!(view.dimensions(), (8, 8));
+        assert_eq!(view.get_pixel(4, 1), &Rgba([0, 0, 0, 255]));
+        assert_eq!(view.get_pixel(1, 4), &Rgba([0, 0, 0, 255]));
+        assert_eq!(view.get_pixel(5, 5), &Rgba([255, 255, 255, 255]));
+    }
+}
@@ -0,0 +1,262 @@
+use image::{Rgba, RgbaImage};
+use std::collections::VecDeque;
+use std::time::Instant;
+use crate::formats::blend_over;
+use crate::memory::MemoryUsage;
+use super::latency::GRAPH_THRESHOLDS_MS;
+use super::tiles::{TileCoord, TILE_SIZE};
+
+/// 記録しておく最近のフレームの数（フレーム時間グラフの棒の数）
+pub const PERF_OVERLAY_FRAMES: usize = 60;
+
+/// FPS を求めるのに使う最近のフレーム間隔の数
+const FPS_WINDOW: usize = 20;
+
+/// パネルの大きさと余白（表示上のピクセル）
+const PANEL_WIDTH: u32 = PERF_OVERLAY_FRAMES as u32 * 2 + 8;
+const PANEL_MARGIN: u32 = 4;
+/// 文字の大きさ（3x5 の字形を2倍で描く）
+const GLYPH_SCALE: u32 = 2;
+/// フレーム時間グラフの高さ（1ピクセルが0.5ミリ秒。上端は 30Hz の1フレーム強）
+const GRAPH_HEIGHT: u32 = 72;
+/// メモリの棒の高さと間隔
+const MEMORY_BAR_HEIGHT: u32 = 4;
+const MEMORY_BAR_GAP: u32 = 2;
+
+const PANEL_COLOR: Rgba<u8> = Rgba([0, 0, 0, 160]);
+const TEXT_COLOR: Rgba<u8> = Rgba([255, 255, 255, 255]);
+const DIRTY_COLOR: Rgba<u8> = Rgba([0, 220, 255, 200]);
+/// メモリの棒の色（テクスチャ・ステージングバッファ・CPU側のレイヤー画像）
+const MEMORY_COLORS: [Rgba<u8>; 3] = [Rgba([80, 160, 255, 255]), Rgba([255, 160, 0, 255]), Rgba([200, 120, 255, 255])];
+
+/// 表示画像に重ねる性能オーバーレイの記録（FPS・フレーム時間・前回から書き込まれたタイル）
+#[derive(Debug, Clone, Default)]
+pub struct PerfOverlay {
+    enabled: bool,
+    last_frame: Option<Instant>,
+    /// 表示画像を作った間隔（ミリ秒）
+    intervals: VecDeque<f64>,
+    /// 表示画像を作るのにかかった時間（ミリ秒）
+    frame_times: VecDeque<f64>,
+    /// 前回のフレームまでに見たタイルの世代
+    generation: u64,
+}
+
+impl PerfOverlay {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// 表示の有効・無効を切り替える（切り替えると記録を捨てる）
+    pub fn set_enabled(&mut self, enabled: bool) {
+        if self.enabled != enabled {
+            *self = Self { enabled, ..Self::default() };
+        }
+    }
+
+    /// 表示画像を1枚作ったことを記録
+    pub fn record_frame(&mut self, now: Instant, frame_ms: f64) {
+        if let Some(last) = self.last_frame.replace(now) {
+            push_limited(&mut self.intervals, now.saturating_duration_since(last).as_secs_f64() * 1000.0, FPS_WINDOW);
+        }
+        push_limited(&mut self.frame_times, frame_ms.max(0.0), PERF_OVERLAY_FRAMES);
+    }
+
+    /// 最近のフレーム間隔から求めた FPS（2フレーム目までは None）
+    pub fn fps(&self) -> Option<f64> {
+        let total: f64 = self.intervals.iter().sum();
+        (total > 0.0).then(|| self.intervals.len() as f64 * 1000.0 / total)
+    }
+
+    /// 最近のフレーム時間（古い順）
+    pub fn frame_times(&self) -> impl Iterator<Item = f64> + '_ {
+        self.frame_times.iter().copied()
+    }
+
+    /// タイルの現在の世代を記録し、前回のフレームで記録した世代を返す
+    pub fn swap_generation(&mut self, generation: u64) -> u64 {
+        std::mem::replace(&mut self.generation, generation)
+    }
+}
+
+fn push_limited(samples: &mut VecDeque<f64>, value: f64, limit: usize) {
+    if samples.len() == limit {
+        samples.pop_front();
+    }
+    samples.push_back(value);
+}
+
+/// 表示画像の範囲内だけに色を重ねる
+fn blend_rect(view: &mut RgbaImage, x: i64, y: i64, width: u32, height: u32, color: Rgba<u8>) {
+    let x0 = x.clamp(0, view.width() as i64) as u32;
+    let y0 = y.clamp(0, view.height() as i64) as u32;
+    let x1 = (x + width as i64).clamp(0, view.width() as i64) as u32;
+    let y1 = (y + height as i64).clamp(0, view.height() as i64) as u32;
+    for py in y0..y1 {
+        for px in x0..x1 {
+            let pixel = view.get_pixel_mut(px, py);
+            *pixel = blend_over(*pixel, color);
+        }
+    }
+}
+
+/// 3x5 の字形（各行の下位3ビットが左から右）。数字と `FPSM.` だけを持つ
+fn glyph(c: char) -> Option<[u8; 5]> {
+    Some(match c {
+        '0' => [0b111, 0b101, 0b101, 0b101, 0b111],
+        '1' => [0b010, 0b110, 0b010, 0b010, 0b111],
+        '2' => [0b111, 0b001, 0b111, 0b100, 0b111],
+        '3' => [0b111, 0b001, 0b111, 0b001, 0b111],
+        '4' => [0b101, 0b101, 0b111, 0b001, 0b001],
+        '5' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        '6' => [0b111, 0b100, 0b111, 0b101, 0b111],
+        '7' => [0b111, 0b001, 0b010, 0b010, 0b010],
+        '8' => [0b111, 0b101, 0b111, 0b101, 0b111],
+        '9' => [0b111, 0b101, 0b111, 0b001, 0b111],
+        'F' => [0b111, 0b100, 0b110, 0b100, 0b100],
+        'P' => [0b111, 0b101, 0b111, 0b100, 0b100],
+        'S' => [0b111, 0b100, 0b111, 0b001, 0b111],
+        'M' => [0b101, 0b111, 0b111, 0b101, 0b101],
+        '.' => [0b000, 0b000, 0b000, 0b000, 0b010],
+        _ => return None,
+    })
+}
+
+/// 文字列を描く（字形のない文字は空白）
+fn draw_text(view: &mut RgbaImage, x: i64, y: i64, text: &str) {
+    for (index, c) in text.chars().enumerate() {
+        let Some(rows) = glyph(c) else { continue };
+        let left = x + (index as u32 * 4 * GLYPH_SCALE) as i64;
+        for (row, bits) in rows.iter().enumerate() {
+            for column in 0..3 {
+                if bits & (0b100 >> column) != 0 {
+                    let px = left + (column * GLYPH_SCALE) as i64;
+                    let py = y + (row as u32 * GLYPH_SCALE) as i64;
+                    blend_rect(view, px, py, GLYPH_SCALE, GLYPH_SCALE, TEXT_COLOR);
+                }
+            }
+        }
+    }
+}
+
+/// フレーム時間の色（速い順に緑・黄・赤。遅延グラフと同じ境目）
+fn frame_color(ms: f64) -> Rgba<u8> {
+    if ms <= GRAPH_THRESHOLDS_MS.0 {
+        Rgba([0, 200, 0, 220])
+    } else if ms <= GRAPH_THRESHOLDS_MS.1 {
+        Rgba([230, 200, 0, 220])
+    } else {
+        Rgba([230, 0, 0, 220])
+    }
+}
+
+/// 性能オーバーレイを表示画像に重ねる
+///
+/// 前回のフレームから書き込まれたタイル（`dirty`）の枠を描き、右上のパネルに FPS と最新のフレーム時間、
+/// フレーム時間のグラフ、予算に対するメモリ使用量の棒（テクスチャ・ステージングバッファ・CPU側の画像）を描く。
+/// `origin` はキャンバスの左上の表示画像上の位置（枠線の太さ）、`zoom` は表示の拡大率。
+pub fn render_perf_overlay(
+    view: &mut RgbaImage,
+    overlay: &PerfOverlay,
+    dirty: &[TileCoord],
+    origin: u32,
+    zoom: u32,
+    memory: &MemoryUsage,
+    budget_bytes: u64,
+) {
+    let tile = TILE_SIZE * zoom.max(1);
+    for coord in dirty {
+        let x = origin as i64 + coord.x as i64 * tile as i64;
+        let y = origin as i64 + coord.y as i64 * tile as i64;
+        blend_rect(view, x, y, tile, 1, DIRTY_COLOR);
+        blend_rect(view, x, y + tile as i64 - 1, tile, 1, DIRTY_COLOR);
+        blend_rect(view, x, y + 1, 1, tile.saturating_sub(2), DIRTY_COLOR);
+        blend_rect(view, x + tile as i64 - 1, y + 1, 1, tile.saturating_sub(2), DIRTY_COLOR);
+    }
+
+    let line_height = 5 * GLYPH_SCALE;
+    let panel_height = 4 + line_height + 4 + GRAPH_HEIGHT + 4 + 3 * (MEMORY_BAR_HEIGHT + MEMORY_BAR_GAP) + 2;
+    let left = view.width() as i64 - (PANEL_WIDTH + PANEL_MARGIN) as i64;
+    let top = PANEL_MARGIN as i64;
+    blend_rect(view, left, top, PANEL_WIDTH, panel_height, PANEL_COLOR);
+
+    let fps = overlay.fps().map(|fps| format!("{:.0}FPS", fps)).unwrap_or_else(|| "FPS".to_string());
+    let last_ms = overlay.frame_times().last().map(|ms| format!("{:.1}MS", ms)).unwrap_or_default();
+    draw_text(view, left + 4, top + 4, &fps);
+    draw_text(view, left + 4 + (PANEL_WIDTH / 2) as i64, top + 4, &last_ms);
+
+    // フレーム時間（新しいフレームが右端）。60Hz の1フレームの位置に線を引く
+    let graph_top = top + 4 + line_height as i64 + 4;
+    let graph_bottom = graph_top + GRAPH_HEIGHT as i64;
+    let budget_line = graph_bottom - (GRAPH_THRESHOLDS_MS.0 * 2.0).round() as i64;
+    blend_rect(view, left + 4, budget_line, PANEL_WIDTH - 8, 1, Rgba([255, 255, 255, 96]));
+    let frames: Vec<f64> = overlay.frame_times().collect();
+    let graph_left = left + 4 + ((PERF_OVERLAY_FRAMES - frames.len()) * 2) as i64;
+    for (index, ms) in frames.iter().enumerate() {
+        let height = ((ms * 2.0).ceil() as u32).clamp(1, GRAPH_HEIGHT);
+        blend_rect(view, graph_left + index as i64 * 2, graph_bottom - height as i64, 1, height, frame_color(*ms));
+    }
+
+    let bars_top = graph_bottom + 4;
+    let track = PANEL_WIDTH - 8;
+    let amounts = [memory.texture_bytes, memory.staging_bytes, memory.cpu_layer_bytes];
+    for (index, (bytes, color)) in amounts.iter().zip(MEMORY_COLORS).enumerate() {
+        let y = bars_top + (index as u32 * (MEMORY_BAR_HEIGHT + MEMORY_BAR_GAP)) as i64;
+        let ratio = if budget_bytes == 0 { 1.0 } else { (*bytes as f64 / budget_bytes as f64).min(1.0) };
+        blend_rect(view, left + 4, y, track, MEMORY_BAR_HEIGHT, Rgba([255, 255, 255, 48]));
+        blend_rect(view, left + 4, y, (track as f64 * ratio).round() as u32, MEMORY_BAR_HEIGHT, color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_fps_and_toggle() {
+        let mut overlay = PerfOverlay::new();
+        overlay.set_enabled(true);
+        let start = Instant::now();
+        overlay.record_frame(start, 4.0);
+        assert_eq!(overlay.fps(), None);
+        for i in 1..=FPS_WINDOW as u64 + 5 {
+            overlay.record_frame(start + Duration::from_millis(i * 20), 6.0);
+        }
+        assert!((overlay.fps().unwrap() - 50.0).abs() < 1e-6);
+        assert_eq!(overlay.frame_times().count(), FPS_WINDOW + 6);
+        assert_eq!(overlay.swap_generation(7), 0);
+        assert_eq!(overlay.swap_generation(9), 7);
+
+        // 切り替えると記録を捨てる
+        overlay.set_enabled(false);
+        assert_eq!((overlay.fps(), overlay.frame_times().count()), (None, 0));
+    }
+
+    #[test]
+    fn test_render_panel_and_dirty_tiles() {
+        let white = Rgba([255, 255, 255, 255]);
+        let mut view = RgbaImage::from_pixel(TILE_SIZE * 2 + 2, 200, white);
+        let mut overlay = PerfOverlay::new();
+        overlay.set_enabled(true);
+        overlay.record_frame(Instant::now(), 50.0);
+        let memory = MemoryUsage { texture_bytes: 50, staging_bytes: 0, cpu_layer_bytes: 100 };
+        render_perf_overlay(&mut view, &overlay, &[TileCoord { x: 1, y: 0 }], 1, 1, &memory, 100);
+
+        // 書き込まれたタイルの枠（左上はキャンバスの原点から1タイル右）
+        assert_ne!(view.get_pixel(TILE_SIZE + 1, 150), &white);
+        assert_eq!(view.get_pixel(TILE_SIZE + 3, 150), &white);
+        assert_eq!(view.get_pixel(1, 150), &white);
+
+        // 右上のパネルとグラフの最新の棒（遅いフレームは赤）
+        let right = view.width() - PANEL_MARGIN - 5;
+        let bar = view.get_pixel(right - 1, PANEL_MARGIN + 4 + 5 * GLYPH_SCALE + 4 + GRAPH_HEIGHT - 1);
+        assert!(bar[0] > bar[1] && bar[0] > bar[2]);
+        assert_ne!(view.get_pixel(right, PANEL_MARGIN + 1), &white);
+        assert_eq!(view.get_pixel(right, PANEL_MARGIN - 1), &white);
+    }
+}
@@ -2,6 +2,11 @@ use wgpu::*;
 use log::{info, debug};
 use std::error::Error;
 use std::fmt;
+use crate::shaders::{self, ShaderError};
+use super::color_space::BlendSpace;
+use super::coverage::{CoverageBlend, CoverageBuffer, StrokeCoverage};
+use super::msaa::MsaaTarget;
+use super::texture::ManagedTexture;
 
 /// 描画パイプラインのエラー型
 #[derive(Debug)]
@@ -41,15 +46,21 @@ impl fmt::Display for PipelineError {
 
 impl Error for PipelineError {}
 
+impl From<ShaderError> for PipelineError {
+    fn from(error: ShaderError) -> Self {
+        PipelineError::ShaderCompilationFailed(error.to_string())
+    }
+}
+
 /// 2D描画用の頂点データ
 #[repr(C)]
 #[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
 pub struct Vertex2D {
-    /// 正規化座標 (-1.0 ～ 1.0)
+    /// ドキュメント座標（ピクセル。正規化座標への変換はシェーダーで行う）
     pub position: [f32; 2],
     /// RGBA色 (0.0 ～ 1.0)
     pub color: [f32; 4],
-    /// 線の幅（筆圧対応の準備）
+    /// 線の幅（ピクセル）
     pub line_width: f32,
 }
 
@@ -92,6 +103,86 @@ impl Vertex2D {
     }
 }
 
+/// 描画ごとのユニフォーム（ドキュメント座標から正規化座標への変換に使う）
+///
+/// 頂点はドキュメントのピクセル座標のまま送り、表示行列を掛けたあとに描画先のサイズで
+/// 正規化座標へ変換する。ズーム・パンは表示行列を変えるだけで、頂点を作り直す必要はない。
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct DrawUniforms {
+    /// ドキュメント座標に掛ける表示行列（列優先）
+    pub view: [[f32; 4]; 4],
+    /// 描画先のサイズ（ピクセル）
+    pub canvas_size: [f32; 2],
+    /// WGSL の構造体の境界（16バイト）に合わせるための詰め物
+    _padding: [f32; 2],
+}
+
+impl DrawUniforms {
+    /// 単位行列（表示の変換なし）
+    pub const IDENTITY: [[f32; 4]; 4] = [
+        [1.0, 0.0, 0.0, 0.0],
+        [0.0, 1.0, 0.0, 0.0],
+        [0.0, 0.0, 1.0, 0.0],
+        [0.0, 0.0, 0.0, 1.0],
+    ];
+
+    /// 表示の変換なしで描画先にそのまま描く
+    pub fn new(canvas_size: (u32, u32)) -> Self {
+        Self::with_view(canvas_size, Self::IDENTITY)
+    }
+
+    /// 表示行列を指定して作成
+    pub fn with_view(canvas_size: (u32, u32), view: [[f32; 4]; 4]) -> Self {
+        Self {
+            view,
+            canvas_size: [canvas_size.0.max(1) as f32, canvas_size.1.max(1) as f32],
+            _padding: [0.0; 2],
+        }
+    }
+
+    /// ズームとパンの表示行列（ドキュメント座標を拡大してから平行移動する）
+    pub fn zoom_pan(zoom: f32, pan: (f32, f32)) -> [[f32; 4]; 4] {
+        Self::scale_translate((zoom, zoom), pan)
+    }
+
+    /// 縦横別の拡大と平行移動の表示行列
+    pub fn scale_translate(scale: (f32, f32), translate: (f32, f32)) -> [[f32; 4]; 4] {
+        [
+            [scale.0, 0.0, 0.0, 0.0],
+            [0.0, scale.1, 0.0, 0.0],
+            [0.0, 0.0, 1.0, 0.0],
+            [translate.0, translate.1, 0.0, 1.0],
+        ]
+    }
+
+    /// シェーダーと同じ変換をCPUで行う（確認用）
+    pub fn to_normalized(&self, position: [f32; 2]) -> [f32; 2] {
+        let m = &self.view;
+        let x = m[0][0] * position[0] + m[1][0] * position[1] + m[3][0];
+        let y = m[0][1] * position[0] + m[1][1] * position[1] + m[3][1];
+        [x / self.canvas_size[0] * 2.0 - 1.0, 1.0 - y / self.canvas_size[1] * 2.0]
+    }
+}
+
+/// 頂点バッファの初期容量（頂点数）
+pub const INITIAL_VERTEX_CAPACITY: usize = 10000;
+
+/// 頂点バッファの上限の既定値（これを超えるストロークは複数回に分けて描画する）
+pub const DEFAULT_VERTEX_LIMIT: usize = 262_144;
+
+/// 設定できる頂点バッファの上限の範囲
+///
+/// 上限は wgpu の既定の `max_buffer_size`（256 MiB）に収まる頂点数。
+pub const MIN_VERTEX_LIMIT: usize = 1024;
+pub const MAX_VERTEX_LIMIT: usize = (256 << 20) / std::mem::size_of::<Vertex2D>();
+
+/// デバイスの `max_buffer_size` に収まる頂点数の上限
+pub fn max_vertex_limit(limits: &Limits) -> usize {
+    let fits = usize::try_from(limits.max_buffer_size / std::mem::size_of::<Vertex2D>() as u64).unwrap_or(usize::MAX);
+    fits.clamp(MIN_VERTEX_LIMIT, MAX_VERTEX_LIMIT)
+}
+
 /// 描画ストローク（連続する点のデータ）
 #[derive(Debug, Clone)]
 pub struct DrawStroke {
@@ -127,65 +218,173 @@ impl DrawStroke {
         self.is_closed = true;
     }
 
-    /// 三角形データに変換（線分の描画用）
+    /// 三角形データに変換（インデックスを展開した頂点列。CPU 側での確認用）
     pub fn to_triangles(&self) -> Vec<Vertex2D> {
+        self.to_mesh().triangles()
+    }
+
+    /// インデックス付きのメッシュに変換（線分の描画用）
+    pub fn to_mesh(&self) -> StrokeMesh {
+        self.mesh_from(0)
+    }
+
+    /// 指定した線分以降だけをメッシュに変換（線分 i は点 i と点 i + 1 を結ぶ）
+    pub fn mesh_from(&self, first_segment: usize) -> StrokeMesh {
+        let mut mesh = StrokeMesh::default();
         if self.points.len() < 2 {
-            return Vec::new();
+            return mesh;
         }
 
-        let mut triangles = Vec::new();
-        
-        for i in 0..self.points.len() - 1 {
-            let p1 = &self.points[i];
-            let p2 = &self.points[i + 1];
-            
-            // 線分の方向ベクトルを計算
-            let dx = p2.position[0] - p1.position[0];
-            let dy = p2.position[1] - p1.position[1];
-            let length = (dx * dx + dy * dy).sqrt();
-            
-            if length < 1e-6 {
-                continue; // 長さがゼロの線分はスキップ
+        for pair in self.points[first_segment.min(self.points.len() - 1)..].windows(2) {
+            if let Some(quad) = Self::segment_quad(&pair[0], &pair[1]) {
+                mesh.push_quad(quad);
             }
-            
-            // 法線ベクトル（線分に垂直）
-            let nx = -dy / length;
-            let ny = dx / length;
-            
-            // 線の幅を考慮した4つの頂点を計算
-            let half_width1 = p1.line_width * 0.001; // 正規化座標での幅調整
-            let half_width2 = p2.line_width * 0.001;
-            
-            let v1 = Vertex2D::new(
-                p1.position[0] + nx * half_width1,
-                p1.position[1] + ny * half_width1,
-                p1.color,
-                p1.line_width,
-            );
-            let v2 = Vertex2D::new(
-                p1.position[0] - nx * half_width1,
-                p1.position[1] - ny * half_width1,
-                p1.color,
-                p1.line_width,
-            );
-            let v3 = Vertex2D::new(
-                p2.position[0] + nx * half_width2,
-                p2.position[1] + ny * half_width2,
-                p2.color,
-                p2.line_width,
-            );
-            let v4 = Vertex2D::new(
-                p2.position[0] - nx * half_width2,
-                p2.position[1] - ny * half_width2,
-                p2.color,
-                p2.line_width,
-            );
-            
-            // 2つの三角形を追加（四角形を構成）
-            triangles.extend_from_slice(&[v1, v2, v3, v2, v4, v3]);
         }
+        mesh
+    }
+
+    /// 1つの線分を四角形の4頂点にする。長さがゼロの線分は None
+    fn segment_quad(p1: &Vertex2D, p2: &Vertex2D) -> Option<[Vertex2D; 4]> {
+        // 線分の方向ベクトルを計算
+        let dx = p2.position[0] - p1.position[0];
+        let dy = p2.position[1] - p1.position[1];
+        let length = (dx * dx + dy * dy).sqrt();
+        
+        if length < 1e-6 {
+            return None; // 長さがゼロの線分はスキップ
+        }
+        
+        // 法線ベクトル（線分に垂直）
+        let nx = -dy / length;
+        let ny = dx / length;
         
-        triangles
+        // 線の幅を考慮した4つの頂点を計算
+        let half_width1 = p1.line_width * 0.5;
+        let half_width2 = p2.line_width * 0.5;
+        
+        let v1 = Vertex2D::new(
+            p1.position[0] + nx * half_width1,
+            p1.position[1] + ny * half_width1,
+            p1.color,
+            p1.line_width,
+        );
+        let v2 = Vertex2D::new(
+            p1.position[0] - nx * half_width1,
+            p1.position[1] - ny * half_width1,
+            p1.color,
+            p1.line_width,
+        );
+        let v3 = Vertex2D::new(
+            p2.position[0] + nx * half_width2,
+            p2.position[1] + ny * half_width2,
+            p2.color,
+            p2.line_width,
+        );
+        let v4 = Vertex2D::new(
+            p2.position[0] - nx * half_width2,
+            p2.position[1] - ny * half_width2,
+            p2.color,
+            p2.line_width,
+        );
+        
+        Some([v1, v2, v3, v4])
+    }
+}
+
+/// インデックス付きの三角形メッシュ（線分ごとに4頂点・6インデックス）
+#[derive(Debug, Clone, Default)]
+pub struct StrokeMesh {
+    pub vertices: Vec<Vertex2D>,
+    pub indices: Vec<u32>,
+}
+
+impl StrokeMesh {
+    /// 四角形の頂点の並び（v1, v2, v3, v4）から2つの三角形を作るインデックス
+    const QUAD_INDICES: [u32; 6] = [0, 1, 2, 1, 3, 2];
+
+    pub(crate) fn push_quad(&mut self, quad: [Vertex2D; 4]) {
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&quad);
+        self.indices.extend(Self::QUAD_INDICES.iter().map(|i| base + i));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indices.is_empty()
+    }
+
+    /// 頂点のアルファ（被覆率を溜めるときの流量）をすべて `flow` にする
+    pub fn with_flow(mut self, flow: f32) -> Self {
+        for vertex in &mut self.vertices {
+            vertex.color[3] = flow;
+        }
+        self
+    }
+
+    /// 頂点数が `max_vertices` 以下になるよう四角形単位で分割（インデックスは各メッシュの先頭から振り直す）
+    pub fn chunks(&self, max_vertices: usize) -> Vec<StrokeMesh> {
+        let quads_per_chunk = (max_vertices / 4).max(1);
+        self.vertices.chunks(quads_per_chunk * 4)
+            .map(|vertices| {
+                let mut mesh = StrokeMesh::default();
+                for quad in vertices.chunks_exact(4) {
+                    mesh.push_quad([quad[0], quad[1], quad[2], quad[3]]);
+                }
+                mesh
+            })
+            .collect()
+    }
+
+    /// インデックスを展開した三角形の頂点列
+    pub fn triangles(&self) -> Vec<Vertex2D> {
+        self.indices.iter().map(|&i| self.vertices[i as usize]).collect()
+    }
+}
+
+/// 描画中のストロークを少しずつ三角形分割する（前回以降に追加された線分だけを変換する）
+#[derive(Debug, Clone, Default)]
+pub struct StrokeTessellator {
+    /// 変換済みの線分の数
+    segments: usize,
+}
+
+impl StrokeTessellator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 前回の呼び出し以降に追加された線分のメッシュ
+    pub fn next_mesh(&mut self, stroke: &DrawStroke) -> StrokeMesh {
+        let mesh = stroke.mesh_from(self.segments);
+        self.segments = stroke.points.len().saturating_sub(1).max(self.segments);
+        mesh
+    }
+
+    pub fn segments(&self) -> usize {
+        self.segments
+    }
+}
+
+/// ストロークの描画先の設定（パイプラインの作り分けに使う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct StrokeTarget {
+    /// 描画先のビューの形式（sRGB で合成する場合は sRGB の付かない形式）
+    format: TextureFormat,
+    sample_count: u32,
+    blend_space: BlendSpace,
+}
+
+impl StrokeTarget {
+    fn new(layer_format: TextureFormat, sample_count: u32, blend_space: BlendSpace) -> Self {
+        let format = match blend_space {
+            BlendSpace::Linear => layer_format,
+            BlendSpace::Srgb => layer_format.remove_srgb_suffix(),
+        };
+        Self { format, sample_count, blend_space }
+    }
+
+    /// ブラシの色をシェーダーで sRGB に変換するか（ハードウェアが変換しないビューに描く場合）
+    fn encode_srgb(&self) -> bool {
+        self.blend_space == BlendSpace::Srgb
     }
 }
 
@@ -193,54 +392,159 @@ impl DrawStroke {
 pub struct BasicDrawPipeline {
     /// 描画パイプライン
     render_pipeline: RenderPipeline,
-    /// 頂点バッファ
+    /// 設定に合わせてパイプラインを作り直すためのシェーダーとレイアウト
+    vertex_shader: ShaderModule,
+    fragment_shader: ShaderModule,
+    pipeline_layout: PipelineLayout,
+    format: TextureFormat,
+    /// 現在の描画先の設定
+    target: StrokeTarget,
+    /// 既定（1x・線形で合成）以外の設定で使うパイプラインと、マルチサンプリングの描画先
+    variant: Option<(RenderPipeline, Option<MsaaTarget>)>,
+    /// 被覆率を溜めてから1回だけ合成する描画（現在の描画先の設定に合わせて作る）
+    coverage: StrokeCoverage,
+    /// 頂点バッファ（描画ごとに作り直さず使い回し、足りなければ上限まで拡張する）
     vertex_buffer: Buffer,
-    /// 最大頂点数
-    max_vertices: usize,
+    /// インデックスバッファ（線分ごとに4頂点・6インデックスなので頂点数の 1.5 倍）
+    index_buffer: Buffer,
+    /// 表示行列・描画先のサイズ（描画ごとに書き換える）
+    uniform_buffer: Buffer,
+    uniform_bind_group: BindGroup,
+    /// 現在のバッファに入る頂点数
+    vertex_capacity: usize,
+    /// バッファを拡張できる頂点数の上限
+    vertex_limit: usize,
 }
 
 impl BasicDrawPipeline {
-    /// 新しい描画パイプラインを作成
-    pub fn new(device: &Device, format: TextureFormat) -> Result<Self, PipelineError> {
+    /// 新しい描画パイプラインを作成（`cache` があれば前回コンパイルした結果を再利用する）
+    pub fn new(device: &Device, format: TextureFormat, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        Self::with_shaders(device, format, cache, Self::vertex_shader_source(), Self::fragment_shader_source())
+    }
+
+    /// 埋め込みのシェーダーの代わりに指定した WGSL で作成（開発時のシェーダー差し替え用）
+    pub fn with_shaders(
+        device: &Device,
+        format: TextureFormat,
+        cache: Option<&PipelineCache>,
+        vertex_source: &str,
+        fragment_source: &str,
+    ) -> Result<Self, PipelineError> {
         info!("[BasicDrawPipeline] 新しいパイプライン作成開始");
 
         // 頂点シェーダー
         let vertex_shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("Vertex Shader"),
-            source: ShaderSource::Wgsl(Self::vertex_shader_source().into()),
+            source: ShaderSource::Wgsl(shaders::compose(vertex_source)?.into()),
         });
 
         // フラグメントシェーダー
         let fragment_shader = device.create_shader_module(ShaderModuleDescriptor {
             label: Some("Fragment Shader"), 
-            source: ShaderSource::Wgsl(Self::fragment_shader_source().into()),
+            source: ShaderSource::Wgsl(shaders::compose(fragment_source)?.into()),
         });
 
         debug!("[BasicDrawPipeline] シェーダー作成完了");
 
+        // ユニフォーム（描画ごとに内容だけを書き換え、バインドグループは使い回す）
+        let uniform_bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Draw Uniform Bind Group Layout"),
+            entries: &[BindGroupLayoutEntry {
+                binding: 0,
+                visibility: ShaderStages::VERTEX,
+                ty: BindingType::Buffer {
+                    ty: BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Draw Uniform Buffer"),
+            size: std::mem::size_of::<DrawUniforms>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let uniform_bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Draw Uniform Bind Group"),
+            layout: &uniform_bind_group_layout,
+            entries: &[BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+
         // パイプラインレイアウト
         let render_pipeline_layout =
             device.create_pipeline_layout(&PipelineLayoutDescriptor {
                 label: Some("Draw Pipeline Layout"),
-                bind_group_layouts: &[],
+                bind_group_layouts: &[&uniform_bind_group_layout],
                 push_constant_ranges: &[],
             });
 
         // レンダーパイプライン作成
-        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+        let target = StrokeTarget::new(format, 1, BlendSpace::Linear);
+        let render_pipeline = Self::create_render_pipeline(
+            device, &render_pipeline_layout, &vertex_shader, &fragment_shader, target, cache,
+        );
+
+        let coverage = StrokeCoverage::new(
+            device, &vertex_shader, &render_pipeline_layout, target.format, target.sample_count, target.encode_srgb(), cache,
+        )?;
+
+        debug!("[BasicDrawPipeline] レンダーパイプライン作成完了");
+
+        // 頂点・インデックスバッファ作成（初期容量10000頂点）
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, INITIAL_VERTEX_CAPACITY);
+
+        info!("[BasicDrawPipeline] パイプライン作成完了: {}頂点（上限 {}）", INITIAL_VERTEX_CAPACITY, DEFAULT_VERTEX_LIMIT);
+
+        Ok(Self {
+            render_pipeline,
+            vertex_shader,
+            fragment_shader,
+            pipeline_layout: render_pipeline_layout,
+            format,
+            target,
+            variant: None,
+            coverage,
+            vertex_buffer,
+            index_buffer,
+            uniform_buffer,
+            uniform_bind_group,
+            vertex_capacity: INITIAL_VERTEX_CAPACITY,
+            vertex_limit: DEFAULT_VERTEX_LIMIT,
+        })
+    }
+
+    fn create_render_pipeline(
+        device: &Device,
+        layout: &PipelineLayout,
+        vertex_shader: &ShaderModule,
+        fragment_shader: &ShaderModule,
+        target: StrokeTarget,
+        cache: Option<&PipelineCache>,
+    ) -> RenderPipeline {
+        // 差し替えたシェーダーが定数を宣言していなくても既定の設定では作れるよう、必要なときだけ指定する
+        let constants: &[(&str, f64)] = if target.encode_srgb() { &[("encode_srgb", 1.0)] } else { &[] };
+        device.create_render_pipeline(&RenderPipelineDescriptor {
             label: Some("Basic Draw Pipeline"),
-            layout: Some(&render_pipeline_layout),
+            layout: Some(layout),
             vertex: VertexState {
-                module: &vertex_shader,
+                module: vertex_shader,
                 entry_point: Some("vs_main"),
                 buffers: &[Vertex2D::desc()],
-                compilation_options: PipelineCompilationOptions::default(),
+                compilation_options: PipelineCompilationOptions {
+                    constants,
+                    ..Default::default()
+                },
             },
             fragment: Some(FragmentState {
-                module: &fragment_shader,
+                module: fragment_shader,
                 entry_point: Some("fs_main"),
                 targets: &[Some(ColorTargetState {
-                    format,
+                    format: target.format,
                     blend: Some(BlendState {
                         color: BlendComponent {
                             src_factor: BlendFactor::SrcAlpha,
@@ -268,41 +572,155 @@ impl BasicDrawPipeline {
             },
             depth_stencil: None,
             multisample: MultisampleState {
-                count: 1,
+                count: target.sample_count,
                 mask: !0,
                 alpha_to_coverage_enabled: false,
             },
             multiview: None,
-            cache: None,
-        });
+            cache,
+        })
+    }
 
-        debug!("[BasicDrawPipeline] レンダーパイプライン作成完了");
+    /// ストロークのサンプル数（1 はマルチサンプリングなし）
+    pub fn sample_count(&self) -> u32 {
+        self.target.sample_count
+    }
+
+    /// ストロークを合成する色空間
+    pub fn blend_space(&self) -> BlendSpace {
+        self.target.blend_space
+    }
+
+    /// ストロークのサンプル数を変更（デバイスが対応している数であること）
+    pub fn set_sample_count(&mut self, device: &Device, sample_count: u32, cache: Option<&PipelineCache>) -> Result<(), PipelineError> {
+        self.set_target(device, StrokeTarget { sample_count, ..self.target }, cache)?;
+        info!("[BasicDrawPipeline] ストロークのサンプル数: {}x", sample_count);
+        Ok(())
+    }
+
+    /// ストロークを合成する色空間を変更
+    pub fn set_blend_space(&mut self, device: &Device, blend_space: BlendSpace, cache: Option<&PipelineCache>) -> Result<(), PipelineError> {
+        self.set_target(device, StrokeTarget::new(self.format, self.target.sample_count, blend_space), cache)?;
+        info!("[BasicDrawPipeline] ストロークの合成: {:?}", blend_space);
+        Ok(())
+    }
+
+    fn set_target(&mut self, device: &Device, target: StrokeTarget, cache: Option<&PipelineCache>) -> Result<(), PipelineError> {
+        if target == self.target {
+            return Ok(());
+        }
+        self.variant = if target == StrokeTarget::new(self.format, 1, BlendSpace::Linear) {
+            None
+        } else {
+            let pipeline = Self::create_render_pipeline(
+                device, &self.pipeline_layout, &self.vertex_shader, &self.fragment_shader, target, cache,
+            );
+            let msaa = (target.sample_count > 1)
+                .then(|| MsaaTarget::new(device, target.format, target.sample_count, cache))
+                .transpose()?;
+            Some((pipeline, msaa))
+        };
+        self.coverage = StrokeCoverage::new(
+            device, &self.vertex_shader, &self.pipeline_layout, target.format, target.sample_count, target.encode_srgb(), cache,
+        )?;
+        self.target = target;
+        Ok(())
+    }
+
+    /// 描画に使い回している作業用のテクスチャ（マルチサンプリング・被覆率）のメモリ使用量（バイト）
+    pub fn scratch_memory_size(&self) -> u64 {
+        let msaa = self.variant.as_ref()
+            .and_then(|(_, msaa)| msaa.as_ref())
+            .map_or(0, |msaa| msaa.memory_size());
+        msaa + self.coverage.scratch_memory_size()
+    }
 
-        // 頂点バッファ作成（最大10000頂点）
-        let max_vertices = 10000;
+    /// 作業用のテクスチャを解放し、解放したバイト数を返す（次の描画で作り直す）
+    pub fn release_scratch_textures(&mut self) -> u64 {
+        let mut released = self.coverage.release_scratch();
+        if let Some(msaa) = self.variant.as_mut().and_then(|(_, msaa)| msaa.as_mut()) {
+            released += msaa.memory_size();
+            msaa.release_texture();
+        }
+        released
+    }
+
+    /// 描画中のストロークに使う被覆率バッファを作成（現在のサンプル数に合わせる）
+    pub fn create_coverage_buffer(&self, device: &Device, size: (u32, u32)) -> CoverageBuffer {
+        CoverageBuffer::new(device, size, self.coverage.sample_count())
+    }
+
+    /// 1回で描き終えるストロークに使い回す被覆率バッファを取り出す（使い終わったら `put_coverage_buffer` で戻す）
+    pub fn take_coverage_buffer(&mut self, device: &Device, size: (u32, u32)) -> CoverageBuffer {
+        self.coverage.take_scratch(device, size)
+    }
+
+    pub fn put_coverage_buffer(&mut self, buffer: CoverageBuffer) {
+        self.coverage.put_scratch(buffer);
+    }
+
+    fn create_buffers(device: &Device, vertex_capacity: usize) -> (Buffer, Buffer) {
+        let vertex_capacity = vertex_capacity.min(max_vertex_limit(&device.limits()));
         let vertex_buffer = device.create_buffer(&BufferDescriptor {
             label: Some("Vertex Buffer"),
-            size: (max_vertices * std::mem::size_of::<Vertex2D>()) as u64,
+            size: (vertex_capacity * std::mem::size_of::<Vertex2D>()) as u64,
             usage: BufferUsages::VERTEX | BufferUsages::COPY_DST,
             mapped_at_creation: false,
         });
+        let index_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Index Buffer"),
+            size: (vertex_capacity / 4 * 6 * std::mem::size_of::<u32>()) as u64,
+            usage: BufferUsages::INDEX | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        (vertex_buffer, index_buffer)
+    }
 
-        info!("[BasicDrawPipeline] パイプライン作成完了: 最大{}頂点", max_vertices);
+    pub fn vertex_capacity(&self) -> usize {
+        self.vertex_capacity
+    }
 
-        Ok(Self {
-            render_pipeline,
-            vertex_buffer,
-            max_vertices,
-        })
+    /// 1回の描画で使える頂点数の上限（これを超えるメッシュは分割して描画する）
+    pub fn vertex_limit(&self) -> usize {
+        self.vertex_limit
+    }
+
+    /// 頂点バッファの上限を変更（現在のバッファが上限より大きければ上限まで縮める）
+    ///
+    /// 上限はデバイスの `max_buffer_size` に収まるよう切り詰められる。
+    pub fn set_vertex_limit(&mut self, device: &Device, limit: usize) {
+        self.vertex_limit = limit.clamp(MIN_VERTEX_LIMIT, max_vertex_limit(&device.limits()));
+        if self.vertex_capacity > self.vertex_limit {
+            self.resize_buffers(device, self.vertex_limit);
+        }
+        debug!("[BasicDrawPipeline] 頂点バッファの上限: {}", self.vertex_limit);
+    }
+
+    /// 必要な頂点数が入るようバッファを拡張（倍々に増やし、上限で頭打ちにする）
+    fn ensure_capacity(&mut self, device: &Device, vertices: usize) {
+        if vertices <= self.vertex_capacity {
+            return;
+        }
+        let capacity = vertices.next_power_of_two().clamp(self.vertex_capacity, self.vertex_limit);
+        self.resize_buffers(device, capacity);
+    }
+
+    fn resize_buffers(&mut self, device: &Device, capacity: usize) {
+        let (vertex_buffer, index_buffer) = Self::create_buffers(device, capacity);
+        self.vertex_buffer = vertex_buffer;
+        self.index_buffer = index_buffer;
+        info!("[BasicDrawPipeline] 頂点バッファを変更: {} → {} 頂点", self.vertex_capacity, capacity);
+        self.vertex_capacity = capacity;
     }
 
     /// 2点間の線を描画
     pub fn draw_line(
-        &self,
-        _device: &Device,
+        &mut self,
+        device: &Device,
         queue: &Queue,
         encoder: &mut CommandEncoder,
         target_view: &TextureView,
+        uniforms: &DrawUniforms,
         start: (f32, f32),
         end: (f32, f32),
         color: [f32; 4],
@@ -314,16 +732,17 @@ impl BasicDrawPipeline {
         stroke.add_point(start.0, start.1, 1.0);
         stroke.add_point(end.0, end.1, 1.0);
 
-        self.draw_stroke(_device, queue, encoder, target_view, &stroke)
+        self.draw_stroke(device, queue, encoder, target_view, uniforms, &stroke)
     }
 
     /// ストローク（連続する点）を描画
     pub fn draw_stroke(
-        &self,
-        _device: &Device,
+        &mut self,
+        device: &Device,
         queue: &Queue,
         encoder: &mut CommandEncoder,
         target_view: &TextureView,
+        uniforms: &DrawUniforms,
         stroke: &DrawStroke,
     ) -> Result<(), PipelineError> {
         debug!("[BasicDrawPipeline] ストローク描画: {} 点", stroke.points.len());
@@ -332,48 +751,184 @@ impl BasicDrawPipeline {
             return Ok(());
         }
 
-        // 三角形データに変換
-        let triangles = stroke.to_triangles();
-        if triangles.is_empty() {
+        // メッシュに変換
+        self.draw_mesh(device, queue, encoder, target_view, uniforms, &stroke.to_mesh())
+    }
+
+    /// 三角形分割済みのメッシュを描画
+    ///
+    /// 頂点バッファは必要に応じて上限まで拡張する。上限を超えるメッシュは描画できないため、
+    /// 呼び出し側で `StrokeMesh::chunks` で分割し、チャンクごとにコマンドを送信する
+    /// （同じバッファへの書き込みは送信前にまとめて実行されるため、1つのエンコーダーには積めない）。
+    /// ユニフォームも同じ理由で、異なる表示行列の描画は別々に送信する。
+    pub fn draw_mesh(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target_view: &TextureView,
+        uniforms: &DrawUniforms,
+        mesh: &StrokeMesh,
+    ) -> Result<(), PipelineError> {
+        if mesh.is_empty() {
             return Ok(());
         }
 
-        if triangles.len() > self.max_vertices {
+        if mesh.vertices.len() > self.vertex_limit {
             return Err(PipelineError::InvalidVertexData(
-                format!("頂点数が上限を超えています: {} > {}", triangles.len(), self.max_vertices)
+                format!("頂点数が上限を超えています: {} > {}", mesh.vertices.len(), self.vertex_limit)
             ));
         }
+        self.upload_mesh(device, queue, uniforms, mesh);
 
-        // 頂点データをバッファに書き込み
-        let vertex_data = bytemuck::cast_slice(&triangles);
-        queue.write_buffer(&self.vertex_buffer, 0, vertex_data);
+        let attachment = RenderPassColorAttachment {
+            view: target_view,
+            resolve_target: None,
+            ops: Operations {
+                load: LoadOp::Load, // 既存の内容を保持
+                store: StoreOp::Store,
+            },
+        };
+        Self::record_stroke_pass(encoder, &self.render_pipeline, attachment, &self.uniform_bind_group,
+                                 &self.vertex_buffer, &self.index_buffer, mesh);
+        info!("[BasicDrawPipeline] ストローク描画完了: {} 三角形", mesh.indices.len() / 3);
+        Ok(())
+    }
 
-        // レンダーパスを開始
-        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
-            label: Some("Draw Stroke Pass"),
-            color_attachments: &[Some(RenderPassColorAttachment {
-                view: target_view,
+    /// レイヤーのテクスチャにメッシュを描画
+    ///
+    /// サンプル数が 2 以上ならマルチサンプリングして解決し、sRGB で合成する設定なら
+    /// レイヤーの非 sRGB のビューに描く。分割・送信の制約は `draw_mesh` と同じ。
+    pub fn draw_mesh_to_texture(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &ManagedTexture,
+        uniforms: &DrawUniforms,
+        mesh: &StrokeMesh,
+    ) -> Result<(), PipelineError> {
+        if self.variant.is_none() {
+            return self.draw_mesh(device, queue, encoder, &target.view, uniforms, mesh);
+        }
+        if mesh.is_empty() {
+            return Ok(());
+        }
+        if mesh.vertices.len() > self.vertex_limit {
+            return Err(PipelineError::InvalidVertexData(
+                format!("頂点数が上限を超えています: {} > {}", mesh.vertices.len(), self.vertex_limit)
+            ));
+        }
+        self.upload_mesh(device, queue, uniforms, mesh);
+
+        let encoded_view;
+        let layer_view = match self.target.blend_space {
+            BlendSpace::Linear => &target.view,
+            BlendSpace::Srgb => {
+                encoded_view = target.encoded_view();
+                &encoded_view
+            }
+        };
+        let (pipeline, msaa) = self.variant.as_mut().expect("既定以外の設定");
+        let attachment = match msaa {
+            Some(msaa) => RenderPassColorAttachment {
+                view: msaa.prepare(device, encoder, layer_view, (target.spec.width, target.spec.height)),
+                resolve_target: Some(layer_view),
+                ops: Operations {
+                    load: LoadOp::Load, // 転写したレイヤーの内容を保持
+                    store: StoreOp::Discard,
+                },
+            },
+            None => RenderPassColorAttachment {
+                view: layer_view,
                 resolve_target: None,
                 ops: Operations {
-                    load: LoadOp::Load, // 既存の内容を保持
+                    load: LoadOp::Load,
                     store: StoreOp::Store,
                 },
-            })],
+            },
+        };
+        Self::record_stroke_pass(encoder, pipeline, attachment, &self.uniform_bind_group,
+                                 &self.vertex_buffer, &self.index_buffer, mesh);
+        debug!("[BasicDrawPipeline] ストローク描画完了 ({:?}): {} 三角形", self.target, mesh.indices.len() / 3);
+        Ok(())
+    }
+
+    /// メッシュの被覆率を `coverage` に溜める（頂点のアルファを流量として重ねる）
+    ///
+    /// 分割・送信の制約は `draw_mesh` と同じ。溜め終わったら `composite_coverage` でレイヤーに合成する。
+    pub fn accumulate_coverage(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        coverage: &mut CoverageBuffer,
+        uniforms: &DrawUniforms,
+        mesh: &StrokeMesh,
+    ) -> Result<(), PipelineError> {
+        if mesh.is_empty() {
+            return Ok(());
+        }
+        if mesh.vertices.len() > self.vertex_limit {
+            return Err(PipelineError::InvalidVertexData(
+                format!("頂点数が上限を超えています: {} > {}", mesh.vertices.len(), self.vertex_limit)
+            ));
+        }
+        self.upload_mesh(device, queue, uniforms, mesh);
+        self.coverage.record_accumulate(encoder, coverage, &self.uniform_bind_group,
+                                        &self.vertex_buffer, &self.index_buffer, mesh)
+    }
+
+    /// 溜めた被覆率をレイヤーのテクスチャに合成（色を重ねるか、アルファを削る）
+    ///
+    /// sRGB で色を重ねる設定ならレイヤーの非 sRGB のビューに合成する（アルファを削るだけなら
+    /// どちらのビューでも同じ）。1回の送信で合成できるのは1回だけ。
+    pub fn composite_coverage(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        encoder: &mut CommandEncoder,
+        target: &ManagedTexture,
+        coverage: &CoverageBuffer,
+        blend: CoverageBlend,
+    ) {
+        match (self.target.blend_space, blend) {
+            (BlendSpace::Srgb, CoverageBlend::Paint(_)) => {
+                self.coverage.record_composite(device, queue, encoder, coverage, &target.encoded_view(), blend)
+            }
+            _ => self.coverage.record_composite(device, queue, encoder, coverage, &target.view, blend),
+        }
+    }
+
+    /// 頂点・インデックス・ユニフォームをバッファに書き込み
+    fn upload_mesh(&mut self, device: &Device, queue: &Queue, uniforms: &DrawUniforms, mesh: &StrokeMesh) {
+        self.ensure_capacity(device, mesh.vertices.len());
+        queue.write_buffer(&self.vertex_buffer, 0, bytemuck::cast_slice(&mesh.vertices));
+        queue.write_buffer(&self.index_buffer, 0, bytemuck::cast_slice(&mesh.indices));
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(uniforms));
+    }
+
+    fn record_stroke_pass(
+        encoder: &mut CommandEncoder,
+        pipeline: &RenderPipeline,
+        attachment: RenderPassColorAttachment,
+        uniform_bind_group: &BindGroup,
+        vertex_buffer: &Buffer,
+        index_buffer: &Buffer,
+        mesh: &StrokeMesh,
+    ) {
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Draw Stroke Pass"),
+            color_attachments: &[Some(attachment)],
             depth_stencil_attachment: None,
             timestamp_writes: None,
             occlusion_query_set: None,
         });
-
-        // パイプラインを設定
-        render_pass.set_pipeline(&self.render_pipeline);
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-
-        // 描画
-        render_pass.draw(0..triangles.len() as u32, 0..1);
-
-        drop(render_pass);
-        info!("[BasicDrawPipeline] ストローク描画完了: {} 三角形", triangles.len() / 3);
-        Ok(())
+        render_pass.set_pipeline(pipeline);
+        render_pass.set_bind_group(0, uniform_bind_group, &[]);
+        render_pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+        render_pass.set_index_buffer(index_buffer.slice(..), IndexFormat::Uint32);
+        render_pass.draw_indexed(0..mesh.indices.len() as u32, 0, 0..1);
     }
 
     /// 座標変換：スクリーン座標 -> 正規化座標
@@ -391,8 +946,22 @@ impl BasicDrawPipeline {
     }
 
     /// 頂点シェーダーのソースコード（WGSL）
-    fn vertex_shader_source() -> &'static str {
+    pub(crate) fn vertex_shader_source() -> &'static str {
         r#"
+        #import coords
+        #import color
+
+        // 非 sRGB のビューに描く（sRGB の値のまま合成する）ときは、ブラシの色を手動で変換する
+        override encode_srgb: bool = false;
+
+        struct DrawUniforms {
+            view: mat4x4<f32>,
+            canvas_size: vec2<f32>,
+        }
+
+        @group(0) @binding(0)
+        var<uniform> uniforms: DrawUniforms;
+
         struct VertexInput {
             @location(0) position: vec2<f32>,
             @location(1) color: vec4<f32>,
@@ -409,15 +978,20 @@ impl BasicDrawPipeline {
         fn vs_main(model: VertexInput) -> VertexOutput {
             var out: VertexOutput;
             out.color = model.color;
+            if encode_srgb {
+                out.color = vec4<f32>(linear_to_srgb(model.color.rgb), model.color.a);
+            }
             out.line_width = model.line_width;
-            out.clip_position = vec4<f32>(model.position, 0.0, 1.0);
+            // ドキュメント座標 → 表示座標（ピクセル） → 正規化座標（Y軸反転）
+            let pixel = (uniforms.view * vec4<f32>(model.position, 0.0, 1.0)).xy;
+            out.clip_position = vec4<f32>(pixel_to_clip(pixel, uniforms.canvas_size), 0.0, 1.0);
             return out;
         }
         "#
     }
 
     /// フラグメントシェーダーのソースコード（WGSL）
-    fn fragment_shader_source() -> &'static str {
+    pub(crate) fn fragment_shader_source() -> &'static str {
         r#"
         struct FragmentInput {
             @location(0) color: vec4<f32>,
@@ -429,7 +1003,7 @@ impl BasicDrawPipeline {
             // アンチエイリアシングのための簡単な処理
             var alpha = in.color.a;
             
-            // 線の幅に応じたアルファ調整（将来の拡張用）
+            // 1ピクセルより細い線は幅に応じて薄くする
             if (in.line_width < 1.0) {
                 alpha = alpha * in.line_width;
             }
@@ -481,6 +1055,18 @@ mod tests {
         })
     }
 
+    #[test]
+    fn test_vertex_limit_fits_max_buffer_size() {
+        let limits = Limits::default();
+        assert!((MAX_VERTEX_LIMIT * std::mem::size_of::<Vertex2D>()) as u64 <= limits.max_buffer_size);
+        assert_eq!(max_vertex_limit(&limits), MAX_VERTEX_LIMIT);
+
+        let small = Limits { max_buffer_size: 1 << 20, ..Limits::default() };
+        assert_eq!(max_vertex_limit(&small), (1 << 20) / std::mem::size_of::<Vertex2D>());
+        let tiny = Limits { max_buffer_size: 64, ..Limits::default() };
+        assert_eq!(max_vertex_limit(&tiny), MIN_VERTEX_LIMIT);
+    }
+
     #[test]
     fn test_vertex2d_creation() {
         let vertex = Vertex2D::new(0.5, -0.3, [1.0, 0.0, 0.0, 1.0], 2.0);
@@ -498,11 +1084,56 @@ mod tests {
         assert!(!stroke.is_closed);
 
         stroke.add_point(0.0, 0.0, 1.0);
-        stroke.add_point(1.0, 1.0, 0.5);
+        stroke.add_point(10.0, 0.0, 0.5);
         assert_eq!(stroke.points.len(), 2);
         
         let triangles = stroke.to_triangles();
         assert_eq!(triangles.len(), 6); // 1線分 = 2三角形 = 6頂点
+
+        // 線幅はピクセル単位（筆圧を掛けた幅の半分ずつ両側に広がる）
+        let mesh = stroke.to_mesh();
+        assert_eq!(mesh.vertices[0].position, [0.0, 1.5]);
+        assert_eq!(mesh.vertices[1].position, [0.0, -1.5]);
+        assert_eq!(mesh.vertices[2].position, [10.0, 0.75]);
+    }
+
+    #[test]
+    fn test_mesh_chunks() {
+        let mut stroke = DrawStroke::new([0.0, 0.0, 0.0, 1.0], 2.0);
+        for i in 0..11 {
+            stroke.add_point(i as f32 * 0.1 - 0.5, 0.0, 1.0);
+        }
+        let mesh = stroke.to_mesh();
+        let chunks = mesh.chunks(16);
+        assert_eq!(chunks.iter().map(|c| c.vertices.len()).collect::<Vec<_>>(), vec![16, 16, 8]);
+        assert!(chunks.iter().all(|c| c.indices.iter().all(|&i| (i as usize) < c.vertices.len())));
+
+        let positions = |triangles: Vec<Vertex2D>| triangles.into_iter().map(|v| v.position).collect::<Vec<_>>();
+        let rejoined: Vec<[f32; 2]> = chunks.into_iter().flat_map(|c| positions(c.triangles())).collect();
+        assert_eq!(rejoined, positions(mesh.triangles()));
+    }
+
+    #[test]
+    fn test_incremental_tessellation() {
+        let mut stroke = DrawStroke::new([0.0, 0.0, 0.0, 1.0], 2.0);
+        let mut tessellator = StrokeTessellator::new();
+        assert!(tessellator.next_mesh(&stroke).is_empty());
+
+        stroke.add_point(0.0, 0.0, 1.0);
+        stroke.add_point(0.1, 0.0, 1.0);
+        stroke.add_point(0.2, 0.1, 1.0);
+        let first = tessellator.next_mesh(&stroke);
+        assert_eq!((first.vertices.len(), first.indices.len()), (8, 12));
+
+        stroke.add_point(0.3, 0.1, 1.0);
+        let second = tessellator.next_mesh(&stroke);
+        assert_eq!((second.vertices.len(), second.indices.len()), (4, 6));
+        assert!(tessellator.next_mesh(&stroke).is_empty());
+
+        // 少しずつ変換した結果は一度に変換した結果と同じ
+        let all: Vec<[f32; 2]> = stroke.to_triangles().iter().map(|v| v.position).collect();
+        let incremental: Vec<[f32; 2]> = first.triangles().iter().chain(&second.triangles()).map(|v| v.position).collect();
+        assert_eq!(all, incremental);
     }
 
     #[test]
@@ -527,16 +1158,39 @@ mod tests {
         assert!((top_left_norm.1 - 1.0).abs() < 1e-6);
     }
 
+    #[test]
+    fn test_draw_uniforms() {
+        // WGSL の構造体と同じ大きさ（mat4x4 + vec2 を16バイト境界に揃える）
+        assert_eq!(std::mem::size_of::<DrawUniforms>(), 80);
+
+        let uniforms = DrawUniforms::new((800, 600));
+        assert_eq!(uniforms.to_normalized([400.0, 300.0]), [0.0, 0.0]);
+        assert_eq!(uniforms.to_normalized([0.0, 0.0]), [-1.0, 1.0]);
+        assert_eq!(uniforms.to_normalized([800.0, 600.0]), [1.0, -1.0]);
+
+        // 2倍に拡大して (100, 75) を左上に表示する
+        let zoomed = DrawUniforms::with_view((800, 600), DrawUniforms::zoom_pan(2.0, (-200.0, -150.0)));
+        assert_eq!(zoomed.to_normalized([100.0, 75.0]), [-1.0, 1.0]);
+        assert_eq!(zoomed.to_normalized([300.0, 225.0]), [0.0, 0.0]);
+    }
+
     #[tokio::test]
     async fn test_pipeline_creation() {
         let (device, _queue) = create_test_device();
         let format = TextureFormat::Rgba8UnormSrgb;
         
-        let pipeline = BasicDrawPipeline::new(&device, format);
+        let pipeline = BasicDrawPipeline::new(&device, format, None);
         assert!(pipeline.is_ok());
         
-        let pipeline = pipeline.unwrap();
-        assert_eq!(pipeline.max_vertices, 10000);
+        let mut pipeline = pipeline.unwrap();
+        assert_eq!(pipeline.vertex_capacity(), INITIAL_VERTEX_CAPACITY);
+        
+        // 上限より小さい範囲でだけ拡張される
+        pipeline.set_vertex_limit(&device, 20000);
+        pipeline.ensure_capacity(&device, 15000);
+        assert_eq!(pipeline.vertex_capacity(), 16384);
+        pipeline.ensure_capacity(&device, 40000);
+        assert_eq!(pipeline.vertex_capacity(), 20000);
     }
 
     #[test]
@@ -576,5 +1230,10 @@ mod tests {
         stroke.add_point(1.0, 1.0, 0.8);
         let triangles = stroke.to_triangles();
         assert_eq!(triangles.len(), 12); // 2線分 = 4三角形 = 12頂点
+
+        // インデックス付きでは線分ごとに4頂点を共有する
+        let mesh = stroke.to_mesh();
+        assert_eq!(mesh.vertices.len(), 8);
+        assert_eq!(mesh.indices, vec![0, 1, 2, 1, 3, 2, 4, 5, 6, 5, 7, 6]);
     }
 }
\ No newline at end of file
@@ -0,0 +1,197 @@
+use log::{info, debug, warn};
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+use wgpu::{Adapter, AdapterInfo, Device, Features, PipelineCache, PipelineCacheDescriptor};
+
+/// キャッシュファイルの先頭に置く識別子
+const CACHE_MAGIC: &[u8; 4] = b"KPCC";
+
+/// キャッシュファイルの形式のバージョン
+const CACHE_FORMAT_VERSION: u32 = 1;
+
+/// パイプラインキャッシュの保存先
+///
+/// wgpu のキャッシュデータは作成したアダプター・ドライバーでしか使えないため、
+/// アダプターとドライバーのバージョン、アプリのバージョンを識別文字列として一緒に保存し、
+/// 読み込み時に一致しなければ破棄する（ドライバー更新・GPU 交換時の無効化）。
+/// wgpu がキャッシュに対応しているのは現在 Vulkan のみで、他のバックエンドではドライバー自身の
+/// キャッシュに任せる。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PipelineCacheStore {
+    path: PathBuf,
+    fingerprint: String,
+}
+
+impl PipelineCacheStore {
+    /// アダプター用のキャッシュファイルを `dir` に置く（キャッシュ非対応のバックエンドでは None）
+    pub fn for_adapter(dir: &Path, info: &AdapterInfo) -> Option<Self> {
+        let key = wgpu::util::pipeline_cache_key(info)?;
+        Some(Self {
+            path: dir.join(format!("{}.bin", key)),
+            fingerprint: adapter_fingerprint(info),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 保存されたキャッシュデータを読む
+    ///
+    /// 別のドライバー・アプリのバージョンで作られたものや壊れたものは削除して None を返す。
+    pub fn load(&self) -> Option<Vec<u8>> {
+        let data = match std::fs::read(&self.path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == ErrorKind::NotFound => {
+                debug!("[PipelineCache] キャッシュファイルがありません: {}", self.path.display());
+                return None;
+            }
+            Err(e) => {
+                warn!("[PipelineCache] キャッシュを読み込めません: {}", e);
+                return None;
+            }
+        };
+
+        match decode_cache_file(&data, &self.fingerprint) {
+            Ok(payload) => {
+                info!("[PipelineCache] キャッシュを読み込みました: {} ({} bytes)", self.path.display(), payload.len());
+                Some(payload.to_vec())
+            }
+            Err(reason) => {
+                info!("[PipelineCache] キャッシュを破棄します: {}", reason);
+                if let Err(e) = std::fs::remove_file(&self.path) {
+                    warn!("[PipelineCache] 古いキャッシュを削除できません: {}", e);
+                }
+                None
+            }
+        }
+    }
+
+    /// キャッシュデータを保存（一時ファイルに書いてから置き換えるため、書き込み途中のファイルは残らない）
+    pub fn save(&self, data: &[u8]) -> std::io::Result<()> {
+        if let Some(dir) = self.path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+        let temp_path = self.path.with_extension("tmp");
+        std::fs::write(&temp_path, encode_cache_file(data, &self.fingerprint))?;
+        std::fs::rename(&temp_path, &self.path)?;
+        debug!("[PipelineCache] キャッシュを保存しました: {} ({} bytes)", self.path.display(), data.len());
+        Ok(())
+    }
+}
+
+/// キャッシュを作成した環境の識別文字列（どれかが変わればキャッシュは使えない）
+///
+/// 末尾はアプリのバージョンで、wgpu 自体のバージョンは含まない（wgpu を更新するリリースではアプリのバージョンも上がる）。
+pub fn adapter_fingerprint(info: &AdapterInfo) -> String {
+    format!(
+        "{}|{:04x}:{:04x}|{:?}|{}|{}|kinegraph-{}",
+        info.name, info.vendor, info.device, info.backend, info.driver, info.driver_info,
+        env!("CARGO_PKG_VERSION"),
+    )
+}
+
+/// アダプターがパイプラインキャッシュに対応していれば、デバイスに要求する機能を返す
+pub fn required_features(adapter: &Adapter) -> Features {
+    adapter.features() & Features::PIPELINE_CACHE
+}
+
+/// 保存されたデータからパイプラインキャッシュを作成（デバイスが非対応なら None）
+pub fn create_pipeline_cache(device: &Device, data: Option<&[u8]>) -> Option<PipelineCache> {
+    if !device.features().contains(Features::PIPELINE_CACHE) {
+        return None;
+    }
+    // SAFETY: data は `PipelineCacheStore::load` が返したもので、同じアダプター・ドライバーの
+    // `PipelineCache::get_data` の出力であることを識別文字列とチェックサムで確認している
+    let cache = unsafe {
+        device.create_pipeline_cache(&PipelineCacheDescriptor {
+            label: Some("Kinegraph Pipeline Cache"),
+            data,
+            fallback: true,
+        })
+    };
+    Some(cache)
+}
+
+/// 識別子・バージョン・識別文字列・CRC32 をデータの前に付ける
+fn encode_cache_file(data: &[u8], fingerprint: &str) -> Vec<u8> {
+    let mut out = Vec::with_capacity(data.len() + fingerprint.len() + 16);
+    out.extend_from_slice(CACHE_MAGIC);
+    out.extend_from_slice(&CACHE_FORMAT_VERSION.to_le_bytes());
+    out.extend_from_slice(&(fingerprint.len() as u32).to_le_bytes());
+    out.extend_from_slice(fingerprint.as_bytes());
+    out.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+    out.extend_from_slice(data);
+    out
+}
+
+/// キャッシュファイルを検証してデータ部分を返す（使えない場合は理由）
+fn decode_cache_file<'a>(file: &'a [u8], fingerprint: &str) -> Result<&'a [u8], String> {
+    let read_u32 = |at: usize| -> Result<u32, String> {
+        file.get(at..at + 4)
+            .map(|bytes| u32::from_le_bytes(bytes.try_into().unwrap()))
+            .ok_or_else(|| "ファイルが途中で終わっています".to_string())
+    };
+    if !file.starts_with(CACHE_MAGIC) {
+        return Err("キャッシュファイルではありません".to_string());
+    }
+    let version = read_u32(4)?;
+    if version != CACHE_FORMAT_VERSION {
+        return Err(format!("形式のバージョンが異なります: {}", version));
+    }
+    let fingerprint_len = read_u32(8)? as usize;
+    let stored = file.get(12..12 + fingerprint_len).ok_or("ファイルが途中で終わっています")?;
+    if stored != fingerprint.as_bytes() {
+        return Err(format!("アダプターまたはドライバーが変更されています: {}", String::from_utf8_lossy(stored)));
+    }
+    let expected = read_u32(12 + fingerprint_len)?;
+    let payload = &file[16 + fingerprint_len..];
+    if crc32fast::hash(payload) != expected {
+        return Err("チェックサムが一致しません".to_string());
+    }
+    Ok(payload)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use wgpu::{Backend, DeviceType};
+
+    fn adapter_info(driver_info: &str) -> AdapterInfo {
+        AdapterInfo {
+            name: "Test GPU".to_string(),
+            vendor: 0x10de,
+            device: 0x2684,
+            device_type: DeviceType::DiscreteGpu,
+            driver: "test".to_string(),
+            driver_info: driver_info.to_string(),
+            backend: Backend::Vulkan,
+        }
+    }
+
+    #[test]
+    fn test_invalidates_on_driver_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = PipelineCacheStore::for_adapter(dir.path(), &adapter_info("550.1")).unwrap();
+        assert_eq!(store.load(), None);
+        store.save(b"compiled pipelines").unwrap();
+        assert_eq!(store.load().as_deref(), Some(&b"compiled pipelines"[..]));
+
+        // 同じ GPU でもドライバーが更新されたら使わず、ファイルも削除する
+        let updated = PipelineCacheStore::for_adapter(dir.path(), &adapter_info("560.2")).unwrap();
+        assert_eq!(updated.path(), store.path());
+        assert_eq!(updated.load(), None);
+        assert!(!store.path().exists());
+
+        // 壊れたファイルも破棄する
+        store.save(b"compiled pipelines").unwrap();
+        let mut data = std::fs::read(store.path()).unwrap();
+        *data.last_mut().unwrap() ^= 0xff;
+        std::fs::write(store.path(), data).unwrap();
+        assert_eq!(store.load(), None);
+
+        // wgpu がキャッシュに対応していないバックエンドでは使わない
+        let gl = AdapterInfo { backend: Backend::Gl, ..adapter_info("550.1") };
+        assert!(PipelineCacheStore::for_adapter(dir.path(), &gl).is_none());
+    }
+}
@@ -13,13 +13,32 @@ async fn create_test_environment() -> Result<(DrawingEngine, (u32, u32)), Box<dy
     Ok((engine, canvas_size))
 }
 
+/// GPUがある環境でだけエンジンを初期化する（アダプターがなければ None でテストを省略）
+///
+/// CIなどGPUのない環境でも、GPUに依存しない部分のテストは失敗させずに実行できるようにする。
+async fn gpu_engine() -> Option<DrawingEngine> {
+    let instance = Instance::new(&InstanceDescriptor::default());
+    if instance.request_adapter(&RequestAdapterOptions::default()).await.is_err() {
+        println!("- GPUアダプターがないためスキップ");
+        return None;
+    }
+    let mut engine = DrawingEngine::new();
+    match engine.initialize().await {
+        Ok(()) => Some(engine),
+        Err(e) => {
+            println!("- 描画エンジンを初期化できないためスキップ: {}", e);
+            None
+        }
+    }
+}
+
 #[tokio::test]
 async fn test_draw_single_line() -> Result<(), Box<dyn std::error::Error>> {
-    let (engine, canvas_size) = create_test_environment().await?;
+    let (mut engine, _) = create_test_environment().await?;
     
     // 赤い線を描画（左上から右下へ）
-    let start = engine.screen_to_normalized((50.0, 50.0), canvas_size);
-    let end = engine.screen_to_normalized((450.0, 450.0), canvas_size);
+    let start = (50.0, 50.0);
+    let end = (450.0, 450.0);
     let red_color = [1.0, 0.0, 0.0, 1.0]; // 赤色
     let line_width = 3.0;
     
@@ -40,7 +59,7 @@ async fn test_draw_single_line() -> Result<(), Box<dyn std::error::Error>> {
 
 #[tokio::test]
 async fn test_draw_stroke_with_pressure() -> Result<(), Box<dyn std::error::Error>> {
-    let (engine, canvas_size) = create_test_environment().await?;
+    let (mut engine, _) = create_test_environment().await?;
     
     // 筆圧変化のあるストロークを作成
     let mut stroke = DrawStroke::new([0.0, 1.0, 0.0, 1.0], 5.0); // 緑色、基本幅5px
@@ -52,8 +71,7 @@ async fn test_draw_stroke_with_pressure() -> Result<(), Box<dyn std::error::Erro
         let y = 200.0 + (t * std::f32::consts::PI * 2.0).sin() * 50.0; // サイン波
         let pressure = 0.3 + 0.7 * (t * std::f32::consts::PI).sin().abs(); // 筆圧変化
         
-        let norm_pos = engine.screen_to_normalized((x, y), canvas_size);
-        stroke.add_point(norm_pos.0, norm_pos.1, pressure);
+        stroke.add_point(x, y, pressure);
     }
     
     engine.draw_stroke_to_layer("test_layer", &stroke)?;
@@ -68,7 +86,7 @@ async fn test_draw_stroke_with_pressure() -> Result<(), Box<dyn std::error::Erro
 
 #[tokio::test]
 async fn test_multiple_overlapping_strokes() -> Result<(), Box<dyn std::error::Error>> {
-    let (engine, canvas_size) = create_test_environment().await?;
+    let (mut engine, _) = create_test_environment().await?;
     
     // 複数の重なり合うストロークを描画
     let colors = [
@@ -90,8 +108,7 @@ async fn test_multiple_overlapping_strokes() -> Result<(), Box<dyn std::error::E
             let x = center.0 + (angle + t * std::f32::consts::PI).cos() * radius * t;
             let y = center.1 + (angle + t * std::f32::consts::PI).sin() * radius * t;
             
-            let norm_pos = engine.screen_to_normalized((x, y), canvas_size);
-            stroke.add_point(norm_pos.0, norm_pos.1, 1.0);
+            stroke.add_point(x, y, 1.0);
         }
         
         engine.draw_stroke_to_layer("test_layer", &stroke)?;
@@ -133,20 +150,16 @@ async fn test_coordinate_conversion_accuracy() -> Result<(), Box<dyn std::error:
 
 #[tokio::test]
 async fn test_clear_and_redraw() -> Result<(), Box<dyn std::error::Error>> {
-    let (mut engine, canvas_size) = create_test_environment().await?;
+    let (mut engine, _) = create_test_environment().await?;
     
     // 最初に線を描画
-    let start = engine.screen_to_normalized((100.0, 100.0), canvas_size);
-    let end = engine.screen_to_normalized((400.0, 400.0), canvas_size);
-    engine.draw_line_to_layer("test_layer", start, end, [1.0, 0.0, 0.0, 1.0], 2.0)?;
+    engine.draw_line_to_layer("test_layer", (100.0, 100.0), (400.0, 400.0), [1.0, 0.0, 0.0, 1.0], 2.0)?;
     
     // レイヤーをクリア
     engine.clear_layer_texture("test_layer", None)?; // 透明でクリア
     
     // 新しい線を描画
-    let start2 = engine.screen_to_normalized((400.0, 100.0), canvas_size);
-    let end2 = engine.screen_to_normalized((100.0, 400.0), canvas_size);
-    engine.draw_line_to_layer("test_layer", start2, end2, [0.0, 1.0, 0.0, 1.0], 3.0)?;
+    engine.draw_line_to_layer("test_layer", (400.0, 100.0), (100.0, 400.0), [0.0, 1.0, 0.0, 1.0], 3.0)?;
     
     // 結果を確認
     let pixel_data = engine.get_layer_texture_data("test_layer").await?;
@@ -159,7 +172,7 @@ async fn test_clear_and_redraw() -> Result<(), Box<dyn std::error::Error>> {
 /// パフォーマンステスト：大量のストローク描画
 #[tokio::test]
 async fn test_performance_many_strokes() -> Result<(), Box<dyn std::error::Error>> {
-    let (engine, canvas_size) = create_test_environment().await?;
+    let (mut engine, _) = create_test_environment().await?;
     
     let start_time = std::time::Instant::now();
     
@@ -182,8 +195,7 @@ async fn test_performance_many_strokes() -> Result<(), Box<dyn std::error::Error
             let x = 50.0 + (i % 10) as f32 * 40.0 + t * 100.0;
             let y = 50.0 + (i / 10) as f32 * 40.0 + (t * std::f32::consts::PI * 4.0).sin() * 20.0;
             
-            let norm_pos = engine.screen_to_normalized((x, y), canvas_size);
-            stroke.add_point(norm_pos.0, norm_pos.1, 1.0);
+            stroke.add_point(x, y, 1.0);
         }
         
         engine.draw_stroke_to_layer("test_layer", &stroke)?;
@@ -230,4 +242,383 @@ async fn test_memory_usage() -> Result<(), Box<dyn std::error::Error>> {
     println!("✓ 複数レイヤーメモリテスト成功: {}KB使用", after_memory / 1024);
     
     Ok(())
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn test_infinite_canvas_scroll_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    let layer_ids = vec!["test_layer".to_string()];
+    
+    // 原点付近にドット絵の線を描いてから無限キャンバスを有効化
+    engine.draw_pixel_stroke_to_layer("test_layer", &[(10.0, 10.0), (20.0, 10.0)], [1.0, 0.0, 0.0, 1.0], true)?;
+    engine.set_infinite_canvas(true);
+    
+    // 線が表示範囲外になるまで移動すると、内容はタイルに保持される
+    engine.scroll_infinite_canvas(&layer_ids, (300, 0))?;
+    let image = engine.get_layer_image("test_layer").await?;
+    assert!(image.pixels().all(|p| p[3] == 0));
+    assert_eq!(engine.infinite_canvas().unwrap().tile_count(), 1);
+    
+    // 戻ると表示範囲の位置に合わせて読み込まれる
+    engine.scroll_infinite_canvas(&layer_ids, (5, 0))?;
+    let image = engine.get_layer_image("test_layer").await?;
+    assert_eq!(image.get_pixel(5, 10).0, [255, 0, 0, 255]);
+    assert_eq!(image.get_pixel(4, 10)[3], 0);
+    
+    println!("✓ 無限キャンバスのスクロールテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_long_stroke_beyond_vertex_limit() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    engine.set_stroke_vertex_limit(MIN_VERTEX_LIMIT);
+    
+    // 上限の数倍の頂点になるストロークも分割して描画される
+    let mut stroke = DrawStroke::new([0.0, 0.0, 1.0, 1.0], 3.0);
+    for i in 0..2000 {
+        let t = i as f32 / 1999.0;
+        stroke.add_point(50.0 + t * 400.0, 256.0 + (t * 40.0).sin() * 100.0, 1.0);
+    }
+    assert!(stroke.to_mesh().vertices.len() > MIN_VERTEX_LIMIT * 4);
+    engine.draw_stroke_to_layer("test_layer", &stroke)?;
+    
+    // 最後のチャンクまで描画されている
+    let image = engine.get_layer_image("test_layer").await?;
+    let [x, y] = stroke.points[1998].position;
+    assert!(image.get_pixel(x as u32, y as u32)[3] > 0);
+    
+    println!("✓ 頂点上限を超えるストロークの描画テスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_line_width_in_pixels_and_view() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    
+    // 線幅10pxの水平線は中心から上下5pxの範囲だけを塗る
+    engine.draw_line_to_layer("test_layer", (50.0, 100.0), (450.0, 100.0), [1.0, 0.0, 0.0, 1.0], 10.0)?;
+    let image = engine.get_layer_image("test_layer").await?;
+    assert_eq!(image.get_pixel(250, 96)[3], 255);
+    assert_eq!(image.get_pixel(250, 103)[3], 255);
+    assert_eq!(image.get_pixel(250, 94)[3], 0);
+    assert_eq!(image.get_pixel(250, 106)[3], 0);
+    
+    // 表示行列で2倍に拡大すると、同じ頂点で位置も線幅も2倍になる
+    engine.clear_layer_texture("test_layer", None)?;
+    let mut stroke = DrawStroke::new([0.0, 0.0, 1.0, 1.0], 4.0);
+    stroke.add_point(10.0, 100.0, 1.0);
+    stroke.add_point(200.0, 100.0, 1.0);
+    engine.draw_mesh_to_layer_with_view("test_layer", &stroke.to_mesh(), DrawUniforms::zoom_pan(2.0, (0.0, 0.0)))?;
+    let image = engine.get_layer_image("test_layer").await?;
+    assert_eq!(image.get_pixel(300, 197)[3], 255);
+    assert_eq!(image.get_pixel(300, 95)[3], 0);
+    assert_eq!(image.get_pixel(300, 205)[3], 0);
+    
+    println!("✓ ピクセル単位の線幅と表示行列のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_reload_shader_keeps_pipeline_on_error() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+
+    // 不正な WGSL は拒否され、元のパイプラインで描画を続けられる
+    let result = engine.reload_shader(ShaderId::DrawFragment, "fn fs_main( {".to_string()).await;
+    assert!(matches!(result, Err(PipelineError::ShaderCompilationFailed(_))));
+
+    // 常に緑で塗るフラグメントシェーダーに差し替える
+    let green = r#"
+        struct FragmentInput {
+            @location(0) color: vec4<f32>,
+            @location(1) line_width: f32,
+        }
+
+        @fragment
+        fn fs_main(in: FragmentInput) -> @location(0) vec4<f32> {
+            return vec4<f32>(0.0, 1.0, 0.0, 1.0);
+        }
+    "#;
+    engine.reload_shader(ShaderId::DrawFragment, green.to_string()).await?;
+    // フラグメントシェーダーはメッシュを直接描くときに使う（被覆率を溜める描画は使わない）
+    let mut stroke = DrawStroke::new([1.0, 0.0, 0.0, 1.0], 10.0);
+    stroke.add_point(50.0, 100.0, 1.0);
+    stroke.add_point(450.0, 100.0, 1.0);
+    engine.draw_mesh_to_layer("test_layer", &stroke.to_mesh())?;
+    let image = engine.get_layer_image("test_layer").await?;
+    assert_eq!(image.get_pixel(250, 100).0, [0, 255, 0, 255]);
+
+    println!("✓ シェーダー差し替えテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_msaa_smooths_diagonals_and_keeps_layer() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    let status = engine.render_status();
+    assert_eq!(status.sample_count, 1);
+    if !status.supported_sample_counts.contains(&4) {
+        println!("4x MSAA 非対応のデバイスのためスキップ");
+        return Ok(());
+    }
+
+    // 既存の内容はマルチサンプリングで描いても変わらない
+    let mut existing = image::RgbaImage::new(4, 4);
+    existing.pixels_mut().for_each(|pixel| *pixel = image::Rgba([10, 200, 30, 128]));
+    engine.write_layer_image("test_layer", 400, 20, &existing)?;
+
+    let partial_pixels = |image: &image::RgbaImage| image.pixels().filter(|p| p[3] > 0 && p[3] < 255).count();
+    engine.draw_line_to_layer("test_layer", (50.0, 60.0), (350.0, 300.0), [0.0, 0.0, 0.0, 1.0], 3.0)?;
+    let aliased = engine.get_layer_image("test_layer").await?;
+
+    assert_eq!(engine.set_msaa_mode(MsaaMode::X4)?, 4);
+    engine.clear_layer_texture("test_layer", None)?;
+    engine.write_layer_image("test_layer", 400, 20, &existing)?;
+    engine.draw_line_to_layer("test_layer", (50.0, 60.0), (350.0, 300.0), [0.0, 0.0, 0.0, 1.0], 3.0)?;
+    let smoothed = engine.get_layer_image("test_layer").await?;
+
+    // 斜めの線の縁に半透明の画素が増える
+    assert!(partial_pixels(&smoothed) > partial_pixels(&aliased) + 100);
+    assert_eq!(smoothed.get_pixel(401, 21), existing.get_pixel(1, 1));
+    assert_eq!(smoothed.get_pixel(200, 180)[3], 255);
+    assert_eq!(engine.render_status().sample_count, 4);
+
+    assert_eq!(engine.set_msaa_mode(MsaaMode::Off)?, 1);
+    println!("✓ マルチサンプリングのテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_blend_space_only_changes_translucent_pixels() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    if !engine.render_status().srgb_blend_supported {
+        assert!(engine.set_blend_space(BlendSpace::Srgb).is_err());
+        assert_eq!(engine.render_status().settings.blend_space, BlendSpace::Linear);
+        // パイプライン（シェーダーの変換）はデバイスに関わらず作れる
+        let device = engine.device.as_ref().unwrap();
+        let pipeline = engine.draw_pipeline.as_mut().unwrap();
+        pipeline.set_blend_space(device, BlendSpace::Srgb, None)?;
+        assert_eq!(pipeline.blend_space(), BlendSpace::Srgb);
+        println!("sRGB での合成に対応していないデバイスのため、描画の比較はスキップ");
+        return Ok(());
+    }
+
+    // 不透明に塗った色はどちらの合成でも同じ値で保存される
+    engine.set_blend_space(BlendSpace::Srgb)?;
+    engine.draw_line_to_layer("test_layer", (50.0, 100.0), (450.0, 100.0), [0.2, 0.5, 0.8, 1.0], 10.0)?;
+    let srgb = engine.get_layer_image("test_layer").await?;
+    engine.set_blend_space(BlendSpace::Linear)?;
+    engine.clear_layer_texture("test_layer", None)?;
+    engine.draw_line_to_layer("test_layer", (50.0, 100.0), (450.0, 100.0), [0.2, 0.5, 0.8, 1.0], 10.0)?;
+    let linear = engine.get_layer_image("test_layer").await?;
+    let (a, b) = (srgb.get_pixel(250, 100).0, linear.get_pixel(250, 100).0);
+    assert!(a.iter().zip(b).all(|(a, b)| a.abs_diff(b) <= 1), "{:?} {:?}", a, b);
+
+    // 比較用の画像は半透明に重ねた部分だけが異なり、合成の設定は元に戻る
+    let images = engine.render_blend_comparison(128, 64).await?;
+    assert_eq!(images.iter().map(|(space, _)| *space).collect::<Vec<_>>(), BlendSpace::ALL);
+    let (linear, srgb) = (&images[0].1, &images[1].1);
+    assert_eq!(linear.get_pixel(0, 0), srgb.get_pixel(0, 0));
+    assert_ne!(linear, srgb);
+    assert_eq!(engine.render_status().settings.blend_space, BlendSpace::Linear);
+    assert_eq!(engine.layer_texture_size("__blend_audit_linear"), None);
+
+    println!("✓ 合成の色空間のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_translucent_stroke_opacity_is_applied_once() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    let color = [1.0, 0.0, 0.0, 0.5];
+
+    // 自分自身と交差する半透明のストローク（交点と継ぎ目も他の部分と同じ濃さ）
+    let mut stroke = DrawStroke::new(color, 10.0);
+    for (x, y) in [(100.0, 100.0), (300.0, 100.0), (300.0, 200.0), (200.0, 200.0), (200.0, 50.0)] {
+        stroke.add_point(x, y, 1.0);
+    }
+    engine.draw_stroke_to_layer("test_layer", &stroke)?;
+    let image = engine.get_layer_image("test_layer").await?;
+    let plain = image.get_pixel(150, 100)[3];
+    assert!(plain.abs_diff(128) <= 2, "{}", plain);
+    assert_eq!(image.get_pixel(200, 100)[3], plain); // 交点
+    assert_eq!(image.get_pixel(300, 100)[3], plain); // 継ぎ目
+
+    // 点を追加しながら描いても、区切りの継ぎ目を含めて一度に描いたときと同じになる
+    engine.create_layer_texture("live_layer", 512, 512)?;
+    engine.begin_layer_stroke("live_layer")?;
+    let mut tessellator = StrokeTessellator::new();
+    let mut live = DrawStroke::new(color, 10.0);
+    for point in &stroke.points {
+        live.add_point(point.position[0], point.position[1], 1.0);
+        engine.extend_layer_stroke("live_layer", &tessellator.next_mesh(&live).with_flow(1.0), color)?;
+    }
+    assert!(engine.scratch_memory_size() > 0);
+    assert!(engine.end_layer_stroke("live_layer")?);
+    assert_eq!(engine.get_layer_image("live_layer").await?, image);
+
+    // スタンプの不透明度（流量）は重なるほど濃くなり、色のアルファが上限になる
+    engine.clear_layer_texture("test_layer", None)?;
+    let stamps: Vec<Stamp> = (0..20)
+        .map(|i| Stamp { center: [100.0 + i as f32 * 2.0, 300.0], radius: 20.0, opacity: 0.3 })
+        .collect();
+    let shape = StampShape { color: [0.0, 0.0, 1.0, 0.6], hardness: 1.0, angle: 0.0, roundness: 1.0 };
+    engine.draw_stamps_to_layer("test_layer", &stamps, &shape, StampRasterizer::Compute)?;
+    let image = engine.get_layer_image("test_layer").await?;
+    let dense = image.get_pixel(120, 300)[3];
+    let single = image.get_pixel(81, 300)[3];
+    assert!(dense.abs_diff(153) <= 3, "{}", dense);
+    assert!(single < dense && single.abs_diff(46) <= 3, "{}", single);
+
+    println!("✓ ストロークの不透明度と流量のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_erase_stroke_previews_until_committed() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    let red = wgpu::Color { r: 1.0, g: 0.0, b: 0.0, a: 1.0 };
+    engine.clear_layer_texture("test_layer", Some(red))?;
+    let original = engine.get_layer_image("test_layer").await?;
+    let layers = ["test_layer".to_string()];
+
+    // 点を追加しながら消す（消しゴムでは色のアルファが消す強さ）
+    let erase_points = |engine: &mut DrawingEngine| -> Result<DrawStroke, Box<dyn std::error::Error>> {
+        let mut erase = DrawStroke::new([0.0, 0.0, 0.0, 1.0], 10.0);
+        let mut tessellator = StrokeTessellator::new();
+        for (x, y) in [(100.0, 100.0), (300.0, 100.0), (300.0, 200.0)] {
+            erase.add_point(x, y, 1.0);
+            engine.extend_layer_stroke("test_layer", &tessellator.next_mesh(&erase).with_flow(1.0), erase.color)?;
+        }
+        Ok(erase)
+    };
+
+    // 描画中はプレビューだけが消え、レイヤーはそのまま
+    engine.begin_erase_stroke("test_layer")?;
+    erase_points(&mut engine)?;
+    assert_eq!(engine.get_layer_image("test_layer").await?, original);
+    let preview = engine.get_layer_preview_images(&layers).await?.remove(0);
+    assert_eq!(preview.get_pixel(200, 100)[3], 0);
+    assert_eq!(preview.get_pixel(200, 100)[0], 255); // 色は残す
+    assert_eq!(preview.get_pixel(200, 150), original.get_pixel(200, 150));
+
+    // 取り消すとプレビューも元に戻る
+    assert!(engine.cancel_layer_stroke("test_layer")?);
+    assert_eq!(engine.get_layer_preview_images(&layers).await?.remove(0), original);
+
+    // 確定するとプレビューと同じ結果がレイヤーに残る
+    engine.begin_erase_stroke("test_layer")?;
+    let erase = erase_points(&mut engine)?;
+    assert!(engine.end_layer_stroke("test_layer")?);
+    assert_eq!(engine.get_layer_image("test_layer").await?, preview);
+    assert!(!engine.end_layer_stroke("test_layer")?);
+
+    // 一度に消しても同じ（強さを下げると半分だけ消える）
+    engine.clear_layer_texture("test_layer", Some(red))?;
+    let mesh = StrokeTessellator::new().next_mesh(&erase).with_flow(1.0);
+    engine.erase_mesh_from_layer("test_layer", &mesh, 1.0)?;
+    assert_eq!(engine.get_layer_image("test_layer").await?, preview);
+    engine.clear_layer_texture("test_layer", Some(red))?;
+    engine.erase_mesh_from_layer("test_layer", &mesh, 0.5)?;
+    let alpha = engine.get_layer_image("test_layer").await?.get_pixel(200, 100)[3];
+    assert!(alpha.abs_diff(128) <= 2, "{}", alpha);
+
+    // 描き込みのストロークは取り消すと描き始める前の内容に戻る
+    engine.begin_layer_stroke("test_layer")?;
+    let before = engine.get_layer_image("test_layer").await?;
+    let mut paint = DrawStroke::new([0.0, 0.0, 1.0, 1.0], 10.0);
+    paint.add_point(100.0, 300.0, 1.0);
+    paint.add_point(300.0, 300.0, 1.0);
+    engine.extend_layer_stroke("test_layer", &StrokeTessellator::new().next_mesh(&paint).with_flow(1.0), paint.color)?;
+    assert_ne!(engine.get_layer_image("test_layer").await?, before);
+    assert!(engine.cancel_layer_stroke("test_layer")?);
+    assert_eq!(engine.get_layer_image("test_layer").await?, before);
+
+    // 描画中のストロークをまとめて取り消す（Esc やパームリジェクション）
+    engine.begin_layer_stroke("test_layer")?;
+    engine.extend_layer_stroke("test_layer", &StrokeTessellator::new().next_mesh(&paint).with_flow(1.0), paint.color)?;
+    assert_eq!(engine.cancel_all_layer_strokes()?, vec!["test_layer".to_string()]);
+    assert_eq!(engine.get_layer_image("test_layer").await?, before);
+    assert!(engine.cancel_all_layer_strokes()?.is_empty());
+
+    println!("✓ 消しゴムのプレビューと確定のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_render_stroke_frames_leaves_no_layer() -> Result<(), Box<dyn std::error::Error>> {
+    let (mut engine, _) = create_test_environment().await?;
+    let mut stroke = DrawStroke::new([0.0, 0.0, 0.0, 1.0], 4.0);
+    stroke.add_point(10.0, 10.0, 1.0);
+    stroke.add_point(50.0, 10.0, 1.0);
+
+    let images = engine.render_stroke_frames(64, 32, &[Vec::new(), vec![stroke]]).await?;
+    assert_eq!(images.len(), 2);
+    assert!(images[0].pixels().all(|p| p[3] == 0));
+    assert_eq!(images[1].dimensions(), (64, 32));
+    assert_eq!(images[1].get_pixel(30, 10)[3], 255);
+    assert_eq!(engine.layer_texture_size("__stroke_preview"), None);
+
+    println!("✓ ストロークのプレビュー描画のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_stroke_life_cycle_marks_changed_tiles() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut engine) = gpu_engine().await else { return Ok(()) };
+    engine.create_layer_texture("tiled", TILE_SIZE * 2, TILE_SIZE * 2)?;
+    let since = engine.tile_generations().generation();
+
+    // 左上のタイルの中だけに描く
+    let mut stroke = DrawStroke::new([0.0, 0.0, 1.0, 1.0], 6.0);
+    let mut tessellator = StrokeTessellator::new();
+    engine.begin_layer_stroke("tiled")?;
+    for (x, y) in [(20.0, 20.0), (120.0, 40.0), (200.0, 120.0)] {
+        stroke.add_point(x, y, 1.0);
+        engine.extend_layer_stroke("tiled", &tessellator.next_mesh(&stroke).with_flow(1.0), stroke.color)?;
+    }
+    assert!(engine.end_layer_stroke("tiled")?);
+
+    let image = engine.get_layer_image("tiled").await?;
+    assert_eq!(image.get_pixel(120, 40)[3], 255);
+    assert_eq!(image.get_pixel(TILE_SIZE + 20, TILE_SIZE + 20)[3], 0);
+    assert_eq!(engine.tile_generations().changed_since(["tiled"], since), vec![TileCoord { x: 0, y: 0 }]);
+
+    // 削除したレイヤーのタイルは残さない
+    let since = engine.tile_generations().generation();
+    assert!(engine.remove_layer_texture("tiled"));
+    assert!(engine.tile_generations().changed_since(["tiled"], 0).is_empty());
+    assert_eq!(engine.tile_generations().generation(), since);
+
+    println!("✓ ストロークの開始から確定までのタイル更新のテスト成功");
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_rotate_odd_sized_layer_keeps_readback_layout() -> Result<(), Box<dyn std::error::Error>> {
+    let Some(mut engine) = gpu_engine().await else { return Ok(()) };
+    // 1行のバイト数が読み出しの行揃え（256バイト）の倍数にならない大きさ
+    let (width, height) = (37, 11);
+    let image = image::RgbaImage::from_fn(width, height, |x, y| image::Rgba([x as u8 * 6, y as u8 * 20, 0, 255]));
+    engine.create_layer_texture("odd", width, height)?;
+    engine.write_layer_image("odd", 0, 0, &image)?;
+
+    // 生のデータは行ごとに揃えた幅のまま返り、画像として読むとパディングを除く
+    let padded_row = (width * 4).div_ceil(COPY_BYTES_PER_ROW_ALIGNMENT) * COPY_BYTES_PER_ROW_ALIGNMENT;
+    let data = engine.get_layer_texture_data("odd").await?;
+    assert_eq!(data.len(), (padded_row * height) as usize);
+    assert_eq!(&data[padded_row as usize..][..(width * 4) as usize], &image.as_raw()[(width * 4) as usize..][..(width * 4) as usize]);
+    assert_eq!(engine.get_layer_image("odd").await?, image);
+
+    // 回転するとレイヤーの大きさが入れ替わる
+    let transform = CanvasTransform::Rotate90Clockwise;
+    assert_eq!(engine.transform_layer_texture("odd", transform)?, (height, width));
+    assert_eq!(engine.layer_texture_size("odd"), Some((height, width)));
+    let rotated = engine.get_layer_image("odd").await?;
+    for (x, y, pixel) in rotated.enumerate_pixels() {
+        let (source_x, source_y) = transform.source_pixel((x, y), (width, height));
+        assert_eq!(pixel, image.get_pixel(source_x, source_y), "({}, {})", x, y);
+    }
+
+    println!("✓ 奇数サイズのレイヤーの回転と読み出しのテスト成功");
+    Ok(())
+}
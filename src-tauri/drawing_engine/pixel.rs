@@ -0,0 +1,157 @@
+use image::{Rgba, RgbaImage};
+
+/// 表示用の最近傍拡大の最大倍率
+pub const MAX_PIXEL_ZOOM: u32 = 64;
+
+/// 2点間を Bresenham のアルゴリズムで結ぶピクセル列（両端を含む）
+pub fn bresenham_line(from: (i32, i32), to: (i32, i32)) -> Vec<(i32, i32)> {
+    let (mut x, mut y) = from;
+    let dx = (to.0 - x).abs();
+    let dy = -(to.1 - y).abs();
+    let step_x = if x < to.0 { 1 } else { -1 };
+    let step_y = if y < to.1 { 1 } else { -1 };
+    let mut error = dx + dy;
+
+    let mut pixels = Vec::with_capacity((dx - dy) as usize + 1);
+    loop {
+        pixels.push((x, y));
+        if (x, y) == to {
+            break;
+        }
+        let doubled = 2 * error;
+        if doubled >= dy {
+            error += dy;
+            x += step_x;
+        }
+        if doubled <= dx {
+            error += dx;
+            y += step_y;
+        }
+    }
+    pixels
+}
+
+/// ストロークの点列を 1px の線のピクセル列に変換
+///
+/// 点はピクセル単位に切り捨て（サブピクセル位置は使わない）、隣り合う点を Bresenham で結ぶ。
+/// `pixel_perfect` が有効なら、斜めの線に出る L 字の角のピクセルを取り除く。
+pub fn pixel_stroke_path(points: &[(f32, f32)], pixel_perfect: bool) -> Vec<(i32, i32)> {
+    let mut path: Vec<(i32, i32)> = Vec::new();
+    let to_pixel = |(x, y): (f32, f32)| (x.floor() as i32, y.floor() as i32);
+
+    for (i, point) in points.iter().enumerate() {
+        let pixel = to_pixel(*point);
+        let segment = match path.last() {
+            Some(&last) if i > 0 => bresenham_line(last, pixel),
+            _ => vec![pixel],
+        };
+        for p in segment {
+            if path.last() != Some(&p) {
+                path.push(p);
+            }
+        }
+    }
+
+    if pixel_perfect {
+        remove_corners(&path)
+    } else {
+        path
+    }
+}
+
+/// 前後のピクセルと縦横に接し、前後同士が斜めに接するピクセル（L 字の角）を除く
+fn remove_corners(path: &[(i32, i32)]) -> Vec<(i32, i32)> {
+    let mut result: Vec<(i32, i32)> = Vec::with_capacity(path.len());
+    let mut i = 0;
+    while i < path.len() {
+        let current = path[i];
+        if let (Some(&previous), Some(&next)) = (result.last(), path.get(i + 1)) {
+            let orthogonal = |a: (i32, i32), b: (i32, i32)| a.0 == b.0 || a.1 == b.1;
+            let diagonal = (previous.0 - next.0).abs() == 1 && (previous.1 - next.1).abs() == 1;
+            if orthogonal(previous, current) && orthogonal(current, next) && diagonal {
+                i += 1;
+                continue;
+            }
+        }
+        result.push(current);
+        i += 1;
+    }
+    result
+}
+
+/// レイヤー内のピクセルを重複なく行ごとの連続区間 `(x, y, 長さ)` にまとめる
+pub fn pixel_runs(path: &[(i32, i32)], (width, height): (u32, u32)) -> Vec<(u32, u32, u32)> {
+    let mut pixels: Vec<(u32, u32)> = path.iter()
+        .filter(|(x, y)| *x >= 0 && *y >= 0 && (*x as u32) < width && (*y as u32) < height)
+        .map(|(x, y)| (*y as u32, *x as u32))
+        .collect();
+    pixels.sort_unstable();
+    pixels.dedup();
+
+    let mut runs: Vec<(u32, u32, u32)> = Vec::new();
+    for (y, x) in pixels {
+        match runs.last_mut() {
+            Some((run_x, run_y, length)) if *run_y == y && *run_x + *length == x => *length += 1,
+            _ => runs.push((x, y, 1)),
+        }
+    }
+    runs
+}
+
+/// 色（0.0〜1.0）を 8bit の RGBA に変換
+pub fn color_to_rgba8(color: [f32; 4]) -> Rgba<u8> {
+    Rgba(color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8))
+}
+
+/// 最近傍補間で整数倍に拡大（ドット絵をぼかさずに表示するため）
+pub fn scale_nearest(image: &RgbaImage, zoom: u32) -> RgbaImage {
+    let zoom = zoom.max(1);
+    RgbaImage::from_fn(image.width() * zoom, image.height() * zoom, |x, y| {
+        *image.get_pixel(x / zoom, y / zoom)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bresenham_line() {
+        assert_eq!(bresenham_line((0, 0), (3, 1)), vec![(0, 0), (1, 0), (2, 1), (3, 1)]);
+        assert_eq!(bresenham_line((2, 2), (2, -1)), vec![(2, 2), (2, 1), (2, 0), (2, -1)]);
+        assert_eq!(bresenham_line((1, 1), (1, 1)), vec![(1, 1)]);
+
+        let diagonal = bresenham_line((5, 0), (0, 5));
+        assert_eq!(diagonal.len(), 6);
+        assert!(diagonal.windows(2).all(|w| (w[0].0 - w[1].0).abs() == 1 && (w[0].1 - w[1].1).abs() == 1));
+    }
+
+    #[test]
+    fn test_pixel_perfect_removes_corners() {
+        // (0,0) → (1,0) → (1,1) は L 字になるため (1,0) を除く
+        let points = [(0.2, 0.7), (1.9, 0.1), (1.5, 1.5), (2.5, 2.5)];
+        assert_eq!(pixel_stroke_path(&points, false), vec![(0, 0), (1, 0), (1, 1), (2, 2)]);
+        assert_eq!(pixel_stroke_path(&points, true), vec![(0, 0), (1, 1), (2, 2)]);
+
+        // 直線はそのまま
+        let straight = [(0.0, 0.0), (4.0, 0.0)];
+        assert_eq!(pixel_stroke_path(&straight, true).len(), 5);
+    }
+
+    #[test]
+    fn test_pixel_runs_clip_and_merge() {
+        let path = vec![(-1, 0), (0, 0), (1, 0), (1, 0), (3, 0), (2, 1), (5, 5)];
+        assert_eq!(pixel_runs(&path, (4, 4)), vec![(0, 0, 2), (3, 0, 1), (2, 1, 1)]);
+    }
+
+    #[test]
+    fn test_scale_nearest() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+        let scaled = scale_nearest(&image, 3);
+        assert_eq!(scaled.dimensions(), (6, 3));
+        assert_eq!(scaled.get_pixel(2, 2), &Rgba([0, 0, 0, 0]));
+        assert_eq!(scaled.get_pixel(3, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(color_to_rgba8([1.0, 0.5, 0.0, 2.0]), Rgba([255, 128, 0, 255]));
+    }
+}
@@ -0,0 +1,39 @@
+use serde::{Deserialize, Serialize};
+use super::color_space::BlendSpace;
+use super::msaa::MsaaMode;
+
+/// キャンバスごとの描画品質の設定（プロジェクトと共に保存される）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CanvasRenderSettings {
+    pub msaa: MsaaMode,
+    /// ストロークを合成する色空間
+    pub blend_space: BlendSpace,
+}
+
+/// 描画品質の設定と、実際に使っている値（フロントエンドの品質設定の表示用）
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CanvasRenderStatus {
+    pub settings: CanvasRenderSettings,
+    /// 実際に使っているサンプル数
+    pub sample_count: u32,
+    /// デバイスで使えるサンプル数
+    pub supported_sample_counts: Vec<u32>,
+    /// 実際に使っている合成の色空間
+    pub blend_space: BlendSpace,
+    /// デバイスが sRGB での合成に対応しているか
+    pub srgb_blend_supported: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_settings_default_for_old_projects() {
+        let settings: CanvasRenderSettings = serde_json::from_str("{}").unwrap();
+        assert_eq!(settings, CanvasRenderSettings { msaa: MsaaMode::Off, blend_space: BlendSpace::Linear });
+        let settings: CanvasRenderSettings = serde_json::from_str(r#"{"msaa": "x4", "blend_space": "srgb"}"#).unwrap();
+        assert_eq!(settings, CanvasRenderSettings { msaa: MsaaMode::X4, blend_space: BlendSpace::Srgb });
+    }
+}
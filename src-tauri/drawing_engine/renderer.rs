@@ -2,6 +2,7 @@ use wgpu::*;
 use log::{info, debug, warn};
 use std::error::Error;
 use std::fmt;
+use super::limits::DEFAULT_MAX_CANVAS_DIMENSION;
 
 /// オフスクリーンレンダリングのエラー型
 #[derive(Debug)]
@@ -53,13 +54,24 @@ pub struct OffscreenRenderer {
     pub texture: Option<Texture>,
     pub render_texture_view: Option<TextureView>,
     pub output_buffer: Option<Buffer>,
+    /// 幅・高さの上限
+    max_dimension: u32,
 }
 
 impl OffscreenRenderer {
-    /// 新しいOffscreenRendererインスタンスを作成
+    /// 新しいOffscreenRendererインスタンスを作成（各辺は既定の上限まで）
+    ///
+    /// デバイスを使わずに作るため上限はデバイスから取れず、`DEFAULT_MAX_CANVAS_DIMENSION`（4096）を使う。
+    /// 以前の 3840x2160 固定では縦長のキャンバスや 4096 四方のレイヤーを書き出せなかったため、
+    /// レイヤーテクスチャの既定の上限に揃えている。デバイスの上限に合わせるには
+    /// `DrawingEngine::create_offscreen_renderer` を使う。
     pub fn new(width: u32, height: u32) -> Result<Self, OffscreenRenderError> {
-        // 寸法の検証（最大4K解像度をサポート）
-        if width == 0 || height == 0 || width > 3840 || height > 2160 {
+        Self::with_max_dimension(width, height, DEFAULT_MAX_CANVAS_DIMENSION)
+    }
+
+    /// 各辺の上限を指定して作成（デバイスの上限に合わせる場合に使う）
+    pub fn with_max_dimension(width: u32, height: u32, max_dimension: u32) -> Result<Self, OffscreenRenderError> {
+        if width == 0 || height == 0 || width > max_dimension || height > max_dimension {
             return Err(OffscreenRenderError::InvalidDimensions(width, height));
         }
 
@@ -78,6 +90,7 @@ impl OffscreenRenderer {
             texture: None,
             render_texture_view: None,
             output_buffer: None,
+            max_dimension,
         })
     }
 
@@ -232,7 +245,7 @@ impl OffscreenRenderer {
 
     /// 新しい寸法でリサイズ（再初期化が必要）
     pub fn resize(&mut self, width: u32, height: u32) -> Result<(), OffscreenRenderError> {
-        if width == 0 || height == 0 || width > 3840 || height > 2160 {
+        if width == 0 || height == 0 || width > self.max_dimension || height > self.max_dimension {
             return Err(OffscreenRenderError::InvalidDimensions(width, height));
         }
 
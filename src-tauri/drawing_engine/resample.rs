@@ -0,0 +1,526 @@
+use wgpu::*;
+use log::{info, debug};
+use image::RgbaImage;
+use rayon::prelude::*;
+use crate::shaders;
+use serde::{Deserialize, Serialize};
+use super::pipeline::PipelineError;
+use super::texture::TextureError;
+
+/// コンピュートシェーダーのワークグループの大きさ（1辺）
+const WORKGROUP_SIZE: u32 = 16;
+
+/// 拡大・縮小のフィルター
+///
+/// ドット絵は `Nearest`（整数倍なら画素をそのまま複製する）、描いた絵は `Mitchell` か `Lanczos3` を使う。
+/// 縮小するときはフィルターの幅を縮小率に合わせて広げ、元の画素を平均する。
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ResampleFilter {
+    Nearest,
+    /// Mitchell-Netravali（B = C = 1/3）。Lanczos より輪郭のリンギングが出にくい
+    Mitchell,
+    #[default]
+    Lanczos3,
+}
+
+impl ResampleFilter {
+    /// シェーダーに渡す番号
+    fn index(self) -> u32 {
+        match self {
+            ResampleFilter::Nearest => 0,
+            ResampleFilter::Mitchell => 1,
+            ResampleFilter::Lanczos3 => 2,
+        }
+    }
+
+    /// フィルターの半径（元の画像の画素単位、等倍のとき）
+    fn radius(self) -> f32 {
+        match self {
+            ResampleFilter::Nearest => 0.5,
+            ResampleFilter::Mitchell => 2.0,
+            ResampleFilter::Lanczos3 => 3.0,
+        }
+    }
+
+    /// フィルターの重み（シェーダーと同じ式）
+    fn weight(self, x: f32) -> f32 {
+        let t = x.abs();
+        match self {
+            ResampleFilter::Nearest => 1.0,
+            ResampleFilter::Mitchell if t < 1.0 => (7.0 * t * t * t - 12.0 * t * t + 16.0 / 3.0) / 6.0,
+            ResampleFilter::Mitchell if t < 2.0 => (-7.0 / 3.0 * t * t * t + 12.0 * t * t - 20.0 * t + 32.0 / 3.0) / 6.0,
+            ResampleFilter::Lanczos3 if t < 3.0 => sinc(t) * sinc(t / 3.0),
+            _ => 0.0,
+        }
+    }
+
+    /// 出力の1列（1行）に使う元の画素の範囲と重み
+    fn taps(self, position: u32, source: u32, target: u32) -> (u32, Vec<f32>) {
+        let scale = source as f32 / target as f32;
+        let center = (position as f32 + 0.5) * scale;
+        if self == ResampleFilter::Nearest {
+            return ((center.floor() as u32).min(source - 1), vec![1.0]);
+        }
+        let support = scale.max(1.0);
+        let radius = self.radius() * support;
+        let start = (center - radius).floor().max(0.0) as u32;
+        let end = ((center + radius).ceil() as u32).min(source);
+        let weights = (start..end).map(|i| self.weight((i as f32 + 0.5 - center) / support)).collect();
+        (start, weights)
+    }
+}
+
+fn sinc(x: f32) -> f32 {
+    if x < 1e-5 {
+        return 1.0;
+    }
+    let p = std::f32::consts::PI * x;
+    p.sin() / p
+}
+
+/// 画像を拡大・縮小する（CPU で計算。`ResamplePipeline` と同じ結果になる）
+///
+/// 半透明の画素の色が透明な画素に引っ張られないよう、乗算済みアルファで重みを掛ける。
+pub fn resample(image: &RgbaImage, width: u32, height: u32, filter: ResampleFilter) -> RgbaImage {
+    let (source_width, source_height) = image.dimensions();
+    if (source_width, source_height) == (width, height) {
+        return image.clone();
+    }
+    if source_width == 0 || source_height == 0 {
+        return RgbaImage::new(width, height);
+    }
+    let columns: Vec<_> = (0..width).map(|x| filter.taps(x, source_width, width)).collect();
+    let rows: Vec<_> = (0..height).map(|y| filter.taps(y, source_height, height)).collect();
+
+    let mut data = vec![0u8; width as usize * height as usize * 4];
+    data.par_chunks_mut(width as usize * 4).zip(&rows).for_each(|(line, (y0, wy))| {
+        for (pixel, (x0, wx)) in line.chunks_exact_mut(4).zip(&columns) {
+            if filter == ResampleFilter::Nearest {
+                let texel = image.get_pixel(*x0, *y0).0;
+                pixel.copy_from_slice(if texel[3] == 0 { &[0; 4] } else { &texel });
+                continue;
+            }
+            let mut sum = [0.0f32; 4];
+            let mut total = 0.0f32;
+            for (dy, &row_weight) in wy.iter().enumerate() {
+                for (dx, &column_weight) in wx.iter().enumerate() {
+                    let weight = row_weight * column_weight;
+                    let texel = image.get_pixel(x0 + dx as u32, y0 + dy as u32).0.map(|c| c as f32 / 255.0);
+                    for channel in 0..3 {
+                        sum[channel] += texel[channel] * texel[3] * weight;
+                    }
+                    sum[3] += texel[3] * weight;
+                    total += weight;
+                }
+            }
+            pixel.copy_from_slice(&unpremultiply(sum, total));
+        }
+    });
+    RgbaImage::from_raw(width, height, data).expect("画素数は出力の大きさと一致する")
+}
+
+/// 重みの合計で割り、乗算済みアルファを戻す（シェーダーと同じ丸め）
+fn unpremultiply(sum: [f32; 4], total: f32) -> [u8; 4] {
+    let alpha = if total == 0.0 { 0.0 } else { (sum[3] / total).clamp(0.0, 1.0) };
+    if alpha <= 0.0 {
+        return [0; 4];
+    }
+    let to_u8 = |value: f32| (value.clamp(0.0, 1.0) * 255.0).round() as u8;
+    [
+        to_u8(sum[0] / total / alpha),
+        to_u8(sum[1] / total / alpha),
+        to_u8(sum[2] / total / alpha),
+        to_u8(alpha),
+    ]
+}
+
+/// 書き出し用に画像を拡大・縮小するパイプライン
+///
+/// 元の画像を sRGB の値のまま（`Rgba8Unorm` として）アップロードし、出力の画素ごとに
+/// フィルターの範囲の画素を読んで重みを掛ける。結果はストレージバッファに RGBA8 で書き、
+/// 大きな出力はバッファの上限に収まる行数ずつに分けて計算する。
+pub struct ResamplePipeline {
+    compute_pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+}
+
+impl ResamplePipeline {
+    pub fn new(device: &Device, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        Self::with_shader(device, cache, Self::shader_source())
+    }
+
+    pub fn with_shader(device: &Device, cache: Option<&PipelineCache>, source: &str) -> Result<Self, PipelineError> {
+        info!("[ResamplePipeline] パイプライン作成開始");
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Resample Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(source)?.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Resample Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Storage { read_only: false },
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Resample Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Resample Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache,
+        });
+
+        info!("[ResamplePipeline] パイプライン作成完了");
+        Ok(Self {
+            compute_pipeline,
+            bind_group_layout,
+        })
+    }
+
+    /// 画像を `width`x`height` に拡大・縮小する
+    pub async fn resample(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        image: &RgbaImage,
+        width: u32,
+        height: u32,
+        filter: ResampleFilter,
+    ) -> Result<RgbaImage, TextureError> {
+        let (source_width, source_height) = image.dimensions();
+        let max_dimension = device.limits().max_texture_dimension_2d;
+        if source_width == 0 || source_height == 0 || source_width > max_dimension || source_height > max_dimension {
+            return Err(TextureError::InvalidDimensions(source_width, source_height));
+        }
+        if width == 0 || height == 0 {
+            return Err(TextureError::InvalidDimensions(width, height));
+        }
+        debug!("[ResamplePipeline] 拡大・縮小: {}x{} -> {}x{} ({:?})", source_width, source_height, width, height, filter);
+
+        let size = Extent3d { width: source_width, height: source_height, depth_or_array_layers: 1 };
+        let source = device.create_texture(&TextureDescriptor {
+            label: Some("Resample Source Texture"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: TextureDimension::D2,
+            format: TextureFormat::Rgba8Unorm,
+            usage: TextureUsages::TEXTURE_BINDING | TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &source,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            image.as_raw(),
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(source_width * 4),
+                rows_per_image: Some(source_height),
+            },
+            size,
+        );
+        let source_view = source.create_view(&TextureViewDescriptor::default());
+
+        // ストレージバッファの上限に収まる行数ずつ計算する
+        let row_bytes = width as u64 * 4;
+        let max_binding = device.limits().max_storage_buffer_binding_size as u64;
+        let strip_rows = ((max_binding / row_bytes).max(1) as u32).min(height);
+        let mut data = Vec::with_capacity(row_bytes as usize * height as usize);
+        let mut row_offset = 0;
+        while row_offset < height {
+            let rows = strip_rows.min(height - row_offset);
+            let strip_size = row_bytes * rows as u64;
+            let output_buffer = device.create_buffer(&BufferDescriptor {
+                label: Some("Resample Output Buffer"),
+                size: strip_size,
+                usage: BufferUsages::STORAGE | BufferUsages::COPY_SRC,
+                mapped_at_creation: false,
+            });
+            let params_buffer = device.create_buffer(&BufferDescriptor {
+                label: Some("Resample Params Buffer"),
+                size: 32,
+                usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            let params: [u32; 8] = [source_width, source_height, width, height, filter.index(), row_offset, rows, 0];
+            queue.write_buffer(&params_buffer, 0, bytemuck::cast_slice(&params));
+            let read_buffer = device.create_buffer(&BufferDescriptor {
+                label: Some("Resample Read Buffer"),
+                size: strip_size,
+                usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                mapped_at_creation: false,
+            });
+
+            let bind_group = device.create_bind_group(&BindGroupDescriptor {
+                label: Some("Resample Bind Group"),
+                layout: &self.bind_group_layout,
+                entries: &[
+                    BindGroupEntry {
+                        binding: 0,
+                        resource: BindingResource::TextureView(&source_view),
+                    },
+                    BindGroupEntry {
+                        binding: 1,
+                        resource: output_buffer.as_entire_binding(),
+                    },
+                    BindGroupEntry {
+                        binding: 2,
+                        resource: params_buffer.as_entire_binding(),
+                    },
+                ],
+            });
+
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Resample Encoder"),
+            });
+            {
+                let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                    label: Some("Resample Pass"),
+                    timestamp_writes: None,
+                });
+                compute_pass.set_pipeline(&self.compute_pipeline);
+                compute_pass.set_bind_group(0, &bind_group, &[]);
+                compute_pass.dispatch_workgroups(width.div_ceil(WORKGROUP_SIZE), rows.div_ceil(WORKGROUP_SIZE), 1);
+            }
+            encoder.copy_buffer_to_buffer(&output_buffer, 0, &read_buffer, 0, strip_size);
+            queue.submit(std::iter::once(encoder.finish()));
+
+            let buffer_slice = read_buffer.slice(..);
+            let (sender, receiver) = futures::channel::oneshot::channel();
+            buffer_slice.map_async(MapMode::Read, move |result| {
+                sender.send(result).unwrap();
+            });
+
+            let _ = device.poll(wgpu::MaintainBase::Wait);
+
+            receiver.await
+                .map_err(|_| TextureError::BufferReadFailed("バッファマップ待機に失敗".to_string()))?
+                .map_err(|e| TextureError::BufferReadFailed(format!("バッファマップに失敗: {:?}", e)))?;
+
+            data.extend_from_slice(&buffer_slice.get_mapped_range());
+            read_buffer.unmap();
+            row_offset += rows;
+        }
+
+        debug!("[ResamplePipeline] 拡大・縮小完了: {}x{}", width, height);
+        RgbaImage::from_raw(width, height, data)
+            .ok_or_else(|| TextureError::BufferReadFailed("出力の大きさが一致しません".to_string()))
+    }
+
+    /// 拡大・縮小のシェーダー（WGSL）
+    ///
+    /// フィルターの式と丸めは CPU の `resample` と揃えている。
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        struct Params {
+            source_size: vec2<u32>,
+            target_size: vec2<u32>,
+            // 0: 最近傍, 1: Mitchell, 2: Lanczos3
+            mode: u32,
+            row_offset: u32,
+            rows: u32,
+            _padding: u32,
+        }
+
+        @group(0) @binding(0) var source: texture_2d<f32>;
+        @group(0) @binding(1) var<storage, read_write> output: array<u32>;
+        @group(0) @binding(2) var<uniform> params: Params;
+
+        const PI: f32 = 3.14159265358979;
+
+        fn sinc(x: f32) -> f32 {
+            if x < 1e-5 {
+                return 1.0;
+            }
+            let p = PI * x;
+            return sin(p) / p;
+        }
+
+        fn weight(x: f32) -> f32 {
+            let t = abs(x);
+            if params.mode == 1u {
+                if t < 1.0 {
+                    return (7.0 * t * t * t - 12.0 * t * t + 16.0 / 3.0) / 6.0;
+                }
+                if t < 2.0 {
+                    return (-7.0 / 3.0 * t * t * t + 12.0 * t * t - 20.0 * t + 32.0 / 3.0) / 6.0;
+                }
+                return 0.0;
+            }
+            if t < 3.0 {
+                return sinc(t) * sinc(t / 3.0);
+            }
+            return 0.0;
+        }
+
+        fn radius() -> f32 {
+            if params.mode == 1u {
+                return 2.0;
+            }
+            return 3.0;
+        }
+
+        fn to_unorm(value: f32) -> f32 {
+            return round(clamp(value, 0.0, 1.0) * 255.0) / 255.0;
+        }
+
+        @compute @workgroup_size(16, 16)
+        fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+            if id.x >= params.target_size.x || id.y >= params.rows {
+                return;
+            }
+            let y = id.y + params.row_offset;
+            let scale = vec2<f32>(params.source_size) / vec2<f32>(params.target_size);
+            let center = (vec2<f32>(f32(id.x), f32(y)) + 0.5) * scale;
+            let index = id.y * params.target_size.x + id.x;
+
+            if params.mode == 0u {
+                let texel_position = min(vec2<u32>(floor(center)), params.source_size - 1u);
+                let texel = textureLoad(source, vec2<i32>(texel_position), 0);
+                output[index] = pack4x8unorm(select(texel, vec4<f32>(0.0), texel.a <= 0.0));
+                return;
+            }
+
+            let support = max(scale, vec2<f32>(1.0));
+            let extent = radius() * support;
+            let start = vec2<u32>(max(floor(center - extent), vec2<f32>(0.0)));
+            let end = min(vec2<u32>(ceil(center + extent)), params.source_size);
+
+            var sum = vec4<f32>(0.0);
+            var total = 0.0;
+            for (var sy = start.y; sy < end.y; sy++) {
+                let row_weight = weight((f32(sy) + 0.5 - center.y) / support.y);
+                for (var sx = start.x; sx < end.x; sx++) {
+                    let w = row_weight * weight((f32(sx) + 0.5 - center.x) / support.x);
+                    let texel = textureLoad(source, vec2<i32>(i32(sx), i32(sy)), 0);
+                    sum += vec4<f32>(texel.rgb * texel.a, texel.a) * w;
+                    total += w;
+                }
+            }
+
+            var alpha = 0.0;
+            if total != 0.0 {
+                alpha = clamp(sum.a / total, 0.0, 1.0);
+            }
+            if alpha <= 0.0 {
+                output[index] = 0u;
+                return;
+            }
+            let color = sum.rgb / total / alpha;
+            output[index] = pack4x8unorm(vec4<f32>(
+                to_unorm(color.r), to_unorm(color.g), to_unorm(color.b), to_unorm(alpha),
+            ));
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// 市松模様（ドット絵の代わり）
+    fn checker(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            if (x + y) % 2 == 0 { Rgba([255, 0, 0, 255]) } else { Rgba([0, 0, 255, 128]) }
+        })
+    }
+
+    #[test]
+    fn test_nearest_integer_scale_duplicates_pixels() {
+        let image = checker(4, 3);
+        let scaled = resample(&image, 12, 9, ResampleFilter::Nearest);
+        assert_eq!(scaled.dimensions(), (12, 9));
+        for (x, y, pixel) in scaled.enumerate_pixels() {
+            assert_eq!(pixel, image.get_pixel(x / 3, y / 3));
+        }
+        // 縮小は対応する画素を1つ選ぶ
+        let halved = resample(&checker(8, 8), 4, 4, ResampleFilter::Nearest);
+        assert_eq!(halved.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(halved.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+    }
+
+    #[test]
+    fn test_smooth_filters_keep_flat_areas_and_premultiply() {
+        let flat = RgbaImage::from_pixel(10, 6, Rgba([40, 120, 200, 255]));
+        for filter in [ResampleFilter::Mitchell, ResampleFilter::Lanczos3] {
+            let up = resample(&flat, 25, 15, filter);
+            assert!(up.pixels().all(|pixel| pixel == &Rgba([40, 120, 200, 255])));
+            let down = resample(&flat, 3, 2, filter);
+            assert!(down.pixels().all(|pixel| pixel == &Rgba([40, 120, 200, 255])));
+        }
+
+        // 透明な画素の色は混ざらない
+        let mut image = RgbaImage::from_pixel(4, 4, Rgba([0, 255, 0, 0]));
+        image.put_pixel(0, 0, Rgba([255, 0, 0, 255]));
+        image.put_pixel(1, 0, Rgba([255, 0, 0, 255]));
+        let down = resample(&image, 2, 2, ResampleFilter::Mitchell);
+        let pixel = down.get_pixel(0, 0);
+        assert!(pixel[3] > 0 && pixel[3] < 255);
+        assert_eq!(&pixel.0[..3], &[255, 0, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_gpu_resample_matches_cpu() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = crate::drawing_engine::DrawingEngine::new();
+        engine.initialize().await?;
+
+        let image = RgbaImage::from_fn(37, 23, |x, y| Rgba([(x * 7) as u8, (y * 11) as u8, 90, if x % 5 == 0 { 0 } else { 200 }]));
+        for filter in [ResampleFilter::Nearest, ResampleFilter::Mitchell, ResampleFilter::Lanczos3] {
+            for (width, height) in [(74, 46), (15, 9)] {
+                let gpu = engine.resample_image(&image, width, height, filter).await?;
+                let cpu = resample(&image, width, height, filter);
+                let max_difference = gpu.as_raw().iter().zip(cpu.as_raw())
+                    .map(|(a, b)| a.abs_diff(*b))
+                    .max()
+                    .unwrap_or(0);
+                assert!(max_difference <= 1, "{:?} {}x{}: {}", filter, width, height, max_difference);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,95 @@
+use std::time::Duration;
+
+/// 最後の要求から解像度の変更を適用するまでの既定の待ち時間
+pub const DEFAULT_RESIZE_QUIET_PERIOD: Duration = Duration::from_millis(250);
+
+/// 待ち時間の上限
+pub const MAX_RESIZE_QUIET_PERIOD: Duration = Duration::from_secs(5);
+
+/// まだ適用していない解像度の変更（続けて届いた要求をまとめたもの）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PendingResize {
+    /// 最初の要求の変更前のサイズ（まとめた間はキャンバスを変えていないため、これが現在のサイズ）
+    pub from: (u32, u32),
+    /// 最後の要求のサイズ
+    pub to: (u32, u32),
+    /// まとめた要求の数
+    pub requests: u32,
+}
+
+/// キャンバスの解像度変更の要求をまとめ、最後の要求だけを適用する
+///
+/// リサイズハンドルのドラッグ中は要求が続けて届くため、その都度テクスチャを作り直さず、
+/// 要求が途切れて待ち時間が過ぎたとき（または明示的に確定したとき）に最後のサイズだけを適用する。
+/// 要求ごとに世代を振り、待ち終えた要求が最新のときだけ取り出せる。
+#[derive(Debug, Clone, Default)]
+pub struct ResizeDebouncer {
+    pending: Option<PendingResize>,
+    generation: u64,
+}
+
+impl ResizeDebouncer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 要求を記録し、その世代を返す（適用前の要求があれば変更後のサイズだけを差し替える）
+    pub fn request(&mut self, from: (u32, u32), to: (u32, u32)) -> u64 {
+        self.generation += 1;
+        self.pending = Some(match self.pending {
+            Some(pending) => PendingResize { to, requests: pending.requests + 1, ..pending },
+            None => PendingResize { from, to, requests: 1 },
+        });
+        self.generation
+    }
+
+    /// `generation` がまだ最新の要求なら取り出す（後から要求が届いていれば None）
+    pub fn take_if_latest(&mut self, generation: u64) -> Option<PendingResize> {
+        if generation == self.generation {
+            self.pending.take()
+        } else {
+            None
+        }
+    }
+
+    /// 待ち時間を待たずに取り出す（待っている要求は何もせずに終わる）
+    pub fn take(&mut self) -> Option<PendingResize> {
+        self.generation += 1;
+        self.pending.take()
+    }
+
+    pub fn pending(&self) -> Option<PendingResize> {
+        self.pending
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_requests_coalesce_to_last_size() {
+        let mut debouncer = ResizeDebouncer::new();
+        let first = debouncer.request((100, 100), (110, 100));
+        let second = debouncer.request((100, 100), (120, 105));
+        let last = debouncer.request((100, 100), (130, 110));
+
+        // 後から要求が届いた要求は何も取り出さない
+        assert_eq!(debouncer.take_if_latest(first), None);
+        assert_eq!(debouncer.take_if_latest(second), None);
+        assert_eq!(
+            debouncer.take_if_latest(last),
+            Some(PendingResize { from: (100, 100), to: (130, 110), requests: 3 })
+        );
+        assert_eq!(debouncer.pending(), None);
+    }
+
+    #[test]
+    fn test_explicit_take_supersedes_waiting_request() {
+        let mut debouncer = ResizeDebouncer::new();
+        let generation = debouncer.request((64, 64), (128, 128));
+        assert_eq!(debouncer.take().map(|pending| pending.to), Some((128, 128)));
+        assert_eq!(debouncer.take_if_latest(generation), None);
+        assert_eq!(debouncer.take(), None);
+    }
+}
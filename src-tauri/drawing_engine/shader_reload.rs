@@ -0,0 +1,180 @@
+use log::{info, debug, warn};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use super::bounds::ContentBoundsPipeline;
+use super::histogram::HistogramPipeline;
+use super::resample::ResamplePipeline;
+use super::pipeline::BasicDrawPipeline;
+use super::stamp::StampComputePipeline;
+use super::transform::CanvasTransformPipeline;
+
+/// 開発時に差し替えられるシェーダー
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShaderId {
+    DrawVertex,
+    DrawFragment,
+    CanvasTransform,
+    ContentBounds,
+    StampCompute,
+    Histogram,
+    Resample,
+}
+
+impl ShaderId {
+    pub const ALL: [ShaderId; 7] = [
+        ShaderId::DrawVertex,
+        ShaderId::DrawFragment,
+        ShaderId::CanvasTransform,
+        ShaderId::ContentBounds,
+        ShaderId::StampCompute,
+        ShaderId::Histogram,
+        ShaderId::Resample,
+    ];
+
+    /// shaders/ ディレクトリ内のファイル名
+    pub fn file_name(self) -> &'static str {
+        match self {
+            ShaderId::DrawVertex => "draw_vertex.wgsl",
+            ShaderId::DrawFragment => "draw_fragment.wgsl",
+            ShaderId::CanvasTransform => "canvas_transform.wgsl",
+            ShaderId::ContentBounds => "content_bounds.wgsl",
+            ShaderId::StampCompute => "stamp_compute.wgsl",
+            ShaderId::Histogram => "histogram.wgsl",
+            ShaderId::Resample => "resample.wgsl",
+        }
+    }
+
+    /// バイナリに埋め込まれたソース
+    pub fn embedded_source(self) -> &'static str {
+        match self {
+            ShaderId::DrawVertex => BasicDrawPipeline::vertex_shader_source(),
+            ShaderId::DrawFragment => BasicDrawPipeline::fragment_shader_source(),
+            ShaderId::CanvasTransform => CanvasTransformPipeline::shader_source(),
+            ShaderId::ContentBounds => ContentBoundsPipeline::shader_source(),
+            ShaderId::StampCompute => StampComputePipeline::shader_source(),
+            ShaderId::Histogram => HistogramPipeline::shader_source(),
+            ShaderId::Resample => ResamplePipeline::shader_source(),
+        }
+    }
+}
+
+/// 埋め込みのソースの代わりに使うシェーダー（差し替えたものだけを持つ）
+#[derive(Debug, Clone, Default)]
+pub struct ShaderOverrides {
+    sources: HashMap<ShaderId, String>,
+}
+
+impl ShaderOverrides {
+    /// パイプラインの作成に使うソース
+    pub fn source(&self, id: ShaderId) -> &str {
+        self.sources.get(&id).map(String::as_str).unwrap_or_else(|| id.embedded_source())
+    }
+
+    pub fn set(&mut self, id: ShaderId, source: String) {
+        self.sources.insert(id, source);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.sources.is_empty()
+    }
+}
+
+/// shaders/ ディレクトリの WGSL ファイルの変更を更新時刻のポーリングで検出する
+#[derive(Debug)]
+pub struct ShaderWatcher {
+    dir: PathBuf,
+    /// 最後に読み込んだ時点の更新時刻
+    modified: HashMap<ShaderId, SystemTime>,
+}
+
+impl ShaderWatcher {
+    /// ディレクトリに既にあるファイルは最初の `poll` で変更として読み込まれる
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into(), modified: HashMap::new() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// ディレクトリにないシェーダーを埋め込みのソースから書き出す（編集の起点にする）
+    pub fn export_missing(&mut self) -> std::io::Result<usize> {
+        std::fs::create_dir_all(&self.dir)?;
+        let mut exported = 0;
+        for id in ShaderId::ALL {
+            let path = self.dir.join(id.file_name());
+            if !path.exists() {
+                std::fs::write(&path, id.embedded_source().trim_start_matches('\n'))?;
+                if let Some(modified) = self.modified_time(id) {
+                    self.modified.insert(id, modified);
+                }
+                exported += 1;
+            }
+        }
+        if exported > 0 {
+            info!("[ShaderReload] 埋め込みのシェーダーを書き出しました: {} 件 ({})", exported, self.dir.display());
+        }
+        Ok(exported)
+    }
+
+    /// 前回から更新されたシェーダーを読み込む（読めなかったものはエラーの内容を返す）
+    pub fn poll(&mut self) -> Vec<(ShaderId, Result<String, String>)> {
+        let mut changed = Vec::new();
+        for id in ShaderId::ALL {
+            let Some(modified) = self.modified_time(id) else { continue };
+            if self.modified.get(&id) == Some(&modified) {
+                continue;
+            }
+            self.modified.insert(id, modified);
+            let path = self.dir.join(id.file_name());
+            debug!("[ShaderReload] 変更を検出: {}", path.display());
+            let source = std::fs::read_to_string(&path).map_err(|e| {
+                warn!("[ShaderReload] シェーダーを読み込めません: {}: {}", path.display(), e);
+                format!("{} を読み込めません: {}", id.file_name(), e)
+            });
+            changed.push((id, source));
+        }
+        changed
+    }
+
+    fn modified_time(&self, id: ShaderId) -> Option<SystemTime> {
+        std::fs::metadata(self.dir.join(id.file_name())).and_then(|meta| meta.modified()).ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detects_changed_shaders() {
+        let dir = std::env::temp_dir().join(format!("kinegraph_shader_reload_{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        let mut watcher = ShaderWatcher::new(&dir);
+        assert_eq!(watcher.export_missing().unwrap(), ShaderId::ALL.len());
+        assert_eq!(watcher.export_missing().unwrap(), 0);
+        // 書き出した直後は変更なし
+        assert!(watcher.poll().is_empty());
+        // 新しく監視を始めると既存のファイルを読み込む
+        assert_eq!(ShaderWatcher::new(&dir).poll().len(), ShaderId::ALL.len());
+
+        let path = dir.join(ShaderId::StampCompute.file_name());
+        std::fs::write(&path, "// edited").unwrap();
+        let file = std::fs::File::options().write(true).open(&path).unwrap();
+        file.set_modified(SystemTime::now() + std::time::Duration::from_secs(10)).unwrap();
+        let changed = watcher.poll();
+        assert_eq!(changed.len(), 1);
+        assert_eq!(changed[0].0, ShaderId::StampCompute);
+        assert_eq!(changed[0].1.as_deref(), Ok("// edited"));
+        assert!(watcher.poll().is_empty());
+
+        let mut overrides = ShaderOverrides::default();
+        assert_eq!(overrides.source(ShaderId::DrawVertex), ShaderId::DrawVertex.embedded_source());
+        overrides.set(ShaderId::DrawVertex, "// vertex".to_string());
+        assert_eq!(overrides.source(ShaderId::DrawVertex), "// vertex");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,586 @@
+use wgpu::*;
+use log::{info, debug};
+use crate::shaders;
+use serde::Serialize;
+use super::pipeline::{PipelineError, StrokeMesh, Vertex2D};
+use super::texture::ManagedTexture;
+
+/// コンピュートシェーダーのタイルの一辺（1ワークグループが1タイルを受け持つ）
+pub const STAMP_TILE_SIZE: u32 = 16;
+
+/// 三角形で描画するときに円を近似する多角形の頂点数
+const STAMP_SEGMENTS: usize = 16;
+
+/// ブラシのスタンプ1つ（レイヤーのピクセル座標）
+#[repr(C)]
+#[derive(Copy, Clone, Debug, PartialEq, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct Stamp {
+    pub center: [f32; 2],
+    /// 半径（ピクセル）
+    pub radius: f32,
+    /// 不透明度（ブラシの流量と筆圧を掛けたもの。ストローク内で重なるほど濃くなる）
+    pub opacity: f32,
+}
+
+/// 1ストロークで共通のスタンプの形と色
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StampShape {
+    pub color: [f32; 4],
+    /// 0.0=ぼかし最大, 1.0=くっきり
+    pub hardness: f32,
+    /// 先端の回転角（度）
+    pub angle: f32,
+    /// 先端の縦横比（1.0=真円）
+    pub roundness: f32,
+}
+
+impl StampShape {
+    /// スタンプ中心からのずれに対する被覆率（シェーダーと同じ計算。確認用）
+    pub fn coverage(&self, stamp: &Stamp, offset: (f32, f32)) -> f32 {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let x = offset.0 * cos + offset.1 * sin;
+        let y = (-offset.0 * sin + offset.1 * cos) / self.roundness;
+        let distance = (x * x + y * y).sqrt() / stamp.radius.max(f32::EPSILON);
+        if self.hardness >= 1.0 {
+            if distance <= 1.0 { 1.0 } else { 0.0 }
+        } else {
+            ((1.0 - distance) / (1.0 - self.hardness)).clamp(0.0, 1.0)
+        }
+    }
+
+    /// 先端の座標系（真円になる座標系）の点をレイヤーのピクセル座標に戻す
+    fn layer_position(&self, stamp: &Stamp, (x, y): (f32, f32)) -> [f32; 2] {
+        let (sin, cos) = self.angle.to_radians().sin_cos();
+        let y = y * self.roundness;
+        [stamp.center[0] + x * cos - y * sin, stamp.center[1] + x * sin + y * cos]
+    }
+}
+
+/// スタンプの描画方法
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StampRasterizer {
+    /// スタンプごとに多角形の三角形を作り、通常の描画パイプラインで描く
+    Triangles,
+    /// コンピュートシェーダーでスタンプを直接ピクセルに合成する
+    Compute,
+}
+
+/// スタンプを三角形のメッシュにする
+///
+/// 内側（hardness の半径まで）は不透明、外側の輪は縁に向かって透明になる。
+/// 扇形の三角形も頂点を重ねた四角形として積むため、`StrokeMesh::chunks` で分割できる。
+pub fn stamp_mesh(stamps: &[Stamp], shape: &StampShape) -> StrokeMesh {
+    let ring = |stamp: &Stamp, scale: f32| -> Vec<[f32; 2]> {
+        (0..STAMP_SEGMENTS)
+            .map(|k| {
+                let theta = k as f32 / STAMP_SEGMENTS as f32 * std::f32::consts::TAU;
+                shape.layer_position(stamp, (theta.cos() * stamp.radius * scale, theta.sin() * stamp.radius * scale))
+            })
+            .collect()
+    };
+
+    let mut mesh = StrokeMesh::default();
+    for stamp in stamps {
+        let alpha = shape.color[3] * stamp.opacity;
+        if alpha <= 0.0 || stamp.radius <= 0.0 {
+            continue;
+        }
+        let solid = [shape.color[0], shape.color[1], shape.color[2], alpha];
+        let clear = [shape.color[0], shape.color[1], shape.color[2], 0.0];
+        let vertex = |position: [f32; 2], color: [f32; 4]| Vertex2D::new(position[0], position[1], color, stamp.radius * 2.0);
+        let center = vertex(stamp.center, solid);
+
+        let inner = ring(stamp, shape.hardness.clamp(0.0, 1.0));
+        for k in 0..STAMP_SEGMENTS {
+            let next = (k + 1) % STAMP_SEGMENTS;
+            mesh.push_quad([center, center, vertex(inner[next], solid), vertex(inner[k], solid)]);
+        }
+        if shape.hardness < 1.0 {
+            let outer = ring(stamp, 1.0);
+            for k in 0..STAMP_SEGMENTS {
+                let next = (k + 1) % STAMP_SEGMENTS;
+                mesh.push_quad([
+                    vertex(inner[k], solid),
+                    vertex(inner[next], solid),
+                    vertex(outer[k], clear),
+                    vertex(outer[next], clear),
+                ]);
+            }
+        }
+    }
+    mesh
+}
+
+/// レイヤー上の矩形（ピクセル）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StampRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl StampRegion {
+    /// スタンプが掛かるレイヤー上の範囲（1つも掛からなければ None）
+    pub fn covering(stamps: &[Stamp], layer_size: (u32, u32)) -> Option<Self> {
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (f32::MAX, f32::MAX, f32::MIN, f32::MIN);
+        for stamp in stamps.iter().filter(|s| s.radius > 0.0 && s.opacity > 0.0) {
+            let left = stamp.center[0] - stamp.radius;
+            let top = stamp.center[1] - stamp.radius;
+            let right = stamp.center[0] + stamp.radius;
+            let bottom = stamp.center[1] + stamp.radius;
+            if right <= 0.0 || bottom <= 0.0 || left >= layer_size.0 as f32 || top >= layer_size.1 as f32 {
+                continue;
+            }
+            min_x = min_x.min(left);
+            min_y = min_y.min(top);
+            max_x = max_x.max(right);
+            max_y = max_y.max(bottom);
+        }
+        let x = min_x.floor().max(0.0) as u32;
+        let y = min_y.floor().max(0.0) as u32;
+        let right = (max_x.ceil().max(0.0) as u32).min(layer_size.0);
+        let bottom = (max_y.ceil().max(0.0) as u32).min(layer_size.1);
+        if right <= x || bottom <= y {
+            return None;
+        }
+        Some(Self { x, y, width: right - x, height: bottom - y })
+    }
+}
+
+/// タイルごとのスタンプの一覧（タイル i のスタンプは indices[offsets[i]..offsets[i + 1]]）
+#[derive(Debug, Clone, PartialEq)]
+pub struct StampBins {
+    pub region: StampRegion,
+    pub tiles_x: u32,
+    pub tiles_y: u32,
+    pub offsets: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+impl StampBins {
+    /// スタンプを重なるタイルに振り分ける（タイル内ではスタンプの順序を保つ）
+    ///
+    /// スタンプがレイヤーに1つも掛からなければ None。
+    pub fn build(stamps: &[Stamp], layer_size: (u32, u32)) -> Option<Self> {
+        let region = StampRegion::covering(stamps, layer_size)?;
+        let (x, y) = (region.x, region.y);
+        let extent = |stamp: &Stamp| {
+            (
+                stamp.center[0] - stamp.radius,
+                stamp.center[1] - stamp.radius,
+                stamp.center[0] + stamp.radius,
+                stamp.center[1] + stamp.radius,
+            )
+        };
+        let tiles_x = region.width.div_ceil(STAMP_TILE_SIZE);
+        let tiles_y = region.height.div_ceil(STAMP_TILE_SIZE);
+
+        // タイルの範囲（両端を含む。region の外に掛からないスタンプは None）
+        let tile_range = |stamp: &Stamp| {
+            let (left, top, right, bottom) = extent(stamp);
+            let to_tile = |value: f32, origin: u32, tiles: u32| {
+                (((value - origin as f32) / STAMP_TILE_SIZE as f32).floor().max(0.0) as u32).min(tiles - 1)
+            };
+            let outside = right < x as f32 || bottom < y as f32
+                || left > (x + region.width) as f32 || top > (y + region.height) as f32;
+            (!outside && stamp.radius > 0.0 && stamp.opacity > 0.0).then(|| (
+                to_tile(left, x, tiles_x),
+                to_tile(top, y, tiles_y),
+                to_tile(right, x, tiles_x),
+                to_tile(bottom, y, tiles_y),
+            ))
+        };
+
+        // 数え上げてから詰める（計数ソート）
+        let mut offsets = vec![0u32; (tiles_x * tiles_y) as usize + 1];
+        for (tx0, ty0, tx1, ty1) in stamps.iter().filter_map(tile_range) {
+            for ty in ty0..=ty1 {
+                for tx in tx0..=tx1 {
+                    offsets[(ty * tiles_x + tx) as usize + 1] += 1;
+                }
+            }
+        }
+        for i in 1..offsets.len() {
+            offsets[i] += offsets[i - 1];
+        }
+        let mut cursor = offsets.clone();
+        let mut indices = vec![0u32; *offsets.last().unwrap_or(&0) as usize];
+        for (index, stamp) in stamps.iter().enumerate() {
+            let Some((tx0, ty0, tx1, ty1)) = tile_range(stamp) else { continue };
+            for ty in ty0..=ty1 {
+                for tx in tx0..=tx1 {
+                    let tile = (ty * tiles_x + tx) as usize;
+                    indices[cursor[tile] as usize] = index as u32;
+                    cursor[tile] += 1;
+                }
+            }
+        }
+
+        Some(Self { region, tiles_x, tiles_y, offsets, indices })
+    }
+}
+
+/// シェーダーに渡すパラメータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct StampParams {
+    color: [f32; 4],
+    origin: [u32; 2],
+    size: [u32; 2],
+    tiles_x: u32,
+    hardness: f32,
+    cos_angle: f32,
+    sin_angle: f32,
+    roundness: f32,
+    _padding: [f32; 3],
+}
+
+/// 密なスタンプをコンピュートシェーダーで直接レイヤーに合成するパイプライン
+///
+/// スタンプを CPU で 16x16 のタイルに振り分け、1ワークグループが1タイルの画素を受け持つ。
+/// 各画素は自分のタイルのスタンプを順に合成するため、スタンプの重なり順は三角形での描画と変わらない。
+/// sRGB のレイヤーテクスチャはストレージテクスチャにできないため、合成結果を作業用テクスチャに書き出し、
+/// 変更した矩形だけをレイヤーへコピーする。
+pub struct StampComputePipeline {
+    compute_pipeline: ComputePipeline,
+    bind_group_layout: BindGroupLayout,
+    /// 作業用テクスチャ（足りなくなったときだけ作り直す）
+    scratch: Option<Texture>,
+}
+
+impl StampComputePipeline {
+    pub fn new(device: &Device, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        Self::with_shader(device, cache, Self::shader_source())
+    }
+
+    pub fn with_shader(device: &Device, cache: Option<&PipelineCache>, source: &str) -> Result<Self, PipelineError> {
+        info!("[StampComputePipeline] パイプライン作成開始");
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Stamp Compute Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(source)?.into()),
+        });
+
+        let storage = |binding: u32| BindGroupLayoutEntry {
+            binding,
+            visibility: ShaderStages::COMPUTE,
+            ty: BindingType::Buffer {
+                ty: BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Stamp Compute Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+                storage(3),
+                BindGroupLayoutEntry {
+                    binding: 4,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 5,
+                    visibility: ShaderStages::COMPUTE,
+                    ty: BindingType::StorageTexture {
+                        access: StorageTextureAccess::WriteOnly,
+                        format: TextureFormat::Rgba8Unorm,
+                        view_dimension: TextureViewDimension::D2,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Stamp Compute Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let compute_pipeline = device.create_compute_pipeline(&ComputePipelineDescriptor {
+            label: Some("Stamp Compute Pipeline"),
+            layout: Some(&layout),
+            module: &shader,
+            entry_point: Some("cs_main"),
+            compilation_options: PipelineCompilationOptions::default(),
+            cache,
+        });
+
+        info!("[StampComputePipeline] パイプライン作成完了");
+        Ok(Self {
+            compute_pipeline,
+            bind_group_layout,
+            scratch: None,
+        })
+    }
+
+    /// 作業用テクスチャ（指定の大きさ以上）を用意
+    fn ensure_scratch(&mut self, device: &Device, width: u32, height: u32) {
+        let fits = self.scratch.as_ref()
+            .is_some_and(|texture| texture.width() >= width && texture.height() >= height);
+        if !fits {
+            let (width, height) = match &self.scratch {
+                Some(texture) => (width.max(texture.width()), height.max(texture.height())),
+                None => (width, height),
+            };
+            debug!("[StampComputePipeline] 作業用テクスチャを作成: {}x{}", width, height);
+            self.scratch = Some(device.create_texture(&TextureDescriptor {
+                label: Some("Stamp Scratch Texture"),
+                size: Extent3d { width, height, depth_or_array_layers: 1 },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: TextureDimension::D2,
+                format: TextureFormat::Rgba8Unorm,
+                usage: TextureUsages::STORAGE_BINDING | TextureUsages::COPY_SRC,
+                view_formats: &[],
+            }));
+        }
+    }
+
+    /// スタンプをレイヤーテクスチャに合成（合成した画素数を返す）
+    pub fn draw(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        target: &ManagedTexture,
+        stamps: &[Stamp],
+        shape: &StampShape,
+    ) -> Result<u64, PipelineError> {
+        let Some(bins) = StampBins::build(stamps, (target.spec.width, target.spec.height)) else {
+            return Ok(0);
+        };
+        if bins.indices.is_empty() {
+            return Ok(0);
+        }
+        let region = bins.region;
+        debug!("[StampComputePipeline] {} スタンプを {}x{} タイルで合成", stamps.len(), bins.tiles_x, bins.tiles_y);
+
+        let (sin_angle, cos_angle) = shape.angle.to_radians().sin_cos();
+        let params = StampParams {
+            color: shape.color,
+            origin: [region.x, region.y],
+            size: [region.width, region.height],
+            tiles_x: bins.tiles_x,
+            hardness: shape.hardness.clamp(0.0, 1.0),
+            cos_angle,
+            sin_angle,
+            roundness: shape.roundness.max(f32::EPSILON),
+            _padding: [0.0; 3],
+        };
+        let buffer = |label: &str, contents: &[u8], usage: BufferUsages| {
+            let buffer = device.create_buffer(&BufferDescriptor {
+                label: Some(label),
+                size: contents.len() as u64,
+                usage: usage | BufferUsages::COPY_DST,
+                mapped_at_creation: false,
+            });
+            queue.write_buffer(&buffer, 0, contents);
+            buffer
+        };
+        let params_buffer = buffer("Stamp Params Buffer", bytemuck::bytes_of(&params), BufferUsages::UNIFORM);
+        let stamp_buffer = buffer("Stamp Buffer", bytemuck::cast_slice(stamps), BufferUsages::STORAGE);
+        let offset_buffer = buffer("Stamp Tile Offset Buffer", bytemuck::cast_slice(&bins.offsets), BufferUsages::STORAGE);
+        let index_buffer = buffer("Stamp Tile Index Buffer", bytemuck::cast_slice(&bins.indices), BufferUsages::STORAGE);
+
+        self.ensure_scratch(device, region.width, region.height);
+        let scratch = self.scratch.as_ref().expect("直前に作成済み");
+        let scratch_view = scratch.create_view(&TextureViewDescriptor::default());
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Stamp Compute Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry { binding: 0, resource: params_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 1, resource: stamp_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 2, resource: offset_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 3, resource: index_buffer.as_entire_binding() },
+                BindGroupEntry { binding: 4, resource: BindingResource::TextureView(&target.view) },
+                BindGroupEntry { binding: 5, resource: BindingResource::TextureView(&scratch_view) },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Stamp Compute Encoder"),
+        });
+        {
+            let mut compute_pass = encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("Stamp Compute Pass"),
+                timestamp_writes: None,
+            });
+            compute_pass.set_pipeline(&self.compute_pipeline);
+            compute_pass.set_bind_group(0, &bind_group, &[]);
+            compute_pass.dispatch_workgroups(bins.tiles_x, bins.tiles_y, 1);
+        }
+        // 書き出した矩形をレイヤーへ戻す（sRGB の有無だけが異なる形式どうしはコピーできる）
+        encoder.copy_texture_to_texture(
+            TexelCopyTextureInfo {
+                texture: scratch,
+                mip_level: 0,
+                origin: Origin3d::ZERO,
+                aspect: TextureAspect::All,
+            },
+            TexelCopyTextureInfo {
+                texture: &target.texture,
+                mip_level: 0,
+                origin: Origin3d { x: region.x, y: region.y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            Extent3d { width: region.width, height: region.height, depth_or_array_layers: 1 },
+        );
+        queue.submit(std::iter::once(encoder.finish()));
+
+        Ok(region.width as u64 * region.height as u64)
+    }
+
+    /// スタンプを合成するシェーダー（WGSL）
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        #import color
+
+        struct Stamp {
+            center: vec2<f32>,
+            radius: f32,
+            opacity: f32,
+        }
+
+        struct StampParams {
+            color: vec4<f32>,
+            origin: vec2<u32>,
+            size: vec2<u32>,
+            tiles_x: u32,
+            hardness: f32,
+            cos_angle: f32,
+            sin_angle: f32,
+            roundness: f32,
+        }
+
+        @group(0) @binding(0) var<uniform> params: StampParams;
+        @group(0) @binding(1) var<storage, read> stamps: array<Stamp>;
+        @group(0) @binding(2) var<storage, read> tile_offsets: array<u32>;
+        @group(0) @binding(3) var<storage, read> tile_stamps: array<u32>;
+        @group(0) @binding(4) var layer: texture_2d<f32>;
+        @group(0) @binding(5) var output: texture_storage_2d<rgba8unorm, write>;
+
+        fn coverage(stamp: Stamp, pixel: vec2<f32>) -> f32 {
+            let offset = pixel - stamp.center;
+            let x = offset.x * params.cos_angle + offset.y * params.sin_angle;
+            let y = (-offset.x * params.sin_angle + offset.y * params.cos_angle) / params.roundness;
+            let distance = length(vec2<f32>(x, y)) / max(stamp.radius, 1e-6);
+            if params.hardness >= 1.0 {
+                return select(0.0, 1.0, distance <= 1.0);
+            }
+            return clamp((1.0 - distance) / (1.0 - params.hardness), 0.0, 1.0);
+        }
+
+        @compute @workgroup_size(16, 16)
+        fn cs_main(
+            @builtin(global_invocation_id) id: vec3<u32>,
+            @builtin(workgroup_id) group: vec3<u32>,
+        ) {
+            if id.x >= params.size.x || id.y >= params.size.y {
+                return;
+            }
+            let pixel = params.origin + id.xy;
+            let center = vec2<f32>(pixel) + vec2<f32>(0.5);
+
+            // レイヤーは sRGB なので読み込んだ値は線形。描画パイプラインと同じ式で合成する
+            var color = textureLoad(layer, vec2<i32>(pixel), 0);
+            let tile = group.y * params.tiles_x + group.x;
+            for (var i = tile_offsets[tile]; i < tile_offsets[tile + 1u]; i++) {
+                let stamp = stamps[tile_stamps[i]];
+                let alpha = params.color.a * stamp.opacity * coverage(stamp, center);
+                if alpha > 0.0 {
+                    color = blend_over(color, params.color.rgb, alpha);
+                }
+            }
+            textureStore(output, vec2<i32>(id.xy), vec4<f32>(linear_to_srgb(color.rgb), color.a));
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stamp(x: f32, y: f32, radius: f32) -> Stamp {
+        Stamp { center: [x, y], radius, opacity: 1.0 }
+    }
+
+    #[test]
+    fn test_bins_keep_order_and_clip_to_layer() {
+        let stamps = [stamp(8.0, 8.0, 4.0), stamp(30.0, 8.0, 4.0), stamp(20.0, 8.0, 6.0), stamp(-50.0, 0.0, 2.0)];
+        let bins = StampBins::build(&stamps, (64, 64)).unwrap();
+        assert_eq!(bins.region, StampRegion { x: 4, y: 2, width: 30, height: 12 });
+        assert_eq!((bins.tiles_x, bins.tiles_y), (2, 1));
+        // 1つ目のタイルは x=4〜19、2つ目は x=20〜33
+        assert_eq!(&bins.indices[bins.offsets[0] as usize..bins.offsets[1] as usize], &[0, 2]);
+        assert_eq!(&bins.indices[bins.offsets[1] as usize..bins.offsets[2] as usize], &[1, 2]);
+
+        assert_eq!(StampBins::build(&[stamp(-50.0, 0.0, 2.0)], (64, 64)), None);
+    }
+
+    #[test]
+    fn test_coverage_and_mesh() {
+        let shape = StampShape { color: [0.0, 0.0, 0.0, 1.0], hardness: 0.5, angle: 90.0, roundness: 0.5 };
+        let s = stamp(0.0, 0.0, 10.0);
+        assert_eq!(shape.coverage(&s, (0.0, 0.0)), 1.0);
+        // 90度回転した楕円は縦に長い
+        assert!(shape.coverage(&s, (0.0, 7.0)) > 0.0);
+        assert_eq!(shape.coverage(&s, (7.0, 0.0)), 0.0);
+
+        let mesh = stamp_mesh(&[s, s], &shape);
+        assert_eq!(mesh.vertices.len(), 2 * STAMP_SEGMENTS * 2 * 4);
+        // 四角形単位なので上限で分割できる
+        assert!(mesh.chunks(64).iter().all(|chunk| chunk.vertices.len() <= 64));
+    }
+
+    #[tokio::test]
+    async fn test_compute_matches_triangles() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = crate::drawing_engine::DrawingEngine::new();
+        engine.initialize().await?;
+        engine.create_layer_texture("triangles", 64, 64)?;
+        engine.create_layer_texture("compute", 64, 64)?;
+
+        let shape = StampShape { color: [0.9, 0.2, 0.1, 0.8], hardness: 1.0, angle: 0.0, roundness: 1.0 };
+        let stamps: Vec<Stamp> = (0..40)
+            .map(|i| Stamp { center: [31.0 + (i % 3) as f32, 31.0 + (i % 2) as f32], radius: 20.0, opacity: 0.05 })
+            .collect();
+        engine.draw_stamps_to_layer("triangles", &stamps, &shape, StampRasterizer::Triangles)?;
+        engine.draw_stamps_to_layer("compute", &stamps, &shape, StampRasterizer::Compute)?;
+
+        let triangles = engine.get_layer_image("triangles").await?;
+        let compute = engine.get_layer_image("compute").await?;
+        // 全てのスタンプが重なる中心はほぼ同じ色になる
+        // （三角形での描画は1枚ごとに 8bit の sRGB へ丸めるため、薄いスタンプを重ねると色が少しずれる）
+        let (a, b) = (triangles.get_pixel(32, 32), compute.get_pixel(32, 32));
+        assert!(a.0[..3].iter().zip(&b.0[..3]).all(|(a, b)| a.abs_diff(*b) <= 8), "{:?} != {:?}", a, b);
+        assert!(a[3].abs_diff(b[3]) <= 2, "{:?} != {:?}", a, b);
+        assert_eq!(compute.get_pixel(1, 1)[3], 0);
+        // 円の近似による縁の差を除けば被覆率もほぼ同じ
+        let coverage = |image: &image::RgbaImage| image.pixels().map(|p| p[3] as f64).sum::<f64>();
+        let ratio = coverage(&compute) / coverage(&triangles);
+        assert!((0.95..1.05).contains(&ratio), "被覆率の差が大きすぎます: {}", ratio);
+        Ok(())
+    }
+}
@@ -0,0 +1,296 @@
+use std::error::Error;
+use std::fmt;
+
+/// 線幅の上限（ピクセル。これより太い指定は上限に揃える）
+pub const MAX_STROKE_WIDTH: f32 = 1000.0;
+
+/// レイヤーの外に受け付ける範囲（ピクセル。ペンがキャンバスの外へ出た分はこの範囲まで描く）
+pub const STROKE_BOUNDS_MARGIN: f32 = 4096.0;
+
+/// 直前の点とこれより近い点は重複として捨てる（長さがゼロの線分を作らない）
+pub const MIN_POINT_SPACING: f32 = 1e-3;
+
+/// 描画エンジンに渡す前のストロークの点（レイヤーのピクセル座標）
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct StrokeInputPoint {
+    pub x: f32,
+    pub y: f32,
+    /// 筆圧（0.0〜1.0。筆圧のない入力は 1.0）
+    pub pressure: f32,
+    /// ストローク開始からの経過時間（ミリ秒。検査せずにそのまま残す）
+    pub time_ms: Option<u32>,
+}
+
+impl StrokeInputPoint {
+    pub fn new(x: f32, y: f32, pressure: f32) -> Self {
+        Self { x, y, pressure, time_ms: None }
+    }
+}
+
+/// 入力を整えたときに捨てた・直した点の数
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SanitizeReport {
+    /// 座標か筆圧が NaN・無限大だった点
+    pub non_finite: usize,
+    /// レイヤーから `STROKE_BOUNDS_MARGIN` より離れていた点
+    pub out_of_bounds: usize,
+    /// 直前の点と重なっていた点
+    pub duplicates: usize,
+    /// 筆圧を 0.0〜1.0 に収めた点
+    pub clamped_pressure: usize,
+    /// 線幅を `MAX_STROKE_WIDTH` に揃えた
+    pub clamped_width: bool,
+}
+
+impl SanitizeReport {
+    /// 捨てた点の数
+    pub fn dropped(&self) -> usize {
+        self.non_finite + self.out_of_bounds + self.duplicates
+    }
+
+    /// 入力をそのまま使えたか
+    pub fn is_clean(&self) -> bool {
+        *self == SanitizeReport::default()
+    }
+}
+
+/// 整えたストロークの入力
+#[derive(Debug, Clone, PartialEq)]
+pub struct SanitizedStroke {
+    pub points: Vec<StrokeInputPoint>,
+    /// 各チャンネルを 0.0〜1.0 に収めた色
+    pub color: [f32; 4],
+    pub width: f32,
+    pub report: SanitizeReport,
+}
+
+/// ストロークの入力のエラー型（整えても描けない入力）
+#[derive(Debug, Clone, PartialEq)]
+pub enum StrokeInputError {
+    /// 線幅が NaN・無限大・0 以下
+    InvalidWidth(f32),
+    /// 色に NaN・無限大が含まれる
+    InvalidColor([f32; 4]),
+    /// 使える点が1つもない（`received` は届いた点の数）
+    NoValidPoints { received: usize },
+}
+
+impl fmt::Display for StrokeInputError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StrokeInputError::InvalidWidth(width) => write!(f, "線幅は0より大きい有限の値である必要があります: {}", width),
+            StrokeInputError::InvalidColor(color) => write!(f, "色に無効な値が含まれています: {:?}", color),
+            StrokeInputError::NoValidPoints { received } => write!(f, "ストロークに有効な点がありません ({} 点を受信)", received),
+        }
+    }
+}
+
+impl Error for StrokeInputError {}
+
+/// 点がレイヤーの外へ `STROKE_BOUNDS_MARGIN` より離れていないか（NaN・無限大は範囲外）
+///
+/// 入力を整えるとき（`sanitize_stroke`）と記録済みの操作を検証するとき（`history::validate_operations`）で
+/// 同じ範囲を使う。
+pub fn within_stroke_bounds(x: f32, y: f32, (width, height): (u32, u32)) -> bool {
+    (-STROKE_BOUNDS_MARGIN..=width as f32 + STROKE_BOUNDS_MARGIN).contains(&x)
+        && (-STROKE_BOUNDS_MARGIN..=height as f32 + STROKE_BOUNDS_MARGIN).contains(&y)
+}
+
+/// 色と線幅を検査して整える（逐次描画で点より先に色・線幅が決まるときにも使う）
+///
+/// 線幅が 0 以下・有限でない場合や色が有限でない場合は拒否し、色は各チャンネル 0.0〜1.0、
+/// 線幅は `MAX_STROKE_WIDTH` までに収める。
+pub fn sanitize_style(color: [f32; 4], width: f32) -> Result<([f32; 4], f32), StrokeInputError> {
+    if !(width.is_finite() && width > 0.0) {
+        return Err(StrokeInputError::InvalidWidth(width));
+    }
+    if !color.iter().all(|channel| channel.is_finite()) {
+        return Err(StrokeInputError::InvalidColor(color));
+    }
+    Ok((color.map(|channel| channel.clamp(0.0, 1.0)), width.min(MAX_STROKE_WIDTH)))
+}
+
+/// ストロークの入力を検査し、描画エンジンが安全に扱える形に整える
+///
+/// ベクターのストローク（三角形分割）とピクセルブラシ（Bresenham）の両方が描く前に通す。
+/// 結果は入力だけで決まり、整えた結果をもう一度通しても変わらない。
+///
+/// - 座標・筆圧が NaN・無限大の点と、`layer_size` の外へ `STROKE_BOUNDS_MARGIN` より離れた点は捨てる
+/// - 直前の点（`previous` を含む）と `MIN_POINT_SPACING` 未満しか離れていない点は捨てる
+/// - 筆圧は 0.0〜1.0 に収め、色と線幅は `sanitize_style` で検査する
+///
+/// `previous` は逐次描画で既に受け付けた最後の点。これがあるときは点が残らなくてもエラーにしない。
+pub fn sanitize_stroke(
+    points: &[StrokeInputPoint],
+    color: [f32; 4],
+    width: f32,
+    layer_size: (u32, u32),
+    previous: Option<StrokeInputPoint>,
+) -> Result<SanitizedStroke, StrokeInputError> {
+    let (color, sanitized_width) = sanitize_style(color, width)?;
+    let mut report = SanitizeReport { clamped_width: width > MAX_STROKE_WIDTH, ..SanitizeReport::default() };
+    let mut last = previous;
+    let mut sanitized = Vec::with_capacity(points.len());
+    for point in points {
+        if !(point.x.is_finite() && point.y.is_finite() && point.pressure.is_finite()) {
+            report.non_finite += 1;
+            continue;
+        }
+        if !within_stroke_bounds(point.x, point.y, layer_size) {
+            report.out_of_bounds += 1;
+            continue;
+        }
+        if last.is_some_and(|last| (point.x - last.x).hypot(point.y - last.y) < MIN_POINT_SPACING) {
+            report.duplicates += 1;
+            continue;
+        }
+        let pressure = point.pressure.clamp(0.0, 1.0);
+        if pressure != point.pressure {
+            report.clamped_pressure += 1;
+        }
+        let point = StrokeInputPoint { pressure, ..*point };
+        sanitized.push(point);
+        last = Some(point);
+    }
+
+    if sanitized.is_empty() && previous.is_none() {
+        return Err(StrokeInputError::NoValidPoints { received: points.len() });
+    }
+    Ok(SanitizedStroke {
+        points: sanitized,
+        color,
+        width: sanitized_width,
+        report,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drawing_engine::pipeline::DrawStroke;
+    use crate::drawing_engine::pixel::pixel_stroke_path;
+
+    const LAYER: (u32, u32) = (64, 48);
+
+    /// 壊れた値を混ぜた入力を作る擬似乱数（SplitMix64。毎回同じ列になる）
+    struct Fuzzer(u64);
+
+    impl Fuzzer {
+        fn next_u64(&mut self) -> u64 {
+            self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = self.0;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        }
+
+        fn value(&mut self, range: f32) -> f32 {
+            const HOSTILE: [f32; 8] = [f32::NAN, f32::INFINITY, f32::NEG_INFINITY, f32::MAX, f32::MIN, -0.0, 1e-30, 1e9];
+            match self.next_u64() % 10 {
+                0 => HOSTILE[(self.next_u64() % HOSTILE.len() as u64) as usize],
+                _ => ((self.next_u64() >> 40) as f32 / (1u64 << 24) as f32) * range * 2.0 - range / 2.0,
+            }
+        }
+
+        fn points(&mut self) -> Vec<StrokeInputPoint> {
+            let count = (self.next_u64() % 16) as usize;
+            let mut points: Vec<StrokeInputPoint> = Vec::with_capacity(count);
+            for _ in 0..count {
+                // 重複した点も混ぜる
+                let point = match points.last() {
+                    Some(&last) if self.next_u64() & 3 == 0 => last,
+                    _ => StrokeInputPoint::new(self.value(128.0), self.value(96.0), self.value(1.5)),
+                };
+                points.push(point);
+            }
+            points
+        }
+    }
+
+    #[test]
+    fn test_hostile_points_are_dropped_or_clamped() {
+        let points = [
+            StrokeInputPoint::new(1.0, 1.0, 0.5),
+            StrokeInputPoint::new(f32::NAN, 2.0, 0.5),
+            StrokeInputPoint::new(1.0, 1.0, 0.9), // 重複
+            StrokeInputPoint::new(5.0, f32::INFINITY, 0.5),
+            StrokeInputPoint::new(-5000.0, 10.0, 0.5),
+            StrokeInputPoint::new(10.0, 10.0, f32::NAN),
+            StrokeInputPoint::new(10.0, 10.0, 3.0),
+        ];
+        let stroke = sanitize_stroke(&points, [1.5, -0.5, 0.0, 1.0], 5000.0, LAYER, None).unwrap();
+        assert_eq!(stroke.points, vec![StrokeInputPoint::new(1.0, 1.0, 0.5), StrokeInputPoint::new(10.0, 10.0, 1.0)]);
+        assert_eq!(stroke.color, [1.0, 0.0, 0.0, 1.0]);
+        assert_eq!(stroke.width, MAX_STROKE_WIDTH);
+        assert_eq!(stroke.report, SanitizeReport {
+            non_finite: 3,
+            out_of_bounds: 1,
+            duplicates: 1,
+            clamped_pressure: 1,
+            clamped_width: true,
+        });
+
+        let nan = [StrokeInputPoint::new(f32::NAN, 0.0, 1.0)];
+        assert_eq!(sanitize_stroke(&nan, [0.0; 4], 2.0, LAYER, None), Err(StrokeInputError::NoValidPoints { received: 1 }));
+        // 逐次描画で既に点があれば、何も残らなくてもエラーにしない
+        let previous = StrokeInputPoint::new(3.0, 3.0, 1.0);
+        let repeated = [StrokeInputPoint::new(3.0, 3.0005, 1.0)];
+        assert!(sanitize_stroke(&repeated, [0.0; 4], 2.0, LAYER, Some(previous)).unwrap().points.is_empty());
+        for width in [0.0, -1.0, f32::NAN, f32::INFINITY] {
+            assert!(matches!(sanitize_stroke(&repeated, [0.0; 4], width, LAYER, None), Err(StrokeInputError::InvalidWidth(_))));
+        }
+        assert!(matches!(
+            sanitize_stroke(&repeated, [0.0, f32::NAN, 0.0, 1.0], 2.0, LAYER, None),
+            Err(StrokeInputError::InvalidColor(_))
+        ));
+    }
+
+    #[test]
+    fn test_sanitized_strokes_are_safe_for_both_engines() {
+        let mut fuzzer = Fuzzer(0x6b69_6e65);
+        for _ in 0..500 {
+            let points = fuzzer.points();
+            let width = fuzzer.value(40.0);
+            let previous = (fuzzer.next_u64() & 3 == 0).then(|| StrokeInputPoint::new(10.0, 10.0, 1.0));
+            let first = sanitize_stroke(&points, [0.2, 0.4, 0.6, 1.0], width, LAYER, previous);
+
+            // 同じ入力は同じ結果になる（拒否するかどうかも含めて。NaN の線幅を比べられるよう表示で比べる）
+            let second = sanitize_stroke(&points, [0.2, 0.4, 0.6, 1.0], width, LAYER, previous);
+            assert_eq!(format!("{:?}", first), format!("{:?}", second));
+            let Ok(stroke) = first else { continue };
+
+            let mut last = previous;
+            for point in &stroke.points {
+                assert!(point.x.is_finite() && point.y.is_finite());
+                assert!((-STROKE_BOUNDS_MARGIN..=LAYER.0 as f32 + STROKE_BOUNDS_MARGIN).contains(&point.x));
+                assert!((-STROKE_BOUNDS_MARGIN..=LAYER.1 as f32 + STROKE_BOUNDS_MARGIN).contains(&point.y));
+                assert!((0.0..=1.0).contains(&point.pressure));
+                if let Some(last) = last {
+                    assert!((point.x - last.x).hypot(point.y - last.y) >= MIN_POINT_SPACING);
+                }
+                last = Some(*point);
+            }
+            assert!(stroke.width > 0.0 && stroke.width <= MAX_STROKE_WIDTH);
+
+            // 整えた結果をもう一度通しても変わらない
+            let again = sanitize_stroke(&stroke.points, stroke.color, stroke.width, LAYER, previous).unwrap();
+            assert_eq!(again.points, stroke.points);
+            assert!(again.report.is_clean());
+
+            // ベクターのストロークは有限の頂点だけを作る
+            let mut draw_stroke = DrawStroke::new(stroke.color, stroke.width);
+            for point in &stroke.points {
+                draw_stroke.add_point(point.x, point.y, point.pressure);
+            }
+            let mesh = draw_stroke.to_mesh();
+            assert!(mesh.vertices.iter().all(|vertex| vertex.position.iter().all(|v| v.is_finite())));
+            assert!(mesh.vertices.len() <= stroke.points.len().saturating_sub(1) * 4);
+
+            // ピクセルブラシの経路は受け付ける範囲の中で、線分ごとの長さも範囲の大きさまでに収まる
+            let xy: Vec<(f32, f32)> = stroke.points.iter().map(|point| (point.x, point.y)).collect();
+            let path = pixel_stroke_path(&xy, false);
+            let span = (LAYER.0 + LAYER.1) as usize + 4 * STROKE_BOUNDS_MARGIN as usize + 2;
+            assert!(path.len() <= stroke.points.len() * span);
+        }
+    }
+}
@@ -0,0 +1,40 @@
+use rayon::prelude::*;
+use log::debug;
+use wgpu::*;
+
+/// まとめて送信したコマンドの完了待ち
+///
+/// wgpu はデバイスごとにキューが1つで、同じキューに送ったコマンドは送信順に実行される。
+/// そのため先に送信した描画（書き込み）はこの送信より前に完了しており、
+/// フェンスを待ってからバッファを読めば書き込み途中の画素を読むことはない。
+#[derive(Debug, Clone)]
+pub struct SubmissionFence {
+    index: SubmissionIndex,
+}
+
+impl SubmissionFence {
+    /// 送信したコマンドが GPU で完了するまで待つ（マップ済みバッファのコールバックもここで呼ばれる）
+    pub fn wait(&self, device: &Device) -> Result<(), PollError> {
+        device.poll(MaintainBase::WaitForSubmissionIndex(self.index.clone())).map(|_| ())
+    }
+}
+
+/// 互いに依存しないコマンドを項目ごとのエンコーダーに並列で記録し、1回の送信にまとめる
+///
+/// 各エンコーダーは別のリソースだけに触れる前提で、記録の順序は送信時に `items` の順に揃える。
+pub fn submit_parallel<T, F>(device: &Device, queue: &Queue, label: &str, items: &[T], record: F) -> SubmissionFence
+where
+    T: Sync,
+    F: Fn(&mut CommandEncoder, &T) + Sync,
+{
+    let command_buffers: Vec<CommandBuffer> = items.par_iter()
+        .map(|item| {
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor { label: Some(label) });
+            record(&mut encoder, item);
+            encoder.finish()
+        })
+        .collect();
+
+    debug!("[Submit] {} 個のコマンドバッファをまとめて送信: {}", command_buffers.len(), label);
+    SubmissionFence { index: queue.submit(command_buffers) }
+}
@@ -1,9 +1,13 @@
 use wgpu::*;
 use log::{info, debug, error};
-use std::collections::{HashMap, VecDeque};
+use serde::Serialize;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, Ordering};
 use std::error::Error;
 use std::fmt;
+use super::limits::DEFAULT_MAX_CANVAS_DIMENSION;
+use super::submit::submit_parallel;
 
 /// テクスチャ管理のエラー型
 #[derive(Debug)]
@@ -71,7 +75,10 @@ impl TextureSpec {
             width,
             height,
             format: TextureFormat::Rgba8UnormSrgb,
-            usage: TextureUsages::RENDER_ATTACHMENT | TextureUsages::COPY_SRC | TextureUsages::COPY_DST,
+            usage: TextureUsages::RENDER_ATTACHMENT
+                | TextureUsages::TEXTURE_BINDING
+                | TextureUsages::COPY_SRC
+                | TextureUsages::COPY_DST,
         }
     }
 
@@ -107,6 +114,15 @@ impl ManagedTexture {
         }
     }
 
+    /// sRGB の変換をしないビュー（保存されている値をそのまま読み書きする）
+    pub fn encoded_view(&self) -> TextureView {
+        self.texture.create_view(&TextureViewDescriptor {
+            label: Some("Encoded Layer View"),
+            format: Some(self.spec.format.remove_srgb_suffix()),
+            ..Default::default()
+        })
+    }
+
     pub fn mark_used(&mut self) {
         self.last_used = std::time::Instant::now();
         self.is_in_use = true;
@@ -117,10 +133,33 @@ impl ManagedTexture {
     }
 }
 
+/// テクスチャプールの整理の結果
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct PoolDefragment {
+    pub pool_entries_before: usize,
+    pub pool_entries_after: usize,
+    /// プールに枠のあるテクスチャの仕様（サイズ）の数
+    pub pool_specs_before: usize,
+    pub pool_specs_after: usize,
+    pub released_textures: usize,
+    pub freed_bytes: u64,
+}
+
+/// テクスチャ全体の内容を識別するキー（幅, 高さ, ピクセルのハッシュ）
+type ContentKey = (u32, u32, u64);
+
 /// テクスチャ管理システム
+///
+/// 同じ内容のレイヤーは1枚のテクスチャを共有し、書き込む前に複製する（コピーオンライト）。
 pub struct TextureManager {
     /// アクティブなテクスチャ（レイヤーID -> テクスチャID）
     layer_textures: HashMap<String, String>,
+    /// テクスチャを参照しているレイヤー数（テクスチャID -> 数。2 以上なら共有中）
+    texture_refs: HashMap<String, usize>,
+    /// 全体を書き込んだテクスチャの内容（内容のキー -> テクスチャID）
+    content_index: HashMap<ContentKey, String>,
+    /// `content_index` の逆引き（テクスチャID -> 内容のキー。書き込みで内容が変わったら消す）
+    content_keys: HashMap<String, ContentKey>,
     /// 管理対象のテクスチャ（テクスチャID -> テクスチャ）
     textures: HashMap<String, ManagedTexture>,
     /// テクスチャプール（仕様 -> 利用可能なテクスチャIDキュー）
@@ -131,6 +170,31 @@ pub struct TextureManager {
     memory_limit: u64,
     /// 次のテクスチャID
     next_texture_id: u64,
+    /// 読み出し中のステージングバッファの合計サイズ（バイト）
+    staging_memory_usage: AtomicU64,
+    /// レイヤーテクスチャの最大辺（デバイスの上限に合わせて設定される）
+    max_dimension: u32,
+    /// sRGB の変換をしないビューを作れるようにする（sRGB で合成する描画で使う）
+    encoded_views: bool,
+}
+
+/// ステージングバッファのサイズを読み出しの間だけ計上する
+struct StagingAllocation<'a> {
+    counter: &'a AtomicU64,
+    size: u64,
+}
+
+impl<'a> StagingAllocation<'a> {
+    fn new(counter: &'a AtomicU64, size: u64) -> Self {
+        counter.fetch_add(size, Ordering::Relaxed);
+        Self { counter, size }
+    }
+}
+
+impl Drop for StagingAllocation<'_> {
+    fn drop(&mut self) {
+        self.counter.fetch_sub(self.size, Ordering::Relaxed);
+    }
 }
 
 impl TextureManager {
@@ -139,14 +203,31 @@ impl TextureManager {
         info!("[TextureManager] 新しいインスタンスを作成");
         Self {
             layer_textures: HashMap::new(),
+            texture_refs: HashMap::new(),
+            content_index: HashMap::new(),
+            content_keys: HashMap::new(),
             textures: HashMap::new(),
             texture_pool: HashMap::new(),
             current_memory_usage: 0,
             memory_limit: 2 * 1024 * 1024 * 1024, // 2GB
             next_texture_id: 1,
+            staging_memory_usage: AtomicU64::new(0),
+            max_dimension: DEFAULT_MAX_CANVAS_DIMENSION,
+            encoded_views: false,
         }
     }
 
+    /// sRGB の変換をしないビューを作れるテクスチャにするか（デバイスが対応している場合のみ有効にする）
+    pub fn set_encoded_views(&mut self, enabled: bool) {
+        self.encoded_views = enabled;
+    }
+
+    /// レイヤーテクスチャの最大辺を設定
+    pub fn set_max_dimension(&mut self, max_dimension: u32) {
+        debug!("[TextureManager] レイヤーテクスチャの最大辺を設定: {}", max_dimension);
+        self.max_dimension = max_dimension;
+    }
+
     /// メモリ使用量上限を設定
     pub fn set_memory_limit(&mut self, limit_bytes: u64) {
         debug!("[TextureManager] メモリ使用量上限を設定: {} bytes", limit_bytes);
@@ -163,35 +244,20 @@ impl TextureManager {
     ) -> Result<&ManagedTexture, TextureError> {
         debug!("[TextureManager] レイヤーテクスチャ作成: {} ({}x{})", layer_id, width, height);
 
-        // 寸法の検証（最大4K解像度をサポート）
-        if width == 0 || height == 0 || width > 3840 || height > 2160 {
-            return Err(TextureError::InvalidDimensions(width, height));
-        }
+        self.validate_layer_dimensions(width, height)?;
 
         let spec = TextureSpec::layer_texture(width, height);
         
-        // 既存のレイヤーテクスチャがある場合は解放
-        if let Some(old_texture_id) = self.layer_textures.get(layer_id).cloned() {
-            self.release_texture(&old_texture_id);
-        }
+        // 既存のレイヤーテクスチャがある場合は参照を外す（共有していなければプールに戻る）
+        self.unlink_layer_texture(layer_id);
 
         // プールから再利用可能なテクスチャを探す
-        let texture_id = if let Some(reused_id) = self.get_texture_from_pool(&spec) {
-            debug!("[TextureManager] プールからテクスチャを再利用: {}", reused_id);
-            reused_id
-        } else {
-            // 新しいテクスチャを作成
-            let texture_id = self.generate_texture_id();
-            self.create_new_texture(device, &texture_id, &spec)?;
-            texture_id
-        };
+        let texture_id = self.acquire_texture(device, &spec)?;
+
+        // レイヤーにテクスチャを関連付け（使用中にマークされる）
+        self.link_layer_texture(layer_id, &texture_id);
 
-        // レイヤーにテクスチャを関連付け
-        self.layer_textures.insert(layer_id.to_string(), texture_id.clone());
-        
-        // テクスチャを使用中にマーク
         if let Some(managed_texture) = self.textures.get_mut(&texture_id) {
-            managed_texture.mark_used();
             info!("[TextureManager] レイヤーテクスチャ作成完了: {}", layer_id);
             Ok(managed_texture)
         } else {
@@ -199,6 +265,46 @@ impl TextureManager {
         }
     }
 
+    /// レイヤーのテクスチャを指定サイズの新しいテクスチャに置き換える
+    ///
+    /// `copy` には旧テクスチャと新テクスチャが渡される。旧テクスチャはコピー後に参照を外す
+    /// （他のレイヤーと共有していなければプールへ戻す）。
+    pub fn replace_layer_texture<F>(
+        &mut self,
+        device: &Device,
+        layer_id: &str,
+        size: (u32, u32),
+        copy: F,
+    ) -> Result<(), TextureError>
+    where
+        F: FnOnce(&ManagedTexture, &ManagedTexture),
+    {
+        let (width, height) = size;
+        debug!("[TextureManager] レイヤーテクスチャ置き換え: {} ({}x{})", layer_id, width, height);
+        self.validate_layer_dimensions(width, height)?;
+
+        let old_texture_id = self.layer_textures.get(layer_id).cloned()
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+
+        // 旧テクスチャは使用中でプールに無いため、新旧が同じテクスチャになることはない
+        let spec = TextureSpec::layer_texture(width, height);
+        let new_texture_id = self.acquire_texture(device, &spec)?;
+
+        {
+            let old_texture = self.textures.get(&old_texture_id)
+                .ok_or_else(|| TextureError::TextureNotFound(old_texture_id.clone()))?;
+            let new_texture = self.textures.get(&new_texture_id)
+                .ok_or_else(|| TextureError::TextureNotFound(new_texture_id.clone()))?;
+            copy(old_texture, new_texture);
+        }
+
+        self.unlink_layer_texture(layer_id);
+        self.link_layer_texture(layer_id, &new_texture_id);
+
+        info!("[TextureManager] レイヤーテクスチャ置き換え完了: {}", layer_id);
+        Ok(())
+    }
+
     /// テクスチャからピクセルデータを取得
     pub async fn get_texture_data(
         &self,
@@ -206,79 +312,114 @@ impl TextureManager {
         queue: &Queue,
         layer_id: &str,
     ) -> Result<Vec<u8>, TextureError> {
-        debug!("[TextureManager] テクスチャデータ取得開始: {}", layer_id);
-
-        let texture_id = self.layer_textures.get(layer_id)
-            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+        let mut data = self.get_textures_data(device, queue, &[layer_id.to_string()]).await?;
+        Ok(data.remove(0))
+    }
 
-        let managed_texture = self.textures.get(texture_id)
-            .ok_or_else(|| TextureError::TextureNotFound(texture_id.clone()))?;
+    /// 複数のテクスチャのピクセルデータをまとめて取得（行は 256 バイト境界までパディングされる）
+    ///
+    /// コピーはレイヤーごとのエンコーダーに並列で記録して1回で送信し、
+    /// その送信の完了（フェンス）を1度だけ待ってから全てのバッファを読む。
+    pub async fn get_textures_data(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        layer_ids: &[String],
+    ) -> Result<Vec<Vec<u8>>, TextureError> {
+        let mut textures = Vec::with_capacity(layer_ids.len());
+        for layer_id in layer_ids {
+            let managed_texture = self.get_layer_texture(layer_id)
+                .ok_or_else(|| TextureError::TextureNotFound(layer_id.clone()))?;
+            textures.push((layer_id.as_str(), managed_texture));
+        }
+        self.read_textures_data(device, queue, &textures).await
+    }
 
-        // バッファサイズの計算（アライメント考慮）
-        let bytes_per_pixel = 4; // RGBA8
-        let unpadded_bytes_per_row = managed_texture.spec.width * bytes_per_pixel;
-        let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
-        let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
-        let buffer_size = (padded_bytes_per_row * managed_texture.spec.height) as u64;
-
-        // 読み取り用バッファを作成
-        let output_buffer = device.create_buffer(&BufferDescriptor {
-            label: Some("Texture Read Buffer"),
-            size: buffer_size,
-            usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
-            mapped_at_creation: false,
-        });
+    /// 管理外のものを含む複数のテクスチャのピクセルデータをまとめて取得（`get_textures_data` と同じ読み出し）
+    ///
+    /// 名前はログ用。描画中の消しゴムのプレビューなど、レイヤーの代わりに表示するテクスチャの読み出しに使う。
+    pub async fn read_textures_data(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        textures: &[(&str, &ManagedTexture)],
+    ) -> Result<Vec<Vec<u8>>, TextureError> {
+        debug!("[TextureManager] テクスチャデータ取得開始: {} 枚", textures.len());
+
+        // 読み取り用バッファを作成（アライメント考慮）
+        let readbacks: Vec<(&ManagedTexture, Buffer, u32)> = textures.iter()
+            .map(|&(_, managed_texture)| {
+                let bytes_per_pixel = 4; // RGBA8
+                let unpadded_bytes_per_row = managed_texture.spec.width * bytes_per_pixel;
+                let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+                let padded_bytes_per_row = (unpadded_bytes_per_row + align - 1) / align * align;
+                let buffer = device.create_buffer(&BufferDescriptor {
+                    label: Some("Texture Read Buffer"),
+                    size: (padded_bytes_per_row * managed_texture.spec.height) as u64,
+                    usage: BufferUsages::COPY_DST | BufferUsages::MAP_READ,
+                    mapped_at_creation: false,
+                });
+                (managed_texture, buffer, padded_bytes_per_row)
+            })
+            .collect();
+        let total_size = readbacks.iter().map(|(_, buffer, _)| buffer.size()).sum();
+        let _staging = StagingAllocation::new(&self.staging_memory_usage, total_size);
 
         // テクスチャからバッファにコピー
-        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
-            label: Some("Texture Copy Encoder"),
-        });
-
-        encoder.copy_texture_to_buffer(
-            TexelCopyTextureInfo {
-                texture: &managed_texture.texture,
-                mip_level: 0,
-                origin: Origin3d::ZERO,
-                aspect: TextureAspect::All,
-            },
-            TexelCopyBufferInfo {
-                buffer: &output_buffer,
-                layout: TexelCopyBufferLayout {
-                    offset: 0,
-                    bytes_per_row: Some(padded_bytes_per_row),
-                    rows_per_image: Some(managed_texture.spec.height),
+        let fence = submit_parallel(device, queue, "Texture Copy Encoder", &readbacks, |encoder, (managed_texture, buffer, padded_bytes_per_row)| {
+            encoder.copy_texture_to_buffer(
+                TexelCopyTextureInfo {
+                    texture: &managed_texture.texture,
+                    mip_level: 0,
+                    origin: Origin3d::ZERO,
+                    aspect: TextureAspect::All,
                 },
-            },
-            Extent3d {
-                width: managed_texture.spec.width,
-                height: managed_texture.spec.height,
-                depth_or_array_layers: 1,
-            },
-        );
-
-        queue.submit(std::iter::once(encoder.finish()));
-
-        // バッファを読み取り
-        let buffer_slice = output_buffer.slice(..);
-        let (sender, receiver) = futures::channel::oneshot::channel();
-        buffer_slice.map_async(MapMode::Read, move |result| {
-            sender.send(result).unwrap();
+                TexelCopyBufferInfo {
+                    buffer,
+                    layout: TexelCopyBufferLayout {
+                        offset: 0,
+                        bytes_per_row: Some(*padded_bytes_per_row),
+                        rows_per_image: Some(managed_texture.spec.height),
+                    },
+                },
+                Extent3d {
+                    width: managed_texture.spec.width,
+                    height: managed_texture.spec.height,
+                    depth_or_array_layers: 1,
+                },
+            );
         });
 
-        let _ = device.poll(wgpu::MaintainBase::Wait);
-
-        receiver.await
-            .map_err(|_| TextureError::BufferReadFailed("バッファマップ待機に失敗".to_string()))?
-            .map_err(|e| TextureError::BufferReadFailed(format!("バッファマップに失敗: {:?}", e)))?;
-
-        let data = buffer_slice.get_mapped_range();
-        let result = data.to_vec();
-        
-        drop(data);
-        output_buffer.unmap();
+        // バッファを読み取り
+        let receivers: Vec<_> = readbacks.iter()
+            .map(|(_, buffer, _)| {
+                let (sender, receiver) = futures::channel::oneshot::channel();
+                buffer.slice(..).map_async(MapMode::Read, move |result| {
+                    let _ = sender.send(result);
+                });
+                receiver
+            })
+            .collect();
+
+        fence.wait(device)
+            .map_err(|e| TextureError::BufferReadFailed(format!("GPU の完了待ちに失敗: {}", e)))?;
+
+        let mut results = Vec::with_capacity(readbacks.len());
+        for (((layer_id, _), (managed_texture, buffer, _)), receiver) in textures.iter().zip(&readbacks).zip(receivers) {
+            receiver.await
+                .map_err(|_| TextureError::BufferReadFailed("バッファマップ待機に失敗".to_string()))?
+                .map_err(|e| TextureError::BufferReadFailed(format!("バッファマップに失敗: {:?}", e)))?;
+
+            let data = buffer.slice(..).get_mapped_range();
+            results.push(data.to_vec());
+            drop(data);
+            buffer.unmap();
+            debug!("[TextureManager] テクスチャデータ読み出し: {} ({}x{})",
+                   layer_id, managed_texture.spec.width, managed_texture.spec.height);
+        }
 
-        info!("[TextureManager] テクスチャデータ取得完了: {} ({} bytes)", layer_id, result.len());
-        Ok(result)
+        info!("[TextureManager] テクスチャデータ取得完了: {} 枚 ({} bytes)", results.len(), total_size);
+        Ok(results)
     }
 
     /// テクスチャサイズを変更
@@ -295,7 +436,7 @@ impl TextureManager {
         self.create_layer_texture(device, layer_id, width, height)
     }
 
-    /// テクスチャをクリア（透明色で塗りつぶし）
+    /// テクスチャをクリア（透明色で塗りつぶし。共有中のテクスチャは複製してからクリアする）
     pub fn clear_texture(
         &mut self,
         device: &Device,
@@ -304,6 +445,7 @@ impl TextureManager {
         clear_color: Option<Color>,
     ) -> Result<(), TextureError> {
         debug!("[TextureManager] テクスチャクリア: {}", layer_id);
+        self.prepare_layer_write(device, queue, layer_id)?;
 
         let texture_id = self.layer_textures.get(layer_id)
             .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
@@ -348,16 +490,188 @@ impl TextureManager {
         Ok(())
     }
 
+    /// テクスチャの矩形領域にRGBAピクセルを書き込む（範囲外ははみ出し分を切り捨て）
+    ///
+    /// 共有中のテクスチャにはそのまま書き込むため、先に `prepare_layer_write` を呼んでおく。
+    pub fn write_texture_region(
+        &mut self,
+        queue: &Queue,
+        layer_id: &str,
+        origin: (u32, u32),
+        size: (u32, u32),
+        rgba: &[u8],
+    ) -> Result<(), TextureError> {
+        let ((x, y), (width, height)) = (origin, size);
+        debug!("[TextureManager] テクスチャ書き込み: {} ({},{} {}x{})", layer_id, x, y, width, height);
+
+        if rgba.len() != (width * height * 4) as usize {
+            return Err(TextureError::InvalidDimensions(width, height));
+        }
+
+        let texture_id = self.layer_textures.get(layer_id).cloned()
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+        self.forget_content(&texture_id);
+
+        let managed_texture = self.textures.get_mut(&texture_id)
+            .ok_or_else(|| TextureError::TextureNotFound(texture_id.clone()))?;
+
+        let copy_width = width.min(managed_texture.spec.width.saturating_sub(x));
+        let copy_height = height.min(managed_texture.spec.height.saturating_sub(y));
+        if copy_width == 0 || copy_height == 0 {
+            debug!("[TextureManager] 書き込み領域がテクスチャ外のためスキップ: {}", layer_id);
+            return Ok(());
+        }
+
+        queue.write_texture(
+            TexelCopyTextureInfo {
+                texture: &managed_texture.texture,
+                mip_level: 0,
+                origin: Origin3d { x, y, z: 0 },
+                aspect: TextureAspect::All,
+            },
+            rgba,
+            TexelCopyBufferLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            Extent3d {
+                width: copy_width,
+                height: copy_height,
+                depth_or_array_layers: 1,
+            },
+        );
+        managed_texture.mark_used();
+
+        info!("[TextureManager] テクスチャ書き込み完了: {}", layer_id);
+        Ok(())
+    }
+
+    /// レイヤー全体にRGBAピクセルを書き込み、同じ内容のテクスチャがあればそれを共有する（共有したら true）
+    ///
+    /// 全体を書き込んだ内容はハッシュを覚えておき、以降に同じ内容を書き込むレイヤーはアップロードせずに
+    /// そのテクスチャを参照する。大きさがレイヤーと異なる場合は `write_texture_region` と同じく左上から書き込む。
+    pub fn write_layer_content(
+        &mut self,
+        device: &Device,
+        queue: &Queue,
+        layer_id: &str,
+        size: (u32, u32),
+        rgba: &[u8],
+    ) -> Result<bool, TextureError> {
+        let texture_id = self.layer_textures.get(layer_id).cloned()
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+        let spec = self.textures.get(&texture_id)
+            .ok_or_else(|| TextureError::TextureNotFound(texture_id.clone()))?
+            .spec.clone();
+        if size != (spec.width, spec.height) || rgba.len() as u64 != spec.memory_size() {
+            self.prepare_layer_write(device, queue, layer_id)?;
+            return self.write_texture_region(queue, layer_id, (0, 0), size, rgba).map(|_| false);
+        }
+
+        let key = content_key(size, rgba);
+        if let Some(existing_id) = self.content_index.get(&key).cloned() {
+            if existing_id != texture_id {
+                self.unlink_layer_texture(layer_id);
+                self.link_layer_texture(layer_id, &existing_id);
+                debug!("[TextureManager] 同じ内容のテクスチャを共有: {} -> {}", layer_id, existing_id);
+            }
+            return Ok(true);
+        }
+
+        // 全体を書き換えるので、共有中なら複製せずに新しいテクスチャに差し替える
+        if self.is_layer_texture_shared(layer_id) {
+            let new_texture_id = self.acquire_texture(device, &spec)?;
+            self.unlink_layer_texture(layer_id);
+            self.link_layer_texture(layer_id, &new_texture_id);
+        }
+        self.write_texture_region(queue, layer_id, (0, 0), size, rgba)?;
+        if let Some(texture_id) = self.layer_textures.get(layer_id).cloned() {
+            self.content_index.insert(key, texture_id.clone());
+            self.content_keys.insert(texture_id, key);
+        }
+        Ok(false)
+    }
+
+    /// レイヤーのテクスチャを別のレイヤーと共有する
+    ///
+    /// `target_layer` の元のテクスチャは参照を外す。どちらかに書き込むときに複製される（コピーオンライト）。
+    pub fn share_layer_texture(&mut self, source_layer: &str, target_layer: &str) -> Result<(), TextureError> {
+        let texture_id = self.layer_textures.get(source_layer).cloned()
+            .ok_or_else(|| TextureError::TextureNotFound(source_layer.to_string()))?;
+        if self.layer_textures.get(target_layer) == Some(&texture_id) {
+            return Ok(());
+        }
+        self.unlink_layer_texture(target_layer);
+        self.link_layer_texture(target_layer, &texture_id);
+        debug!("[TextureManager] テクスチャを共有: {} -> {} ({})", source_layer, target_layer, texture_id);
+        Ok(())
+    }
+
+    /// レイヤーのテクスチャを他のレイヤーと共有しているか
+    pub fn is_layer_texture_shared(&self, layer_id: &str) -> bool {
+        self.layer_textures.get(layer_id)
+            .and_then(|texture_id| self.texture_refs.get(texture_id))
+            .is_some_and(|&count| count > 1)
+    }
+
+    /// レイヤーに書き込む前の準備
+    ///
+    /// 他のレイヤーと共有しているテクスチャは複製して、このレイヤーだけのものにする（コピーオンライト）。
+    /// 書き込みで内容が変わるため、覚えていた内容のハッシュは破棄する。
+    pub fn prepare_layer_write(&mut self, device: &Device, queue: &Queue, layer_id: &str) -> Result<(), TextureError> {
+        let texture_id = self.layer_textures.get(layer_id).cloned()
+            .ok_or_else(|| TextureError::TextureNotFound(layer_id.to_string()))?;
+        if !self.is_layer_texture_shared(layer_id) {
+            self.forget_content(&texture_id);
+            return Ok(());
+        }
+
+        let spec = self.textures.get(&texture_id)
+            .ok_or_else(|| TextureError::TextureNotFound(texture_id.clone()))?
+            .spec.clone();
+        let copy_id = self.acquire_texture(device, &spec)?;
+        {
+            let source = self.textures.get(&texture_id)
+                .ok_or_else(|| TextureError::TextureNotFound(texture_id.clone()))?;
+            let target = self.textures.get(&copy_id)
+                .ok_or_else(|| TextureError::TextureNotFound(copy_id.clone()))?;
+            let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+                label: Some("Texture Copy-On-Write Encoder"),
+            });
+            encoder.copy_texture_to_texture(
+                source.texture.as_image_copy(),
+                target.texture.as_image_copy(),
+                source.texture.size(),
+            );
+            queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.unlink_layer_texture(layer_id);
+        self.link_layer_texture(layer_id, &copy_id);
+
+        debug!("[TextureManager] 共有テクスチャを複製: {} ({} -> {})", layer_id, texture_id, copy_id);
+        Ok(())
+    }
+
+    /// 共有で節約しているバイト数（同じテクスチャを2つ目以降のレイヤーで使っている分）
+    pub fn shared_memory_savings(&self) -> u64 {
+        self.texture_refs.iter()
+            .filter_map(|(texture_id, &count)| {
+                let texture = self.textures.get(texture_id)?;
+                Some(texture.spec.memory_size() * count.saturating_sub(1) as u64)
+            })
+            .sum()
+    }
+
     /// レイヤーテクスチャを取得
     pub fn get_layer_texture(&self, layer_id: &str) -> Option<&ManagedTexture> {
         let texture_id = self.layer_textures.get(layer_id)?;
         self.textures.get(texture_id)
     }
 
-    /// レイヤーテクスチャを削除
+    /// レイヤーテクスチャを削除（共有中のテクスチャは他のレイヤーのために残る）
     pub fn remove_layer_texture(&mut self, layer_id: &str) -> bool {
-        if let Some(texture_id) = self.layer_textures.remove(layer_id) {
-            self.release_texture(&texture_id);
+        if self.unlink_layer_texture(layer_id) {
             info!("[TextureManager] レイヤーテクスチャ削除: {}", layer_id);
             true
         } else {
@@ -392,6 +706,78 @@ impl TextureManager {
         self.current_memory_usage
     }
 
+    /// 読み出し中のステージングバッファの合計サイズを取得
+    pub fn get_staging_memory_usage(&self) -> u64 {
+        self.staging_memory_usage.load(Ordering::Relaxed)
+    }
+
+    /// プールに戻っている（どのレイヤーにも使われていない）テクスチャをすべて解放し、解放したバイト数を返す
+    pub fn release_pooled_textures(&mut self) -> u64 {
+        let initial_usage = self.current_memory_usage;
+        let unused: Vec<String> = self.textures.iter()
+            .filter(|(_, texture)| !texture.is_in_use)
+            .map(|(texture_id, _)| texture_id.clone())
+            .collect();
+        for texture_id in unused {
+            self.remove_texture_completely(&texture_id);
+        }
+
+        let freed_memory = initial_usage - self.current_memory_usage;
+        info!("[TextureManager] プールのテクスチャを解放: {} bytes", freed_memory);
+        freed_memory
+    }
+
+    /// テクスチャプールを整理し、使われそうにないテクスチャを解放する
+    ///
+    /// 使用中のレイヤーと同じ仕様のテクスチャは最近使ったものから `keep_per_spec` 枚だけ残し、
+    /// どのレイヤーとも仕様が合わないテクスチャ（以前のキャンバスサイズのものなど）はすべて解放する。
+    /// 残したテクスチャは最近使ったものから再利用されるよう並べ直し、空になった仕様の枠も取り除く。
+    pub fn defragment_pool(&mut self, keep_per_spec: usize) -> PoolDefragment {
+        let initial_usage = self.current_memory_usage;
+        let pool_entries_before = self.texture_pool.values().map(VecDeque::len).sum();
+        let pool_specs_before = self.texture_pool.len();
+
+        let live_specs: HashSet<TextureSpec> = self.textures.values()
+            .filter(|texture| texture.is_in_use)
+            .map(|texture| texture.spec.clone())
+            .collect();
+        let mut pooled: HashMap<TextureSpec, Vec<(String, std::time::Instant)>> = HashMap::new();
+        for (texture_id, texture) in &self.textures {
+            if !texture.is_in_use {
+                pooled.entry(texture.spec.clone()).or_default().push((texture_id.clone(), texture.last_used));
+            }
+        }
+
+        let mut texture_pool = HashMap::new();
+        let mut released_textures = 0;
+        for (spec, mut entries) in pooled {
+            entries.sort_by_key(|(_, last_used)| std::cmp::Reverse(*last_used));
+            let keep = if live_specs.contains(&spec) { keep_per_spec } else { 0 };
+            for (texture_id, _) in entries.split_off(keep.min(entries.len())) {
+                self.remove_texture_completely(&texture_id);
+                released_textures += 1;
+            }
+            if !entries.is_empty() {
+                texture_pool.insert(spec, entries.into_iter().map(|(texture_id, _)| texture_id).collect::<VecDeque<_>>());
+            }
+        }
+        // プールに残っていた使用中・削除済みのIDもここで消える
+        self.texture_pool = texture_pool;
+
+        let result = PoolDefragment {
+            pool_entries_before,
+            pool_entries_after: self.texture_pool.values().map(VecDeque::len).sum(),
+            pool_specs_before,
+            pool_specs_after: self.texture_pool.len(),
+            released_textures,
+            freed_bytes: initial_usage - self.current_memory_usage,
+        };
+        info!("[TextureManager] プールを整理: {} → {} 枚 ({} → {} 仕様, {} bytes 解放)",
+              result.pool_entries_before, result.pool_entries_after,
+              result.pool_specs_before, result.pool_specs_after, result.freed_bytes);
+        result
+    }
+
     /// メモリ使用量統計を取得
     pub fn get_memory_stats(&self) -> (u64, u64, usize, usize) {
         let active_textures = self.layer_textures.len();
@@ -401,6 +787,14 @@ impl TextureManager {
 
     // プライベートメソッド
 
+    /// レイヤーテクスチャの寸法を検証（各辺が `max_dimension` 以下）
+    fn validate_layer_dimensions(&self, width: u32, height: u32) -> Result<(), TextureError> {
+        if width == 0 || height == 0 || width > self.max_dimension || height > self.max_dimension {
+            return Err(TextureError::InvalidDimensions(width, height));
+        }
+        Ok(())
+    }
+
     fn generate_texture_id(&mut self) -> String {
         let id = format!("tex_{}", self.next_texture_id);
         self.next_texture_id += 1;
@@ -420,6 +814,7 @@ impl TextureManager {
             self.force_cleanup_memory(texture_memory)?;
         }
 
+        let encoded_format = [spec.format.remove_srgb_suffix()];
         let texture = device.create_texture(&TextureDescriptor {
             label: Some(&format!("Managed Texture {}", texture_id)),
             size: Extent3d {
@@ -432,7 +827,8 @@ impl TextureManager {
             dimension: TextureDimension::D2,
             format: spec.format,
             usage: spec.usage,
-            view_formats: &[],
+            // sRGB で合成する描画では変換しないビューを使う
+            view_formats: if self.encoded_views { &encoded_format } else { &[] },
         });
 
         let managed_texture = ManagedTexture::new(texture, spec.clone());
@@ -448,6 +844,50 @@ impl TextureManager {
         pool.pop_front()
     }
 
+    /// プールから再利用できるテクスチャを取り出し、なければ新しく作成する
+    fn acquire_texture(&mut self, device: &Device, spec: &TextureSpec) -> Result<String, TextureError> {
+        if let Some(reused_id) = self.get_texture_from_pool(spec) {
+            debug!("[TextureManager] プールからテクスチャを再利用: {}", reused_id);
+            return Ok(reused_id);
+        }
+        let texture_id = self.generate_texture_id();
+        self.create_new_texture(device, &texture_id, spec)?;
+        Ok(texture_id)
+    }
+
+    /// レイヤーにテクスチャを関連付けて参照数を増やす（レイヤーの前のテクスチャは外しておく）
+    fn link_layer_texture(&mut self, layer_id: &str, texture_id: &str) {
+        self.layer_textures.insert(layer_id.to_string(), texture_id.to_string());
+        *self.texture_refs.entry(texture_id.to_string()).or_default() += 1;
+        if let Some(managed_texture) = self.textures.get_mut(texture_id) {
+            managed_texture.mark_used();
+        }
+    }
+
+    /// レイヤーからテクスチャを外し、どのレイヤーからも参照されなくなればプールに戻す
+    fn unlink_layer_texture(&mut self, layer_id: &str) -> bool {
+        let Some(texture_id) = self.layer_textures.remove(layer_id) else { return false };
+        let remaining = self.texture_refs.get_mut(&texture_id)
+            .map(|count| {
+                *count = count.saturating_sub(1);
+                *count
+            })
+            .unwrap_or(0);
+        if remaining == 0 {
+            self.texture_refs.remove(&texture_id);
+            self.forget_content(&texture_id);
+            self.release_texture(&texture_id);
+        }
+        true
+    }
+
+    /// テクスチャの内容のハッシュを破棄（内容が変わるとき）
+    fn forget_content(&mut self, texture_id: &str) {
+        if let Some(key) = self.content_keys.remove(texture_id) {
+            self.content_index.remove(&key);
+        }
+    }
+
     fn release_texture(&mut self, texture_id: &str) {
         if let Some(mut managed_texture) = self.textures.remove(texture_id) {
             managed_texture.mark_unused();
@@ -511,6 +951,13 @@ impl TextureManager {
     }
 }
 
+/// テクスチャ全体の内容のキー
+fn content_key((width, height): (u32, u32), rgba: &[u8]) -> ContentKey {
+    let mut hasher = std::collections::hash_map::DefaultHasher::new();
+    rgba.hash(&mut hasher);
+    (width, height, hasher.finish())
+}
+
 impl Drop for TextureManager {
     fn drop(&mut self) {
         info!("[TextureManager] テクスチャマネージャーを解放: {} テクスチャ, {} bytes", 
@@ -603,6 +1050,109 @@ mod tests {
         assert_eq!(total_textures, 1);
     }
 
+    #[tokio::test]
+    async fn test_release_pooled_textures() {
+        let (device, _queue) = create_test_device();
+        let mut manager = TextureManager::new();
+
+        manager.create_layer_texture(&device, "layer1", 64, 64).unwrap();
+        manager.create_layer_texture(&device, "layer2", 32, 32).unwrap();
+        assert!(manager.remove_layer_texture("layer1"));
+
+        // 使用中のレイヤーのテクスチャは残る
+        assert_eq!(manager.release_pooled_textures(), 64 * 64 * 4);
+        assert_eq!(manager.get_memory_usage(), 32 * 32 * 4);
+        assert!(manager.get_layer_texture("layer2").is_some());
+        assert_eq!(manager.get_staging_memory_usage(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_defragment_pool() {
+        let (device, _queue) = create_test_device();
+        let mut manager = TextureManager::new();
+
+        // 64x64 を使うレイヤーが残り、同じ仕様の未使用テクスチャが2枚・以前のサイズが1枚プールにある
+        for layer_id in ["a", "b", "c"] {
+            manager.create_layer_texture(&device, layer_id, 64, 64).unwrap();
+        }
+        manager.create_layer_texture(&device, "old", 32, 32).unwrap();
+        for layer_id in ["b", "c", "old"] {
+            assert!(manager.remove_layer_texture(layer_id));
+        }
+
+        let result = manager.defragment_pool(1);
+        assert_eq!((result.pool_entries_before, result.pool_entries_after), (3, 1));
+        assert_eq!((result.pool_specs_before, result.pool_specs_after), (2, 1));
+        assert_eq!(result.released_textures, 2);
+        assert_eq!(result.freed_bytes, 64 * 64 * 4 + 32 * 32 * 4);
+        assert_eq!(manager.get_memory_usage(), 2 * 64 * 64 * 4);
+
+        // 残したテクスチャは再利用される
+        manager.create_layer_texture(&device, "d", 64, 64).unwrap();
+        assert_eq!(manager.get_memory_stats().3, 2);
+        // 取り出した後に空で残っていた仕様の枠も取り除く
+        let result = manager.defragment_pool(1);
+        assert_eq!((result.pool_specs_before, result.pool_specs_after, result.released_textures), (1, 0, 0));
+    }
+
+    #[tokio::test]
+    async fn test_batched_readback() {
+        let (device, queue) = create_test_device();
+        let mut manager = TextureManager::new();
+
+        manager.create_layer_texture(&device, "red", 70, 3).unwrap();
+        manager.create_layer_texture(&device, "blue", 16, 16).unwrap();
+        manager.clear_texture(&device, &queue, "red", Some(Color::RED)).unwrap();
+        manager.clear_texture(&device, &queue, "blue", Some(Color::BLUE)).unwrap();
+
+        // 直前のクリアの結果が読める（送信順に実行される）
+        let ids = ["red".to_string(), "blue".to_string()];
+        let data = manager.get_textures_data(&device, &queue, &ids).await.unwrap();
+        assert_eq!(data.len(), 2);
+        // 行は 256 バイト境界までパディングされる
+        assert_eq!(data[0].len(), 3 * 512);
+        assert_eq!(&data[0][..4], &[255, 0, 0, 255]);
+        assert_eq!(&data[1][..4], &[0, 0, 255, 255]);
+        assert_eq!(data[1], manager.get_texture_data(&device, &queue, "blue").await.unwrap());
+        assert_eq!(manager.get_staging_memory_usage(), 0);
+
+        assert!(matches!(
+            manager.get_textures_data(&device, &queue, &["missing".to_string()]).await,
+            Err(TextureError::TextureNotFound(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_shared_content_copy_on_write() {
+        let (device, queue) = create_test_device();
+        let mut manager = TextureManager::new();
+        let red: Vec<u8> = [255, 0, 0, 255].repeat(8 * 8);
+        for id in ["a", "b", "c"] {
+            manager.create_layer_texture(&device, id, 8, 8).unwrap();
+        }
+
+        // 同じ内容を書き込んだレイヤーは1枚のテクスチャを共有する
+        assert!(!manager.write_layer_content(&device, &queue, "a", (8, 8), &red).unwrap());
+        assert!(manager.write_layer_content(&device, &queue, "b", (8, 8), &red).unwrap());
+        assert!(manager.is_layer_texture_shared("a"));
+        assert_eq!(manager.shared_memory_savings(), 8 * 8 * 4);
+
+        // 書き込むと複製され、もう一方の内容は変わらない
+        manager.clear_texture(&device, &queue, "b", Some(Color::BLUE)).unwrap();
+        assert!(!manager.is_layer_texture_shared("a"));
+        let ids = ["a".to_string(), "b".to_string()];
+        let data = manager.get_textures_data(&device, &queue, &ids).await.unwrap();
+        assert_eq!(&data[0][..4], &[255, 0, 0, 255]);
+        assert_eq!(&data[1][..4], &[0, 0, 255, 255]);
+
+        // 共有元を削除しても共有先のテクスチャは残る
+        manager.share_layer_texture("a", "c").unwrap();
+        assert!(manager.remove_layer_texture("a"));
+        assert!(!manager.is_layer_texture_shared("c"));
+        assert_eq!(&manager.get_texture_data(&device, &queue, "c").await.unwrap()[..4], &[255, 0, 0, 255]);
+        assert_eq!(manager.get_memory_stats().2, 2);
+    }
+
     #[tokio::test]
     async fn test_invalid_dimensions() {
         let (device, _queue) = create_test_device();
@@ -615,9 +1165,14 @@ mod tests {
         let result = manager.create_layer_texture(&device, "invalid", 256, 0);
         assert!(result.is_err());
 
-        // 4Kを超える寸法
+        // 既定の上限（4096）を超える寸法
         let result = manager.create_layer_texture(&device, "invalid", 5000, 256);
         assert!(result.is_err());
+
+        // 上限を下げるとそれを超える寸法は作成できない
+        manager.set_max_dimension(1024);
+        assert!(manager.create_layer_texture(&device, "invalid", 1025, 256).is_err());
+        assert!(manager.create_layer_texture(&device, "valid", 1024, 256).is_ok());
     }
 
     #[test]
@@ -0,0 +1,324 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+/// タイルの一辺のピクセル数
+pub const TILE_SIZE: u32 = 256;
+
+/// タイルの位置（キャンバス座標をタイルの大きさで割ったもの。負の値も取る）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct TileCoord {
+    pub x: i32,
+    pub y: i32,
+}
+
+impl TileCoord {
+    /// キャンバス座標を含むタイル
+    pub fn containing(x: i64, y: i64) -> Self {
+        let size = TILE_SIZE as i64;
+        Self {
+            x: x.div_euclid(size) as i32,
+            y: y.div_euclid(size) as i32,
+        }
+    }
+
+    /// タイル左上のキャンバス座標
+    pub fn origin(&self) -> (i64, i64) {
+        (self.x as i64 * TILE_SIZE as i64, self.y as i64 * TILE_SIZE as i64)
+    }
+}
+
+/// 表示範囲（左上のキャンバス座標と大きさ）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Viewport {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Viewport {
+    /// 表示範囲と重なるタイル（行優先）
+    pub fn visible_tiles(&self) -> Vec<TileCoord> {
+        if self.width == 0 || self.height == 0 {
+            return Vec::new();
+        }
+        let first = TileCoord::containing(self.x as i64, self.y as i64);
+        let last = TileCoord::containing(
+            self.x as i64 + self.width as i64 - 1,
+            self.y as i64 + self.height as i64 - 1,
+        );
+        (first.y..=last.y)
+            .flat_map(|y| (first.x..=last.x).map(move |x| TileCoord { x, y }))
+            .collect()
+    }
+}
+
+/// 内容のあるタイルだけを保持する疎なタイル集合（1レイヤー分）
+#[derive(Debug, Clone, Default)]
+pub struct TileStore {
+    tiles: HashMap<TileCoord, RgbaImage>,
+}
+
+impl TileStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.tiles.len()
+    }
+
+    pub fn get(&self, coord: TileCoord) -> Option<&RgbaImage> {
+        self.tiles.get(&coord)
+    }
+
+    pub fn memory_bytes(&self) -> u64 {
+        self.tiles.len() as u64 * (TILE_SIZE * TILE_SIZE * 4) as u64
+    }
+
+    /// 画像の内容で範囲内のピクセルを置き換える（透明になったタイルは破棄する）
+    pub fn store_region(&mut self, origin: (i32, i32), image: &RgbaImage) {
+        let viewport = Viewport { x: origin.0, y: origin.1, width: image.width(), height: image.height() };
+        for coord in viewport.visible_tiles() {
+            let existing = self.tiles.remove(&coord);
+            let has_content = existing.is_some()
+                || overlapping_pixels(coord, origin, image.dimensions())
+                    .any(|(ix, iy, _, _)| image.get_pixel(ix, iy)[3] != 0);
+            if !has_content {
+                continue;
+            }
+
+            let mut tile = existing.unwrap_or_else(|| RgbaImage::new(TILE_SIZE, TILE_SIZE));
+            for (ix, iy, tx, ty) in overlapping_pixels(coord, origin, image.dimensions()) {
+                tile.put_pixel(tx, ty, *image.get_pixel(ix, iy));
+            }
+            if tile.pixels().any(|p| p[3] != 0) {
+                self.tiles.insert(coord, tile);
+            }
+        }
+    }
+
+    /// 範囲の画像を合成（タイルのない部分は透明）
+    pub fn read_region(&self, viewport: Viewport) -> RgbaImage {
+        let mut image = RgbaImage::new(viewport.width, viewport.height);
+        let origin = (viewport.x, viewport.y);
+        for coord in viewport.visible_tiles() {
+            if let Some(tile) = self.tiles.get(&coord) {
+                for (ix, iy, tx, ty) in overlapping_pixels(coord, origin, image.dimensions()) {
+                    image.put_pixel(ix, iy, *tile.get_pixel(tx, ty));
+                }
+            }
+        }
+        image
+    }
+
+    /// 透明になったタイルを破棄し、タイルの表を使っている分だけに縮める（破棄したタイル数を返す）
+    pub fn compact(&mut self) -> usize {
+        let before = self.tiles.len();
+        self.tiles.retain(|_, tile| tile.pixels().any(|p| p[3] != 0));
+        self.tiles.shrink_to_fit();
+        before - self.tiles.len()
+    }
+
+    /// 内容のあるタイル全体を覆う範囲（タイル単位）
+    pub fn tile_bounds(&self) -> Option<Viewport> {
+        let min_x = self.tiles.keys().map(|c| c.x).min()?;
+        let min_y = self.tiles.keys().map(|c| c.y).min()?;
+        let max_x = self.tiles.keys().map(|c| c.x).max()?;
+        let max_y = self.tiles.keys().map(|c| c.y).max()?;
+        let (x, y) = TileCoord { x: min_x, y: min_y }.origin();
+        Some(Viewport {
+            x: x as i32,
+            y: y as i32,
+            width: (max_x - min_x + 1) as u32 * TILE_SIZE,
+            height: (max_y - min_y + 1) as u32 * TILE_SIZE,
+        })
+    }
+}
+
+/// 画像（左上が origin）とタイルが重なるピクセルを `(画像x, 画像y, タイルx, タイルy)` で列挙
+fn overlapping_pixels(
+    coord: TileCoord,
+    origin: (i32, i32),
+    (width, height): (u32, u32),
+) -> impl Iterator<Item = (u32, u32, u32, u32)> {
+    let (tile_x, tile_y) = coord.origin();
+    let left = tile_x.max(origin.0 as i64);
+    let top = tile_y.max(origin.1 as i64);
+    let right = (tile_x + TILE_SIZE as i64).min(origin.0 as i64 + width as i64);
+    let bottom = (tile_y + TILE_SIZE as i64).min(origin.1 as i64 + height as i64);
+
+    (top..bottom.max(top)).flat_map(move |y| {
+        (left..right.max(left)).map(move |x| (
+            (x - origin.0 as i64) as u32,
+            (y - origin.1 as i64) as u32,
+            (x - tile_x) as u32,
+            (y - tile_y) as u32,
+        ))
+    })
+}
+
+/// 無限キャンバスの状態（レイヤーテクスチャは表示範囲の窓として使い、内容はタイルに保持する）
+#[derive(Debug, Clone, Default)]
+pub struct InfiniteCanvas {
+    /// 表示範囲の左上（レイヤーテクスチャの原点に対応するキャンバス座標）
+    pub origin: (i32, i32),
+    /// レイヤーID → タイル
+    pub layers: HashMap<String, TileStore>,
+}
+
+impl InfiniteCanvas {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn tile_count(&self) -> usize {
+        self.layers.values().map(TileStore::tile_count).sum()
+    }
+
+    pub fn memory_bytes(&self) -> u64 {
+        self.layers.values().map(TileStore::memory_bytes).sum()
+    }
+
+    /// 全レイヤーのタイルを詰め、タイルのなくなったレイヤーの表を取り除く（破棄したタイル数を返す）
+    pub fn compact(&mut self) -> usize {
+        let removed = self.layers.values_mut().map(TileStore::compact).sum();
+        self.layers.retain(|_, store| store.tile_count() > 0);
+        self.layers.shrink_to_fit();
+        removed
+    }
+}
+
+/// レイヤーのタイルごとの更新世代
+///
+/// 書き込むたびに世代を1つ進め、書き込んだ範囲に重なるタイルにその世代を記録する。
+/// 表示側は前回読んだときの世代を渡し、それ以降に変わったタイルだけを読み直す。
+/// 座標はレイヤーテクスチャのピクセル座標（無限キャンバスでは表示範囲の左上が原点）。
+#[derive(Debug, Clone, Default)]
+pub struct TileGenerations {
+    generation: u64,
+    layers: HashMap<String, HashMap<TileCoord, u64>>,
+}
+
+impl TileGenerations {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 現在の世代（まだ何も書き込んでいなければ 0）
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// レイヤーの範囲に書き込んだことを記録
+    pub fn mark(&mut self, layer_id: &str, region: Viewport) {
+        let tiles = region.visible_tiles();
+        if tiles.is_empty() {
+            return;
+        }
+        self.generation += 1;
+        let layer = self.layers.entry(layer_id.to_string()).or_default();
+        for coord in tiles {
+            layer.insert(coord, self.generation);
+        }
+    }
+
+    pub fn remove_layer(&mut self, layer_id: &str) {
+        self.layers.remove(layer_id);
+    }
+
+    /// 世代 `since` より後に書き込んだタイル（指定したレイヤーを合わせたもの。行優先）
+    pub fn changed_since<'a>(&self, layer_ids: impl IntoIterator<Item = &'a str>, since: u64) -> Vec<TileCoord> {
+        let mut tiles: Vec<TileCoord> = layer_ids.into_iter()
+            .filter_map(|layer_id| self.layers.get(layer_id))
+            .flat_map(|layer| layer.iter().filter(|(_, &generation)| generation > since).map(|(coord, _)| *coord))
+            .collect();
+        tiles.sort_by_key(|coord| (coord.y, coord.x));
+        tiles.dedup();
+        tiles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_visible_tiles_with_negative_origin() {
+        let viewport = Viewport { x: -10, y: 250, width: 300, height: 10 };
+        assert_eq!(viewport.visible_tiles(), vec![
+            TileCoord { x: -1, y: 0 },
+            TileCoord { x: 0, y: 0 },
+            TileCoord { x: 1, y: 0 },
+            TileCoord { x: -1, y: 1 },
+            TileCoord { x: 0, y: 1 },
+            TileCoord { x: 1, y: 1 },
+        ]);
+        assert!(Viewport { x: 0, y: 0, width: 0, height: 5 }.visible_tiles().is_empty());
+    }
+
+    #[test]
+    fn test_store_and_read_region() {
+        let red = Rgba([255, 0, 0, 255]);
+        let mut image = RgbaImage::new(20, 20);
+        image.put_pixel(0, 0, red);
+        image.put_pixel(19, 19, red);
+
+        let mut store = TileStore::new();
+        store.store_region((-5, 250), &image);
+        // 透明な部分しかないタイルは作らない
+        assert_eq!(store.tile_count(), 2);
+        assert!(store.get(TileCoord { x: -1, y: 0 }).is_some());
+        assert!(store.get(TileCoord { x: 0, y: 1 }).is_some());
+
+        assert_eq!(store.read_region(Viewport { x: -5, y: 250, width: 20, height: 20 }), image);
+        let shifted = store.read_region(Viewport { x: -6, y: 249, width: 4, height: 4 });
+        assert_eq!(shifted.get_pixel(1, 1), &red);
+        assert_eq!(shifted.get_pixel(0, 0), &Rgba([0, 0, 0, 0]));
+
+        assert_eq!(store.tile_bounds(), Some(Viewport { x: -256, y: 0, width: 512, height: 512 }));
+
+        // 透明で上書きすると空になったタイルは破棄される
+        store.store_region((-5, 250), &RgbaImage::new(20, 20));
+        assert_eq!(store.tile_count(), 0);
+        assert_eq!(store.tile_bounds(), None);
+    }
+
+    #[test]
+    fn test_compact_infinite_canvas() {
+        let mut image = RgbaImage::new(4, 4);
+        image.put_pixel(1, 1, Rgba([0, 0, 255, 255]));
+        let mut canvas = InfiniteCanvas::new();
+        canvas.layers.entry("kept".to_string()).or_default().store_region((0, 0), &image);
+        // 消し切ったレイヤーは空の表が残る
+        let erased = canvas.layers.entry("erased".to_string()).or_default();
+        erased.store_region((0, 0), &image);
+        erased.store_region((0, 0), &RgbaImage::new(4, 4));
+        // 透明なタイルが残っていれば破棄する
+        canvas.layers.get_mut("kept").unwrap().tiles.insert(TileCoord { x: 3, y: 3 }, RgbaImage::new(TILE_SIZE, TILE_SIZE));
+
+        assert_eq!(canvas.compact(), 1);
+        assert_eq!(canvas.layers.keys().collect::<Vec<_>>(), vec!["kept"]);
+        assert_eq!(canvas.tile_count(), 1);
+    }
+
+    #[test]
+    fn test_tile_generations() {
+        let mut generations = TileGenerations::new();
+        generations.mark("a", Viewport { x: 0, y: 0, width: 512, height: 256 });
+        let seen = generations.generation();
+        generations.mark("a", Viewport { x: 300, y: 10, width: 4, height: 4 });
+        generations.mark("b", Viewport { x: 10, y: 260, width: 4, height: 4 });
+        generations.mark("b", Viewport { x: 10, y: 260, width: 0, height: 4 });
+        assert_eq!(generations.generation(), seen + 2);
+
+        assert_eq!(generations.changed_since(["a", "b"], seen), vec![TileCoord { x: 1, y: 0 }, TileCoord { x: 0, y: 1 }]);
+        assert_eq!(generations.changed_since(["a"], 0).len(), 2);
+        assert!(generations.changed_since(["a", "b"], generations.generation()).is_empty());
+
+        generations.remove_layer("b");
+        assert_eq!(generations.changed_since(["b"], 0), Vec::new());
+    }
+}
@@ -0,0 +1,287 @@
+use wgpu::*;
+use log::{info, debug};
+use crate::shaders;
+use serde::{Deserialize, Serialize};
+use super::pipeline::PipelineError;
+use super::texture::ManagedTexture;
+
+/// キャンバス全体の回転・反転（表示だけでなくピクセルを並べ替える）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CanvasTransform {
+    Rotate90Clockwise,
+    Rotate90CounterClockwise,
+    Rotate180,
+    FlipHorizontal,
+}
+
+impl CanvasTransform {
+    /// 変換後のサイズ
+    pub fn output_size(&self, (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            CanvasTransform::Rotate90Clockwise | CanvasTransform::Rotate90CounterClockwise => (height, width),
+            CanvasTransform::Rotate180 | CanvasTransform::FlipHorizontal => (width, height),
+        }
+    }
+
+    /// 変換後のピクセル位置に対応する変換前のピクセル位置
+    ///
+    /// シェーダー内の計算と同じ対応関係（テストでCPU側の結果と照合する）。
+    pub fn source_pixel(&self, (x, y): (u32, u32), (width, height): (u32, u32)) -> (u32, u32) {
+        match self {
+            CanvasTransform::Rotate90Clockwise => (y, height - 1 - x),
+            CanvasTransform::Rotate90CounterClockwise => (width - 1 - y, x),
+            CanvasTransform::Rotate180 => (width - 1 - x, height - 1 - y),
+            CanvasTransform::FlipHorizontal => (width - 1 - x, y),
+        }
+    }
+
+    fn shader_mode(&self) -> u32 {
+        match self {
+            CanvasTransform::Rotate90Clockwise => 0,
+            CanvasTransform::Rotate90CounterClockwise => 1,
+            CanvasTransform::Rotate180 => 2,
+            CanvasTransform::FlipHorizontal => 3,
+        }
+    }
+}
+
+/// シェーダーに渡す変換パラメータ
+#[repr(C)]
+#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
+struct TransformUniform {
+    mode: u32,
+    source_width: u32,
+    source_height: u32,
+    _padding: u32,
+}
+
+/// テクスチャを回転・反転して別のテクスチャへコピーするパイプライン
+pub struct CanvasTransformPipeline {
+    render_pipeline: RenderPipeline,
+    bind_group_layout: BindGroupLayout,
+    uniform_buffer: Buffer,
+}
+
+impl CanvasTransformPipeline {
+    pub fn new(device: &Device, format: TextureFormat, cache: Option<&PipelineCache>) -> Result<Self, PipelineError> {
+        Self::with_shader(device, format, cache, Self::shader_source())
+    }
+
+    pub fn with_shader(device: &Device, format: TextureFormat, cache: Option<&PipelineCache>, source: &str) -> Result<Self, PipelineError> {
+        info!("[CanvasTransformPipeline] パイプライン作成開始");
+
+        let shader = device.create_shader_module(ShaderModuleDescriptor {
+            label: Some("Canvas Transform Shader"),
+            source: ShaderSource::Wgsl(shaders::compose(source)?.into()),
+        });
+
+        let bind_group_layout = device.create_bind_group_layout(&BindGroupLayoutDescriptor {
+            label: Some("Canvas Transform Bind Group Layout"),
+            entries: &[
+                BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Texture {
+                        sample_type: TextureSampleType::Float { filterable: false },
+                        view_dimension: TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: ShaderStages::FRAGMENT,
+                    ty: BindingType::Buffer {
+                        ty: BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let layout = device.create_pipeline_layout(&PipelineLayoutDescriptor {
+            label: Some("Canvas Transform Pipeline Layout"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+
+        let render_pipeline = device.create_render_pipeline(&RenderPipelineDescriptor {
+            label: Some("Canvas Transform Pipeline"),
+            layout: Some(&layout),
+            vertex: VertexState {
+                module: &shader,
+                entry_point: Some("vs_main"),
+                buffers: &[],
+                compilation_options: PipelineCompilationOptions::default(),
+            },
+            fragment: Some(FragmentState {
+                module: &shader,
+                entry_point: Some("fs_main"),
+                // ピクセルをそのまま置き換えるためブレンドしない
+                targets: &[Some(ColorTargetState {
+                    format,
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                compilation_options: PipelineCompilationOptions::default(),
+            }),
+            primitive: PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: MultisampleState::default(),
+            multiview: None,
+            cache,
+        });
+
+        let uniform_buffer = device.create_buffer(&BufferDescriptor {
+            label: Some("Canvas Transform Uniform Buffer"),
+            size: std::mem::size_of::<TransformUniform>() as u64,
+            usage: BufferUsages::UNIFORM | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        info!("[CanvasTransformPipeline] パイプライン作成完了");
+        Ok(Self {
+            render_pipeline,
+            bind_group_layout,
+            uniform_buffer,
+        })
+    }
+
+    /// `source` を変換して `target` に書き込む（target は変換後のサイズであること）
+    pub fn transform(
+        &self,
+        device: &Device,
+        queue: &Queue,
+        source: &ManagedTexture,
+        target: &ManagedTexture,
+        transform: CanvasTransform,
+    ) {
+        let source_size = (source.spec.width, source.spec.height);
+        debug!("[CanvasTransformPipeline] 変換: {:?} ({}x{})", transform, source_size.0, source_size.1);
+
+        let uniform = TransformUniform {
+            mode: transform.shader_mode(),
+            source_width: source_size.0,
+            source_height: source_size.1,
+            _padding: 0,
+        };
+        queue.write_buffer(&self.uniform_buffer, 0, bytemuck::bytes_of(&uniform));
+
+        let bind_group = device.create_bind_group(&BindGroupDescriptor {
+            label: Some("Canvas Transform Bind Group"),
+            layout: &self.bind_group_layout,
+            entries: &[
+                BindGroupEntry {
+                    binding: 0,
+                    resource: BindingResource::TextureView(&source.view),
+                },
+                BindGroupEntry {
+                    binding: 1,
+                    resource: self.uniform_buffer.as_entire_binding(),
+                },
+            ],
+        });
+
+        let mut encoder = device.create_command_encoder(&CommandEncoderDescriptor {
+            label: Some("Canvas Transform Encoder"),
+        });
+        let mut render_pass = encoder.begin_render_pass(&RenderPassDescriptor {
+            label: Some("Canvas Transform Pass"),
+            color_attachments: &[Some(RenderPassColorAttachment {
+                view: &target.view,
+                resolve_target: None,
+                ops: Operations {
+                    load: LoadOp::Clear(Color::TRANSPARENT),
+                    store: StoreOp::Store,
+                },
+            })],
+            depth_stencil_attachment: None,
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        render_pass.set_pipeline(&self.render_pipeline);
+        render_pass.set_bind_group(0, &bind_group, &[]);
+        // 画面全体を覆う三角形1枚
+        render_pass.draw(0..3, 0..1);
+        drop(render_pass);
+
+        queue.submit(std::iter::once(encoder.finish()));
+    }
+
+    /// 回転・反転シェーダー（WGSL）
+    ///
+    /// 出力ピクセルごとに対応する入力ピクセルを textureLoad で読むため補間は発生しない。
+    pub(crate) fn shader_source() -> &'static str {
+        r#"
+        #import fullscreen
+
+        struct TransformUniform {
+            mode: u32,
+            source_width: u32,
+            source_height: u32,
+            _padding: u32,
+        }
+
+        @group(0) @binding(0) var source: texture_2d<f32>;
+        @group(0) @binding(1) var<uniform> params: TransformUniform;
+
+        @vertex
+        fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+            return fullscreen_triangle(index);
+        }
+
+        @fragment
+        fn fs_main(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+            let x = u32(position.x);
+            let y = u32(position.y);
+            let w = params.source_width;
+            let h = params.source_height;
+
+            var src: vec2<u32>;
+            switch params.mode {
+                case 0u: { src = vec2<u32>(y, h - 1u - x); }
+                case 1u: { src = vec2<u32>(w - 1u - y, x); }
+                case 2u: { src = vec2<u32>(w - 1u - x, h - 1u - y); }
+                default: { src = vec2<u32>(w - 1u - x, y); }
+            }
+            return textureLoad(source, vec2<i32>(src), 0);
+        }
+        "#
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{imageops, Rgba, RgbaImage};
+
+    fn numbered_image(width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([x as u8, y as u8, 0, 255]))
+    }
+
+    fn apply_cpu(image: &RgbaImage, transform: CanvasTransform) -> RgbaImage {
+        let size = image.dimensions();
+        let (width, height) = transform.output_size(size);
+        RgbaImage::from_fn(width, height, |x, y| {
+            let (sx, sy) = transform.source_pixel((x, y), size);
+            *image.get_pixel(sx, sy)
+        })
+    }
+
+    #[test]
+    fn test_source_pixel_matches_imageops() {
+        let image = numbered_image(5, 3);
+        assert_eq!(apply_cpu(&image, CanvasTransform::Rotate90Clockwise), imageops::rotate90(&image));
+        assert_eq!(apply_cpu(&image, CanvasTransform::Rotate90CounterClockwise), imageops::rotate270(&image));
+        assert_eq!(apply_cpu(&image, CanvasTransform::Rotate180), imageops::rotate180(&image));
+        assert_eq!(apply_cpu(&image, CanvasTransform::FlipHorizontal), imageops::flip_horizontal(&image));
+    }
+
+    #[test]
+    fn test_output_size() {
+        assert_eq!(CanvasTransform::Rotate90Clockwise.output_size((640, 480)), (480, 640));
+        assert_eq!(CanvasTransform::Rotate180.output_size((640, 480)), (640, 480));
+    }
+}
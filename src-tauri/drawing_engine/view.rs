@@ -0,0 +1,104 @@
+use serde::{Deserialize, Serialize};
+use super::pipeline::DrawUniforms;
+
+/// 表示の変換（ドキュメント座標 → 画面座標）
+///
+/// ドキュメント座標を `zoom` 倍し、左右反転してから原点を中心に `rotation_degrees` 度
+/// （画面上で時計回り）回転し、`pan` だけ平行移動したものが画面座標になる。
+/// 表示側と同じ値を持たせておき、ペンの画面座標をドキュメント座標に戻すのに使う。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ViewTransform {
+    pub pan_x: f32,
+    pub pan_y: f32,
+    pub zoom: f32,
+    pub rotation_degrees: f32,
+    pub flip_horizontal: bool,
+}
+
+impl Default for ViewTransform {
+    fn default() -> Self {
+        Self { pan_x: 0.0, pan_y: 0.0, zoom: 1.0, rotation_degrees: 0.0, flip_horizontal: false }
+    }
+}
+
+impl ViewTransform {
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.zoom > 0.0 && self.zoom.is_finite()) {
+            return Err(format!("表示の拡大率は正の値で指定してください: {}", self.zoom));
+        }
+        if ![self.pan_x, self.pan_y, self.rotation_degrees].iter().all(|value| value.is_finite()) {
+            return Err("表示の移動量と回転角は有限の値で指定してください".to_string());
+        }
+        Ok(())
+    }
+
+    /// 拡大・反転・回転をまとめた 2x2 行列（列優先）
+    fn linear(&self) -> [[f32; 2]; 2] {
+        let (sin, cos) = self.rotation_degrees.to_radians().sin_cos();
+        let flip = if self.flip_horizontal { -1.0 } else { 1.0 };
+        [
+            [cos * flip * self.zoom, sin * flip * self.zoom],
+            [-sin * self.zoom, cos * self.zoom],
+        ]
+    }
+
+    /// ドキュメント座標を画面座標に変換
+    pub fn document_to_screen(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let m = self.linear();
+        (m[0][0] * x + m[1][0] * y + self.pan_x, m[0][1] * x + m[1][1] * y + self.pan_y)
+    }
+
+    /// 画面座標をドキュメント座標に変換（ペン入力をテッセレーションの前に戻す）
+    pub fn screen_to_document(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (sin, cos) = self.rotation_degrees.to_radians().sin_cos();
+        let (dx, dy) = (x - self.pan_x, y - self.pan_y);
+        // 回転を戻してから反転と拡大を戻す
+        let (rx, ry) = (cos * dx + sin * dy, -sin * dx + cos * dy);
+        let flip = if self.flip_horizontal { -1.0 } else { 1.0 };
+        (rx * flip / self.zoom, ry / self.zoom)
+    }
+
+    /// 描画パイプラインの表示行列（`DrawUniforms::with_view` に渡す）
+    pub fn to_matrix(&self) -> [[f32; 4]; 4] {
+        let m = self.linear();
+        let mut matrix = DrawUniforms::IDENTITY;
+        matrix[0][..2].copy_from_slice(&m[0]);
+        matrix[1][..2].copy_from_slice(&m[1]);
+        matrix[3][0] = self.pan_x;
+        matrix[3][1] = self.pan_y;
+        matrix
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close((ax, ay): (f32, f32), (bx, by): (f32, f32)) {
+        assert!((ax - bx).abs() < 1e-4 && (ay - by).abs() < 1e-4, "({}, {}) != ({}, {})", ax, ay, bx, by);
+    }
+
+    #[test]
+    fn test_screen_round_trip() {
+        let view = ViewTransform { pan_x: 40.0, pan_y: -12.0, zoom: 2.5, rotation_degrees: 30.0, flip_horizontal: true };
+        for point in [(0.0, 0.0), (10.0, 3.0), (-7.5, 120.0)] {
+            assert_close(view.screen_to_document(view.document_to_screen(point)), point);
+        }
+
+        // 90度回転（時計回り）すると、ドキュメントの右が画面の下になる
+        let rotated = ViewTransform { rotation_degrees: 90.0, ..ViewTransform::default() };
+        assert_close(rotated.document_to_screen((10.0, 0.0)), (0.0, 10.0));
+        assert_close(rotated.screen_to_document((0.0, 10.0)), (10.0, 0.0));
+        let flipped = ViewTransform { flip_horizontal: true, pan_x: 100.0, ..ViewTransform::default() };
+        assert_close(flipped.screen_to_document((90.0, 5.0)), (10.0, 5.0));
+
+        // 表示行列は CPU の変換と同じ結果になる
+        let m = view.to_matrix();
+        let (x, y) = (10.0, 3.0);
+        assert_close((m[0][0] * x + m[1][0] * y + m[3][0], m[0][1] * x + m[1][1] * y + m[3][1]), view.document_to_screen((x, y)));
+
+        assert!(ViewTransform { zoom: 0.0, ..view }.validate().is_err());
+        assert!(ViewTransform { rotation_degrees: f32::NAN, ..view }.validate().is_err());
+    }
+}
@@ -0,0 +1,284 @@
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use wide::{f32x4, CmpLe};
+use crate::animation::BlendMode;
+use super::effects::apply_effects;
+use super::{FormatError, RasterLayer};
+
+/// 書き出しの背景（アルファを残すか、マット色の上に平坦化するか）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportBackground {
+    /// 透明部分をアルファとして残す
+    #[default]
+    Transparent,
+    /// 不透明なマット色（RGB 0.0〜1.0）の上に合成する
+    Matte { color: [f32; 3] },
+}
+
+impl ExportBackground {
+    pub fn validate(&self) -> Result<(), FormatError> {
+        if let ExportBackground::Matte { color } = self {
+            if color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+                return Err(FormatError::InvalidData(format!("マット色が不正です: {:?}", color)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 合成の下地にするマット色（透明のままなら None）
+    pub fn matte(&self) -> Option<Rgba<u8>> {
+        match self {
+            ExportBackground::Transparent => None,
+            ExportBackground::Matte { color } => {
+                let [r, g, b] = color.map(|c| (c.clamp(0.0, 1.0) * 255.0).round() as u8);
+                Some(Rgba([r, g, b, 255]))
+            }
+        }
+    }
+}
+
+/// レイヤーを合成して1枚の画像にする（先頭が最背面）
+///
+/// キャンバスの行ごとに並列で処理し、各行では全レイヤーを背面から順に重ねる。
+/// 1画素の RGB は SIMD（f32x4）でまとめて計算する。レイヤー効果は先にレイヤーごとに重ねておく。
+pub fn flatten_layers(layers: &[RasterLayer], width: u32, height: u32) -> RgbaImage {
+    flatten_layers_on(layers, width, height, None)
+}
+
+/// マット色を敷いたキャンバスにレイヤーを合成する（`matte` が None なら透明なキャンバス）
+///
+/// マット色は最背面の下地として扱うため、乗算やスクリーンのレイヤーもマット色と混ざる。
+pub fn flatten_layers_on(layers: &[RasterLayer], width: u32, height: u32, matte: Option<Rgba<u8>>) -> RgbaImage {
+    let mut canvas = match matte {
+        Some(color) => RgbaImage::from_pixel(width, height, color),
+        None => RgbaImage::new(width, height),
+    };
+    let visible: Vec<&RasterLayer> = layers.iter().filter(|l| l.visible && l.opacity > 0.0).collect();
+    if visible.is_empty() || width == 0 || height == 0 {
+        return canvas;
+    }
+    let with_effects: Vec<Option<RasterLayer>> = visible.par_iter().map(|layer| apply_effects(layer)).collect();
+    let visible: Vec<&RasterLayer> = visible.iter().zip(&with_effects)
+        .map(|(layer, applied)| applied.as_ref().unwrap_or(layer))
+        .collect();
+
+    canvas.par_chunks_mut(width as usize * 4)
+        .enumerate()
+        .for_each(|(y, row)| {
+            for layer in &visible {
+                composite_row(row, layer, y as i64);
+            }
+        });
+    canvas
+}
+
+/// キャンバスの1行にレイヤーの対応する行を重ねる
+fn composite_row(row: &mut [u8], layer: &RasterLayer, canvas_y: i64) {
+    let (offset_x, offset_y) = (layer.offset.0 as i64, layer.offset.1 as i64);
+    let layer_y = canvas_y - offset_y;
+    if layer_y < 0 || layer_y >= layer.image.height() as i64 {
+        return;
+    }
+    let canvas_width = (row.len() / 4) as i64;
+    let x_start = offset_x.max(0);
+    let x_end = (offset_x + layer.image.width() as i64).min(canvas_width);
+    if x_start >= x_end {
+        return;
+    }
+
+    let layer_row_start = layer_y as usize * layer.image.width() as usize * 4;
+    let src = &layer.image.as_raw()[layer_row_start + (x_start - offset_x) as usize * 4..layer_row_start + (x_end - offset_x) as usize * 4];
+    let dst = &mut row[x_start as usize * 4..x_end as usize * 4];
+    let opacity = layer.opacity.clamp(0.0, 1.0);
+
+    for (dst, src) in dst.chunks_exact_mut(4).zip(src.chunks_exact(4)) {
+        blend_pixel(dst, src, opacity, &layer.blend_mode);
+    }
+}
+
+/// 1画素の合成（W3C Compositing の source-over にブレンド関数を組み合わせたもの）
+#[inline]
+pub(super) fn blend_pixel(dst: &mut [u8], src: &[u8], opacity: f32, mode: &BlendMode) {
+    let src_alpha = src[3] as f32 / 255.0 * opacity;
+    if src_alpha <= 0.0 {
+        return;
+    }
+    let dst_alpha = dst[3] as f32 / 255.0;
+    let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+
+    let scale = f32x4::splat(255.0);
+    let cs = f32x4::from([src[0] as f32, src[1] as f32, src[2] as f32, 0.0]) / scale;
+    let cb = f32x4::from([dst[0] as f32, dst[1] as f32, dst[2] as f32, 0.0]) / scale;
+    let blended = blend_channels(mode, cb, cs);
+    let color = f32x4::splat(src_alpha * (1.0 - dst_alpha)) * cs
+        + f32x4::splat(src_alpha * dst_alpha) * blended
+        + f32x4::splat((1.0 - src_alpha) * dst_alpha) * cb;
+    let color = (color / f32x4::splat(out_alpha) * scale).to_array();
+
+    // 丸めは f32::round（0.5 は切り上げ）に揃える
+    for channel in 0..3 {
+        dst[channel] = color[channel].round().clamp(0.0, 255.0) as u8;
+    }
+    dst[3] = (out_alpha * 255.0).round() as u8;
+}
+
+/// ブレンドモードごとの色の合成（cb: 下地, cs: 描画色。各レーンが1チャンネル）
+#[inline]
+fn blend_channels(mode: &BlendMode, cb: f32x4, cs: f32x4) -> f32x4 {
+    let one = f32x4::ONE;
+    let two = f32x4::splat(2.0);
+    match mode {
+        BlendMode::Normal => cs,
+        BlendMode::Multiply => cb * cs,
+        BlendMode::Screen => cb + cs - cb * cs,
+        BlendMode::Overlay => {
+            let dark = two * cb * cs;
+            let light = one - two * (one - cb) * (one - cs);
+            cb.cmp_le(f32x4::splat(0.5)).blend(dark, light)
+        }
+    }
+}
+
+/// 透明な部分をマット色で埋める（カメラの切り出しでキャンバスの外になった部分など）
+pub fn apply_matte(image: &mut RgbaImage, matte: Rgba<u8>) {
+    image.par_chunks_mut(4).for_each(|pixel| {
+        let blended = blend_over(matte, Rgba([pixel[0], pixel[1], pixel[2], pixel[3]]));
+        pixel.copy_from_slice(&blended.0);
+    });
+}
+
+/// 1画素のストレートアルファの source-over 合成（表示用のオーバーレイや選択範囲の描画で使う）
+///
+/// 結果が完全に透明になる場合は色を持たない `[0, 0, 0, 0]` を返す。
+pub fn blend_over(dst: Rgba<u8>, src: Rgba<u8>) -> Rgba<u8> {
+    let src_alpha = src[3] as f32 / 255.0;
+    if src_alpha >= 1.0 {
+        return src;
+    }
+    let dst_alpha = dst[3] as f32 / 255.0;
+    let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+    if out_alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    if src_alpha <= 0.0 {
+        return dst;
+    }
+    let mut out = [0u8; 4];
+    for channel in 0..3 {
+        let color = src[channel] as f32 * src_alpha + dst[channel] as f32 * dst_alpha * (1.0 - src_alpha);
+        out[channel] = (color / out_alpha).round().clamp(0.0, 255.0) as u8;
+    }
+    out[3] = (out_alpha * 255.0).round() as u8;
+    Rgba(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blend_over() {
+        let dst = Rgba([0, 0, 255, 255]);
+        assert_eq!(blend_over(dst, Rgba([255, 0, 0, 255])), Rgba([255, 0, 0, 255]));
+        assert_eq!(blend_over(dst, Rgba([255, 0, 0, 0])), dst);
+        assert_eq!(blend_over(dst, Rgba([255, 0, 0, 128])), Rgba([128, 0, 127, 255]));
+        // 透明な下地には色がそのまま乗る
+        assert_eq!(blend_over(Rgba([0, 0, 0, 0]), Rgba([10, 20, 30, 64])), Rgba([10, 20, 30, 64]));
+        // 透明同士の合成は下地の色を残さない
+        assert_eq!(blend_over(Rgba([40, 50, 60, 0]), Rgba([10, 20, 30, 0])), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_flatten_on_matte() {
+        let mut image = RgbaImage::new(2, 1);
+        image.put_pixel(0, 0, Rgba([255, 255, 255, 128]));
+        let layer = RasterLayer {
+            blend_mode: BlendMode::Multiply,
+            ..RasterLayer::new("multiply".to_string(), image)
+        };
+        let matte = ExportBackground::Matte { color: [1.0, 0.0, 0.0] }.matte();
+        assert_eq!(matte, Some(Rgba([255, 0, 0, 255])));
+        assert_eq!(ExportBackground::Transparent.matte(), None);
+        assert!(ExportBackground::Matte { color: [1.5, 0.0, 0.0] }.validate().is_err());
+
+        // 乗算のレイヤーもマット色と混ざり、透明な部分はマット色になる
+        let result = flatten_layers_on(std::slice::from_ref(&layer), 2, 1, matte);
+        assert_eq!(result.get_pixel(0, 0), &Rgba([255, 0, 0, 255]));
+        assert_eq!(result.get_pixel(1, 0), &Rgba([255, 0, 0, 255]));
+        let transparent = flatten_layers(&[layer], 2, 1);
+        assert_eq!(transparent.get_pixel(1, 0)[3], 0);
+
+        let mut cropped = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 0, 0]));
+        apply_matte(&mut cropped, Rgba([0, 255, 0, 255]));
+        assert_eq!(cropped.get_pixel(0, 0), &Rgba([0, 255, 0, 255]));
+    }
+
+    /// 画素ごとにスカラーで計算する合成（SIMD 版の検証用）
+    fn flatten_scalar(layers: &[RasterLayer], width: u32, height: u32) -> RgbaImage {
+        let mut canvas = RgbaImage::new(width, height);
+        for layer in layers.iter().filter(|l| l.visible && l.opacity > 0.0) {
+            for (x, y, src) in layer.image.enumerate_pixels() {
+                let cx = x as i64 + layer.offset.0 as i64;
+                let cy = y as i64 + layer.offset.1 as i64;
+                if cx < 0 || cy < 0 || cx >= width as i64 || cy >= height as i64 {
+                    continue;
+                }
+                let src_alpha = src[3] as f32 / 255.0 * layer.opacity.clamp(0.0, 1.0);
+                if src_alpha <= 0.0 {
+                    continue;
+                }
+                let dst = canvas.get_pixel_mut(cx as u32, cy as u32);
+                let dst_alpha = dst[3] as f32 / 255.0;
+                let out_alpha = src_alpha + dst_alpha * (1.0 - src_alpha);
+                for channel in 0..3 {
+                    let cs = src[channel] as f32 / 255.0;
+                    let cb = dst[channel] as f32 / 255.0;
+                    let blended = match layer.blend_mode {
+                        BlendMode::Normal => cs,
+                        BlendMode::Multiply => cb * cs,
+                        BlendMode::Screen => cb + cs - cb * cs,
+                        BlendMode::Overlay if cb <= 0.5 => 2.0 * cb * cs,
+                        BlendMode::Overlay => 1.0 - 2.0 * (1.0 - cb) * (1.0 - cs),
+                    };
+                    let color = src_alpha * (1.0 - dst_alpha) * cs
+                        + src_alpha * dst_alpha * blended
+                        + (1.0 - src_alpha) * dst_alpha * cb;
+                    dst[channel] = (color / out_alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+                }
+                dst[3] = (out_alpha * 255.0).round() as u8;
+            }
+        }
+        canvas
+    }
+
+    fn pattern(width: u32, height: u32, seed: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| {
+            let v = (x * 31 + y * 17 + seed * 101).wrapping_mul(2654435761);
+            Rgba([(v >> 24) as u8, (v >> 16) as u8, (v >> 8) as u8, v as u8])
+        })
+    }
+
+    #[test]
+    fn test_matches_scalar_compositing() {
+        let modes = [BlendMode::Normal, BlendMode::Multiply, BlendMode::Screen, BlendMode::Overlay];
+        let mut layers: Vec<RasterLayer> = modes.iter().enumerate()
+            .map(|(index, mode)| {
+                let mut layer = RasterLayer::new(format!("layer{}", index), pattern(37, 23, index as u32));
+                layer.blend_mode = mode.clone();
+                layer.opacity = 1.0 - index as f32 * 0.2;
+                layer.offset = (index as i32 * 7 - 10, 5 - index as i32 * 4);
+                layer
+            })
+            .collect();
+        let mut hidden = RasterLayer::new("hidden", pattern(41, 29, 7));
+        hidden.visible = false;
+        let mut outside = RasterLayer::new("outside", pattern(4, 4, 9));
+        outside.offset = (100, 0);
+        layers.extend([hidden, outside]);
+
+        assert_eq!(flatten_layers(&layers, 41, 29), flatten_scalar(&layers, 41, 29));
+        assert_eq!(flatten_layers(&layers, 0, 0).dimensions(), (0, 0));
+    }
+}
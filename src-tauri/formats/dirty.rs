@@ -0,0 +1,190 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use crate::animation::{Layer, Project};
+use crate::history::Operation;
+
+/// 最後に保存（または読み込み）してから変更されたレイヤーの追跡
+///
+/// 追記保存では、ここで変更ありとされたレイヤーと保存済みファイルにないレイヤーだけを書き込む。
+/// フレームは含むレイヤーのどれかが書き込まれれば変更ありとみなす。
+#[derive(Debug, Default)]
+pub struct SaveTracker {
+    /// 最後に保存したファイル
+    saved: Option<SavedFile>,
+    dirty_layers: HashSet<String>,
+    /// レイヤーを特定できない変更（キャンバス変換・履歴の置き換えなど）があった
+    all_dirty: bool,
+}
+
+/// 保存済みファイルの場所と、保存直後のファイルの長さ（他で書き換えられていないかの確認用）
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedFile {
+    pub path: PathBuf,
+    pub len: u64,
+}
+
+/// 保存開始時点の変更内容（保存中の変更は `SaveTracker` 側に新たに記録される）
+#[derive(Debug, Clone, Default)]
+pub struct SaveSnapshot {
+    dirty_layers: HashSet<String>,
+    all_dirty: bool,
+}
+
+impl SaveTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 操作が変更したレイヤーを記録（レイヤーを持たない操作は全体の変更とする）
+    pub fn mark_operation(&mut self, operation: &Operation) {
+        match operation.layer_id() {
+            Some(layer_id) => self.mark_layer(layer_id),
+            None => self.mark_all(),
+        }
+    }
+
+    pub fn mark_layer(&mut self, layer_id: &str) {
+        if !self.all_dirty {
+            self.dirty_layers.insert(layer_id.to_string());
+        }
+    }
+
+    pub fn mark_all(&mut self) {
+        self.all_dirty = true;
+        self.dirty_layers.clear();
+    }
+
+    /// 読み込んだファイルを保存済みの状態とする
+    pub fn mark_clean(&mut self, path: impl Into<PathBuf>, len: u64) {
+        self.saved = Some(SavedFile { path: path.into(), len });
+        self.dirty_layers.clear();
+        self.all_dirty = false;
+    }
+
+    /// 最後に保存してから変更があるか
+    pub fn is_dirty(&self) -> bool {
+        self.all_dirty || !self.dirty_layers.is_empty()
+    }
+
+    /// `path` への保存に追記を使える場合、その保存済みファイルを返す
+    pub fn incremental_base(&self, path: &Path) -> Option<&SavedFile> {
+        self.saved.as_ref().filter(|saved| !self.all_dirty && saved.path == path)
+    }
+
+    /// 保存を始める（それまでの変更を取り出し、以降の変更は新たに記録する）
+    pub fn begin_save(&mut self) -> SaveSnapshot {
+        SaveSnapshot {
+            dirty_layers: std::mem::take(&mut self.dirty_layers),
+            all_dirty: std::mem::take(&mut self.all_dirty),
+        }
+    }
+
+    /// 保存が完了した（保存中の変更は残る）
+    pub fn finish_save(&mut self, path: impl Into<PathBuf>, len: u64) {
+        self.saved = Some(SavedFile { path: path.into(), len });
+    }
+
+    /// 保存に失敗した（取り出した変更を戻す。ファイルの状態が不明なため追記の元にはしない）
+    pub fn abort_save(&mut self, snapshot: SaveSnapshot) {
+        self.saved = None;
+        if snapshot.all_dirty {
+            self.mark_all();
+        } else {
+            for layer_id in snapshot.dirty_layers {
+                self.mark_layer(&layer_id);
+            }
+        }
+    }
+}
+
+impl SaveSnapshot {
+    pub fn is_layer_dirty(&self, layer_id: &str) -> bool {
+        self.all_dirty || self.dirty_layers.contains(layer_id)
+    }
+
+    /// 書き込む必要のあるレイヤー（レイヤーかマスクが変更されたもの・保存済みファイルにないもの。重複は除く）
+    pub fn layers_to_write(&self, project: &Project, is_saved: impl Fn(&str) -> bool) -> Vec<Layer> {
+        let mut seen = HashSet::new();
+        project.frames.iter()
+            .flat_map(|frame| &frame.layers)
+            .filter(|layer| layer.texture_ids().any(|id| self.is_layer_dirty(id) || !is_saved(id)))
+            .filter(|layer| seen.insert(layer.id.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    /// 変更されたレイヤーを含むフレームの番号
+    pub fn dirty_frames(&self, project: &Project) -> Vec<usize> {
+        project.frames.iter().enumerate()
+            .filter(|(_, frame)| frame.texture_ids().any(|id| self.is_layer_dirty(id)))
+            .map(|(index, _)| index)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{BlendMode, Frame};
+    use crate::drawing_engine::CanvasTransform;
+
+    fn project() -> Project {
+        let mut project = Project::new("test".to_string(), 16, 16, 12.0);
+        project.frames = (0..3).map(|i| Frame {
+            id: format!("frame_{}", i),
+            layers: vec![Layer {
+                id: format!("layer_{}", i),
+                name: "ink".to_string(),
+                visible: true,
+                opacity: 1.0,
+                blend_mode: BlendMode::Normal,
+                locked: false,
+                effects: Vec::new(),
+                fill: None,
+                mask: None,
+            }],
+            duration: 1.0 / 12.0,
+        }).collect();
+        project
+    }
+
+    #[test]
+    fn test_tracks_layers_across_save() {
+        let project = project();
+        let mut tracker = SaveTracker::new();
+        tracker.mark_clean("a.kine", 100);
+        assert!(!tracker.is_dirty());
+
+        tracker.mark_operation(&Operation::ClearLayer { layer_id: "layer_1".to_string() });
+        assert!(tracker.incremental_base(Path::new("a.kine")).is_some());
+        assert!(tracker.incremental_base(Path::new("b.kine")).is_none());
+
+        let snapshot = tracker.begin_save();
+        assert_eq!(snapshot.dirty_frames(&project), vec![1]);
+        let written: Vec<String> = snapshot.layers_to_write(&project, |id| id != "layer_2")
+            .into_iter().map(|layer| layer.id).collect();
+        assert_eq!(written, vec!["layer_1", "layer_2"]);
+
+        // 保存中の変更は次の保存に持ち越す
+        tracker.mark_layer("layer_0");
+        tracker.finish_save("a.kine", 200);
+        assert_eq!(tracker.begin_save().dirty_frames(&project), vec![0]);
+    }
+
+    #[test]
+    fn test_failed_save_and_canvas_changes() {
+        let project = project();
+        let mut tracker = SaveTracker::new();
+        tracker.mark_clean("a.kine", 100);
+        tracker.mark_layer("layer_2");
+        let snapshot = tracker.begin_save();
+        tracker.abort_save(snapshot);
+        assert_eq!(tracker.begin_save().dirty_frames(&project), vec![2]);
+        assert!(tracker.incremental_base(Path::new("a.kine")).is_none());
+
+        tracker.mark_clean("a.kine", 100);
+        tracker.mark_operation(&Operation::TransformCanvas { transform: CanvasTransform::FlipHorizontal });
+        assert!(tracker.incremental_base(Path::new("a.kine")).is_none());
+        assert_eq!(tracker.begin_save().dirty_frames(&project), vec![0, 1, 2]);
+    }
+}
@@ -0,0 +1,316 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use crate::animation::BlendMode;
+use super::composite::blend_pixel;
+use super::{FormatError, RasterLayer};
+
+/// 効果の半径・影のずらし量の上限（ピクセル）
+pub const MAX_EFFECT_RADIUS: f32 = 256.0;
+pub const MAX_SHADOW_OFFSET: i32 = 1024;
+
+/// レイヤー効果（非破壊。合成時にレイヤーの不透明部分から作り、レイヤーの下に重ねる）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LayerEffect {
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    #[serde(flatten)]
+    pub kind: EffectKind,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// 効果の種類（`color` の A は効果の不透明度）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum EffectKind {
+    /// ずらしてぼかした影
+    DropShadow { offset_x: i32, offset_y: i32, blur: f32, color: [u8; 4] },
+    /// 外側の縁取り（`width` ピクセル）
+    Outline { width: f32, color: [u8; 4] },
+    /// 外側にぼかして広げた光彩
+    OuterGlow { radius: f32, color: [u8; 4] },
+}
+
+impl LayerEffect {
+    pub fn validate(&self) -> Result<(), FormatError> {
+        let radius = match &self.kind {
+            EffectKind::DropShadow { offset_x, offset_y, blur, .. } => {
+                if offset_x.abs() > MAX_SHADOW_OFFSET || offset_y.abs() > MAX_SHADOW_OFFSET {
+                    return Err(FormatError::InvalidData(format!("影のずらし量は ±{} 以内で指定してください", MAX_SHADOW_OFFSET)));
+                }
+                *blur
+            }
+            EffectKind::Outline { width, .. } => *width,
+            EffectKind::OuterGlow { radius, .. } => *radius,
+        };
+        if !(0.0..=MAX_EFFECT_RADIUS).contains(&radius) {
+            return Err(FormatError::InvalidData(format!("効果の大きさは 0〜{} で指定してください: {}", MAX_EFFECT_RADIUS, radius)));
+        }
+        Ok(())
+    }
+
+    /// レイヤーの周囲に必要な余白
+    fn padding(&self) -> u32 {
+        match &self.kind {
+            EffectKind::DropShadow { offset_x, offset_y, blur, .. } => {
+                offset_x.unsigned_abs().max(offset_y.unsigned_abs()) + blur_extent(*blur)
+            }
+            EffectKind::Outline { width, .. } => width.ceil() as u32 + 1,
+            EffectKind::OuterGlow { radius, .. } => blur_extent(*radius),
+        }
+    }
+
+    /// 効果の形（余白を含む `width`x`height` の不透明度、`mask` はレイヤーの不透明度）
+    fn coverage(&self, mask: &[f32], width: usize, height: usize) -> Vec<f32> {
+        match &self.kind {
+            EffectKind::DropShadow { offset_x, offset_y, blur, .. } => {
+                let mut shifted = vec![0.0; mask.len()];
+                for y in 0..height {
+                    let source_y = y as i64 - *offset_y as i64;
+                    if source_y < 0 || source_y >= height as i64 {
+                        continue;
+                    }
+                    for x in 0..width {
+                        let source_x = x as i64 - *offset_x as i64;
+                        if (0..width as i64).contains(&source_x) {
+                            shifted[y * width + x] = mask[source_y as usize * width + source_x as usize];
+                        }
+                    }
+                }
+                gaussian_blur(&mut shifted, width, height, *blur / 2.0);
+                shifted
+            }
+            EffectKind::Outline { width: stroke, .. } => {
+                // 距離は画素中心どうしのため、縁からの距離は半画素短い
+                let distances = distance_to_opaque(mask, width, height);
+                distances.iter().zip(mask)
+                    .map(|(distance, alpha)| (stroke + 1.0 - distance).clamp(0.0, 1.0).max(*alpha))
+                    .collect()
+            }
+            EffectKind::OuterGlow { radius, .. } => {
+                let mut glow = mask.to_vec();
+                gaussian_blur(&mut glow, width, height, *radius / 2.0);
+                // ぼかしで薄くなった分を持ち上げ、縁の近くははっきり光らせる
+                glow.iter_mut().for_each(|value| *value = (*value * 2.0).min(1.0));
+                glow
+            }
+        }
+    }
+
+    fn color(&self) -> [u8; 4] {
+        match &self.kind {
+            EffectKind::DropShadow { color, .. } | EffectKind::Outline { color, .. } | EffectKind::OuterGlow { color, .. } => *color,
+        }
+    }
+}
+
+/// 有効な効果をレイヤーの下に重ねたレイヤー（有効な効果がなければ None）
+///
+/// 効果は並び順に下から重ね、最後にレイヤー自身を重ねる。結果は効果が収まるよう余白を付けた画像になり、
+/// レイヤーの不透明度・ブレンドモードはまとめた画像全体にかかる。
+pub fn apply_effects(layer: &RasterLayer) -> Option<RasterLayer> {
+    let effects: Vec<&LayerEffect> = layer.effects.iter()
+        .filter(|effect| effect.enabled && effect.color()[3] > 0)
+        .collect();
+    if effects.is_empty() || layer.image.width() == 0 || layer.image.height() == 0 {
+        return None;
+    }
+    let padding = effects.iter().map(|effect| effect.padding()).max().unwrap_or(0);
+    let width = layer.image.width() + padding * 2;
+    let height = layer.image.height() + padding * 2;
+    let (w, h) = (width as usize, height as usize);
+
+    let mut mask = vec![0.0f32; w * h];
+    for (x, y, pixel) in layer.image.enumerate_pixels() {
+        mask[(y + padding) as usize * w + (x + padding) as usize] = pixel[3] as f32 / 255.0;
+    }
+
+    let mut group = RgbaImage::new(width, height);
+    for effect in effects {
+        let [r, g, b, a] = effect.color();
+        for (pixel, coverage) in group.pixels_mut().zip(effect.coverage(&mask, w, h)) {
+            let alpha = (coverage * a as f32).round().clamp(0.0, 255.0) as u8;
+            if alpha > 0 {
+                blend_pixel(&mut pixel.0, &[r, g, b, alpha], 1.0, &BlendMode::Normal);
+            }
+        }
+    }
+    for (x, y, pixel) in layer.image.enumerate_pixels() {
+        blend_pixel(&mut group.get_pixel_mut(x + padding, y + padding).0, &pixel.0, 1.0, &BlendMode::Normal);
+    }
+
+    Some(RasterLayer {
+        name: layer.name.clone(),
+        visible: layer.visible,
+        opacity: layer.opacity,
+        blend_mode: layer.blend_mode.clone(),
+        offset: (layer.offset.0 - padding as i32, layer.offset.1 - padding as i32),
+        image: group,
+        effects: Vec::new(),
+    })
+}
+
+/// ガウスぼかしの影響が及ぶ範囲（σ の3倍）
+fn blur_extent(blur: f32) -> u32 {
+    (blur / 2.0 * 3.0).ceil() as u32
+}
+
+/// 3回の箱型ぼかしで近似したガウスぼかし
+fn gaussian_blur(values: &mut [f32], width: usize, height: usize, sigma: f32) {
+    // 3回重ねた分散が σ² になる箱の半径
+    let radius = (((4.0 * sigma * sigma + 1.0).sqrt() - 1.0) / 2.0).round() as usize;
+    if radius == 0 {
+        return;
+    }
+    let mut line = Vec::new();
+    for _ in 0..3 {
+        for y in 0..height {
+            line.clear();
+            line.extend((0..width).map(|x| values[y * width + x]));
+            box_blur_line(&line, radius, |x, value| values[y * width + x] = value);
+        }
+        for x in 0..width {
+            line.clear();
+            line.extend((0..height).map(|y| values[y * width + x]));
+            box_blur_line(&line, radius, |y, value| values[y * width + x] = value);
+        }
+    }
+}
+
+/// 1行の箱型ぼかし（範囲外は 0 として累積和で求める）
+fn box_blur_line(line: &[f32], radius: usize, mut write: impl FnMut(usize, f32)) {
+    let scale = 1.0 / (radius * 2 + 1) as f32;
+    let mut sum: f32 = line.iter().take(radius).sum();
+    for index in 0..line.len() {
+        if let Some(entering) = line.get(index + radius) {
+            sum += entering;
+        }
+        if index > radius {
+            sum -= line[index - radius - 1];
+        }
+        write(index, (sum * scale).max(0.0));
+    }
+}
+
+/// 各画素から最も近い不透明な画素（不透明度 0.5 以上）までの距離（ユークリッド距離変換）
+fn distance_to_opaque(mask: &[f32], width: usize, height: usize) -> Vec<f32> {
+    const FAR: f32 = 1e12;
+    let mut squared: Vec<f32> = mask.iter().map(|alpha| if *alpha >= 0.5 { 0.0 } else { FAR }).collect();
+    let mut line = Vec::new();
+    for x in 0..width {
+        line.clear();
+        line.extend((0..height).map(|y| squared[y * width + x]));
+        for (y, value) in distance_transform_1d(&line).into_iter().enumerate() {
+            squared[y * width + x] = value;
+        }
+    }
+    for y in 0..height {
+        let row = &mut squared[y * width..(y + 1) * width];
+        let transformed = distance_transform_1d(row);
+        row.copy_from_slice(&transformed);
+    }
+    squared.into_iter().map(f32::sqrt).collect()
+}
+
+/// 1次元の二乗距離変換（下側包絡線を使う Felzenszwalb の方法）
+fn distance_transform_1d(f: &[f32]) -> Vec<f32> {
+    let n = f.len();
+    let mut result = vec![0.0; n];
+    if n == 0 {
+        return result;
+    }
+    let mut vertices = vec![0usize; n];
+    let mut bounds = vec![0.0f32; n + 1];
+    let mut k = 0;
+    bounds[0] = f32::NEG_INFINITY;
+    bounds[1] = f32::INFINITY;
+    let intersection = |q: usize, p: usize| {
+        ((f[q] + (q * q) as f32) - (f[p] + (p * p) as f32)) / (2.0 * q as f32 - 2.0 * p as f32)
+    };
+    for q in 1..n {
+        let mut s = intersection(q, vertices[k]);
+        while s <= bounds[k] {
+            k -= 1;
+            s = intersection(q, vertices[k]);
+        }
+        k += 1;
+        vertices[k] = q;
+        bounds[k] = s;
+        bounds[k + 1] = f32::INFINITY;
+    }
+    k = 0;
+    for (q, value) in result.iter_mut().enumerate() {
+        while bounds[k + 1] < q as f32 {
+            k += 1;
+        }
+        let offset = q as f32 - vertices[k] as f32;
+        *value = offset * offset + f[vertices[k]];
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn square_layer(effects: Vec<LayerEffect>) -> RasterLayer {
+        let mut image = RgbaImage::new(10, 10);
+        for y in 3..7 {
+            for x in 3..7 {
+                image.put_pixel(x, y, Rgba([255, 255, 255, 255]));
+            }
+        }
+        RasterLayer { effects, ..RasterLayer::new("layer", image) }
+    }
+
+    fn effect(kind: EffectKind) -> LayerEffect {
+        LayerEffect { enabled: true, kind }
+    }
+
+    #[test]
+    fn test_outline_surrounds_content_below_layer() {
+        let layer = square_layer(vec![effect(EffectKind::Outline { width: 2.0, color: [255, 0, 0, 255] })]);
+        let result = apply_effects(&layer).unwrap();
+        let padding = (0 - result.offset.0) as u32;
+        assert_eq!(padding, 3);
+        let at = |x: i32, y: i32| result.image.get_pixel((x + padding as i32) as u32, (y + padding as i32) as u32).0;
+
+        // 中身はレイヤーの色のまま、外側2ピクセルが縁取り、それより外は透明
+        assert_eq!(at(4, 4), [255, 255, 255, 255]);
+        assert_eq!(at(2, 4), [255, 0, 0, 255]);
+        assert_eq!(at(1, 4), [255, 0, 0, 255]);
+        assert_eq!(at(0, 4)[3], 0);
+        // 角は丸くなる
+        assert!(at(1, 1)[3] < 128);
+
+        // 無効にした効果は合成しない
+        let mut disabled = layer.clone();
+        disabled.effects[0].enabled = false;
+        assert!(apply_effects(&disabled).is_none());
+    }
+
+    #[test]
+    fn test_drop_shadow_and_glow_spread_beyond_layer() {
+        let shadow = square_layer(vec![effect(EffectKind::DropShadow { offset_x: 4, offset_y: 0, blur: 0.0, color: [0, 0, 0, 128] })]);
+        let result = apply_effects(&shadow).unwrap();
+        assert_eq!(result.offset, (-4, -4));
+        // 影はレイヤーの右端（10）を越えて描かれる
+        assert_eq!(result.image.get_pixel(10 + 4, 4 + 4).0, [0, 0, 0, 128]);
+        assert_eq!(result.image.get_pixel(3 + 4, 4 + 4).0, [255, 255, 255, 255]);
+
+        let glow = square_layer(vec![effect(EffectKind::OuterGlow { radius: 4.0, color: [255, 255, 0, 255] })]);
+        let result = apply_effects(&glow).unwrap();
+        let padding = (0 - result.offset.0) as u32;
+        let near = result.image.get_pixel(2 + padding, 4 + padding).0[3];
+        let far = result.image.get_pixel(padding, 4 + padding).0[3];
+        assert!(near > far && far > 0, "{} {}", near, far);
+
+        assert!(effect(EffectKind::OuterGlow { radius: 1000.0, color: [0; 4] }).validate().is_err());
+        let json = r#"{"type":"outline","width":1.5,"color":[0,0,0,255]}"#;
+        let parsed: LayerEffect = serde_json::from_str(json).unwrap();
+        assert!(parsed.enabled);
+    }
+}
@@ -0,0 +1,227 @@
+use image::{Rgba, RgbaImage};
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use super::FormatError;
+
+/// グラデーションの色の数の上限
+pub const MAX_GRADIENT_STOPS: usize = 32;
+
+/// 模様の1マスの大きさの上限（ピクセル）
+pub const MAX_PATTERN_CELL: u32 = 1024;
+
+/// 塗りつぶしレイヤーの内容（画素を持たず、合成時にパラメータから生成する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LayerFill {
+    Solid { color: [u8; 4] },
+    /// `start` から `end` へ向かう線形グラデーション（キャンバス座標）
+    LinearGradient { start: [f32; 2], end: [f32; 2], stops: Vec<GradientStop> },
+    /// `center` から半径 `radius` の円形グラデーション
+    RadialGradient { center: [f32; 2], radius: f32, stops: Vec<GradientStop> },
+    /// 組み込みの模様
+    Pattern { pattern: FillPattern, cell_size: u32, color: [u8; 4], background: [u8; 4] },
+}
+
+/// グラデーションの色（`offset` は 0〜1 の位置）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct GradientStop {
+    pub offset: f32,
+    pub color: [u8; 4],
+}
+
+/// 組み込みの模様
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FillPattern {
+    Checker,
+    /// 斜めの縞
+    Stripes,
+    /// 水玉
+    Dots,
+    /// 格子の線
+    Grid,
+}
+
+impl LayerFill {
+    pub fn validate(&self) -> Result<(), FormatError> {
+        match self {
+            LayerFill::Solid { .. } => Ok(()),
+            LayerFill::LinearGradient { start, end, stops } => {
+                if start.iter().chain(end).any(|v| !v.is_finite()) {
+                    return Err(FormatError::InvalidData("グラデーションの座標が不正です".to_string()));
+                }
+                validate_stops(stops)
+            }
+            LayerFill::RadialGradient { center, radius, stops } => {
+                if center.iter().any(|v| !v.is_finite()) || !(radius.is_finite() && *radius > 0.0) {
+                    return Err(FormatError::InvalidData("グラデーションの中心・半径が不正です".to_string()));
+                }
+                validate_stops(stops)
+            }
+            LayerFill::Pattern { cell_size, .. } => {
+                if !(1..=MAX_PATTERN_CELL).contains(cell_size) {
+                    return Err(FormatError::InvalidData(format!("模様の大きさは 1〜{} で指定してください: {}", MAX_PATTERN_CELL, cell_size)));
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// `width`x`height` の画像を生成（非乗算アルファ）
+    pub fn render(&self, width: u32, height: u32) -> RgbaImage {
+        let mut image = RgbaImage::new(width, height);
+        if width == 0 || height == 0 {
+            return image;
+        }
+        if let LayerFill::Solid { color } = self {
+            image.pixels_mut().for_each(|pixel| *pixel = Rgba(*color));
+            return image;
+        }
+        image.par_chunks_mut(width as usize * 4)
+            .enumerate()
+            .for_each(|(y, row)| {
+                for (x, pixel) in row.chunks_exact_mut(4).enumerate() {
+                    pixel.copy_from_slice(&self.sample(x as f32 + 0.5, y as f32 + 0.5));
+                }
+            });
+        image
+    }
+
+    /// 画素中心 (x, y) の色
+    fn sample(&self, x: f32, y: f32) -> [u8; 4] {
+        match self {
+            LayerFill::Solid { color } => *color,
+            LayerFill::LinearGradient { start, end, stops } => {
+                let (dx, dy) = (end[0] - start[0], end[1] - start[1]);
+                let length_squared = dx * dx + dy * dy;
+                let t = if length_squared > 0.0 {
+                    ((x - start[0]) * dx + (y - start[1]) * dy) / length_squared
+                } else {
+                    0.0
+                };
+                gradient_color(stops, t)
+            }
+            LayerFill::RadialGradient { center, radius, stops } => {
+                let distance = ((x - center[0]).powi(2) + (y - center[1]).powi(2)).sqrt();
+                gradient_color(stops, distance / radius)
+            }
+            LayerFill::Pattern { pattern, cell_size, color, background } => {
+                let cell = *cell_size as f32;
+                let coverage = match pattern {
+                    FillPattern::Checker => {
+                        let parity = (x / cell).floor() as i64 + (y / cell).floor() as i64;
+                        if parity.rem_euclid(2) == 0 { 1.0 } else { 0.0 }
+                    }
+                    FillPattern::Stripes => if ((x + y) / cell).floor() as i64 % 2 == 0 { 1.0 } else { 0.0 },
+                    FillPattern::Dots => {
+                        let (cx, cy) = ((x / cell).floor() * cell + cell / 2.0, (y / cell).floor() * cell + cell / 2.0);
+                        let distance = ((x - cx).powi(2) + (y - cy).powi(2)).sqrt();
+                        (cell * 0.3 + 0.5 - distance).clamp(0.0, 1.0)
+                    }
+                    FillPattern::Grid => {
+                        let on_line = |v: f32| v.rem_euclid(cell) < 1.0;
+                        if on_line(x) || on_line(y) { 1.0 } else { 0.0 }
+                    }
+                };
+                mix(*background, *color, coverage)
+            }
+        }
+    }
+}
+
+fn validate_stops(stops: &[GradientStop]) -> Result<(), FormatError> {
+    if stops.is_empty() || stops.len() > MAX_GRADIENT_STOPS {
+        return Err(FormatError::InvalidData(format!("グラデーションの色は 1〜{} 個で指定してください", MAX_GRADIENT_STOPS)));
+    }
+    if stops.iter().any(|stop| !(0.0..=1.0).contains(&stop.offset)) {
+        return Err(FormatError::InvalidData("グラデーションの位置は 0〜1 で指定してください".to_string()));
+    }
+    if stops.windows(2).any(|pair| pair[0].offset > pair[1].offset) {
+        return Err(FormatError::InvalidData("グラデーションの色は位置の順に並べてください".to_string()));
+    }
+    Ok(())
+}
+
+/// グラデーションの `t` の位置の色（範囲外は端の色）
+fn gradient_color(stops: &[GradientStop], t: f32) -> [u8; 4] {
+    let t = t.clamp(0.0, 1.0);
+    let next = stops.partition_point(|stop| stop.offset <= t);
+    match (next.checked_sub(1).map(|i| &stops[i]), stops.get(next)) {
+        (Some(a), Some(b)) if b.offset > a.offset => mix(a.color, b.color, (t - a.offset) / (b.offset - a.offset)),
+        (Some(stop), _) | (None, Some(stop)) => stop.color,
+        (None, None) => [0; 4],
+    }
+}
+
+/// 乗算済みアルファで2色を補間（透明な色の RGB がにじまないようにする）
+fn mix(a: [u8; 4], b: [u8; 4], t: f32) -> [u8; 4] {
+    let (alpha_a, alpha_b) = (a[3] as f32 / 255.0, b[3] as f32 / 255.0);
+    let alpha = alpha_a + (alpha_b - alpha_a) * t;
+    if alpha <= 0.0 {
+        return [0; 4];
+    }
+    let mut out = [0u8; 4];
+    for channel in 0..3 {
+        let premultiplied = a[channel] as f32 * alpha_a + (b[channel] as f32 * alpha_b - a[channel] as f32 * alpha_a) * t;
+        out[channel] = (premultiplied / alpha).round().clamp(0.0, 255.0) as u8;
+    }
+    out[3] = (alpha * 255.0).round().clamp(0.0, 255.0) as u8;
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stops() -> Vec<GradientStop> {
+        vec![
+            GradientStop { offset: 0.0, color: [255, 0, 0, 255] },
+            GradientStop { offset: 1.0, color: [0, 0, 255, 0] },
+        ]
+    }
+
+    #[test]
+    fn test_gradients_interpolate_between_stops() {
+        let linear = LayerFill::LinearGradient { start: [0.0, 0.0], end: [10.0, 0.0], stops: stops() };
+        assert!(linear.validate().is_ok());
+        let image = linear.render(10, 2);
+        assert_eq!(image.get_pixel(0, 0).0[0], 255);
+        // 透明な端に向かっても色は赤のまま薄くなる
+        let middle = image.get_pixel(4, 1).0;
+        assert_eq!(&middle[..3], &[255, 0, 0]);
+        assert_eq!(middle[3], 140);
+
+        let radial = LayerFill::RadialGradient { center: [5.0, 5.0], radius: 5.0, stops: stops() };
+        let image = radial.render(10, 10);
+        assert!(image.get_pixel(4, 4).0[3] > 200);
+        assert_eq!(image.get_pixel(0, 0).0[3], 0);
+
+        let unordered = vec![stops()[1], stops()[0]];
+        assert!(LayerFill::LinearGradient { start: [0.0; 2], end: [1.0; 2], stops: unordered }.validate().is_err());
+        assert!(LayerFill::RadialGradient { center: [0.0; 2], radius: 0.0, stops: stops() }.validate().is_err());
+    }
+
+    #[test]
+    fn test_patterns_and_solid() {
+        let solid = LayerFill::Solid { color: [1, 2, 3, 4] }.render(3, 3);
+        assert!(solid.pixels().all(|pixel| pixel.0 == [1, 2, 3, 4]));
+
+        let fill = |pattern| LayerFill::Pattern { pattern, cell_size: 4, color: [0, 0, 0, 255], background: [255, 255, 255, 255] };
+        let checker = fill(FillPattern::Checker).render(8, 8);
+        assert_eq!(checker.get_pixel(0, 0).0, [0, 0, 0, 255]);
+        assert_eq!(checker.get_pixel(4, 0).0, [255, 255, 255, 255]);
+        assert_eq!(checker.get_pixel(4, 4).0, [0, 0, 0, 255]);
+
+        let grid = fill(FillPattern::Grid).render(8, 8);
+        assert_eq!(grid.get_pixel(4, 2).0, [0, 0, 0, 255]);
+        assert_eq!(grid.get_pixel(2, 2).0, [255, 255, 255, 255]);
+
+        let dots = fill(FillPattern::Dots).render(8, 8);
+        assert!(dots.get_pixel(2, 2).0[0] < 16);
+        assert_eq!(dots.get_pixel(0, 0).0, [255, 255, 255, 255]);
+
+        assert!(LayerFill::Pattern { pattern: FillPattern::Stripes, cell_size: 0, color: [0; 4], background: [0; 4] }.validate().is_err());
+        let json = r#"{"type":"pattern","pattern":"stripes","cell_size":8,"color":[0,0,0,255],"background":[0,0,0,0]}"#;
+        assert!(serde_json::from_str::<LayerFill>(json).unwrap().validate().is_ok());
+    }
+}
@@ -0,0 +1,233 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::selection::Selection;
+use super::{blend_over, FormatError};
+
+/// 隙間閉じの幅の上限（ピクセル）
+pub const MAX_GAP_CLOSING: u32 = 32;
+
+/// 着色モードで塗りを線の下へ広げる幅（ピクセル）
+pub const LINEART_OVERLAP: usize = 1;
+
+/// 塗りつぶし（バケツ）の設定
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FloodFillOptions {
+    /// 起点と同じ色とみなす差（各チャンネルの差の最大。0 は完全一致）
+    pub tolerance: u8,
+    /// 線の隙間を閉じる幅（ピクセル。0 で無効）
+    ///
+    /// 線画の境界をこの幅だけ太らせた仮のマスクで塗る範囲を決めるため、幅の2倍までの隙間から
+    /// 塗りが漏れない。塗った範囲はそのあと同じ幅だけ広げ直し、線との間に隙間を残さない。
+    pub gap_closing: u32,
+    /// 着色モード（線画レイヤーで範囲を決め、別のレイヤーに塗る）
+    ///
+    /// 塗った範囲を線の下まで `LINEART_OVERLAP` ピクセル広げ、線のアンチエイリアスとの間に
+    /// 塗り残しが出ないようにする。
+    pub colorize: bool,
+}
+
+impl Default for FloodFillOptions {
+    fn default() -> Self {
+        Self { tolerance: 16, gap_closing: 0, colorize: false }
+    }
+}
+
+impl FloodFillOptions {
+    pub fn validate(&self) -> Result<(), FormatError> {
+        if self.gap_closing > MAX_GAP_CLOSING {
+            return Err(FormatError::InvalidData(format!("隙間閉じの幅は 0〜{} で指定してください: {}", MAX_GAP_CLOSING, self.gap_closing)));
+        }
+        Ok(())
+    }
+}
+
+/// 透明な画素は色を無視して比べる
+pub(crate) fn normalized(pixel: Rgba<u8>) -> [u8; 4] {
+    if pixel[3] == 0 { [0; 4] } else { pixel.0 }
+}
+
+/// 起点から続く同じ色の範囲を選択範囲にする（起点が画像の外、または塗れる範囲がなければ None）
+///
+/// `reference` の色で境界を決める（線画のレイヤーを参照して別のレイヤーを塗るときは線画の画像）。
+/// 隙間閉じを使うと、起点が線に近すぎる細い範囲は塗れない。
+pub fn flood_fill_selection(reference: &RgbaImage, (seed_x, seed_y): (u32, u32), options: &FloodFillOptions) -> Option<Selection> {
+    let (width, height) = reference.dimensions();
+    if seed_x >= width || seed_y >= height {
+        return None;
+    }
+    let (w, h) = (width as usize, height as usize);
+    let seed = normalized(*reference.get_pixel(seed_x, seed_y));
+    let tolerance = options.tolerance;
+    let boundary: Vec<bool> = reference.pixels()
+        .map(|pixel| {
+            let color = normalized(*pixel);
+            color.iter().zip(&seed).any(|(a, b)| a.abs_diff(*b) > tolerance)
+        })
+        .collect();
+
+    let radius = options.gap_closing as usize;
+    let walls = if radius > 0 { dilate(&boundary, w, h, radius) } else { boundary.clone() };
+    let seed_index = seed_y as usize * w + seed_x as usize;
+    if walls[seed_index] {
+        return None;
+    }
+
+    // 4近傍でつながる範囲を塗る
+    let mut filled = vec![false; w * h];
+    let mut stack = vec![seed_index];
+    filled[seed_index] = true;
+    while let Some(index) = stack.pop() {
+        let (x, y) = (index % w, index / w);
+        let neighbors = [
+            (x > 0).then(|| index - 1),
+            (x + 1 < w).then(|| index + 1),
+            (y > 0).then(|| index - w),
+            (y + 1 < h).then(|| index + w),
+        ];
+        for next in neighbors.into_iter().flatten() {
+            if !filled[next] && !walls[next] {
+                filled[next] = true;
+                stack.push(next);
+            }
+        }
+    }
+
+    // 太らせた分だけ塗りを広げ直す（元の境界は越えない）
+    if radius > 0 {
+        filled = dilate(&filled, w, h, radius).into_iter().zip(&boundary)
+            .map(|(filled, boundary)| filled && !boundary)
+            .collect();
+    }
+    if options.colorize {
+        filled = dilate(&filled, w, h, LINEART_OVERLAP);
+    }
+
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (w, h, 0, 0);
+    for (index, _) in filled.iter().enumerate().filter(|(_, filled)| **filled) {
+        let (x, y) = (index % w, index / w);
+        (min_x, min_y) = (min_x.min(x), min_y.min(y));
+        (max_x, max_y) = (max_x.max(x), max_y.max(y));
+    }
+    let (region_width, region_height) = (max_x - min_x + 1, max_y - min_y + 1);
+    let mask: Vec<u8> = (min_y..=max_y)
+        .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+        .map(|(x, y)| if filled[y * w + x] { 255 } else { 0 })
+        .collect();
+    Some(Selection {
+        x: min_x as i32,
+        y: min_y as i32,
+        width: region_width as u32,
+        height: region_height as u32,
+        mask: Some(mask),
+    })
+}
+
+/// マスクを一辺 `2 * radius + 1` の正方形で膨張させる（横・縦の順に、累積和で幅によらず O(画素数)）
+pub(crate) fn dilate(mask: &[bool], width: usize, height: usize, radius: usize) -> Vec<bool> {
+    let pass = |source: &[bool], length: usize, lines: usize, at: &dyn Fn(usize, usize) -> usize| {
+        let mut out = vec![false; source.len()];
+        let mut prefix = vec![0u32; length + 1];
+        for line in 0..lines {
+            for i in 0..length {
+                prefix[i + 1] = prefix[i] + source[at(line, i)] as u32;
+            }
+            for i in 0..length {
+                let (start, end) = (i.saturating_sub(radius), (i + radius + 1).min(length));
+                out[at(line, i)] = prefix[end] > prefix[start];
+            }
+        }
+        out
+    };
+    let horizontal = pass(mask, width, height, &|y, x| y * width + x);
+    pass(&horizontal, height, width, &|x, y| y * width + x)
+}
+
+/// 選択範囲を色で塗った画像を、外接矩形の範囲だけ切り出して返す（既存の画素の上に重ねる）
+///
+/// 画像と重ならなければ None。戻り値の位置に貼り付けるとレイヤーに塗りが反映される。
+pub fn fill_selection(target: &RgbaImage, selection: &Selection, color: [u8; 4]) -> Option<(u32, u32, RgbaImage)> {
+    let x = selection.x.max(0) as u32;
+    let y = selection.y.max(0) as u32;
+    let right = ((selection.x + selection.width as i32).max(0) as u32).min(target.width());
+    let bottom = ((selection.y + selection.height as i32).max(0) as u32).min(target.height());
+    if x >= right || y >= bottom {
+        return None;
+    }
+    let region = RgbaImage::from_fn(right - x, bottom - y, |lx, ly| {
+        let (px, py) = (x + lx, y + ly);
+        let pixel = *target.get_pixel(px, py);
+        let coverage = selection.coverage(px as i64, py as i64);
+        if coverage == 0 {
+            return pixel;
+        }
+        let alpha = (color[3] as u32 * coverage as u32 + 127) / 255;
+        blend_over(pixel, Rgba([color[0], color[1], color[2], alpha as u8]))
+    });
+    Some((x, y, region))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LINE: Rgba<u8> = Rgba([0, 0, 0, 255]);
+
+    /// 中央に 2px の隙間がある 1px の枠（20x20 の中の 2..=17）
+    fn outline_with_gap() -> RgbaImage {
+        RgbaImage::from_fn(20, 20, |x, y| {
+            let on_frame = ((x == 2 || x == 17) && (2..=17).contains(&y)) || ((y == 2 || y == 17) && (2..=17).contains(&x));
+            let gap = x == 17 && (9..=10).contains(&y);
+            if on_frame && !gap { LINE } else { Rgba([0, 0, 0, 0]) }
+        })
+    }
+
+    #[test]
+    fn test_fill_leaks_without_gap_closing() {
+        let image = outline_with_gap();
+        let selection = flood_fill_selection(&image, (8, 8), &FloodFillOptions::default()).unwrap();
+        // 隙間から外側に漏れる
+        assert_eq!(selection.coverage(0, 0), 255);
+        assert_eq!(selection.coverage(2, 5), 0);
+
+        // 起点が画像の外・線の上
+        assert!(flood_fill_selection(&image, (20, 0), &FloodFillOptions::default()).is_none());
+        let on_line = flood_fill_selection(&image, (2, 5), &FloodFillOptions::default()).unwrap();
+        assert_eq!(on_line.coverage(17, 5), 255);
+        assert_eq!(on_line.coverage(8, 8), 0);
+    }
+
+    #[test]
+    fn test_gap_closing_stops_at_near_closed_outline() {
+        let image = outline_with_gap();
+        let options = FloodFillOptions { gap_closing: 1, ..FloodFillOptions::default() };
+        let selection = flood_fill_selection(&image, (8, 8), &options).unwrap();
+        assert_eq!((selection.x, selection.y, selection.width, selection.height), (3, 3, 14, 14));
+        // 線の内側まで隙間なく塗り、隙間の外には出ない
+        assert_eq!(selection.coverage(3, 3), 255);
+        assert_eq!(selection.coverage(16, 9), 255);
+        assert_eq!(selection.coverage(17, 9), 0);
+        assert_eq!(selection.coverage(0, 0), 0);
+
+        // 塗りは既存の画素の上に重ね、範囲外の画素はそのまま
+        let mut target = RgbaImage::from_pixel(20, 20, Rgba([0, 0, 255, 255]));
+        target.put_pixel(5, 5, Rgba([0, 0, 255, 0]));
+        let (x, y, region) = fill_selection(&target, &selection, [255, 0, 0, 128]).unwrap();
+        assert_eq!((x, y, region.dimensions()), (3, 3, (14, 14)));
+        assert_eq!(region.get_pixel(2, 2).0, [255, 0, 0, 128]);
+        let over = region.get_pixel(0, 0).0;
+        assert!(over[0] > 120 && over[2] > 120 && over[3] == 255);
+        let small = Selection { mask: Some(vec![0, 255]), ..Selection::rect(0, 0, 2, 1) };
+        let (_, _, region) = fill_selection(&target, &small, [255, 0, 0, 255]).unwrap();
+        assert_eq!(region.get_pixel(0, 0).0, [0, 0, 255, 255]);
+
+        // 着色モードでは線の下まで広げる
+        let colorize = FloodFillOptions { colorize: true, ..options };
+        let selection = flood_fill_selection(&image, (8, 8), &colorize).unwrap();
+        assert_eq!((selection.x, selection.y, selection.width, selection.height), (2, 2, 16, 16));
+        assert_eq!(selection.coverage(2, 5), 255);
+        assert_eq!(selection.coverage(18, 9), 0);
+
+        assert!(FloodFillOptions { gap_closing: MAX_GAP_CLOSING + 1, ..options }.validate().is_err());
+    }
+}
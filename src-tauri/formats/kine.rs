@@ -0,0 +1,1134 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::RgbaImage;
+use log::{info, debug, warn};
+use serde::Deserialize;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::io::{Read, Seek, SeekFrom, Write};
+use crate::animation::Project;
+use crate::history::{self, Operation, OperationLog};
+use super::migration::{self, PROJECT_MIGRATIONS};
+use super::{lz4, FormatError, MAX_IMPORT_DIMENSION};
+
+/// .kine ファイルの先頭に置く識別子
+pub const KINE_MAGIC: &[u8; 4] = b"KINE";
+
+/// .kine コンテナのバージョン
+///
+/// v2: プロジェクト情報から操作ログを除き、末尾に CRC32 を付加
+/// v3: レイヤー画像を独立したチャンクとし、末尾の索引から参照する（変更したチャンクだけを追記できる）
+/// v4: レイヤーマスクの画像を各レイヤーの直後に格納する（`Layer::texture_ids` の順）
+/// v5: プロジェクト情報に操作ログを含める（開き直してもアンドゥ・リドゥできる）
+/// v6: 操作ログをプロジェクト情報から独立した LZ4 圧縮のチャンクに移し、レイヤー全体の貼り付けは
+///     PNG の代わりにそのレイヤーのチャンクを参照する
+pub const KINE_VERSION: u16 = 6;
+
+/// 末尾の CRC32 を付加するようになったバージョン
+const CHECKSUM_SINCE_VERSION: u16 = 2;
+
+/// 索引とチャンクで構成するようになったバージョン
+const INDEXED_SINCE_VERSION: u16 = 3;
+
+/// 操作ログを独立したチャンクに格納するようになったバージョン
+const HISTORY_CHUNK_SINCE_VERSION: u16 = 6;
+
+/// 操作ログのチャンクで、PNG の代わりにレイヤーのチャンクを参照する貼り付け操作に付ける印
+const LAYER_CHUNK_KEY: &str = "layer_chunk";
+
+/// 索引の位置を示す末尾の識別子
+const FOOTER_MAGIC: &[u8; 4] = b"KEND";
+
+/// 末尾の長さ（索引の位置 u64・長さ u32・CRC32 u32・識別子）
+const FOOTER_LEN: u64 = 20;
+
+/// ファイル先頭の識別子とバージョンの長さ
+const PREAMBLE_LEN: u64 = 6;
+
+/// 差分の元がないことを示すチャンク番号
+const NO_BASE: u32 = u32::MAX;
+
+/// キーフレーム（差分を使わず単独で展開できるフレーム）の既定の間隔
+pub const DEFAULT_KEYFRAME_INTERVAL: u32 = 12;
+
+/// レイヤー画像の格納方式
+const CHUNK_KEYFRAME: u8 = 0;
+const CHUNK_DELTA: u8 = 1;
+/// .kine 書き出しオプション
+#[derive(Debug, Clone)]
+pub struct KineWriteOptions {
+    /// この間隔ごとに全レイヤーをキーフレームとして格納する（1 なら差分を使わない）
+    pub keyframe_interval: u32,
+}
+
+impl Default for KineWriteOptions {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: DEFAULT_KEYFRAME_INTERVAL,
+        }
+    }
+}
+
+/// 読み込んだ .kine ファイルの内容
+#[derive(Debug, Clone)]
+pub struct KineDocument {
+    pub project: Project,
+    /// フレームごとのレイヤーとマスクの画像（`project.frames[i].texture_ids()` の順）
+    pub frames: Vec<Vec<RgbaImage>>,
+}
+
+/// 直前のフレームとの差分（バイトごとの XOR。変化のない部分は 0 になり LZ4 でよく縮む）
+pub fn encode_delta(previous: &[u8], current: &[u8]) -> Vec<u8> {
+    previous.iter().zip(current).map(|(a, b)| a ^ b).collect()
+}
+
+/// 差分を直前のフレームに適用して元の画像データに戻す
+pub fn apply_delta(previous: &[u8], delta: &mut [u8]) {
+    for (byte, base) in delta.iter_mut().zip(previous) {
+        *byte ^= base;
+    }
+}
+
+/// 索引に記録するレイヤー画像のチャンク
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KineChunk {
+    kind: u8,
+    pub width: u32,
+    pub height: u32,
+    /// 差分の元になるチャンクの番号（常にこのチャンクより前）
+    base: Option<u32>,
+    /// ファイル先頭からの位置
+    pub offset: u64,
+    pub len: u32,
+    crc: u32,
+}
+
+/// 索引に記録する操作ログのチャンク（LZ4 で圧縮した JSON）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct HistoryChunk {
+    offset: u64,
+    len: u32,
+    /// 展開後の長さ
+    raw_len: u32,
+    crc: u32,
+}
+
+/// .kine ファイル（v3 以降）の索引
+///
+/// 画像を展開せずに読めるため、追記保存ではこれを元に変更のないチャンクを使い回す。
+#[derive(Debug, Clone)]
+pub struct KineIndex {
+    /// ファイルのバージョン（現在のバージョンでなければ追記できない）
+    pub version: u16,
+    /// プロジェクト情報（v6 以降の操作ログは別のチャンクにあり、`decode_kine` で読む）
+    pub project: Project,
+    pub keyframe_interval: u32,
+    pub chunks: Vec<KineChunk>,
+    /// フレーム順・レイヤー順に並べた各レイヤーのチャンク番号
+    layer_chunks: Vec<u32>,
+    /// レイヤーID → チャンク番号
+    chunk_by_layer: HashMap<String, u32>,
+    /// 操作ログのチャンク（空のログは書かない）
+    history: Option<HistoryChunk>,
+    /// 索引の長さ
+    index_len: u32,
+    /// 索引を読んだ時点のファイルの長さ
+    pub file_len: u64,
+}
+
+impl KineIndex {
+    /// レイヤーIDから格納済みのチャンク番号を引く
+    pub fn layer_chunk(&self, layer_id: &str) -> Option<u32> {
+        self.chunk_by_layer.get(layer_id).copied()
+    }
+
+    /// 現在の索引から参照されているデータの量（バイト）
+    pub fn live_bytes(&self) -> u64 {
+        PREAMBLE_LEN + self.chunks.iter().map(|chunk| chunk.len as u64).sum::<u64>()
+            + self.history.map_or(0, |history| history.len as u64) + self.index_len as u64 + FOOTER_LEN
+    }
+
+    /// 追記で使われなくなった領域が有効なデータより大きければ、全体を書き直すべき
+    pub fn should_compact(&self) -> bool {
+        self.file_len.saturating_sub(self.live_bytes()) > self.live_bytes()
+    }
+}
+
+/// 追記保存の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KineAppendStats {
+    /// 新たに書き込んだチャンク数
+    pub chunks_written: usize,
+    /// 書き込んだチャンクを含むフレーム数
+    pub frames_rewritten: usize,
+    /// 追記したバイト数（索引を含む）
+    pub bytes_written: u64,
+    /// 追記後のファイルの長さ
+    pub file_len: u64,
+}
+
+/// プロジェクトとフレームごとのレイヤー画像を .kine 形式にエンコード
+///
+/// レイヤー画像は直前のフレームの同じ位置のレイヤーとの差分を LZ4 で圧縮したチャンクとして格納する。
+/// `keyframe_interval` フレームごと、およびサイズが変わったレイヤーは差分を使わない。
+/// 操作ログはレイヤー画像の後に独立したチャンクとして格納し、読み込み時にアンドゥ・リドゥの履歴として復元する。
+/// チャンクの後にプロジェクト情報とチャンクの一覧（索引）を置き、末尾に索引の位置と CRC32 を書く。
+pub fn encode_kine(
+    project: &Project,
+    frames: &[Vec<RgbaImage>],
+    options: &KineWriteOptions,
+) -> Result<Vec<u8>, FormatError> {
+    if frames.len() != project.frames.len() {
+        return Err(FormatError::InvalidData(format!(
+            "フレーム数が一致しません: {} != {}", frames.len(), project.frames.len()
+        )));
+    }
+    for (index, (frame, images)) in project.frames.iter().zip(frames).enumerate() {
+        let texture_count = frame.texture_ids().count();
+        if texture_count != images.len() {
+            return Err(FormatError::InvalidData(format!(
+                "フレーム {} のレイヤー数が一致しません: {} != {}", index, images.len(), texture_count
+            )));
+        }
+    }
+    let keyframe_interval = options.keyframe_interval.max(1);
+    let header = project_header(project)?;
+
+    let mut out = Vec::new();
+    out.extend_from_slice(KINE_MAGIC);
+    out.extend_from_slice(&KINE_VERSION.to_le_bytes());
+
+    let mut chunks = Vec::new();
+    let mut layer_chunks = Vec::new();
+    // 直前のフレームの最初のレイヤーのチャンク番号の位置
+    let mut previous_start = 0;
+    let mut raw_bytes = 0usize;
+    let mut delta_chunks = 0usize;
+    for (frame_index, images) in frames.iter().enumerate() {
+        let is_keyframe = (frame_index as u32).is_multiple_of(keyframe_interval);
+        let frame_start = layer_chunks.len();
+        for (layer_index, image) in images.iter().enumerate() {
+            let previous = frame_index.checked_sub(1)
+                .and_then(|prev| frames[prev].get(layer_index))
+                .filter(|prev| !is_keyframe && prev.dimensions() == image.dimensions());
+
+            let (payload, base) = match previous {
+                Some(previous) => {
+                    delta_chunks += 1;
+                    (lz4::compress(&encode_delta(previous.as_raw(), image.as_raw())), Some(layer_chunks[previous_start + layer_index]))
+                }
+                None => (lz4::compress(image.as_raw()), None),
+            };
+            layer_chunks.push(chunks.len() as u32);
+            chunks.push(write_chunk(&mut out, image, base, &payload));
+            raw_bytes += image.as_raw().len();
+        }
+        previous_start = frame_start;
+    }
+    let layer_sizes = project.frames.iter()
+        .flat_map(|frame| frame.texture_ids())
+        .zip(frames.iter().flatten().map(|image| image.dimensions()))
+        .collect();
+    let history = write_history(&mut out, &project.history, &layer_sizes)?;
+    write_index(&mut out, &header, keyframe_interval, &chunks, &layer_chunks, history.as_ref());
+
+    info!("[Kine] 書き出し完了: {} フレーム, 差分 {} 件, {} -> {} bytes",
+          frames.len(), delta_chunks, raw_bytes, out.len());
+    Ok(out)
+}
+
+/// 保存済みの .kine ファイルの末尾に、変更したレイヤーのチャンクと新しい索引を追記する
+///
+/// `changed` に含まれるレイヤー（レイヤーID → 画像）だけをキーフレームとして書き込み、
+/// それ以外は `index` の既存チャンクを参照する。新しい末尾を書き終えるまで既存の内容には触れないため、
+/// 途中で失敗した場合は呼び出し側でファイルを `index.file_len` に切り詰めれば元に戻る。
+/// 操作ログは毎回新しいチャンクとして書き込む。古いバージョンのファイルには追記できない。
+pub fn append_kine<W: Write + Seek>(
+    writer: &mut W,
+    index: &KineIndex,
+    project: &Project,
+    changed: &HashMap<String, RgbaImage>,
+) -> Result<KineAppendStats, FormatError> {
+    if index.version != KINE_VERSION {
+        return Err(FormatError::Unsupported(format!("v{} のファイルには追記できません", index.version)));
+    }
+    let file_len = writer.seek(SeekFrom::End(0))?;
+    if file_len != index.file_len {
+        return Err(FormatError::InvalidData(format!(
+            "索引を読んだ後にファイルが変更されています: {} != {} bytes", file_len, index.file_len
+        )));
+    }
+    let header = project_header(project)?;
+
+    // 追記する内容はファイル上の位置から始まる
+    let mut out = Vec::new();
+    let mut chunks = index.chunks.clone();
+    let mut written: HashMap<&str, u32> = HashMap::new();
+    let mut layer_chunks = Vec::new();
+    let mut frames_rewritten = 0;
+    for frame in &project.frames {
+        let mut rewritten = false;
+        for layer_id in frame.texture_ids() {
+            let chunk = match changed.get(layer_id) {
+                Some(image) => {
+                    rewritten = true;
+                    match written.get(layer_id) {
+                        Some(chunk) => *chunk,
+                        None => {
+                            let payload = lz4::compress(image.as_raw());
+                            let mut chunk = write_chunk(&mut out, image, None, &payload);
+                            chunk.offset += file_len;
+                            chunks.push(chunk);
+                            written.insert(layer_id, chunks.len() as u32 - 1);
+                            chunks.len() as u32 - 1
+                        }
+                    }
+                }
+                None => index.layer_chunk(layer_id).ok_or_else(|| FormatError::InvalidData(format!(
+                    "保存されていないレイヤーの画像がありません: {}", layer_id
+                )))?,
+            };
+            layer_chunks.push(chunk);
+        }
+        if rewritten {
+            frames_rewritten += 1;
+        }
+    }
+
+    // 参照されなくなったチャンクを索引から外す（差分の元として使われているものは残す）
+    let mut reachable = vec![false; chunks.len()];
+    for &chunk in &layer_chunks {
+        let mut next = Some(chunk);
+        while let Some(id) = next.filter(|id| !reachable[*id as usize]) {
+            reachable[id as usize] = true;
+            next = chunks[id as usize].base;
+        }
+    }
+    let mut remap = vec![NO_BASE; chunks.len()];
+    let mut live_chunks = Vec::new();
+    for (id, chunk) in chunks.iter().enumerate().filter(|(id, _)| reachable[*id]) {
+        remap[id] = live_chunks.len() as u32;
+        live_chunks.push(KineChunk { base: chunk.base.map(|base| remap[base as usize]), ..*chunk });
+    }
+    let layer_chunks: Vec<u32> = layer_chunks.iter().map(|chunk| remap[*chunk as usize]).collect();
+
+    let layer_sizes = project.frames.iter()
+        .flat_map(|frame| frame.texture_ids())
+        .zip(layer_chunks.iter().map(|chunk| (live_chunks[*chunk as usize].width, live_chunks[*chunk as usize].height)))
+        .collect();
+    let mut history = write_history(&mut out, &project.history, &layer_sizes)?;
+    if let Some(history) = history.as_mut() {
+        history.offset += file_len;
+    }
+
+    let index_offset = file_len + out.len() as u64;
+    let index_bytes = encode_index(&header, index.keyframe_interval, &live_chunks, &layer_chunks, history.as_ref());
+    out.extend_from_slice(&index_bytes);
+    write_footer(&mut out, index_offset, &index_bytes);
+
+    writer.write_all(&out)?;
+    writer.flush()?;
+
+    let stats = KineAppendStats {
+        chunks_written: written.len(),
+        frames_rewritten,
+        bytes_written: out.len() as u64,
+        file_len: file_len + out.len() as u64,
+    };
+    info!("[Kine] 追記保存: {} チャンク / {} フレームを書き込み, {} bytes 追記 (不要になったチャンク {} 件)",
+          stats.chunks_written, stats.frames_rewritten, stats.bytes_written, chunks.len() - live_chunks.len());
+    Ok(stats)
+}
+
+/// .kine ファイル（v3 以降）の索引だけを読む
+///
+/// v2 以前のファイルは索引を持たないため `FormatError::Unsupported` になる。
+pub fn read_kine_index<R: Read + Seek>(reader: &mut R) -> Result<KineIndex, FormatError> {
+    let file_len = reader.seek(SeekFrom::End(0))?;
+    reader.seek(SeekFrom::Start(0))?;
+    let mut preamble = [0u8; PREAMBLE_LEN as usize];
+    reader.read_exact(&mut preamble).map_err(|_| truncated())?;
+    let version = read_preamble(&preamble)?;
+    if version < INDEXED_SINCE_VERSION {
+        return Err(FormatError::Unsupported(format!("v{} のファイルには索引がありません", version)));
+    }
+
+    let footer_start = file_len.checked_sub(FOOTER_LEN).filter(|start| *start >= PREAMBLE_LEN).ok_or_else(truncated)?;
+    reader.seek(SeekFrom::Start(footer_start))?;
+    let mut footer = [0u8; FOOTER_LEN as usize];
+    reader.read_exact(&mut footer)?;
+    if &footer[16..] != FOOTER_MAGIC {
+        return Err(truncated());
+    }
+    let index_offset = u64::from_le_bytes(footer[..8].try_into().unwrap());
+    let index_len = u32::from_le_bytes(footer[8..12].try_into().unwrap());
+    let expected = u32::from_le_bytes(footer[12..16].try_into().unwrap());
+    if index_offset < PREAMBLE_LEN || index_offset.checked_add(index_len as u64) != Some(footer_start) {
+        return Err(FormatError::InvalidData("索引の位置が不正です".to_string()));
+    }
+
+    reader.seek(SeekFrom::Start(index_offset))?;
+    let mut index_bytes = vec![0u8; index_len as usize];
+    reader.read_exact(&mut index_bytes)?;
+    let actual = crc32fast::hash(&index_bytes);
+    if actual != expected {
+        return Err(FormatError::ChecksumMismatch { expected, actual });
+    }
+
+    let mut reader = Reader { data: &index_bytes, pos: 0 };
+    let project = read_project_header(&mut reader, version)?;
+    let keyframe_interval = reader.read_u32()?;
+    let chunk_count = reader.read_u32()? as usize;
+    let mut chunks = Vec::with_capacity(chunk_count.min(index_bytes.len()));
+    for id in 0..chunk_count {
+        let kind = reader.take(1)?[0];
+        let width = reader.read_u32()?;
+        let height = reader.read_u32()?;
+        if width > MAX_IMPORT_DIMENSION || height > MAX_IMPORT_DIMENSION {
+            return Err(FormatError::DimensionsTooLarge(width, height));
+        }
+        let base = Some(reader.read_u32()?).filter(|base| *base != NO_BASE);
+        let offset = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let len = reader.read_u32()?;
+        let crc = reader.read_u32()?;
+
+        match (kind, base) {
+            (CHUNK_KEYFRAME, None) => {}
+            (CHUNK_DELTA, Some(base)) if (base as usize) < id && chunks.get(base as usize)
+                .is_some_and(|base: &KineChunk| (base.width, base.height) == (width, height)) => {}
+            (CHUNK_DELTA, _) => {
+                return Err(FormatError::InvalidData(format!("差分の元になるチャンクが不正です: {}", id)));
+            }
+            (other, _) => {
+                return Err(FormatError::InvalidData(format!("不明なレイヤー格納方式: {}", other)));
+            }
+        }
+        if offset < PREAMBLE_LEN || offset.checked_add(len as u64).is_none_or(|end| end > index_offset) {
+            return Err(FormatError::InvalidData(format!("チャンクの位置が不正です: {}", id)));
+        }
+        chunks.push(KineChunk { kind, width, height, base, offset, len, crc });
+    }
+
+    let layer_count: usize = project.frames.iter().map(|frame| frame.texture_ids().count()).sum();
+    let mut layer_chunks = Vec::with_capacity(layer_count);
+    for _ in 0..layer_count {
+        let chunk = reader.read_u32()?;
+        if chunk as usize >= chunks.len() {
+            return Err(FormatError::InvalidData(format!("存在しないチャンクを参照しています: {}", chunk)));
+        }
+        layer_chunks.push(chunk);
+    }
+    let history = if version >= HISTORY_CHUNK_SINCE_VERSION && reader.take(1)?[0] != 0 {
+        let offset = u64::from_le_bytes(reader.take(8)?.try_into().unwrap());
+        let len = reader.read_u32()?;
+        let raw_len = reader.read_u32()?;
+        let crc = reader.read_u32()?;
+        if offset < PREAMBLE_LEN || offset.checked_add(len as u64).is_none_or(|end| end > index_offset) {
+            return Err(FormatError::InvalidData("操作ログのチャンクの位置が不正です".to_string()));
+        }
+        Some(HistoryChunk { offset, len, raw_len, crc })
+    } else {
+        None
+    };
+    if reader.pos != index_bytes.len() {
+        return Err(FormatError::InvalidData(format!("索引の末尾に余分なデータがあります: {} bytes", index_bytes.len() - reader.pos)));
+    }
+
+    debug!("[Kine] 索引読み込み: {} フレーム, {} チャンク (キーフレーム間隔 {})",
+           project.frames.len(), chunks.len(), keyframe_interval);
+    let chunk_by_layer = project.frames.iter()
+        .flat_map(|frame| frame.texture_ids())
+        .zip(&layer_chunks)
+        .map(|(layer_id, chunk)| (layer_id.to_string(), *chunk))
+        .collect();
+    Ok(KineIndex { version, project, keyframe_interval, chunks, layer_chunks, chunk_by_layer, history, index_len, file_len })
+}
+
+/// .kine ファイルをデコード
+///
+/// 古いバージョンのファイルは `migration::PROJECT_MIGRATIONS` を順に適用して読み込む。
+/// 新しすぎるバージョンは `FormatError::UnsupportedVersion`、破損は `FormatError::ChecksumMismatch` になる。
+/// 操作ログのチャンクだけが読めない場合は、レイヤー画像を優先して空の操作ログで開く。
+pub fn decode_kine(data: &[u8]) -> Result<KineDocument, FormatError> {
+    let preamble = data.get(..PREAMBLE_LEN as usize).ok_or_else(truncated)?;
+    let version = read_preamble(preamble)?;
+    if version < INDEXED_SINCE_VERSION {
+        return decode_sequential(data, version);
+    }
+
+    let index = read_kine_index(&mut std::io::Cursor::new(data))?;
+    let mut images: Vec<Option<RgbaImage>> = Vec::with_capacity(index.chunks.len());
+    for (id, chunk) in index.chunks.iter().enumerate() {
+        let payload = &data[chunk.offset as usize..(chunk.offset + chunk.len as u64) as usize];
+        let actual = crc32fast::hash(payload);
+        if actual != chunk.crc {
+            return Err(FormatError::ChecksumMismatch { expected: chunk.crc, actual });
+        }
+        let mut raw = lz4::decompress(payload, chunk.width as usize * chunk.height as usize * 4)?;
+        if let Some(base) = chunk.base {
+            let base = images[base as usize].as_ref()
+                .ok_or_else(|| FormatError::InvalidData(format!("差分の元になるチャンクがありません: {}", id)))?;
+            apply_delta(base.as_raw(), &mut raw);
+        }
+        let image = RgbaImage::from_raw(chunk.width, chunk.height, raw)
+            .ok_or_else(|| FormatError::InvalidData("レイヤー画像のサイズが不正です".to_string()))?;
+        images.push(Some(image));
+    }
+
+    // 最後に参照するレイヤーには複製せずに渡す
+    let mut remaining = vec![0usize; images.len()];
+    for chunk in &index.layer_chunks {
+        remaining[*chunk as usize] += 1;
+    }
+    let mut layer_chunks = index.layer_chunks.iter();
+    let mut frames = Vec::with_capacity(index.project.frames.len());
+    for frame in &index.project.frames {
+        let texture_count = frame.texture_ids().count();
+        let mut layers = Vec::with_capacity(texture_count);
+        for chunk in layer_chunks.by_ref().take(texture_count) {
+            let id = *chunk as usize;
+            remaining[id] -= 1;
+            let image = if remaining[id] == 0 { images[id].take() } else { images[id].clone() };
+            layers.push(image.ok_or_else(|| FormatError::InvalidData(format!("チャンクを展開できません: {}", id)))?);
+        }
+        frames.push(layers);
+    }
+
+    let mut project = index.project;
+    if let Some(chunk) = &index.history {
+        match read_history(data, chunk, &project, &frames) {
+            Ok(log) => project.history = log,
+            Err(e) => warn!("[Kine] 操作ログを読めないため、履歴なしで開きます: {}", e),
+        }
+    }
+    Ok(KineDocument { project, frames })
+}
+
+/// v2 以前の、チャンクを順に並べた形式をデコード
+fn decode_sequential(data: &[u8], version: u16) -> Result<KineDocument, FormatError> {
+    let mut reader = Reader { data, pos: PREAMBLE_LEN as usize };
+
+    // 内容を解釈する前に破損を検出する
+    let body_len = if version >= CHECKSUM_SINCE_VERSION {
+        let body_len = data.len().checked_sub(4)
+            .filter(|len| *len >= reader.pos)
+            .ok_or_else(truncated)?;
+        let expected = u32::from_le_bytes(data[body_len..].try_into().unwrap());
+        let actual = crc32fast::hash(&data[..body_len]);
+        if actual != expected {
+            return Err(FormatError::ChecksumMismatch { expected, actual });
+        }
+        body_len
+    } else {
+        data.len()
+    };
+    reader.data = &data[..body_len];
+
+    let project = read_project_header(&mut reader, version)?;
+    let keyframe_interval = reader.read_u32()?;
+    debug!("[Kine] 読み込み: {} フレーム (キーフレーム間隔 {})", project.frames.len(), keyframe_interval);
+
+    let mut frames: Vec<Vec<RgbaImage>> = Vec::with_capacity(project.frames.len());
+    for (frame_index, frame) in project.frames.iter().enumerate() {
+        let texture_count = frame.texture_ids().count();
+        let mut images = Vec::with_capacity(texture_count);
+        for layer_index in 0..texture_count {
+            let kind = reader.take(1)?[0];
+            let width = reader.read_u32()?;
+            let height = reader.read_u32()?;
+            if width > MAX_IMPORT_DIMENSION || height > MAX_IMPORT_DIMENSION {
+                return Err(FormatError::DimensionsTooLarge(width, height));
+            }
+            let payload_len = reader.read_u32()? as usize;
+            let payload = reader.take(payload_len)?;
+            let mut raw = lz4::decompress(payload, width as usize * height as usize * 4)?;
+
+            match kind {
+                CHUNK_KEYFRAME => {}
+                CHUNK_DELTA => {
+                    let previous = frame_index.checked_sub(1)
+                        .and_then(|prev| frames[prev].get(layer_index))
+                        .filter(|prev| prev.dimensions() == (width, height))
+                        .ok_or_else(|| FormatError::InvalidData(format!(
+                            "差分の元になるレイヤーがありません: フレーム {} レイヤー {}", frame_index, layer_index
+                        )))?;
+                    apply_delta(previous.as_raw(), &mut raw);
+                }
+                other => {
+                    return Err(FormatError::InvalidData(format!("不明なレイヤー格納方式: {}", other)));
+                }
+            }
+            let image = RgbaImage::from_raw(width, height, raw)
+                .ok_or_else(|| FormatError::InvalidData("レイヤー画像のサイズが不正です".to_string()))?;
+            images.push(image);
+        }
+        frames.push(images);
+    }
+
+    if reader.pos != body_len {
+        return Err(FormatError::InvalidData(format!("末尾に余分なデータがあります: {} bytes", body_len - reader.pos)));
+    }
+    Ok(KineDocument { project, frames })
+}
+
+/// 先頭の識別子とバージョンを確認
+fn read_preamble(preamble: &[u8]) -> Result<u16, FormatError> {
+    if &preamble[..4] != KINE_MAGIC {
+        return Err(FormatError::InvalidData("Kinegraph プロジェクトファイルではありません".to_string()));
+    }
+    let version = u16::from_le_bytes(preamble[4..6].try_into().unwrap());
+    if version == 0 || version > KINE_VERSION {
+        return Err(FormatError::UnsupportedVersion(version as u32));
+    }
+    Ok(version)
+}
+
+/// 保存するプロジェクト情報（JSON。操作ログは独立したチャンクに格納するため含めない）
+fn project_header(project: &Project) -> Result<Vec<u8>, FormatError> {
+    let mut header = serde_json::to_value(project)
+        .map_err(|e| FormatError::InvalidData(format!("プロジェクト情報のシリアライズに失敗: {}", e)))?;
+    if let Some(header) = header.as_object_mut() {
+        header.remove("history");
+    }
+    serde_json::to_vec(&header)
+        .map_err(|e| FormatError::InvalidData(format!("プロジェクト情報のシリアライズに失敗: {}", e)))
+}
+
+/// 操作ログを LZ4 で圧縮したチャンクとして書き込み、`out` の先頭からの位置を記録したチャンク情報を返す
+///
+/// `layer_sizes` は保存するレイヤーのチャンクのサイズ（レイヤーID → 幅・高さ）。
+/// 貼り付けた画像がそのまま保存するレイヤーの画像になっている操作は、PNG を省いてそのチャンクを参照する。
+fn write_history(
+    out: &mut Vec<u8>,
+    log: &OperationLog,
+    layer_sizes: &HashMap<&str, (u32, u32)>,
+) -> Result<Option<HistoryChunk>, FormatError> {
+    if log.is_empty() {
+        return Ok(None);
+    }
+    let mut value = serde_json::to_value(log)
+        .map_err(|e| FormatError::InvalidData(format!("操作ログのシリアライズに失敗: {}", e)))?;
+    let referenced = layer_chunk_pastes(log, layer_sizes);
+    if let Some(entries) = value.get_mut("entries").and_then(Value::as_array_mut) {
+        for index in &referenced {
+            if let Some(operation) = entries.get_mut(*index)
+                .and_then(|entry| entry.get_mut("operation"))
+                .and_then(Value::as_object_mut)
+            {
+                operation.remove("png_base64");
+                operation.insert(LAYER_CHUNK_KEY.to_string(), Value::Bool(true));
+            }
+        }
+    }
+    let raw = serde_json::to_vec(&value)
+        .map_err(|e| FormatError::InvalidData(format!("操作ログのシリアライズに失敗: {}", e)))?;
+    let raw_len = u32::try_from(raw.len())
+        .map_err(|_| FormatError::InvalidData(format!("操作ログが大きすぎます: {} bytes", raw.len())))?;
+
+    let payload = lz4::compress(&raw);
+    let chunk = HistoryChunk {
+        offset: out.len() as u64,
+        len: payload.len() as u32,
+        raw_len,
+        crc: crc32fast::hash(&payload),
+    };
+    out.extend_from_slice(&payload);
+    debug!("[Kine] 操作ログ: {} 件 (レイヤーのチャンクを参照する貼り付け {} 件), {} -> {} bytes",
+           log.len(), referenced.len(), raw.len(), payload.len());
+    Ok(Some(chunk))
+}
+
+/// PNG の代わりにレイヤーのチャンクを参照できる貼り付け操作のエントリ番号
+///
+/// 適用済みの操作のうち、レイヤーの左上からレイヤーと同じ大きさの画像を貼り付け、その後にそのレイヤーにも
+/// キャンバス全体にも操作していないもの（貼り付けた画像が保存するレイヤーの画像そのもの）を選ぶ。
+fn layer_chunk_pastes(log: &OperationLog, layer_sizes: &HashMap<&str, (u32, u32)>) -> Vec<usize> {
+    // レイヤーごとの最後の操作（キャンバス全体への操作より後のものだけ）
+    let mut last: HashMap<&str, usize> = HashMap::new();
+    for (index, entry) in log.applied_entries().iter().enumerate() {
+        match entry.operation.layer_id() {
+            Some(layer_id) => {
+                last.insert(layer_id, index);
+            }
+            None => last.clear(),
+        }
+    }
+
+    let mut referenced: Vec<usize> = last.into_values()
+        .filter(|&index| match &log.entries()[index].operation {
+            Operation::PasteImage { layer_id, x: 0, y: 0, png_base64 } => png_dimensions(png_base64)
+                .is_some_and(|size| layer_sizes.get(layer_id.as_str()) == Some(&size)),
+            _ => false,
+        })
+        .collect();
+    referenced.sort_unstable();
+    referenced
+}
+
+/// Base64 の PNG の幅と高さ（画像全体は展開せず IHDR だけを読む）
+fn png_dimensions(png_base64: &str) -> Option<(u32, u32)> {
+    // シグネチャ 8 バイト + IHDR の長さ・種類 8 バイト + 幅・高さ 8 バイト
+    let head = STANDARD.decode(png_base64.get(..32)?).ok()?;
+    if head.get(..8)? != b"\x89PNG\r\n\x1a\n" || head.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    let width = u32::from_be_bytes(head.get(16..20)?.try_into().ok()?);
+    let height = u32::from_be_bytes(head.get(20..24)?.try_into().ok()?);
+    Some((width, height))
+}
+
+/// 操作ログのチャンクを展開し、レイヤーのチャンクを参照する貼り付け操作に PNG を戻す
+///
+/// `frames` は `decode_kine` で展開したレイヤー画像（`project.frames[i].texture_ids()` の順）。
+fn read_history(
+    data: &[u8],
+    chunk: &HistoryChunk,
+    project: &Project,
+    frames: &[Vec<RgbaImage>],
+) -> Result<OperationLog, FormatError> {
+    let payload = data.get(chunk.offset as usize..(chunk.offset + chunk.len as u64) as usize).ok_or_else(truncated)?;
+    let actual = crc32fast::hash(payload);
+    if actual != chunk.crc {
+        return Err(FormatError::ChecksumMismatch { expected: chunk.crc, actual });
+    }
+    let raw = lz4::decompress(payload, chunk.raw_len as usize)?;
+    let mut value: Value = serde_json::from_slice(&raw)
+        .map_err(|e| FormatError::InvalidData(format!("操作ログが不正です: {}", e)))?;
+
+    let images: HashMap<&str, &RgbaImage> = project.frames.iter()
+        .flat_map(|frame| frame.texture_ids())
+        .zip(frames.iter().flatten())
+        .collect();
+    let operations = value.get_mut("entries").and_then(Value::as_array_mut).into_iter().flatten()
+        .filter_map(|entry| entry.get_mut("operation"))
+        .filter_map(Value::as_object_mut);
+    for operation in operations {
+        if operation.remove(LAYER_CHUNK_KEY).is_none() {
+            continue;
+        }
+        let layer_id = operation.get("layer_id").and_then(Value::as_str).unwrap_or_default();
+        let image = images.get(layer_id).ok_or_else(|| FormatError::InvalidData(format!(
+            "操作ログが参照するレイヤーの画像がありません: {}", layer_id
+        )))?;
+        let png_base64 = history::encode_png_base64(image).map_err(FormatError::InvalidData)?;
+        operation.insert("png_base64".to_string(), Value::String(png_base64));
+    }
+
+    OperationLog::deserialize(value)
+        .map_err(|e| FormatError::InvalidData(format!("操作ログが不正です: {}", e)))
+}
+
+/// プロジェクト情報を読み、現在のバージョンまで移行する
+fn read_project_header(reader: &mut Reader, version: u16) -> Result<Project, FormatError> {
+    let header_len = reader.read_u32()? as usize;
+    let mut header: serde_json::Value = serde_json::from_slice(reader.take(header_len)?)
+        .map_err(|e| FormatError::InvalidData(format!("プロジェクト情報が不正です: {}", e)))?;
+    migration::migrate(&mut header, version, KINE_VERSION, PROJECT_MIGRATIONS)?;
+    serde_json::from_value(header)
+        .map_err(|e| FormatError::InvalidData(format!("プロジェクト情報が不正です: {}", e)))
+}
+
+/// 圧縮済みのチャンクを書き込み、`out` の先頭からの位置を記録したチャンク情報を返す
+fn write_chunk(out: &mut Vec<u8>, image: &RgbaImage, base: Option<u32>, payload: &[u8]) -> KineChunk {
+    let chunk = KineChunk {
+        kind: if base.is_some() { CHUNK_DELTA } else { CHUNK_KEYFRAME },
+        width: image.width(),
+        height: image.height(),
+        base,
+        offset: out.len() as u64,
+        len: payload.len() as u32,
+        crc: crc32fast::hash(payload),
+    };
+    out.extend_from_slice(payload);
+    chunk
+}
+
+/// 索引（プロジェクト情報・チャンク一覧・レイヤーごとのチャンク番号・操作ログのチャンク）をエンコード
+fn encode_index(
+    header: &[u8],
+    keyframe_interval: u32,
+    chunks: &[KineChunk],
+    layer_chunks: &[u32],
+    history: Option<&HistoryChunk>,
+) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_u32(&mut out, header.len() as u32);
+    out.extend_from_slice(header);
+    write_u32(&mut out, keyframe_interval);
+    write_u32(&mut out, chunks.len() as u32);
+    for chunk in chunks {
+        out.push(chunk.kind);
+        write_u32(&mut out, chunk.width);
+        write_u32(&mut out, chunk.height);
+        write_u32(&mut out, chunk.base.unwrap_or(NO_BASE));
+        out.extend_from_slice(&chunk.offset.to_le_bytes());
+        write_u32(&mut out, chunk.len);
+        write_u32(&mut out, chunk.crc);
+    }
+    for chunk in layer_chunks {
+        write_u32(&mut out, *chunk);
+    }
+    match history {
+        Some(history) => {
+            out.push(1);
+            out.extend_from_slice(&history.offset.to_le_bytes());
+            write_u32(&mut out, history.len);
+            write_u32(&mut out, history.raw_len);
+            write_u32(&mut out, history.crc);
+        }
+        None => out.push(0),
+    }
+    out
+}
+
+/// 索引と末尾をファイル全体の末尾に書く
+fn write_index(
+    out: &mut Vec<u8>,
+    header: &[u8],
+    keyframe_interval: u32,
+    chunks: &[KineChunk],
+    layer_chunks: &[u32],
+    history: Option<&HistoryChunk>,
+) {
+    let index_offset = out.len() as u64;
+    let index = encode_index(header, keyframe_interval, chunks, layer_chunks, history);
+    out.extend_from_slice(&index);
+    write_footer(out, index_offset, &index);
+}
+
+fn write_footer(out: &mut Vec<u8>, index_offset: u64, index: &[u8]) {
+    out.extend_from_slice(&index_offset.to_le_bytes());
+    write_u32(out, index.len() as u32);
+    write_u32(out, crc32fast::hash(index));
+    out.extend_from_slice(FOOTER_MAGIC);
+}
+
+fn truncated() -> FormatError {
+    FormatError::InvalidData("ファイルが途中で終わっています".to_string())
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| FormatError::InvalidData("ファイルが途中で終わっています".to_string()))?;
+        let bytes = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn read_u32(&mut self) -> Result<u32, FormatError> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation::{BlendMode, Frame, Layer, LayerMask};
+    use image::Rgba;
+
+    fn project(frame_count: usize) -> Project {
+        let mut project = Project::new("test".to_string(), 16, 8, 12.0);
+        project.frames = (0..frame_count).map(|i| Frame {
+            id: format!("frame_{}", i),
+            layers: vec![Layer {
+                id: format!("layer_{}", i),
+                name: "ink".to_string(),
+                visible: true,
+                opacity: 1.0,
+                blend_mode: BlendMode::Normal,
+                locked: false,
+                effects: Vec::new(),
+                fill: None,
+                mask: None,
+            }],
+            duration: 1.0 / 12.0,
+        }).collect();
+        project
+    }
+
+    /// フレーム間で共通の細かい模様の上で、1ピクセルだけが動く
+    fn frames(count: usize) -> Vec<Vec<RgbaImage>> {
+        (0..count).map(|i| {
+            let mut image = RgbaImage::from_fn(16, 8, |x, y| {
+                let noise = (x * 31 + y * 17).wrapping_mul(2654435761) >> 24;
+                Rgba([noise as u8, (noise * 7) as u8, (noise * 13) as u8, 255])
+            });
+            image.put_pixel(i as u32, 3, Rgba([0, 0, 0, 255]));
+            vec![image]
+        }).collect()
+    }
+
+    #[test]
+    fn test_roundtrip_with_deltas_and_keyframes() {
+        let project = project(5);
+        let frames = frames(5);
+        let data = encode_kine(&project, &frames, &KineWriteOptions { keyframe_interval: 3 }).unwrap();
+
+        let document = decode_kine(&data).unwrap();
+        assert_eq!(document.project.frames.len(), 5);
+        assert_eq!(document.frames, frames);
+
+        // 差分を使うほうが小さくなる
+        let keyframes_only = encode_kine(&project, &frames, &KineWriteOptions { keyframe_interval: 1 }).unwrap();
+        assert!(data.len() < keyframes_only.len());
+    }
+
+    #[test]
+    fn test_history_is_stored_with_the_project() {
+        let mut project = project(2);
+        project.history.push(Operation::CreateLayer { layer_id: "layer_0".to_string(), width: 16, height: 8 });
+        project.history.push(Operation::CreateLayer { layer_id: "layer_1".to_string(), width: 16, height: 8 });
+        project.history.undo();
+        let data = encode_kine(&project, &frames(2), &KineWriteOptions::default()).unwrap();
+        let history = decode_kine(&data).unwrap().project.history;
+        assert_eq!((history.len(), history.position()), (2, 1));
+
+        // 操作ログはプロジェクト情報に含めず、追記保存でも新しいチャンクとして書き直される
+        project.history.redo();
+        let mut file = std::io::Cursor::new(data);
+        let index = read_kine_index(&mut file).unwrap();
+        assert!(index.project.history.is_empty());
+        append_kine(&mut file, &index, &project, &HashMap::new()).unwrap();
+        let history = decode_kine(file.get_ref()).unwrap().project.history;
+        assert_eq!((history.len(), history.position()), (2, 2));
+    }
+
+    #[test]
+    fn test_pasted_layers_refer_to_their_chunks() {
+        let mut project = project(1);
+        let frames = frames(1);
+        let paste = Operation::paste_image("layer_0", 0, 0, &frames[0][0]).unwrap();
+        project.history.push(Operation::CreateLayer { layer_id: "layer_0".to_string(), width: 16, height: 8 });
+        project.history.push(paste.clone());
+        let referenced = encode_kine(&project, &frames, &KineWriteOptions::default()).unwrap();
+
+        // PNG を持たずに保存し、読み込み時にレイヤーの画像から同じ操作に戻す
+        let history = decode_kine(&referenced).unwrap().project.history;
+        assert_eq!(history.entries()[1].operation, paste);
+
+        // 貼り付けた後に描いたレイヤーは画像が変わっているため PNG をそのまま保存する
+        project.history.push(Operation::ClearLayer { layer_id: "layer_0".to_string() });
+        assert!(layer_chunk_pastes(&project.history, &HashMap::from([("layer_0", (16, 8))])).is_empty());
+        project.history.undo();
+        assert_eq!(layer_chunk_pastes(&project.history, &HashMap::from([("layer_0", (16, 8))])), vec![1]);
+        assert!(layer_chunk_pastes(&project.history, &HashMap::from([("layer_0", (8, 8))])).is_empty());
+        let inline = encode_kine(&project, &frames, &KineWriteOptions::default()).unwrap();
+        assert!(referenced.len() < inline.len());
+        let history = decode_kine(&inline).unwrap().project.history;
+        assert_eq!(history.entries()[1].operation, paste);
+    }
+
+    #[test]
+    fn test_unreadable_history_keeps_the_images() {
+        let mut project = project(1);
+        project.history.push(Operation::CreateLayer { layer_id: "layer_0".to_string(), width: 16, height: 8 });
+        let mut data = encode_kine(&project, &frames(1), &KineWriteOptions::default()).unwrap();
+        let index = read_kine_index(&mut std::io::Cursor::new(&data)).unwrap();
+        let history = index.history.unwrap();
+        data[history.offset as usize] ^= 0x01;
+
+        let document = decode_kine(&data).unwrap();
+        assert!(document.project.history.is_empty());
+        assert_eq!(document.frames, frames(1));
+    }
+
+    #[test]
+    fn test_masks_are_stored_after_their_layer() {
+        let mut project = project(2);
+        project.frames[1].layers[0].mask = Some(LayerMask { texture_id: "layer_1_mask".to_string(), enabled: true });
+        let mut frames = frames(2);
+        let mask = RgbaImage::from_pixel(16, 8, Rgba([255, 255, 255, 255]));
+        frames[1].push(mask.clone());
+        let data = encode_kine(&project, &frames, &KineWriteOptions::default()).unwrap();
+        assert_eq!(decode_kine(&data).unwrap().frames, frames);
+
+        // マスクだけを描き換えて追記できる
+        let mut file = std::io::Cursor::new(data);
+        let index = read_kine_index(&mut file).unwrap();
+        assert!(index.layer_chunk("layer_1_mask").is_some());
+        frames[1][1].put_pixel(0, 0, Rgba([0, 0, 0, 255]));
+        let changed = HashMap::from([("layer_1_mask".to_string(), frames[1][1].clone())]);
+        let stats = append_kine(&mut file, &index, &project, &changed).unwrap();
+        assert_eq!((stats.chunks_written, stats.frames_rewritten), (1, 1));
+        assert_eq!(decode_kine(file.get_ref()).unwrap().frames, frames);
+
+        // マスクの画像がなければレイヤー数が合わない
+        frames[1].pop();
+        assert!(encode_kine(&project, &frames, &KineWriteOptions::default()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_invalid_files() {
+        let project = project(2);
+        let data = encode_kine(&project, &frames(2), &KineWriteOptions::default()).unwrap();
+
+        assert!(matches!(decode_kine(b"NOPE"), Err(FormatError::InvalidData(_))));
+        assert!(decode_kine(&data[..data.len() - 3]).is_err());
+        assert!(encode_kine(&project, &frames(1), &KineWriteOptions::default()).is_err());
+
+        let mut future = data.clone();
+        future[4..6].copy_from_slice(&(KINE_VERSION + 1).to_le_bytes());
+        assert!(matches!(decode_kine(&future), Err(FormatError::UnsupportedVersion(7))));
+
+        let mut corrupted = data.clone();
+        let middle = data.len() / 2;
+        corrupted[middle] ^= 0x01;
+        assert!(matches!(decode_kine(&corrupted), Err(FormatError::ChecksumMismatch { .. })));
+    }
+
+    #[test]
+    fn test_migrates_v1_files() {
+        // v1: プロジェクト情報に空の操作ログを含み、チェックサムを持たない
+        let project = project(1);
+        let header = serde_json::to_vec(&project).unwrap();
+        let image = &frames(1)[0][0];
+        let payload = lz4::compress(image.as_raw());
+
+        let mut data = Vec::new();
+        data.extend_from_slice(KINE_MAGIC);
+        data.extend_from_slice(&1u16.to_le_bytes());
+        write_u32(&mut data, header.len() as u32);
+        data.extend_from_slice(&header);
+        write_u32(&mut data, DEFAULT_KEYFRAME_INTERVAL);
+        data.push(CHUNK_KEYFRAME);
+        write_u32(&mut data, image.width());
+        write_u32(&mut data, image.height());
+        write_u32(&mut data, payload.len() as u32);
+        data.extend_from_slice(&payload);
+
+        let document = decode_kine(&data).unwrap();
+        assert_eq!(document.project.frames[0].layers[0].id, "layer_0");
+        assert_eq!(&document.frames[0][0], image);
+    }
+
+    #[test]
+    fn test_append_rewrites_only_changed_layers() {
+        let project = project(4);
+        let mut frames = frames(4);
+        let data = encode_kine(&project, &frames, &KineWriteOptions { keyframe_interval: 2 }).unwrap();
+        let mut file = std::io::Cursor::new(data);
+        let index = read_kine_index(&mut file).unwrap();
+        assert_eq!(index.chunks.len(), 4);
+        assert!(!index.should_compact());
+
+        // 差分の元になっているフレーム 0 を描き換えても、フレーム 1 の差分は元のチャンクから展開できる
+        frames[0][0].put_pixel(5, 5, Rgba([255, 0, 0, 255]));
+        let changed = HashMap::from([("layer_0".to_string(), frames[0][0].clone())]);
+        let stats = append_kine(&mut file, &index, &project, &changed).unwrap();
+        assert_eq!((stats.chunks_written, stats.frames_rewritten), (1, 1));
+        assert_eq!(stats.file_len, file.get_ref().len() as u64);
+        assert_eq!(decode_kine(file.get_ref()).unwrap().frames, frames);
+
+        // 削除したフレームのチャンクは索引から外れる
+        let mut trimmed = project.clone();
+        trimmed.frames.truncate(2);
+        let index = read_kine_index(&mut file).unwrap();
+        append_kine(&mut file, &index, &trimmed, &HashMap::new()).unwrap();
+        let index = read_kine_index(&mut file).unwrap();
+        // 元のフレーム 0（フレーム 1 の差分の元）・新しいフレーム 0・フレーム 1
+        assert_eq!(index.chunks.len(), 3);
+        assert_eq!(decode_kine(file.get_ref()).unwrap().frames, frames[..2].to_vec());
+
+        // 保存されていないレイヤーは画像が必要で、読んだ後に変わったファイルには追記しない
+        assert!(append_kine(&mut file, &index, &project, &HashMap::new()).is_err());
+        file.get_mut().push(0);
+        assert!(append_kine(&mut file, &index, &trimmed, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_rejects_overflowing_chunk_offset() {
+        let project = project(1);
+        let data = encode_kine(&project, &frames(1), &KineWriteOptions::default()).unwrap();
+        let index = read_kine_index(&mut std::io::Cursor::new(&data)).unwrap();
+
+        // 位置 + 長さが u64 を超えるチャンクを指す索引に書き換える
+        let mut chunks = index.chunks.clone();
+        chunks[0].offset = u64::MAX;
+        let index_offset = index.file_len - FOOTER_LEN - index.index_len as u64;
+        let mut broken = data[..index_offset as usize].to_vec();
+        write_index(&mut broken, &project_header(&project).unwrap(), index.keyframe_interval, &chunks, &index.layer_chunks, None);
+        assert!(matches!(read_kine_index(&mut std::io::Cursor::new(&broken)), Err(FormatError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_repeated_appends_request_compaction() {
+        let project = project(1);
+        let mut file = std::io::Cursor::new(encode_kine(&project, &frames(1), &KineWriteOptions::default()).unwrap());
+        let changed = HashMap::from([("layer_0".to_string(), frames(1)[0][0].clone())]);
+        for _ in 0..3 {
+            let index = read_kine_index(&mut file).unwrap();
+            append_kine(&mut file, &index, &project, &changed).unwrap();
+        }
+        assert!(read_kine_index(&mut file).unwrap().should_compact());
+        assert_eq!(decode_kine(file.get_ref()).unwrap().frames, frames(1));
+    }
+
+    #[test]
+    fn test_reads_v5_history_from_the_header() {
+        // v5: 操作ログをプロジェクト情報に含め、索引は操作ログのチャンクを持たない
+        let mut project = project(1);
+        project.history.push(Operation::CreateLayer { layer_id: "layer_0".to_string(), width: 16, height: 8 });
+        let header = serde_json::to_vec(&project).unwrap();
+        let image = &frames(1)[0][0];
+
+        let mut data = Vec::new();
+        data.extend_from_slice(KINE_MAGIC);
+        data.extend_from_slice(&5u16.to_le_bytes());
+        let chunk = write_chunk(&mut data, image, None, &lz4::compress(image.as_raw()));
+        let mut index = encode_index(&header, DEFAULT_KEYFRAME_INTERVAL, &[chunk], &[0], None);
+        index.pop();
+        let index_offset = data.len() as u64;
+        data.extend_from_slice(&index);
+        write_footer(&mut data, index_offset, &index);
+
+        let document = decode_kine(&data).unwrap();
+        assert_eq!(document.project.history.len(), 1);
+        assert_eq!(&document.frames[0][0], image);
+
+        // 古い形式のファイルには追記せず、全体を書き直させる
+        let mut file = std::io::Cursor::new(data);
+        let index = read_kine_index(&mut file).unwrap();
+        assert!(matches!(append_kine(&mut file, &index, &project, &HashMap::new()), Err(FormatError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_reads_v2_files() {
+        // v2: チャンクを順に並べ、末尾に全体の CRC32 を持つ
+        let project = project(2);
+        let frames = frames(2);
+        let header = project_header(&project).unwrap();
+
+        let mut data = Vec::new();
+        data.extend_from_slice(KINE_MAGIC);
+        data.extend_from_slice(&2u16.to_le_bytes());
+        write_u32(&mut data, header.len() as u32);
+        data.extend_from_slice(&header);
+        write_u32(&mut data, DEFAULT_KEYFRAME_INTERVAL);
+        for (index, images) in frames.iter().enumerate() {
+            let (kind, payload) = match index {
+                0 => (CHUNK_KEYFRAME, lz4::compress(images[0].as_raw())),
+                _ => (CHUNK_DELTA, lz4::compress(&encode_delta(frames[0][0].as_raw(), images[0].as_raw()))),
+            };
+            data.push(kind);
+            write_u32(&mut data, images[0].width());
+            write_u32(&mut data, images[0].height());
+            write_u32(&mut data, payload.len() as u32);
+            data.extend_from_slice(&payload);
+        }
+        let checksum = crc32fast::hash(&data);
+        write_u32(&mut data, checksum);
+
+        assert_eq!(decode_kine(&data).unwrap().frames, frames);
+        assert!(matches!(read_kine_index(&mut std::io::Cursor::new(data)), Err(FormatError::Unsupported(_))));
+    }
+}
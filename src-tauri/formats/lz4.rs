@@ -0,0 +1,183 @@
+use super::FormatError;
+
+// LZ4ブロック形式の圧縮・展開（フレーム形式のヘッダーは扱わない）
+
+/// 一致として扱う最小の長さ
+const MIN_MATCH: usize = 4;
+/// 末尾のこのバイト数は必ずリテラルとして出力する（LZ4の仕様）
+const LAST_LITERALS: usize = 5;
+/// 末尾からこのバイト数以内では一致の探索を始めない（LZ4の仕様）
+const MF_LIMIT: usize = 12;
+const MAX_OFFSET: usize = u16::MAX as usize;
+const HASH_LOG: u32 = 16;
+
+fn read_u32(data: &[u8], pos: usize) -> u32 {
+    u32::from_le_bytes([data[pos], data[pos + 1], data[pos + 2], data[pos + 3]])
+}
+
+fn hash(sequence: u32) -> usize {
+    (sequence.wrapping_mul(2654435761) >> (32 - HASH_LOG)) as usize
+}
+
+fn write_length(out: &mut Vec<u8>, mut length: usize) {
+    while length >= 255 {
+        out.push(255);
+        length -= 255;
+    }
+    out.push(length as u8);
+}
+
+fn write_sequence(out: &mut Vec<u8>, literals: &[u8], match_info: Option<(u16, usize)>) {
+    let literal_nibble = literals.len().min(15) as u8;
+    let match_nibble = match_info.map(|(_, len)| (len - MIN_MATCH).min(15) as u8).unwrap_or(0);
+    out.push((literal_nibble << 4) | match_nibble);
+    if literals.len() >= 15 {
+        write_length(out, literals.len() - 15);
+    }
+    out.extend_from_slice(literals);
+
+    if let Some((offset, len)) = match_info {
+        out.extend_from_slice(&offset.to_le_bytes());
+        if len - MIN_MATCH >= 15 {
+            write_length(out, len - MIN_MATCH - 15);
+        }
+    }
+}
+
+/// LZ4ブロック形式で圧縮
+pub fn compress(input: &[u8]) -> Vec<u8> {
+    let mut out = Vec::with_capacity(input.len() / 2 + 16);
+    let mut anchor = 0;
+
+    if input.len() > MF_LIMIT {
+        // 位置 + 1 を記録（0 は未登録）
+        let mut table = vec![0u32; 1 << HASH_LOG];
+        let limit = input.len() - MF_LIMIT;
+        let mut pos = 0;
+        while pos < limit {
+            let sequence = read_u32(input, pos);
+            let slot = hash(sequence);
+            let candidate = table[slot] as usize;
+            table[slot] = pos as u32 + 1;
+
+            if candidate > 0 {
+                let candidate = candidate - 1;
+                if pos - candidate <= MAX_OFFSET && read_u32(input, candidate) == sequence {
+                    let max_len = input.len() - LAST_LITERALS - pos;
+                    let mut len = MIN_MATCH;
+                    while len < max_len && input[candidate + len] == input[pos + len] {
+                        len += 1;
+                    }
+                    write_sequence(&mut out, &input[anchor..pos], Some(((pos - candidate) as u16, len)));
+                    pos += len;
+                    anchor = pos;
+                    continue;
+                }
+            }
+            pos += 1;
+        }
+    }
+
+    write_sequence(&mut out, &input[anchor..], None);
+    out
+}
+
+fn read_length(input: &[u8], pos: &mut usize) -> Result<usize, FormatError> {
+    let mut length = 0usize;
+    loop {
+        let byte = *input.get(*pos).ok_or_else(truncated)?;
+        *pos += 1;
+        length = length.checked_add(byte as usize).ok_or_else(|| invalid("長さが大きすぎます"))?;
+        if byte != 255 {
+            return Ok(length);
+        }
+    }
+}
+
+fn truncated() -> FormatError {
+    FormatError::InvalidData("LZ4データが途中で終わっています".to_string())
+}
+
+fn invalid(msg: &str) -> FormatError {
+    FormatError::InvalidData(format!("LZ4データが不正です: {}", msg))
+}
+
+/// LZ4ブロック形式を展開（`expected_len` は展開後のサイズ）
+pub fn decompress(input: &[u8], expected_len: usize) -> Result<Vec<u8>, FormatError> {
+    // 1バイトの入力から展開されるのは最大255バイト程度なので、不正なサイズで過大に確保しない
+    let mut out = Vec::with_capacity(expected_len.min(input.len().saturating_mul(255)));
+    let mut pos = 0;
+
+    loop {
+        let token = *input.get(pos).ok_or_else(truncated)?;
+        pos += 1;
+
+        let mut literal_len = (token >> 4) as usize;
+        if literal_len == 15 {
+            literal_len += read_length(input, &mut pos)?;
+        }
+        let literals = input.get(pos..pos + literal_len).ok_or_else(truncated)?;
+        if out.len() + literal_len > expected_len {
+            return Err(invalid("展開後のサイズが超過しています"));
+        }
+        out.extend_from_slice(literals);
+        pos += literal_len;
+
+        if pos == input.len() {
+            break;
+        }
+
+        let offset_bytes = input.get(pos..pos + 2).ok_or_else(truncated)?;
+        let offset = u16::from_le_bytes([offset_bytes[0], offset_bytes[1]]) as usize;
+        pos += 2;
+        if offset == 0 || offset > out.len() {
+            return Err(invalid("参照位置が範囲外です"));
+        }
+
+        let mut match_len = (token & 0x0f) as usize + MIN_MATCH;
+        if match_len == 15 + MIN_MATCH {
+            match_len += read_length(input, &mut pos)?;
+        }
+        if out.len() + match_len > expected_len {
+            return Err(invalid("展開後のサイズが超過しています"));
+        }
+        // 参照範囲は出力と重なりうるため1バイトずつコピー
+        let start = out.len() - offset;
+        for index in 0..match_len {
+            let byte = out[start + index];
+            out.push(byte);
+        }
+    }
+
+    if out.len() != expected_len {
+        return Err(invalid(&format!("展開後のサイズが一致しません: {} != {}", out.len(), expected_len)));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip() {
+        let mut repetitive = vec![0u8; 70_000];
+        repetitive.extend((0..1000u32).flat_map(|i| (i % 7).to_le_bytes()));
+        let noisy: Vec<u8> = (0..5000u32).map(|i| (i.wrapping_mul(2654435761) >> 13) as u8).collect();
+
+        for input in [Vec::new(), b"abc".to_vec(), repetitive, noisy] {
+            let compressed = compress(&input);
+            assert_eq!(decompress(&compressed, input.len()).unwrap(), input);
+        }
+        assert!(compress(&[0u8; 70_000]).len() < 400);
+    }
+
+    #[test]
+    fn test_rejects_corrupt_data() {
+        let compressed = compress(&[7u8; 1000]);
+        assert!(decompress(&compressed, 999).is_err());
+        assert!(decompress(&compressed[..compressed.len() - 1], 1000).is_err());
+        // 出力より前を参照するオフセット
+        assert!(decompress(&[0x10, b'a', 5, 0], 100).is_err());
+    }
+}
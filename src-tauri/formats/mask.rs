@@ -0,0 +1,43 @@
+use image::RgbaImage;
+
+/// マスクの画素の値（0〜1。明るさと不透明度の積で、白は表示・黒や透明は隠す）
+#[inline]
+pub fn mask_value(pixel: [u8; 4]) -> f32 {
+    let luminance = 0.299 * pixel[0] as f32 + 0.587 * pixel[1] as f32 + 0.114 * pixel[2] as f32;
+    luminance / 255.0 * (pixel[3] as f32 / 255.0)
+}
+
+/// レイヤーの不透明度にマスクを掛ける（マスクの範囲外は隠す）
+pub fn apply_mask(image: &mut RgbaImage, mask: &RgbaImage) {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        let value = if x < mask.width() && y < mask.height() {
+            mask_value(mask.get_pixel(x, y).0)
+        } else {
+            0.0
+        };
+        pixel[3] = (pixel[3] as f32 * value).round() as u8;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_mask_scales_alpha_by_luminance() {
+        let mut image = RgbaImage::from_pixel(3, 2, Rgba([10, 20, 30, 200]));
+        let mut mask = RgbaImage::new(2, 2);
+        mask.put_pixel(0, 0, Rgba([255, 255, 255, 255]));
+        mask.put_pixel(1, 0, Rgba([128, 128, 128, 255]));
+        mask.put_pixel(0, 1, Rgba([255, 255, 255, 128]));
+        apply_mask(&mut image, &mask);
+
+        assert_eq!(image.get_pixel(0, 0).0, [10, 20, 30, 200]);
+        assert_eq!(image.get_pixel(1, 0).0[3], 100);
+        assert_eq!(image.get_pixel(0, 1).0[3], 100);
+        // 黒・透明・マスクの範囲外は隠れる（色は残す）
+        assert_eq!(image.get_pixel(1, 1).0, [10, 20, 30, 0]);
+        assert_eq!(image.get_pixel(2, 0).0[3], 0);
+    }
+}
@@ -0,0 +1,138 @@
+use serde::Deserialize;
+use serde_json::Value;
+use log::{info, warn};
+use crate::history::OperationLog;
+use super::FormatError;
+
+// .kine プロジェクト情報（JSONヘッダー）のバージョン間移行
+
+/// 1バージョン分の移行処理
+pub struct Migration {
+    /// 移行元のバージョン（適用後は `from_version + 1` になる）
+    pub from_version: u16,
+    pub description: &'static str,
+    pub apply: fn(&mut Value) -> Result<(), FormatError>,
+}
+
+/// プロジェクト情報の移行処理（`from_version` の昇順に並べる）
+pub const PROJECT_MIGRATIONS: &[Migration] = &[
+    Migration {
+        from_version: 1,
+        description: "読めない操作ログをプロジェクト情報から削除",
+        apply: drop_unreadable_history,
+    },
+    Migration {
+        from_version: 2,
+        description: "コンテナを索引付きの追記可能な形式に変更（プロジェクト情報は変更なし）",
+        apply: keep_header,
+    },
+    Migration {
+        from_version: 3,
+        description: "レイヤーマスクの画像を格納できるようにした（プロジェクト情報は変更なし）",
+        apply: keep_header,
+    },
+    Migration {
+        from_version: 4,
+        description: "操作ログを保存するようにした（それ以前のファイルは空の操作ログとして読む）",
+        apply: keep_header,
+    },
+    Migration {
+        from_version: 5,
+        description: "操作ログを独立したチャンクに移した（v5 のプロジェクト情報に含まれる操作ログはそのまま読む）",
+        apply: keep_header,
+    },
+];
+
+/// `version` のプロジェクト情報を `target_version` まで順に移行し、適用した移行の説明を返す
+///
+/// `target_version` より新しいファイルは `FormatError::UnsupportedVersion` になる。
+pub fn migrate(
+    value: &mut Value,
+    version: u16,
+    target_version: u16,
+    migrations: &[Migration],
+) -> Result<Vec<&'static str>, FormatError> {
+    if version == 0 || version > target_version {
+        return Err(FormatError::UnsupportedVersion(version as u32));
+    }
+
+    let mut applied = Vec::new();
+    for current in version..target_version {
+        let migration = migrations.iter()
+            .find(|migration| migration.from_version == current)
+            .ok_or_else(|| FormatError::Unsupported(format!("バージョン {} からの移行処理がありません", current)))?;
+        (migration.apply)(value)?;
+        info!("[Migration] v{} -> v{}: {}", current, current + 1, migration.description);
+        applied.push(migration.description);
+    }
+    Ok(applied)
+}
+
+/// v1 → v2: v1 のヘッダーは操作ログを含んでいた（v2〜v4 は含めず、v5 で再び含め、v6 からは独立したチャンクに格納する）
+///
+/// 読める操作ログはそのまま残して v5 と同じく復元し、現在の形式で読めないものだけを取り除く。
+fn drop_unreadable_history(value: &mut Value) -> Result<(), FormatError> {
+    let object = value.as_object_mut()
+        .ok_or_else(|| FormatError::InvalidData("プロジェクト情報がオブジェクトではありません".to_string()))?;
+    let readable = object.get("history")
+        .is_some_and(|history| OperationLog::deserialize(history).is_ok_and(|log| log.validate().is_ok()));
+    if !readable && object.remove("history").is_some() {
+        warn!("[Migration] v1 の操作ログを読めないため破棄します");
+    }
+    Ok(())
+}
+
+/// v2 → v3, v3 → v4, v4 → v5, v5 → v6: 変わったのはレイヤー画像と操作ログの格納方法だけで、プロジェクト情報はそのまま読める
+fn keep_header(_value: &mut Value) -> Result<(), FormatError> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn append_suffix(value: &mut Value) -> Result<(), FormatError> {
+        let name = value["name"].as_str().unwrap_or_default().to_string();
+        value["name"] = Value::String(name + "+");
+        Ok(())
+    }
+
+    const TEST_MIGRATIONS: &[Migration] = &[
+        Migration { from_version: 1, description: "one", apply: append_suffix },
+        Migration { from_version: 2, description: "two", apply: append_suffix },
+    ];
+
+    #[test]
+    fn test_applies_migrations_in_order() {
+        let mut value = json!({ "name": "a" });
+        assert_eq!(migrate(&mut value, 1, 3, TEST_MIGRATIONS).unwrap(), vec!["one", "two"]);
+        assert_eq!(value["name"], "a++");
+
+        let mut value = json!({ "name": "a" });
+        assert!(migrate(&mut value, 3, 3, TEST_MIGRATIONS).unwrap().is_empty());
+        assert_eq!(value["name"], "a");
+    }
+
+    #[test]
+    fn test_rejects_unknown_versions() {
+        let mut value = json!({});
+        assert!(matches!(migrate(&mut value, 4, 3, TEST_MIGRATIONS), Err(FormatError::UnsupportedVersion(4))));
+        assert!(matches!(migrate(&mut value, 0, 3, TEST_MIGRATIONS), Err(FormatError::UnsupportedVersion(0))));
+        // 移行処理が抜けている
+        assert!(matches!(migrate(&mut value, 1, 4, TEST_MIGRATIONS), Err(FormatError::Unsupported(_))));
+    }
+
+    #[test]
+    fn test_v1_header_keeps_readable_history() {
+        let log = serde_json::to_value(OperationLog::new()).unwrap();
+        let mut value = json!({ "name": "p", "history": log });
+        migrate(&mut value, 1, 2, PROJECT_MIGRATIONS).unwrap();
+        assert_eq!(value["history"], log);
+
+        // 現在の形式で読めない操作ログだけを捨てる
+        let mut value = json!({ "name": "p", "history": { "entries": [] } });
+        migrate(&mut value, 1, 2, PROJECT_MIGRATIONS).unwrap();
+        assert!(value.get("history").is_none());
+    }
+}
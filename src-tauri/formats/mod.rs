@@ -0,0 +1,300 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use crate::animation::BlendMode;
+use crate::drawing_engine::ContentBounds;
+
+// Photoshop形式
+pub mod psd;
+// OpenRaster形式
+pub mod ora;
+// Kinegraph プロジェクト形式（.kine）
+pub mod kine;
+// LZ4ブロック圧縮
+pub mod lz4;
+// プロジェクト形式のバージョン間移行
+pub mod migration;
+// レイヤーの合成
+pub mod composite;
+pub use composite::{apply_matte, blend_over, flatten_layers, flatten_layers_on, ExportBackground};
+
+// レイヤー効果（ドロップシャドウ・縁取り・光彩）
+pub mod effects;
+pub use effects::{apply_effects, EffectKind, LayerEffect};
+
+// 塗りつぶしレイヤー（単色・グラデーション・模様）
+pub mod fill;
+pub use fill::{FillPattern, GradientStop, LayerFill};
+
+// バケツ塗り（隙間閉じ付きの塗りつぶし）
+pub mod flood;
+pub use flood::FloodFillOptions;
+// 線画の領域分割（領域ごとのベタ塗り）
+pub mod regions;
+pub use regions::{RegionInfo, RegionMap};
+
+// レイヤーマスク
+pub mod mask;
+pub use mask::apply_mask;
+// アニメーションの連番画像書き出し
+pub mod sequence;
+pub use sequence::FrameSequenceOptions;
+// 名前付きの書き出しプリセット（一括書き出し用）
+pub mod presets;
+pub use presets::{ExportPreset, FrameRange, PresetFormat};
+// 保存後に変更されたレイヤーの追跡（追記保存用）
+pub mod dirty;
+pub use dirty::SaveTracker;
+
+/// 読み込みを許可する画像の最大の幅・高さ
+pub const MAX_IMPORT_DIMENSION: u32 = 30000;
+
+/// ファイル形式変換のエラー型
+#[derive(Debug)]
+pub enum FormatError {
+    InvalidData(String),
+    Unsupported(String),
+    DimensionsTooLarge(u32, u32),
+    /// 対応していない（新しすぎる）形式バージョン
+    UnsupportedVersion(u32),
+    /// 格納されたチェックサムと内容が一致しない
+    ChecksumMismatch { expected: u32, actual: u32 },
+    Io(std::io::Error),
+}
+
+impl fmt::Display for FormatError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FormatError::InvalidData(msg) => write!(f, "ファイルデータが不正です: {}", msg),
+            FormatError::Unsupported(msg) => write!(f, "未対応の機能です: {}", msg),
+            FormatError::DimensionsTooLarge(width, height) => {
+                write!(f, "画像サイズが大きすぎます: {}x{}", width, height)
+            }
+            FormatError::UnsupportedVersion(version) => {
+                write!(f, "未対応のファイルバージョンです: {}", version)
+            }
+            FormatError::ChecksumMismatch { expected, actual } => {
+                write!(f, "ファイルが破損しています（チェックサム {:08x} != {:08x}）", actual, expected)
+            }
+            FormatError::Io(e) => write!(f, "ファイルの入出力エラー: {}", e),
+        }
+    }
+}
+
+impl Error for FormatError {}
+
+impl From<std::io::Error> for FormatError {
+    fn from(e: std::io::Error) -> Self {
+        FormatError::Io(e)
+    }
+}
+
+/// 形式変換用のラスターレイヤー（RGBA、非乗算アルファ）
+#[derive(Debug, Clone)]
+pub struct RasterLayer {
+    pub name: String,
+    pub visible: bool,
+    pub opacity: f32,
+    pub blend_mode: BlendMode,
+    /// キャンバス左上からのオフセット
+    pub offset: (i32, i32),
+    pub image: RgbaImage,
+    /// 合成時に重ねるレイヤー効果
+    pub effects: Vec<LayerEffect>,
+}
+
+impl RasterLayer {
+    pub fn new(name: impl Into<String>, image: RgbaImage) -> Self {
+        Self {
+            name: name.into(),
+            visible: true,
+            opacity: 1.0,
+            blend_mode: BlendMode::Normal,
+            offset: (0, 0),
+            image,
+            effects: Vec::new(),
+        }
+    }
+
+    /// オフセットを反映したキャンバスサイズの画像（はみ出した部分は切り捨て）
+    pub fn canvas_image(&self, width: u32, height: u32) -> RgbaImage {
+        let mut canvas = RgbaImage::new(width, height);
+        image::imageops::replace(&mut canvas, &self.image, self.offset.0 as i64, self.offset.1 as i64);
+        canvas
+    }
+}
+
+/// 読み込んだレイヤー構成
+#[derive(Debug, Clone)]
+pub struct LayeredDocument {
+    pub width: u32,
+    pub height: u32,
+    /// レイヤー（先頭が最背面）
+    pub layers: Vec<RasterLayer>,
+}
+
+/// 書き出しの設定（プロジェクトと共に保存される）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportSettings {
+    /// 不透明なピクセルを含む範囲に切り詰めて書き出す
+    pub trim_to_content: bool,
+    /// 切り詰める際に残す余白（ピクセル）
+    pub trim_padding: u32,
+    /// アルファを残すか、マット色の上に平坦化するか（PSD では最背面に背景レイヤーを加える）
+    pub background: ExportBackground,
+}
+
+/// レイヤーを指定範囲に切り抜く（範囲の左上が新しい原点になる）
+pub fn crop_layers(layers: &mut [RasterLayer], bounds: ContentBounds) {
+    for layer in layers {
+        layer.offset = (
+            layer.offset.0 - bounds.x as i32,
+            layer.offset.1 - bounds.y as i32,
+        );
+        layer.image = layer.canvas_image(bounds.width, bounds.height);
+        layer.offset = (0, 0);
+    }
+}
+
+/// ファイル内容から形式を判定して読み込む（PSD / OpenRaster）
+pub fn decode_layered_document(data: &[u8]) -> Result<LayeredDocument, FormatError> {
+    if data.starts_with(b"8BPS") {
+        psd::decode_psd(data)
+    } else if data.starts_with(b"PK") {
+        ora::decode_ora(data)
+    } else {
+        Err(FormatError::Unsupported("PSD / OpenRaster 以外のファイル形式".to_string()))
+    }
+}
+
+/// 読み込めるファイルの種類（拡張子で判定）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileKind {
+    /// 1枚の画像（PNG / JPEG）
+    Image,
+    /// レイヤー構成のある文書（PSD / OpenRaster）
+    LayeredDocument,
+    /// Kinegraph プロジェクト（.kine）
+    Project,
+}
+
+impl FileKind {
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+        match extension.as_str() {
+            "png" | "jpg" | "jpeg" => Some(FileKind::Image),
+            "psd" | "ora" => Some(FileKind::LayeredDocument),
+            "kine" => Some(FileKind::Project),
+            _ => None,
+        }
+    }
+}
+
+/// PNG / JPEG の画像を読み込む
+pub fn decode_image(data: &[u8]) -> Result<RgbaImage, FormatError> {
+    let format = image::guess_format(data).map_err(|e| FormatError::InvalidData(e.to_string()))?;
+    if !matches!(format, image::ImageFormat::Png | image::ImageFormat::Jpeg) {
+        return Err(FormatError::Unsupported(format!("{:?} 形式の画像", format)));
+    }
+    let image = image::load_from_memory_with_format(data, format)
+        .map_err(|e| FormatError::InvalidData(e.to_string()))?
+        .to_rgba8();
+    if image.width() == 0 || image.height() == 0 {
+        return Err(FormatError::InvalidData("画像が空です".to_string()));
+    }
+    if image.width() > MAX_IMPORT_DIMENSION || image.height() > MAX_IMPORT_DIMENSION {
+        return Err(FormatError::DimensionsTooLarge(image.width(), image.height()));
+    }
+    Ok(image)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn solid(width: u32, height: u32, color: [u8; 4]) -> RgbaImage {
+        RgbaImage::from_pixel(width, height, Rgba(color))
+    }
+
+    #[test]
+    fn test_canvas_image_clips_offset() {
+        let mut layer = RasterLayer::new("layer", solid(2, 2, [255, 0, 0, 255]));
+        layer.offset = (-1, 1);
+
+        let canvas = layer.canvas_image(3, 2);
+        assert_eq!(*canvas.get_pixel(0, 1), Rgba([255, 0, 0, 255]));
+        assert_eq!(*canvas.get_pixel(1, 1), Rgba([0, 0, 0, 0]));
+        assert_eq!(*canvas.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_crop_layers() {
+        let mut image = RgbaImage::new(4, 4);
+        image.put_pixel(2, 3, Rgba([255, 0, 0, 255]));
+        let mut offset = RasterLayer::new("offset", solid(1, 1, [0, 255, 0, 255]));
+        offset.offset = (1, 1);
+        let mut layers = [RasterLayer::new("layer", image), offset];
+
+        crop_layers(&mut layers, ContentBounds { x: 1, y: 1, width: 2, height: 3 });
+        assert_eq!(layers[0].image.dimensions(), (2, 3));
+        assert_eq!(*layers[0].image.get_pixel(1, 2), Rgba([255, 0, 0, 255]));
+        assert_eq!(*layers[1].image.get_pixel(0, 0), Rgba([0, 255, 0, 255]));
+        assert_eq!(layers[1].offset, (0, 0));
+    }
+
+    #[test]
+    fn test_flatten_normal_and_opacity() {
+        let bottom = RasterLayer::new("bottom", solid(2, 2, [255, 0, 0, 255]));
+        let mut top = RasterLayer::new("top", solid(2, 2, [0, 0, 255, 255]));
+        top.opacity = 0.5;
+
+        let result = flatten_layers(&[bottom, top], 2, 2);
+        assert_eq!(*result.get_pixel(0, 0), Rgba([128, 0, 128, 255]));
+    }
+
+    #[test]
+    fn test_flatten_blend_modes() {
+        let bottom = RasterLayer::new("bottom", solid(1, 1, [200, 100, 50, 255]));
+
+        let mut multiply = RasterLayer::new("multiply", solid(1, 1, [128, 255, 0, 255]));
+        multiply.blend_mode = BlendMode::Multiply;
+        let result = flatten_layers(&[bottom.clone(), multiply], 1, 1);
+        assert_eq!(*result.get_pixel(0, 0), Rgba([100, 100, 0, 255]));
+
+        let mut screen = RasterLayer::new("screen", solid(1, 1, [0, 255, 255, 255]));
+        screen.blend_mode = BlendMode::Screen;
+        let result = flatten_layers(&[bottom, screen], 1, 1);
+        assert_eq!(*result.get_pixel(0, 0), Rgba([200, 255, 255, 255]));
+    }
+
+    #[test]
+    fn test_file_kind_and_decode_image() {
+        assert_eq!(FileKind::from_path(Path::new("/tmp/Sketch.PNG")), Some(FileKind::Image));
+        assert_eq!(FileKind::from_path(Path::new("a.ora")), Some(FileKind::LayeredDocument));
+        assert_eq!(FileKind::from_path(Path::new("a.kine")), Some(FileKind::Project));
+        assert_eq!(FileKind::from_path(Path::new("a.txt")), None);
+        assert_eq!(FileKind::from_path(Path::new("kine")), None);
+
+        let mut png = std::io::Cursor::new(Vec::new());
+        solid(3, 2, [1, 2, 3, 255]).write_to(&mut png, image::ImageFormat::Png).unwrap();
+        assert_eq!(decode_image(png.get_ref()).unwrap(), solid(3, 2, [1, 2, 3, 255]));
+        assert!(decode_image(b"not an image").is_err());
+    }
+
+    #[test]
+    fn test_flatten_skips_hidden_and_respects_offset() {
+        let mut hidden = RasterLayer::new("hidden", solid(2, 2, [255, 255, 255, 255]));
+        hidden.visible = false;
+        let mut offset = RasterLayer::new("offset", solid(1, 1, [0, 255, 0, 255]));
+        offset.offset = (1, 1);
+
+        let result = flatten_layers(&[hidden, offset], 2, 2);
+        assert_eq!(*result.get_pixel(0, 0), Rgba([0, 0, 0, 0]));
+        assert_eq!(*result.get_pixel(1, 1), Rgba([0, 255, 0, 255]));
+    }
+}
@@ -0,0 +1,199 @@
+use image::RgbaImage;
+use log::{info, debug, warn};
+use std::io::{Cursor, Read};
+use crate::animation::BlendMode;
+use super::{FormatError, LayeredDocument, RasterLayer, MAX_IMPORT_DIMENSION};
+
+/// OpenRaster（.ora）ファイルをデコード
+///
+/// 入れ子のスタックは展開し、スタックの不透明度・非表示は子レイヤーに反映する。
+pub fn decode_ora(data: &[u8]) -> Result<LayeredDocument, FormatError> {
+    let mut archive = zip::ZipArchive::new(Cursor::new(data))
+        .map_err(|e| FormatError::InvalidData(format!("zipとして読み込めません: {}", e)))?;
+
+    let stack_xml = read_entry_string(&mut archive, "stack.xml")?;
+    let xml = roxmltree::Document::parse(&stack_xml)
+        .map_err(|e| FormatError::InvalidData(format!("stack.xml の解析に失敗: {}", e)))?;
+
+    let image_node = xml.root_element();
+    if image_node.tag_name().name() != "image" {
+        return Err(FormatError::InvalidData("stack.xml のルートが image ではありません".to_string()));
+    }
+    let width = parse_attr(&image_node, "w", 0u32)?;
+    let height = parse_attr(&image_node, "h", 0u32)?;
+    if width == 0 || height == 0 || width > MAX_IMPORT_DIMENSION || height > MAX_IMPORT_DIMENSION {
+        return Err(FormatError::DimensionsTooLarge(width, height));
+    }
+    info!("[ORA] 読み込み開始: {}x{}", width, height);
+
+    let root_stack = image_node.children()
+        .find(|n| n.has_tag_name("stack"))
+        .ok_or_else(|| FormatError::InvalidData("stack.xml にスタックがありません".to_string()))?;
+
+    // stack.xml は最前面が先頭なので、集めた後で逆順にする
+    let mut layers = Vec::new();
+    collect_stack(&mut archive, root_stack, 1.0, true, (0, 0), &mut layers)?;
+    layers.reverse();
+
+    info!("[ORA] 読み込み完了: {} レイヤー", layers.len());
+    Ok(LayeredDocument { width, height, layers })
+}
+
+fn collect_stack(
+    archive: &mut zip::ZipArchive<Cursor<&[u8]>>,
+    stack: roxmltree::Node,
+    parent_opacity: f32,
+    parent_visible: bool,
+    parent_offset: (i32, i32),
+    layers: &mut Vec<RasterLayer>,
+) -> Result<(), FormatError> {
+    for node in stack.children().filter(|n| n.is_element()) {
+        let opacity = parent_opacity * parse_attr(&node, "opacity", 1.0f32)?.clamp(0.0, 1.0);
+        let visible = parent_visible && node.attribute("visibility") != Some("hidden");
+        let offset = (
+            parent_offset.0 + parse_attr(&node, "x", 0i32)?,
+            parent_offset.1 + parse_attr(&node, "y", 0i32)?,
+        );
+
+        match node.tag_name().name() {
+            "stack" => collect_stack(archive, node, opacity, visible, offset, layers)?,
+            "layer" => {
+                let src = node.attribute("src")
+                    .ok_or_else(|| FormatError::InvalidData("layer に src がありません".to_string()))?;
+                let name = node.attribute("name").unwrap_or(src).to_string();
+                let image = read_entry_png(archive, src)?;
+                let composite_op = node.attribute("composite-op").unwrap_or("svg:src-over");
+                let blend_mode = blend_mode_from_composite_op(composite_op).unwrap_or_else(|| {
+                    warn!("[ORA] 未対応の合成方法 {} は通常として読み込みます: {}", composite_op, name);
+                    BlendMode::Normal
+                });
+                debug!("[ORA] レイヤー読み込み: {} ({}x{})", name, image.width(), image.height());
+
+                layers.push(RasterLayer {
+                    name,
+                    visible,
+                    opacity,
+                    blend_mode,
+                    offset,
+                    image,
+                    effects: Vec::new(),
+                });
+            }
+            // テキスト等の未対応要素は読み飛ばす
+            other => debug!("[ORA] 未対応の要素を読み飛ばし: {}", other),
+        }
+    }
+    Ok(())
+}
+
+/// OpenRaster の composite-op から変換（対応しないモードは None）
+pub fn blend_mode_from_composite_op(op: &str) -> Option<BlendMode> {
+    match op {
+        "svg:src-over" => Some(BlendMode::Normal),
+        "svg:multiply" => Some(BlendMode::Multiply),
+        "svg:screen" => Some(BlendMode::Screen),
+        "svg:overlay" => Some(BlendMode::Overlay),
+        _ => None,
+    }
+}
+
+fn parse_attr<T: std::str::FromStr>(node: &roxmltree::Node, name: &str, default: T) -> Result<T, FormatError> {
+    match node.attribute(name) {
+        Some(value) => value.trim().parse().map_err(|_| {
+            FormatError::InvalidData(format!("属性 {} の値が不正です: {}", name, value))
+        }),
+        None => Ok(default),
+    }
+}
+
+fn read_entry_bytes(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<Vec<u8>, FormatError> {
+    let mut entry = archive.by_name(name)
+        .map_err(|e| FormatError::InvalidData(format!("{} を読み込めません: {}", name, e)))?;
+    let mut bytes = Vec::with_capacity(entry.size() as usize);
+    entry.read_to_end(&mut bytes)
+        .map_err(|e| FormatError::InvalidData(format!("{} の展開に失敗: {}", name, e)))?;
+    Ok(bytes)
+}
+
+fn read_entry_string(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<String, FormatError> {
+    String::from_utf8(read_entry_bytes(archive, name)?)
+        .map_err(|_| FormatError::InvalidData(format!("{} がUTF-8ではありません", name)))
+}
+
+fn read_entry_png(archive: &mut zip::ZipArchive<Cursor<&[u8]>>, name: &str) -> Result<RgbaImage, FormatError> {
+    let bytes = read_entry_bytes(archive, name)?;
+    let image = image::load_from_memory_with_format(&bytes, image::ImageFormat::Png)
+        .map_err(|e| FormatError::InvalidData(format!("{} のPNGデコードに失敗: {}", name, e)))?;
+    if image.width() > MAX_IMPORT_DIMENSION || image.height() > MAX_IMPORT_DIMENSION {
+        return Err(FormatError::DimensionsTooLarge(image.width(), image.height()));
+    }
+    Ok(image.to_rgba8())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+    use std::io::Write;
+    use zip::write::SimpleFileOptions;
+
+    fn png_bytes(image: &RgbaImage) -> Vec<u8> {
+        let mut bytes = Cursor::new(Vec::new());
+        image.write_to(&mut bytes, image::ImageFormat::Png).unwrap();
+        bytes.into_inner()
+    }
+
+    fn build_ora(stack_xml: &str, files: &[(&str, Vec<u8>)]) -> Vec<u8> {
+        let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+        let options = SimpleFileOptions::default();
+        writer.start_file("mimetype", options.compression_method(zip::CompressionMethod::Stored)).unwrap();
+        writer.write_all(b"image/openraster").unwrap();
+        writer.start_file("stack.xml", options).unwrap();
+        writer.write_all(stack_xml.as_bytes()).unwrap();
+        for (name, data) in files {
+            writer.start_file(*name, options).unwrap();
+            writer.write_all(data).unwrap();
+        }
+        writer.finish().unwrap().into_inner()
+    }
+
+    #[test]
+    fn test_decode_stack() {
+        let red = RgbaImage::from_pixel(2, 2, Rgba([255, 0, 0, 255]));
+        let blue = RgbaImage::from_pixel(1, 1, Rgba([0, 0, 255, 255]));
+        let data = build_ora(r#"<?xml version="1.0" encoding="UTF-8"?>
+            <image version="0.0.5" w="4" h="3">
+              <stack>
+                <stack opacity="0.5" x="1" y="1">
+                  <layer src="data/blue.png" name="線画" composite-op="svg:multiply" visibility="hidden"/>
+                </stack>
+                <layer src="data/red.png" name="背景" composite-op="svg:color-dodge"/>
+              </stack>
+            </image>"#,
+            &[("data/red.png", png_bytes(&red)), ("data/blue.png", png_bytes(&blue))],
+        );
+
+        let document = decode_ora(&data).unwrap();
+        assert_eq!((document.width, document.height), (4, 3));
+        assert_eq!(document.layers.len(), 2);
+
+        let background = &document.layers[0];
+        assert_eq!(background.name, "背景");
+        assert!(matches!(background.blend_mode, BlendMode::Normal));
+        assert_eq!(background.image, red);
+
+        let line = &document.layers[1];
+        assert_eq!(line.name, "線画");
+        assert!(matches!(line.blend_mode, BlendMode::Multiply));
+        assert!(!line.visible);
+        assert_eq!(line.opacity, 0.5);
+        assert_eq!(line.offset, (1, 1));
+    }
+
+    #[test]
+    fn test_decode_rejects_missing_entries() {
+        let data = build_ora(r#"<image w="4" h="3"><stack><layer src="data/missing.png"/></stack></image>"#, &[]);
+        assert!(matches!(decode_ora(&data), Err(FormatError::InvalidData(_))));
+        assert!(matches!(decode_ora(b"not a zip"), Err(FormatError::InvalidData(_))));
+    }
+}
@@ -0,0 +1,223 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::ops::Range;
+use crate::drawing_engine::ResampleFilter;
+use super::sequence::{encode_png, MIN_INDEX_DIGITS};
+use super::{ExportBackground, FormatError};
+
+/// 書き出しの拡大率の上限
+pub const MAX_EXPORT_SCALE: f32 = 8.0;
+
+/// 書き出しプリセットの画像形式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum PresetFormat {
+    #[default]
+    Png,
+    /// `quality` は 1〜100（アルファを持てないため、背景が透明なら白のマット色で合成する）
+    Jpeg { quality: u8 },
+}
+
+impl PresetFormat {
+    pub fn extension(&self) -> &'static str {
+        match self {
+            PresetFormat::Png => "png",
+            PresetFormat::Jpeg { .. } => "jpg",
+        }
+    }
+}
+
+/// 書き出すフレームの範囲（書き出す順の位置、0始まりで両端を含む）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FrameRange {
+    pub first: usize,
+    /// None は最後のフレームまで
+    pub last: Option<usize>,
+}
+
+/// 名前を付けた書き出しの設定（設定ファイルに保存する）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExportPreset {
+    /// プリセット名（一括書き出しではこの名前のフォルダに書き出す）
+    pub name: String,
+    pub format: PresetFormat,
+    /// 拡大率（1.0 で等倍）
+    pub scale: f32,
+    /// 拡大・縮小のフィルター（ドット絵は最近傍）
+    pub filter: ResampleFilter,
+    pub background: ExportBackground,
+    /// 書き出すフレームの範囲（None で全フレーム）
+    pub frame_range: Option<FrameRange>,
+    /// ファイル名の接頭辞（`<接頭辞>_0001.png` の形式になる）
+    pub file_prefix: String,
+}
+
+impl Default for ExportPreset {
+    fn default() -> Self {
+        Self {
+            name: String::new(),
+            format: PresetFormat::default(),
+            scale: 1.0,
+            filter: ResampleFilter::default(),
+            background: ExportBackground::default(),
+            frame_range: None,
+            file_prefix: "frame".to_string(),
+        }
+    }
+}
+
+/// ファイル名やフォルダ名に使える文字列か
+fn is_valid_file_name(name: &str) -> bool {
+    let name = name.trim();
+    !name.is_empty() && !name.contains(['/', '\\']) && !name.starts_with('.')
+}
+
+impl ExportPreset {
+    pub fn validate(&self) -> Result<(), FormatError> {
+        if !is_valid_file_name(&self.name) {
+            return Err(FormatError::InvalidData(format!("プリセット名が不正です: '{}'", self.name)));
+        }
+        if !is_valid_file_name(&self.file_prefix) {
+            return Err(FormatError::InvalidData(format!("ファイル名の接頭辞が不正です: '{}'", self.file_prefix)));
+        }
+        if !(self.scale > 0.0 && self.scale <= MAX_EXPORT_SCALE) {
+            return Err(FormatError::InvalidData(format!("拡大率は 0 より大きく {} 以下で指定してください: {}", MAX_EXPORT_SCALE, self.scale)));
+        }
+        if let PresetFormat::Jpeg { quality } = self.format {
+            if !(1..=100).contains(&quality) {
+                return Err(FormatError::InvalidData(format!("JPEG の品質は 1〜100 で指定してください: {}", quality)));
+            }
+        }
+        self.background.validate()?;
+        if let Some(FrameRange { first, last: Some(last) }) = self.frame_range {
+            if first > last {
+                return Err(FormatError::InvalidData(format!("フレームの範囲が不正です: {}〜{}", first, last)));
+            }
+        }
+        Ok(())
+    }
+
+    /// `frame_count` フレームのうち書き出すフレームの位置
+    pub fn frame_indices(&self, frame_count: usize) -> Range<usize> {
+        match self.frame_range {
+            None => 0..frame_count,
+            Some(FrameRange { first, last }) => {
+                let end = last.map_or(frame_count, |last| (last + 1).min(frame_count));
+                first.min(end)..end
+            }
+        }
+    }
+
+    /// 書き出す範囲の `index` 番目（0始まり）のファイル名（番号は1始まりで、枚数に合わせて桁を揃える）
+    pub fn file_name(&self, index: usize, count: usize) -> String {
+        let digits = count.to_string().len().max(MIN_INDEX_DIGITS);
+        format!("{}_{:0digits$}.{}", self.file_prefix.trim(), index + 1, self.format.extension(), digits = digits)
+    }
+
+    /// 拡大・縮小した後の大きさ
+    pub fn output_size(&self, width: u32, height: u32) -> (u32, u32) {
+        scaled_size((width, height), self.scale)
+    }
+
+    /// 合成の下地にするマット色（JPEG で背景が透明なら白）
+    pub fn matte(&self) -> Option<Rgba<u8>> {
+        match (self.background.matte(), self.format) {
+            (Some(color), _) => Some(color),
+            (None, PresetFormat::Jpeg { .. }) => Some(Rgba([255, 255, 255, 255])),
+            (None, PresetFormat::Png) => None,
+        }
+    }
+
+    /// 合成・拡大縮小済みのフレームをエンコードする（マット色は合成時に敷いておく）
+    pub fn encode(&self, frame: &RgbaImage) -> Result<Vec<u8>, FormatError> {
+        match self.format {
+            PresetFormat::Png => encode_png(frame),
+            PresetFormat::Jpeg { quality } => {
+                let rgb = image::DynamicImage::ImageRgba8(frame.clone()).into_rgb8();
+                let mut jpeg = Vec::new();
+                image::codecs::jpeg::JpegEncoder::new_with_quality(&mut jpeg, quality)
+                    .encode_image(&rgb)
+                    .map_err(|e| FormatError::InvalidData(format!("JPEGエンコードに失敗: {}", e)))?;
+                Ok(jpeg)
+            }
+        }
+    }
+}
+
+/// 拡大率を掛けた大きさ（四捨五入、最低1ピクセル）
+pub fn scaled_size((width, height): (u32, u32), scale: f32) -> (u32, u32) {
+    let scale = |length: u32| ((length as f32 * scale).round() as u32).max(1);
+    (scale(width), scale(height))
+}
+
+/// プリセットの一覧を検証する（名前の重複も不可）
+pub fn validate_presets(presets: &[ExportPreset]) -> Result<(), FormatError> {
+    let mut names = HashSet::new();
+    for preset in presets {
+        preset.validate()?;
+        if !names.insert(preset.name.trim()) {
+            return Err(FormatError::InvalidData(format!("プリセット名が重複しています: {}", preset.name)));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str) -> ExportPreset {
+        ExportPreset { name: name.to_string(), ..ExportPreset::default() }
+    }
+
+    #[test]
+    fn test_validate_and_frame_indices() {
+        assert!(preset("web").validate().is_ok());
+        assert!(preset("").validate().is_err());
+        assert!(preset("../web").validate().is_err());
+        assert!(ExportPreset { scale: 0.0, ..preset("web") }.validate().is_err());
+        assert!(ExportPreset { format: PresetFormat::Jpeg { quality: 0 }, ..preset("web") }.validate().is_err());
+        let reversed = FrameRange { first: 3, last: Some(1) };
+        assert!(ExportPreset { frame_range: Some(reversed), ..preset("web") }.validate().is_err());
+        assert!(validate_presets(&[preset("web"), preset("print")]).is_ok());
+        assert!(validate_presets(&[preset("web"), preset("web ")]).is_err());
+
+        assert_eq!(preset("web").frame_indices(5), 0..5);
+        let range = |first, last| ExportPreset { frame_range: Some(FrameRange { first, last }), ..preset("web") };
+        assert_eq!(range(1, Some(2)).frame_indices(5), 1..3);
+        assert_eq!(range(3, None).frame_indices(5), 3..5);
+        assert_eq!(range(7, Some(9)).frame_indices(5), 5..5);
+
+        let jpeg = ExportPreset { format: PresetFormat::Jpeg { quality: 90 }, ..preset("web") };
+        assert_eq!(jpeg.file_name(0, 12), "frame_0001.jpg");
+    }
+
+    #[test]
+    fn test_matte_and_encode() {
+        let half = ExportPreset {
+            scale: 0.5,
+            background: ExportBackground::Matte { color: [0.0, 0.0, 1.0] },
+            ..preset("web")
+        };
+        assert_eq!(half.output_size(4, 2), (2, 1));
+        assert_eq!(half.output_size(1, 1), (1, 1));
+        assert_eq!(scaled_size((10, 3), 2.5), (25, 8));
+        assert_eq!(half.matte(), Some(Rgba([0, 0, 255, 255])));
+        assert_eq!(preset("web").matte(), None);
+        assert!(ExportPreset { background: ExportBackground::Matte { color: [0.0, -1.0, 0.0] }, ..preset("web") }.validate().is_err());
+
+        // PNG はアルファを残す
+        let frame = RgbaImage::from_pixel(4, 2, Rgba([255, 0, 0, 64]));
+        let decoded = image::load_from_memory(&preset("web").encode(&frame).unwrap()).unwrap().into_rgba8();
+        assert_eq!(decoded.dimensions(), (4, 2));
+        assert_eq!(decoded.get_pixel(0, 0), &Rgba([255, 0, 0, 64]));
+
+        let jpeg = ExportPreset { format: PresetFormat::Jpeg { quality: 100 }, ..preset("web") };
+        assert_eq!(jpeg.matte(), Some(Rgba([255, 255, 255, 255])));
+        let white = RgbaImage::from_pixel(4, 2, Rgba([255, 255, 255, 255]));
+        let decoded = image::load_from_memory(&jpeg.encode(&white).unwrap()).unwrap().into_rgb8();
+        assert!(decoded.get_pixel(1, 1).0.iter().all(|channel| *channel > 250));
+    }
+}
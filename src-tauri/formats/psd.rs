@@ -0,0 +1,665 @@
+use image::RgbaImage;
+use log::{info, debug, warn};
+use crate::animation::BlendMode;
+use super::{flatten_layers, FormatError, LayeredDocument, RasterLayer};
+
+/// PSD（バージョン1）で扱える最大の幅・高さ
+pub const PSD_MAX_DIMENSION: u32 = 30000;
+
+/// Photoshop が扱えるレイヤー数の上限
+pub const PSD_MAX_LAYERS: usize = 8000;
+
+/// PSD書き出しオプション
+#[derive(Debug, Clone, Default)]
+pub struct PsdWriteOptions {
+    /// レイヤーを統合した1枚の画像として書き出す
+    pub flatten: bool,
+}
+
+/// チャンネルID（R, G, B, 透明度）
+const CHANNEL_IDS: [i16; 4] = [0, 1, 2, -1];
+
+/// RGBAレイヤーをPSDファイルにエンコード（レイヤーは先頭が最背面）
+pub fn encode_psd(
+    layers: &[RasterLayer],
+    width: u32,
+    height: u32,
+    options: &PsdWriteOptions,
+) -> Result<Vec<u8>, FormatError> {
+    if width == 0 || height == 0 || width > PSD_MAX_DIMENSION || height > PSD_MAX_DIMENSION {
+        return Err(FormatError::DimensionsTooLarge(width, height));
+    }
+
+    let flatten = options.flatten || requires_flatten(layers);
+    if flatten && !options.flatten {
+        warn!("[PSD] レイヤー構成をPSDで表現できないため統合して書き出します");
+    }
+    info!("[PSD] 書き出し開始: {}x{} ({} レイヤー, 統合: {})", width, height, layers.len(), flatten);
+
+    let composite = flatten_layers(layers, width, height);
+    let flattened;
+    let file_layers = if flatten {
+        flattened = [RasterLayer::new("Background", composite.clone())];
+        &flattened[..]
+    } else {
+        layers
+    };
+
+    let mut out = Vec::new();
+
+    // ファイルヘッダー
+    out.extend_from_slice(b"8BPS");
+    write_u16(&mut out, 1);
+    out.extend_from_slice(&[0; 6]);
+    write_u16(&mut out, 4);
+    write_u32(&mut out, height);
+    write_u32(&mut out, width);
+    write_u16(&mut out, 8);
+    write_u16(&mut out, 3); // RGB
+
+    // カラーモードデータ・画像リソース（なし）
+    write_u32(&mut out, 0);
+    write_u32(&mut out, 0);
+
+    // レイヤーとマスク情報
+    let layer_info = encode_layer_info(file_layers);
+    write_u32(&mut out, layer_info.len() as u32 + 4 + 4);
+    write_u32(&mut out, layer_info.len() as u32);
+    out.extend_from_slice(&layer_info);
+    write_u32(&mut out, 0); // グローバルレイヤーマスク情報
+
+    // 統合画像（無圧縮、プレーナー）
+    write_u16(&mut out, 0);
+    for channel in 0..4 {
+        out.extend(composite.pixels().map(|p| p[channel]));
+    }
+
+    debug!("[PSD] 書き出し完了: {} bytes", out.len());
+    Ok(out)
+}
+
+/// レイヤーを保ったまま書き出せない構成か（統合して書き出す必要があるか）
+pub fn requires_flatten(layers: &[RasterLayer]) -> bool {
+    layers.len() > PSD_MAX_LAYERS || layers.iter().any(|layer| {
+        let (width, height) = layer.image.dimensions();
+        layer.offset.0.checked_add_unsigned(width).is_none()
+            || layer.offset.1.checked_add_unsigned(height).is_none()
+    })
+}
+
+/// レイヤー情報セクション（長さフィールドを除く）
+fn encode_layer_info(layers: &[RasterLayer]) -> Vec<u8> {
+    let mut info = Vec::new();
+    // 負の値は統合画像の先頭アルファチャンネルが透明度であることを示す
+    write_i16(&mut info, -(layers.len() as i16));
+
+    for layer in layers {
+        let (left, top) = layer.offset;
+        let (width, height) = layer.image.dimensions();
+        write_i32(&mut info, top);
+        write_i32(&mut info, left);
+        write_i32(&mut info, top + height as i32);
+        write_i32(&mut info, left + width as i32);
+
+        write_u16(&mut info, CHANNEL_IDS.len() as u16);
+        let channel_len = 2 + width * height;
+        for id in CHANNEL_IDS {
+            write_i16(&mut info, id);
+            write_u32(&mut info, channel_len);
+        }
+
+        info.extend_from_slice(b"8BIM");
+        info.extend_from_slice(blend_mode_key(&layer.blend_mode));
+        info.push((layer.opacity.clamp(0.0, 1.0) * 255.0).round() as u8);
+        info.push(0); // クリッピング
+        info.push(if layer.visible { 0 } else { 0x02 });
+        info.push(0);
+
+        let extra = encode_layer_extra(&layer.name);
+        write_u32(&mut info, extra.len() as u32);
+        info.extend_from_slice(&extra);
+    }
+
+    // チャンネル画像データ（無圧縮）
+    for layer in layers {
+        for channel in [0, 1, 2, 3] {
+            write_u16(&mut info, 0);
+            info.extend(layer.image.pixels().map(|p| p[channel]));
+        }
+    }
+
+    if info.len() % 2 != 0 {
+        info.push(0);
+    }
+    info
+}
+
+/// レイヤーの追加データ（マスク・ブレンド範囲・名前・Unicode名）
+fn encode_layer_extra(name: &str) -> Vec<u8> {
+    let mut extra = Vec::new();
+    write_u32(&mut extra, 0); // レイヤーマスク
+    write_u32(&mut extra, 0); // ブレンド範囲
+
+    // パスカル文字列（ASCII以外は Unicode 名で保持）
+    let ascii: Vec<u8> = name.chars()
+        .map(|c| if c.is_ascii() && !c.is_ascii_control() { c as u8 } else { b'?' })
+        .take(255)
+        .collect();
+    extra.push(ascii.len() as u8);
+    extra.extend_from_slice(&ascii);
+    while extra.len() % 4 != 0 {
+        extra.push(0);
+    }
+
+    // 追加情報ブロックの長さはパディング込みで4の倍数にする
+    let utf16: Vec<u16> = name.encode_utf16().collect();
+    let data_len = 4 + utf16.len() as u32 * 2;
+    let padded_len = data_len.div_ceil(4) * 4;
+    extra.extend_from_slice(b"8BIM");
+    extra.extend_from_slice(b"luni");
+    write_u32(&mut extra, padded_len);
+    write_u32(&mut extra, utf16.len() as u32);
+    for unit in utf16 {
+        write_u16(&mut extra, unit);
+    }
+    extra.resize(extra.len() + (padded_len - data_len) as usize, 0);
+    extra
+}
+
+/// ブレンドモードのPSDキー
+pub fn blend_mode_key(mode: &BlendMode) -> &'static [u8; 4] {
+    match mode {
+        BlendMode::Normal => b"norm",
+        BlendMode::Multiply => b"mul ",
+        BlendMode::Screen => b"scrn",
+        BlendMode::Overlay => b"over",
+    }
+}
+
+/// レイヤーレコードの読み込み中の情報
+struct LayerRecord {
+    top: i32,
+    left: i32,
+    width: u32,
+    height: u32,
+    channels: Vec<(i16, u32)>,
+    blend_key: [u8; 4],
+    opacity: u8,
+    visible: bool,
+    name: String,
+    /// グループの開始・終了マーカー（lsct）
+    is_group_marker: bool,
+}
+
+/// PSDファイルをデコード（8bit RGB、無圧縮・RLEのみ対応）
+///
+/// レイヤー情報が無いファイルは統合画像を1枚のレイヤーとして読み込む。
+pub fn decode_psd(data: &[u8]) -> Result<LayeredDocument, FormatError> {
+    let mut reader = ByteReader::new(data);
+
+    if reader.bytes(4)? != b"8BPS" {
+        return Err(FormatError::InvalidData("PSDシグネチャがありません".to_string()));
+    }
+    let version = reader.u16()?;
+    if version != 1 {
+        return Err(FormatError::Unsupported(format!("PSDバージョン {}（PSB形式）", version)));
+    }
+    reader.skip(6)?;
+    let channel_count = reader.u16()?;
+    let height = reader.u32()?;
+    let width = reader.u32()?;
+    let depth = reader.u16()?;
+    let color_mode = reader.u16()?;
+    if depth != 8 {
+        return Err(FormatError::Unsupported(format!("ビット深度 {}", depth)));
+    }
+    if color_mode != 3 {
+        return Err(FormatError::Unsupported(format!("カラーモード {}（RGBのみ対応）", color_mode)));
+    }
+    if width == 0 || height == 0 || width > PSD_MAX_DIMENSION || height > PSD_MAX_DIMENSION {
+        return Err(FormatError::DimensionsTooLarge(width, height));
+    }
+    info!("[PSD] 読み込み開始: {}x{} ({} チャンネル)", width, height, channel_count);
+
+    // カラーモードデータ・画像リソースは使わない
+    let len = reader.u32()? as usize;
+    reader.skip(len)?;
+    let len = reader.u32()? as usize;
+    reader.skip(len)?;
+
+    let section_len = reader.u32()? as usize;
+    let section_end = reader.pos + section_len;
+    let mut layers = Vec::new();
+    if section_len >= 4 {
+        let info_len = reader.u32()? as usize;
+        if info_len > 0 {
+            layers = decode_layer_info(&mut reader.sub(info_len)?)?;
+        }
+    }
+    reader.seek(section_end)?;
+
+    if layers.is_empty() {
+        debug!("[PSD] レイヤー情報なし - 統合画像を読み込み");
+        let composite = decode_composite(&mut reader, width, height, channel_count)?;
+        layers.push(RasterLayer::new("Background", composite));
+    }
+
+    info!("[PSD] 読み込み完了: {} レイヤー", layers.len());
+    Ok(LayeredDocument { width, height, layers })
+}
+
+fn decode_layer_info(reader: &mut ByteReader) -> Result<Vec<RasterLayer>, FormatError> {
+    let count = reader.i16()?.unsigned_abs() as usize;
+    let mut records = Vec::with_capacity(count);
+
+    for _ in 0..count {
+        let top = reader.i32()?;
+        let left = reader.i32()?;
+        let bottom = reader.i32()?;
+        let right = reader.i32()?;
+        if bottom < top || right < left {
+            return Err(FormatError::InvalidData(format!("レイヤー矩形が不正です: ({},{})-({},{})", left, top, right, bottom)));
+        }
+        let (width, height) = ((right - left) as u32, (bottom - top) as u32);
+        if width > PSD_MAX_DIMENSION || height > PSD_MAX_DIMENSION {
+            return Err(FormatError::DimensionsTooLarge(width, height));
+        }
+
+        let channel_count = reader.u16()?;
+        let mut channels = Vec::with_capacity(channel_count as usize);
+        for _ in 0..channel_count {
+            channels.push((reader.i16()?, reader.u32()?));
+        }
+
+        if reader.bytes(4)? != b"8BIM" {
+            return Err(FormatError::InvalidData("ブレンドモードのシグネチャが不正です".to_string()));
+        }
+        let mut blend_key = [0; 4];
+        blend_key.copy_from_slice(reader.bytes(4)?);
+        let opacity = reader.u8()?;
+        reader.skip(1)?;
+        let flags = reader.u8()?;
+        reader.skip(1)?;
+
+        let extra_len = reader.u32()? as usize;
+        let (name, is_group_marker) = decode_layer_extra(&mut reader.sub(extra_len)?)?;
+
+        records.push(LayerRecord {
+            top,
+            left,
+            width,
+            height,
+            channels,
+            blend_key,
+            opacity,
+            visible: flags & 0x02 == 0,
+            name,
+            is_group_marker,
+        });
+    }
+
+    let mut layers = Vec::with_capacity(records.len());
+    for record in records {
+        let mut image = RgbaImage::from_pixel(record.width, record.height, image::Rgba([0, 0, 0, 255]));
+        for &(id, len) in &record.channels {
+            let mut channel = reader.sub(len as usize)?;
+            let index = match id {
+                0..=2 => id as usize,
+                -1 => 3,
+                // ユーザーマスクなどは読み飛ばす
+                _ => continue,
+            };
+            if record.width == 0 || record.height == 0 {
+                continue;
+            }
+            let plane = decode_channel(&mut channel, record.width, record.height)?;
+            for (pixel, value) in image.pixels_mut().zip(plane) {
+                pixel[index] = value;
+            }
+        }
+
+        if record.is_group_marker {
+            debug!("[PSD] グループマーカーを読み飛ばし: {}", record.name);
+            continue;
+        }
+
+        let blend_mode = blend_mode_from_key(&record.blend_key).unwrap_or_else(|| {
+            warn!("[PSD] 未対応のブレンドモード {:?} は通常として読み込みます: {}",
+                  String::from_utf8_lossy(&record.blend_key), record.name);
+            BlendMode::Normal
+        });
+        layers.push(RasterLayer {
+            name: record.name,
+            visible: record.visible,
+            opacity: record.opacity as f32 / 255.0,
+            blend_mode,
+            offset: (record.left, record.top),
+            image,
+            effects: Vec::new(),
+        });
+    }
+
+    Ok(layers)
+}
+
+/// 追加データから名前（Unicode名を優先）とグループマーカーかどうかを取り出す
+fn decode_layer_extra(reader: &mut ByteReader) -> Result<(String, bool), FormatError> {
+    let mask_len = reader.u32()? as usize;
+    reader.skip(mask_len)?;
+    let ranges_len = reader.u32()? as usize;
+    reader.skip(ranges_len)?;
+
+    let name_len = reader.u8()? as usize;
+    let mut name = String::from_utf8_lossy(reader.bytes(name_len)?).to_string();
+    let padding = (4 - (name_len + 1) % 4) % 4;
+    reader.skip(padding.min(reader.remaining()))?;
+
+    let mut is_group_marker = false;
+    while reader.remaining() >= 12 {
+        let signature = reader.bytes(4)?;
+        if signature != b"8BIM" && signature != b"8B64" {
+            break;
+        }
+        let mut key = [0; 4];
+        key.copy_from_slice(reader.bytes(4)?);
+        let len = reader.u32()? as usize;
+        let mut block = reader.sub(len.min(reader.remaining()))?;
+        match &key {
+            b"luni" => {
+                let count = block.u32()? as usize;
+                let units = (0..count).map(|_| block.u16()).collect::<Result<Vec<_>, _>>()?;
+                name = String::from_utf16_lossy(&units);
+            }
+            b"lsct" | b"lsdk" => {
+                // 1, 2: グループ開始, 3: グループ終了（境界）
+                is_group_marker = block.u32()? != 0;
+            }
+            _ => {}
+        }
+    }
+
+    Ok((name, is_group_marker))
+}
+
+/// 統合画像セクションの読み込み
+fn decode_composite(
+    reader: &mut ByteReader,
+    width: u32,
+    height: u32,
+    channel_count: u16,
+) -> Result<RgbaImage, FormatError> {
+    let compression = reader.u16()?;
+    let plane_len = (width * height) as usize;
+    let used_channels = channel_count.min(4) as usize;
+
+    let planes: Vec<Vec<u8>> = match compression {
+        0 => (0..used_channels)
+            .map(|_| reader.bytes(plane_len).map(|b| b.to_vec()))
+            .collect::<Result<_, _>>()?,
+        1 => {
+            // 全チャンネル分の行バイト数が先に並ぶ
+            let row_counts = (0..channel_count as usize * height as usize)
+                .map(|_| reader.u16())
+                .collect::<Result<Vec<_>, _>>()?;
+            let mut planes = Vec::with_capacity(used_channels);
+            for channel in 0..used_channels {
+                let counts = &row_counts[channel * height as usize..(channel + 1) * height as usize];
+                planes.push(decode_rle_rows(reader, counts, width)?);
+            }
+            planes
+        }
+        other => return Err(FormatError::Unsupported(format!("圧縮方式 {}", other))),
+    };
+    if planes.len() < 3 {
+        return Err(FormatError::Unsupported(format!("チャンネル数 {}", channel_count)));
+    }
+
+    let mut image = RgbaImage::from_pixel(width, height, image::Rgba([0, 0, 0, 255]));
+    for (index, plane) in planes.iter().enumerate() {
+        for (pixel, value) in image.pixels_mut().zip(plane) {
+            pixel[index] = *value;
+        }
+    }
+    Ok(image)
+}
+
+/// レイヤーの1チャンネルを読み込み
+fn decode_channel(reader: &mut ByteReader, width: u32, height: u32) -> Result<Vec<u8>, FormatError> {
+    match reader.u16()? {
+        0 => Ok(reader.bytes((width * height) as usize)?.to_vec()),
+        1 => {
+            let row_counts = (0..height).map(|_| reader.u16()).collect::<Result<Vec<_>, _>>()?;
+            decode_rle_rows(reader, &row_counts, width)
+        }
+        other => Err(FormatError::Unsupported(format!("圧縮方式 {}", other))),
+    }
+}
+
+/// PackBits で圧縮された行を展開
+fn decode_rle_rows(reader: &mut ByteReader, row_counts: &[u16], width: u32) -> Result<Vec<u8>, FormatError> {
+    let mut plane = Vec::with_capacity(width as usize * row_counts.len());
+    for &count in row_counts {
+        let mut row = ByteReader::new(reader.bytes(count as usize)?);
+        let row_start = plane.len();
+        while row.remaining() > 0 && plane.len() - row_start < width as usize {
+            let header = row.u8()? as i8;
+            if header >= 0 {
+                plane.extend_from_slice(row.bytes(header as usize + 1)?);
+            } else if header != -128 {
+                let value = row.u8()?;
+                plane.extend(std::iter::repeat_n(value, (1 - header as isize) as usize));
+            }
+        }
+        plane.resize(row_start + width as usize, 0);
+    }
+    Ok(plane)
+}
+
+/// PSDのブレンドモードキーから変換（対応しないモードは None）
+pub fn blend_mode_from_key(key: &[u8; 4]) -> Option<BlendMode> {
+    match key {
+        b"norm" => Some(BlendMode::Normal),
+        b"mul " => Some(BlendMode::Multiply),
+        b"scrn" => Some(BlendMode::Screen),
+        b"over" => Some(BlendMode::Overlay),
+        _ => None,
+    }
+}
+
+/// ビッグエンディアンのバイト列リーダー
+struct ByteReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ByteReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], FormatError> {
+        if len > self.remaining() {
+            return Err(FormatError::InvalidData(format!("ファイルが途中で終わっています (位置 {})", self.pos)));
+        }
+        let bytes = &self.data[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    /// 指定長の範囲を切り出したリーダー
+    fn sub(&mut self, len: usize) -> Result<ByteReader<'a>, FormatError> {
+        Ok(ByteReader::new(self.bytes(len)?))
+    }
+
+    fn skip(&mut self, len: usize) -> Result<(), FormatError> {
+        self.bytes(len).map(|_| ())
+    }
+
+    fn seek(&mut self, pos: usize) -> Result<(), FormatError> {
+        if pos > self.data.len() {
+            return Err(FormatError::InvalidData(format!("ファイルが途中で終わっています (位置 {})", pos)));
+        }
+        self.pos = pos;
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, FormatError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, FormatError> {
+        Ok(u16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn i16(&mut self) -> Result<i16, FormatError> {
+        Ok(i16::from_be_bytes(self.bytes(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32, FormatError> {
+        Ok(u32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+
+    fn i32(&mut self) -> Result<i32, FormatError> {
+        Ok(i32::from_be_bytes(self.bytes(4)?.try_into().unwrap()))
+    }
+}
+
+fn write_u16(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_i16(out: &mut Vec<u8>, value: i16) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_u32(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+fn write_i32(out: &mut Vec<u8>, value: i32) {
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    fn read_u32(data: &[u8], offset: usize) -> u32 {
+        u32::from_be_bytes(data[offset..offset + 4].try_into().unwrap())
+    }
+
+    fn read_i16(data: &[u8], offset: usize) -> i16 {
+        i16::from_be_bytes(data[offset..offset + 2].try_into().unwrap())
+    }
+
+    fn sample_layers() -> Vec<RasterLayer> {
+        let background = RasterLayer::new("背景", RgbaImage::from_pixel(3, 2, Rgba([255, 255, 255, 255])));
+        let mut ink = RasterLayer::new("ink", RgbaImage::from_pixel(3, 2, Rgba([0, 0, 0, 128])));
+        ink.blend_mode = BlendMode::Multiply;
+        ink.opacity = 0.5;
+        vec![background, ink]
+    }
+
+    #[test]
+    fn test_header_and_sections() {
+        let data = encode_psd(&sample_layers(), 3, 2, &PsdWriteOptions::default()).unwrap();
+
+        assert_eq!(&data[0..4], b"8BPS");
+        assert_eq!(read_u32(&data, 14), 2); // 高さ
+        assert_eq!(read_u32(&data, 18), 3); // 幅
+
+        // ヘッダー(26) + カラーモード(4) + 画像リソース(4) の後にレイヤー情報
+        let section_len = read_u32(&data, 34) as usize;
+        let layer_info_len = read_u32(&data, 38) as usize;
+        assert_eq!(section_len, layer_info_len + 8);
+        assert_eq!(layer_info_len % 2, 0);
+        assert_eq!(read_i16(&data, 42), -2);
+
+        // 統合画像: 圧縮方式 + 4チャンネル × 6ピクセル
+        let image_data = 38 + section_len;
+        assert_eq!(data.len(), image_data + 2 + 4 * 6);
+    }
+
+    #[test]
+    fn test_layer_record_fields() {
+        let data = encode_psd(&sample_layers(), 3, 2, &PsdWriteOptions::default()).unwrap();
+
+        // 1枚目のレイヤーレコード: 矩形(16) + チャンネル数(2) + チャンネル情報(6×4)
+        let record = 44;
+        assert_eq!(read_u32(&data, record + 8), 2);
+        assert_eq!(read_u32(&data, record + 12), 3);
+        let blend = record + 18 + 24;
+        assert_eq!(&data[blend..blend + 8], b"8BIMnorm");
+
+        let extra_len = read_u32(&data, blend + 12) as usize;
+        let second = blend + 16 + extra_len;
+        let blend = second + 18 + 24;
+        assert_eq!(&data[blend..blend + 8], b"8BIMmul ");
+        assert_eq!(data[blend + 8], 128);
+    }
+
+    #[test]
+    fn test_flatten_option_writes_single_layer() {
+        let data = encode_psd(&sample_layers(), 3, 2, &PsdWriteOptions { flatten: true }).unwrap();
+        assert_eq!(read_i16(&data, 42), -1);
+    }
+
+    #[test]
+    fn test_decode_roundtrip() {
+        let mut layers = sample_layers();
+        layers[1].visible = false;
+        layers[1].offset = (1, -1);
+        let data = encode_psd(&layers, 3, 2, &PsdWriteOptions::default()).unwrap();
+
+        let document = decode_psd(&data).unwrap();
+        assert_eq!((document.width, document.height), (3, 2));
+        assert_eq!(document.layers.len(), 2);
+        assert_eq!(document.layers[0].name, "背景");
+        assert_eq!(document.layers[0].image, layers[0].image);
+
+        let ink = &document.layers[1];
+        assert_eq!(ink.name, "ink");
+        assert!(matches!(ink.blend_mode, BlendMode::Multiply));
+        assert!(!ink.visible);
+        assert_eq!(ink.offset, (1, -1));
+        assert!((ink.opacity - 128.0 / 255.0).abs() < 1e-6);
+        assert_eq!(ink.image, layers[1].image);
+    }
+
+    #[test]
+    fn test_decode_rle_rows() {
+        // 3バイトのリテラル + 4回の繰り返し
+        let row = [2u8, 1, 2, 3, 0xFD, 9];
+        let mut reader = ByteReader::new(&row);
+        let plane = decode_rle_rows(&mut reader, &[row.len() as u16], 7).unwrap();
+        assert_eq!(plane, vec![1, 2, 3, 9, 9, 9, 9]);
+    }
+
+    #[test]
+    fn test_decode_rejects_truncated_file() {
+        let data = encode_psd(&sample_layers(), 3, 2, &PsdWriteOptions::default()).unwrap();
+        assert!(matches!(decode_psd(&data[..60]), Err(FormatError::InvalidData(_))));
+        assert!(matches!(decode_psd(b"PK\x03\x04"), Err(FormatError::InvalidData(_))));
+    }
+
+    #[test]
+    fn test_too_many_layers_fall_back_to_flatten() {
+        let layers = vec![RasterLayer::new("cel", RgbaImage::new(1, 1)); PSD_MAX_LAYERS + 1];
+        assert!(requires_flatten(&layers));
+        assert!(!requires_flatten(&sample_layers()));
+
+        let data = encode_psd(&layers, 1, 1, &PsdWriteOptions::default()).unwrap();
+        assert_eq!(read_i16(&data, 42), -1);
+    }
+
+    #[test]
+    fn test_rejects_oversized_canvas() {
+        let result = encode_psd(&[], PSD_MAX_DIMENSION + 1, 1, &PsdWriteOptions::default());
+        assert!(matches!(result, Err(FormatError::DimensionsTooLarge(_, _))));
+    }
+}
@@ -0,0 +1,216 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use crate::drawing_engine::ContentBounds;
+use crate::selection::Selection;
+use super::flood::{dilate, normalized, FloodFillOptions, LINEART_OVERLAP};
+
+/// 線画で囲まれた領域
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RegionInfo {
+    /// 領域ID（1 から。0 は線）
+    pub id: u32,
+    /// 画素数
+    pub area: usize,
+    pub bounds: ContentBounds,
+    /// 画像の端に接している（線画の外側の背景）
+    pub touches_border: bool,
+}
+
+/// 線画の領域分割（画素ごとの領域ID）
+///
+/// 透明な背景の線画レイヤーを前提に、透明から `tolerance` より離れた画素を線とみなす。
+/// `gap_closing` は塗りつぶしと同じく線を太らせて領域を分け、分けたあと線の際まで領域を広げ直す。
+#[derive(Debug, Clone)]
+pub struct RegionMap {
+    width: u32,
+    height: u32,
+    labels: Vec<u32>,
+    regions: Vec<RegionInfo>,
+    /// 分割したときの線画と設定の世代（変わったら作り直す）
+    revision: u64,
+}
+
+impl RegionMap {
+    /// 線画を領域に分割する（`revision` は線画の内容と設定から作った世代）
+    pub fn segment(lineart: &RgbaImage, options: &FloodFillOptions, revision: u64) -> Self {
+        let (width, height) = lineart.dimensions();
+        let (w, h) = (width as usize, height as usize);
+        let boundary: Vec<bool> = lineart.pixels()
+            .map(|pixel| normalized(*pixel).iter().any(|channel| *channel > options.tolerance))
+            .collect();
+        let radius = options.gap_closing as usize;
+        let walls = if radius > 0 { dilate(&boundary, w, h, radius) } else { boundary.clone() };
+
+        // 4近傍でつながる線以外の画素に同じIDを振る
+        let mut labels = vec![0u32; w * h];
+        let mut next_id = 1;
+        let mut queue = VecDeque::new();
+        for start in 0..w * h {
+            if walls[start] || labels[start] != 0 {
+                continue;
+            }
+            labels[start] = next_id;
+            queue.push_back(start);
+            while let Some(index) = queue.pop_front() {
+                for next in neighbors(index, w, h) {
+                    if !walls[next] && labels[next] == 0 {
+                        labels[next] = next_id;
+                        queue.push_back(next);
+                    }
+                }
+            }
+            next_id += 1;
+        }
+
+        // 太らせた分を隣の領域から1画素ずつ広げ直す（元の線は越えない）
+        let mut frontier: Vec<usize> = (0..w * h).filter(|&index| labels[index] != 0).collect();
+        for _ in 0..radius {
+            let mut grown = Vec::new();
+            for &index in &frontier {
+                for next in neighbors(index, w, h) {
+                    if labels[next] == 0 && !boundary[next] {
+                        labels[next] = labels[index];
+                        grown.push(next);
+                    }
+                }
+            }
+            frontier = grown;
+        }
+
+        let mut regions: Vec<RegionInfo> = (1..next_id)
+            .map(|id| RegionInfo {
+                id,
+                area: 0,
+                bounds: ContentBounds { x: u32::MAX, y: u32::MAX, width: 0, height: 0 },
+                touches_border: false,
+            })
+            .collect();
+        let mut extents = vec![(u32::MAX, u32::MAX, 0u32, 0u32); regions.len()];
+        for (index, &label) in labels.iter().enumerate().filter(|(_, label)| **label != 0) {
+            let (x, y) = ((index % w) as u32, (index / w) as u32);
+            let region = &mut regions[label as usize - 1];
+            region.area += 1;
+            region.touches_border |= x == 0 || y == 0 || x == width - 1 || y == height - 1;
+            let extent = &mut extents[label as usize - 1];
+            *extent = (extent.0.min(x), extent.1.min(y), extent.2.max(x), extent.3.max(y));
+        }
+        for (region, (left, top, right, bottom)) in regions.iter_mut().zip(extents) {
+            region.bounds = ContentBounds { x: left, y: top, width: right - left + 1, height: bottom - top + 1 };
+        }
+
+        Self { width, height, labels, regions, revision }
+    }
+
+    pub fn regions(&self) -> &[RegionInfo] {
+        &self.regions
+    }
+
+    /// 位置の領域ID（線の上や範囲外は None）
+    pub fn region_at(&self, x: f32, y: f32) -> Option<u32> {
+        if !(x >= 0.0 && y >= 0.0 && x < self.width as f32 && y < self.height as f32) {
+            return None;
+        }
+        let label = self.labels[y as usize * self.width as usize + x as usize];
+        (label != 0).then_some(label)
+    }
+
+    /// 領域の中の1画素（上の行から探して最初の画素。ない領域は None）
+    ///
+    /// 別のフレームの線画で同じ場所の領域を塗るときの起点に使う。
+    pub fn seed_point(&self, id: u32) -> Option<(u32, u32)> {
+        let region = self.regions.get((id as usize).checked_sub(1)?)?;
+        let bounds = region.bounds;
+        (bounds.y..bounds.y + bounds.height)
+            .flat_map(|y| (bounds.x..bounds.x + bounds.width).map(move |x| (x, y)))
+            .find(|&(x, y)| self.labels[y as usize * self.width as usize + x as usize] == id)
+    }
+
+    /// 分割したときの線画の状態のままか
+    pub fn is_current(&self, revision: u64) -> bool {
+        self.revision == revision
+    }
+
+    /// 領域を塗る選択範囲（線の下まで `LINEART_OVERLAP` ピクセル広げる。ない領域は None）
+    pub fn selection(&self, id: u32) -> Option<Selection> {
+        let region = self.regions.get((id as usize).checked_sub(1)?)?;
+        let pad = LINEART_OVERLAP as u32;
+        let bounds = region.bounds.padded(pad, (self.width, self.height));
+        let (w, h) = (bounds.width as usize, bounds.height as usize);
+        let inside: Vec<bool> = (0..bounds.height)
+            .flat_map(|y| (0..bounds.width).map(move |x| (x, y)))
+            .map(|(x, y)| self.labels[(bounds.y + y) as usize * self.width as usize + (bounds.x + x) as usize] == id)
+            .collect();
+        let mask = dilate(&inside, w, h, LINEART_OVERLAP).into_iter()
+            .map(|inside| if inside { 255 } else { 0 })
+            .collect();
+        Some(Selection {
+            x: bounds.x as i32,
+            y: bounds.y as i32,
+            width: bounds.width,
+            height: bounds.height,
+            mask: Some(mask),
+        })
+    }
+}
+
+fn neighbors(index: usize, width: usize, height: usize) -> impl Iterator<Item = usize> {
+    let (x, y) = (index % width, index / width);
+    [
+        (x > 0).then(|| index - 1),
+        (x + 1 < width).then(|| index + 1),
+        (y > 0).then(|| index - width),
+        (y + 1 < height).then(|| index + width),
+    ]
+    .into_iter()
+    .flatten()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    /// 縦線で左右に分かれた 1px の枠（右の枠には 2px の隙間）
+    fn lineart() -> RgbaImage {
+        RgbaImage::from_fn(24, 12, |x, y| {
+            let frame = ((x == 2 || x == 11 || x == 20) && (2..=9).contains(&y))
+                || ((y == 2 || y == 9) && (2..=20).contains(&x));
+            let gap = x == 20 && (5..=6).contains(&y);
+            if frame && !gap { Rgba([0, 0, 0, 255]) } else { Rgba([0, 0, 0, 0]) }
+        })
+    }
+
+    #[test]
+    fn test_segment_closed_regions() {
+        let map = RegionMap::segment(&lineart(), &FloodFillOptions::default(), 1);
+        // 外側と左の枠の中（右の枠は隙間から外側とつながる）
+        assert_eq!(map.regions().len(), 2);
+        let outside = map.region_at(0.0, 0.0).unwrap();
+        let left = map.region_at(5.0, 5.0).unwrap();
+        assert_eq!(map.region_at(15.0, 5.0), Some(outside));
+        assert!(map.regions()[outside as usize - 1].touches_border);
+        assert_eq!(map.regions()[left as usize - 1].area, 8 * 6);
+        assert_eq!(map.regions()[left as usize - 1].bounds, ContentBounds { x: 3, y: 3, width: 8, height: 6 });
+        assert_eq!(map.region_at(2.0, 5.0), None);
+
+        // 隙間を閉じると右の枠も別の領域になり、線の際まで広がる
+        let options = FloodFillOptions { gap_closing: 1, ..FloodFillOptions::default() };
+        let map = RegionMap::segment(&lineart(), &options, 1);
+        assert_eq!(map.regions().len(), 3);
+        let right = map.region_at(15.0, 5.0).unwrap();
+        assert_eq!(map.regions()[right as usize - 1].bounds, ContentBounds { x: 12, y: 3, width: 8, height: 6 });
+        assert!(map.is_current(1));
+        assert!(!map.is_current(2));
+
+        // 塗る範囲は線の下まで1ピクセル広げる
+        let selection = map.selection(right).unwrap();
+        assert_eq!((selection.x, selection.y, selection.width, selection.height), (11, 2, 10, 8));
+        assert_eq!(selection.coverage(11, 5), 255);
+        assert_eq!(selection.coverage(10, 5), 0);
+        assert_eq!(map.seed_point(right), Some((13, 3)));
+        assert_eq!(map.seed_point(4), None);
+        assert!(map.selection(0).is_none());
+        assert!(map.selection(4).is_none());
+    }
+}
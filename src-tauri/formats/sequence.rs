@@ -0,0 +1,99 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use super::{ExportBackground, FormatError};
+use crate::animation::SceneExportMode;
+
+/// 連番書き出しで同時に処理中のフレームが使ってよいメモリの既定値（バイト）
+pub const DEFAULT_SEQUENCE_MEMORY_BUDGET: u64 = 512 * 1024 * 1024;
+
+/// 連番ファイル名の番号の最小桁数
+pub(crate) const MIN_INDEX_DIGITS: usize = 4;
+
+/// 連番画像書き出しの設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct FrameSequenceOptions {
+    /// ファイル名の接頭辞（`<接頭辞>_0001.png` の形式になる）
+    pub file_prefix: String,
+    /// 同時に処理中のフレーム（読み出したレイヤーと合成結果）が使ってよいメモリ（バイト）
+    pub memory_budget_bytes: u64,
+    /// 書き出すシーン（既定はシーンを区別せず全フレーム）
+    pub scenes: SceneExportMode,
+    /// アルファを残すか、マット色の上に平坦化するか
+    pub background: ExportBackground,
+}
+
+impl Default for FrameSequenceOptions {
+    fn default() -> Self {
+        Self {
+            file_prefix: "frame".to_string(),
+            memory_budget_bytes: DEFAULT_SEQUENCE_MEMORY_BUDGET,
+            scenes: SceneExportMode::default(),
+            background: ExportBackground::default(),
+        }
+    }
+}
+
+impl FrameSequenceOptions {
+    pub fn validate(&self) -> Result<(), FormatError> {
+        let prefix = self.file_prefix.trim();
+        if prefix.is_empty() || prefix.contains(['/', '\\']) || prefix.starts_with('.') {
+            return Err(FormatError::InvalidData(format!("ファイル名の接頭辞が不正です: '{}'", self.file_prefix)));
+        }
+        if self.memory_budget_bytes == 0 {
+            return Err(FormatError::InvalidData("メモリ上限は1以上である必要があります".to_string()));
+        }
+        self.background.validate()
+    }
+
+    /// `index` 番目（0始まり）のフレームのファイル名（番号は1始まりで、フレーム数に合わせて桁を揃える）
+    pub fn file_name(&self, index: usize, frame_count: usize) -> String {
+        let digits = frame_count.to_string().len().max(MIN_INDEX_DIGITS);
+        format!("{}_{:0digits$}.png", self.file_prefix.trim(), index + 1, digits = digits)
+    }
+
+    /// 同時に処理するフレーム数（メモリ上限とワーカー数の小さい方。最低1）
+    ///
+    /// 1フレームはレイヤーの画像に合成結果とPNGの作業領域を加えた分として見積もる。
+    pub fn frames_in_flight(&self, width: u32, height: u32, max_layers: usize, workers: usize) -> usize {
+        let image_bytes = width as u64 * height as u64 * 4;
+        let frame_bytes = (image_bytes * (max_layers as u64 + 2)).max(1);
+        ((self.memory_budget_bytes / frame_bytes) as usize).clamp(1, workers.max(1))
+    }
+}
+
+/// 合成済みのフレームをPNGにエンコード
+pub fn encode_png(image: &RgbaImage) -> Result<Vec<u8>, FormatError> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| FormatError::InvalidData(format!("PNGエンコードに失敗: {}", e)))?;
+    Ok(png.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_names_and_validation() {
+        let options = FrameSequenceOptions::default();
+        assert!(options.validate().is_ok());
+        assert_eq!(options.file_name(0, 500), "frame_0001.png");
+        assert_eq!(options.file_name(12344, 12345), "frame_12345.png");
+
+        for prefix in ["", "a/b", "..", "c\\d"] {
+            let options = FrameSequenceOptions { file_prefix: prefix.to_string(), ..Default::default() };
+            assert!(options.validate().is_err(), "{}", prefix);
+        }
+    }
+
+    #[test]
+    fn test_frames_in_flight_respects_budget() {
+        // 1920x1080 の3レイヤーは1フレーム約 41MB
+        let options = FrameSequenceOptions { memory_budget_bytes: 100 * 1024 * 1024, ..Default::default() };
+        assert_eq!(options.frames_in_flight(1920, 1080, 3, 8), 2);
+        assert_eq!(options.frames_in_flight(64, 64, 3, 8), 8);
+        // 上限より大きいフレームでも1枚ずつは処理する
+        assert_eq!(options.frames_in_flight(8192, 8192, 10, 8), 1);
+    }
+}
@@ -0,0 +1,402 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use log::debug;
+
+// 透視ガイド
+pub mod perspective;
+pub use perspective::PerspectiveGuide;
+// セーフエリアと画面比のガイド
+pub mod safe_area;
+pub use safe_area::SafeAreaGuide;
+
+/// 等角グリッドの線の傾き（30度）の tan
+const ISOMETRIC_TAN: f32 = 0.577_350_26;
+
+/// ガイド・グリッドのエラー型
+#[derive(Debug)]
+pub enum GuideError {
+    InvalidSettings(String),
+    GuideNotFound(String),
+}
+
+impl fmt::Display for GuideError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GuideError::InvalidSettings(msg) => write!(f, "ガイド設定が不正です: {}", msg),
+            GuideError::GuideNotFound(id) => write!(f, "ガイドが見つかりません: {}", id),
+        }
+    }
+}
+
+impl Error for GuideError {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GuideOrientation {
+    /// 水平線（position は y 座標）
+    Horizontal,
+    /// 垂直線（position は x 座標）
+    Vertical,
+}
+
+/// キャンバス上の直線ガイド
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Guide {
+    pub id: String,
+    pub orientation: GuideOrientation,
+    pub position: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GridKind {
+    /// 正方形のピクセルグリッド
+    Square,
+    /// 垂直線と ±30度の線からなる等角グリッド（spacing は平行線の間隔）
+    Isometric,
+}
+
+/// グリッド設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GridSettings {
+    pub enabled: bool,
+    pub kind: GridKind,
+    pub spacing: f32,
+    pub origin: (f32, f32),
+    pub color: [u8; 4],
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            kind: GridKind::Square,
+            spacing: 32.0,
+            origin: (0.0, 0.0),
+            color: [128, 128, 128, 96],
+        }
+    }
+}
+
+impl GridSettings {
+    /// 最も近いグリッドの交点
+    pub fn nearest_point(&self, (x, y): (f32, f32)) -> (f32, f32) {
+        let (ox, oy) = self.origin;
+        let spacing = self.spacing;
+        match self.kind {
+            GridKind::Square => (
+                ox + ((x - ox) / spacing).round() * spacing,
+                oy + ((y - oy) / spacing).round() * spacing,
+            ),
+            GridKind::Isometric => {
+                // 交点は (i * spacing, j * spacing * tan30) のうち i + j が偶数の点
+                let row_height = spacing * ISOMETRIC_TAN;
+                let i0 = ((x - ox) / spacing).round() as i64;
+                let j0 = ((y - oy) / row_height).round() as i64;
+
+                let mut best = (ox, oy);
+                let mut best_distance = f32::MAX;
+                for i in i0 - 1..=i0 + 1 {
+                    for j in j0 - 1..=j0 + 1 {
+                        if (i + j) % 2 != 0 {
+                            continue;
+                        }
+                        let candidate = (ox + i as f32 * spacing, oy + j as f32 * row_height);
+                        let distance = (candidate.0 - x).powi(2) + (candidate.1 - y).powi(2);
+                        if distance < best_distance {
+                            best = candidate;
+                            best_distance = distance;
+                        }
+                    }
+                }
+                best
+            }
+        }
+    }
+
+    /// ピクセルがグリッド線上（線幅1px）にあるか
+    fn covers_pixel(&self, (x, y): (f32, f32)) -> bool {
+        let (dx, dy) = (x - self.origin.0, y - self.origin.1);
+        let on_line = |distance: f32| {
+            let offset = distance / self.spacing;
+            (offset - offset.round()).abs() * self.spacing < 0.5
+        };
+        match self.kind {
+            GridKind::Square => on_line(dx) || on_line(dy),
+            GridKind::Isometric => {
+                // ±30度の線は法線 (∓sin30, cos30) 方向に spacing 間隔で並ぶ
+                let (sin, cos) = (0.5, 0.866_025_4);
+                on_line(dx) || on_line(-sin * dx + cos * dy) || on_line(sin * dx + cos * dy)
+            }
+        }
+    }
+}
+
+/// スナップ設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SnapSettings {
+    pub enabled: bool,
+    pub to_guides: bool,
+    pub to_grid: bool,
+    /// ガイドに吸着する距離（ピクセル）
+    pub threshold: f32,
+}
+
+impl Default for SnapSettings {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            to_guides: true,
+            to_grid: true,
+            threshold: 8.0,
+        }
+    }
+}
+
+/// プロジェクトに保存されるガイド・グリッド・スナップの設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GuideSettings {
+    /// オーバーレイを表示するか
+    pub visible: bool,
+    pub guides: Vec<Guide>,
+    pub guide_color: [u8; 4],
+    pub grid: GridSettings,
+    pub snap: SnapSettings,
+    #[serde(default)]
+    pub perspective: PerspectiveGuide,
+    /// セーフエリアと画面比（表示のみで書き出しには含めない）
+    #[serde(default)]
+    pub safe_area: SafeAreaGuide,
+}
+
+impl Default for GuideSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            guides: Vec::new(),
+            guide_color: [0, 200, 255, 192],
+            grid: GridSettings::default(),
+            snap: SnapSettings::default(),
+            perspective: PerspectiveGuide::default(),
+            safe_area: SafeAreaGuide::default(),
+        }
+    }
+}
+
+impl GuideSettings {
+    pub fn validate(&self) -> Result<(), GuideError> {
+        if !(self.grid.spacing.is_finite() && self.grid.spacing >= 1.0) {
+            return Err(GuideError::InvalidSettings(format!("グリッド間隔は1以上である必要があります: {}", self.grid.spacing)));
+        }
+        if !(self.snap.threshold.is_finite() && self.snap.threshold >= 0.0) {
+            return Err(GuideError::InvalidSettings(format!("スナップ距離が不正です: {}", self.snap.threshold)));
+        }
+        if let Some(guide) = self.guides.iter().find(|g| !g.position.is_finite()) {
+            return Err(GuideError::InvalidSettings(format!("ガイドの位置が不正です: {}", guide.id)));
+        }
+        self.perspective.validate()?;
+        self.safe_area.validate()
+    }
+
+    pub fn add_guide(&mut self, orientation: GuideOrientation, position: f32) -> Result<Guide, GuideError> {
+        if !position.is_finite() {
+            return Err(GuideError::InvalidSettings(format!("ガイドの位置が不正です: {}", position)));
+        }
+        let guide = Guide {
+            id: format!("guide_{}_{}", chrono::Utc::now().timestamp_millis(), self.guides.len()),
+            orientation,
+            position,
+        };
+        self.guides.push(guide.clone());
+        Ok(guide)
+    }
+
+    pub fn remove_guide(&mut self, guide_id: &str) -> Result<Guide, GuideError> {
+        let index = self.guides.iter().position(|g| g.id == guide_id)
+            .ok_or_else(|| GuideError::GuideNotFound(guide_id.to_string()))?;
+        Ok(self.guides.remove(index))
+    }
+
+    /// スナップが有効ならグリッド・ガイドに吸着させた座標を返す
+    ///
+    /// グリッドへは常に量子化し、ガイドは閾値内にある場合のみ軸ごとに優先する。
+    pub fn snap_point(&self, point: (f32, f32)) -> (f32, f32) {
+        if !self.snap.enabled {
+            return point;
+        }
+
+        let mut snapped = point;
+        if self.snap.to_grid && self.grid.enabled {
+            snapped = self.grid.nearest_point(point);
+        }
+        if self.snap.to_guides {
+            if let Some(x) = self.nearest_guide(GuideOrientation::Vertical, point.0) {
+                snapped.0 = x;
+            }
+            if let Some(y) = self.nearest_guide(GuideOrientation::Horizontal, point.1) {
+                snapped.1 = y;
+            }
+        }
+
+        if snapped != point {
+            debug!("[Guides] スナップ: {:?} -> {:?}", point, snapped);
+        }
+        snapped
+    }
+
+    /// 入力された点列にスナップと透視補正を適用する
+    ///
+    /// 透視補正が有効な場合は始点のみスナップし、残りの点は始点を通る透視線上に射影する。
+    pub fn apply_to_points(&self, points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        match points.first() {
+            Some(&first) if points.len() > 1 && self.perspective.constrains_strokes() => {
+                let anchor = self.snap_point(first);
+                let mut constrained = self.perspective.constrain(anchor, &points[1..]);
+                constrained.insert(0, anchor);
+                constrained
+            }
+            _ => points.iter().map(|&p| self.snap_point(p)).collect(),
+        }
+    }
+
+    fn nearest_guide(&self, orientation: GuideOrientation, value: f32) -> Option<f32> {
+        self.guides.iter()
+            .filter(|g| g.orientation == orientation)
+            .map(|g| g.position)
+            .filter(|position| (position - value).abs() <= self.snap.threshold)
+            .min_by(|a, b| (a - value).abs().total_cmp(&(b - value).abs()))
+    }
+
+    /// ガイド・グリッド・透視線を描いた透明なオーバーレイ画像
+    pub fn render_overlay(&self, width: u32, height: u32) -> RgbaImage {
+        let mut overlay = RgbaImage::new(width, height);
+
+        if self.grid.enabled {
+            for (x, y, pixel) in overlay.enumerate_pixels_mut() {
+                if self.grid.covers_pixel((x as f32, y as f32)) {
+                    *pixel = Rgba(self.grid.color);
+                }
+            }
+        }
+
+        self.perspective.draw_overlay(&mut overlay);
+        if self.safe_area.is_active() {
+            self.safe_area.draw(&mut overlay, (0, 0, width, height));
+        }
+
+        for guide in &self.guides {
+            let index = guide.position.floor();
+            match guide.orientation {
+                GuideOrientation::Vertical if index >= 0.0 && index < width as f32 => {
+                    for y in 0..height {
+                        overlay.put_pixel(index as u32, y, Rgba(self.guide_color));
+                    }
+                }
+                GuideOrientation::Horizontal if index >= 0.0 && index < height as f32 => {
+                    for x in 0..width {
+                        overlay.put_pixel(x, index as u32, Rgba(self.guide_color));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        overlay
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapping(kind: GridKind) -> GuideSettings {
+        GuideSettings {
+            grid: GridSettings { enabled: true, kind, spacing: 10.0, ..GridSettings::default() },
+            snap: SnapSettings { enabled: true, ..SnapSettings::default() },
+            ..GuideSettings::default()
+        }
+    }
+
+    #[test]
+    fn test_snap_disabled_returns_input() {
+        let mut settings = snapping(GridKind::Square);
+        settings.snap.enabled = false;
+        assert_eq!(settings.snap_point((3.3, 7.7)), (3.3, 7.7));
+    }
+
+    #[test]
+    fn test_snap_to_square_grid() {
+        let mut settings = snapping(GridKind::Square);
+        settings.grid.origin = (2.0, 0.0);
+        assert_eq!(settings.snap_point((13.0, 16.0)), (12.0, 20.0));
+    }
+
+    #[test]
+    fn test_snap_to_isometric_grid() {
+        let settings = snapping(GridKind::Isometric);
+        let row_height = 10.0 * ISOMETRIC_TAN;
+
+        // (1, 1) は交点、(1, 0) は交点ではない
+        let (x, y) = settings.snap_point((10.5, row_height - 0.5));
+        assert!((x - 10.0).abs() < 1e-4 && (y - row_height).abs() < 1e-4);
+        let (x, y) = settings.snap_point((10.0, 0.1));
+        assert!((x - 10.0).abs() < 1e-4 && (y - row_height).abs() < 1e-4);
+    }
+
+    #[test]
+    fn test_guides_override_grid_within_threshold() {
+        let mut settings = snapping(GridKind::Square);
+        settings.add_guide(GuideOrientation::Vertical, 14.0).unwrap();
+        settings.add_guide(GuideOrientation::Horizontal, 100.0).unwrap();
+
+        assert_eq!(settings.snap_point((16.0, 23.0)), (14.0, 20.0));
+
+        settings.grid.enabled = false;
+        assert_eq!(settings.snap_point((30.0, 23.0)), (30.0, 23.0));
+    }
+
+    #[test]
+    fn test_remove_guide() {
+        let mut settings = GuideSettings::default();
+        let guide = settings.add_guide(GuideOrientation::Horizontal, 5.0).unwrap();
+        assert_eq!(settings.remove_guide(&guide.id).unwrap(), guide);
+        assert!(matches!(settings.remove_guide(&guide.id), Err(GuideError::GuideNotFound(_))));
+    }
+
+    #[test]
+    fn test_render_overlay() {
+        let mut settings = snapping(GridKind::Square);
+        settings.add_guide(GuideOrientation::Horizontal, 5.5).unwrap();
+        let overlay = settings.render_overlay(20, 20);
+
+        assert_eq!(*overlay.get_pixel(10, 3), Rgba(settings.grid.color));
+        assert_eq!(*overlay.get_pixel(3, 5), Rgba(settings.guide_color));
+        assert_eq!(*overlay.get_pixel(3, 3), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_validate() {
+        let mut settings = GuideSettings::default();
+        assert!(settings.validate().is_ok());
+        settings.grid.spacing = 0.0;
+        assert!(settings.validate().is_err());
+    }
+
+    #[test]
+    fn test_apply_to_points_with_perspective() {
+        let mut settings = snapping(GridKind::Square);
+        settings.perspective = PerspectiveGuide {
+            enabled: true,
+            vanishing_points: vec![(1000.0, 0.0)],
+            constrain_strokes: true,
+            ..PerspectiveGuide::default()
+        };
+
+        // 始点だけグリッドに吸着し、以降は消失点へ向かう水平線に揃う
+        let points = settings.apply_to_points(&[(1.0, 2.0), (50.0, 4.0), (90.0, -3.0)]);
+        assert_eq!(points, vec![(0.0, 0.0), (50.0, 0.0), (90.0, 0.0)]);
+
+        settings.perspective.constrain_strokes = false;
+        assert_eq!(settings.apply_to_points(&[(1.0, 2.0), (52.0, 4.0)]), vec![(0.0, 0.0), (50.0, 0.0)]);
+    }
+}
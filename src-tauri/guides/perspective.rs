@@ -0,0 +1,235 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use super::GuideError;
+
+/// 1点〜3点透視のガイド
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PerspectiveGuide {
+    pub enabled: bool,
+    /// 消失点（1〜3個。キャンバス外も可）
+    pub vanishing_points: Vec<(f32, f32)>,
+    /// 消失点ごとに描く放射線の本数
+    pub ray_count: u32,
+    pub color: [u8; 4],
+    /// ストロークを最も近い消失点へ向かう直線に補正するか
+    pub constrain_strokes: bool,
+}
+
+impl Default for PerspectiveGuide {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            vanishing_points: Vec::new(),
+            ray_count: 24,
+            color: [255, 96, 64, 160],
+            constrain_strokes: false,
+        }
+    }
+}
+
+impl PerspectiveGuide {
+    pub fn validate(&self) -> Result<(), GuideError> {
+        if self.enabled && !(1..=3).contains(&self.vanishing_points.len()) {
+            return Err(GuideError::InvalidSettings(format!(
+                "消失点は1〜3個である必要があります: {} 個", self.vanishing_points.len()
+            )));
+        }
+        if self.vanishing_points.iter().any(|(x, y)| !x.is_finite() || !y.is_finite()) {
+            return Err(GuideError::InvalidSettings("消失点の座標が不正です".to_string()));
+        }
+        if self.ray_count == 0 || self.ray_count > 360 {
+            return Err(GuideError::InvalidSettings(format!("放射線の本数は1〜360である必要があります: {}", self.ray_count)));
+        }
+        Ok(())
+    }
+
+    /// ストローク補正が有効か
+    pub fn constrains_strokes(&self) -> bool {
+        self.enabled && self.constrain_strokes && !self.vanishing_points.is_empty()
+    }
+
+    /// ストロークを始点から消失点へ向かう直線上に射影する
+    ///
+    /// 候補は各消失点への方向と、透視の数に応じて平行のまま残る垂直線（1・2点透視）と
+    /// 水平線（1点透視）。ストロークの始点→終点の向きに最も近い候補を選ぶ。
+    pub fn constrain(&self, anchor: (f32, f32), points: &[(f32, f32)]) -> Vec<(f32, f32)> {
+        let Some(&last) = points.last() else {
+            return Vec::new();
+        };
+        let Some(stroke_direction) = normalize((last.0 - anchor.0, last.1 - anchor.1)) else {
+            return vec![anchor; points.len()];
+        };
+
+        let mut candidates: Vec<(f32, f32)> = self.vanishing_points.iter()
+            .filter_map(|vp| normalize((vp.0 - anchor.0, vp.1 - anchor.1)))
+            .collect();
+        if self.vanishing_points.len() < 3 {
+            candidates.push((0.0, 1.0));
+        }
+        if self.vanishing_points.len() == 1 {
+            candidates.push((1.0, 0.0));
+        }
+
+        let Some(direction) = candidates.into_iter().max_by(|a, b| {
+            dot(*a, stroke_direction).abs().total_cmp(&dot(*b, stroke_direction).abs())
+        }) else {
+            return points.to_vec();
+        };
+
+        points.iter().map(|p| {
+            let t = dot((p.0 - anchor.0, p.1 - anchor.1), direction);
+            (anchor.0 + direction.0 * t, anchor.1 + direction.1 * t)
+        }).collect()
+    }
+
+    /// 消失点からの放射線と地平線をオーバーレイに描く
+    pub fn draw_overlay(&self, overlay: &mut RgbaImage) {
+        if !self.enabled {
+            return;
+        }
+        let (width, height) = overlay.dimensions();
+        let color = Rgba(self.color);
+
+        for &vp in &self.vanishing_points {
+            // 消失点がキャンバス外でも放射線がキャンバスを横切るだけの長さにする
+            let reach = (vp.0.abs() + vp.1.abs() + (width + height) as f32) * 2.0;
+            for i in 0..self.ray_count {
+                let angle = i as f32 / self.ray_count as f32 * std::f32::consts::TAU;
+                let end = (vp.0 + angle.cos() * reach, vp.1 + angle.sin() * reach);
+                draw_segment(overlay, vp, end, color);
+            }
+        }
+
+        // 2点以上なら最初の2点を結ぶ直線が地平線
+        if let [a, b, ..] = self.vanishing_points[..] {
+            if let Some(direction) = normalize((b.0 - a.0, b.1 - a.1)) {
+                let reach = (a.0.abs() + a.1.abs() + (width + height) as f32) * 2.0;
+                let start = (a.0 - direction.0 * reach, a.1 - direction.1 * reach);
+                let end = (a.0 + direction.0 * reach, a.1 + direction.1 * reach);
+                draw_segment(overlay, start, end, color);
+            }
+        }
+    }
+}
+
+fn dot(a: (f32, f32), b: (f32, f32)) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn normalize(v: (f32, f32)) -> Option<(f32, f32)> {
+    let length = (v.0 * v.0 + v.1 * v.1).sqrt();
+    (length > 1e-3).then(|| (v.0 / length, v.1 / length))
+}
+
+/// キャンバス内に切り詰めた線分を1px幅で描く
+fn draw_segment(image: &mut RgbaImage, from: (f32, f32), to: (f32, f32), color: Rgba<u8>) {
+    let (width, height) = image.dimensions();
+    let Some((from, to)) = clip_segment(from, to, (width as f32, height as f32)) else {
+        return;
+    };
+
+    let steps = (to.0 - from.0).abs().max((to.1 - from.1).abs()).ceil().max(1.0) as u32;
+    for step in 0..=steps {
+        let t = step as f32 / steps as f32;
+        let x = (from.0 + (to.0 - from.0) * t).floor();
+        let y = (from.1 + (to.1 - from.1) * t).floor();
+        if x >= 0.0 && y >= 0.0 && x < width as f32 && y < height as f32 {
+            image.put_pixel(x as u32, y as u32, color);
+        }
+    }
+}
+
+/// Liang-Barsky 法で線分を矩形 [0, size] に切り詰める
+fn clip_segment(from: (f32, f32), to: (f32, f32), size: (f32, f32)) -> Option<((f32, f32), (f32, f32))> {
+    let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+    let (mut t0, mut t1) = (0.0f32, 1.0f32);
+    let edges = [(-dx, from.0), (dx, size.0 - from.0), (-dy, from.1), (dy, size.1 - from.1)];
+
+    for (p, q) in edges {
+        if p == 0.0 {
+            if q < 0.0 {
+                return None;
+            }
+        } else {
+            let r = q / p;
+            if p < 0.0 {
+                t0 = t0.max(r);
+            } else {
+                t1 = t1.min(r);
+            }
+        }
+    }
+
+    (t0 <= t1).then_some((
+        (from.0 + dx * t0, from.1 + dy * t0),
+        (from.0 + dx * t1, from.1 + dy * t1),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn guide(vanishing_points: Vec<(f32, f32)>) -> PerspectiveGuide {
+        PerspectiveGuide {
+            enabled: true,
+            vanishing_points,
+            constrain_strokes: true,
+            ..PerspectiveGuide::default()
+        }
+    }
+
+    fn assert_close(actual: (f32, f32), expected: (f32, f32)) {
+        assert!((actual.0 - expected.0).abs() < 1e-3 && (actual.1 - expected.1).abs() < 1e-3,
+                "{:?} != {:?}", actual, expected);
+    }
+
+    #[test]
+    fn test_constrain_toward_vanishing_point() {
+        let guide = guide(vec![(100.0, 0.0)]);
+        let points = guide.constrain((0.0, 0.0), &[(0.0, 0.0), (40.0, 5.0), (80.0, -3.0)]);
+        assert_close(points[1], (40.0, 0.0));
+        assert_close(points[2], (80.0, 0.0));
+    }
+
+    #[test]
+    fn test_constrain_picks_nearest_direction() {
+        // 2点透視: 右上に向かうストロークは右の消失点へ、真下は垂直線へ
+        let guide = guide(vec![(-100.0, 0.0), (100.0, 0.0)]);
+        let anchor = (0.0, 100.0);
+
+        let right = guide.constrain(anchor, &[(50.0, 45.0)]);
+        assert_close(right[0], (52.5, 47.5));
+
+        let down = guide.constrain(anchor, &[(3.0, 150.0)]);
+        assert_close(down[0], (0.0, 150.0));
+    }
+
+    #[test]
+    fn test_validate_point_count() {
+        assert!(guide(vec![]).validate().is_err());
+        assert!(guide(vec![(0.0, 0.0); 4]).validate().is_err());
+        assert!(guide(vec![(0.0, 0.0), (10.0, 0.0), (5.0, 50.0)]).validate().is_ok());
+        assert!(PerspectiveGuide::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_overlay_draws_rays_from_outside_vanishing_point() {
+        let mut guide = guide(vec![(-50.0, 5.0)]);
+        guide.ray_count = 4;
+        let mut overlay = RgbaImage::new(20, 10);
+        guide.draw_overlay(&mut overlay);
+
+        // 角度0の放射線は y=5 の水平線になる
+        assert_eq!(*overlay.get_pixel(10, 5), Rgba(guide.color));
+        assert_eq!(*overlay.get_pixel(10, 2), Rgba([0, 0, 0, 0]));
+    }
+
+    #[test]
+    fn test_clip_segment() {
+        assert!(clip_segment((-10.0, -10.0), (-5.0, 30.0), (20.0, 20.0)).is_none());
+        let (from, to) = clip_segment((-10.0, 5.0), (30.0, 5.0), (20.0, 20.0)).unwrap();
+        assert_close(from, (0.0, 5.0));
+        assert_close(to, (20.0, 5.0));
+    }
+}
@@ -0,0 +1,180 @@
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Serialize};
+use crate::formats::blend_over;
+use super::GuideError;
+
+/// 放送用のセーフエリアと画面比（レターボックス）のガイド
+///
+/// 表示用のオーバーレイにだけ描き、書き出しには含めない。セーフエリアは画面比で
+/// 切り詰めた後の映像部分に対する割合で描く。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SafeAreaGuide {
+    /// アクションセーフ（動きの重要な部分を収める範囲）を表示
+    pub action_safe: bool,
+    /// 映像の幅・高さに対するアクションセーフの大きさ（%）
+    pub action_safe_percent: f32,
+    /// タイトルセーフ（文字を収める範囲）を表示
+    pub title_safe: bool,
+    pub title_safe_percent: f32,
+    /// 映像の画面比（幅 / 高さ、例: 2.39）。指定するとはみ出した部分を帯で覆う
+    pub aspect_ratio: Option<f32>,
+    pub line_color: [u8; 4],
+    /// RGBA（アルファで下の画像と合成）
+    pub letterbox_color: [u8; 4],
+}
+
+impl Default for SafeAreaGuide {
+    fn default() -> Self {
+        // 割合は SMPTE ST 2046-1 の値
+        Self {
+            action_safe: false,
+            action_safe_percent: 93.0,
+            title_safe: false,
+            title_safe_percent: 90.0,
+            aspect_ratio: None,
+            line_color: [255, 255, 0, 192],
+            letterbox_color: [0, 0, 0, 160],
+        }
+    }
+}
+
+impl SafeAreaGuide {
+    pub fn validate(&self) -> Result<(), GuideError> {
+        for percent in [self.action_safe_percent, self.title_safe_percent] {
+            if !(percent > 0.0 && percent <= 100.0) {
+                return Err(GuideError::InvalidSettings(format!("セーフエリアの割合は 0 より大きく 100 以下である必要があります: {}", percent)));
+            }
+        }
+        if let Some(aspect) = self.aspect_ratio {
+            if !(aspect.is_finite() && aspect > 0.0) {
+                return Err(GuideError::InvalidSettings(format!("画面比が不正です: {}", aspect)));
+            }
+        }
+        Ok(())
+    }
+
+    /// 何か描くものがあるか
+    pub fn is_active(&self) -> bool {
+        self.action_safe || self.title_safe || self.aspect_ratio.is_some()
+    }
+
+    /// 画面比で切り詰めた映像部分 `(x, y, width, height)`（画面比の指定がなければ全体）
+    pub fn picture_area(&self, width: f32, height: f32) -> (f32, f32, f32, f32) {
+        match self.aspect_ratio {
+            Some(aspect) if width > height * aspect => {
+                let picture_width = height * aspect;
+                ((width - picture_width) / 2.0, 0.0, picture_width, height)
+            }
+            Some(aspect) => {
+                let picture_height = width / aspect;
+                (0.0, (height - picture_height) / 2.0, width, picture_height)
+            }
+            None => (0.0, 0.0, width, height),
+        }
+    }
+
+    /// 映像部分の中央に置いた `percent` % の範囲
+    fn safe_rect(percent: f32, (x, y, width, height): (f32, f32, f32, f32)) -> (f32, f32, f32, f32) {
+        let scale = percent / 100.0;
+        let (safe_width, safe_height) = (width * scale, height * scale);
+        (x + (width - safe_width) / 2.0, y + (height - safe_height) / 2.0, safe_width, safe_height)
+    }
+
+    /// 画像の範囲 `(left, top, width, height)` を画面として帯とセーフエリアの枠を描く
+    ///
+    /// 拡大表示では拡大後の範囲を渡すと、枠線は拡大率によらず1px幅になる。
+    pub fn draw(&self, image: &mut RgbaImage, (left, top, width, height): (u32, u32, u32, u32)) {
+        let right = (left + width).min(image.width());
+        let bottom = (top + height).min(image.height());
+        let picture = self.picture_area(width as f32, height as f32);
+
+        if self.aspect_ratio.is_some() {
+            let color = Rgba(self.letterbox_color);
+            let (px, py, pw, ph) = picture;
+            let (px0, py0) = (px.round() as u32, py.round() as u32);
+            let (px1, py1) = ((px + pw).round() as u32, (py + ph).round() as u32);
+            for y in top..bottom {
+                for x in left..right {
+                    let (vx, vy) = (x - left, y - top);
+                    if vx < px0 || vx >= px1 || vy < py0 || vy >= py1 {
+                        let pixel = image.get_pixel_mut(x, y);
+                        *pixel = blend_over(*pixel, color);
+                    }
+                }
+            }
+        }
+
+        let enabled = [(self.action_safe, self.action_safe_percent), (self.title_safe, self.title_safe_percent)];
+        for (_, percent) in enabled.into_iter().filter(|(enabled, _)| *enabled) {
+            let (x, y, w, h) = Self::safe_rect(percent, picture);
+            let (x0, y0) = (left + x.round() as u32, top + y.round() as u32);
+            let x1 = (left + (x + w).round() as u32).saturating_sub(1).max(x0);
+            let y1 = (top + (y + h).round() as u32).saturating_sub(1).max(y0);
+            let color = Rgba(self.line_color);
+            let mut blend = |x: u32, y: u32| {
+                if x < right && y < bottom {
+                    let pixel = image.get_pixel_mut(x, y);
+                    *pixel = blend_over(*pixel, color);
+                }
+            };
+            for x in x0..=x1 {
+                blend(x, y0);
+                if y1 != y0 {
+                    blend(x, y1);
+                }
+            }
+            for y in y0 + 1..y1 {
+                blend(x0, y);
+                if x1 != x0 {
+                    blend(x1, y);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_picture_area_and_validate() {
+        let mut guide = SafeAreaGuide { aspect_ratio: Some(2.0), ..SafeAreaGuide::default() };
+        // 16:9 に 2:1 を合わせると上下に帯が付く
+        assert_eq!(guide.picture_area(160.0, 90.0), (0.0, 5.0, 160.0, 80.0));
+        // 1:1 に 1:2 を合わせると左右に帯が付く
+        guide.aspect_ratio = Some(0.5);
+        assert_eq!(guide.picture_area(100.0, 100.0), (25.0, 0.0, 50.0, 100.0));
+        assert!(guide.validate().is_ok());
+
+        guide.aspect_ratio = Some(0.0);
+        assert!(guide.validate().is_err());
+        assert!(SafeAreaGuide { title_safe_percent: 120.0, ..SafeAreaGuide::default() }.validate().is_err());
+        assert!(!SafeAreaGuide::default().is_active());
+    }
+
+    #[test]
+    fn test_draw_letterbox_and_safe_frames() {
+        let guide = SafeAreaGuide {
+            title_safe: true,
+            title_safe_percent: 50.0,
+            aspect_ratio: Some(2.0),
+            line_color: [255, 0, 0, 255],
+            letterbox_color: [0, 0, 0, 255],
+            ..SafeAreaGuide::default()
+        };
+        // 枠線の外側に 2px の余白がある表示画像
+        let mut image = RgbaImage::from_pixel(24, 24, Rgba([255, 255, 255, 255]));
+        guide.draw(&mut image, (2, 2, 20, 20));
+
+        // 映像部分は 20x10（上下に 5px の帯）
+        assert_eq!(image.get_pixel(10, 4), &Rgba([0, 0, 0, 255]));
+        assert_eq!(image.get_pixel(10, 20), &Rgba([0, 0, 0, 255]));
+        assert_eq!(image.get_pixel(1, 1), &Rgba([255, 255, 255, 255]));
+        // タイトルセーフは映像部分の中央の 10x5
+        assert_eq!(image.get_pixel(7, 10), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(16, 14), &Rgba([255, 0, 0, 255]));
+        assert_eq!(image.get_pixel(10, 12), &Rgba([255, 255, 255, 255]));
+    }
+}
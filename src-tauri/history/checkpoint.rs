@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use log::{debug, info, warn};
+use crate::drawing_engine::{DrawingEngine, TextureError};
+use super::{HistoryError, OperationLog};
+
+/// チェックポイントを記録する間隔（再生中に履歴の位置がこの倍数を通るたびに記録する）
+pub const CHECKPOINT_INTERVAL: usize = 32;
+
+/// 保持するチェックポイントの数（再構築した位置から遠いものから破棄する）
+pub const MAX_CHECKPOINTS: usize = 4;
+
+/// チェックポイントのテクスチャを参照するレイヤーIDの接頭辞
+const CHECKPOINT_LAYER_PREFIX: &str = "__checkpoint_";
+
+/// 履歴のある位置でのレイヤー構成（テクスチャはエンジン側で共有している）
+#[derive(Debug, Clone)]
+struct Checkpoint {
+    position: usize,
+    /// 記録したときの先頭から `position` 件のエントリの並び
+    fingerprint: u64,
+    layers: HashMap<String, (u32, u32)>,
+}
+
+impl Checkpoint {
+    fn texture_id(&self, layer_id: &str) -> String {
+        format!("{}{}_{}", CHECKPOINT_LAYER_PREFIX, self.position, layer_id)
+    }
+
+    fn release(&self, engine: &mut DrawingEngine) {
+        for layer_id in self.layers.keys() {
+            engine.remove_layer_texture(&self.texture_id(layer_id));
+        }
+    }
+}
+
+/// 履歴の途中の状態のチェックポイント（アンドゥ・リドゥで操作ログを先頭から再生しないため）
+///
+/// レイヤーのテクスチャをコピーオンライトで共有して覚えておくため、記録自体はコピーを伴わない。
+/// 記録後に描き込んだレイヤーだけがテクスチャ1枚分のメモリを使う。
+/// 無限キャンバスのタイルは共有できないため、無限キャンバスが有効な位置では記録しない。
+#[derive(Debug, Default)]
+pub struct HistoryCheckpoints {
+    checkpoints: Vec<Checkpoint>,
+}
+
+impl HistoryCheckpoints {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn len(&self) -> usize {
+        self.checkpoints.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.checkpoints.is_empty()
+    }
+
+    /// チェックポイントの履歴位置（古い順）
+    pub fn positions(&self) -> Vec<usize> {
+        self.checkpoints.iter().map(|checkpoint| checkpoint.position).collect()
+    }
+
+    /// `log` の適用済み部分の状態をエンジンに再構築し、再生した操作の数を返す
+    ///
+    /// 現在位置以前で一番近いチェックポイントがあればそこから、なければ先頭から再生する。
+    /// 再生中に間隔の倍数の位置を通るたびにチェックポイントを記録するため、続けてアンドゥしても
+    /// 2回目からは直前のチェックポイント以降の操作を再生するだけで済む。
+    pub fn rebuild(
+        &mut self,
+        log: &OperationLog,
+        engine: &mut DrawingEngine,
+        layers: &mut HashMap<String, (u32, u32)>,
+    ) -> Result<usize, HistoryError> {
+        self.prune(log, engine);
+        let start = match self.restore(log, engine, layers) {
+            Ok(Some(position)) => position,
+            Ok(None) => {
+                OperationLog::clear_engine_state(engine, layers);
+                0
+            }
+            Err(e) => {
+                warn!("[HistoryCheckpoints] チェックポイントから復元できません: {}", e);
+                self.clear(Some(engine));
+                OperationLog::clear_engine_state(engine, layers);
+                0
+            }
+        };
+
+        let end = log.position();
+        let mut position = start;
+        while position < end {
+            let next = ((position / CHECKPOINT_INTERVAL + 1) * CHECKPOINT_INTERVAL).min(end);
+            log.replay_range(position..next, engine, layers)?;
+            position = next;
+            if position % CHECKPOINT_INTERVAL == 0 {
+                if let Err(e) = self.capture(log, position, engine, layers) {
+                    warn!("[HistoryCheckpoints] チェックポイントの記録に失敗: 位置 {} ({})", position, e);
+                }
+            }
+        }
+
+        info!("[HistoryCheckpoints] 状態再構築完了: 位置 {} から {} 操作を再生", start, end - start);
+        Ok(end - start)
+    }
+
+    /// 現在のエンジン状態を `log` の `position` の位置のチェックポイントとして記録
+    ///
+    /// `layers` は `log` の先頭から `position` 件の操作を再生した状態と一致していること。
+    fn capture(
+        &mut self,
+        log: &OperationLog,
+        position: usize,
+        engine: &mut DrawingEngine,
+        layers: &HashMap<String, (u32, u32)>,
+    ) -> Result<(), TextureError> {
+        if engine.infinite_canvas().is_some() || self.checkpoints.iter().any(|c| c.position == position) {
+            return Ok(());
+        }
+
+        let checkpoint = Checkpoint {
+            position,
+            fingerprint: log.prefix_fingerprint(position),
+            layers: layers.clone(),
+        };
+        for layer_id in layers.keys() {
+            if let Err(e) = engine.share_layer_texture(layer_id, &checkpoint.texture_id(layer_id)) {
+                checkpoint.release(engine);
+                return Err(e);
+            }
+        }
+        self.checkpoints.push(checkpoint);
+        self.checkpoints.sort_by_key(|c| c.position);
+        if self.checkpoints.len() > MAX_CHECKPOINTS {
+            let farthest = (0..self.checkpoints.len())
+                .max_by_key(|&i| self.checkpoints[i].position.abs_diff(log.position()))
+                .unwrap_or(0);
+            self.checkpoints.remove(farthest).release(engine);
+        }
+
+        debug!("[HistoryCheckpoints] チェックポイント記録: 位置 {} ({} レイヤー)", position, layers.len());
+        Ok(())
+    }
+
+    /// `log` の現在位置以前で一番近いチェックポイントの状態にエンジンを戻し、その位置を返す
+    ///
+    /// 使えるチェックポイントがなければ何もせずに None を返す。
+    fn restore(
+        &self,
+        log: &OperationLog,
+        engine: &mut DrawingEngine,
+        layers: &mut HashMap<String, (u32, u32)>,
+    ) -> Result<Option<usize>, TextureError> {
+        let Some(checkpoint) = self.checkpoints.iter().rev()
+            .find(|c| c.position <= log.position())
+        else {
+            return Ok(None);
+        };
+
+        OperationLog::clear_engine_state(engine, layers);
+        for (layer_id, &size) in &checkpoint.layers {
+            engine.share_layer_texture(&checkpoint.texture_id(layer_id), layer_id)?;
+            layers.insert(layer_id.clone(), size);
+        }
+
+        debug!("[HistoryCheckpoints] チェックポイントから復元: 位置 {} → {}", checkpoint.position, log.position());
+        Ok(Some(checkpoint.position))
+    }
+
+    /// `log` の先頭部分と一致しなくなったチェックポイント（リドゥ履歴を破棄した後など）を破棄
+    pub fn prune(&mut self, log: &OperationLog, engine: &mut DrawingEngine) {
+        self.checkpoints.retain(|checkpoint| {
+            let valid = checkpoint.position <= log.len()
+                && checkpoint.fingerprint == log.prefix_fingerprint(checkpoint.position);
+            if !valid {
+                checkpoint.release(engine);
+            }
+            valid
+        });
+    }
+
+    /// すべてのチェックポイントを破棄（エンジンが作り直された後などテクスチャが既にない場合は None）
+    pub fn clear(&mut self, engine: Option<&mut DrawingEngine>) {
+        if self.checkpoints.is_empty() {
+            return;
+        }
+        if let Some(engine) = engine {
+            for checkpoint in &self.checkpoints {
+                checkpoint.release(engine);
+            }
+        }
+        info!("[HistoryCheckpoints] チェックポイントを破棄: {} 件", self.checkpoints.len());
+        self.checkpoints.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::Operation;
+
+    fn line_op(layer_id: &str, y: f32) -> Operation {
+        Operation::DrawLine {
+            layer_id: layer_id.to_string(),
+            x1: 0.0,
+            y1: y,
+            x2: 64.0,
+            y2: y,
+            color: [1.0, 0.0, 0.0, 1.0],
+            width: 2.0,
+        }
+    }
+
+    /// レイヤーを1枚作って線を引き続けた操作ログ（エンジンには適用しない）
+    fn drawing_log(count: usize) -> OperationLog {
+        let create = Operation::CreateLayer { layer_id: "layer1".to_string(), width: 64, height: 64 };
+        OperationLog::from_operations(std::iter::once(create)
+            .chain((1..count).map(|i| line_op("layer1", (i % 64) as f32))))
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_replays_from_nearest_checkpoint() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = DrawingEngine::new();
+        engine.initialize().await?;
+        let mut layers = HashMap::new();
+        let mut checkpoints = HistoryCheckpoints::new();
+        let mut log = drawing_log(CHECKPOINT_INTERVAL * 2 + 4);
+
+        // 最初の再構築は先頭から再生し、通った位置にチェックポイントを残す
+        assert_eq!(checkpoints.rebuild(&log, &mut engine, &mut layers)?, log.len());
+        assert_eq!(checkpoints.positions(), vec![CHECKPOINT_INTERVAL, CHECKPOINT_INTERVAL * 2]);
+
+        // 1つ戻すと直前のチェックポイントから残りだけを再生し、先頭からの再生と同じ結果になる
+        log.undo();
+        assert_eq!(checkpoints.rebuild(&log, &mut engine, &mut layers)?, 3);
+        let restored = engine.get_layer_texture_data("layer1").await?;
+        log.rebuild(&mut engine, &mut layers)?;
+        assert_eq!(restored, engine.get_layer_texture_data("layer1").await?);
+
+        // 復元後に描き込んでもチェックポイントの内容は変わらない
+        log.seek(CHECKPOINT_INTERVAL)?;
+        assert_eq!(checkpoints.rebuild(&log, &mut engine, &mut layers)?, 0);
+        let at_checkpoint = engine.get_layer_texture_data("layer1").await?;
+        line_op("layer1", 60.0).apply(&mut engine, &mut layers)?;
+        checkpoints.rebuild(&log, &mut engine, &mut layers)?;
+        assert_eq!(at_checkpoint, engine.get_layer_texture_data("layer1").await?);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_discarded_redo_branch_invalidates_checkpoints() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = DrawingEngine::new();
+        engine.initialize().await?;
+        let mut layers = HashMap::new();
+        let mut checkpoints = HistoryCheckpoints::new();
+        let mut log = drawing_log(CHECKPOINT_INTERVAL * 2);
+        checkpoints.rebuild(&log, &mut engine, &mut layers)?;
+        assert_eq!(checkpoints.len(), 2);
+
+        // チェックポイントより前へ戻って別の操作を記録すると、同じ位置まで進んでも使わない
+        log.seek(CHECKPOINT_INTERVAL - 2)?;
+        for i in 0..4 {
+            log.push(line_op("layer1", i as f32));
+        }
+        assert_eq!(checkpoints.rebuild(&log, &mut engine, &mut layers)?, CHECKPOINT_INTERVAL + 2);
+        assert_eq!(checkpoints.positions(), vec![CHECKPOINT_INTERVAL]);
+
+        checkpoints.clear(Some(&mut engine));
+        assert!(checkpoints.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+use crate::selection::{polygon_contains, polygon_crossings, Selection};
+use super::{HistoryEntry, Operation, OperationLog, StrokePointRecord, StrokeRecord};
+
+/// 投げ縄消去の結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct LassoEraseSummary {
+    /// 投げ縄の境界で分割したストローク
+    pub split_strokes: usize,
+    /// 全体が投げ縄の内側にあって取り除いたストローク
+    pub removed_strokes: usize,
+}
+
+/// 折れ線を投げ縄の外側の部分に分ける（投げ縄に掛からなければ None）
+///
+/// 線分は多角形の辺と交わる位置で区切り、区間の中点が内側にある区間を取り除く。
+/// 区切った位置の点は両端の点から補間する。
+fn split_polyline<P: Copy>(
+    points: &[P],
+    polygon: &[(f32, f32)],
+    position: impl Fn(&P) -> (f32, f32),
+    lerp: impl Fn(&P, &P, f32) -> P,
+) -> Option<Vec<Vec<P>>> {
+    if let [point] = points {
+        let (x, y) = position(point);
+        return polygon_contains(polygon, x, y).then(Vec::new);
+    }
+
+    let mut pieces = Vec::new();
+    let mut current: Vec<P> = Vec::new();
+    let mut touched = false;
+    for pair in points.windows(2) {
+        let (a, b) = (&pair[0], &pair[1]);
+        let (pa, pb) = (position(a), position(b));
+        let mut cuts = vec![0.0];
+        cuts.extend(polygon_crossings(polygon, pa, pb));
+        cuts.push(1.0);
+        for span in cuts.windows(2) {
+            let (t0, t1) = (span[0], span[1]);
+            let mid = (t0 + t1) * 0.5;
+            if polygon_contains(polygon, pa.0 + (pb.0 - pa.0) * mid, pa.1 + (pb.1 - pa.1) * mid) {
+                touched = true;
+                if current.len() > 1 {
+                    pieces.push(std::mem::take(&mut current));
+                }
+                current.clear();
+                continue;
+            }
+            if current.is_empty() {
+                current.push(if t0 == 0.0 { *a } else { lerp(a, b, t0) });
+            }
+            current.push(if t1 == 1.0 { *b } else { lerp(a, b, t1) });
+        }
+    }
+    if current.len() > 1 {
+        pieces.push(current);
+    }
+    touched.then_some(pieces)
+}
+
+fn split_stroke(stroke: &StrokeRecord, polygon: &[(f32, f32)]) -> Option<Vec<StrokeRecord>> {
+    let pieces = split_polyline(
+        &stroke.points,
+        polygon,
+        |p| (p.x, p.y),
+        |a, b, t| StrokePointRecord {
+            x: a.x + (b.x - a.x) * t,
+            y: a.y + (b.y - a.y) * t,
+            pressure: a.pressure + (b.pressure - a.pressure) * t,
+            time_ms: a.time_ms.zip(b.time_ms)
+                .map(|(a, b)| (a as f32 + (b as f32 - a as f32) * t).round() as u32),
+        },
+    )?;
+    Some(pieces.into_iter().map(|points| StrokeRecord { points, ..stroke.clone() }).collect())
+}
+
+impl Operation {
+    /// ベクターのストロークを投げ縄の外側の部分に分けた操作（投げ縄に掛からない操作は None）
+    ///
+    /// 全体が内側にあれば空の列になる。スタンプブラシのストロークはスタンプの配置が
+    /// 始点からの距離で決まり分けると形が変わるため、画素として消す（ここでは分けない）。
+    pub fn split_by_lasso(&self, polygon: &[(f32, f32)]) -> Option<Vec<Operation>> {
+        let lerp = |a: &(f32, f32), b: &(f32, f32), t: f32| (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t);
+        match self {
+            Operation::DrawLine { layer_id, x1, y1, x2, y2, color, width } => {
+                let pieces = split_polyline(&[(*x1, *y1), (*x2, *y2)], polygon, |p| *p, lerp)?;
+                Some(pieces.into_iter().map(|piece| {
+                    let ((x1, y1), (x2, y2)) = (piece[0], piece[piece.len() - 1]);
+                    Operation::DrawLine { layer_id: layer_id.clone(), x1, y1, x2, y2, color: *color, width: *width }
+                }).collect())
+            }
+            Operation::DrawStroke { layer_id, stroke } => {
+                Some(split_stroke(stroke, polygon)?.into_iter()
+                    .map(|stroke| Operation::DrawStroke { layer_id: layer_id.clone(), stroke })
+                    .collect())
+            }
+            Operation::EraseStroke { layer_id, stroke } => {
+                Some(split_stroke(stroke, polygon)?.into_iter()
+                    .map(|stroke| Operation::EraseStroke { layer_id: layer_id.clone(), stroke })
+                    .collect())
+            }
+            Operation::DrawPixelStroke { layer_id, points, color, pixel_perfect } => {
+                let pieces = split_polyline(points, polygon, |p| *p, lerp)?;
+                Some(pieces.into_iter().map(|points| Operation::DrawPixelStroke {
+                    layer_id: layer_id.clone(),
+                    points,
+                    color: *color,
+                    pixel_perfect: *pixel_perfect,
+                }).collect())
+            }
+            _ => None,
+        }
+    }
+
+    /// レイヤーの画素を動かす操作か（これより前のストロークは座標が今の見た目と一致しない）
+    fn moves_pixels_of(&self, layer_id: &str) -> bool {
+        match self {
+            Operation::TransformSelection { layer_id: target, .. } => target == layer_id,
+            Operation::TransformCanvas { .. } | Operation::SetViewport { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+impl OperationLog {
+    /// レイヤーの投げ縄の内側を消した操作ログを作る（投げ縄がピクセルを含まなければ None）
+    ///
+    /// ベクターのストロークは履歴の中で投げ縄の境界で分割し、内側の部分を取り除く。
+    /// そのうえで投げ縄の範囲の画素を消す操作を末尾に追加し、ブラシのストロークや貼り付け画像、
+    /// 分割したストロークの線幅のはみ出しを消す。画素を動かす操作より前のストロークは分割しない。
+    /// 履歴を書き換えるため、リドゥ可能な操作は破棄され、消去自体はアンドゥできない。
+    pub fn lasso_erased(&self, layer_id: &str, polygon: &[(f32, f32)]) -> Option<(OperationLog, LassoEraseSummary)> {
+        let selection = Selection::from_polygon(polygon)?;
+        let applied = self.applied_entries();
+        let first_splittable = applied.iter()
+            .rposition(|entry| entry.operation.moves_pixels_of(layer_id))
+            .map_or(0, |index| index + 1);
+
+        let mut log = self.clone();
+        let mut summary = LassoEraseSummary::default();
+        let mut entries = Vec::with_capacity(applied.len());
+        for (index, entry) in applied.iter().enumerate() {
+            let pieces = (index >= first_splittable && entry.operation.layer_id() == Some(layer_id))
+                .then(|| entry.operation.split_by_lasso(polygon))
+                .flatten();
+            let Some(pieces) = pieces else {
+                entries.push(entry.clone());
+                continue;
+            };
+            if pieces.is_empty() {
+                summary.removed_strokes += 1;
+            } else {
+                summary.split_strokes += 1;
+            }
+            // 最初の部分は元の `seq` を引き継ぎ、残りには新しい `seq` を振る
+            for (piece_index, operation) in pieces.into_iter().enumerate() {
+                let seq = if piece_index == 0 {
+                    entry.seq
+                } else {
+                    log.next_seq += 1;
+                    log.next_seq - 1
+                };
+                entries.push(HistoryEntry { seq, timestamp: entry.timestamp, operation });
+            }
+        }
+        log.position = entries.len();
+        log.entries = entries;
+        log.push(Operation::EraseSelection { layer_id: layer_id.to_string(), selection });
+        Some((log, summary))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stroke(points: &[(f32, f32)]) -> StrokeRecord {
+        StrokeRecord {
+            points: points.iter().enumerate()
+                .map(|(i, &(x, y))| StrokePointRecord { x, y, pressure: 1.0, time_ms: Some(i as u32 * 10) })
+                .collect(),
+            color: [0.0, 0.0, 0.0, 1.0],
+            base_width: 2.0,
+        }
+    }
+
+    const SQUARE: [(f32, f32); 4] = [(10.0, 10.0), (20.0, 10.0), (20.0, 20.0), (10.0, 20.0)];
+
+    #[test]
+    fn test_split_stroke_at_lasso_boundary() {
+        let operation = Operation::DrawStroke { layer_id: "a".to_string(), stroke: stroke(&[(0.0, 15.0), (30.0, 15.0)]) };
+        let pieces = operation.split_by_lasso(&SQUARE).unwrap();
+        let ranges: Vec<_> = pieces.iter().map(|operation| match operation {
+            Operation::DrawStroke { stroke, .. } => (stroke.points[0].x, stroke.points[stroke.points.len() - 1].x),
+            _ => unreachable!(),
+        }).collect();
+        assert_eq!(ranges, vec![(0.0, 10.0), (20.0, 30.0)]);
+        // 区切った位置の時刻は補間する
+        let Operation::DrawStroke { stroke, .. } = &pieces[1] else { unreachable!() };
+        assert_eq!(stroke.points[0].time_ms, Some(7));
+
+        // 掛からない線はそのまま、内側だけの線は取り除く
+        let outside = Operation::DrawLine { layer_id: "a".to_string(), x1: 0.0, y1: 0.0, x2: 30.0, y2: 0.0, color: [1.0; 4], width: 1.0 };
+        assert_eq!(outside.split_by_lasso(&SQUARE), None);
+        let inside = Operation::DrawPixelStroke { layer_id: "a".to_string(), points: vec![(12.0, 12.0), (18.0, 18.0)], color: [1.0; 4], pixel_perfect: false };
+        assert_eq!(inside.split_by_lasso(&SQUARE), Some(Vec::new()));
+    }
+
+    #[test]
+    fn test_lasso_erased_rewrites_history() {
+        let layer_id = "a".to_string();
+        let mut log = OperationLog::from_operations([
+            Operation::CreateLayer { layer_id: layer_id.clone(), width: 40, height: 40 },
+            Operation::DrawStroke { layer_id: layer_id.clone(), stroke: stroke(&[(0.0, 15.0), (30.0, 15.0)]) },
+            Operation::DrawStroke { layer_id: layer_id.clone(), stroke: stroke(&[(12.0, 12.0), (18.0, 12.0)]) },
+            Operation::DrawStroke { layer_id: "b".to_string(), stroke: stroke(&[(0.0, 15.0), (30.0, 15.0)]) },
+        ]);
+        log.push(Operation::ClearLayer { layer_id: layer_id.clone() });
+        log.undo();
+
+        let (erased, summary) = log.lasso_erased(&layer_id, &SQUARE).unwrap();
+        assert_eq!(summary, LassoEraseSummary { split_strokes: 1, removed_strokes: 1 });
+        // 作成・分割した2本・他のレイヤー・画素の消去（リドゥ可能な操作は破棄）
+        assert_eq!((erased.len(), erased.position()), (5, 5));
+        let seqs: Vec<u64> = erased.entries().iter().map(|entry| entry.seq).collect();
+        assert_eq!(&seqs[..2], &[log.entries()[0].seq, log.entries()[1].seq]);
+        assert!(seqs.iter().collect::<std::collections::HashSet<_>>().len() == seqs.len());
+        assert!(matches!(erased.entries()[4].operation, Operation::EraseSelection { .. }));
+
+        // キャンバスを回転した後は、それより前のストロークは分割しない
+        let mut rotated = log.clone();
+        rotated.seek(4).unwrap();
+        rotated.push(Operation::TransformCanvas { transform: crate::drawing_engine::CanvasTransform::FlipHorizontal });
+        let (erased, summary) = rotated.lasso_erased(&layer_id, &SQUARE).unwrap();
+        assert_eq!(summary, LassoEraseSummary::default());
+        assert_eq!(erased.len(), rotated.len() + 1);
+        assert!(log.lasso_erased(&layer_id, &SQUARE[..2]).is_none());
+    }
+}
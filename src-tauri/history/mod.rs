@@ -0,0 +1,844 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use log::{info, debug, warn};
+use crate::drawing_engine::{DrawingEngine, DrawStroke, Vertex2D, CanvasTransform};
+use crate::selection::{apply_selection_transform, Selection, SelectionTransform};
+use crate::brush::{stamps::select_rasterizer, StampSettings};
+
+// ストロークの再生
+pub mod replay;
+pub use replay::{ReplayFrame, ReplayOptions, build_replay_plan};
+
+// 解像度変更時の再ラスタライズ
+pub mod rescale;
+pub use rescale::RescaleSummary;
+
+// 長いストロークの点の間引き
+pub mod simplify;
+pub use simplify::SIMPLIFY_MIN_POINTS;
+
+// カーソル位置のストロークを選ぶためのIDバッファ
+pub mod pick;
+pub use pick::StrokePickBuffer;
+
+// 確定したストロークの一覧と削除
+pub mod strokes;
+pub use strokes::{StrokeInfo, StrokeTool};
+
+// 投げ縄の範囲の消去とストロークの分割
+pub mod lasso;
+pub use lasso::LassoEraseSummary;
+
+// GPUに触れる前の操作の検証
+pub mod validate;
+pub use validate::{ValidationIssue, validate_operations};
+
+// アンドゥで先頭から再生しないためのチェックポイント
+pub mod checkpoint;
+pub use checkpoint::HistoryCheckpoints;
+
+/// 操作ログのフォーマットバージョン
+///
+/// 2: 線幅をピクセル単位で記録する（1 では正規化座標での幅 × 1000 だった）
+pub const OPERATION_LOG_VERSION: u32 = 2;
+
+/// 履歴管理のエラー型
+#[derive(Debug)]
+pub enum HistoryError {
+    InvalidPosition(usize, usize),
+    UnsupportedVersion(u32),
+    ReplayFailed(u64, String),
+    SerializationFailed(String),
+    StrokeNotFound(u64),
+}
+
+impl fmt::Display for HistoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HistoryError::InvalidPosition(position, len) => {
+                write!(f, "無効な履歴位置です: {} (履歴数: {})", position, len)
+            }
+            HistoryError::UnsupportedVersion(version) => {
+                write!(f, "未対応の操作ログバージョンです: {}", version)
+            }
+            HistoryError::ReplayFailed(seq, msg) => {
+                write!(f, "操作 #{} の再生に失敗しました: {}", seq, msg)
+            }
+            HistoryError::SerializationFailed(msg) => {
+                write!(f, "操作ログのシリアライズに失敗しました: {}", msg)
+            }
+            HistoryError::StrokeNotFound(seq) => {
+                write!(f, "ストローク #{} が見つかりません", seq)
+            }
+        }
+    }
+}
+
+impl Error for HistoryError {}
+
+/// ストロークの入力点（キャンバスのピクセル座標）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StrokePointRecord {
+    pub x: f32,
+    pub y: f32,
+    pub pressure: f32,
+    /// ストローク開始からの経過時間（ミリ秒。記録していない場合は None）
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u32>,
+}
+
+/// ベクターとして保存されるストローク
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeRecord {
+    pub points: Vec<StrokePointRecord>,
+    pub color: [f32; 4],
+    /// 筆圧 1.0 での線幅（ピクセル）
+    pub base_width: f32,
+}
+
+impl StrokeRecord {
+    /// 描画用ストロークに変換（座標・線幅はピクセルのまま。正規化座標への変換はシェーダーで行う）
+    pub fn to_draw_stroke(&self) -> DrawStroke {
+        let points = self.points.iter()
+            .map(|p| Vertex2D::new(p.x, p.y, self.color, self.base_width * p.pressure))
+            .collect();
+
+        DrawStroke {
+            points,
+            color: self.color,
+            base_width: self.base_width,
+            is_closed: false,
+        }
+    }
+}
+
+/// 貼り付け操作に記録する形式（Base64 の PNG）に画像をエンコード
+pub fn encode_png_base64(image: &image::RgbaImage) -> Result<String, String> {
+    let mut png = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut png, image::ImageFormat::Png)
+        .map_err(|e| format!("PNGエンコードに失敗: {}", e))?;
+    Ok(STANDARD.encode(png.into_inner()))
+}
+
+/// 履歴に記録される文書操作
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Operation {
+    CreateLayer {
+        layer_id: String,
+        width: u32,
+        height: u32,
+    },
+    RemoveLayer {
+        layer_id: String,
+    },
+    ClearLayer {
+        layer_id: String,
+    },
+    FillLayer {
+        layer_id: String,
+        color: [f32; 4],
+    },
+    DrawLine {
+        layer_id: String,
+        x1: f32,
+        y1: f32,
+        x2: f32,
+        y2: f32,
+        color: [f32; 4],
+        width: f32,
+    },
+    DrawStroke {
+        layer_id: String,
+        stroke: StrokeRecord,
+    },
+    /// 消しゴムのストローク（`stroke.color` のアルファが消す強さ。色は使わない）
+    EraseStroke {
+        layer_id: String,
+        stroke: StrokeRecord,
+    },
+    /// スタンプブラシのストローク（スタンプの配置は `seed` から再現する）
+    DrawBrushStroke {
+        layer_id: String,
+        points: Vec<StrokePointRecord>,
+        color: [f32; 4],
+        brush: StampSettings,
+        seed: u64,
+        /// 使ったブラシのID（`パックID/ブラシID`。記録する前の操作ログでは None）
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        brush_id: Option<String>,
+    },
+    /// アンチエイリアスなしの 1px の線（ドット絵用。座標はレイヤーのピクセル座標）
+    DrawPixelStroke {
+        layer_id: String,
+        points: Vec<(f32, f32)>,
+        color: [f32; 4],
+        pixel_perfect: bool,
+    },
+    /// 選択範囲の画素の移動・拡大縮小・回転
+    TransformSelection {
+        layer_id: String,
+        selection: Selection,
+        transform: SelectionTransform,
+    },
+    /// 選択範囲の画素の消去（切り取り）
+    EraseSelection {
+        layer_id: String,
+        selection: Selection,
+    },
+    /// ラスター画像の貼り付け（取り込んだ画像など。PNGをBase64で保持）
+    PasteImage {
+        layer_id: String,
+        x: u32,
+        y: u32,
+        png_base64: String,
+    },
+    /// キャンバス全体の回転・反転（全レイヤーに適用）
+    TransformCanvas {
+        transform: CanvasTransform,
+    },
+    /// 無限キャンバスモードの切り替え
+    SetInfiniteCanvas {
+        enabled: bool,
+    },
+    /// 無限キャンバスの表示範囲の移動（左上のキャンバス座標。全レイヤーに適用）
+    SetViewport {
+        x: i32,
+        y: i32,
+    },
+}
+
+impl Operation {
+    /// 操作対象のレイヤーID（キャンバス全体への操作は None）
+    pub fn layer_id(&self) -> Option<&str> {
+        match self {
+            Operation::CreateLayer { layer_id, .. }
+            | Operation::RemoveLayer { layer_id }
+            | Operation::ClearLayer { layer_id }
+            | Operation::FillLayer { layer_id, .. }
+            | Operation::DrawLine { layer_id, .. }
+            | Operation::DrawStroke { layer_id, .. }
+            | Operation::EraseStroke { layer_id, .. }
+            | Operation::DrawBrushStroke { layer_id, .. }
+            | Operation::DrawPixelStroke { layer_id, .. }
+            | Operation::TransformSelection { layer_id, .. }
+            | Operation::EraseSelection { layer_id, .. }
+            | Operation::PasteImage { layer_id, .. } => Some(layer_id),
+            Operation::TransformCanvas { .. }
+            | Operation::SetInfiniteCanvas { .. }
+            | Operation::SetViewport { .. } => None,
+        }
+    }
+
+    /// 画像貼り付け操作を作成
+    pub fn paste_image(layer_id: &str, x: u32, y: u32, image: &image::RgbaImage) -> Result<Self, String> {
+        Ok(Operation::PasteImage {
+            layer_id: layer_id.to_string(),
+            x,
+            y,
+            png_base64: encode_png_base64(image)?,
+        })
+    }
+
+    /// 操作をエンジンに適用（描画コマンドと同じ結果になるように再現）
+    pub fn apply(
+        &self,
+        engine: &mut DrawingEngine,
+        layers: &mut HashMap<String, (u32, u32)>,
+    ) -> Result<(), String> {
+        match self {
+            Operation::CreateLayer { layer_id, width, height } => {
+                engine.create_layer_texture(layer_id, *width, *height)
+                    .map_err(|e| e.to_string())?;
+                engine.clear_layer_texture(layer_id, Some(wgpu::Color::TRANSPARENT))
+                    .map_err(|e| e.to_string())?;
+                layers.insert(layer_id.clone(), (*width, *height));
+            }
+            Operation::RemoveLayer { layer_id } => {
+                engine.remove_layer_texture(layer_id);
+                layers.remove(layer_id);
+            }
+            Operation::ClearLayer { layer_id } => {
+                engine.clear_layer_tiles(layer_id);
+                engine.clear_layer_texture(layer_id, Some(wgpu::Color::TRANSPARENT))
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::FillLayer { layer_id, color } => {
+                let fill = wgpu::Color {
+                    r: color[0] as f64,
+                    g: color[1] as f64,
+                    b: color[2] as f64,
+                    a: color[3] as f64,
+                };
+                engine.clear_layer_texture(layer_id, Some(fill))
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::DrawLine { layer_id, x1, y1, x2, y2, color, width } => {
+                engine.draw_line_to_layer(layer_id, (*x1, *y1), (*x2, *y2), *color, *width)
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::DrawStroke { layer_id, stroke } => {
+                engine.draw_stroke_to_layer(layer_id, &stroke.to_draw_stroke())
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::EraseStroke { layer_id, stroke } => {
+                engine.erase_stroke_from_layer(layer_id, &stroke.to_draw_stroke())
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::DrawBrushStroke { layer_id, points, color, brush, seed, .. } => {
+                let stamps = brush.stamps(points, *seed);
+                engine.draw_stamps_to_layer(layer_id, &stamps, &brush.shape(*color), select_rasterizer(stamps.len()))
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::DrawPixelStroke { layer_id, points, color, pixel_perfect } => {
+                engine.draw_pixel_stroke_to_layer(layer_id, points, *color, *pixel_perfect)
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::TransformSelection { layer_id, selection, transform } => {
+                // テクスチャの読み出しは poll(Wait) で完了するため、同期的に待っても詰まらない
+                let image = pollster::block_on(engine.get_layer_image(layer_id))
+                    .map_err(|e| e.to_string())?;
+                let transformed = apply_selection_transform(&image, selection, transform);
+                engine.write_layer_image(layer_id, 0, 0, &transformed)
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::EraseSelection { layer_id, selection } => {
+                let mut image = pollster::block_on(engine.get_layer_image(layer_id))
+                    .map_err(|e| e.to_string())?;
+                selection.erase(&mut image);
+                engine.write_layer_image(layer_id, 0, 0, &image)
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::PasteImage { layer_id, x, y, png_base64 } => {
+                let png = STANDARD.decode(png_base64)
+                    .map_err(|e| format!("画像データのデコードに失敗: {}", e))?;
+                let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+                    .map_err(|e| format!("PNGデコードに失敗: {}", e))?
+                    .to_rgba8();
+                engine.write_layer_image(layer_id, *x, *y, &image)
+                    .map_err(|e| e.to_string())?;
+            }
+            Operation::TransformCanvas { transform } => {
+                if engine.infinite_canvas().is_some() {
+                    return Err("無限キャンバスでは回転・反転できません".to_string());
+                }
+                let mut layer_ids: Vec<String> = layers.keys().cloned().collect();
+                layer_ids.sort();
+                for layer_id in layer_ids {
+                    let size = engine.transform_layer_texture(&layer_id, *transform)
+                        .map_err(|e| e.to_string())?;
+                    layers.insert(layer_id, size);
+                }
+            }
+            Operation::SetInfiniteCanvas { enabled } => {
+                engine.set_infinite_canvas(*enabled);
+            }
+            Operation::SetViewport { x, y } => {
+                let mut layer_ids: Vec<String> = layers.keys().cloned().collect();
+                layer_ids.sort();
+                engine.scroll_infinite_canvas(&layer_ids, (*x, *y))
+                    .map_err(|e| e.to_string())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 履歴エントリ
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct HistoryEntry {
+    pub seq: u64,
+    pub timestamp: i64,
+    pub operation: Operation,
+}
+
+/// フロントエンド向けの履歴状態
+#[derive(Debug, Clone, Serialize)]
+pub struct HistoryInfo {
+    pub position: usize,
+    pub length: usize,
+    pub can_undo: bool,
+    pub can_redo: bool,
+}
+
+/// 永続化可能な操作ログ（アンドゥ・リドゥの基盤）
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationLog {
+    version: u32,
+    entries: Vec<HistoryEntry>,
+    /// 適用済みエントリ数（entries[..position] が現在の状態）
+    position: usize,
+    next_seq: u64,
+}
+
+impl Default for OperationLog {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl OperationLog {
+    /// 空の操作ログを作成
+    pub fn new() -> Self {
+        Self {
+            version: OPERATION_LOG_VERSION,
+            entries: Vec::new(),
+            position: 0,
+            next_seq: 1,
+        }
+    }
+
+    /// 操作列から全て適用済みの操作ログを作成
+    pub fn from_operations(operations: impl IntoIterator<Item = Operation>) -> Self {
+        let mut log = Self::new();
+        for operation in operations {
+            log.push(operation);
+        }
+        log
+    }
+
+    /// 操作を記録（リドゥ可能な操作は破棄される）
+    pub fn push(&mut self, operation: Operation) -> u64 {
+        if self.position < self.entries.len() {
+            debug!("[OperationLog] リドゥ履歴を破棄: {} 件", self.entries.len() - self.position);
+            self.entries.truncate(self.position);
+        }
+
+        let seq = self.next_seq;
+        self.next_seq += 1;
+        self.entries.push(HistoryEntry {
+            seq,
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            operation,
+        });
+        self.position = self.entries.len();
+        seq
+    }
+
+    /// 1操作戻す
+    pub fn undo(&mut self) -> Option<&HistoryEntry> {
+        if self.position == 0 {
+            return None;
+        }
+        self.position -= 1;
+        self.entries.get(self.position)
+    }
+
+    /// 1操作進める
+    pub fn redo(&mut self) -> Option<&HistoryEntry> {
+        if self.position >= self.entries.len() {
+            return None;
+        }
+        self.position += 1;
+        self.entries.get(self.position - 1)
+    }
+
+    /// 任意の履歴位置へ移動
+    pub fn seek(&mut self, position: usize) -> Result<(), HistoryError> {
+        if position > self.entries.len() {
+            return Err(HistoryError::InvalidPosition(position, self.entries.len()));
+        }
+        self.position = position;
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        self.position > 0
+    }
+
+    pub fn can_redo(&self) -> bool {
+        self.position < self.entries.len()
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// 全エントリ（リドゥ可能なものを含む）
+    pub fn entries(&self) -> &[HistoryEntry] {
+        &self.entries
+    }
+
+    /// 現在の状態を構成する適用済みエントリ
+    pub fn applied_entries(&self) -> &[HistoryEntry] {
+        &self.entries[..self.position]
+    }
+
+    /// レイヤーの内容の世代（レイヤーに掛かる適用済みの操作の並びが同じなら同じ値）
+    ///
+    /// キャンバス全体への操作も含める。レイヤーから作ったキャッシュが古いかの判定に使う。
+    pub fn layer_revision(&self, layer_id: &str) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        layer_id.hash(&mut hasher);
+        for entry in self.applied_entries() {
+            if entry.operation.layer_id().is_none_or(|id| id == layer_id) {
+                entry.seq.hash(&mut hasher);
+            }
+        }
+        hasher.finish()
+    }
+
+    /// 貼り付け画像としてCPU側に保持しているデータのバイト数（リドゥ可能なものを含む）
+    pub fn raster_data_bytes(&self) -> u64 {
+        self.entries.iter().map(|entry| match &entry.operation {
+            Operation::PasteImage { png_base64, .. } => png_base64.len() as u64,
+            _ => 0,
+        }).sum()
+    }
+
+    pub fn info(&self) -> HistoryInfo {
+        HistoryInfo {
+            position: self.position,
+            length: self.entries.len(),
+            can_undo: self.can_undo(),
+            can_redo: self.can_redo(),
+        }
+    }
+
+    /// 読み込んだログの整合性を検証
+    pub fn validate(&self) -> Result<(), HistoryError> {
+        if self.version > OPERATION_LOG_VERSION {
+            return Err(HistoryError::UnsupportedVersion(self.version));
+        }
+        if self.position > self.entries.len() {
+            return Err(HistoryError::InvalidPosition(self.position, self.entries.len()));
+        }
+        Ok(())
+    }
+
+    pub fn to_json(&self) -> Result<String, HistoryError> {
+        serde_json::to_string(self)
+            .map_err(|e| HistoryError::SerializationFailed(e.to_string()))
+    }
+
+    /// 古いバージョンのログを現在の形式に変換する
+    ///
+    /// バージョン 1 の線幅は正規化座標での幅 × 1000 で、レイヤーの大きさで見た目の太さが変わっていた。
+    /// 記録時のレイヤーの大きさ（縦横の平均）でピクセル単位に換算する。
+    pub fn upgrade(&mut self) {
+        if self.version >= OPERATION_LOG_VERSION {
+            return;
+        }
+        let mut sizes: HashMap<String, (u32, u32)> = HashMap::new();
+        let mut converted = 0;
+        for entry in &mut self.entries {
+            let to_pixels = |sizes: &HashMap<String, (u32, u32)>, layer_id: &str, width: f32| {
+                let (w, h) = sizes.get(layer_id).copied().unwrap_or((1000, 1000));
+                width * 0.001 * (w + h) as f32 * 0.5
+            };
+            match &mut entry.operation {
+                Operation::CreateLayer { layer_id, width, height } => {
+                    sizes.insert(layer_id.clone(), (*width, *height));
+                }
+                Operation::TransformCanvas {
+                    transform: CanvasTransform::Rotate90Clockwise | CanvasTransform::Rotate90CounterClockwise,
+                } => {
+                    for size in sizes.values_mut() {
+                        *size = (size.1, size.0);
+                    }
+                }
+                Operation::DrawLine { layer_id, width, .. } => {
+                    *width = to_pixels(&sizes, layer_id, *width);
+                    converted += 1;
+                }
+                Operation::DrawStroke { layer_id, stroke } => {
+                    stroke.base_width = to_pixels(&sizes, layer_id, stroke.base_width);
+                    converted += 1;
+                }
+                _ => {}
+            }
+        }
+        info!("[OperationLog] 操作ログを変換: バージョン {} → {} ({} 件の線幅)", self.version, OPERATION_LOG_VERSION, converted);
+        self.version = OPERATION_LOG_VERSION;
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, HistoryError> {
+        let log: OperationLog = serde_json::from_str(json)
+            .map_err(|e| HistoryError::SerializationFailed(e.to_string()))?;
+        log.restored()
+    }
+
+    /// ファイルから読み込んだログを検証し、現在の形式に揃える
+    pub fn restored(mut self) -> Result<Self, HistoryError> {
+        self.validate()?;
+        self.upgrade();
+        // 古いログで next_seq が欠けていても連番が重複しないようにする
+        let max_seq = self.entries.iter().map(|e| e.seq).max().unwrap_or(0);
+        self.next_seq = self.next_seq.max(max_seq + 1);
+        Ok(self)
+    }
+
+    /// 既存レイヤーを破棄し、適用済みの操作を先頭から再生してエンジン状態を再構築
+    pub fn rebuild(
+        &self,
+        engine: &mut DrawingEngine,
+        layers: &mut HashMap<String, (u32, u32)>,
+    ) -> Result<(), HistoryError> {
+        info!("[OperationLog] 状態再構築開始: {} / {} 操作", self.position, self.entries.len());
+
+        Self::clear_engine_state(engine, layers);
+        self.replay_range(0..self.position, engine, layers)?;
+
+        info!("[OperationLog] 状態再構築完了: {} レイヤー", layers.len());
+        Ok(())
+    }
+
+    /// エンジンから `layers` のレイヤーを取り除き、操作を1つも適用していない状態にする
+    pub fn clear_engine_state(engine: &mut DrawingEngine, layers: &mut HashMap<String, (u32, u32)>) {
+        for layer_id in layers.keys() {
+            engine.remove_layer_texture(layer_id);
+        }
+        layers.clear();
+        engine.set_infinite_canvas(false);
+    }
+
+    /// 適用済みの操作のうち `range` の位置のものを再生する（エンジンは `range.start` の時点の状態にしておく）
+    pub fn replay_range(
+        &self,
+        range: std::ops::Range<usize>,
+        engine: &mut DrawingEngine,
+        layers: &mut HashMap<String, (u32, u32)>,
+    ) -> Result<(), HistoryError> {
+        for entry in self.applied_entries().get(range).unwrap_or_default() {
+            if let Err(e) = entry.operation.apply(engine, layers) {
+                warn!("[OperationLog] 操作 #{} の再生に失敗: {}", entry.seq, e);
+                return Err(HistoryError::ReplayFailed(entry.seq, e));
+            }
+        }
+        Ok(())
+    }
+
+    /// 先頭から `position` 件のエントリの並びを表す値（途中の状態を覚えておくときの照合に使う）
+    ///
+    /// エントリの `seq` と記録時刻だけから作るため、同じ `seq` のまま操作を書き換えたログとは区別できない。
+    pub fn prefix_fingerprint(&self, position: usize) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        for entry in &self.entries[..position.min(self.entries.len())] {
+            entry.seq.hash(&mut hasher);
+            entry.timestamp.hash(&mut hasher);
+        }
+        hasher.finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn line_op(layer_id: &str) -> Operation {
+        Operation::DrawLine {
+            layer_id: layer_id.to_string(),
+            x1: 10.0,
+            y1: 10.0,
+            x2: 100.0,
+            y2: 100.0,
+            color: [1.0, 0.0, 0.0, 1.0],
+            width: 3.0,
+        }
+    }
+
+    fn create_op(layer_id: &str) -> Operation {
+        Operation::CreateLayer {
+            layer_id: layer_id.to_string(),
+            width: 256,
+            height: 256,
+        }
+    }
+
+    #[test]
+    fn test_push_undo_redo() {
+        let mut log = OperationLog::new();
+        assert!(!log.can_undo());
+
+        log.push(create_op("layer1"));
+        log.push(line_op("layer1"));
+        assert_eq!(log.position(), 2);
+
+        let undone = log.undo().unwrap();
+        assert_eq!(undone.seq, 2);
+        assert_eq!(log.applied_entries().len(), 1);
+        assert!(log.can_redo());
+
+        let redone = log.redo().unwrap();
+        assert_eq!(redone.seq, 2);
+        assert!(log.redo().is_none());
+    }
+
+    #[test]
+    fn test_push_discards_redo_branch() {
+        let mut log = OperationLog::new();
+        log.push(create_op("layer1"));
+        log.push(line_op("layer1"));
+        log.undo();
+
+        let seq = log.push(Operation::ClearLayer { layer_id: "layer1".to_string() });
+        assert_eq!(seq, 3);
+        assert_eq!(log.len(), 2);
+        assert!(!log.can_redo());
+    }
+
+    #[test]
+    fn test_layer_revision_tracks_layer_operations() {
+        let mut log = OperationLog::from_operations([create_op("layer1"), create_op("layer2")]);
+        let revision = log.layer_revision("layer1");
+        log.push(line_op("layer2"));
+        assert_eq!(log.layer_revision("layer1"), revision);
+        log.push(line_op("layer1"));
+        assert_ne!(log.layer_revision("layer1"), revision);
+        log.undo();
+        assert_eq!(log.layer_revision("layer1"), revision);
+    }
+
+    #[test]
+    fn test_seek_bounds() {
+        let mut log = OperationLog::new();
+        log.push(create_op("layer1"));
+
+        assert!(log.seek(0).is_ok());
+        assert!(log.applied_entries().is_empty());
+        assert!(matches!(log.seek(5), Err(HistoryError::InvalidPosition(5, 1))));
+    }
+
+    #[test]
+    fn test_json_roundtrip() {
+        let mut log = OperationLog::new();
+        log.push(create_op("layer1"));
+        log.push(Operation::DrawStroke {
+            layer_id: "layer1".to_string(),
+            stroke: StrokeRecord {
+                points: vec![
+                    StrokePointRecord { x: 1.0, y: 2.0, pressure: 0.5, time_ms: None },
+                    StrokePointRecord { x: 3.0, y: 4.0, pressure: 1.0, time_ms: Some(16) },
+                ],
+                color: [0.0, 0.0, 1.0, 1.0],
+                base_width: 2.0,
+            },
+        });
+        log.undo();
+
+        let json = log.to_json().unwrap();
+        let restored = OperationLog::from_json(&json).unwrap();
+        assert_eq!(restored.entries(), log.entries());
+        assert_eq!(restored.position(), 1);
+    }
+
+    #[test]
+    fn test_from_json_rejects_future_version() {
+        let json = r#"{"version":99,"entries":[],"position":0,"next_seq":1}"#;
+        assert!(matches!(OperationLog::from_json(json), Err(HistoryError::UnsupportedVersion(99))));
+    }
+
+    #[test]
+    fn test_stroke_record_conversion() {
+        let record = StrokeRecord {
+            points: vec![StrokePointRecord { x: 50.0, y: 50.0, pressure: 0.5, time_ms: None }],
+            color: [1.0, 1.0, 1.0, 1.0],
+            base_width: 4.0,
+        };
+        let stroke = record.to_draw_stroke();
+        assert_eq!(stroke.points.len(), 1);
+        assert_eq!(stroke.points[0].position, [50.0, 50.0]);
+        assert_eq!(stroke.points[0].line_width, 2.0);
+    }
+
+    #[test]
+    fn test_upgrade_v1_widths() {
+        let json = r#"{"version":1,"entries":[
+            {"seq":1,"timestamp":0,"operation":{"type":"CreateLayer","layer_id":"a","width":2000,"height":1000}},
+            {"seq":2,"timestamp":0,"operation":{"type":"DrawLine","layer_id":"a","x1":0,"y1":0,"x2":10,"y2":10,"color":[0,0,0,1],"width":2.0}},
+            {"seq":3,"timestamp":0,"operation":{"type":"DrawStroke","layer_id":"a","stroke":{"points":[],"color":[0,0,0,1],"base_width":4.0}}}
+        ],"position":3,"next_seq":4}"#;
+        let log = OperationLog::from_json(json).unwrap();
+        assert!(matches!(log.entries()[1].operation, Operation::DrawLine { width, .. } if (width - 3.0).abs() < 1e-4));
+        assert!(matches!(&log.entries()[2].operation, Operation::DrawStroke { stroke, .. } if (stroke.base_width - 6.0).abs() < 1e-4));
+
+        // 変換済みのログは再度変換しない
+        let restored = OperationLog::from_json(&log.to_json().unwrap()).unwrap();
+        assert_eq!(restored.entries(), log.entries());
+    }
+
+    #[tokio::test]
+    async fn test_rebuild_matches_live_drawing() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = DrawingEngine::new();
+        engine.initialize().await?;
+        let mut layers = HashMap::new();
+
+        let mut log = OperationLog::new();
+        for op in [create_op("layer1"), line_op("layer1")] {
+            op.apply(&mut engine, &mut layers)?;
+            log.push(op);
+        }
+        let live = engine.get_layer_texture_data("layer1").await?;
+
+        log.rebuild(&mut engine, &mut layers)?;
+        let replayed = engine.get_layer_texture_data("layer1").await?;
+        assert_eq!(live, replayed);
+
+        // 線描画前の状態に戻すと透明になる
+        log.seek(1)?;
+        log.rebuild(&mut engine, &mut layers)?;
+        let empty = engine.get_layer_texture_data("layer1").await?;
+        assert!(empty.iter().all(|&b| b == 0));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_paste_image_roundtrip() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = DrawingEngine::new();
+        engine.initialize().await?;
+        let mut layers = HashMap::new();
+        create_op("layer1").apply(&mut engine, &mut layers)?;
+
+        // 右下にはみ出す画像は切り取られる
+        let mut image = image::RgbaImage::new(4, 4);
+        image.put_pixel(0, 0, image::Rgba([10, 20, 30, 255]));
+        let paste = Operation::paste_image("layer1", 254, 254, &image)?;
+        paste.apply(&mut engine, &mut layers)?;
+
+        let result = engine.get_layer_image("layer1").await?;
+        assert_eq!(*result.get_pixel(254, 254), image::Rgba([10, 20, 30, 255]));
+        assert_eq!(*result.get_pixel(0, 0), image::Rgba([0, 0, 0, 0]));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_transform_canvas_rotates_all_layers() -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = DrawingEngine::new();
+        engine.initialize().await?;
+        let mut layers = HashMap::new();
+        Operation::CreateLayer { layer_id: "layer1".to_string(), width: 8, height: 4 }
+            .apply(&mut engine, &mut layers)?;
+
+        let mut image = image::RgbaImage::new(1, 1);
+        image.put_pixel(0, 0, image::Rgba([10, 20, 30, 255]));
+        Operation::paste_image("layer1", 0, 0, &image)?.apply(&mut engine, &mut layers)?;
+
+        // 時計回りに90度回転すると左上の画素は右上に移る
+        Operation::TransformCanvas { transform: CanvasTransform::Rotate90Clockwise }
+            .apply(&mut engine, &mut layers)?;
+        assert_eq!(layers["layer1"], (4, 8));
+        let rotated = engine.get_layer_image("layer1").await?;
+        assert_eq!(rotated.dimensions(), (4, 8));
+        assert_eq!(*rotated.get_pixel(3, 0), image::Rgba([10, 20, 30, 255]));
+        assert_eq!(*rotated.get_pixel(0, 0), image::Rgba([0, 0, 0, 0]));
+
+        Operation::TransformCanvas { transform: CanvasTransform::FlipHorizontal }
+            .apply(&mut engine, &mut layers)?;
+        let flipped = engine.get_layer_image("layer1").await?;
+        assert_eq!(*flipped.get_pixel(0, 0), image::Rgba([10, 20, 30, 255]));
+        Ok(())
+    }
+}
@@ -0,0 +1,234 @@
+use crate::drawing_engine::{DrawStroke, StrokeMesh, Vertex2D};
+use super::{HistoryEntry, Operation, OperationLog};
+
+/// レイヤーのピクセルごとに、一番上に描かれたストローク（履歴の `seq`）を記録したIDバッファ
+///
+/// 履歴のベクター操作（線・ストローク・スタンプ・ドット）を描画と同じ形でCPUに塗り、
+/// 消しゴムは塗った範囲のIDを消す。作っておけばカーソル位置のストロークは1回の参照で分かる。
+/// 選択範囲の消去は完全に選択したピクセルのIDだけを消す。
+/// 消去・塗りつぶしや画素を動かす操作（選択範囲の変形・貼り付け・キャンバスの回転など）の
+/// 前に描かれたストロークは、見た目と形が一致しないため選べない。
+#[derive(Debug, Clone)]
+pub struct StrokePickBuffer {
+    width: u32,
+    height: u32,
+    ids: Vec<u64>,
+    /// 作ったときの履歴の位置と最後に適用した操作（履歴が変われば作り直す）
+    built_for: (usize, Option<u64>),
+}
+
+impl StrokePickBuffer {
+    fn new(width: u32, height: u32, built_for: (usize, Option<u64>)) -> Self {
+        Self { width, height, ids: vec![0; width as usize * height as usize], built_for }
+    }
+
+    /// レイヤー座標の位置にあるストロークの `seq`（何もなければ None）
+    pub fn pick(&self, x: f32, y: f32) -> Option<u64> {
+        if !(x >= 0.0 && y >= 0.0 && x < self.width as f32 && y < self.height as f32) {
+            return None;
+        }
+        let id = self.ids[y as usize * self.width as usize + x as usize];
+        (id != 0).then_some(id)
+    }
+
+    /// 履歴とレイヤーの大きさが作ったときのままか
+    pub fn is_current(&self, log: &OperationLog, size: (u32, u32)) -> bool {
+        self.built_for == pick_key(log) && (self.width, self.height) == size
+    }
+
+    fn reset(&mut self) {
+        self.ids.fill(0);
+    }
+
+    /// メッシュの三角形が掛かるピクセル（中心が内側にあるもの）を `id` にする
+    fn fill_mesh(&mut self, mesh: &StrokeMesh, id: u64) {
+        for triangle in mesh.triangles().chunks_exact(3) {
+            self.fill_triangle([triangle[0].position, triangle[1].position, triangle[2].position], id);
+        }
+    }
+
+    fn fill_triangle(&mut self, [a, b, c]: [[f32; 2]; 3], id: u64) {
+        let edge = |p: [f32; 2], q: [f32; 2], x: f32, y: f32| (q[0] - p[0]) * (y - p[1]) - (q[1] - p[1]) * (x - p[0]);
+        let area = edge(a, b, c[0], c[1]);
+        if area == 0.0 {
+            return;
+        }
+        let Some((left, top, right, bottom)) = self.clip(
+            a[0].min(b[0]).min(c[0]),
+            a[1].min(b[1]).min(c[1]),
+            a[0].max(b[0]).max(c[0]),
+            a[1].max(b[1]).max(c[1]),
+        ) else {
+            return;
+        };
+        for y in top..bottom {
+            for x in left..right {
+                let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+                // 向きによらず、3辺すべての内側にあれば塗る
+                let inside = [edge(b, c, px, py), edge(c, a, px, py), edge(a, b, px, py)]
+                    .iter()
+                    .all(|w| *w * area >= 0.0);
+                if inside {
+                    self.ids[y as usize * self.width as usize + x as usize] = id;
+                }
+            }
+        }
+    }
+
+    fn fill_disc(&mut self, center: [f32; 2], radius: f32, id: u64) {
+        let Some((left, top, right, bottom)) = self.clip(
+            center[0] - radius,
+            center[1] - radius,
+            center[0] + radius,
+            center[1] + radius,
+        ) else {
+            return;
+        };
+        for y in top..bottom {
+            for x in left..right {
+                let (dx, dy) = (x as f32 + 0.5 - center[0], y as f32 + 0.5 - center[1]);
+                if dx * dx + dy * dy <= radius * radius {
+                    self.ids[y as usize * self.width as usize + x as usize] = id;
+                }
+            }
+        }
+    }
+
+    /// 範囲をレイヤー内のピクセル範囲（右端・下端は含まない）に切り詰める
+    fn clip(&self, min_x: f32, min_y: f32, max_x: f32, max_y: f32) -> Option<(u32, u32, u32, u32)> {
+        let left = min_x.floor().max(0.0) as u32;
+        let top = min_y.floor().max(0.0) as u32;
+        let right = (max_x.ceil().max(0.0) as u32).min(self.width);
+        let bottom = (max_y.ceil().max(0.0) as u32).min(self.height);
+        (left < right && top < bottom).then_some((left, top, right, bottom))
+    }
+
+    fn apply(&mut self, entry: &HistoryEntry) {
+        match &entry.operation {
+            Operation::DrawLine { x1, y1, x2, y2, color, width, .. } => {
+                let mut stroke = DrawStroke::new(*color, *width);
+                stroke.add_point(*x1, *y1, 1.0);
+                stroke.add_point(*x2, *y2, 1.0);
+                self.fill_mesh(&stroke.to_mesh(), entry.seq);
+            }
+            Operation::DrawStroke { stroke, .. } => {
+                self.fill_mesh(&stroke.to_draw_stroke().to_mesh(), entry.seq);
+            }
+            Operation::EraseStroke { stroke, .. } => {
+                self.fill_mesh(&stroke.to_draw_stroke().to_mesh(), 0);
+            }
+            Operation::DrawBrushStroke { points, brush, seed, .. } => {
+                for stamp in brush.stamps(points, *seed) {
+                    self.fill_disc(stamp.center, stamp.radius, entry.seq);
+                }
+            }
+            Operation::DrawPixelStroke { points, color, .. } => {
+                // 1px の線はピクセルの中心を結ぶ幅 1 の線として扱う
+                let points: Vec<Vertex2D> = points.iter()
+                    .map(|(x, y)| Vertex2D::new(x.floor() + 0.5, y.floor() + 0.5, *color, 1.0))
+                    .collect();
+                let stroke = DrawStroke { points, color: *color, base_width: 1.0, is_closed: false };
+                self.fill_mesh(&stroke.to_mesh(), entry.seq);
+                for point in &stroke.points {
+                    self.fill_disc(point.position, 0.5, entry.seq);
+                }
+            }
+            Operation::EraseSelection { selection, .. } => {
+                // 画素を動かさないので、消した範囲のIDだけを消す
+                let Some((left, top, right, bottom)) = self.clip(
+                    selection.x as f32,
+                    selection.y as f32,
+                    selection.x as f32 + selection.width as f32,
+                    selection.y as f32 + selection.height as f32,
+                ) else {
+                    return;
+                };
+                for y in top..bottom {
+                    for x in left..right {
+                        if selection.coverage(x as i64, y as i64) == 255 {
+                            self.ids[y as usize * self.width as usize + x as usize] = 0;
+                        }
+                    }
+                }
+            }
+            Operation::CreateLayer { .. }
+            | Operation::RemoveLayer { .. }
+            | Operation::ClearLayer { .. }
+            | Operation::FillLayer { .. }
+            | Operation::TransformSelection { .. }
+            | Operation::PasteImage { .. }
+            | Operation::TransformCanvas { .. }
+            | Operation::SetInfiniteCanvas { .. }
+            | Operation::SetViewport { .. } => self.reset(),
+        }
+    }
+}
+
+fn pick_key(log: &OperationLog) -> (usize, Option<u64>) {
+    (log.position(), log.applied_entries().last().map(|entry| entry.seq))
+}
+
+impl OperationLog {
+    /// 適用済みの操作から、レイヤーのストロークのIDバッファを作る
+    pub fn stroke_pick_buffer(&self, layer_id: &str, (width, height): (u32, u32)) -> StrokePickBuffer {
+        let mut buffer = StrokePickBuffer::new(width, height, pick_key(self));
+        let entries = self.applied_entries().iter()
+            .filter(|entry| entry.operation.layer_id().is_none_or(|id| id == layer_id));
+        for entry in entries {
+            buffer.apply(entry);
+        }
+        buffer
+    }
+
+    /// 適用済みのエントリを `seq` で探す
+    pub fn applied_entry(&self, seq: u64) -> Option<&HistoryEntry> {
+        self.applied_entries().iter().find(|entry| entry.seq == seq)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{StrokePointRecord, StrokeRecord};
+
+    fn stroke(points: &[(f32, f32)], width: f32) -> StrokeRecord {
+        StrokeRecord {
+            points: points.iter().map(|&(x, y)| StrokePointRecord { x, y, pressure: 1.0, time_ms: None }).collect(),
+            color: [0.0, 0.0, 0.0, 1.0],
+            base_width: width,
+        }
+    }
+
+    #[test]
+    fn test_pick_topmost_stroke() {
+        let layer_id = "layer".to_string();
+        let mut log = OperationLog::from_operations([
+            Operation::CreateLayer { layer_id: layer_id.clone(), width: 64, height: 64 },
+            Operation::DrawStroke { layer_id: layer_id.clone(), stroke: stroke(&[(0.0, 10.0), (64.0, 10.0)], 4.0) },
+            Operation::DrawStroke { layer_id: layer_id.clone(), stroke: stroke(&[(30.0, 0.0), (30.0, 64.0)], 4.0) },
+            Operation::DrawStroke { layer_id: "other".to_string(), stroke: stroke(&[(0.0, 40.0), (64.0, 40.0)], 4.0) },
+            Operation::EraseStroke { layer_id: layer_id.clone(), stroke: stroke(&[(50.0, 0.0), (50.0, 20.0)], 6.0) },
+        ]);
+        let seqs: Vec<u64> = log.entries().iter().map(|entry| entry.seq).collect();
+
+        let buffer = log.stroke_pick_buffer(&layer_id, (64, 64));
+        assert_eq!(buffer.pick(5.0, 10.5), Some(seqs[1]));
+        // 交差する位置では後から描いたストローク
+        assert_eq!(buffer.pick(30.5, 10.5), Some(seqs[2]));
+        // 消しゴムで消した部分と、他のレイヤーのストロークは選べない
+        assert_eq!(buffer.pick(50.5, 10.5), None);
+        assert_eq!(buffer.pick(5.0, 40.5), None);
+        assert_eq!(buffer.pick(5.0, 30.0), None);
+        assert_eq!(buffer.pick(-1.0, 10.0), None);
+        assert!(buffer.is_current(&log, (64, 64)));
+
+        // アンドゥすると作り直しが必要になり、消去より前のストロークは選べない
+        log.undo();
+        assert!(!buffer.is_current(&log, (64, 64)));
+        log.push(Operation::ClearLayer { layer_id: layer_id.clone() });
+        let buffer = log.stroke_pick_buffer(&layer_id, (64, 64));
+        assert_eq!(buffer.pick(5.0, 10.5), None);
+        assert!(log.applied_entry(seqs[1]).is_some());
+        assert!(log.applied_entry(seqs[4]).is_none());
+    }
+}
@@ -0,0 +1,224 @@
+use serde::{Deserialize, Serialize};
+use super::{HistoryEntry, HistoryError, Operation, StrokeRecord};
+
+/// 点にタイミング情報がない場合の1点あたりの間隔（ミリ秒）
+const DEFAULT_POINT_INTERVAL_MS: u32 = 8;
+
+/// ストローク再生の設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ReplayOptions {
+    /// 再生速度の倍率
+    pub speed: f32,
+    /// フレームの間隔（ミリ秒）
+    pub frame_interval_ms: u32,
+    /// 操作と操作の間の待ち時間の上限（ミリ秒）
+    pub max_gap_ms: u32,
+}
+
+impl Default for ReplayOptions {
+    fn default() -> Self {
+        Self {
+            speed: 1.0,
+            frame_interval_ms: 33,
+            max_gap_ms: 500,
+        }
+    }
+}
+
+/// 再生の1フレーム分の描画
+#[derive(Debug, Clone, PartialEq)]
+pub struct ReplayFrame {
+    /// 再生開始からの時刻（速度適用後、ミリ秒）
+    pub at_ms: u64,
+    /// このフレームで適用する操作（ストロークは途中までの部分ストロークに分割済み）
+    pub operations: Vec<Operation>,
+}
+
+/// レイヤーの描画過程を再生するフレーム列を作る
+///
+/// レイヤーが最後に作成された時点から、適用済みの操作を記録時のタイミングで並べる。
+/// ストロークは線分単位に分割するが、三角形分割は線分ごとに独立しているため
+/// 全フレームを適用した結果は元の描画と一致する。
+pub fn build_replay_plan(
+    entries: &[HistoryEntry],
+    layer_id: &str,
+    options: &ReplayOptions,
+) -> Result<Vec<ReplayFrame>, HistoryError> {
+    let start = entries.iter()
+        .rposition(|e| matches!(&e.operation, Operation::CreateLayer { layer_id: id, .. } if id == layer_id))
+        .map(|index| index + 1)
+        .unwrap_or(0);
+
+    // (再生時刻, エントリ番号, 操作)
+    let mut events: Vec<(u64, usize, Operation)> = Vec::new();
+    let mut clock: u64 = 0;
+    let mut previous_timestamp: Option<i64> = None;
+
+    for (index, entry) in entries.iter().enumerate().skip(start) {
+        match &entry.operation {
+            Operation::TransformCanvas { .. } => {
+                return Err(HistoryError::ReplayFailed(
+                    entry.seq,
+                    "キャンバスの回転・反転を含むレイヤーは再生できません".to_string(),
+                ));
+            }
+            Operation::SetViewport { .. } => {
+                return Err(HistoryError::ReplayFailed(
+                    entry.seq,
+                    "無限キャンバスの表示範囲の移動を含むレイヤーは再生できません".to_string(),
+                ));
+            }
+            operation if operation.layer_id() != Some(layer_id) => continue,
+            _ => {}
+        }
+
+        let duration = match &entry.operation {
+            Operation::DrawStroke { stroke, .. } | Operation::EraseStroke { stroke, .. } => stroke_duration_ms(stroke),
+            _ => 0,
+        };
+        if let Some(previous) = previous_timestamp {
+            let gap = (entry.timestamp - duration as i64 - previous).clamp(0, options.max_gap_ms as i64);
+            clock += gap as u64;
+        }
+        previous_timestamp = Some(entry.timestamp);
+
+        match &entry.operation {
+            Operation::DrawStroke { layer_id, stroke } if stroke.points.len() > 1 => {
+                for k in 1..stroke.points.len() {
+                    let segment = StrokeRecord {
+                        points: stroke.points[k - 1..=k].to_vec(),
+                        ..stroke.clone()
+                    };
+                    let at = clock + point_time_ms(stroke, k) as u64;
+                    events.push((at, index, Operation::DrawStroke { layer_id: layer_id.clone(), stroke: segment }));
+                }
+            }
+            operation => events.push((clock, index, operation.clone())),
+        }
+        clock += duration as u64;
+    }
+
+    let speed = if options.speed.is_finite() && options.speed > 0.0 { options.speed } else { 1.0 };
+    let interval = options.frame_interval_ms.max(1) as u64;
+
+    let mut frames: Vec<(u64, Vec<(usize, Operation)>)> = Vec::new();
+    for (at, index, operation) in events {
+        let frame_index = (at as f64 / speed as f64) as u64 / interval;
+        if frames.last().is_none_or(|(current, _)| *current != frame_index) {
+            frames.push((frame_index, Vec::new()));
+        }
+        let (_, operations) = frames.last_mut().expect("直前に追加済み");
+
+        // 同じストロークの連続する線分は1つの部分ストロークにまとめる
+        if let (Some((last_index, Operation::DrawStroke { stroke: merged, .. })), Operation::DrawStroke { stroke: segment, .. })
+            = (operations.last_mut(), &operation)
+        {
+            if *last_index == index {
+                merged.points.extend_from_slice(&segment.points[1..]);
+                continue;
+            }
+        }
+        operations.push((index, operation));
+    }
+
+    Ok(frames.into_iter().map(|(frame_index, operations)| ReplayFrame {
+        at_ms: frame_index * interval,
+        operations: operations.into_iter().map(|(_, operation)| operation).collect(),
+    }).collect())
+}
+
+/// ストロークの開始から k 番目の点までの経過時間
+fn point_time_ms(stroke: &StrokeRecord, k: usize) -> u32 {
+    stroke.points[k].time_ms.unwrap_or(k as u32 * DEFAULT_POINT_INTERVAL_MS)
+}
+
+fn stroke_duration_ms(stroke: &StrokeRecord) -> u32 {
+    match stroke.points.len() {
+        0 => 0,
+        len => point_time_ms(stroke, len - 1),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::StrokePointRecord;
+
+    fn entry(seq: u64, timestamp: i64, operation: Operation) -> HistoryEntry {
+        HistoryEntry { seq, timestamp, operation }
+    }
+
+    fn stroke(layer_id: &str, times: &[Option<u32>]) -> Operation {
+        Operation::DrawStroke {
+            layer_id: layer_id.to_string(),
+            stroke: StrokeRecord {
+                points: times.iter().enumerate()
+                    .map(|(i, &time_ms)| StrokePointRecord { x: i as f32, y: 0.0, pressure: 1.0, time_ms })
+                    .collect(),
+                color: [0.0, 0.0, 0.0, 1.0],
+                base_width: 2.0,
+            },
+        }
+    }
+
+    fn stroke_points(frames: &[ReplayFrame]) -> Vec<Vec<f32>> {
+        frames.iter().flat_map(|f| f.operations.iter()).filter_map(|op| match op {
+            Operation::DrawStroke { stroke, .. } => Some(stroke.points.iter().map(|p| p.x).collect()),
+            _ => None,
+        }).collect()
+    }
+
+    #[test]
+    fn test_stroke_split_by_timing() {
+        let entries = vec![
+            entry(1, 0, Operation::CreateLayer { layer_id: "a".to_string(), width: 8, height: 8 }),
+            entry(2, 1000, stroke("a", &[Some(0), Some(10), Some(50), Some(60)])),
+        ];
+        let options = ReplayOptions { frame_interval_ms: 40, ..ReplayOptions::default() };
+        let frames = build_replay_plan(&entries, "a", &options).unwrap();
+
+        assert_eq!(frames.iter().map(|f| f.at_ms).collect::<Vec<_>>(), vec![0, 40]);
+        assert_eq!(stroke_points(&frames), vec![vec![0.0, 1.0], vec![1.0, 2.0, 3.0]]);
+    }
+
+    #[test]
+    fn test_gaps_are_clamped_and_speed_applied() {
+        let entries = vec![
+            entry(1, 0, stroke("a", &[None, None])),
+            entry(2, 10_000, Operation::ClearLayer { layer_id: "a".to_string() }),
+            entry(3, 10_010, stroke("b", &[None, None])),
+        ];
+        let options = ReplayOptions { speed: 2.0, frame_interval_ms: 1, max_gap_ms: 500 };
+        let frames = build_replay_plan(&entries, "a", &options).unwrap();
+
+        // 線分は既定間隔 8ms、続くクリアは 8ms + 上限 500ms の後（2倍速）
+        assert_eq!(frames.iter().map(|f| f.at_ms).collect::<Vec<_>>(), vec![4, 254]);
+        assert!(matches!(frames[1].operations[0], Operation::ClearLayer { .. }));
+    }
+
+    #[test]
+    fn test_replay_starts_from_last_create() {
+        let entries = vec![
+            entry(1, 0, stroke("a", &[None, None])),
+            entry(2, 10, Operation::RemoveLayer { layer_id: "a".to_string() }),
+            entry(3, 20, Operation::CreateLayer { layer_id: "a".to_string(), width: 8, height: 8 }),
+            entry(4, 30, Operation::FillLayer { layer_id: "a".to_string(), color: [1.0; 4] }),
+        ];
+        let frames = build_replay_plan(&entries, "a", &ReplayOptions::default()).unwrap();
+        assert_eq!(frames.len(), 1);
+        assert!(matches!(frames[0].operations[..], [Operation::FillLayer { .. }]));
+    }
+
+    #[test]
+    fn test_transform_canvas_is_rejected() {
+        let entries = vec![
+            entry(1, 0, stroke("a", &[None, None])),
+            entry(2, 10, Operation::TransformCanvas { transform: crate::drawing_engine::CanvasTransform::Rotate180 }),
+        ];
+        assert!(matches!(
+            build_replay_plan(&entries, "a", &ReplayOptions::default()),
+            Err(HistoryError::ReplayFailed(2, _))
+        ));
+    }
+}
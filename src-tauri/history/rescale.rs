@@ -0,0 +1,234 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use log::{info, debug};
+use crate::drawing_engine::CanvasTransform;
+use crate::selection::SelectionTransform;
+use crate::brush::StampSettings;
+use super::{HistoryError, Operation, OperationLog, StrokeRecord};
+
+/// 解像度変更の結果
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RescaleSummary {
+    /// 座標を変換して再描画するベクター操作の数
+    pub vector_operations: usize,
+    /// ラスター画像を含むため画素の拡大縮小になったレイヤー
+    pub raster_layers: Vec<String>,
+}
+
+impl OperationLog {
+    /// 全操作を新しい解像度の座標系に変換した操作ログを作る
+    ///
+    /// 線やストロークは座標だけを変換するため、再生すると新しい解像度で
+    /// 三角形分割し直した鮮明な線になる（ピクセル単位の線幅は縦横の拡大率の平均で変換する）。
+    /// 貼り付けた画像だけは画素を拡大縮小する。
+    pub fn rescaled(&self, scale: (f32, f32)) -> Result<(OperationLog, RescaleSummary), HistoryError> {
+        if !(scale.0.is_finite() && scale.1.is_finite() && scale.0 > 0.0 && scale.1 > 0.0) {
+            return Err(HistoryError::ReplayFailed(0, format!("拡大率が不正です: {:?}", scale)));
+        }
+        info!("[OperationLog] 解像度変換開始: {:?} ({} 操作)", scale, self.entries.len());
+
+        let mut log = self.clone();
+        let mut summary = RescaleSummary::default();
+        // 90度回転の後は元の座標系に対して縦横が入れ替わる
+        let mut current_scale = scale;
+
+        for entry in &mut log.entries {
+            entry.operation = entry.operation.rescaled(current_scale)
+                .map_err(|e| HistoryError::ReplayFailed(entry.seq, e))?;
+
+            match &entry.operation {
+                Operation::DrawLine { .. }
+                | Operation::DrawStroke { .. }
+                | Operation::EraseStroke { .. }
+                | Operation::DrawBrushStroke { .. }
+                | Operation::DrawPixelStroke { .. } => summary.vector_operations += 1,
+                Operation::PasteImage { layer_id, .. } if !summary.raster_layers.contains(layer_id) => {
+                    summary.raster_layers.push(layer_id.clone());
+                }
+                Operation::TransformCanvas {
+                    transform: CanvasTransform::Rotate90Clockwise | CanvasTransform::Rotate90CounterClockwise,
+                } => current_scale = (current_scale.1, current_scale.0),
+                _ => {}
+            }
+        }
+
+        info!("[OperationLog] 解像度変換完了: ベクター操作 {} 件, ラスターレイヤー {} 件",
+              summary.vector_operations, summary.raster_layers.len());
+        Ok((log, summary))
+    }
+}
+
+impl Operation {
+    /// 座標系を拡大縮小した操作
+    pub fn rescaled(&self, (sx, sy): (f32, f32)) -> Result<Operation, String> {
+        let scale_size = |size: u32, s: f32| ((size as f32 * s).round() as u32).max(1);
+        let width_scale = (sx + sy) * 0.5;
+
+        Ok(match self {
+            Operation::CreateLayer { layer_id, width, height } => Operation::CreateLayer {
+                layer_id: layer_id.clone(),
+                width: scale_size(*width, sx),
+                height: scale_size(*height, sy),
+            },
+            Operation::DrawLine { layer_id, x1, y1, x2, y2, color, width } => Operation::DrawLine {
+                layer_id: layer_id.clone(),
+                x1: x1 * sx,
+                y1: y1 * sy,
+                x2: x2 * sx,
+                y2: y2 * sy,
+                color: *color,
+                width: width * width_scale,
+            },
+            Operation::DrawStroke { layer_id, stroke } => Operation::DrawStroke {
+                layer_id: layer_id.clone(),
+                stroke: StrokeRecord {
+                    points: stroke.points.iter().map(|p| {
+                        let mut point = *p;
+                        point.x *= sx;
+                        point.y *= sy;
+                        point
+                    }).collect(),
+                    base_width: stroke.base_width * width_scale,
+                    ..stroke.clone()
+                },
+            },
+            Operation::EraseStroke { layer_id, stroke } => Operation::EraseStroke {
+                layer_id: layer_id.clone(),
+                stroke: StrokeRecord {
+                    points: stroke.points.iter().map(|p| {
+                        let mut point = *p;
+                        point.x *= sx;
+                        point.y *= sy;
+                        point
+                    }).collect(),
+                    base_width: stroke.base_width * width_scale,
+                    ..stroke.clone()
+                },
+            },
+            Operation::DrawBrushStroke { layer_id, points, color, brush, seed, brush_id } => Operation::DrawBrushStroke {
+                layer_id: layer_id.clone(),
+                points: points.iter().map(|p| {
+                    let mut point = *p;
+                    point.x *= sx;
+                    point.y *= sy;
+                    point
+                }).collect(),
+                color: *color,
+                brush: StampSettings { size: brush.size * width_scale, ..*brush },
+                seed: *seed,
+                brush_id: brush_id.clone(),
+            },
+            Operation::DrawPixelStroke { layer_id, points, color, pixel_perfect } => Operation::DrawPixelStroke {
+                layer_id: layer_id.clone(),
+                points: points.iter().map(|(x, y)| (x * sx, y * sy)).collect(),
+                color: *color,
+                pixel_perfect: *pixel_perfect,
+            },
+            Operation::TransformSelection { layer_id, selection, transform } => Operation::TransformSelection {
+                layer_id: layer_id.clone(),
+                selection: selection.rescaled((sx, sy)),
+                transform: SelectionTransform {
+                    translate_x: transform.translate_x * sx,
+                    translate_y: transform.translate_y * sy,
+                    ..*transform
+                },
+            },
+            Operation::EraseSelection { layer_id, selection } => Operation::EraseSelection {
+                layer_id: layer_id.clone(),
+                selection: selection.rescaled((sx, sy)),
+            },
+            Operation::PasteImage { layer_id, x, y, png_base64 } => {
+                let png = STANDARD.decode(png_base64)
+                    .map_err(|e| format!("画像データのデコードに失敗: {}", e))?;
+                let image = image::load_from_memory_with_format(&png, image::ImageFormat::Png)
+                    .map_err(|e| format!("PNGデコードに失敗: {}", e))?
+                    .to_rgba8();
+                let (width, height) = (scale_size(image.width(), sx), scale_size(image.height(), sy));
+                debug!("[OperationLog] 画像の拡大縮小: {} {}x{} -> {}x{}", layer_id, image.width(), image.height(), width, height);
+                let resized = image::imageops::resize(&image, width, height, image::imageops::FilterType::CatmullRom);
+                Operation::paste_image(layer_id, (*x as f32 * sx).round() as u32, (*y as f32 * sy).round() as u32, &resized)?
+            }
+            Operation::SetViewport { x, y } => Operation::SetViewport {
+                x: (*x as f32 * sx).round() as i32,
+                y: (*y as f32 * sy).round() as i32,
+            },
+            Operation::RemoveLayer { .. }
+            | Operation::ClearLayer { .. }
+            | Operation::FillLayer { .. }
+            | Operation::TransformCanvas { .. }
+            | Operation::SetInfiniteCanvas { .. } => self.clone(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::StrokePointRecord;
+
+    #[test]
+    fn test_rescale_vector_operations() {
+        let log = OperationLog::from_operations([
+            Operation::CreateLayer { layer_id: "a".to_string(), width: 100, height: 50 },
+            Operation::DrawStroke {
+                layer_id: "a".to_string(),
+                stroke: StrokeRecord {
+                    points: vec![StrokePointRecord { x: 10.0, y: 20.0, pressure: 0.5, time_ms: Some(4) }],
+                    color: [0.0, 0.0, 0.0, 1.0],
+                    base_width: 3.0,
+                },
+            },
+        ]);
+
+        let (rescaled, summary) = log.rescaled((2.0, 2.0)).unwrap();
+        assert_eq!(summary, RescaleSummary { vector_operations: 1, raster_layers: vec![] });
+        assert_eq!(rescaled.position(), log.position());
+
+        let ops: Vec<&Operation> = rescaled.entries().iter().map(|e| &e.operation).collect();
+        assert_eq!(*ops[0], Operation::CreateLayer { layer_id: "a".to_string(), width: 200, height: 100 });
+        match ops[1] {
+            Operation::DrawStroke { stroke, .. } => {
+                assert_eq!(stroke.points[0], StrokePointRecord { x: 20.0, y: 40.0, pressure: 0.5, time_ms: Some(4) });
+                assert_eq!(stroke.base_width, 6.0);
+            }
+            other => panic!("unexpected operation: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rescale_swaps_axes_after_quarter_turn() {
+        let log = OperationLog::from_operations([
+            Operation::CreateLayer { layer_id: "a".to_string(), width: 100, height: 50 },
+            Operation::TransformCanvas { transform: CanvasTransform::Rotate90Clockwise },
+            Operation::DrawLine { layer_id: "a".to_string(), x1: 10.0, y1: 10.0, x2: 20.0, y2: 20.0, color: [1.0; 4], width: 2.0 },
+        ]);
+
+        let (rescaled, _) = log.rescaled((2.0, 3.0)).unwrap();
+        match &rescaled.entries()[2].operation {
+            Operation::DrawLine { x1, y1, width, .. } => assert_eq!((*x1, *y1, *width), (30.0, 20.0, 5.0)),
+            other => panic!("unexpected operation: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_rescale_raster_paste() {
+        let image = image::RgbaImage::from_pixel(4, 2, image::Rgba([255, 0, 0, 255]));
+        let log = OperationLog::from_operations([
+            Operation::CreateLayer { layer_id: "a".to_string(), width: 8, height: 8 },
+            Operation::paste_image("a", 2, 1, &image).unwrap(),
+        ]);
+
+        let (rescaled, summary) = log.rescaled((0.5, 0.5)).unwrap();
+        assert_eq!(summary.raster_layers, vec!["a".to_string()]);
+        match &rescaled.entries()[1].operation {
+            Operation::PasteImage { x, y, png_base64, .. } => {
+                assert_eq!((*x, *y), (1, 1));
+                let png = STANDARD.decode(png_base64).unwrap();
+                let resized = image::load_from_memory(&png).unwrap();
+                assert_eq!((resized.width(), resized.height()), (2, 1));
+            }
+            other => panic!("unexpected operation: {:?}", other),
+        }
+
+        assert!(log.rescaled((0.0, 1.0)).is_err());
+    }
+}
@@ -0,0 +1,122 @@
+use log::debug;
+use super::{StrokePointRecord, StrokeRecord};
+
+/// 線の半径に対する許容誤差の割合
+const TOLERANCE_RATIO: f32 = 0.25;
+
+/// 許容誤差の下限・上限（ピクセル）
+const MIN_TOLERANCE: f32 = 0.1;
+const MAX_TOLERANCE: f32 = 2.0;
+
+/// 間引きを行う最小の点数（短いストロークはそのまま記録する）
+pub const SIMPLIFY_MIN_POINTS: usize = 16;
+
+impl StrokeRecord {
+    /// 点のピクセル上の半径（描画時と同じく筆圧を掛けた線幅の半分）
+    fn half_width_px(&self, point: &StrokePointRecord) -> f32 {
+        self.base_width * point.pressure * 0.5
+    }
+
+    /// 見た目がほとんど変わらない点を間引いたストロークを作る（Ramer–Douglas–Peucker）
+    ///
+    /// 許容誤差は点ごとの線の半径に比例させる（細い線ほど厳密に残す）。
+    /// 位置のずれに加えて線幅（筆圧）のずれも誤差として扱うため、筆圧の抜き・入りは保たれる。
+    pub fn simplified(&self) -> StrokeRecord {
+        let count = self.points.len();
+        if count < SIMPLIFY_MIN_POINTS {
+            return self.clone();
+        }
+
+        let widths: Vec<f32> = self.points.iter().map(|p| self.half_width_px(p)).collect();
+        let mut keep = vec![false; count];
+        keep[0] = true;
+        keep[count - 1] = true;
+
+        // 再帰の代わりに区間のスタックで処理する（数千点のストロークでもスタックを消費しない）
+        let mut ranges = vec![(0, count - 1)];
+        while let Some((start, end)) = ranges.pop() {
+            if end <= start + 1 {
+                continue;
+            }
+            let mut worst = (0, 1.0_f32);
+            for index in start + 1..end {
+                let error = self.deviation(&widths, start, end, index);
+                let tolerance = (widths[index] * TOLERANCE_RATIO).clamp(MIN_TOLERANCE, MAX_TOLERANCE);
+                let ratio = error / tolerance;
+                if ratio > worst.1 {
+                    worst = (index, ratio);
+                }
+            }
+            if worst.0 != 0 {
+                keep[worst.0] = true;
+                ranges.push((start, worst.0));
+                ranges.push((worst.0, end));
+            }
+        }
+
+        let points: Vec<StrokePointRecord> = self.points.iter().zip(&keep)
+            .filter(|(_, keep)| **keep)
+            .map(|(point, _)| *point)
+            .collect();
+        debug!("[StrokeRecord] ストロークを間引き: {} → {} 点", count, points.len());
+        StrokeRecord { points, ..self.clone() }
+    }
+
+    /// 区間の両端を結ぶ線分から見た点のずれ（位置と半径の大きい方、ピクセル）
+    fn deviation(&self, widths: &[f32], start: usize, end: usize, index: usize) -> f32 {
+        let (a, b, p) = (&self.points[start], &self.points[end], &self.points[index]);
+        let (dx, dy) = (b.x - a.x, b.y - a.y);
+        let length_sq = dx * dx + dy * dy;
+
+        // 線分上の最も近い位置（両端の外側は端点までの距離）
+        let t = if length_sq > f32::EPSILON {
+            (((p.x - a.x) * dx + (p.y - a.y) * dy) / length_sq).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let (cx, cy) = (a.x + dx * t, a.y + dy * t);
+        let distance = ((p.x - cx).powi(2) + (p.y - cy).powi(2)).sqrt();
+
+        let width = widths[start] + (widths[end] - widths[start]) * t;
+        distance.max((widths[index] - width).abs())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(points: impl IntoIterator<Item = (f32, f32, f32)>) -> StrokeRecord {
+        StrokeRecord {
+            points: points.into_iter().map(|(x, y, pressure)| StrokePointRecord { x, y, pressure, time_ms: None }).collect(),
+            color: [0.0, 0.0, 0.0, 1.0],
+            base_width: 2.0,
+        }
+    }
+
+    #[test]
+    fn test_collinear_points_are_removed() {
+        let stroke = record((0..100).map(|i| (i as f32, 10.0, 1.0)));
+        let simplified = stroke.simplified();
+        assert_eq!(simplified.points.len(), 2);
+        assert_eq!(simplified.points[0], stroke.points[0]);
+        assert_eq!(simplified.points[1], stroke.points[99]);
+    }
+
+    #[test]
+    fn test_corners_and_pressure_changes_are_kept() {
+        // 直角に曲がる線
+        let corner = record((0..50).map(|i| (i as f32, 0.0, 1.0)).chain((1..50).map(|i| (49.0, i as f32, 1.0))));
+        let simplified = corner.simplified();
+        assert_eq!(simplified.points.len(), 3);
+        assert_eq!((simplified.points[1].x, simplified.points[1].y), (49.0, 0.0));
+
+        // まっすぐだが途中で筆圧が大きく変わる線
+        let pressure = record((0..100).map(|i| (i as f32, 0.0, if i < 50 { 0.2 } else { 2.0 })));
+        assert!(pressure.simplified().points.len() > 2);
+
+        // 短いストロークはそのまま
+        let short = record((0..5).map(|i| (i as f32, 0.0, 1.0)));
+        assert_eq!(short.simplified(), short);
+    }
+}
@@ -0,0 +1,168 @@
+use serde::{Deserialize, Serialize};
+use crate::drawing_engine::coverage::mesh_bounds;
+use crate::drawing_engine::{ContentBounds, DrawStroke};
+use super::{HistoryEntry, HistoryError, Operation, OperationLog};
+
+/// ストロークを描いた道具
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum StrokeTool {
+    Pen,
+    Line,
+    Brush,
+    Pixel,
+    Eraser,
+}
+
+/// 確定したストロークの情報（IDは履歴のエントリの `seq`）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct StrokeInfo {
+    pub id: u64,
+    pub layer_id: String,
+    /// 確定した時刻（UNIX エポックからのミリ秒）
+    pub created_at: i64,
+    pub tool: StrokeTool,
+    /// スタンプブラシのID（ブラシ以外は None）
+    pub brush_id: Option<String>,
+    /// ストロークが掛かるレイヤー上の範囲（レイヤーの外なら None）
+    pub bounds: Option<ContentBounds>,
+}
+
+impl StrokeInfo {
+    /// ストロークの操作なら情報を作る（それ以外の操作は None）
+    pub fn from_entry(entry: &HistoryEntry, layer_size: (u32, u32)) -> Option<Self> {
+        let (layer_id, tool, brush_id, bounds) = match &entry.operation {
+            Operation::DrawLine { layer_id, x1, y1, x2, y2, color, width } => {
+                let mut stroke = DrawStroke::new(*color, *width);
+                stroke.add_point(*x1, *y1, 1.0);
+                stroke.add_point(*x2, *y2, 1.0);
+                (layer_id, StrokeTool::Line, None, mesh_bounds(&stroke.to_mesh(), layer_size))
+            }
+            Operation::DrawStroke { layer_id, stroke } => {
+                (layer_id, StrokeTool::Pen, None, mesh_bounds(&stroke.to_draw_stroke().to_mesh(), layer_size))
+            }
+            Operation::EraseStroke { layer_id, stroke } => {
+                (layer_id, StrokeTool::Eraser, None, mesh_bounds(&stroke.to_draw_stroke().to_mesh(), layer_size))
+            }
+            Operation::DrawBrushStroke { layer_id, points, brush, seed, brush_id, .. } => {
+                let extent = brush.stamps(points, *seed).iter()
+                    .map(|stamp| (stamp.center, stamp.radius))
+                    .fold(None, |extent, (center, radius)| {
+                        let stamp = (center[0] - radius, center[1] - radius, center[0] + radius, center[1] + radius);
+                        Some(merge_extent(extent, stamp))
+                    });
+                (layer_id, StrokeTool::Brush, brush_id.clone(), extent.and_then(|extent| clip_extent(extent, layer_size)))
+            }
+            Operation::DrawPixelStroke { layer_id, points, .. } => {
+                let extent = points.iter()
+                    .map(|(x, y)| (x.floor(), y.floor(), x.floor() + 1.0, y.floor() + 1.0))
+                    .fold(None, |extent, pixel| Some(merge_extent(extent, pixel)));
+                (layer_id, StrokeTool::Pixel, None, extent.and_then(|extent| clip_extent(extent, layer_size)))
+            }
+            _ => return None,
+        };
+        Some(Self {
+            id: entry.seq,
+            layer_id: layer_id.clone(),
+            created_at: entry.timestamp,
+            tool,
+            brush_id,
+            bounds,
+        })
+    }
+}
+
+type Extent = (f32, f32, f32, f32);
+
+fn merge_extent(extent: Option<Extent>, (left, top, right, bottom): Extent) -> Extent {
+    match extent {
+        Some((l, t, r, b)) => (l.min(left), t.min(top), r.max(right), b.max(bottom)),
+        None => (left, top, right, bottom),
+    }
+}
+
+/// 範囲をレイヤー内のピクセル範囲に切り詰める（重ならなければ None）
+fn clip_extent((left, top, right, bottom): Extent, (width, height): (u32, u32)) -> Option<ContentBounds> {
+    let x = left.floor().max(0.0) as u32;
+    let y = top.floor().max(0.0) as u32;
+    let right = (right.ceil().max(0.0) as u32).min(width);
+    let bottom = (bottom.ceil().max(0.0) as u32).min(height);
+    (x < right && y < bottom).then(|| ContentBounds { x, y, width: right - x, height: bottom - y })
+}
+
+fn intersects(a: &ContentBounds, b: &ContentBounds) -> bool {
+    a.x < b.x + b.width && b.x < a.x + a.width && a.y < b.y + b.height && b.y < a.y + a.height
+}
+
+impl OperationLog {
+    /// レイヤーの確定済みのストローク（古い順。`region` を指定するとその範囲に掛かるものだけ）
+    pub fn list_strokes(&self, layer_id: &str, layer_size: (u32, u32), region: Option<&ContentBounds>) -> Vec<StrokeInfo> {
+        self.applied_entries().iter()
+            .filter(|entry| entry.operation.layer_id() == Some(layer_id))
+            .filter_map(|entry| StrokeInfo::from_entry(entry, layer_size))
+            .filter(|info| match (region, &info.bounds) {
+                (Some(region), Some(bounds)) => intersects(region, bounds),
+                (Some(_), None) => false,
+                (None, _) => true,
+            })
+            .collect()
+    }
+
+    /// 確定済みのストロークを1本取り除いた操作ログを作る
+    ///
+    /// 後の操作はそのまま残し、再生するとそのストロークだけがなかった状態になる。
+    /// 履歴を書き換えるため、取り除いたこと自体はアンドゥできない。
+    pub fn without_stroke(&self, stroke_id: u64) -> Result<OperationLog, HistoryError> {
+        let index = self.applied_entries().iter()
+            .position(|entry| entry.seq == stroke_id && StrokeInfo::from_entry(entry, (0, 0)).is_some())
+            .ok_or(HistoryError::StrokeNotFound(stroke_id))?;
+        let mut log = self.clone();
+        log.entries.remove(index);
+        log.position -= 1;
+        Ok(log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::history::{StrokePointRecord, StrokeRecord};
+
+    fn stroke(points: &[(f32, f32)]) -> StrokeRecord {
+        StrokeRecord {
+            points: points.iter().map(|&(x, y)| StrokePointRecord { x, y, pressure: 1.0, time_ms: None }).collect(),
+            color: [0.0, 0.0, 0.0, 1.0],
+            base_width: 4.0,
+        }
+    }
+
+    #[test]
+    fn test_list_and_delete_strokes() {
+        let layer_id = "layer".to_string();
+        let mut log = OperationLog::from_operations([
+            Operation::CreateLayer { layer_id: layer_id.clone(), width: 100, height: 100 },
+            Operation::DrawStroke { layer_id: layer_id.clone(), stroke: stroke(&[(10.0, 10.0), (30.0, 10.0)]) },
+            Operation::DrawPixelStroke { layer_id: layer_id.clone(), points: vec![(60.0, 60.0), (70.5, 65.0)], color: [1.0; 4], pixel_perfect: false },
+            Operation::EraseStroke { layer_id: "other".to_string(), stroke: stroke(&[(0.0, 0.0), (5.0, 5.0)]) },
+        ]);
+        log.push(Operation::ClearLayer { layer_id: layer_id.clone() });
+        log.undo();
+
+        let strokes = log.list_strokes(&layer_id, (100, 100), None);
+        assert_eq!(strokes.iter().map(|s| s.tool).collect::<Vec<_>>(), vec![StrokeTool::Pen, StrokeTool::Pixel]);
+        assert_eq!(strokes[0].bounds, Some(ContentBounds { x: 9, y: 7, width: 22, height: 6 }));
+        assert_eq!(strokes[1].bounds, Some(ContentBounds { x: 60, y: 60, width: 11, height: 6 }));
+        assert_eq!(strokes[0].created_at, log.entries()[1].timestamp);
+
+        let region = ContentBounds { x: 50, y: 50, width: 20, height: 20 };
+        let inside = log.list_strokes(&layer_id, (100, 100), Some(&region));
+        assert_eq!(inside.iter().map(|s| s.id).collect::<Vec<_>>(), vec![strokes[1].id]);
+
+        // 取り除いても後の操作とアンドゥ済みの操作は残る
+        let removed = log.without_stroke(strokes[0].id).unwrap();
+        assert_eq!(removed.list_strokes(&layer_id, (100, 100), None).len(), 1);
+        assert_eq!((removed.position(), removed.len()), (log.position() - 1, log.len() - 1));
+        // ストローク以外の操作は取り除けない
+        assert!(log.without_stroke(log.entries()[0].seq).is_err());
+    }
+}
@@ -0,0 +1,261 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use crate::brush::{MAX_BRUSH_SIZE, MAX_STAMP_COUNT};
+use crate::drawing_engine::CanvasLimits;
+use crate::drawing_engine::stroke_input::{within_stroke_bounds, MAX_STROKE_WIDTH, STROKE_BOUNDS_MARGIN};
+use super::{Operation, StrokePointRecord, StrokeRecord};
+
+/// 1つの操作に含められる点の数の上限
+pub const MAX_OPERATION_POINTS: usize = 1 << 20;
+
+/// 操作の検証で見つかった問題
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ValidationIssue {
+    /// 問題のある操作の位置（操作列の中の順番）
+    pub index: usize,
+    pub layer_id: Option<String>,
+    pub message: String,
+}
+
+/// 操作列をGPUに触れずに検証し、見つかった問題を返す（問題がなければ空）
+///
+/// レイヤーの作成・削除・キャンバスの回転は操作列の中で順に反映するため、同じ操作列で作ったレイヤーへの
+/// 描画も検証できる。点と線幅は描画コマンドが入力を整えるとき（`sanitize_stroke`）と同じ範囲で検査し、
+/// レイヤーの外へ `STROKE_BOUNDS_MARGIN` より離れた点と `MAX_STROKE_WIDTH` より太い線は誤りとする。
+pub fn validate_operations<'a>(
+    operations: impl IntoIterator<Item = &'a Operation>,
+    layers: &HashMap<String, (u32, u32)>,
+    limits: &CanvasLimits,
+) -> Vec<ValidationIssue> {
+    let mut layers = layers.clone();
+    let mut issues = Vec::new();
+    for (index, operation) in operations.into_iter().enumerate() {
+        if let Err(message) = validate_operation(operation, &mut layers, limits) {
+            issues.push(ValidationIssue {
+                index,
+                layer_id: operation.layer_id().map(str::to_string),
+                message,
+            });
+        }
+    }
+    issues
+}
+
+/// 1つの操作を検証し、レイヤーの作成・削除・サイズの変化を `layers` に反映する
+fn validate_operation(
+    operation: &Operation,
+    layers: &mut HashMap<String, (u32, u32)>,
+    limits: &CanvasLimits,
+) -> Result<(), String> {
+    if let Operation::CreateLayer { layer_id, width, height } = operation {
+        if layer_id.is_empty() {
+            return Err("レイヤーIDが空です".to_string());
+        }
+        if layers.contains_key(layer_id) {
+            return Err(format!("レイヤーは既に存在します: {}", layer_id));
+        }
+        if !limits.allows(*width, *height) {
+            return Err(format!("レイヤーのサイズが上限（{}px）を超えているか 0 です: {}x{}", limits.max_dimension(), width, height));
+        }
+        layers.insert(layer_id.clone(), (*width, *height));
+        return Ok(());
+    }
+
+    let size = match operation.layer_id() {
+        Some(layer_id) => Some(*layers.get(layer_id).ok_or(format!("レイヤーが見つかりません: {}", layer_id))?),
+        None => None,
+    };
+    match operation {
+        Operation::CreateLayer { .. } => unreachable!(),
+        Operation::RemoveLayer { layer_id } => {
+            layers.remove(layer_id);
+        }
+        Operation::ClearLayer { .. } => {}
+        Operation::FillLayer { color, .. } => check_color(color)?,
+        Operation::DrawLine { x1, y1, x2, y2, color, width, .. } => {
+            check_color(color)?;
+            check_width(*width)?;
+            let size = size.unwrap_or_default();
+            check_point((*x1, *y1), size)?;
+            check_point((*x2, *y2), size)?;
+        }
+        Operation::DrawStroke { stroke, .. } | Operation::EraseStroke { stroke, .. } => {
+            check_stroke(stroke, size.unwrap_or_default())?;
+        }
+        Operation::DrawBrushStroke { points, color, brush, .. } => {
+            check_color(color)?;
+            if !(brush.size > 0.0 && brush.size <= MAX_BRUSH_SIZE) {
+                return Err(format!("ブラシの直径は 0 より大きく {} 以下である必要があります: {}", MAX_BRUSH_SIZE, brush.size));
+            }
+            if !(brush.spacing.is_finite() && brush.spacing > 0.0) {
+                return Err(format!("スタンプ間隔が不正です: {}", brush.spacing));
+            }
+            if brush.count == 0 || brush.count > MAX_STAMP_COUNT {
+                return Err(format!("スタンプ数は 1〜{} で指定してください: {}", MAX_STAMP_COUNT, brush.count));
+            }
+            check_points(points, size.unwrap_or_default())?;
+        }
+        Operation::DrawPixelStroke { points, color, .. } => {
+            check_color(color)?;
+            check_count(points.len())?;
+            let size = size.unwrap_or_default();
+            for point in points {
+                check_point(*point, size)?;
+            }
+        }
+        Operation::TransformSelection { selection, transform, .. } => {
+            selection.validate().map_err(|e| e.to_string())?;
+            transform.validate().map_err(|e| e.to_string())?;
+        }
+        Operation::EraseSelection { selection, .. } => {
+            selection.validate().map_err(|e| e.to_string())?;
+        }
+        Operation::PasteImage { x, y, .. } => {
+            let (width, height) = size.unwrap_or_default();
+            if *x >= width || *y >= height {
+                return Err(format!("貼り付け位置がレイヤーの外です: ({}, {})", x, y));
+            }
+        }
+        Operation::TransformCanvas { transform } => {
+            for layer_size in layers.values_mut() {
+                *layer_size = transform.output_size(*layer_size);
+            }
+        }
+        Operation::SetInfiniteCanvas { .. } | Operation::SetViewport { .. } => {}
+    }
+    Ok(())
+}
+
+fn check_color(color: &[f32; 4]) -> Result<(), String> {
+    if color.iter().all(|c| (0.0..=1.0).contains(c)) {
+        Ok(())
+    } else {
+        Err(format!("色の成分は 0〜1 で指定してください: {:?}", color))
+    }
+}
+
+fn check_width(width: f32) -> Result<(), String> {
+    if width > 0.0 && width <= MAX_STROKE_WIDTH {
+        Ok(())
+    } else {
+        Err(format!("線幅は 0 より大きく {} 以下である必要があります: {}", MAX_STROKE_WIDTH, width))
+    }
+}
+
+fn check_count(count: usize) -> Result<(), String> {
+    if count == 0 {
+        Err("ストロークの点が空です".to_string())
+    } else if count > MAX_OPERATION_POINTS {
+        Err(format!("ストロークの点が多すぎます: {}（上限 {}）", count, MAX_OPERATION_POINTS))
+    } else {
+        Ok(())
+    }
+}
+
+/// 点の座標が有限で、レイヤーから `STROKE_BOUNDS_MARGIN` より離れていないか
+fn check_point((x, y): (f32, f32), size: (u32, u32)) -> Result<(), String> {
+    if !(x.is_finite() && y.is_finite()) {
+        return Err(format!("座標が不正です: ({}, {})", x, y));
+    }
+    if !within_stroke_bounds(x, y, size) {
+        return Err(format!("座標がレイヤーの範囲から {}px 以上離れています: ({}, {})", STROKE_BOUNDS_MARGIN, x, y));
+    }
+    Ok(())
+}
+
+fn check_points(points: &[StrokePointRecord], size: (u32, u32)) -> Result<(), String> {
+    check_count(points.len())?;
+    for point in points {
+        check_point((point.x, point.y), size)?;
+        if !(point.pressure.is_finite() && point.pressure >= 0.0) {
+            return Err(format!("筆圧が不正です: {}", point.pressure));
+        }
+    }
+    Ok(())
+}
+
+fn check_stroke(stroke: &StrokeRecord, size: (u32, u32)) -> Result<(), String> {
+    check_color(&stroke.color)?;
+    check_width(stroke.base_width)?;
+    check_points(&stroke.points, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::drawing_engine::CanvasTransform;
+
+    fn line(layer_id: &str, x2: f32, width: f32) -> Operation {
+        Operation::DrawLine {
+            layer_id: layer_id.to_string(),
+            x1: 0.0,
+            y1: 0.0,
+            x2,
+            y2: 10.0,
+            color: [0.0, 0.0, 0.0, 1.0],
+            width,
+        }
+    }
+
+    #[test]
+    fn test_validate_tracks_layers_in_batch() {
+        let existing = HashMap::from([("base".to_string(), (100, 50))]);
+        let limits = CanvasLimits::default();
+        let operations = vec![
+            Operation::CreateLayer { layer_id: "ink".to_string(), width: 100, height: 100 },
+            line("ink", 150.0, 2.0),
+            Operation::RemoveLayer { layer_id: "base".to_string() },
+            line("base", 10.0, 2.0),
+        ];
+        let issues = validate_operations(&operations, &existing, &limits);
+        assert_eq!(issues.len(), 1);
+        assert_eq!((issues[0].index, issues[0].layer_id.as_deref()), (3, Some("base")));
+
+        // 回転後は幅と高さが入れ替わる（50x100 の右端から離れた点は範囲外）
+        let far = 100.0 + STROKE_BOUNDS_MARGIN;
+        let rotated = vec![
+            Operation::TransformCanvas { transform: CanvasTransform::Rotate90Clockwise },
+            line("base", far, 2.0),
+        ];
+        assert_eq!(validate_operations(&rotated, &existing, &limits)[0].index, 1);
+        assert!(validate_operations(&[line("base", far, 2.0)], &existing, &limits).is_empty());
+    }
+
+    #[test]
+    fn test_validate_matches_stroke_sanitizer() {
+        use crate::drawing_engine::stroke_input::{sanitize_stroke, StrokeInputPoint};
+
+        // 描画コマンドが受け付ける点・線幅と、記録済みの操作として受け付ける点・線幅は一致する
+        let existing = HashMap::from([("base".to_string(), (100, 100))]);
+        let limits = CanvasLimits::default();
+        let margin = STROKE_BOUNDS_MARGIN;
+        for (x, y) in [(50.0, 50.0), (-margin, 0.0), (-margin - 1.0, 0.0), (100.0 + margin, 100.0 + margin),
+                       (0.0, 100.0 + margin + 1.0), (f32::INFINITY, 0.0)] {
+            let sanitized = sanitize_stroke(&[StrokeInputPoint::new(x, y, 1.0)], [0.0; 4], 2.0, (100, 100), None);
+            let operation = Operation::DrawLine {
+                layer_id: "base".to_string(), x1: x, y1: y, x2: 0.0, y2: 0.0, color: [0.0; 4], width: 2.0,
+            };
+            assert_eq!(validate_operations(&[operation], &existing, &limits).is_empty(), sanitized.is_ok(), "({}, {})", x, y);
+        }
+        assert!(validate_operations(&[line("base", 10.0, MAX_STROKE_WIDTH)], &existing, &limits).is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_parameters() {
+        let existing = HashMap::from([("base".to_string(), (100, 100))]);
+        let limits = CanvasLimits::default();
+        let operations = vec![
+            line("base", f32::NAN, 2.0),
+            line("base", 10.0, 0.0),
+            line("base", 10_000.0, 2.0),
+            line("base", 10.0, MAX_STROKE_WIDTH + 1.0),
+            Operation::FillLayer { layer_id: "base".to_string(), color: [2.0, 0.0, 0.0, 1.0] },
+            Operation::CreateLayer { layer_id: "huge".to_string(), width: 100_000, height: 10 },
+            Operation::CreateLayer { layer_id: "base".to_string(), width: 10, height: 10 },
+            Operation::DrawPixelStroke { layer_id: "base".to_string(), points: Vec::new(), color: [0.0; 4], pixel_perfect: true },
+            Operation::PasteImage { layer_id: "base".to_string(), x: 100, y: 0, png_base64: String::new() },
+        ];
+        let issues = validate_operations(&operations, &existing, &limits);
+        assert_eq!(issues.iter().map(|issue| issue.index).collect::<Vec<_>>(), (0..operations.len()).collect::<Vec<_>>());
+    }
+}
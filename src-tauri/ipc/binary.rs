@@ -0,0 +1,354 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use log::debug;
+use crate::formats::lz4;
+
+/// 転送データの先頭に置く識別子
+pub const TRANSFER_MAGIC: &[u8; 4] = b"KGBT";
+
+/// 転送データ形式のバージョン
+pub const TRANSFER_VERSION: u16 = 2;
+
+/// ヘッダーのサイズ（識別子・バージョン・圧縮方式・予約・幅・高さ・展開後サイズ・辞書ID・連番・CRC32）
+pub const TRANSFER_HEADER_LEN: usize = 4 + 2 + 1 + 1 + 4 * 6;
+
+/// zstd の既定の圧縮レベル（LZ4 と同程度の速度になる低めのレベル）
+pub const DEFAULT_ZSTD_LEVEL: i32 = 3;
+
+/// 辞書の学習に使うタイルの一辺（ピクセル）
+pub const DICTIONARY_TILE_SIZE: u32 = 64;
+
+/// 学習する辞書の最大サイズ
+pub const MAX_DICTIONARY_SIZE: usize = 112 * 1024;
+
+/// 転送データのエラー型
+#[derive(Debug)]
+pub enum TransferError {
+    InvalidData(String),
+    Compression(String),
+    /// 圧縮時と異なる辞書（または辞書なし）で展開しようとした
+    DictionaryMismatch { expected: u32, actual: Option<u32> },
+    /// 展開したデータのチェックサムがヘッダーと一致しない
+    ChecksumMismatch { sequence: u32, expected: u32, actual: u32 },
+}
+
+impl fmt::Display for TransferError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TransferError::InvalidData(msg) => write!(f, "転送データが不正です: {}", msg),
+            TransferError::Compression(msg) => write!(f, "圧縮処理に失敗しました: {}", msg),
+            TransferError::DictionaryMismatch { expected, actual } => {
+                write!(f, "辞書が一致しません: 必要な辞書 {}, 指定された辞書 {:?}", expected, actual)
+            }
+            TransferError::ChecksumMismatch { sequence, expected, actual } => {
+                write!(f, "転送データ #{} のチェックサムが一致しません: {:08x} != {:08x}", sequence, actual, expected)
+            }
+        }
+    }
+}
+
+impl Error for TransferError {}
+
+/// 転送データの圧縮方式
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TransferCodec {
+    /// 無圧縮
+    None,
+    /// LZ4ブロック形式（展開が最も速い）
+    #[default]
+    Lz4,
+    /// zstd（画面のような内容では LZ4 より高い圧縮率）
+    Zstd,
+}
+
+impl TransferCodec {
+    fn to_byte(self) -> u8 {
+        match self {
+            TransferCodec::None => 0,
+            TransferCodec::Lz4 => 1,
+            TransferCodec::Zstd => 2,
+        }
+    }
+
+    fn from_byte(byte: u8) -> Result<Self, TransferError> {
+        match byte {
+            0 => Ok(TransferCodec::None),
+            1 => Ok(TransferCodec::Lz4),
+            2 => Ok(TransferCodec::Zstd),
+            other => Err(TransferError::InvalidData(format!("不明な圧縮方式: {}", other))),
+        }
+    }
+}
+
+/// 転送ごとのエンコード設定
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransferOptions {
+    pub codec: TransferCodec,
+    /// zstd の圧縮レベル（省略時は `DEFAULT_ZSTD_LEVEL`）
+    pub level: Option<i32>,
+    /// 学習済みの辞書があれば zstd で使う
+    pub use_dictionary: bool,
+}
+
+/// RGBAタイル用に学習した zstd 辞書
+#[derive(Debug, Clone)]
+pub struct TransferDictionary {
+    pub id: u32,
+    pub data: Vec<u8>,
+}
+
+impl TransferDictionary {
+    /// レイヤー画像をタイルに分割したものを標本として辞書を学習
+    pub fn train(images: &[&image::RgbaImage]) -> Result<Self, TransferError> {
+        let samples: Vec<Vec<u8>> = images.iter()
+            .flat_map(|image| tile_samples(image, DICTIONARY_TILE_SIZE))
+            .collect();
+        let total: usize = samples.iter().map(Vec::len).sum();
+        let data = zstd::dict::from_samples(&samples, MAX_DICTIONARY_SIZE.min(total / 10).max(256))
+            .map_err(|e| TransferError::Compression(format!("辞書の学習に失敗: {}", e)))?;
+        Self::from_bytes(data)
+    }
+
+    /// 学習済みの辞書データから作成（辞書IDはデータに埋め込まれたものを使う）
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, TransferError> {
+        let id = zstd::zstd_safe::get_dict_id_from_dict(&data)
+            .ok_or_else(|| TransferError::InvalidData("辞書IDがありません".to_string()))?
+            .get();
+        Ok(Self { id, data })
+    }
+}
+
+/// 画像を一辺 `tile_size` のタイルに分割し、各タイルのRGBAバイト列を返す（端は小さいタイルになる）
+fn tile_samples(image: &image::RgbaImage, tile_size: u32) -> Vec<Vec<u8>> {
+    let (width, height) = image.dimensions();
+    let mut samples = Vec::new();
+    for tile_y in (0..height).step_by(tile_size as usize) {
+        for tile_x in (0..width).step_by(tile_size as usize) {
+            let tile_width = tile_size.min(width - tile_x);
+            let tile_height = tile_size.min(height - tile_y);
+            let mut sample = Vec::with_capacity((tile_width * tile_height * 4) as usize);
+            for y in tile_y..tile_y + tile_height {
+                let start = ((y * width + tile_x) * 4) as usize;
+                sample.extend_from_slice(&image.as_raw()[start..start + tile_width as usize * 4]);
+            }
+            samples.push(sample);
+        }
+    }
+    samples
+}
+
+/// フロントエンドへ送るRGBA画像（ヘッダー付きのバイト列として送る）
+#[derive(Debug, Clone, PartialEq)]
+pub struct BinaryTransfer {
+    pub codec: TransferCodec,
+    pub width: u32,
+    pub height: u32,
+    /// 展開後のバイト数
+    pub raw_len: u32,
+    /// 圧縮に使った辞書のID（辞書なしは None）
+    pub dictionary_id: Option<u32>,
+    /// 送信順の連番（欠落や順序の入れ替わりの検出用）
+    pub sequence: u32,
+    /// 展開後のRGBAデータの CRC32
+    pub checksum: u32,
+    pub payload: Vec<u8>,
+}
+
+impl BinaryTransfer {
+    /// RGBAデータを指定の方式で圧縮
+    ///
+    /// `use_dictionary` は zstd のときだけ有効で、辞書が渡されなければ辞書なしで圧縮する。
+    pub fn encode(
+        sequence: u32,
+        width: u32,
+        height: u32,
+        raw: &[u8],
+        options: &TransferOptions,
+        dictionary: Option<&TransferDictionary>,
+    ) -> Result<Self, TransferError> {
+        if raw.len() != width as usize * height as usize * 4 {
+            return Err(TransferError::InvalidData(format!(
+                "画像サイズとデータ長が一致しません: {}x{} / {} bytes", width, height, raw.len()
+            )));
+        }
+
+        let dictionary = dictionary.filter(|_| options.use_dictionary && options.codec == TransferCodec::Zstd);
+        let payload = match options.codec {
+            TransferCodec::None => raw.to_vec(),
+            TransferCodec::Lz4 => lz4::compress(raw),
+            TransferCodec::Zstd => {
+                let level = options.level.unwrap_or(DEFAULT_ZSTD_LEVEL);
+                let result = match dictionary {
+                    Some(dictionary) => zstd::bulk::Compressor::with_dictionary(level, &dictionary.data)
+                        .and_then(|mut compressor| compressor.compress(raw)),
+                    None => zstd::bulk::compress(raw, level),
+                };
+                result.map_err(|e| TransferError::Compression(e.to_string()))?
+            }
+        };
+        debug!("[BinaryTransfer] {:?} 圧縮: {} -> {} bytes (辞書: {:?})",
+               options.codec, raw.len(), payload.len(), dictionary.map(|d| d.id));
+
+        Ok(Self {
+            codec: options.codec,
+            width,
+            height,
+            raw_len: raw.len() as u32,
+            dictionary_id: dictionary.map(|d| d.id),
+            sequence,
+            checksum: crc32fast::hash(raw),
+            payload,
+        })
+    }
+
+    /// RGBAデータに展開し、チェックサムを検証
+    pub fn decode(&self, dictionary: Option<&TransferDictionary>) -> Result<Vec<u8>, TransferError> {
+        let raw_len = self.raw_len as usize;
+        let raw = match self.codec {
+            TransferCodec::None => self.payload.clone(),
+            TransferCodec::Lz4 => lz4::decompress(&self.payload, raw_len)
+                .map_err(|e| TransferError::InvalidData(e.to_string()))?,
+            TransferCodec::Zstd => {
+                let result = match self.dictionary_id {
+                    Some(expected) => {
+                        let dictionary = dictionary.filter(|d| d.id == expected).ok_or(
+                            TransferError::DictionaryMismatch { expected, actual: dictionary.map(|d| d.id) }
+                        )?;
+                        zstd::bulk::Decompressor::with_dictionary(&dictionary.data)
+                            .and_then(|mut decompressor| decompressor.decompress(&self.payload, raw_len))
+                    }
+                    None => zstd::bulk::decompress(&self.payload, raw_len),
+                };
+                result.map_err(|e| TransferError::InvalidData(e.to_string()))?
+            }
+        };
+        if raw.len() != raw_len {
+            return Err(TransferError::InvalidData(format!("展開後のサイズが一致しません: {} != {}", raw.len(), raw_len)));
+        }
+        let actual = crc32fast::hash(&raw);
+        if actual != self.checksum {
+            return Err(TransferError::ChecksumMismatch {
+                sequence: self.sequence,
+                expected: self.checksum,
+                actual,
+            });
+        }
+        Ok(raw)
+    }
+
+    /// ヘッダー付きのバイト列にする（数値はリトルエンディアン）
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(TRANSFER_HEADER_LEN + self.payload.len());
+        out.extend_from_slice(TRANSFER_MAGIC);
+        out.extend_from_slice(&TRANSFER_VERSION.to_le_bytes());
+        out.push(self.codec.to_byte());
+        out.push(0);
+        out.extend_from_slice(&self.width.to_le_bytes());
+        out.extend_from_slice(&self.height.to_le_bytes());
+        out.extend_from_slice(&self.raw_len.to_le_bytes());
+        out.extend_from_slice(&self.dictionary_id.unwrap_or(0).to_le_bytes());
+        out.extend_from_slice(&self.sequence.to_le_bytes());
+        out.extend_from_slice(&self.checksum.to_le_bytes());
+        out.extend_from_slice(&self.payload);
+        out
+    }
+
+    /// `to_bytes` の出力から復元
+    pub fn from_bytes(data: &[u8]) -> Result<Self, TransferError> {
+        if data.len() < TRANSFER_HEADER_LEN || &data[0..4] != TRANSFER_MAGIC {
+            return Err(TransferError::InvalidData("転送ヘッダーがありません".to_string()));
+        }
+        let version = u16::from_le_bytes([data[4], data[5]]);
+        if version != TRANSFER_VERSION {
+            return Err(TransferError::InvalidData(format!("未対応のバージョン: {}", version)));
+        }
+        let read_u32 = |pos: usize| u32::from_le_bytes(data[pos..pos + 4].try_into().unwrap());
+        let dictionary_id = read_u32(20);
+
+        Ok(Self {
+            codec: TransferCodec::from_byte(data[6])?,
+            width: read_u32(8),
+            height: read_u32(12),
+            raw_len: read_u32(16),
+            dictionary_id: (dictionary_id != 0).then_some(dictionary_id),
+            sequence: read_u32(24),
+            checksum: read_u32(28),
+            payload: data[TRANSFER_HEADER_LEN..].to_vec(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgba, RgbaImage};
+
+    /// 画面のような内容（単色の背景に矩形と線）
+    fn screen_like(seed: u32) -> RgbaImage {
+        let mut image = RgbaImage::from_pixel(128, 96, Rgba([240, 240, 240, 255]));
+        for y in 0..96 {
+            for x in 0..128 {
+                if (x + seed) % 32 < 12 && y % 24 < 16 {
+                    image.put_pixel(x, y, Rgba([40, 90, (seed * 40) as u8, 255]));
+                }
+                if (x * 3 + y + seed).is_multiple_of(17) {
+                    image.put_pixel(x, y, Rgba([0, 0, 0, 255]));
+                }
+            }
+        }
+        image
+    }
+
+    #[test]
+    fn test_roundtrip_all_codecs() {
+        let image = screen_like(1);
+        for codec in [TransferCodec::None, TransferCodec::Lz4, TransferCodec::Zstd] {
+            let options = TransferOptions { codec, ..Default::default() };
+            let transfer = BinaryTransfer::encode(3, 128, 96, image.as_raw(), &options, None).unwrap();
+            let parsed = BinaryTransfer::from_bytes(&transfer.to_bytes()).unwrap();
+            assert_eq!(parsed, transfer);
+            assert_eq!(parsed.decode(None).unwrap(), image.as_raw().as_slice());
+        }
+
+        assert!(BinaryTransfer::encode(0, 128, 95, image.as_raw(), &TransferOptions::default(), None).is_err());
+        assert!(BinaryTransfer::from_bytes(b"KGBT").is_err());
+    }
+
+    #[test]
+    fn test_zstd_dictionary() {
+        let samples: Vec<RgbaImage> = (0..6).map(screen_like).collect();
+        let dictionary = TransferDictionary::train(&samples.iter().collect::<Vec<_>>()).unwrap();
+
+        let image = screen_like(7);
+        let options = TransferOptions { codec: TransferCodec::Zstd, use_dictionary: true, level: None };
+        let transfer = BinaryTransfer::encode(0, 128, 96, image.as_raw(), &options, Some(&dictionary)).unwrap();
+        assert_eq!(transfer.dictionary_id, Some(dictionary.id));
+        assert_eq!(transfer.decode(Some(&dictionary)).unwrap(), image.as_raw().as_slice());
+        assert!(matches!(transfer.decode(None), Err(TransferError::DictionaryMismatch { .. })));
+
+        // LZ4 では辞書を使わない
+        let lz4 = TransferOptions { codec: TransferCodec::Lz4, ..options };
+        let transfer = BinaryTransfer::encode(0, 128, 96, image.as_raw(), &lz4, Some(&dictionary)).unwrap();
+        assert_eq!(transfer.dictionary_id, None);
+    }
+
+    #[test]
+    fn test_detects_corrupted_payload() {
+        let image = screen_like(2);
+        for codec in [TransferCodec::None, TransferCodec::Lz4] {
+            let options = TransferOptions { codec, ..Default::default() };
+            let mut bytes = BinaryTransfer::encode(9, 128, 96, image.as_raw(), &options, None).unwrap().to_bytes();
+            // LZ4 の末尾は必ずリテラルなので、最後の1バイトを壊しても展開自体は成功する
+            let last = bytes.len() - 1;
+            bytes[last] ^= 0xff;
+
+            match BinaryTransfer::from_bytes(&bytes).unwrap().decode(None) {
+                Err(TransferError::ChecksumMismatch { sequence, .. }) => assert_eq!(sequence, 9),
+                other => panic!("チェックサムの不一致が検出されていません: {:?}", other),
+            }
+        }
+    }
+}
@@ -0,0 +1,5 @@
+// フロントエンドとの間でやり取りするバイナリデータの形式
+pub mod binary;
+
+// 不具合の再現のための描画コマンドの記録
+pub mod recording;
@@ -0,0 +1,568 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::{Path, PathBuf};
+use std::time::Instant;
+use log::{info, warn};
+use CommandKind::{Ignored, Replayable, Unreplayable};
+
+/// 記録・再生でのコマンドの扱い
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CommandKind {
+    /// 記録して再生できる（キャンバスを変えるコマンドと、GPUから読み出して表示画像を作るコマンド）
+    Replayable,
+    /// キャンバスや描画結果を変えるが再生できない
+    ///
+    /// 記録はするが、これを含む記録は途中からキャンバスが記録時と食い違うため再生しない。
+    Unreplayable,
+    /// キャンバスと描画結果に影響しない（記録しない）
+    Ignored,
+}
+
+/// `generate_handler!` に登録した全コマンドの扱い（登録と同じ順。コマンドを追加したらここにも載せる）
+///
+/// `draw_brush_stroke` はスタンプの乱数の種が時刻で決まるため再生しても同じ結果にならない。
+pub const COMMAND_KINDS: &[(&str, CommandKind)] = &[
+    // 既存のプロジェクトAPI
+    ("create_project", Unreplayable),
+    ("get_system_info", Ignored),
+    ("create_layer", Unreplayable),
+    ("draw_line", Unreplayable),
+    ("draw_stroke", Unreplayable),
+    ("get_layer_data", Ignored),
+
+    // 新しい描画API
+    ("initialize_drawing_engine", Replayable),
+    ("create_drawing_layer", Replayable),
+    ("draw_line_on_layer", Replayable),
+    ("draw_stroke_on_layer", Replayable),
+    ("begin_stroke", Replayable),
+    ("extend_stroke", Replayable),
+    ("extend_stroke_packed", Replayable),
+    ("end_stroke", Replayable),
+    ("cancel_stroke", Replayable),
+    ("cancel_all_strokes", Replayable),
+    ("report_stroke_presented", Ignored),
+    ("draw_pixel_stroke_on_layer", Replayable),
+    ("get_layer_image_zoomed", Replayable),
+    ("render_canvas_view", Replayable),
+    ("render_comparison_view", Ignored),
+    ("compare_layers", Ignored),
+    ("get_layer_image_data", Replayable),
+    ("clear_layer", Replayable),
+    ("remove_layer", Replayable),
+    ("get_drawing_stats", Ignored),
+    ("cleanup_textures", Replayable),
+    ("defragment_textures", Replayable),
+    ("get_device_capabilities", Ignored),
+    ("set_max_canvas_size", Unreplayable),
+    ("get_canvas_render_settings", Ignored),
+    ("set_canvas_render_settings", Unreplayable),
+
+    // GPU選択API
+    ("list_gpu_adapters", Ignored),
+    ("get_gpu_preference", Ignored),
+    ("select_gpu_adapter", Unreplayable),
+
+    // 設定API
+    ("get_settings", Ignored),
+    ("set_settings", Unreplayable),
+    ("reset_settings", Unreplayable),
+    ("list_export_presets", Ignored),
+    ("save_export_preset", Ignored),
+    ("delete_export_preset", Ignored),
+
+    // ショートカットAPI
+    ("get_shortcuts", Ignored),
+    ("set_shortcut", Ignored),
+    ("reset_shortcuts", Ignored),
+    ("resolve_shortcut", Ignored),
+    ("get_shortcut_conflicts", Ignored),
+
+    // ペンタブレットAPI
+    ("get_tablet_settings", Ignored),
+    ("start_pressure_calibration", Ignored),
+    ("record_pressure_samples", Ignored),
+    ("finish_pressure_calibration", Unreplayable),
+    ("reset_pressure_curve", Unreplayable),
+    ("classify_pointer_input", Ignored),
+
+    // 無限キャンバスAPI
+    ("get_viewport", Ignored),
+    ("set_infinite_canvas", Unreplayable),
+    ("set_viewport", Unreplayable),
+    ("scroll_viewport", Unreplayable),
+    ("get_changed_tiles", Ignored),
+    ("get_view_transform", Ignored),
+    ("set_view_transform", Unreplayable),
+    ("get_navigator_image", Ignored),
+    ("fill_layer", Replayable),
+    ("rotate_canvas_90", Replayable),
+    ("rotate_canvas_180", Replayable),
+    ("flip_canvas_horizontal", Replayable),
+
+    // 選択範囲API
+    ("set_selection", Unreplayable),
+    ("clear_selection", Unreplayable),
+    ("get_selection", Ignored),
+    ("begin_selection_transform", Unreplayable),
+    ("preview_selection_transform", Unreplayable),
+    ("commit_selection_transform", Unreplayable),
+    ("cancel_selection_transform", Unreplayable),
+    ("enter_quick_mask", Unreplayable),
+    ("exit_quick_mask", Unreplayable),
+
+    // クリップボードAPI
+    ("copy_selection", Unreplayable),
+    ("cut_selection", Unreplayable),
+    ("paste_in_place", Unreplayable),
+    ("paste_as_new_layer", Unreplayable),
+    ("get_clipboard_info", Ignored),
+    ("export_clipboard_png", Ignored),
+    ("import_clipboard_png", Unreplayable),
+    ("copy_canvas_to_system_clipboard", Ignored),
+    ("paste_from_system_clipboard", Unreplayable),
+
+    // セッション復元API
+    ("get_recoverable_session", Ignored),
+    ("recover_session", Unreplayable),
+    ("discard_recoverable_session", Ignored),
+
+    // ベンチマークAPI
+    ("run_benchmark", Ignored),
+    ("benchmark_ipc_echo", Ignored),
+    ("summarize_ipc_benchmark", Ignored),
+
+    // 中割りAPI
+    ("generate_inbetweens", Ignored),
+
+    // アニメーション再生API
+    ("set_player_frames", Ignored),
+    ("set_playback_options", Ignored),
+    ("get_player_status", Ignored),
+    ("start_playback", Ignored),
+    ("stop_playback", Ignored),
+    ("step_forward", Ignored),
+    ("step_backward", Ignored),
+    ("goto_frame", Ignored),
+
+    // タイムラインAPI
+    ("get_timeline_metadata", Ignored),
+    ("set_frame_markers", Ignored),
+    ("add_frame_marker", Ignored),
+    ("rename_frame_marker", Ignored),
+    ("move_frame_marker", Ignored),
+    ("remove_frame_marker", Ignored),
+
+    // シーンAPI
+    ("get_scenes", Ignored),
+    ("create_scene", Ignored),
+    ("update_scene", Ignored),
+    ("reorder_scenes", Ignored),
+    ("duplicate_scene", Unreplayable),
+
+    // カメラAPI
+    ("set_camera_keyframe", Ignored),
+    ("remove_camera_keyframe", Ignored),
+    ("get_camera_rect", Ignored),
+    ("render_camera_frame", Ignored),
+
+    // レイヤー効果API
+    ("set_layer_effects", Unreplayable),
+    ("add_layer_effect", Unreplayable),
+    ("update_layer_effect", Unreplayable),
+    ("set_layer_effect_enabled", Unreplayable),
+    ("remove_layer_effect", Unreplayable),
+    ("rasterize_layer_effects", Unreplayable),
+
+    // 塗りつぶしレイヤーAPI
+    ("create_fill_layer", Unreplayable),
+    ("set_layer_fill", Unreplayable),
+    ("rasterize_fill_layer", Unreplayable),
+    ("bucket_fill", Replayable),
+    ("set_lineart_layer", Unreplayable),
+    ("get_lineart_layer", Ignored),
+    ("segment_regions", Ignored),
+    ("get_region_at", Ignored),
+    ("fill_region", Unreplayable),
+    ("batch_bucket_fill", Unreplayable),
+
+    // レイヤーマスクAPI
+    ("add_layer_mask", Unreplayable),
+    ("remove_layer_mask", Unreplayable),
+    ("set_layer_mask_enabled", Unreplayable),
+    ("apply_layer_mask", Unreplayable),
+
+    // ヒストグラムAPI
+    ("get_histogram", Ignored),
+
+    // パレット抽出API
+    ("extract_palette", Ignored),
+
+    // プロジェクト整理API
+    ("cleanup_project", Unreplayable),
+
+    // 診断レポートAPI
+    ("generate_diagnostics_report", Ignored),
+    ("set_perf_overlay", Unreplayable),
+
+    // 履歴API
+    ("undo", Replayable),
+    ("redo", Replayable),
+    ("seek_history", Unreplayable),
+    ("get_history_info", Ignored),
+    ("pick_object", Ignored),
+    ("list_strokes", Ignored),
+    ("delete_stroke", Unreplayable),
+    ("lasso_erase", Unreplayable),
+    ("get_operation_log", Ignored),
+    ("load_operation_log", Unreplayable),
+    ("apply_operations", Unreplayable),
+    ("rerasterize_canvas", Unreplayable),
+    ("request_canvas_resize", Unreplayable),
+    ("commit_canvas_resize", Unreplayable),
+    ("cancel_canvas_resize", Ignored),
+    ("replay_strokes", Unreplayable),
+    ("cancel_replay", Ignored),
+
+    // 共同編集API
+    ("start_collaboration", Unreplayable),
+    ("stop_collaboration", Ignored),
+    ("get_collaboration_status", Ignored),
+    ("get_local_ops_since", Ignored),
+    ("apply_remote_ops", Unreplayable),
+    ("create_sync_session", Unreplayable),
+    ("join_sync_session", Unreplayable),
+    ("leave_sync_session", Ignored),
+    ("get_sync_session", Ignored),
+
+    // ブラシAPI
+    ("import_brush_pack", Unreplayable),
+    ("list_brush_packs", Ignored),
+    ("remove_brush_pack", Unreplayable),
+    ("export_brush_pack", Ignored),
+    ("list_brushes", Ignored),
+    ("draw_brush_stroke", Unreplayable),
+
+    // ファイル形式API
+    ("export_psd", Ignored),
+    ("export_frame_sequence", Ignored),
+    ("batch_export", Ignored),
+    ("export_canvas_png", Ignored),
+    ("get_content_bounds", Ignored),
+    ("get_canvas_stats", Ignored),
+    ("import_project", Unreplayable),
+    ("import_image_layer", Unreplayable),
+    ("save_project", Ignored),
+    ("load_project", Unreplayable),
+
+    // タイムラプスAPI
+    ("start_timelapse", Ignored),
+    ("pause_timelapse", Ignored),
+    ("resume_timelapse", Ignored),
+    ("stop_timelapse", Ignored),
+    ("get_timelapse_status", Ignored),
+    ("export_timelapse", Ignored),
+
+    // ガイドAPI
+    ("get_guides", Ignored),
+    ("set_guides", Unreplayable),
+    ("add_guide", Unreplayable),
+    ("remove_guide", Unreplayable),
+    ("set_perspective_guide", Unreplayable),
+    ("set_safe_area_guide", Unreplayable),
+    ("preview_stroke_constraint", Ignored),
+    ("snap_point", Ignored),
+    ("get_guide_overlay", Ignored),
+
+    // バックグラウンドジョブAPI
+    ("submit_job", Unreplayable),
+    ("get_job", Ignored),
+    ("list_jobs", Ignored),
+    ("cancel_job", Ignored),
+    ("take_job_result", Ignored),
+
+    // メモリ監視API
+    ("get_memory_report", Ignored),
+    ("get_memory_config", Ignored),
+    ("set_memory_config", Unreplayable),
+    ("start_memory_monitor", Ignored),
+    ("stop_memory_monitor", Ignored),
+
+    // アイドル時のメンテナンスAPI
+    ("start_idle_maintenance", Unreplayable),
+    ("stop_idle_maintenance", Ignored),
+
+    // コマンドの記録・再生API
+    ("start_command_recording", Ignored),
+    ("stop_command_recording", Ignored),
+    ("replay_command_recording", Ignored),
+
+    // フレームページングAPI
+    ("page_frames", Replayable),
+    ("restore_paged_layers", Replayable),
+
+    // バイナリ転送API
+    ("get_layer_image_transfer", Ignored),
+    ("train_transfer_dictionary", Ignored),
+    ("get_transfer_dictionary", Ignored),
+
+    // スクリプトAPI
+    ("run_script", Unreplayable),
+
+    // シェーダーのホットリロードAPI（開発ビルドのみ）
+    ("start_shader_hot_reload", Unreplayable),
+    ("stop_shader_hot_reload", Ignored),
+
+    // デバッグAPI
+    ("get_detailed_engine_state", Ignored),
+    ("export_blend_comparison", Ignored),
+    ("get_all_layers_info", Ignored),
+    ("get_system_memory_info", Ignored),
+    ("log_detailed_state", Ignored),
+];
+
+/// コマンドの扱い（表にないコマンドは、記録外の変更を見逃さないよう再生できないものとして扱う）
+pub fn command_kind(command: &str) -> CommandKind {
+    COMMAND_KINDS.iter()
+        .find(|(name, _)| *name == command)
+        .map_or(Unreplayable, |(_, kind)| *kind)
+}
+
+/// コマンドの記録のエラー型
+#[derive(Debug)]
+pub enum RecordingError {
+    Io(std::io::Error),
+    Serialize(String),
+    /// 記録した引数がハッシュと一致しない（記録ファイルが書き換えられている）
+    HashMismatch { sequence: u64, expected: u32, actual: u32 },
+}
+
+impl fmt::Display for RecordingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RecordingError::Io(e) => write!(f, "コマンドの記録の入出力エラー: {}", e),
+            RecordingError::Serialize(msg) => write!(f, "コマンドの記録のシリアライズエラー: {}", msg),
+            RecordingError::HashMismatch { sequence, expected, actual } => write!(
+                f, "コマンド #{} の引数がハッシュと一致しません: 記録 {:08x} / 実際 {:08x}", sequence, expected, actual
+            ),
+        }
+    }
+}
+
+impl Error for RecordingError {}
+
+impl From<std::io::Error> for RecordingError {
+    fn from(e: std::io::Error) -> Self {
+        RecordingError::Io(e)
+    }
+}
+
+/// 記録したコマンド1件（JSON Lines の1行）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RecordedCommand {
+    /// 記録を始めてからの連番
+    pub sequence: u64,
+    /// 記録を始めてからの経過時間（ミリ秒）
+    pub elapsed_ms: f64,
+    pub command: String,
+    /// 引数の JSON の CRC32（再生前に記録の破損・書き換えを見つける）
+    pub payload_hash: u32,
+    /// フロントエンドから届いた引数（キーは camelCase）
+    pub payload: Value,
+}
+
+/// 引数のハッシュ（JSON の文字列表現の CRC32）
+pub fn payload_hash(payload: &Value) -> u32 {
+    crc32fast::hash(payload.to_string().as_bytes())
+}
+
+pub fn is_recorded_command(command: &str) -> bool {
+    command_kind(command) == Replayable
+}
+
+/// 記録に書き出すコマンド（再生できるものと、再生を拒む目印になるもの）
+pub fn should_record(command: &str) -> bool {
+    command_kind(command) != Ignored
+}
+
+/// 届いた描画コマンドを順にファイルへ書き出す
+pub struct CommandRecorder {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    started: Instant,
+    next_sequence: u64,
+}
+
+impl CommandRecorder {
+    /// 記録ファイルを作成（既存の内容は破棄する）
+    pub fn create(path: &Path) -> Result<Self, RecordingError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let writer = BufWriter::new(File::create(path)?);
+        info!("[Recording] コマンドの記録開始: {:?}", path);
+        Ok(Self { path: path.to_path_buf(), writer, started: Instant::now(), next_sequence: 0 })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 記録したコマンドの数
+    pub fn count(&self) -> u64 {
+        self.next_sequence
+    }
+
+    /// コマンドを1行追記（クラッシュの直前まで残るよう、1件ごとに書き出す）
+    pub fn record(&mut self, command: &str, payload: &Value) -> Result<(), RecordingError> {
+        let record = RecordedCommand {
+            sequence: self.next_sequence,
+            elapsed_ms: self.started.elapsed().as_secs_f64() * 1000.0,
+            command: command.to_string(),
+            payload_hash: payload_hash(payload),
+            payload: payload.clone(),
+        };
+        let mut line = serde_json::to_string(&record).map_err(|e| RecordingError::Serialize(e.to_string()))?;
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        self.writer.flush()?;
+        self.next_sequence += 1;
+        Ok(())
+    }
+}
+
+/// 読み込んだ記録
+#[derive(Debug, Clone)]
+pub struct Recording {
+    pub commands: Vec<RecordedCommand>,
+    /// 書き込み途中で終わった行があった（クラッシュ時の最後の記録は欠けることがある）
+    pub truncated: bool,
+    /// 記録に含まれる再生できないコマンド（重複なし、最初に現れた順）
+    pub unreplayable: Vec<String>,
+}
+
+impl Recording {
+    /// JSON Lines を解析し、引数のハッシュを確かめる（壊れた行以降は書き込み途中とみなして無視する）
+    pub fn parse(data: &str) -> Result<Self, RecordingError> {
+        let mut recording = Recording { commands: Vec::new(), truncated: false, unreplayable: Vec::new() };
+        for line in data.lines().filter(|line| !line.trim().is_empty()) {
+            let command = match serde_json::from_str::<RecordedCommand>(line) {
+                Ok(command) => command,
+                Err(e) => {
+                    warn!("[Recording] 壊れた記録以降を無視: {}", e);
+                    recording.truncated = true;
+                    break;
+                }
+            };
+            let actual = payload_hash(&command.payload);
+            if actual != command.payload_hash {
+                return Err(RecordingError::HashMismatch { sequence: command.sequence, expected: command.payload_hash, actual });
+            }
+            if !is_recorded_command(&command.command) && !recording.unreplayable.contains(&command.command) {
+                recording.unreplayable.push(command.command.clone());
+            }
+            recording.commands.push(command);
+        }
+        Ok(recording)
+    }
+
+    /// 全てのコマンドを再生できる（記録外の変更を含まない）
+    pub fn is_replayable(&self) -> bool {
+        self.unreplayable.is_empty()
+    }
+
+    pub fn read(path: &Path) -> Result<Self, RecordingError> {
+        Self::parse(&fs::read_to_string(path)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_record_and_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("commands.jsonl");
+        let mut recorder = CommandRecorder::create(&path).unwrap();
+        recorder.record("begin_stroke", &json!({ "layerId": "a", "color": [0.0, 0.0, 0.0, 1.0] })).unwrap();
+        recorder.record("end_stroke", &json!({ "layerId": "a" })).unwrap();
+        assert_eq!(recorder.count(), 2);
+
+        let recording = Recording::read(&path).unwrap();
+        assert!(!recording.truncated);
+        let commands: Vec<_> = recording.commands.iter().map(|c| (c.sequence, c.command.as_str())).collect();
+        assert_eq!(commands, vec![(0, "begin_stroke"), (1, "end_stroke")]);
+        assert!(recording.commands[0].elapsed_ms <= recording.commands[1].elapsed_ms);
+        assert_eq!(recording.commands[1].payload["layerId"], "a");
+    }
+
+    #[test]
+    fn test_tampered_and_torn_records() {
+        let payload = json!({ "layerId": "a" });
+        let record = RecordedCommand {
+            sequence: 0,
+            elapsed_ms: 0.0,
+            command: "clear_layer".to_string(),
+            payload_hash: payload_hash(&payload),
+            payload,
+        };
+        let line = serde_json::to_string(&record).unwrap();
+
+        let torn = Recording::parse(&format!("{}\n{}", line, &line[..line.len() / 2])).unwrap();
+        assert!(torn.truncated);
+        assert_eq!(torn.commands.len(), 1);
+
+        let tampered = line.replace("\"a\"", "\"b\"");
+        assert!(matches!(Recording::parse(&tampered), Err(RecordingError::HashMismatch { sequence: 0, .. })));
+        assert!(is_recorded_command("extend_stroke_packed") && !is_recorded_command("save_project"));
+    }
+
+    #[test]
+    fn test_unrecorded_mutations_make_stream_unreplayable() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("commands.jsonl");
+        let mut recorder = CommandRecorder::create(&path).unwrap();
+        recorder.record("begin_stroke", &json!({ "layerId": "a" })).unwrap();
+        recorder.record("end_stroke", &json!({ "layerId": "a" })).unwrap();
+        assert!(Recording::read(&path).unwrap().is_replayable());
+
+        recorder.record("draw_brush_stroke", &json!({ "layerId": "a" })).unwrap();
+        recorder.record("apply_operations", &json!({ "operations": [] })).unwrap();
+        recorder.record("draw_brush_stroke", &json!({ "layerId": "a" })).unwrap();
+        let recording = Recording::read(&path).unwrap();
+        assert!(!recording.is_replayable());
+        assert_eq!(recording.unreplayable, vec!["draw_brush_stroke", "apply_operations"]);
+
+        // 読み出しだけのコマンドは記録しない。表にないコマンドは再生できないものとして記録する
+        assert!(should_record("draw_brush_stroke") && should_record("undo") && !should_record("get_selection"));
+        assert!(should_record("unknown_command") && !is_recorded_command("unknown_command"));
+    }
+
+    #[test]
+    fn test_every_registered_command_is_classified() {
+        let lib = include_str!("../src/lib.rs");
+        let start = lib.find("generate_handler![").unwrap();
+        let end = start + lib[start..].find("]));").unwrap();
+        let registered: Vec<&str> = lib[start..end].lines()
+            .filter_map(|line| line.trim().strip_prefix("api::"))
+            .map(|name| name.trim_end_matches(','))
+            .collect();
+        assert!(registered.len() > 200, "登録したコマンドを読み取れません: {}", registered.len());
+
+        // 登録したコマンドは全て表に載っていて、表のコマンドは全て登録されている（重複もない）
+        let unclassified: Vec<_> = registered.iter()
+            .filter(|command| !COMMAND_KINDS.iter().any(|(name, _)| name == *command))
+            .collect();
+        assert!(unclassified.is_empty(), "記録での扱いが決まっていないコマンド: {:?}", unclassified);
+        for (index, (name, _)) in COMMAND_KINDS.iter().enumerate() {
+            assert!(registered.contains(name), "登録されていないコマンド: {}", name);
+            assert!(!COMMAND_KINDS[..index].iter().any(|(other, _)| other == name), "重複したコマンド: {}", name);
+        }
+    }
+}
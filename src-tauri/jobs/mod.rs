@@ -0,0 +1,393 @@
+use serde::Serialize;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use log::{info, debug, warn};
+use tokio::sync::Semaphore;
+
+/// 同時に実行するジョブ数の既定値
+pub const DEFAULT_MAX_CONCURRENT_JOBS: usize = 2;
+
+/// 結果を取り出されないまま保持する終了済みジョブの上限
+const MAX_FINISHED_JOBS: usize = 64;
+
+/// ジョブ管理のエラー型
+#[derive(Debug, Clone, PartialEq)]
+pub enum JobError {
+    /// ジョブが中断された
+    Cancelled,
+    JobNotFound(u64),
+    /// ジョブがまだ終了していない
+    NotFinished(u64),
+    Failed(String),
+}
+
+impl fmt::Display for JobError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JobError::Cancelled => write!(f, "ジョブは中断されました"),
+            JobError::JobNotFound(id) => write!(f, "ジョブが見つかりません: {}", id),
+            JobError::NotFinished(id) => write!(f, "ジョブはまだ終了していません: {}", id),
+            JobError::Failed(msg) => write!(f, "ジョブが失敗しました: {}", msg),
+        }
+    }
+}
+
+impl Error for JobError {}
+
+impl From<String> for JobError {
+    fn from(msg: String) -> Self {
+        JobError::Failed(msg)
+    }
+}
+
+/// ジョブの状態
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum JobStatus {
+    Queued,
+    Running,
+    Completed,
+    Failed,
+    Cancelled,
+}
+
+impl JobStatus {
+    pub fn is_finished(&self) -> bool {
+        matches!(self, JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled)
+    }
+}
+
+/// ジョブの状態と進捗（進捗イベントとしてフロントエンドへ送られる）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct JobInfo {
+    pub id: u64,
+    /// ジョブの種類（"export_psd" など）
+    pub kind: String,
+    pub status: JobStatus,
+    /// 0.0〜1.0
+    pub progress: f32,
+    /// 現在の処理内容
+    pub message: Option<String>,
+    pub error: Option<String>,
+}
+
+/// 進捗・状態が変わるたびに呼ばれるリスナー
+pub type JobListener = Arc<dyn Fn(&JobInfo) + Send + Sync>;
+
+struct JobEntry {
+    info: JobInfo,
+    cancelled: Arc<AtomicBool>,
+    result: Option<serde_json::Value>,
+    listener: JobListener,
+}
+
+type JobTable = Arc<Mutex<HashMap<u64, JobEntry>>>;
+
+/// 状態を更新してリスナーへ通知（リスナーはロックを解放してから呼ぶ）
+fn update_job(jobs: &JobTable, id: u64, update: impl FnOnce(&mut JobEntry)) {
+    let notification = {
+        let mut jobs_guard = jobs.lock().expect("ジョブ表のロックに失敗");
+        jobs_guard.get_mut(&id).map(|entry| {
+            update(entry);
+            (entry.listener.clone(), entry.info.clone())
+        })
+    };
+    if let Some((listener, info)) = notification {
+        listener(&info);
+    }
+}
+
+/// 実行中のジョブに渡される進捗報告・中断確認用のハンドル
+#[derive(Clone)]
+pub struct JobContext {
+    id: u64,
+    jobs: JobTable,
+    cancelled: Arc<AtomicBool>,
+    /// 報告された進捗（0.0〜1.0）を割り当てる全体の区間
+    range: (f32, f32),
+}
+
+impl JobContext {
+    /// キューに属さないハンドル（同期的なコマンドからジョブの処理を直接呼ぶ場合に使う）
+    ///
+    /// 進捗の報告は無視され、中断されることもない。
+    pub fn detached() -> Self {
+        Self {
+            id: 0,
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            cancelled: Arc::new(AtomicBool::new(false)),
+            range: (0.0, 1.0),
+        }
+    }
+
+    /// 進捗を `start`〜`end` の区間に割り当てたハンドル（複数の処理を1つのジョブで続けて行う場合に使う）
+    pub fn subrange(&self, start: f32, end: f32) -> Self {
+        let (from, to) = self.range;
+        let map = |p: f32| from + (to - from) * p.clamp(0.0, 1.0);
+        Self {
+            range: (map(start), map(end)),
+            ..self.clone()
+        }
+    }
+
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// 中断が要求されていれば `JobError::Cancelled` を返す（処理の区切りで呼ぶ）
+    pub fn check_cancelled(&self) -> Result<(), JobError> {
+        if self.is_cancelled() {
+            Err(JobError::Cancelled)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 進捗を報告
+    pub fn report(&self, progress: f32, message: impl Into<String>) {
+        let message = message.into();
+        let progress = self.range.0 + (self.range.1 - self.range.0) * progress.clamp(0.0, 1.0);
+        debug!("[JobQueue] ジョブ #{} 進捗: {:.0}% {}", self.id, progress * 100.0, message);
+        update_job(&self.jobs, self.id, |entry| {
+            entry.info.progress = progress.clamp(0.0, 1.0);
+            entry.info.message = Some(message);
+        });
+    }
+}
+
+/// バックグラウンドジョブのキュー
+///
+/// ジョブは tokio のタスクとして実行し、同時実行数を超えた分は空きが出るまで待機する。
+/// 終了したジョブの結果は `take_result` で取り出すまで保持する。
+pub struct JobQueue {
+    next_id: AtomicU64,
+    jobs: JobTable,
+    slots: Arc<Semaphore>,
+}
+
+impl JobQueue {
+    pub fn new(max_concurrent: usize) -> Self {
+        Self {
+            next_id: AtomicU64::new(1),
+            jobs: Arc::new(Mutex::new(HashMap::new())),
+            slots: Arc::new(Semaphore::new(max_concurrent.max(1))),
+        }
+    }
+
+    /// ジョブを登録し、ジョブIDを返す
+    pub fn submit<F, Fut>(&self, kind: impl Into<String>, listener: JobListener, task: F) -> u64
+    where
+        F: FnOnce(JobContext) -> Fut + Send + 'static,
+        Fut: Future<Output = Result<serde_json::Value, JobError>> + Send + 'static,
+    {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let kind = kind.into();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let info = JobInfo {
+            id,
+            kind: kind.clone(),
+            status: JobStatus::Queued,
+            progress: 0.0,
+            message: None,
+            error: None,
+        };
+        listener(&info);
+        {
+            let mut jobs_guard = self.jobs.lock().expect("ジョブ表のロックに失敗");
+            prune_finished(&mut jobs_guard);
+            jobs_guard.insert(id, JobEntry {
+                info,
+                cancelled: cancelled.clone(),
+                result: None,
+                listener,
+            });
+        }
+        info!("[JobQueue] ジョブ登録: #{} {}", id, kind);
+
+        let context = JobContext {
+            id,
+            jobs: self.jobs.clone(),
+            cancelled,
+            range: (0.0, 1.0),
+        };
+        let slots = self.slots.clone();
+        tokio::spawn(async move {
+            let _permit = slots.acquire_owned().await.expect("セマフォは閉じられない");
+
+            let outcome = match context.check_cancelled() {
+                Ok(()) => {
+                    update_job(&context.jobs, id, |entry| entry.info.status = JobStatus::Running);
+                    task(context.clone()).await
+                }
+                Err(e) => Err(e),
+            };
+
+            update_job(&context.jobs, id, |entry| match outcome {
+                Ok(result) => {
+                    entry.info.status = JobStatus::Completed;
+                    entry.info.progress = 1.0;
+                    entry.result = Some(result);
+                }
+                Err(JobError::Cancelled) => entry.info.status = JobStatus::Cancelled,
+                Err(e) => {
+                    warn!("[JobQueue] ジョブ #{} 失敗: {}", id, e);
+                    entry.info.status = JobStatus::Failed;
+                    entry.info.error = Some(match e {
+                        JobError::Failed(msg) => msg,
+                        other => other.to_string(),
+                    });
+                }
+            });
+            debug!("[JobQueue] ジョブ終了: #{}", id);
+        });
+
+        id
+    }
+
+    pub fn info(&self, id: u64) -> Option<JobInfo> {
+        self.jobs.lock().expect("ジョブ表のロックに失敗").get(&id).map(|entry| entry.info.clone())
+    }
+
+    /// 全ジョブの状態（ID順）
+    pub fn list(&self) -> Vec<JobInfo> {
+        let jobs_guard = self.jobs.lock().expect("ジョブ表のロックに失敗");
+        let mut jobs: Vec<JobInfo> = jobs_guard.values().map(|entry| entry.info.clone()).collect();
+        jobs.sort_by_key(|info| info.id);
+        jobs
+    }
+
+    /// ジョブの中断を要求（実行中のジョブは次の区切りで中断される）
+    pub fn cancel(&self, id: u64) -> Result<JobInfo, JobError> {
+        let jobs_guard = self.jobs.lock().expect("ジョブ表のロックに失敗");
+        let entry = jobs_guard.get(&id).ok_or(JobError::JobNotFound(id))?;
+        if !entry.info.status.is_finished() {
+            entry.cancelled.store(true, Ordering::Relaxed);
+            info!("[JobQueue] ジョブ中断要求: #{}", id);
+        }
+        Ok(entry.info.clone())
+    }
+
+    /// 終了したジョブを取り除き、結果を返す（失敗・中断したジョブはそのエラー）
+    pub fn take_result(&self, id: u64) -> Result<serde_json::Value, JobError> {
+        let mut jobs_guard = self.jobs.lock().expect("ジョブ表のロックに失敗");
+        let entry = jobs_guard.get(&id).ok_or(JobError::JobNotFound(id))?;
+        if !entry.info.status.is_finished() {
+            return Err(JobError::NotFinished(id));
+        }
+
+        let entry = jobs_guard.remove(&id).expect("直前に確認済み");
+        match entry.info.status {
+            JobStatus::Completed => Ok(entry.result.unwrap_or(serde_json::Value::Null)),
+            JobStatus::Cancelled => Err(JobError::Cancelled),
+            _ => Err(JobError::Failed(entry.info.error.unwrap_or_default())),
+        }
+    }
+}
+
+impl Default for JobQueue {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_CONCURRENT_JOBS)
+    }
+}
+
+/// 終了済みジョブが上限を超えていれば古いものから捨てる
+fn prune_finished(jobs: &mut HashMap<u64, JobEntry>) {
+    let mut finished: Vec<u64> = jobs.values()
+        .filter(|entry| entry.info.status.is_finished())
+        .map(|entry| entry.info.id)
+        .collect();
+    if finished.len() < MAX_FINISHED_JOBS {
+        return;
+    }
+    finished.sort_unstable();
+    for id in &finished[..=finished.len() - MAX_FINISHED_JOBS] {
+        jobs.remove(id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn silent() -> JobListener {
+        Arc::new(|_| {})
+    }
+
+    async fn wait_finished(queue: &JobQueue, id: u64) -> JobInfo {
+        for _ in 0..500 {
+            if let Some(info) = queue.info(id).filter(|info| info.status.is_finished()) {
+                return info;
+            }
+            tokio::time::sleep(Duration::from_millis(2)).await;
+        }
+        panic!("ジョブ #{} が終了しません", id);
+    }
+
+    #[tokio::test]
+    async fn test_job_reports_progress_and_result() {
+        let events = Arc::new(Mutex::new(Vec::new()));
+        let recorded = events.clone();
+        let listener: JobListener = Arc::new(move |info| recorded.lock().unwrap().push((info.status, info.progress)));
+
+        let queue = JobQueue::default();
+        let id = queue.submit("test", listener, |context| async move {
+            context.report(0.5, "半分");
+            context.subrange(0.5, 1.0).subrange(0.5, 1.0).report(0.5, "後半");
+            Ok(serde_json::json!({ "value": 42 }))
+        });
+
+        assert_eq!(wait_finished(&queue, id).await.status, JobStatus::Completed);
+        assert_eq!(*events.lock().unwrap(), vec![
+            (JobStatus::Queued, 0.0),
+            (JobStatus::Running, 0.0),
+            (JobStatus::Running, 0.5),
+            (JobStatus::Running, 0.875),
+            (JobStatus::Completed, 1.0),
+        ]);
+
+        assert_eq!(queue.take_result(id).unwrap()["value"], 42);
+        assert_eq!(queue.take_result(id), Err(JobError::JobNotFound(id)));
+    }
+
+    #[tokio::test]
+    async fn test_cancel_running_and_queued_jobs() {
+        let queue = JobQueue::new(1);
+        let running = queue.submit("running", silent(), |context| async move {
+            while !context.is_cancelled() {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+            context.check_cancelled()?;
+            Ok(serde_json::Value::Null)
+        });
+        let queued = queue.submit("queued", silent(), |_| async { Ok(serde_json::Value::Null) });
+
+        assert_eq!(queue.take_result(queued), Err(JobError::NotFinished(queued)));
+        queue.cancel(queued).unwrap();
+        queue.cancel(running).unwrap();
+
+        assert_eq!(wait_finished(&queue, running).await.status, JobStatus::Cancelled);
+        assert_eq!(wait_finished(&queue, queued).await.status, JobStatus::Cancelled);
+        assert_eq!(queue.take_result(running), Err(JobError::Cancelled));
+    }
+
+    #[tokio::test]
+    async fn test_failed_job_keeps_error() {
+        let queue = JobQueue::default();
+        let id = queue.submit("failing", silent(), |_| async { Err(JobError::Failed("壊れた".to_string())) });
+
+        let info = wait_finished(&queue, id).await;
+        assert_eq!(info.status, JobStatus::Failed);
+        assert_eq!(info.error.as_deref(), Some("壊れた"));
+        assert!(matches!(queue.take_result(id), Err(JobError::Failed(_))));
+        assert_eq!(queue.list(), vec![]);
+    }
+}
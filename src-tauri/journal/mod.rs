@@ -0,0 +1,266 @@
+use crate::history::{Operation, OperationLog};
+use log::{info, debug, warn};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use std::fs::{self, File, OpenOptions};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// 実行中のセッションのジャーナルファイル名
+pub const JOURNAL_FILE_NAME: &str = "session.journal";
+
+/// 前回のセッションから引き継いだ（復元待ちの）ジャーナルファイル名
+pub const RECOVERED_JOURNAL_FILE_NAME: &str = "recovered.journal";
+
+/// ジャーナルのエラー型
+#[derive(Debug)]
+pub enum JournalError {
+    Io(std::io::Error),
+    Serialize(String),
+}
+
+impl fmt::Display for JournalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            JournalError::Io(e) => write!(f, "ジャーナルの入出力エラー: {}", e),
+            JournalError::Serialize(msg) => write!(f, "ジャーナルのシリアライズエラー: {}", msg),
+        }
+    }
+}
+
+impl Error for JournalError {}
+
+impl From<std::io::Error> for JournalError {
+    fn from(e: std::io::Error) -> Self {
+        JournalError::Io(e)
+    }
+}
+
+/// ジャーナルの1行分の記録
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JournalRecord {
+    /// 保存した（または開いた）プロジェクトファイル。復元時はこれを開いてから以降の記録を適用する
+    Saved { path: String },
+    /// 履歴を丸ごと置き換えたときの操作ログ（取り込み・解像度変更など）
+    Base { log: OperationLog },
+    /// 確定した操作
+    Push { operation: Operation },
+    /// アンドゥ・リドゥ・履歴位置の移動（現在位置からの相対値。保存後は起点の操作ログの長さが変わるため）
+    Seek { offset: i64 },
+}
+
+impl JournalRecord {
+    /// 以前の記録をすべて不要にする記録か
+    pub fn is_checkpoint(&self) -> bool {
+        matches!(self, JournalRecord::Saved { .. } | JournalRecord::Base { .. })
+    }
+}
+
+/// 復元の起点
+#[derive(Debug, Clone)]
+pub enum JournalBase {
+    /// 空のキャンバスから始まったセッション
+    Empty,
+    Saved(String),
+    Log(OperationLog),
+}
+
+/// 読み込んだジャーナルの内容
+#[derive(Debug, Clone)]
+pub struct JournalContents {
+    pub base: JournalBase,
+    /// 起点より後の記録（Push / Seek のみ）
+    pub records: Vec<JournalRecord>,
+    /// 書き込み途中で終わった行があった（クラッシュ時の最後の記録は欠けることがある）
+    pub truncated: bool,
+}
+
+impl JournalContents {
+    /// JSON Lines を解析（壊れた行以降は書き込み途中とみなして無視する）
+    pub fn parse(data: &str) -> Self {
+        let mut contents = JournalContents { base: JournalBase::Empty, records: Vec::new(), truncated: false };
+        for line in data.lines().filter(|line| !line.trim().is_empty()) {
+            let record = match serde_json::from_str::<JournalRecord>(line) {
+                Ok(record) => record,
+                Err(e) => {
+                    warn!("[Journal] 壊れた記録以降を無視: {}", e);
+                    contents.truncated = true;
+                    break;
+                }
+            };
+            match record {
+                JournalRecord::Saved { path } => {
+                    contents.base = JournalBase::Saved(path);
+                    contents.records.clear();
+                }
+                JournalRecord::Base { log } => {
+                    contents.base = JournalBase::Log(log);
+                    contents.records.clear();
+                }
+                record => contents.records.push(record),
+            }
+        }
+        contents
+    }
+
+    /// 起点以降に復元すべき変更があるか
+    pub fn has_changes(&self) -> bool {
+        !self.records.is_empty()
+    }
+
+    /// 起点の操作ログに記録を適用（適用できない移動は無視する）し、適用した記録の数を返す
+    pub fn apply_to(&self, log: &mut OperationLog) -> usize {
+        let mut applied = 0;
+        for record in &self.records {
+            match record {
+                JournalRecord::Push { operation } => {
+                    log.push(operation.clone());
+                    applied += 1;
+                }
+                JournalRecord::Seek { offset } => {
+                    let position = log.position() as i64 + offset;
+                    match usize::try_from(position).map(|p| log.seek(p)) {
+                        Ok(Ok(())) => applied += 1,
+                        _ => warn!("[Journal] 履歴位置の移動を適用できません: {}", position),
+                    }
+                }
+                _ => {}
+            }
+        }
+        applied
+    }
+}
+
+/// 追記専用のジャーナル（確定した操作を発生した順にディスクへ書き出す）
+pub struct Journal {
+    path: PathBuf,
+    file: File,
+    /// 最後の圧縮以降に追記した記録の数
+    record_count: usize,
+    /// 起点が保存したプロジェクトファイルか
+    saved_base: bool,
+}
+
+impl Journal {
+    /// 空のジャーナルを作成（既存の内容は破棄する）
+    pub fn create(path: &Path) -> Result<Self, JournalError> {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir)?;
+        }
+        let file = File::create(path)?;
+        info!("[Journal] ジャーナル作成: {:?}", path);
+        Ok(Self { path: path.to_path_buf(), file, record_count: 0, saved_base: false })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    pub fn record_count(&self) -> usize {
+        self.record_count
+    }
+
+    /// 起点が保存したプロジェクトファイルか（ファイルの内容は操作ログだけでは作り直せないため、
+    /// 操作ログを起点にした圧縮はできない）
+    pub fn has_saved_base(&self) -> bool {
+        self.saved_base
+    }
+
+    /// 記録を1行追記してディスクへ同期
+    pub fn append(&mut self, record: &JournalRecord) -> Result<(), JournalError> {
+        if record.is_checkpoint() {
+            return self.compact(record);
+        }
+        let mut line = serde_json::to_string(record).map_err(|e| JournalError::Serialize(e.to_string()))?;
+        line.push('\n');
+        self.file.write_all(line.as_bytes())?;
+        self.file.sync_data()?;
+        self.record_count += 1;
+        Ok(())
+    }
+
+    /// 起点となる記録だけのジャーナルに置き換える（一時ファイルに書いてから差し替える）
+    pub fn compact(&mut self, checkpoint: &JournalRecord) -> Result<(), JournalError> {
+        let mut line = serde_json::to_string(checkpoint).map_err(|e| JournalError::Serialize(e.to_string()))?;
+        line.push('\n');
+
+        let temp_path = self.path.with_extension("journal.tmp");
+        {
+            let mut temp = File::create(&temp_path)?;
+            temp.write_all(line.as_bytes())?;
+            temp.sync_all()?;
+        }
+        fs::rename(&temp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        debug!("[Journal] ジャーナル圧縮: {} 件の記録を破棄", self.record_count);
+        self.record_count = 0;
+        self.saved_base = matches!(checkpoint, JournalRecord::Saved { .. });
+        Ok(())
+    }
+
+    /// ジャーナルファイルを読み込む
+    pub fn read(path: &Path) -> Result<JournalContents, JournalError> {
+        // 最後の行が書き込み途中で UTF-8 として壊れていても読めるようにする
+        let data = fs::read(path)?;
+        Ok(JournalContents::parse(&String::from_utf8_lossy(&data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clear(layer_id: &str) -> Operation {
+        Operation::ClearLayer { layer_id: layer_id.to_string() }
+    }
+
+    #[test]
+    fn test_append_and_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE_NAME);
+        let mut journal = Journal::create(&path).unwrap();
+        journal.append(&JournalRecord::Push { operation: clear("a") }).unwrap();
+        journal.append(&JournalRecord::Push { operation: clear("b") }).unwrap();
+        journal.append(&JournalRecord::Seek { offset: -1 }).unwrap();
+        journal.append(&JournalRecord::Push { operation: clear("c") }).unwrap();
+
+        let contents = Journal::read(&path).unwrap();
+        assert!(matches!(contents.base, JournalBase::Empty));
+        assert!(contents.has_changes());
+
+        let mut log = OperationLog::new();
+        assert_eq!(contents.apply_to(&mut log), 4);
+        let operations: Vec<_> = log.entries().iter().map(|e| e.operation.clone()).collect();
+        assert_eq!(operations, vec![clear("a"), clear("c")]);
+        assert_eq!(log.position(), 2);
+    }
+
+    #[test]
+    fn test_compaction_on_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join(JOURNAL_FILE_NAME);
+        let mut journal = Journal::create(&path).unwrap();
+        journal.append(&JournalRecord::Push { operation: clear("a") }).unwrap();
+        journal.append(&JournalRecord::Saved { path: "/tmp/a.kine".to_string() }).unwrap();
+        assert_eq!(journal.record_count(), 0);
+        assert!(journal.has_saved_base());
+        journal.append(&JournalRecord::Push { operation: clear("b") }).unwrap();
+
+        let contents = Journal::read(&path).unwrap();
+        assert!(matches!(&contents.base, JournalBase::Saved(path) if path == "/tmp/a.kine"));
+        assert!(matches!(&contents.records[..], [JournalRecord::Push { operation }] if *operation == clear("b")));
+        assert_eq!(fs::read_to_string(&path).unwrap().lines().count(), 2);
+    }
+
+    #[test]
+    fn test_torn_last_record_is_ignored() {
+        let line = serde_json::to_string(&JournalRecord::Push { operation: clear("a") }).unwrap();
+        let data = format!("{}\n{}", line, &line[..line.len() / 2]);
+
+        let contents = JournalContents::parse(&data);
+        assert!(contents.truncated);
+        assert_eq!(contents.records.len(), 1);
+    }
+}
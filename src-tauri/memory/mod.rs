@@ -0,0 +1,271 @@
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+use log::{info, warn};
+
+/// プレビュー解像度の倍率の下限
+const MIN_PREVIEW_SCALE: f32 = 0.25;
+
+/// メモリ監視のエラー型
+#[derive(Debug)]
+pub enum MemoryError {
+    InvalidConfig(String),
+}
+
+impl fmt::Display for MemoryError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MemoryError::InvalidConfig(msg) => write!(f, "メモリ監視の設定が不正です: {}", msg),
+        }
+    }
+}
+
+impl Error for MemoryError {}
+
+/// メモリ監視の設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MemoryConfig {
+    /// メモリ使用量の予算（バイト）
+    pub budget_bytes: u64,
+    /// 予算に対してこの割合を超えたら警告
+    pub warning_ratio: f32,
+    /// 予算に対してこの割合を超えたら品質を落とす
+    pub critical_ratio: f32,
+    /// 危険域に入ったときに自動で品質を落とすか
+    pub auto_downgrade: bool,
+    /// 定期監視の間隔（ミリ秒）
+    pub poll_interval_ms: u32,
+}
+
+impl Default for MemoryConfig {
+    fn default() -> Self {
+        Self {
+            budget_bytes: 2 * 1024 * 1024 * 1024,
+            warning_ratio: 0.75,
+            critical_ratio: 0.9,
+            auto_downgrade: true,
+            poll_interval_ms: 2000,
+        }
+    }
+}
+
+impl MemoryConfig {
+    pub fn validate(&self) -> Result<(), MemoryError> {
+        if self.budget_bytes == 0 {
+            return Err(MemoryError::InvalidConfig("予算は0より大きい必要があります".to_string()));
+        }
+        if !(self.warning_ratio > 0.0 && self.warning_ratio < self.critical_ratio && self.critical_ratio <= 1.0) {
+            return Err(MemoryError::InvalidConfig(format!(
+                "しきい値は 0 < 警告 < 危険 <= 1 である必要があります: {} / {}",
+                self.warning_ratio, self.critical_ratio
+            )));
+        }
+        if self.poll_interval_ms < 100 {
+            return Err(MemoryError::InvalidConfig(format!("監視間隔が短すぎます: {}ms", self.poll_interval_ms)));
+        }
+        Ok(())
+    }
+}
+
+/// 種類ごとのメモリ使用量（バイト）
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize)]
+pub struct MemoryUsage {
+    /// GPUテクスチャ（プール内の未使用テクスチャを含む）
+    pub texture_bytes: u64,
+    /// 読み出し中のステージングバッファ
+    pub staging_bytes: u64,
+    /// CPU側に保持しているレイヤー画像（履歴の貼り付け画像、タイムラプスのフレーム）
+    pub cpu_layer_bytes: u64,
+}
+
+impl MemoryUsage {
+    pub fn total(&self) -> u64 {
+        self.texture_bytes + self.staging_bytes + self.cpu_layer_bytes
+    }
+}
+
+/// メモリの逼迫度
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MemoryLevel {
+    Normal,
+    Warning,
+    Critical,
+}
+
+/// 危険域で行う品質の引き下げ
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum DowngradeAction {
+    /// プールに残っている未使用テクスチャを解放
+    ReleaseTexturePool,
+    /// プレビュー（タイムラプスのフレームなど）の解像度を下げる
+    ReducePreviewResolution { scale: f32 },
+    /// 表示していないフレームをディスクへ退避
+    EvictColdFrames,
+}
+
+/// 監視結果（フロントエンドへの通知内容）
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MemoryReport {
+    pub usage: MemoryUsage,
+    pub budget_bytes: u64,
+    pub usage_ratio: f32,
+    pub level: MemoryLevel,
+    /// 現在のプレビュー解像度の倍率
+    pub preview_scale: f32,
+    /// 今回の評価で行うべき品質の引き下げ
+    pub actions: Vec<DowngradeAction>,
+}
+
+/// メモリ使用量を予算と比較し、しきい値をまたいだときに警告・品質の引き下げを決める
+#[derive(Debug, Clone)]
+pub struct MemoryMonitor {
+    config: MemoryConfig,
+    level: MemoryLevel,
+    preview_scale: f32,
+}
+
+impl MemoryMonitor {
+    pub fn new(config: MemoryConfig) -> Result<Self, MemoryError> {
+        config.validate()?;
+        Ok(Self {
+            config,
+            level: MemoryLevel::Normal,
+            preview_scale: 1.0,
+        })
+    }
+
+    pub fn config(&self) -> &MemoryConfig {
+        &self.config
+    }
+
+    pub fn set_config(&mut self, config: MemoryConfig) -> Result<(), MemoryError> {
+        config.validate()?;
+        info!("[MemoryMonitor] 設定更新: 予算 {} bytes", config.budget_bytes);
+        self.config = config;
+        Ok(())
+    }
+
+    pub fn level(&self) -> MemoryLevel {
+        self.level
+    }
+
+    /// 使用量を評価する（戻り値の bool はレベルが変わったか）
+    ///
+    /// 品質の引き下げは危険域に入った時点で1回だけ行い、通常域に戻るとプレビュー解像度を戻す。
+    pub fn evaluate(&mut self, usage: MemoryUsage) -> (MemoryReport, bool) {
+        let usage_ratio = usage.total() as f32 / self.config.budget_bytes as f32;
+        let level = if usage_ratio >= self.config.critical_ratio {
+            MemoryLevel::Critical
+        } else if usage_ratio >= self.config.warning_ratio {
+            MemoryLevel::Warning
+        } else {
+            MemoryLevel::Normal
+        };
+        let changed = level != self.level;
+
+        let mut actions = Vec::new();
+        if changed {
+            match level {
+                MemoryLevel::Critical if self.config.auto_downgrade => {
+                    self.preview_scale = (self.preview_scale * 0.5).max(MIN_PREVIEW_SCALE);
+                    actions.push(DowngradeAction::ReleaseTexturePool);
+                    actions.push(DowngradeAction::ReducePreviewResolution { scale: self.preview_scale });
+                    actions.push(DowngradeAction::EvictColdFrames);
+                }
+                MemoryLevel::Normal => self.preview_scale = 1.0,
+                _ => {}
+            }
+            if level > self.level {
+                warn!("[MemoryMonitor] メモリ逼迫: {:?} ({:.0}%)", level, usage_ratio * 100.0);
+            } else {
+                info!("[MemoryMonitor] メモリ状態回復: {:?} ({:.0}%)", level, usage_ratio * 100.0);
+            }
+            self.level = level;
+        }
+
+        let report = MemoryReport {
+            usage,
+            budget_bytes: self.config.budget_bytes,
+            usage_ratio,
+            level,
+            preview_scale: self.preview_scale,
+            actions,
+        };
+        (report, changed)
+    }
+}
+
+impl Default for MemoryMonitor {
+    fn default() -> Self {
+        Self::new(MemoryConfig::default()).expect("既定の設定は有効")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn usage(texture_bytes: u64) -> MemoryUsage {
+        MemoryUsage { texture_bytes, staging_bytes: 0, cpu_layer_bytes: 0 }
+    }
+
+    fn monitor() -> MemoryMonitor {
+        MemoryMonitor::new(MemoryConfig { budget_bytes: 100, ..MemoryConfig::default() }).unwrap()
+    }
+
+    #[test]
+    fn test_levels_and_change_notification() {
+        let mut monitor = monitor();
+
+        let (report, changed) = monitor.evaluate(usage(50));
+        assert_eq!((report.level, changed), (MemoryLevel::Normal, false));
+
+        let (report, changed) = monitor.evaluate(MemoryUsage { texture_bytes: 40, staging_bytes: 20, cpu_layer_bytes: 20 });
+        assert_eq!((report.level, changed), (MemoryLevel::Warning, true));
+        assert!(report.actions.is_empty());
+
+        let (_, changed) = monitor.evaluate(usage(80));
+        assert!(!changed);
+    }
+
+    #[test]
+    fn test_downgrade_once_per_critical_entry() {
+        let mut monitor = monitor();
+
+        let (report, changed) = monitor.evaluate(usage(95));
+        assert_eq!((report.level, changed), (MemoryLevel::Critical, true));
+        assert_eq!(report.actions, vec![
+            DowngradeAction::ReleaseTexturePool,
+            DowngradeAction::ReducePreviewResolution { scale: 0.5 },
+            DowngradeAction::EvictColdFrames,
+        ]);
+
+        let (report, _) = monitor.evaluate(usage(99));
+        assert!(report.actions.is_empty());
+
+        // 警告域を経由して再び危険域に入るとさらに下げる
+        monitor.evaluate(usage(80));
+        let (report, _) = monitor.evaluate(usage(95));
+        assert_eq!(report.preview_scale, 0.25);
+
+        let (report, changed) = monitor.evaluate(usage(10));
+        assert_eq!((report.level, changed, report.preview_scale), (MemoryLevel::Normal, true, 1.0));
+    }
+
+    #[test]
+    fn test_auto_downgrade_disabled_and_validation() {
+        let mut monitor = MemoryMonitor::new(MemoryConfig {
+            budget_bytes: 100,
+            auto_downgrade: false,
+            ..MemoryConfig::default()
+        }).unwrap();
+        let (report, changed) = monitor.evaluate(usage(100));
+        assert!(changed && report.actions.is_empty());
+
+        assert!(MemoryConfig { warning_ratio: 0.95, ..MemoryConfig::default() }.validate().is_err());
+        assert!(MemoryConfig { budget_bytes: 0, ..MemoryConfig::default() }.validate().is_err());
+    }
+}
@@ -0,0 +1,277 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use std::path::{Path, PathBuf};
+use log::{info, debug, warn};
+
+/// 再生位置の前後で常駐させておくフレーム数の既定値
+pub const DEFAULT_KEEP_RADIUS: usize = 8;
+
+/// メモリ逼迫時に常駐させておくフレーム数
+pub const PRESSURE_KEEP_RADIUS: usize = 2;
+
+/// フレームページングのエラー型
+#[derive(Debug)]
+pub enum PagingError {
+    Io(String),
+    Codec(String),
+    NotPaged(String),
+}
+
+impl fmt::Display for PagingError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PagingError::Io(msg) => write!(f, "退避ファイルの入出力に失敗しました: {}", msg),
+            PagingError::Codec(msg) => write!(f, "退避データの変換に失敗しました: {}", msg),
+            PagingError::NotPaged(id) => write!(f, "レイヤーは退避されていません: {}", id),
+        }
+    }
+}
+
+impl Error for PagingError {}
+
+/// ディスクに退避したレイヤー
+#[derive(Debug, Clone, PartialEq)]
+pub struct PagedLayer {
+    pub path: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    /// 圧縮後のファイルサイズ
+    pub bytes: u64,
+}
+
+/// 1フレームを構成するレイヤー
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FrameLayers {
+    pub frame_id: String,
+    pub layer_ids: Vec<String>,
+}
+
+/// 再生位置から決めた退避・復帰の対象
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PagingPlan {
+    /// 再生位置から遠いフレームだけで使われているレイヤー
+    pub cold: Vec<String>,
+    /// 再生位置の近くのフレームで使われているレイヤー
+    pub warm: Vec<String>,
+}
+
+/// 再生位置から `keep_radius` フレームより離れたフレームのレイヤーを退避対象にする
+///
+/// 近くのフレームと共有されているレイヤーは退避しない。
+pub fn plan_paging(frames: &[FrameLayers], playhead: usize, keep_radius: usize) -> PagingPlan {
+    let warm: HashSet<&String> = frames.iter().enumerate()
+        .filter(|(index, _)| index.abs_diff(playhead) <= keep_radius)
+        .flat_map(|(_, frame)| frame.layer_ids.iter())
+        .collect();
+
+    let mut plan = PagingPlan::default();
+    let mut seen = HashSet::new();
+    for layer_id in frames.iter().flat_map(|frame| frame.layer_ids.iter()) {
+        if !seen.insert(layer_id) {
+            continue;
+        }
+        if warm.contains(layer_id) {
+            plan.warm.push(layer_id.clone());
+        } else {
+            plan.cold.push(layer_id.clone());
+        }
+    }
+    plan
+}
+
+/// レイヤー画像を圧縮ファイルとして書き出し、ファイルサイズを返す
+pub fn write_page(path: &Path, image: &RgbaImage) -> Result<u64, PagingError> {
+    let mut data = std::io::Cursor::new(Vec::new());
+    image.write_to(&mut data, image::ImageFormat::Png)
+        .map_err(|e| PagingError::Codec(e.to_string()))?;
+    let data = data.into_inner();
+    std::fs::write(path, &data).map_err(|e| PagingError::Io(e.to_string()))?;
+    Ok(data.len() as u64)
+}
+
+/// 退避ファイルからレイヤー画像を読み込む
+pub fn read_page(page: &PagedLayer) -> Result<RgbaImage, PagingError> {
+    let data = std::fs::read(&page.path).map_err(|e| PagingError::Io(e.to_string()))?;
+    let image = image::load_from_memory_with_format(&data, image::ImageFormat::Png)
+        .map_err(|e| PagingError::Codec(e.to_string()))?
+        .to_rgba8();
+    if image.dimensions() != (page.width, page.height) {
+        return Err(PagingError::Codec(format!(
+            "画像サイズが一致しません: {:?} != {}x{}", image.dimensions(), page.width, page.height
+        )));
+    }
+    Ok(image)
+}
+
+/// 再生位置から遠いフレームのレイヤーを一時ファイルへ退避する管理表
+///
+/// ファイル名は連番で、レイヤーIDはパスに含めない。破棄時に退避ファイルを削除する。
+pub struct FramePager {
+    dir: PathBuf,
+    paged: HashMap<String, PagedLayer>,
+    next_page: u64,
+    /// 最後に通知されたフレーム構成（メモリ逼迫時の自動退避に使う）
+    layout: Vec<FrameLayers>,
+    playhead: usize,
+}
+
+impl FramePager {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self {
+            dir: dir.into(),
+            paged: HashMap::new(),
+            next_page: 1,
+            layout: Vec::new(),
+            playhead: 0,
+        }
+    }
+
+    /// OSの一時ディレクトリ配下（プロセスごと）に退避する
+    pub fn in_temp_dir() -> Self {
+        Self::new(std::env::temp_dir().join(format!("kinegraph-pages-{}", std::process::id())))
+    }
+
+    pub fn is_paged(&self, layer_id: &str) -> bool {
+        self.paged.contains_key(layer_id)
+    }
+
+    pub fn get(&self, layer_id: &str) -> Option<&PagedLayer> {
+        self.paged.get(layer_id)
+    }
+
+    pub fn paged_layer_ids(&self) -> Vec<String> {
+        let mut ids: Vec<String> = self.paged.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// 退避ファイルの合計サイズ
+    pub fn swapped_bytes(&self) -> u64 {
+        self.paged.values().map(|page| page.bytes).sum()
+    }
+
+    /// 新しい退避ファイルのパスを割り当てる（ディレクトリがなければ作成）
+    pub fn allocate_path(&mut self) -> Result<PathBuf, PagingError> {
+        std::fs::create_dir_all(&self.dir).map_err(|e| PagingError::Io(e.to_string()))?;
+        let path = self.dir.join(format!("page_{}.png", self.next_page));
+        self.next_page += 1;
+        Ok(path)
+    }
+
+    /// 書き出し済みの退避ファイルを登録
+    pub fn insert(&mut self, layer_id: &str, page: PagedLayer) {
+        debug!("[FramePager] 退避: {} -> {:?} ({} bytes)", layer_id, page.path, page.bytes);
+        if let Some(previous) = self.paged.insert(layer_id.to_string(), page) {
+            remove_file(&previous.path);
+        }
+    }
+
+    /// 登録を取り除く（ファイルは呼び出し側が読み込んだ後に `release` で削除する）
+    pub fn take(&mut self, layer_id: &str) -> Result<PagedLayer, PagingError> {
+        self.paged.remove(layer_id).ok_or_else(|| PagingError::NotPaged(layer_id.to_string()))
+    }
+
+    /// 復帰済みの退避ファイルを削除
+    pub fn release(&self, page: &PagedLayer) {
+        remove_file(&page.path);
+    }
+
+    /// 退避内容を破棄（レイヤー削除や状態の再構築時）。退避されていたら true
+    pub fn discard(&mut self, layer_id: &str) -> bool {
+        match self.paged.remove(layer_id) {
+            Some(page) => {
+                remove_file(&page.path);
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// すべての退避内容を破棄
+    pub fn clear(&mut self) {
+        if !self.paged.is_empty() {
+            info!("[FramePager] 退避内容を破棄: {} レイヤー", self.paged.len());
+        }
+        for (_, page) in self.paged.drain() {
+            remove_file(&page.path);
+        }
+    }
+
+    pub fn set_layout(&mut self, frames: Vec<FrameLayers>, playhead: usize) {
+        self.layout = frames;
+        self.playhead = playhead;
+    }
+
+    pub fn layout(&self) -> (&[FrameLayers], usize) {
+        (&self.layout, self.playhead)
+    }
+}
+
+impl Drop for FramePager {
+    fn drop(&mut self) {
+        self.clear();
+        let _ = std::fs::remove_dir(&self.dir);
+    }
+}
+
+fn remove_file(path: &Path) {
+    if let Err(e) = std::fs::remove_file(path) {
+        warn!("[FramePager] 退避ファイルの削除に失敗: {:?} ({})", path, e);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    fn frame(id: &str, layer_ids: &[&str]) -> FrameLayers {
+        FrameLayers {
+            frame_id: id.to_string(),
+            layer_ids: layer_ids.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_plan_keeps_shared_layers_warm() {
+        let frames = vec![
+            frame("f0", &["bg", "a0"]),
+            frame("f1", &["bg", "a1"]),
+            frame("f2", &["bg", "a2"]),
+            frame("f3", &["bg", "a3"]),
+        ];
+        let plan = plan_paging(&frames, 3, 1);
+        assert_eq!(plan.warm, vec!["bg", "a2", "a3"]);
+        assert_eq!(plan.cold, vec!["a0", "a1"]);
+    }
+
+    #[test]
+    fn test_page_roundtrip_and_cleanup() {
+        let dir = std::env::temp_dir().join(format!("kinegraph-pages-test-{}", std::process::id()));
+        let mut pager = FramePager::new(&dir);
+
+        let mut image = RgbaImage::new(3, 2);
+        image.put_pixel(2, 1, Rgba([1, 2, 3, 4]));
+        let path = pager.allocate_path().unwrap();
+        let bytes = write_page(&path, &image).unwrap();
+        pager.insert("layer", PagedLayer { path: path.clone(), width: 3, height: 2, bytes });
+        assert!(pager.is_paged("layer"));
+        assert_eq!(pager.swapped_bytes(), bytes);
+
+        let page = pager.take("layer").unwrap();
+        assert_eq!(read_page(&page).unwrap(), image);
+        pager.release(&page);
+        assert!(!path.exists());
+        assert!(matches!(pager.take("layer"), Err(PagingError::NotPaged(_))));
+
+        let path = pager.allocate_path().unwrap();
+        write_page(&path, &image).unwrap();
+        pager.insert("other", PagedLayer { path: path.clone(), width: 3, height: 2, bytes });
+        drop(pager);
+        assert!(!path.exists());
+        assert!(!dir.exists());
+    }
+}
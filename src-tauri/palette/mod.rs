@@ -0,0 +1,233 @@
+use image::RgbaImage;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt;
+use log::debug;
+
+/// 抽出できる色数の上限
+pub const MAX_PALETTE_COLORS: usize = 256;
+/// 色を数える前に縮小する画像の長辺（ピクセル）
+pub const PALETTE_SAMPLE_SIZE: u32 = 256;
+/// 色として数える最小の不透明度（半透明の縁は除く）
+const MIN_ALPHA: u8 = 128;
+
+/// パレット抽出のエラー型
+#[derive(Debug, PartialEq)]
+pub enum PaletteError {
+    InvalidColorCount(usize),
+    NoOpaquePixels,
+}
+
+impl fmt::Display for PaletteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PaletteError::InvalidColorCount(count) => {
+                write!(f, "色数は 1〜{} で指定してください: {}", MAX_PALETTE_COLORS, count)
+            }
+            PaletteError::NoOpaquePixels => write!(f, "色を抽出できる不透明なピクセルがありません"),
+        }
+    }
+}
+
+impl Error for PaletteError {}
+
+/// 抽出した色と、画像に占める割合
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PaletteColor {
+    pub color: [u8; 3],
+    /// 0〜1（抽出した色の合計で 1）
+    pub share: f32,
+}
+
+/// k-means で色を調整する最大の回数
+const MAX_REFINE_ITERATIONS: usize = 8;
+
+/// 画像の色と、その色のピクセル数
+type WeightedColor = ([u8; 3], u32);
+
+/// メディアンカットで分割中の色の集まり
+struct ColorBox {
+    colors: Vec<WeightedColor>,
+}
+
+impl ColorBox {
+    fn pixel_count(&self) -> u64 {
+        self.colors.iter().map(|&(_, count)| count as u64).sum()
+    }
+
+    /// 最も広がっているチャンネルとその幅
+    fn widest_channel(&self) -> (usize, u8) {
+        (0..3)
+            .map(|channel| {
+                let (min, max) = self.colors.iter().fold((u8::MAX, 0), |(min, max), (color, _)| {
+                    (min.min(color[channel]), max.max(color[channel]))
+                });
+                (channel, max.saturating_sub(min))
+            })
+            .max_by_key(|&(channel, range)| (range, std::cmp::Reverse(channel)))
+            .unwrap_or((0, 0))
+    }
+
+    /// 最も広がっているチャンネルで、ピクセル数の中央の位置で2つに分ける（どちらも1色以上残す）
+    fn split(mut self) -> (ColorBox, ColorBox) {
+        let (channel, _) = self.widest_channel();
+        self.colors.sort_unstable_by_key(|(color, _)| color[channel]);
+        let half = self.pixel_count() / 2;
+        let mut accumulated = 0;
+        let position = self.colors.iter()
+            .position(|&(_, count)| {
+                accumulated += count as u64;
+                accumulated >= half
+            })
+            .unwrap_or(0);
+        let upper = self.colors.split_off((position + 1).clamp(1, self.colors.len() - 1));
+        (self, ColorBox { colors: upper })
+    }
+
+    fn average(&self) -> [u8; 3] {
+        weighted_average(self.colors.iter().copied())
+    }
+}
+
+/// ピクセル数で重み付けした平均の色
+fn weighted_average(colors: impl Iterator<Item = WeightedColor>) -> [u8; 3] {
+    let mut sum = [0u64; 3];
+    let mut total = 0u64;
+    for (color, count) in colors {
+        for channel in 0..3 {
+            sum[channel] += color[channel] as u64 * count as u64;
+        }
+        total += count as u64;
+    }
+    let total = total.max(1);
+    sum.map(|value| ((value + total / 2) / total) as u8)
+}
+
+fn distance_squared(a: [u8; 3], b: [u8; 3]) -> u32 {
+    (0..3).map(|channel| (a[channel] as i32 - b[channel] as i32).pow(2) as u32).sum()
+}
+
+/// 長辺が `max_size` 以下になるように縮小する（小さい画像はそのまま）
+pub fn downsample(image: &RgbaImage, max_size: u32) -> RgbaImage {
+    let longest = image.width().max(image.height());
+    if longest <= max_size {
+        return image.clone();
+    }
+    let scale = max_size as f32 / longest as f32;
+    let width = ((image.width() as f32 * scale).round() as u32).max(1);
+    let height = ((image.height() as f32 * scale).round() as u32).max(1);
+    image::imageops::resize(image, width, height, image::imageops::FilterType::Triangle)
+}
+
+/// 画像の主な色を最大 `count` 色抽出する（割合の大きい順）
+///
+/// メディアンカットで分けた色を初期値に、k-means で各色を近いピクセルの平均に寄せる。
+/// 不透明度が半分未満のピクセルは数えない。画像の色の種類が少なければ `count` より少なくなる。
+pub fn extract_palette(image: &RgbaImage, count: usize) -> Result<Vec<PaletteColor>, PaletteError> {
+    if !(1..=MAX_PALETTE_COLORS).contains(&count) {
+        return Err(PaletteError::InvalidColorCount(count));
+    }
+    let mut histogram: HashMap<[u8; 3], u32> = HashMap::new();
+    for pixel in image.pixels().filter(|pixel| pixel[3] >= MIN_ALPHA) {
+        *histogram.entry([pixel[0], pixel[1], pixel[2]]).or_default() += 1;
+    }
+    if histogram.is_empty() {
+        return Err(PaletteError::NoOpaquePixels);
+    }
+    let mut colors: Vec<WeightedColor> = histogram.into_iter().collect();
+    colors.sort_unstable();
+    let total: u64 = colors.iter().map(|&(_, count)| count as u64).sum();
+
+    // メディアンカット: 広がりとピクセル数の積が最も大きい箱から分割する（1色だけの箱は分けない）
+    let mut boxes = vec![ColorBox { colors: colors.clone() }];
+    while boxes.len() < count {
+        let Some((index, _)) = boxes.iter()
+            .enumerate()
+            .filter(|(_, color_box)| color_box.colors.len() > 1)
+            .map(|(index, color_box)| (index, color_box.widest_channel().1 as u64 * color_box.pixel_count()))
+            .max_by_key(|&(index, score)| (score, std::cmp::Reverse(index)))
+        else {
+            break;
+        };
+        let (lower, upper) = boxes.swap_remove(index).split();
+        boxes.push(lower);
+        boxes.push(upper);
+    }
+    let mut centers: Vec<[u8; 3]> = boxes.iter().map(ColorBox::average).collect();
+
+    // k-means: 各色を最も近い中心に割り当て、中心を割り当てた色の平均に動かす
+    let mut assignment = vec![usize::MAX; colors.len()];
+    for _ in 0..MAX_REFINE_ITERATIONS {
+        let mut changed = false;
+        for (slot, &(color, _)) in assignment.iter_mut().zip(&colors) {
+            let nearest = (0..centers.len())
+                .min_by_key(|&index| distance_squared(color, centers[index]))
+                .unwrap_or(0);
+            changed |= *slot != nearest;
+            *slot = nearest;
+        }
+        if !changed {
+            break;
+        }
+        let mut members = vec![Vec::new(); centers.len()];
+        for (&color, &slot) in colors.iter().zip(&assignment) {
+            members[slot].push(color);
+        }
+        for (center, members) in centers.iter_mut().zip(members) {
+            // 割り当てのない中心は最後に取り除く
+            if !members.is_empty() {
+                *center = weighted_average(members.into_iter());
+            }
+        }
+    }
+
+    let mut counts = vec![0u64; centers.len()];
+    for (&(_, count), &slot) in colors.iter().zip(&assignment) {
+        counts[slot] += count as u64;
+    }
+    let mut palette: Vec<PaletteColor> = centers.into_iter()
+        .zip(counts)
+        .filter(|&(_, count)| count > 0)
+        .map(|(color, count)| PaletteColor { color, share: count as f32 / total as f32 })
+        .collect();
+    palette.sort_by(|a, b| b.share.total_cmp(&a.share).then(a.color.cmp(&b.color)));
+    debug!("[Palette] {} ピクセルから {} 色を抽出", total, palette.len());
+    Ok(palette)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::Rgba;
+
+    #[test]
+    fn test_extract_dominant_colors() {
+        // 赤が 6 割、青が 3 割、緑が 1 割。下端の行は透明
+        let image = RgbaImage::from_fn(10, 11, |x, y| match (x, y) {
+            (_, 10) => Rgba([255, 255, 255, 0]),
+            (0..=5, _) => Rgba([250, 10, 10, 255]),
+            (6..=8, _) => Rgba([10, 10, 240, 255]),
+            _ => Rgba([20, 200, 20, 255]),
+        });
+
+        let palette = extract_palette(&image, 3).unwrap();
+        let colors: Vec<[u8; 3]> = palette.iter().map(|entry| entry.color).collect();
+        assert_eq!(colors, vec![[250, 10, 10], [10, 10, 240], [20, 200, 20]]);
+        assert!((palette[0].share - 0.6).abs() < 1e-6);
+
+        // 色の種類より多くは分けない
+        assert_eq!(extract_palette(&image, 8).unwrap().len(), 3);
+        assert_eq!(extract_palette(&image, 1).unwrap()[0].share, 1.0);
+
+        assert_eq!(extract_palette(&image, 0), Err(PaletteError::InvalidColorCount(0)));
+        assert_eq!(extract_palette(&RgbaImage::new(4, 4), 4), Err(PaletteError::NoOpaquePixels));
+    }
+
+    #[test]
+    fn test_downsample_keeps_aspect() {
+        let image = RgbaImage::new(1000, 250);
+        assert_eq!(downsample(&image, 256).dimensions(), (256, 64));
+        assert_eq!(downsample(&RgbaImage::new(100, 40), 256).dimensions(), (100, 40));
+    }
+}
@@ -0,0 +1,397 @@
+use rhai::{Array, Dynamic, Engine, EvalAltResult, Map, INT};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use std::fmt;
+use std::rc::Rc;
+use log::{info, debug};
+use crate::history::{Operation, StrokePointRecord, StrokeRecord};
+
+/// スクリプト実行のエラー型
+#[derive(Debug)]
+pub enum ScriptError {
+    CompileFailed(String),
+    RuntimeFailed(String),
+}
+
+impl fmt::Display for ScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScriptError::CompileFailed(msg) => write!(f, "スクリプトの構文エラー: {}", msg),
+            ScriptError::RuntimeFailed(msg) => write!(f, "スクリプトの実行エラー: {}", msg),
+        }
+    }
+}
+
+impl Error for ScriptError {}
+
+/// スクリプトの実行制限
+#[derive(Debug, Clone)]
+pub struct ScriptLimits {
+    /// 評価ステップ数の上限（無限ループ対策）
+    pub max_operations: u64,
+    pub max_call_levels: usize,
+    pub max_string_size: usize,
+    pub max_array_size: usize,
+    /// スクリプトが発行できる描画操作数の上限
+    pub max_queued_operations: usize,
+    pub max_exports: usize,
+}
+
+impl Default for ScriptLimits {
+    fn default() -> Self {
+        Self {
+            max_operations: 5_000_000,
+            max_call_levels: 32,
+            max_string_size: 64 * 1024,
+            max_array_size: 100_000,
+            max_queued_operations: 10_000,
+            max_exports: 1_000,
+        }
+    }
+}
+
+/// スクリプトが要求したレイヤーのPNG書き出し
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ExportRequest {
+    pub layer_id: String,
+    pub file_name: String,
+}
+
+/// スクリプトの実行結果
+///
+/// スクリプトはエンジンに直接触れず、操作と書き出し要求を積むだけなので、
+/// 実際の適用は呼び出し側が通常の描画コマンドと同じ経路で行う。
+#[derive(Debug, Clone, Default)]
+pub struct ScriptOutput {
+    pub operations: Vec<Operation>,
+    pub exports: Vec<ExportRequest>,
+    pub logs: Vec<String>,
+}
+
+/// スクリプトから見える文書状態
+struct ScriptContext {
+    layers: BTreeMap<String, (u32, u32)>,
+    output: ScriptOutput,
+    limits: ScriptLimits,
+}
+
+impl ScriptContext {
+    fn ensure_layer(&self, layer_id: &str) -> Result<(u32, u32), Box<EvalAltResult>> {
+        self.layers.get(layer_id).copied()
+            .ok_or_else(|| format!("レイヤーが見つかりません: {}", layer_id).into())
+    }
+
+    fn queue(&mut self, operation: Operation) -> Result<(), Box<EvalAltResult>> {
+        if self.output.operations.len() >= self.limits.max_queued_operations {
+            return Err(format!("描画操作数が上限を超えました: {}", self.limits.max_queued_operations).into());
+        }
+        self.output.operations.push(operation);
+        Ok(())
+    }
+}
+
+type SharedContext = Rc<RefCell<ScriptContext>>;
+
+/// スクリプトを実行して描画操作を収集
+pub fn run_script(
+    source: &str,
+    layers: &HashMap<String, (u32, u32)>,
+    limits: ScriptLimits,
+) -> Result<ScriptOutput, ScriptError> {
+    info!("[Scripting] スクリプト実行開始 ({} bytes, レイヤー数: {})", source.len(), layers.len());
+
+    let context = Rc::new(RefCell::new(ScriptContext {
+        layers: layers.iter().map(|(id, size)| (id.clone(), *size)).collect(),
+        output: ScriptOutput::default(),
+        limits: limits.clone(),
+    }));
+    let engine = build_engine(&context, &limits);
+
+    let ast = engine.compile(source)
+        .map_err(|e| ScriptError::CompileFailed(e.to_string()))?;
+    engine.run_ast(&ast)
+        .map_err(|e| ScriptError::RuntimeFailed(e.to_string()))?;
+    drop(engine);
+
+    let context = Rc::try_unwrap(context)
+        .map_err(|_| ScriptError::RuntimeFailed("スクリプトコンテキストが解放されていません".to_string()))?
+        .into_inner();
+    debug!("[Scripting] スクリプト実行完了: 操作 {} / 書き出し {}",
+           context.output.operations.len(), context.output.exports.len());
+    Ok(context.output)
+}
+
+/// 書き出しファイル名の検証（書き出し先ディレクトリの外に出られないようにする）
+pub fn sanitize_export_file_name(file_name: &str) -> Result<String, String> {
+    let trimmed = file_name.trim();
+    if trimmed.is_empty() || trimmed == "." || trimmed == ".." {
+        return Err(format!("無効なファイル名です: '{}'", file_name));
+    }
+    if trimmed.chars().any(|c| matches!(c, '/' | '\\' | ':') || c.is_control()) {
+        return Err(format!("ファイル名にパス区切り文字は使用できません: '{}'", file_name));
+    }
+    if trimmed.to_ascii_lowercase().ends_with(".png") {
+        Ok(trimmed.to_string())
+    } else {
+        Ok(format!("{}.png", trimmed))
+    }
+}
+
+/// サンドボックス化したスクリプトエンジンを構築
+fn build_engine(context: &SharedContext, limits: &ScriptLimits) -> Engine {
+    let mut engine = Engine::new();
+
+    // ファイルからのモジュール読み込みと動的評価を禁止
+    engine.set_max_modules(0);
+    engine.disable_symbol("eval");
+    engine.set_max_operations(limits.max_operations);
+    engine.set_max_call_levels(limits.max_call_levels);
+    engine.set_max_string_size(limits.max_string_size);
+    engine.set_max_array_size(limits.max_array_size);
+    engine.set_max_map_size(limits.max_array_size);
+
+    let ctx = context.clone();
+    engine.on_print(move |text| {
+        ctx.borrow_mut().output.logs.push(text.to_string());
+    });
+    engine.on_debug(|text, _, pos| {
+        debug!("[Scripting] debug {:?}: {}", pos, text);
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("layers", move || -> Array {
+        ctx.borrow().layers.iter().map(|(id, (width, height))| {
+            let mut layer = Map::new();
+            layer.insert("id".into(), id.clone().into());
+            layer.insert("width".into(), (*width as INT).into());
+            layer.insert("height".into(), (*height as INT).into());
+            Dynamic::from_map(layer)
+        }).collect()
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("layer_exists", move |layer_id: &str| -> bool {
+        ctx.borrow().layers.contains_key(layer_id)
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("create_layer", move |layer_id: &str, width: INT, height: INT| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        if layer_id.is_empty() {
+            return Err("レイヤーIDが空です".into());
+        }
+        if ctx.layers.contains_key(layer_id) {
+            return Err(format!("レイヤーは既に存在します: {}", layer_id).into());
+        }
+        let (width, height) = match (u32::try_from(width), u32::try_from(height)) {
+            (Ok(w), Ok(h)) if w > 0 && h > 0 => (w, h),
+            _ => return Err(format!("無効なレイヤーサイズです: {}x{}", width, height).into()),
+        };
+        ctx.queue(Operation::CreateLayer { layer_id: layer_id.to_string(), width, height })?;
+        ctx.layers.insert(layer_id.to_string(), (width, height));
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("remove_layer", move |layer_id: &str| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.ensure_layer(layer_id)?;
+        ctx.queue(Operation::RemoveLayer { layer_id: layer_id.to_string() })?;
+        ctx.layers.remove(layer_id);
+        Ok(())
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("clear_layer", move |layer_id: &str| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.ensure_layer(layer_id)?;
+        ctx.queue(Operation::ClearLayer { layer_id: layer_id.to_string() })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("fill_layer", move |layer_id: &str, color: Array| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.ensure_layer(layer_id)?;
+        let color = to_color(&color)?;
+        ctx.queue(Operation::FillLayer { layer_id: layer_id.to_string(), color })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("draw_line", move |layer_id: &str, x1: Dynamic, y1: Dynamic, x2: Dynamic, y2: Dynamic, color: Array, width: Dynamic| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.ensure_layer(layer_id)?;
+        let width = to_f32(&width)?;
+        if width <= 0.0 {
+            return Err("線幅は0より大きい値である必要があります".into());
+        }
+        ctx.queue(Operation::DrawLine {
+            layer_id: layer_id.to_string(),
+            x1: to_f32(&x1)?,
+            y1: to_f32(&y1)?,
+            x2: to_f32(&x2)?,
+            y2: to_f32(&y2)?,
+            color: to_color(&color)?,
+            width,
+        })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("draw_stroke", move |layer_id: &str, points: Array, color: Array, base_width: Dynamic| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.ensure_layer(layer_id)?;
+        let base_width = to_f32(&base_width)?;
+        if base_width <= 0.0 {
+            return Err("線幅は0より大きい値である必要があります".into());
+        }
+        let points = points.iter().map(to_stroke_point).collect::<Result<Vec<_>, _>>()?;
+        if points.len() < 2 {
+            return Err("ストロークには2点以上が必要です".into());
+        }
+        let stroke = StrokeRecord { points, color: to_color(&color)?, base_width };
+        ctx.queue(Operation::DrawStroke { layer_id: layer_id.to_string(), stroke })
+    });
+
+    let ctx = context.clone();
+    engine.register_fn("export_png", move |layer_id: &str, file_name: &str| -> Result<(), Box<EvalAltResult>> {
+        let mut ctx = ctx.borrow_mut();
+        ctx.ensure_layer(layer_id)?;
+        if ctx.output.exports.len() >= ctx.limits.max_exports {
+            return Err(format!("書き出し数が上限を超えました: {}", ctx.limits.max_exports).into());
+        }
+        let file_name = sanitize_export_file_name(file_name)?;
+        ctx.output.exports.push(ExportRequest { layer_id: layer_id.to_string(), file_name });
+        Ok(())
+    });
+
+    engine
+}
+
+/// 整数・浮動小数のどちらでも受け付ける
+fn to_f32(value: &Dynamic) -> Result<f32, Box<EvalAltResult>> {
+    if let Ok(v) = value.as_float() {
+        return Ok(v as f32);
+    }
+    if let Ok(v) = value.as_int() {
+        return Ok(v as f32);
+    }
+    Err(format!("数値が必要です: {}", value.type_name()).into())
+}
+
+fn to_color(color: &Array) -> Result<[f32; 4], Box<EvalAltResult>> {
+    if color.len() != 4 {
+        return Err(format!("色は [r, g, b, a] の4要素で指定してください (要素数: {})", color.len()).into());
+    }
+    let mut result = [0.0; 4];
+    for (channel, value) in result.iter_mut().zip(color) {
+        *channel = to_f32(value)?.clamp(0.0, 1.0);
+    }
+    Ok(result)
+}
+
+/// [x, y] / [x, y, pressure] / #{x, y, pressure} 形式の点を変換
+fn to_stroke_point(point: &Dynamic) -> Result<StrokePointRecord, Box<EvalAltResult>> {
+    if let Some(values) = point.read_lock::<Array>() {
+        if values.len() == 2 || values.len() == 3 {
+            let pressure = match values.get(2) {
+                Some(p) => to_f32(p)?,
+                None => 1.0,
+            };
+            return Ok(StrokePointRecord { x: to_f32(&values[0])?, y: to_f32(&values[1])?, pressure, time_ms: None });
+        }
+    } else if let Some(map) = point.read_lock::<Map>() {
+        if let (Some(x), Some(y)) = (map.get("x"), map.get("y")) {
+            let pressure = match map.get("pressure") {
+                Some(p) => to_f32(p)?,
+                None => 1.0,
+            };
+            return Ok(StrokePointRecord { x: to_f32(x)?, y: to_f32(y)?, pressure, time_ms: None });
+        }
+    }
+    Err("ストロークの点は [x, y, pressure] または #{x, y, pressure} で指定してください".into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(source: &str) -> Result<ScriptOutput, ScriptError> {
+        let mut layers = HashMap::new();
+        layers.insert("frame_1".to_string(), (64, 48));
+        layers.insert("frame_2".to_string(), (64, 48));
+        run_script(source, &layers, ScriptLimits::default())
+    }
+
+    #[test]
+    fn test_enumerate_layers_and_export() {
+        let output = run(r#"
+            for layer in layers() {
+                print(layer.id + ":" + layer.width);
+                export_png(layer.id, layer.id);
+            }
+        "#).unwrap();
+
+        assert_eq!(output.logs, vec!["frame_1:64", "frame_2:64"]);
+        assert_eq!(output.exports, vec![
+            ExportRequest { layer_id: "frame_1".to_string(), file_name: "frame_1.png".to_string() },
+            ExportRequest { layer_id: "frame_2".to_string(), file_name: "frame_2.png".to_string() },
+        ]);
+        assert!(output.operations.is_empty());
+    }
+
+    #[test]
+    fn test_draw_commands_are_queued_as_operations() {
+        let output = run(r#"
+            create_layer("overlay", 32, 32);
+            fill_layer("overlay", [1.0, 1.0, 1.0, 1]);
+            draw_line("overlay", 0, 0, 31.5, 31.5, [1.0, 0.0, 0.0, 1.0], 2);
+            draw_stroke("overlay", [[0, 0], #{x: 10, y: 10, pressure: 0.5}], [0.0, 0.0, 0.0, 1.0], 4.0);
+            if layer_exists("overlay") { remove_layer("frame_2"); }
+        "#).unwrap();
+
+        assert_eq!(output.operations.len(), 5);
+        assert_eq!(output.operations[0], Operation::CreateLayer {
+            layer_id: "overlay".to_string(), width: 32, height: 32,
+        });
+        match &output.operations[3] {
+            Operation::DrawStroke { stroke, .. } => {
+                assert_eq!(stroke.points[0].pressure, 1.0);
+                assert_eq!(stroke.points[1], StrokePointRecord { x: 10.0, y: 10.0, pressure: 0.5, time_ms: None });
+            }
+            other => panic!("予期しない操作: {:?}", other),
+        }
+        assert_eq!(output.operations[4], Operation::RemoveLayer { layer_id: "frame_2".to_string() });
+    }
+
+    #[test]
+    fn test_unknown_layer_is_runtime_error() {
+        let result = run(r#"clear_layer("missing");"#);
+        assert!(matches!(result, Err(ScriptError::RuntimeFailed(_))));
+
+        let result = run(r#"remove_layer("frame_1"); clear_layer("frame_1");"#);
+        assert!(matches!(result, Err(ScriptError::RuntimeFailed(_))));
+    }
+
+    #[test]
+    fn test_sandbox_limits() {
+        assert!(matches!(run("loop { }"), Err(ScriptError::RuntimeFailed(_))));
+        assert!(matches!(run(r#"import "os" as os;"#), Err(ScriptError::RuntimeFailed(_)) | Err(ScriptError::CompileFailed(_))));
+        assert!(run(r#"eval("1 + 1");"#).is_err());
+
+        let limits = ScriptLimits { max_queued_operations: 3, ..ScriptLimits::default() };
+        let result = run_script(r#"for i in 0..10 { clear_layer("frame_1"); }"#, &HashMap::from([("frame_1".to_string(), (8, 8))]), limits);
+        assert!(matches!(result, Err(ScriptError::RuntimeFailed(_))));
+    }
+
+    #[test]
+    fn test_sanitize_export_file_name() {
+        assert_eq!(sanitize_export_file_name("frame_01").unwrap(), "frame_01.png");
+        assert_eq!(sanitize_export_file_name("cel.PNG").unwrap(), "cel.PNG");
+        assert!(sanitize_export_file_name("../secret").is_err());
+        assert!(sanitize_export_file_name("dir/frame").is_err());
+        assert!(sanitize_export_file_name("C:frame").is_err());
+        assert!(sanitize_export_file_name("..").is_err());
+        assert!(sanitize_export_file_name("").is_err());
+    }
+}
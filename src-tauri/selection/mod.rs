@@ -0,0 +1,602 @@
+use crate::formats::blend_over;
+use crate::formats::mask::mask_value;
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::{Rgba, RgbaImage};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+
+/// 選択範囲のエラー型
+#[derive(Debug, PartialEq)]
+pub enum SelectionError {
+    EmptySelection,
+    InvalidMask { expected: usize, actual: usize },
+    InvalidTransform(String),
+    InvalidImage(String),
+}
+
+impl fmt::Display for SelectionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SelectionError::EmptySelection => write!(f, "選択範囲が空です"),
+            SelectionError::InvalidMask { expected, actual } => {
+                write!(f, "選択マスクのサイズが一致しません（期待値 {} / 実際 {}）", expected, actual)
+            }
+            SelectionError::InvalidTransform(msg) => write!(f, "変形が不正です: {}", msg),
+            SelectionError::InvalidImage(msg) => write!(f, "画像が不正です: {}", msg),
+        }
+    }
+}
+
+impl Error for SelectionError {}
+
+/// 選択範囲（外接矩形と、矩形内の選択度合い 0〜255 のマスク）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Selection {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    /// 行優先のマスク（None は矩形全体を選択。保存時は Base64）
+    #[serde(default, serialize_with = "serialize_mask", deserialize_with = "deserialize_mask")]
+    pub mask: Option<Vec<u8>>,
+}
+
+fn serialize_mask<S: Serializer>(mask: &Option<Vec<u8>>, serializer: S) -> Result<S::Ok, S::Error> {
+    mask.as_ref().map(|m| STANDARD.encode(m)).serialize(serializer)
+}
+
+fn deserialize_mask<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<u8>>, D::Error> {
+    Option::<String>::deserialize(deserializer)?
+        .map(|encoded| STANDARD.decode(encoded).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+impl Selection {
+    /// 矩形の選択範囲
+    pub fn rect(x: i32, y: i32, width: u32, height: u32) -> Self {
+        Self { x, y, width, height, mask: None }
+    }
+
+    pub fn validate(&self) -> Result<(), SelectionError> {
+        if self.width == 0 || self.height == 0 {
+            return Err(SelectionError::EmptySelection);
+        }
+        if let Some(mask) = &self.mask {
+            let expected = self.width as usize * self.height as usize;
+            if mask.len() != expected {
+                return Err(SelectionError::InvalidMask { expected, actual: mask.len() });
+            }
+        }
+        Ok(())
+    }
+
+    /// キャンバス座標の選択度合い（0〜255、範囲外は 0）
+    pub fn coverage(&self, x: i64, y: i64) -> u8 {
+        let (lx, ly) = (x - self.x as i64, y - self.y as i64);
+        if lx < 0 || ly < 0 || lx >= self.width as i64 || ly >= self.height as i64 {
+            return 0;
+        }
+        match &self.mask {
+            Some(mask) => mask[ly as usize * self.width as usize + lx as usize],
+            None => 255,
+        }
+    }
+
+    /// 中心（変形の基準点）
+    pub fn center(&self) -> (f32, f32) {
+        (self.x as f32 + self.width as f32 / 2.0, self.y as f32 + self.height as f32 / 2.0)
+    }
+
+    /// 座標系を拡大縮小した選択範囲（マスクは最近傍補間）
+    pub fn rescaled(&self, (sx, sy): (f32, f32)) -> Self {
+        let width = ((self.width as f32 * sx).round() as u32).max(1);
+        let height = ((self.height as f32 * sy).round() as u32).max(1);
+        let mask = self.mask.as_ref().map(|mask| {
+            (0..height).flat_map(|y| (0..width).map(move |x| (x, y)))
+                .map(|(x, y)| {
+                    let mx = ((x as f32 / sx) as u32).min(self.width - 1);
+                    let my = ((y as f32 / sy) as u32).min(self.height - 1);
+                    mask[(my * self.width + mx) as usize]
+                })
+                .collect()
+        });
+        Self {
+            x: (self.x as f32 * sx).round() as i32,
+            y: (self.y as f32 * sy).round() as i32,
+            width,
+            height,
+            mask,
+        }
+    }
+
+    /// 選択範囲の画素を切り出す（外接矩形の大きさ。アルファに選択度合いを掛ける）
+    pub fn extract(&self, image: &RgbaImage) -> RgbaImage {
+        RgbaImage::from_fn(self.width, self.height, |lx, ly| {
+            let (x, y) = (self.x as i64 + lx as i64, self.y as i64 + ly as i64);
+            if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+                return Rgba([0, 0, 0, 0]);
+            }
+            let mut pixel = *image.get_pixel(x as u32, y as u32);
+            pixel[3] = scale_alpha(pixel[3], self.coverage(x, y));
+            pixel
+        })
+    }
+
+    /// 選択範囲の画素を消す（選択度合いに応じてアルファを下げる）
+    pub fn erase(&self, image: &mut RgbaImage) {
+        for (x, y, pixel) in image.enumerate_pixels_mut() {
+            let coverage = self.coverage(x as i64, y as i64);
+            if coverage > 0 {
+                pixel[3] = scale_alpha(pixel[3], 255 - coverage);
+                if pixel[3] == 0 {
+                    *pixel = Rgba([0, 0, 0, 0]);
+                }
+            }
+        }
+    }
+
+    /// クイックマスク用の画像（白で選択度合いを不透明度にする。選択外は透明）
+    pub fn to_mask_image(&self, width: u32, height: u32) -> RgbaImage {
+        RgbaImage::from_fn(width, height, |x, y| Rgba([255, 255, 255, self.coverage(x as i64, y as i64)]))
+    }
+
+    /// クイックマスクの画像から選択範囲を作る（明るさと不透明度の積。何も塗られていなければ None）
+    pub fn from_mask_image(image: &RgbaImage) -> Option<Self> {
+        let coverage = |x: u32, y: u32| (mask_value(image.get_pixel(x, y).0) * 255.0).round() as u8;
+        let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+        for (x, y, _) in image.enumerate_pixels() {
+            if coverage(x, y) > 0 {
+                (min_x, min_y) = (min_x.min(x), min_y.min(y));
+                (max_x, max_y) = (max_x.max(x), max_y.max(y));
+            }
+        }
+        if min_x > max_x || min_y > max_y {
+            return None;
+        }
+
+        let (width, height) = (max_x - min_x + 1, max_y - min_y + 1);
+        let mask: Vec<u8> = (min_y..=max_y)
+            .flat_map(|y| (min_x..=max_x).map(move |x| (x, y)))
+            .map(|(x, y)| coverage(x, y))
+            .collect();
+        // 外接矩形がすべて選択されていれば矩形の選択範囲
+        let mask = (!mask.iter().all(|&value| value == 255)).then_some(mask);
+        Some(Self { x: min_x as i32, y: min_y as i32, width, height, mask })
+    }
+
+    /// 投げ縄の多角形から選択範囲を作る（中心が内側にあるピクセルを選択。空なら None）
+    pub fn from_polygon(polygon: &[(f32, f32)]) -> Option<Self> {
+        if polygon.len() < 3 || !polygon.iter().all(|(x, y)| x.is_finite() && y.is_finite()) {
+            return None;
+        }
+        let min_x = polygon.iter().map(|p| p.0).fold(f32::INFINITY, f32::min).floor() as i32;
+        let min_y = polygon.iter().map(|p| p.1).fold(f32::INFINITY, f32::min).floor() as i32;
+        let max_x = polygon.iter().map(|p| p.0).fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+        let max_y = polygon.iter().map(|p| p.1).fold(f32::NEG_INFINITY, f32::max).ceil() as i32;
+        let (width, height) = ((max_x - min_x) as u32, (max_y - min_y) as u32);
+        if width == 0 || height == 0 {
+            return None;
+        }
+        let mask: Vec<u8> = (0..height)
+            .flat_map(|y| (0..width).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let (px, py) = (min_x as f32 + x as f32 + 0.5, min_y as f32 + y as f32 + 0.5);
+                if polygon_contains(polygon, px, py) { 255 } else { 0 }
+            })
+            .collect();
+        if mask.iter().all(|&value| value == 0) {
+            return None;
+        }
+        Some(Self { x: min_x, y: min_y, width, height, mask: Some(mask) })
+    }
+}
+
+/// 点が多角形の内側にあるか（偶奇規則。自己交差した投げ縄も扱える）
+pub fn polygon_contains(polygon: &[(f32, f32)], x: f32, y: f32) -> bool {
+    let mut inside = false;
+    for (index, &(ax, ay)) in polygon.iter().enumerate() {
+        let (bx, by) = polygon[(index + 1) % polygon.len()];
+        if (ay > y) != (by > y) && x < ax + (y - ay) * (bx - ax) / (by - ay) {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// 線分 `a`→`b` が多角形の辺と交わる位置（線分上の 0〜1 の割合。昇順）
+pub fn polygon_crossings(polygon: &[(f32, f32)], a: (f32, f32), b: (f32, f32)) -> Vec<f32> {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let mut crossings: Vec<f32> = (0..polygon.len())
+        .filter_map(|index| {
+            let (p, q) = (polygon[index], polygon[(index + 1) % polygon.len()]);
+            let (ex, ey) = (q.0 - p.0, q.1 - p.1);
+            let denominator = dx * ey - dy * ex;
+            if denominator == 0.0 {
+                return None;
+            }
+            let t = ((p.0 - a.0) * ey - (p.1 - a.1) * ex) / denominator;
+            let u = ((p.0 - a.0) * dy - (p.1 - a.1) * dx) / denominator;
+            ((0.0..=1.0).contains(&t) && (0.0..=1.0).contains(&u)).then_some(t)
+        })
+        .collect();
+    crossings.sort_by(f32::total_cmp);
+    crossings
+}
+
+/// クイックマスクの表示（選択されていない部分に色を重ねる。マスクの範囲外は選択外として扱う）
+pub fn render_quick_mask(composite: &mut RgbaImage, mask: &RgbaImage, color: [u8; 4]) {
+    for (x, y, pixel) in composite.enumerate_pixels_mut() {
+        let selected = if x < mask.width() && y < mask.height() {
+            mask_value(mask.get_pixel(x, y).0)
+        } else {
+            0.0
+        };
+        let alpha = (color[3] as f32 * (1.0 - selected)).round() as u8;
+        *pixel = blend_over(*pixel, Rgba([color[0], color[1], color[2], alpha]));
+    }
+}
+
+fn scale_alpha(alpha: u8, coverage: u8) -> u8 {
+    ((alpha as u32 * coverage as u32 + 127) / 255) as u8
+}
+
+/// 変形時の補間方法
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Interpolation {
+    /// 最近傍（ドット絵向け）
+    Nearest,
+    #[default]
+    Bilinear,
+}
+
+/// 選択範囲の変形（選択範囲の中心を基準に 拡大縮小 → 回転 → 移動）
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SelectionTransform {
+    pub translate_x: f32,
+    pub translate_y: f32,
+    pub scale_x: f32,
+    pub scale_y: f32,
+    /// 時計回りの回転角（度）
+    pub rotation_degrees: f32,
+    pub interpolation: Interpolation,
+}
+
+impl Default for SelectionTransform {
+    fn default() -> Self {
+        Self {
+            translate_x: 0.0,
+            translate_y: 0.0,
+            scale_x: 1.0,
+            scale_y: 1.0,
+            rotation_degrees: 0.0,
+            interpolation: Interpolation::default(),
+        }
+    }
+}
+
+impl SelectionTransform {
+    pub fn validate(&self) -> Result<(), SelectionError> {
+        let values = [self.translate_x, self.translate_y, self.scale_x, self.scale_y, self.rotation_degrees];
+        if values.iter().any(|v| !v.is_finite()) {
+            return Err(SelectionError::InvalidTransform("数値が不正です".to_string()));
+        }
+        if self.scale_x.abs() < 1e-3 || self.scale_y.abs() < 1e-3 {
+            return Err(SelectionError::InvalidTransform(format!("拡大率 {} x {}", self.scale_x, self.scale_y)));
+        }
+        Ok(())
+    }
+
+    /// 選択範囲内の座標を変形後の座標に移す
+    fn forward(&self, pivot: (f32, f32), (x, y): (f32, f32)) -> (f32, f32) {
+        let (sin, cos) = self.rotation_degrees.to_radians().sin_cos();
+        let (dx, dy) = ((x - pivot.0) * self.scale_x, (y - pivot.1) * self.scale_y);
+        (
+            pivot.0 + dx * cos - dy * sin + self.translate_x,
+            pivot.1 + dx * sin + dy * cos + self.translate_y,
+        )
+    }
+
+    /// 変形後の座標から元の座標を求める
+    fn inverse(&self, pivot: (f32, f32), (x, y): (f32, f32)) -> (f32, f32) {
+        let (sin, cos) = self.rotation_degrees.to_radians().sin_cos();
+        let (dx, dy) = (x - pivot.0 - self.translate_x, y - pivot.1 - self.translate_y);
+        (
+            pivot.0 + (dx * cos + dy * sin) / self.scale_x,
+            pivot.1 + (-dx * sin + dy * cos) / self.scale_y,
+        )
+    }
+
+    /// 変形後の選択範囲を覆う矩形 (x, y, width, height)
+    pub fn bounds(&self, selection: &Selection) -> (i32, i32, u32, u32) {
+        let pivot = selection.center();
+        let (x0, y0) = (selection.x as f32, selection.y as f32);
+        let (x1, y1) = (x0 + selection.width as f32, y0 + selection.height as f32);
+        // 90度回転などで生じる浮動小数点の誤差で矩形が 1px 広がらないように丸める
+        let snap = |v: f32| (v * 1000.0).round() / 1000.0;
+        let corners = [(x0, y0), (x1, y0), (x0, y1), (x1, y1)]
+            .map(|p| self.forward(pivot, p))
+            .map(|(x, y)| (snap(x), snap(y)));
+
+        let left = corners.iter().map(|c| c.0).fold(f32::INFINITY, f32::min).floor();
+        let top = corners.iter().map(|c| c.1).fold(f32::INFINITY, f32::min).floor();
+        let right = corners.iter().map(|c| c.0).fold(f32::NEG_INFINITY, f32::max).ceil();
+        let bottom = corners.iter().map(|c| c.1).fold(f32::NEG_INFINITY, f32::max).ceil();
+        (left as i32, top as i32, (right - left) as u32, (bottom - top) as u32)
+    }
+}
+
+/// 選択範囲の画素を変形して元の画像に合成した結果を返す（元の画像は変更しない）
+///
+/// 選択範囲の画素は元の位置から取り除かれ、変形した位置に通常合成で重ねられる。
+pub fn apply_selection_transform(
+    image: &RgbaImage,
+    selection: &Selection,
+    transform: &SelectionTransform,
+) -> RgbaImage {
+    let floating = selection.extract(image);
+    let mut result = image.clone();
+    selection.erase(&mut result);
+
+    let pivot = selection.center();
+    let (bx, by, bw, bh) = transform.bounds(selection);
+    let left = bx.max(0) as u32;
+    let top = by.max(0) as u32;
+    let right = (bx as i64 + bw as i64).clamp(0, image.width() as i64) as u32;
+    let bottom = (by as i64 + bh as i64).clamp(0, image.height() as i64) as u32;
+
+    for y in top..bottom {
+        for x in left..right {
+            // ピクセル中心で逆変換し、切り出した画素上の座標に直す
+            let (sx, sy) = transform.inverse(pivot, (x as f32 + 0.5, y as f32 + 0.5));
+            let local = (sx - selection.x as f32 - 0.5, sy - selection.y as f32 - 0.5);
+            let src = match transform.interpolation {
+                Interpolation::Nearest => sample_nearest(&floating, local),
+                Interpolation::Bilinear => sample_bilinear(&floating, local),
+            };
+            if src[3] > 0 {
+                let dst = result.get_pixel_mut(x, y);
+                *dst = blend_over(*dst, src);
+            }
+        }
+    }
+    result
+}
+
+/// クリップボードの内容（切り出した画素とコピー元の位置）
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipboardImage {
+    /// コピー元の外接矩形の左上（その場に貼り付けるときの位置）
+    pub x: i32,
+    pub y: i32,
+    pub image: RgbaImage,
+}
+
+impl ClipboardImage {
+    /// 選択範囲の画素をコピー
+    pub fn from_selection(image: &RgbaImage, selection: &Selection) -> Self {
+        Self { x: selection.x, y: selection.y, image: selection.extract(image) }
+    }
+
+    /// PNG から読み込む（外部の画像は位置を持たないため原点に置く）
+    pub fn from_png(data: &[u8]) -> Result<Self, SelectionError> {
+        let image = image::load_from_memory_with_format(data, image::ImageFormat::Png)
+            .map_err(|e| SelectionError::InvalidImage(e.to_string()))?
+            .to_rgba8();
+        if image.width() == 0 || image.height() == 0 {
+            return Err(SelectionError::EmptySelection);
+        }
+        Ok(Self { x: 0, y: 0, image })
+    }
+
+    pub fn to_png(&self) -> Result<Vec<u8>, SelectionError> {
+        let mut png = std::io::Cursor::new(Vec::new());
+        self.image.write_to(&mut png, image::ImageFormat::Png)
+            .map_err(|e| SelectionError::InvalidImage(e.to_string()))?;
+        Ok(png.into_inner())
+    }
+
+    /// 画像に重ねたとき変化する範囲を `(x, y, 合成後の画素)` で返す（重ならなければ None）
+    pub fn composite_onto(&self, target: &RgbaImage) -> Option<(u32, u32, RgbaImage)> {
+        let left = self.x.max(0) as u32;
+        let top = self.y.max(0) as u32;
+        let right = (self.x as i64 + self.image.width() as i64).clamp(0, target.width() as i64) as u32;
+        let bottom = (self.y as i64 + self.image.height() as i64).clamp(0, target.height() as i64) as u32;
+        if left >= right || top >= bottom {
+            return None;
+        }
+
+        let region = RgbaImage::from_fn(right - left, bottom - top, |rx, ry| {
+            let (x, y) = (left + rx, top + ry);
+            let src = *self.image.get_pixel(
+                (x as i64 - self.x as i64) as u32,
+                (y as i64 - self.y as i64) as u32,
+            );
+            blend_over(*target.get_pixel(x, y), src)
+        });
+        Some((left, top, region))
+    }
+}
+
+fn pixel_or_transparent(image: &RgbaImage, x: i64, y: i64) -> Rgba<u8> {
+    if x < 0 || y < 0 || x >= image.width() as i64 || y >= image.height() as i64 {
+        Rgba([0, 0, 0, 0])
+    } else {
+        *image.get_pixel(x as u32, y as u32)
+    }
+}
+
+fn sample_nearest(image: &RgbaImage, (x, y): (f32, f32)) -> Rgba<u8> {
+    pixel_or_transparent(image, x.round() as i64, y.round() as i64)
+}
+
+/// アルファで重み付けした双線形補間（透明部分の色が縁に滲まないようにする）
+fn sample_bilinear(image: &RgbaImage, (x, y): (f32, f32)) -> Rgba<u8> {
+    let (x0, y0) = (x.floor(), y.floor());
+    let (fx, fy) = (x - x0, y - y0);
+    let (ix, iy) = (x0 as i64, y0 as i64);
+
+    let mut color = [0.0f32; 3];
+    let mut alpha = 0.0f32;
+    for (dx, dy, weight) in [(0, 0, (1.0 - fx) * (1.0 - fy)), (1, 0, fx * (1.0 - fy)), (0, 1, (1.0 - fx) * fy), (1, 1, fx * fy)] {
+        if weight <= 0.0 {
+            continue;
+        }
+        let p = pixel_or_transparent(image, ix + dx, iy + dy);
+        let a = p[3] as f32 * weight;
+        for c in 0..3 {
+            color[c] += p[c] as f32 * a;
+        }
+        alpha += a;
+    }
+    if alpha <= 0.0 {
+        return Rgba([0, 0, 0, 0]);
+    }
+    Rgba([
+        (color[0] / alpha).round() as u8,
+        (color[1] / alpha).round() as u8,
+        (color[2] / alpha).round() as u8,
+        alpha.round().clamp(0.0, 255.0) as u8,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+    const CLEAR: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+    fn test_image() -> RgbaImage {
+        // 左上 2x2 が赤、それ以外は青の 8x8
+        RgbaImage::from_fn(8, 8, |x, y| if x < 2 && y < 2 { RED } else { BLUE })
+    }
+
+    #[test]
+    fn test_identity_keeps_image() {
+        let image = test_image();
+        let selection = Selection::rect(0, 0, 4, 4);
+        for interpolation in [Interpolation::Nearest, Interpolation::Bilinear] {
+            let transform = SelectionTransform { interpolation, ..SelectionTransform::default() };
+            assert_eq!(apply_selection_transform(&image, &selection, &transform), image);
+        }
+    }
+
+    #[test]
+    fn test_move_leaves_hole() {
+        let image = test_image();
+        let selection = Selection::rect(0, 0, 2, 2);
+        let transform = SelectionTransform { translate_x: 4.0, translate_y: 3.0, ..SelectionTransform::default() };
+        let result = apply_selection_transform(&image, &selection, &transform);
+
+        assert_eq!(result.get_pixel(0, 0), &CLEAR);
+        assert_eq!(result.get_pixel(4, 3), &RED);
+        assert_eq!(result.get_pixel(5, 4), &RED);
+        assert_eq!(result.get_pixel(6, 3), &BLUE);
+        assert_eq!(transform.bounds(&selection), (4, 3, 2, 2));
+    }
+
+    #[test]
+    fn test_rotate_and_scale() {
+        let image = RgbaImage::from_fn(8, 8, |x, _| if x < 4 { RED } else { CLEAR });
+        let selection = Selection::rect(0, 0, 4, 2);
+
+        // 中心 (2, 1) を基準に 90 度回転すると 2x4 の縦長になる
+        let rotate = SelectionTransform {
+            rotation_degrees: 90.0,
+            interpolation: Interpolation::Nearest,
+            ..SelectionTransform::default()
+        };
+        assert_eq!(rotate.bounds(&selection), (1, -1, 2, 4));
+        let rotated = apply_selection_transform(&image, &selection, &rotate);
+        assert_eq!(rotated.get_pixel(1, 2), &RED);
+        assert_eq!(rotated.get_pixel(0, 0), &CLEAR);
+        assert_eq!(rotated.get_pixel(3, 0), &CLEAR);
+
+        let scale = SelectionTransform { scale_x: 2.0, scale_y: 2.0, rotation_degrees: 0.0, ..rotate };
+        assert_eq!(scale.bounds(&selection), (-2, -1, 8, 4));
+        assert!(SelectionTransform { scale_x: 0.0, ..scale }.validate().is_err());
+    }
+
+    #[test]
+    fn test_mask_selection() {
+        let image = test_image();
+        let mut mask = vec![0; 4];
+        mask[0] = 255;
+        let selection = Selection { x: 0, y: 0, width: 2, height: 2, mask: Some(mask) };
+        assert!(selection.validate().is_ok());
+        assert!(Selection { mask: Some(vec![0; 3]), ..selection.clone() }.validate().is_err());
+
+        let floating = selection.extract(&image);
+        assert_eq!(floating.get_pixel(0, 0), &RED);
+        assert_eq!(floating.get_pixel(1, 0)[3], 0);
+
+        let json = serde_json::to_string(&selection).unwrap();
+        assert_eq!(serde_json::from_str::<Selection>(&json).unwrap(), selection);
+
+        let rescaled = selection.rescaled((2.0, 2.0));
+        assert_eq!((rescaled.width, rescaled.height), (4, 4));
+        assert_eq!(rescaled.coverage(1, 1), 255);
+        assert_eq!(rescaled.coverage(2, 0), 0);
+    }
+
+    #[test]
+    fn test_clipboard_composite_and_png() {
+        let clipboard = ClipboardImage::from_selection(&test_image(), &Selection::rect(-1, 1, 3, 2));
+        assert_eq!(clipboard.image.get_pixel(0, 0), &CLEAR);
+        assert_eq!(clipboard.image.get_pixel(1, 0), &RED);
+
+        // 範囲外の部分は切り捨て、透明な部分は下の画素を残す
+        let target = RgbaImage::from_pixel(4, 4, BLUE);
+        let (x, y, region) = clipboard.composite_onto(&target).unwrap();
+        assert_eq!((x, y, region.dimensions()), (0, 1, (2, 2)));
+        assert_eq!(region.get_pixel(0, 0), &RED);
+        assert_eq!(region.get_pixel(1, 1), &BLUE);
+        assert!(ClipboardImage { x: 4, ..clipboard.clone() }.composite_onto(&target).is_none());
+
+        let decoded = ClipboardImage::from_png(&clipboard.to_png().unwrap()).unwrap();
+        assert_eq!(decoded.image, clipboard.image);
+        assert!(ClipboardImage::from_png(b"not png").is_err());
+    }
+
+    #[test]
+    fn test_quick_mask_round_trip() {
+        let mut mask = vec![255; 6];
+        mask[0] = 0;
+        let selection = Selection { x: 1, y: 1, width: 3, height: 2, mask: Some(mask) };
+        let image = selection.to_mask_image(5, 4);
+        assert_eq!(Selection::from_mask_image(&image), Some(selection));
+
+        // 外接矩形がすべて選択されていれば矩形、何もなければ None
+        let image = Selection::rect(-1, 2, 3, 5).to_mask_image(4, 4);
+        assert_eq!(Selection::from_mask_image(&image), Some(Selection::rect(0, 2, 2, 2)));
+        assert_eq!(Selection::from_mask_image(&RgbaImage::new(4, 4)), None);
+
+        // 選択されていない部分だけに色が重なる
+        let mut composite = RgbaImage::from_pixel(5, 4, BLUE);
+        render_quick_mask(&mut composite, &image, [255, 0, 0, 128]);
+        assert_eq!(composite.get_pixel(0, 2), &BLUE);
+        assert_eq!(composite.get_pixel(0, 0).0, [128, 0, 127, 255]);
+        assert_eq!(composite.get_pixel(4, 3).0, [128, 0, 127, 255]);
+    }
+
+    #[test]
+    fn test_polygon_selection() {
+        // 右下が欠けた L 字の投げ縄
+        let polygon = [(0.0, 0.0), (4.0, 0.0), (4.0, 2.0), (2.0, 2.0), (2.0, 4.0), (0.0, 4.0)];
+        let selection = Selection::from_polygon(&polygon).unwrap();
+        assert_eq!((selection.x, selection.y, selection.width, selection.height), (0, 0, 4, 4));
+        assert_eq!(selection.coverage(3, 1), 255);
+        assert_eq!(selection.coverage(3, 3), 0);
+        assert_eq!(Selection::from_polygon(&polygon[..2]), None);
+
+        // 凹んだ部分を横切る線分は辺と2回交わる
+        assert_eq!(polygon_crossings(&polygon, (3.0, 3.0), (3.0, 1.0)), vec![0.5]);
+        assert_eq!(polygon_crossings(&polygon, (-1.0, 3.0), (5.0, 3.0)), vec![1.0 / 6.0, 0.5]);
+        assert!(polygon_contains(&polygon, 1.0, 3.0));
+        assert!(!polygon_contains(&polygon, 3.0, 3.0));
+    }
+}
@@ -0,0 +1,249 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::error::Error;
+use std::fmt;
+use std::path::Path;
+use log::{info, debug};
+use crate::brush::MAX_BRUSH_SIZE;
+use crate::drawing_engine::{GpuPreference, MaintenanceSettings, OverlaySettings, DEFAULT_VERTEX_LIMIT, MAX_VERTEX_LIMIT, MIN_VERTEX_LIMIT};
+use crate::formats::presets::{validate_presets, ExportPreset};
+use crate::memory::MemoryConfig;
+use crate::shortcuts::ShortcutMap;
+use crate::tablet::TabletSettings;
+
+/// 設定ファイルのバージョン
+pub const SETTINGS_VERSION: u32 = 1;
+
+/// 設定ファイル名（アプリの設定ディレクトリ内）
+pub const SETTINGS_FILE_NAME: &str = "settings.json";
+
+/// 設定のエラー型
+#[derive(Debug)]
+pub enum SettingsError {
+    Io(String),
+    ParseFailed(String),
+    UnsupportedVersion(u32),
+    InvalidValue(String),
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Io(msg) => write!(f, "設定ファイルの読み書きに失敗しました: {}", msg),
+            SettingsError::ParseFailed(msg) => write!(f, "設定の解析に失敗しました: {}", msg),
+            SettingsError::UnsupportedVersion(version) => write!(f, "未対応の設定バージョンです: {}", version),
+            SettingsError::InvalidValue(msg) => write!(f, "設定値が不正です: {}", msg),
+        }
+    }
+}
+
+impl Error for SettingsError {}
+
+/// 新規ストロークに使うブラシの既定値
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BrushDefaults {
+    /// ブラシID（`パックID/ブラシID`、None は標準の丸ブラシ）
+    pub brush_id: Option<String>,
+    /// RGBA（0.0〜1.0）
+    pub color: [f32; 4],
+    /// 線幅（ピクセル）
+    pub width: f32,
+}
+
+impl Default for BrushDefaults {
+    fn default() -> Self {
+        Self {
+            brush_id: None,
+            color: [0.0, 0.0, 0.0, 1.0],
+            width: 4.0,
+        }
+    }
+}
+
+/// アプリケーション設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AppSettings {
+    pub version: u32,
+    /// 自動保存の間隔（秒、0 で無効）
+    pub autosave_interval_secs: u32,
+    /// 使用するGPU
+    pub gpu: GpuPreference,
+    pub brush: BrushDefaults,
+    /// メモリ予算と監視の設定
+    pub memory: MemoryConfig,
+    /// 連続してアンドゥできる最大回数（0 で無制限）
+    pub undo_depth: usize,
+    /// キーボードショートカットの割り当て
+    pub shortcuts: ShortcutMap,
+    /// ペンタブレットの筆圧カーブ
+    pub tablet: TabletSettings,
+    /// キャンバス表示のオーバーレイ（市松模様・ピクセルグリッド・枠線）
+    pub overlay: OverlaySettings,
+    /// ストローク描画の頂点バッファの上限（頂点数。超えるストロークは複数回に分けて描画する）
+    pub stroke_vertex_limit: usize,
+    /// 名前付きの書き出しプリセット
+    pub export_presets: Vec<ExportPreset>,
+    /// 性能診断のためにセッション中のフレーム時間を記録する（診断レポートの作成に必要。外部には送信しない）
+    pub collect_diagnostics: bool,
+    /// 描画が途切れている間に行うメンテナンス（縮小画像の更新・テクスチャプールの整理など）
+    pub maintenance: MaintenanceSettings,
+}
+
+impl Default for AppSettings {
+    fn default() -> Self {
+        Self {
+            version: SETTINGS_VERSION,
+            autosave_interval_secs: 300,
+            gpu: GpuPreference::default(),
+            brush: BrushDefaults::default(),
+            memory: MemoryConfig::default(),
+            undo_depth: 0,
+            shortcuts: ShortcutMap::default(),
+            tablet: TabletSettings::default(),
+            overlay: OverlaySettings::default(),
+            stroke_vertex_limit: DEFAULT_VERTEX_LIMIT,
+            export_presets: Vec::new(),
+            collect_diagnostics: false,
+            maintenance: MaintenanceSettings::default(),
+        }
+    }
+}
+
+impl AppSettings {
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        if self.version > SETTINGS_VERSION {
+            return Err(SettingsError::UnsupportedVersion(self.version));
+        }
+        if !(self.brush.width > 0.0 && self.brush.width <= MAX_BRUSH_SIZE) {
+            return Err(SettingsError::InvalidValue(format!("ブラシの線幅: {}", self.brush.width)));
+        }
+        if self.brush.color.iter().any(|c| !(0.0..=1.0).contains(c)) {
+            return Err(SettingsError::InvalidValue(format!("ブラシの色: {:?}", self.brush.color)));
+        }
+        self.memory.validate().map_err(|e| SettingsError::InvalidValue(e.to_string()))?;
+        self.overlay.validate().map_err(SettingsError::InvalidValue)?;
+        if !(MIN_VERTEX_LIMIT..=MAX_VERTEX_LIMIT).contains(&self.stroke_vertex_limit) {
+            return Err(SettingsError::InvalidValue(format!(
+                "頂点バッファの上限は {}〜{} で指定してください: {}",
+                MIN_VERTEX_LIMIT, MAX_VERTEX_LIMIT, self.stroke_vertex_limit
+            )));
+        }
+        self.tablet.validate().map_err(|e| SettingsError::InvalidValue(e.to_string()))?;
+        self.maintenance.validate().map_err(SettingsError::InvalidValue)?;
+        validate_presets(&self.export_presets).map_err(|e| SettingsError::InvalidValue(e.to_string()))?;
+        if let Some(conflict) = self.shortcuts.conflicts().first() {
+            return Err(SettingsError::InvalidValue(format!(
+                "ショートカット {} が重複しています: {:?}", conflict.chord, conflict.actions
+            )));
+        }
+        Ok(())
+    }
+
+    /// 部分的なJSONを現在の設定に重ねた新しい設定を作る（オブジェクトは再帰的にマージ）
+    pub fn merged(&self, patch: &Value) -> Result<Self, SettingsError> {
+        if !patch.is_object() {
+            return Err(SettingsError::ParseFailed("設定の変更内容はオブジェクトである必要があります".to_string()));
+        }
+        let mut value = serde_json::to_value(self)
+            .map_err(|e| SettingsError::ParseFailed(e.to_string()))?;
+        merge_json(&mut value, patch);
+        let settings: AppSettings = serde_json::from_value(value)
+            .map_err(|e| SettingsError::ParseFailed(e.to_string()))?;
+        settings.validate()?;
+        Ok(settings)
+    }
+
+    /// 設定ファイルを読み込む（ファイルがなければ既定値）
+    pub fn load(path: &Path) -> Result<Self, SettingsError> {
+        let data = match std::fs::read(path) {
+            Ok(data) => data,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                debug!("[Settings] 設定ファイルがないため既定値を使用: {}", path.display());
+                return Ok(Self::default());
+            }
+            Err(e) => return Err(SettingsError::Io(e.to_string())),
+        };
+        let settings: AppSettings = serde_json::from_slice(&data)
+            .map_err(|e| SettingsError::ParseFailed(e.to_string()))?;
+        settings.validate()?;
+        info!("[Settings] 設定を読み込みました: {}", path.display());
+        Ok(settings)
+    }
+
+    /// 設定ファイルに保存（一時ファイルに書いてから置き換える）
+    pub fn save(&self, path: &Path) -> Result<(), SettingsError> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|e| SettingsError::Io(e.to_string()))?;
+        }
+        let data = serde_json::to_vec_pretty(self)
+            .map_err(|e| SettingsError::ParseFailed(e.to_string()))?;
+        let temp_path = path.with_extension("json.tmp");
+        std::fs::write(&temp_path, data).map_err(|e| SettingsError::Io(e.to_string()))?;
+        std::fs::rename(&temp_path, path).map_err(|e| SettingsError::Io(e.to_string()))?;
+        debug!("[Settings] 設定を保存しました: {}", path.display());
+        Ok(())
+    }
+}
+
+fn merge_json(target: &mut Value, patch: &Value) {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                match target.get_mut(key) {
+                    Some(existing) => merge_json(existing, value),
+                    None => {
+                        target.insert(key.clone(), value.clone());
+                    }
+                }
+            }
+        }
+        (target, patch) => *target = patch.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_merge_partial_settings() {
+        let settings = AppSettings::default();
+        let merged = settings.merged(&json!({
+            "undo_depth": 50,
+            "brush": { "width": 12.0 },
+            "gpu": { "power_preference": "low_power" },
+        })).unwrap();
+
+        assert_eq!(merged.undo_depth, 50);
+        assert_eq!(merged.brush.width, 12.0);
+        assert_eq!(merged.brush.color, settings.brush.color);
+        assert_eq!(merged.gpu.power_preference, crate::drawing_engine::GpuPowerPreference::LowPower);
+        assert_eq!(merged.memory, settings.memory);
+
+        assert!(settings.merged(&json!({ "brush": { "width": -1.0 } })).is_err());
+        assert!(settings.merged(&json!({ "undo_depth": "many" })).is_err());
+        assert!(settings.merged(&json!(3)).is_err());
+        assert!(settings.merged(&json!({ "stroke_vertex_limit": 10 })).is_err());
+        // 既定の max_buffer_size（256 MiB）に収まらない上限は受け付けない
+        assert!(settings.merged(&json!({ "stroke_vertex_limit": 16_777_216 })).is_err());
+        assert!(settings.merged(&json!({ "shortcuts": { "redo": ["Ctrl+Z"] } })).is_err());
+        assert!(settings.merged(&json!({ "export_presets": [{ "name": "web" }, { "name": "web" }] })).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("config").join(SETTINGS_FILE_NAME);
+        assert_eq!(AppSettings::load(&path).unwrap(), AppSettings::default());
+
+        let settings = AppSettings { autosave_interval_secs: 60, ..AppSettings::default() };
+        settings.save(&path).unwrap();
+        assert_eq!(AppSettings::load(&path).unwrap(), settings);
+
+        std::fs::write(&path, r#"{"version": 99}"#).unwrap();
+        assert!(matches!(AppSettings::load(&path), Err(SettingsError::UnsupportedVersion(99))));
+    }
+}
@@ -0,0 +1,202 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::error::Error;
+use std::fmt;
+use log::debug;
+
+/// 他のモジュールを取り込む指示（行頭に `#import color` のように書く）
+const IMPORT_DIRECTIVE: &str = "#import";
+
+/// シェーダーの組み立てのエラー型
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ShaderError {
+    /// 存在しないモジュールを取り込もうとした
+    UnknownModule { name: String, line: usize },
+    /// `#import` の書式が不正
+    InvalidDirective { line: usize, text: String },
+    /// モジュールが循環して取り込まれている（取り込みの経路）
+    ImportCycle(Vec<String>),
+}
+
+impl fmt::Display for ShaderError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShaderError::UnknownModule { name, line } => {
+                write!(f, "{}行目: シェーダーモジュールが見つかりません: {}", line, name)
+            }
+            ShaderError::InvalidDirective { line, text } => {
+                write!(f, "{}行目: #import の書式が不正です: {}", line, text)
+            }
+            ShaderError::ImportCycle(path) => {
+                write!(f, "シェーダーモジュールが循環して取り込まれています: {}", path.join(" -> "))
+            }
+        }
+    }
+}
+
+impl Error for ShaderError {}
+
+/// WGSL の共通モジュール集
+///
+/// 各シェーダーは `#import <モジュール名>` で共通の関数を取り込む。取り込みは単純な展開で、
+/// 同じモジュールは何度指定されても最初の1回だけ展開される（モジュール同士の取り込みも可）。
+/// wgpu に依存しない文字列処理だけなので、Tauri 以外のビルド（WASM など）からも使える。
+#[derive(Debug, Clone)]
+pub struct ShaderLibrary {
+    modules: HashMap<String, Cow<'static, str>>,
+}
+
+impl ShaderLibrary {
+    /// モジュールを持たないライブラリ
+    pub fn empty() -> Self {
+        Self { modules: HashMap::new() }
+    }
+
+    /// 組み込みのモジュールを登録したライブラリ
+    pub fn builtin() -> Self {
+        let mut library = Self::empty();
+        library.add("color", COLOR_MODULE);
+        library.add("coords", COORDS_MODULE);
+        library.add("fullscreen", FULLSCREEN_MODULE);
+        library
+    }
+
+    /// モジュールを追加（同じ名前のモジュールは置き換える）
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<Cow<'static, str>>) {
+        self.modules.insert(name.into(), source.into());
+    }
+
+    pub fn module(&self, name: &str) -> Option<&str> {
+        self.modules.get(name).map(|source| source.as_ref())
+    }
+
+    /// `#import` を展開して wgpu に渡せるソースを組み立てる
+    pub fn compose(&self, source: &str) -> Result<String, ShaderError> {
+        let mut out = String::with_capacity(source.len());
+        let mut included = HashSet::new();
+        let mut stack = Vec::new();
+        self.expand(source, &mut out, &mut included, &mut stack)?;
+        debug!("[ShaderLibrary] シェーダー組み立て完了: {} モジュール, {} bytes", included.len(), out.len());
+        Ok(out)
+    }
+
+    fn expand<'a>(
+        &'a self,
+        source: &str,
+        out: &mut String,
+        included: &mut HashSet<&'a str>,
+        stack: &mut Vec<&'a str>,
+    ) -> Result<(), ShaderError> {
+        for (index, line) in source.lines().enumerate() {
+            let Some(rest) = line.trim_start().strip_prefix(IMPORT_DIRECTIVE) else {
+                out.push_str(line);
+                out.push('\n');
+                continue;
+            };
+            let name = rest.trim().trim_end_matches(';');
+            if name.is_empty() || !rest.starts_with(char::is_whitespace) || name.contains(char::is_whitespace) {
+                return Err(ShaderError::InvalidDirective { line: index + 1, text: line.trim().to_string() });
+            }
+            let (name, module) = self.modules.get_key_value(name)
+                .ok_or_else(|| ShaderError::UnknownModule { name: name.to_string(), line: index + 1 })?;
+            if stack.contains(&name.as_str()) {
+                let mut path: Vec<String> = stack.iter().map(|name| name.to_string()).collect();
+                path.push(name.clone());
+                return Err(ShaderError::ImportCycle(path));
+            }
+            if !included.insert(name.as_str()) {
+                continue;
+            }
+            stack.push(name.as_str());
+            self.expand(module, out, included, stack)?;
+            stack.pop();
+        }
+        Ok(())
+    }
+}
+
+impl Default for ShaderLibrary {
+    fn default() -> Self {
+        Self::builtin()
+    }
+}
+
+/// 組み込みのモジュールで `#import` を展開する
+pub fn compose(source: &str) -> Result<String, ShaderError> {
+    ShaderLibrary::builtin().compose(source)
+}
+
+/// 色空間の変換と合成
+const COLOR_MODULE: &str = r#"
+fn linear_to_srgb(color: vec3<f32>) -> vec3<f32> {
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c * 12.92;
+    let high = 1.055 * pow(c, vec3<f32>(1.0 / 2.4)) - 0.055;
+    return select(high, low, c <= vec3<f32>(0.0031308));
+}
+
+fn srgb_to_linear(color: vec3<f32>) -> vec3<f32> {
+    let c = clamp(color, vec3<f32>(0.0), vec3<f32>(1.0));
+    let low = c / 12.92;
+    let high = pow((c + 0.055) / 1.055, vec3<f32>(2.4));
+    return select(high, low, c <= vec3<f32>(0.04045));
+}
+
+// 描画パイプラインのアルファブレンドと同じ式で src を dst の上に重ねる
+fn blend_over(dst: vec4<f32>, src: vec3<f32>, alpha: f32) -> vec4<f32> {
+    return vec4<f32>(src * alpha + dst.rgb * (1.0 - alpha), alpha + dst.a * (1.0 - alpha));
+}
+"#;
+
+/// 座標変換
+const COORDS_MODULE: &str = r#"
+// 表示座標（ピクセル、左上原点）→ 正規化座標（Y軸反転）
+fn pixel_to_clip(pixel: vec2<f32>, size: vec2<f32>) -> vec2<f32> {
+    return pixel / size * vec2<f32>(2.0, -2.0) + vec2<f32>(-1.0, 1.0);
+}
+"#;
+
+/// 画面全体を覆う三角形1枚の頂点（draw(0..3) で使う）
+const FULLSCREEN_MODULE: &str = r#"
+fn fullscreen_triangle(index: u32) -> vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_imports_are_expanded_once() {
+        let mut library = ShaderLibrary::builtin();
+        library.add("stroke", "#import color\n#import coords\nfn stroke() {}");
+        let source = library.compose("#import stroke\n  #import color;\nfn main() {}").unwrap();
+
+        assert_eq!(source.matches("fn linear_to_srgb").count(), 1);
+        assert_eq!(source.matches("fn pixel_to_clip").count(), 1);
+        assert!(!source.contains(IMPORT_DIRECTIVE));
+        // 取り込んだモジュールは使う側より前に置かれる
+        assert!(source.find("fn blend_over").unwrap() < source.find("fn stroke").unwrap());
+        assert!(source.ends_with("fn main() {}\n"));
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let mut library = ShaderLibrary::empty();
+        assert_eq!(
+            library.compose("fn a() {}\n#import missing"),
+            Err(ShaderError::UnknownModule { name: "missing".to_string(), line: 2 })
+        );
+        assert!(matches!(library.compose("#import"), Err(ShaderError::InvalidDirective { line: 1, .. })));
+        assert!(matches!(library.compose("#importcolor"), Err(ShaderError::InvalidDirective { .. })));
+
+        library.add("a", "#import b");
+        library.add("b", "#import a");
+        assert_eq!(
+            library.compose("#import a"),
+            Err(ShaderError::ImportCycle(vec!["a".to_string(), "b".to_string(), "a".to_string()]))
+        );
+    }
+}
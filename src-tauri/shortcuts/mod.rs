@@ -0,0 +1,322 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use std::str::FromStr;
+
+/// ショートカットのエラー型
+#[derive(Debug, PartialEq)]
+pub enum ShortcutError {
+    InvalidChord(String),
+    Conflict {
+        chord: KeyChord,
+        action: ShortcutAction,
+    },
+}
+
+impl fmt::Display for ShortcutError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ShortcutError::InvalidChord(msg) => write!(f, "キーの組み合わせが不正です: {}", msg),
+            ShortcutError::Conflict { chord, action } => {
+                write!(f, "{} は既に {} に割り当てられています", chord, action.as_str())
+            }
+        }
+    }
+}
+
+impl Error for ShortcutError {}
+
+/// ショートカットから実行できる操作
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ShortcutAction {
+    Undo,
+    Redo,
+    ToolPen,
+    ToolEraser,
+    ToolBucket,
+    ToolSelect,
+    NextFrame,
+    PreviousFrame,
+}
+
+impl ShortcutAction {
+    pub const ALL: [ShortcutAction; 8] = [
+        ShortcutAction::Undo,
+        ShortcutAction::Redo,
+        ShortcutAction::ToolPen,
+        ShortcutAction::ToolEraser,
+        ShortcutAction::ToolBucket,
+        ShortcutAction::ToolSelect,
+        ShortcutAction::NextFrame,
+        ShortcutAction::PreviousFrame,
+    ];
+
+    /// 設定ファイル・フロントエンドで使う名前
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ShortcutAction::Undo => "undo",
+            ShortcutAction::Redo => "redo",
+            ShortcutAction::ToolPen => "tool_pen",
+            ShortcutAction::ToolEraser => "tool_eraser",
+            ShortcutAction::ToolBucket => "tool_bucket",
+            ShortcutAction::ToolSelect => "tool_select",
+            ShortcutAction::NextFrame => "next_frame",
+            ShortcutAction::PreviousFrame => "previous_frame",
+        }
+    }
+
+    /// 既定の割り当て
+    fn default_chords(&self) -> &'static [&'static str] {
+        match self {
+            ShortcutAction::Undo => &["Ctrl+Z"],
+            ShortcutAction::Redo => &["Ctrl+Shift+Z", "Ctrl+Y"],
+            ShortcutAction::ToolPen => &["P"],
+            ShortcutAction::ToolEraser => &["E"],
+            ShortcutAction::ToolBucket => &["G"],
+            ShortcutAction::ToolSelect => &["M"],
+            ShortcutAction::NextFrame => &["."],
+            ShortcutAction::PreviousFrame => &[","],
+        }
+    }
+}
+
+/// 名前付きのキー（1文字のキーは大文字に正規化して扱う）
+const NAMED_KEYS: [&str; 27] = [
+    "Space", "Enter", "Tab", "Escape", "Backspace", "Delete", "Insert",
+    "Home", "End", "PageUp", "PageDown",
+    "ArrowLeft", "ArrowRight", "ArrowUp", "ArrowDown",
+    "F1", "F2", "F3", "F4", "F5", "F6", "F7", "F8", "F9", "F10", "F11", "F12",
+];
+
+/// 修飾キーとキーの組み合わせ（`Ctrl+Shift+Z` 形式の文字列で保存される）
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct KeyChord {
+    pub ctrl: bool,
+    pub alt: bool,
+    pub shift: bool,
+    pub meta: bool,
+    pub key: String,
+}
+
+impl FromStr for KeyChord {
+    type Err = ShortcutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || ShortcutError::InvalidChord(s.to_string());
+        // "+" キー自体は末尾の空要素として現れるため特別に扱う
+        let (modifiers, key) = match s.strip_suffix("++") {
+            Some(rest) => (rest, "+"),
+            None if s == "+" => ("", "+"),
+            None => s.rsplit_once('+').unwrap_or(("", s)),
+        };
+
+        let mut chord = KeyChord {
+            ctrl: false,
+            alt: false,
+            shift: false,
+            meta: false,
+            key: normalize_key(key.trim()).ok_or_else(invalid)?,
+        };
+        for modifier in modifiers.split('+').map(str::trim).filter(|m| !m.is_empty()) {
+            let flag = match modifier.to_ascii_lowercase().as_str() {
+                "ctrl" | "control" => &mut chord.ctrl,
+                "alt" | "option" => &mut chord.alt,
+                "shift" => &mut chord.shift,
+                "meta" | "cmd" | "command" | "super" => &mut chord.meta,
+                _ => return Err(invalid()),
+            };
+            if *flag {
+                return Err(invalid());
+            }
+            *flag = true;
+        }
+        Ok(chord)
+    }
+}
+
+fn normalize_key(key: &str) -> Option<String> {
+    let mut chars = key.chars();
+    match (chars.next(), chars.next()) {
+        (Some(c), None) if !c.is_whitespace() => Some(c.to_uppercase().collect()),
+        (Some(_), Some(_)) => NAMED_KEYS.iter()
+            .find(|named| named.eq_ignore_ascii_case(key))
+            .map(|named| named.to_string()),
+        _ => None,
+    }
+}
+
+impl fmt::Display for KeyChord {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.ctrl {
+            write!(f, "Ctrl+")?;
+        }
+        if self.alt {
+            write!(f, "Alt+")?;
+        }
+        if self.shift {
+            write!(f, "Shift+")?;
+        }
+        if self.meta {
+            write!(f, "Meta+")?;
+        }
+        write!(f, "{}", self.key)
+    }
+}
+
+impl TryFrom<String> for KeyChord {
+    type Error = ShortcutError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<KeyChord> for String {
+    fn from(chord: KeyChord) -> Self {
+        chord.to_string()
+    }
+}
+
+/// 同じキーの組み合わせが複数の操作に割り当てられている状態
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ShortcutConflict {
+    pub chord: KeyChord,
+    pub actions: Vec<ShortcutAction>,
+}
+
+/// 操作ごとのキー割り当て
+///
+/// 保存されていない操作には既定の割り当てが使われる（空の配列は割り当てなし）。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(from = "BTreeMap<ShortcutAction, Vec<KeyChord>>")]
+pub struct ShortcutMap(BTreeMap<ShortcutAction, Vec<KeyChord>>);
+
+impl Default for ShortcutMap {
+    fn default() -> Self {
+        Self::from(BTreeMap::new())
+    }
+}
+
+impl From<BTreeMap<ShortcutAction, Vec<KeyChord>>> for ShortcutMap {
+    fn from(mut bindings: BTreeMap<ShortcutAction, Vec<KeyChord>>) -> Self {
+        for action in ShortcutAction::ALL {
+            bindings.entry(action).or_insert_with(|| {
+                action.default_chords().iter()
+                    .map(|chord| chord.parse().expect("既定のショートカットは有効"))
+                    .collect()
+            });
+        }
+        Self(bindings)
+    }
+}
+
+impl ShortcutMap {
+    /// 操作に割り当てられたキーの組み合わせ
+    pub fn chords(&self, action: ShortcutAction) -> &[KeyChord] {
+        self.0.get(&action).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    /// キーの組み合わせに対応する操作
+    pub fn resolve(&self, chord: &KeyChord) -> Option<ShortcutAction> {
+        self.0.iter()
+            .find(|(_, chords)| chords.contains(chord))
+            .map(|(action, _)| *action)
+    }
+
+    /// 操作の割り当てを置き換える（他の操作と重なる場合はエラー）
+    pub fn bind(&mut self, action: ShortcutAction, chords: Vec<KeyChord>) -> Result<(), ShortcutError> {
+        for chord in &chords {
+            if let Some(other) = self.resolve(chord).filter(|other| *other != action) {
+                return Err(ShortcutError::Conflict { chord: chord.clone(), action: other });
+            }
+        }
+        let mut chords = chords;
+        chords.dedup();
+        self.0.insert(action, chords);
+        Ok(())
+    }
+
+    /// 操作の割り当てを置き換えた場合に重複するキーの組み合わせを列挙
+    pub fn conflicts_with(&self, action: ShortcutAction, chords: &[KeyChord]) -> Vec<ShortcutConflict> {
+        let mut candidate = self.clone();
+        candidate.0.insert(action, chords.to_vec());
+        candidate.conflicts()
+    }
+
+    /// 複数の操作に割り当てられているキーの組み合わせを列挙
+    pub fn conflicts(&self) -> Vec<ShortcutConflict> {
+        let mut by_chord: BTreeMap<&KeyChord, Vec<ShortcutAction>> = BTreeMap::new();
+        for (action, chords) in &self.0 {
+            for chord in chords {
+                let actions = by_chord.entry(chord).or_default();
+                if !actions.contains(action) {
+                    actions.push(*action);
+                }
+            }
+        }
+        by_chord.into_iter()
+            .filter(|(_, actions)| actions.len() > 1)
+            .map(|(chord, actions)| ShortcutConflict { chord: chord.clone(), actions })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_and_format_chord() {
+        let chord: KeyChord = "shift+ctrl+z".parse().unwrap();
+        assert!(chord.ctrl && chord.shift && !chord.alt && !chord.meta);
+        assert_eq!(chord.to_string(), "Ctrl+Shift+Z");
+        assert_eq!("Cmd+arrowleft".parse::<KeyChord>().unwrap().to_string(), "Meta+ArrowLeft");
+        assert_eq!("Ctrl++".parse::<KeyChord>().unwrap().key, "+");
+
+        assert!("Ctrl+".parse::<KeyChord>().is_err());
+        assert!("Hyper+A".parse::<KeyChord>().is_err());
+        assert!("Ctrl+Ctrl+A".parse::<KeyChord>().is_err());
+        assert!("Ctrl+Banana".parse::<KeyChord>().is_err());
+    }
+
+    #[test]
+    fn test_defaults_have_no_conflicts() {
+        let map = ShortcutMap::default();
+        assert!(map.conflicts().is_empty());
+        assert_eq!(map.resolve(&"Ctrl+Y".parse().unwrap()), Some(ShortcutAction::Redo));
+        assert_eq!(map.resolve(&"Ctrl+Alt+Y".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_bind_detects_conflict() {
+        let mut map = ShortcutMap::default();
+        let chord: KeyChord = "E".parse().unwrap();
+        assert_eq!(
+            map.bind(ShortcutAction::ToolPen, vec![chord.clone()]),
+            Err(ShortcutError::Conflict { chord: chord.clone(), action: ShortcutAction::ToolEraser })
+        );
+
+        let conflicts = map.conflicts_with(ShortcutAction::ToolPen, std::slice::from_ref(&chord));
+        assert_eq!(conflicts[0].actions, vec![ShortcutAction::ToolPen, ShortcutAction::ToolEraser]);
+
+        map.bind(ShortcutAction::ToolEraser, vec![]).unwrap();
+        map.bind(ShortcutAction::ToolPen, vec![chord.clone()]).unwrap();
+        assert_eq!(map.resolve(&chord), Some(ShortcutAction::ToolPen));
+    }
+
+    #[test]
+    fn test_deserialize_fills_missing_actions() {
+        let map: ShortcutMap = serde_json::from_str(r#"{"undo": ["Ctrl+U"], "tool_pen": ["E"]}"#).unwrap();
+        assert_eq!(map.chords(ShortcutAction::Undo)[0].to_string(), "Ctrl+U");
+        assert_eq!(map.chords(ShortcutAction::Redo).len(), 2);
+
+        let conflicts = map.conflicts();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].actions, vec![ShortcutAction::ToolPen, ShortcutAction::ToolEraser]);
+
+        assert!(serde_json::from_str::<ShortcutMap>(r#"{"undo": ["Ctrl+"]}"#).is_err());
+    }
+}
@@ -12,9 +12,87 @@ pub mod drawing_engine {
     include!("../drawing_engine/mod.rs");
 }
 
+pub mod shaders {
+    include!("../shaders/mod.rs");
+}
+
+pub mod history {
+    include!("../history/mod.rs");
+}
+
+pub mod collaboration {
+    include!("../collaboration/mod.rs");
+}
+
+pub mod brush {
+    include!("../brush/mod.rs");
+}
+
+pub mod formats {
+    include!("../formats/mod.rs");
+}
+
+pub mod timelapse {
+    include!("../timelapse/mod.rs");
+}
+
+pub mod guides {
+    include!("../guides/mod.rs");
+}
+
+pub mod jobs {
+    include!("../jobs/mod.rs");
+}
+
+pub mod memory {
+    include!("../memory/mod.rs");
+}
+
+pub mod paging {
+    include!("../paging/mod.rs");
+}
+
+pub mod ipc {
+    include!("../ipc/mod.rs");
+}
+
+pub mod settings {
+    include!("../settings/mod.rs");
+}
+
+pub mod shortcuts {
+    include!("../shortcuts/mod.rs");
+}
+
+pub mod tablet {
+    include!("../tablet/mod.rs");
+}
+
+pub mod selection {
+    include!("../selection/mod.rs");
+}
+
+pub mod journal {
+    include!("../journal/mod.rs");
+}
+
+pub mod benchmark {
+    include!("../benchmark/mod.rs");
+}
+
+pub mod palette {
+    include!("../palette/mod.rs");
+}
+
+#[cfg(feature = "scripting")]
+pub mod scripting {
+    include!("../scripting/mod.rs");
+}
+
 use drawing_engine::DrawingEngine;
 use api::drawing::DrawingState;
 use log::{info, error, debug};
+use tauri::Manager;
 
 // greet function commented out due to macro conflict
 
@@ -57,8 +135,65 @@ pub fn run() {
     let builder = builder.manage(drawing_state);
     debug!("[KINEGRAPH] DrawingState 状態管理登録完了");
     
+    debug!("[KINEGRAPH] BrushState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::BrushState::new());
+    
+    debug!("[KINEGRAPH] JobState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::JobState::new());
+    
+    debug!("[KINEGRAPH] MemoryState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::MemoryState::new());
+    
+    debug!("[KINEGRAPH] TransferState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::TransferState::new());
+    
+    debug!("[KINEGRAPH] TabletState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::TabletState::new());
+    
+    debug!("[KINEGRAPH] SelectionState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::SelectionState::new());
+    
+    debug!("[KINEGRAPH] PlayerState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::PlayerState::new());
+    
+    debug!("[KINEGRAPH] ClipboardState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::ClipboardState::new());
+    
+    debug!("[KINEGRAPH] RecordingState を Tauri 状態管理に登録中...");
+    let builder = builder.manage(api::RecordingState::new());
+    
+    #[cfg(debug_assertions)]
+    let builder = {
+        debug!("[KINEGRAPH] ShaderReloadState を Tauri 状態管理に登録中...");
+        builder.manage(api::ShaderReloadState::new())
+    };
+    
+    #[cfg(feature = "collab-server")]
+    let builder = {
+        debug!("[KINEGRAPH] SyncState を Tauri 状態管理に登録中...");
+        builder.manage(api::SyncState::new())
+    };
+    
+    // 設定は設定ディレクトリの解決に AppHandle が必要なため、起動処理の中で読み込む
+    let builder = builder.setup(|app| {
+        debug!("[KINEGRAPH] SettingsState を読み込み・登録中...");
+        api::manage_settings(app.handle());
+        debug!("[KINEGRAPH] セッションのジャーナルを開いています...");
+        api::open_session_journal(app.handle());
+        Ok(())
+    });
+    
+    // ドロップされたファイルはフロントエンドを経由せずに読み込む
+    let builder = builder.on_window_event(|window, event| {
+        if let tauri::WindowEvent::DragDrop(tauri::DragDropEvent::Drop { paths, .. }) = event {
+            info!("[KINEGRAPH] ファイルがドロップされました: {} 件", paths.len());
+            api::handle_file_drop(window.app_handle(), paths.clone());
+        }
+    });
+    
     debug!("[KINEGRAPH] Tauri invoke_handler 登録中...");
-    let builder = builder.invoke_handler(tauri::generate_handler![
+    // 記録中は描画コマンドを実行前に書き出す（不具合の再現用）
+    let builder = builder.invoke_handler(api::record_commands(tauri::generate_handler![
         // 既存のプロジェクトAPI
         api::create_project,
         api::get_system_info,
@@ -72,18 +207,298 @@ pub fn run() {
         api::create_drawing_layer,
         api::draw_line_on_layer,
         api::draw_stroke_on_layer,
+        api::begin_stroke,
+        api::extend_stroke,
+        api::extend_stroke_packed,
+        api::end_stroke,
+        api::cancel_stroke,
+        api::cancel_all_strokes,
+        api::report_stroke_presented,
+        api::draw_pixel_stroke_on_layer,
+        api::get_layer_image_zoomed,
+        api::render_canvas_view,
+        api::render_comparison_view,
+        api::compare_layers,
         api::get_layer_image_data,
         api::clear_layer,
         api::remove_layer,
         api::get_drawing_stats,
         api::cleanup_textures,
+        api::defragment_textures,
+        api::get_device_capabilities,
+        api::set_max_canvas_size,
+        api::get_canvas_render_settings,
+        api::set_canvas_render_settings,
+        
+        // GPU選択API
+        api::list_gpu_adapters,
+        api::get_gpu_preference,
+        api::select_gpu_adapter,
+
+        // 設定API
+        api::get_settings,
+        api::set_settings,
+        api::reset_settings,
+        api::list_export_presets,
+        api::save_export_preset,
+        api::delete_export_preset,
+
+        // ショートカットAPI
+        api::get_shortcuts,
+        api::set_shortcut,
+        api::reset_shortcuts,
+        api::resolve_shortcut,
+        api::get_shortcut_conflicts,
+
+        // ペンタブレットAPI
+        api::get_tablet_settings,
+        api::start_pressure_calibration,
+        api::record_pressure_samples,
+        api::finish_pressure_calibration,
+        api::reset_pressure_curve,
+        api::classify_pointer_input,
+
+        // 無限キャンバスAPI
+        api::get_viewport,
+        api::set_infinite_canvas,
+        api::set_viewport,
+        api::scroll_viewport,
+        api::get_changed_tiles,
+        api::get_view_transform,
+        api::set_view_transform,
+        api::get_navigator_image,
+        api::fill_layer,
+        api::rotate_canvas_90,
+        api::rotate_canvas_180,
+        api::flip_canvas_horizontal,
+
+        // 選択範囲API
+        api::set_selection,
+        api::clear_selection,
+        api::get_selection,
+        api::begin_selection_transform,
+        api::preview_selection_transform,
+        api::commit_selection_transform,
+        api::cancel_selection_transform,
+        api::enter_quick_mask,
+        api::exit_quick_mask,
+
+        // クリップボードAPI
+        api::copy_selection,
+        api::cut_selection,
+        api::paste_in_place,
+        api::paste_as_new_layer,
+        api::get_clipboard_info,
+        api::export_clipboard_png,
+        api::import_clipboard_png,
+        api::copy_canvas_to_system_clipboard,
+        api::paste_from_system_clipboard,
+
+        // セッション復元API
+        api::get_recoverable_session,
+        api::recover_session,
+        api::discard_recoverable_session,
+
+        // ベンチマークAPI
+        api::run_benchmark,
+        api::benchmark_ipc_echo,
+        api::summarize_ipc_benchmark,
+
+        // 中割りAPI
+        api::generate_inbetweens,
+
+        // アニメーション再生API
+        api::set_player_frames,
+        api::set_playback_options,
+        api::get_player_status,
+        api::start_playback,
+        api::stop_playback,
+        api::step_forward,
+        api::step_backward,
+        api::goto_frame,
+
+        // タイムラインAPI
+        api::get_timeline_metadata,
+        api::set_frame_markers,
+        api::add_frame_marker,
+        api::rename_frame_marker,
+        api::move_frame_marker,
+        api::remove_frame_marker,
+
+        // シーンAPI
+        api::get_scenes,
+        api::create_scene,
+        api::update_scene,
+        api::reorder_scenes,
+        api::duplicate_scene,
+
+        // カメラAPI
+        api::set_camera_keyframe,
+        api::remove_camera_keyframe,
+        api::get_camera_rect,
+        api::render_camera_frame,
+
+        // レイヤー効果API
+        api::set_layer_effects,
+        api::add_layer_effect,
+        api::update_layer_effect,
+        api::set_layer_effect_enabled,
+        api::remove_layer_effect,
+        api::rasterize_layer_effects,
+
+        // 塗りつぶしレイヤーAPI
+        api::create_fill_layer,
+        api::set_layer_fill,
+        api::rasterize_fill_layer,
+        api::bucket_fill,
+        api::set_lineart_layer,
+        api::get_lineart_layer,
+        api::segment_regions,
+        api::get_region_at,
+        api::fill_region,
+        api::batch_bucket_fill,
+        
+        // レイヤーマスクAPI
+        api::add_layer_mask,
+        api::remove_layer_mask,
+        api::set_layer_mask_enabled,
+        api::apply_layer_mask,
+        
+        // ヒストグラムAPI
+        api::get_histogram,
+        
+        // パレット抽出API
+        api::extract_palette,
+        
+        // プロジェクト整理API
+        api::cleanup_project,
+        
+        // 診断レポートAPI
+        api::generate_diagnostics_report,
+        api::set_perf_overlay,
+        
+        // 履歴API
+        api::undo,
+        api::redo,
+        api::seek_history,
+        api::get_history_info,
+        api::pick_object,
+        api::list_strokes,
+        api::delete_stroke,
+        api::lasso_erase,
+        api::get_operation_log,
+        api::load_operation_log,
+        api::apply_operations,
+        api::rerasterize_canvas,
+        api::request_canvas_resize,
+        api::commit_canvas_resize,
+        api::cancel_canvas_resize,
+        api::replay_strokes,
+        api::cancel_replay,
+        
+        // 共同編集API
+        api::start_collaboration,
+        api::stop_collaboration,
+        api::get_collaboration_status,
+        api::get_local_ops_since,
+        api::apply_remote_ops,
+        #[cfg(feature = "collab-server")]
+        api::create_sync_session,
+        #[cfg(feature = "collab-server")]
+        api::join_sync_session,
+        #[cfg(feature = "collab-server")]
+        api::leave_sync_session,
+        #[cfg(feature = "collab-server")]
+        api::get_sync_session,
+
+        // ブラシAPI
+        api::import_brush_pack,
+        api::list_brush_packs,
+        api::remove_brush_pack,
+        api::export_brush_pack,
+        api::list_brushes,
+        api::draw_brush_stroke,
+
+        // ファイル形式API
+        api::export_psd,
+        api::export_frame_sequence,
+        api::batch_export,
+        api::export_canvas_png,
+        api::get_content_bounds,
+        api::get_canvas_stats,
+        api::import_project,
+        api::import_image_layer,
+        api::save_project,
+        api::load_project,
+
+        // タイムラプスAPI
+        api::start_timelapse,
+        api::pause_timelapse,
+        api::resume_timelapse,
+        api::stop_timelapse,
+        api::get_timelapse_status,
+        api::export_timelapse,
+
+        // ガイドAPI
+        api::get_guides,
+        api::set_guides,
+        api::add_guide,
+        api::remove_guide,
+        api::set_perspective_guide,
+        api::set_safe_area_guide,
+        api::preview_stroke_constraint,
+        api::snap_point,
+        api::get_guide_overlay,
+
+        // バックグラウンドジョブAPI
+        api::submit_job,
+        api::get_job,
+        api::list_jobs,
+        api::cancel_job,
+        api::take_job_result,
+
+        // メモリ監視API
+        api::get_memory_report,
+        api::get_memory_config,
+        api::set_memory_config,
+        api::start_memory_monitor,
+        api::stop_memory_monitor,
+
+        // アイドル時のメンテナンスAPI
+        api::start_idle_maintenance,
+        api::stop_idle_maintenance,
+
+        // コマンドの記録・再生API
+        api::start_command_recording,
+        api::stop_command_recording,
+        api::replay_command_recording,
+
+        // フレームページングAPI
+        api::page_frames,
+        api::restore_paged_layers,
+
+        // バイナリ転送API
+        api::get_layer_image_transfer,
+        api::train_transfer_dictionary,
+        api::get_transfer_dictionary,
+
+        // スクリプトAPI
+        #[cfg(feature = "scripting")]
+        api::run_script,
+
+        // シェーダーのホットリロードAPI（開発ビルドのみ）
+        #[cfg(debug_assertions)]
+        api::start_shader_hot_reload,
+        #[cfg(debug_assertions)]
+        api::stop_shader_hot_reload,
         
         // デバッグAPI
         api::get_detailed_engine_state,
+        api::export_blend_comparison,
         api::get_all_layers_info,
         api::get_system_memory_info,
         api::log_detailed_state
-    ]);
+    ]));
     debug!("[KINEGRAPH] invoke_handler 登録完了");
     
     info!("[KINEGRAPH] Tauri アプリケーション実行開始");
@@ -0,0 +1,261 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use super::TabletError;
+
+/// 入力デバイスの種類（ブラウザの `pointerType` と同じ名前）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointerKind {
+    Pen,
+    Touch,
+    Mouse,
+}
+
+/// ポインターイベントの段階（`pointercancel` は `Up` として扱う）
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PointerPhase {
+    Down,
+    Move,
+    Up,
+}
+
+/// 分類するポインターイベント
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PointerInput {
+    pub pointer_id: i64,
+    pub kind: PointerKind,
+    pub phase: PointerPhase,
+    pub x: f32,
+    pub y: f32,
+    /// 接触面の幅と高さ（ピクセル。不明なら 0）
+    #[serde(default)]
+    pub contact_width: f32,
+    #[serde(default)]
+    pub contact_height: f32,
+}
+
+/// 分類の結果
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum InputAction {
+    /// ストロークとして描く
+    Draw,
+    /// 手のひらとして捨てる（指を離すまで同じポインターは捨て続ける）
+    Reject,
+    /// 使わない入力（指で描かない設定の1本指など）
+    Ignore,
+    /// 2本指のジェスチャーが始まった（1本目の指で描き始めたストロークは取り消す）
+    BeginGesture,
+    /// 前回からの移動量と拡大率（`center` を中心に拡大する）
+    Gesture { pan_x: f32, pan_y: f32, zoom: f32, center_x: f32, center_y: f32 },
+    EndGesture,
+}
+
+/// タッチ入力の設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TouchSettings {
+    /// ペンで描いている間の指と、接触面が大きいタッチを捨てる
+    pub palm_rejection: bool,
+    /// 指として扱う接触面の最大の径（ピクセル。これより大きいと手のひら）
+    pub max_finger_contact: f32,
+    /// 1本指で描く（無効なら指はジェスチャーだけに使う）
+    pub finger_drawing: bool,
+    /// 2本指でスクロール・拡大縮小する
+    pub gestures: bool,
+}
+
+impl Default for TouchSettings {
+    fn default() -> Self {
+        Self {
+            palm_rejection: true,
+            max_finger_contact: 40.0,
+            finger_drawing: true,
+            gestures: true,
+        }
+    }
+}
+
+impl TouchSettings {
+    pub fn validate(&self) -> Result<(), TabletError> {
+        if !(self.max_finger_contact > 0.0 && self.max_finger_contact.is_finite()) {
+            return Err(TabletError::InvalidTouchSettings(format!("指の接触面の上限 {}", self.max_finger_contact)));
+        }
+        Ok(())
+    }
+}
+
+/// ペンと指の入力を分類する（ペン中の手のひらを捨て、2本指をジェスチャーにする）
+#[derive(Debug, Clone, Default)]
+pub struct InputClassifier {
+    /// 画面に触れているペン
+    pens: BTreeSet<i64>,
+    /// 指として受け付けているタッチの現在位置
+    fingers: BTreeMap<i64, (f32, f32)>,
+    /// 指を離すまで捨てるタッチ
+    rejected: BTreeSet<i64>,
+    /// ジェスチャー中（2本指になってから全部の指が離れるまで）
+    gesture: bool,
+}
+
+impl InputClassifier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn classify(&mut self, input: &PointerInput, settings: &TouchSettings) -> InputAction {
+        match input.kind {
+            PointerKind::Mouse => InputAction::Draw,
+            PointerKind::Pen => {
+                match input.phase {
+                    PointerPhase::Down => {
+                        self.pens.insert(input.pointer_id);
+                        // ペンを置く前から触れていた指は手のひら
+                        if settings.palm_rejection && !self.gesture {
+                            self.rejected.extend(std::mem::take(&mut self.fingers).into_keys());
+                        }
+                    }
+                    PointerPhase::Up => {
+                        self.pens.remove(&input.pointer_id);
+                    }
+                    PointerPhase::Move => {}
+                }
+                InputAction::Draw
+            }
+            PointerKind::Touch => self.classify_touch(input, settings),
+        }
+    }
+
+    fn classify_touch(&mut self, input: &PointerInput, settings: &TouchSettings) -> InputAction {
+        let id = input.pointer_id;
+        if self.rejected.contains(&id) {
+            if input.phase == PointerPhase::Up {
+                self.rejected.remove(&id);
+            }
+            return InputAction::Reject;
+        }
+
+        match input.phase {
+            PointerPhase::Down => {
+                let palm = input.contact_width.max(input.contact_height) > settings.max_finger_contact;
+                if settings.palm_rejection && (palm || !self.pens.is_empty()) {
+                    self.rejected.insert(id);
+                    return InputAction::Reject;
+                }
+                self.fingers.insert(id, (input.x, input.y));
+                if settings.gestures && self.fingers.len() == 2 && !self.gesture {
+                    self.gesture = true;
+                    return InputAction::BeginGesture;
+                }
+                self.single_finger_action(settings)
+            }
+            PointerPhase::Move => {
+                let Some(previous) = self.fingers.get(&id).copied() else { return InputAction::Ignore };
+                if !self.gesture {
+                    self.fingers.insert(id, (input.x, input.y));
+                    return self.single_finger_action(settings);
+                }
+                let Some(&other) = self.fingers.iter().find(|(other, _)| **other != id).map(|(_, position)| position) else {
+                    self.fingers.insert(id, (input.x, input.y));
+                    return InputAction::Ignore;
+                };
+                self.fingers.insert(id, (input.x, input.y));
+                gesture_step(previous, (input.x, input.y), other)
+            }
+            PointerPhase::Up => {
+                self.fingers.remove(&id);
+                if !self.gesture {
+                    return self.single_finger_action(settings);
+                }
+                // 残った指はすべて離すまでジェスチャーの一部として扱い、描き始めない
+                if self.fingers.is_empty() {
+                    self.gesture = false;
+                    InputAction::EndGesture
+                } else {
+                    InputAction::Ignore
+                }
+            }
+        }
+    }
+
+    fn single_finger_action(&self, settings: &TouchSettings) -> InputAction {
+        if settings.finger_drawing && !self.gesture {
+            InputAction::Draw
+        } else {
+            InputAction::Ignore
+        }
+    }
+}
+
+/// 1本の指が `from` から `to` へ動いたときの、もう1本の指 `anchor` との2本指ジェスチャー
+fn gesture_step(from: (f32, f32), to: (f32, f32), anchor: (f32, f32)) -> InputAction {
+    let center = |p: (f32, f32)| ((p.0 + anchor.0) / 2.0, (p.1 + anchor.1) / 2.0);
+    let distance = |p: (f32, f32)| (p.0 - anchor.0).hypot(p.1 - anchor.1);
+    let (before, after) = (center(from), center(to));
+    let zoom = if distance(from) > f32::EPSILON { distance(to) / distance(from) } else { 1.0 };
+    InputAction::Gesture {
+        pan_x: after.0 - before.0,
+        pan_y: after.1 - before.1,
+        zoom,
+        center_x: after.0,
+        center_y: after.1,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn input(pointer_id: i64, kind: PointerKind, phase: PointerPhase, x: f32, y: f32) -> PointerInput {
+        PointerInput { pointer_id, kind, phase, x, y, contact_width: 10.0, contact_height: 10.0 }
+    }
+
+    #[test]
+    fn test_palm_rejected_during_pen_stroke() {
+        use PointerPhase::*;
+        let settings = TouchSettings::default();
+        let mut classifier = InputClassifier::new();
+
+        // ペンより先に置いた手のひらも、ペン中に触れた指も捨てる
+        assert_eq!(classifier.classify(&input(1, PointerKind::Touch, Down, 0.0, 0.0), &settings), InputAction::Draw);
+        assert_eq!(classifier.classify(&input(2, PointerKind::Pen, Down, 5.0, 5.0), &settings), InputAction::Draw);
+        assert_eq!(classifier.classify(&input(1, PointerKind::Touch, Move, 1.0, 0.0), &settings), InputAction::Reject);
+        assert_eq!(classifier.classify(&input(3, PointerKind::Touch, Down, 9.0, 9.0), &settings), InputAction::Reject);
+        assert_eq!(classifier.classify(&input(2, PointerKind::Pen, Up, 6.0, 6.0), &settings), InputAction::Draw);
+        // ペンを離しても、離すまでは捨て続ける
+        assert_eq!(classifier.classify(&input(3, PointerKind::Touch, Move, 9.0, 8.0), &settings), InputAction::Reject);
+        assert_eq!(classifier.classify(&input(3, PointerKind::Touch, Up, 9.0, 8.0), &settings), InputAction::Reject);
+
+        // 接触面の大きいタッチはペンがなくても手のひら
+        let palm = PointerInput { contact_width: 80.0, ..input(4, PointerKind::Touch, Down, 0.0, 0.0) };
+        assert_eq!(classifier.classify(&palm, &settings), InputAction::Reject);
+        let off = TouchSettings { palm_rejection: false, ..settings };
+        let palm = PointerInput { pointer_id: 5, ..palm };
+        assert_eq!(classifier.classify(&palm, &off), InputAction::Draw);
+    }
+
+    #[test]
+    fn test_two_finger_gesture() {
+        use PointerPhase::*;
+        let settings = TouchSettings::default();
+        let mut classifier = InputClassifier::new();
+
+        assert_eq!(classifier.classify(&input(1, PointerKind::Touch, Down, 0.0, 0.0), &settings), InputAction::Draw);
+        assert_eq!(classifier.classify(&input(2, PointerKind::Touch, Down, 10.0, 0.0), &settings), InputAction::BeginGesture);
+        // 2本目の指を動かして間隔を2倍に広げると、中心は (10, 0) に移る
+        assert_eq!(
+            classifier.classify(&input(2, PointerKind::Touch, Move, 20.0, 0.0), &settings),
+            InputAction::Gesture { pan_x: 5.0, pan_y: 0.0, zoom: 2.0, center_x: 10.0, center_y: 0.0 }
+        );
+        // 指を1本離しても、もう1本で描き始めない
+        assert_eq!(classifier.classify(&input(2, PointerKind::Touch, Up, 20.0, 0.0), &settings), InputAction::Ignore);
+        assert_eq!(classifier.classify(&input(1, PointerKind::Touch, Move, 1.0, 0.0), &settings), InputAction::Ignore);
+        assert_eq!(classifier.classify(&input(1, PointerKind::Touch, Up, 1.0, 0.0), &settings), InputAction::EndGesture);
+
+        // 指で描かない設定では1本指は使わない
+        let settings = TouchSettings { finger_drawing: false, ..settings };
+        assert_eq!(classifier.classify(&input(3, PointerKind::Touch, Down, 0.0, 0.0), &settings), InputAction::Ignore);
+        assert!(TouchSettings { max_finger_contact: 0.0, ..settings }.validate().is_err());
+    }
+}
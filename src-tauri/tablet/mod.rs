@@ -0,0 +1,237 @@
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+// ペンと指の入力の分類（パームリジェクションと2本指ジェスチャー）
+pub mod input;
+pub use input::{InputAction, InputClassifier, PointerInput, PointerKind, PointerPhase, TouchSettings};
+
+/// 曲線の推定に必要な最小サンプル数
+pub const MIN_CALIBRATION_SAMPLES: usize = 32;
+
+/// ガンマ値の許容範囲
+const GAMMA_RANGE: (f32, f32) = (0.1, 10.0);
+
+/// 入力範囲の推定に使う下位・上位のパーセンタイル（外れ値を除く）
+const RANGE_PERCENTILES: (f32, f32) = (0.02, 0.98);
+
+/// ペンタブレット設定のエラー型
+#[derive(Debug, PartialEq)]
+pub enum TabletError {
+    NotEnoughSamples { required: usize, actual: usize },
+    InvalidSample(f32),
+    InvalidCurve(String),
+    InvalidTouchSettings(String),
+}
+
+impl fmt::Display for TabletError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TabletError::NotEnoughSamples { required, actual } => {
+                write!(f, "筆圧サンプルが不足しています（{} / {}）", actual, required)
+            }
+            TabletError::InvalidSample(value) => write!(f, "筆圧サンプルが不正です: {}", value),
+            TabletError::InvalidCurve(msg) => write!(f, "筆圧カーブが不正です: {}", msg),
+            TabletError::InvalidTouchSettings(msg) => write!(f, "タッチ入力の設定が不正です: {}", msg),
+        }
+    }
+}
+
+impl Error for TabletError {}
+
+/// 筆圧の応答カーブ
+///
+/// 生の筆圧を `input_min`〜`input_max` の範囲で 0〜1 に正規化し、`gamma` 乗して返す。
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct PressureCurve {
+    pub input_min: f32,
+    pub input_max: f32,
+    pub gamma: f32,
+}
+
+impl Default for PressureCurve {
+    fn default() -> Self {
+        Self { input_min: 0.0, input_max: 1.0, gamma: 1.0 }
+    }
+}
+
+impl PressureCurve {
+    pub fn validate(&self) -> Result<(), TabletError> {
+        if !(0.0..=1.0).contains(&self.input_min)
+            || !(0.0..=1.0).contains(&self.input_max)
+            || self.input_min >= self.input_max
+        {
+            return Err(TabletError::InvalidCurve(format!(
+                "入力範囲 {}〜{}", self.input_min, self.input_max
+            )));
+        }
+        if !(GAMMA_RANGE.0..=GAMMA_RANGE.1).contains(&self.gamma) {
+            return Err(TabletError::InvalidCurve(format!("ガンマ値 {}", self.gamma)));
+        }
+        Ok(())
+    }
+
+    /// 生の筆圧にカーブを適用（結果は 0〜1）
+    pub fn apply(&self, pressure: f32) -> f32 {
+        let normalized = ((pressure - self.input_min) / (self.input_max - self.input_min)).clamp(0.0, 1.0);
+        normalized.powf(self.gamma)
+    }
+}
+
+/// 筆圧の校正（サンプルを集めてカーブを推定する）
+#[derive(Debug, Clone, Default)]
+pub struct PressureCalibration {
+    samples: Vec<f32>,
+}
+
+impl PressureCalibration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// 生の筆圧サンプルを追加（ペンが触れていない 0 のサンプルは除外）
+    pub fn record(&mut self, samples: &[f32]) -> Result<usize, TabletError> {
+        if let Some(invalid) = samples.iter().find(|s| !(0.0..=1.0).contains(*s)) {
+            return Err(TabletError::InvalidSample(*invalid));
+        }
+        self.samples.extend(samples.iter().copied().filter(|s| *s > 0.0));
+        Ok(self.samples.len())
+    }
+
+    pub fn sample_count(&self) -> usize {
+        self.samples.len()
+    }
+
+    /// 集めたサンプルからカーブを推定
+    ///
+    /// 外れ値を除いた最小・最大を入力範囲とし、中央値が 0.5 になるようにガンマ値を決める。
+    /// 「普段の筆圧」が出力範囲の中央に来るため、筆圧の弱い・強いペンでも同じ描き味になる。
+    pub fn fit(&self) -> Result<PressureCurve, TabletError> {
+        if self.samples.len() < MIN_CALIBRATION_SAMPLES {
+            return Err(TabletError::NotEnoughSamples {
+                required: MIN_CALIBRATION_SAMPLES,
+                actual: self.samples.len(),
+            });
+        }
+
+        let mut sorted = self.samples.clone();
+        sorted.sort_by(f32::total_cmp);
+        let percentile = |p: f32| sorted[((sorted.len() - 1) as f32 * p).round() as usize];
+
+        let input_min = percentile(RANGE_PERCENTILES.0);
+        let input_max = percentile(RANGE_PERCENTILES.1);
+        if input_max <= input_min {
+            return Err(TabletError::InvalidCurve("筆圧の変化がありません".to_string()));
+        }
+
+        let median = (percentile(0.5) - input_min) / (input_max - input_min);
+        let gamma = if median > 0.0 && median < 1.0 {
+            (0.5f32.ln() / median.ln()).clamp(GAMMA_RANGE.0, GAMMA_RANGE.1)
+        } else {
+            1.0
+        };
+
+        let curve = PressureCurve { input_min, input_max, gamma };
+        curve.validate()?;
+        Ok(curve)
+    }
+}
+
+/// ペンタブレットの設定（全体のカーブ、デバイスごとのプロファイル、タッチ入力）
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TabletSettings {
+    pub pressure_curve: PressureCurve,
+    /// デバイスID → そのデバイス専用のカーブ
+    pub profiles: BTreeMap<String, PressureCurve>,
+    pub touch: TouchSettings,
+}
+
+impl TabletSettings {
+    pub fn validate(&self) -> Result<(), TabletError> {
+        self.pressure_curve.validate()?;
+        self.profiles.values().try_for_each(PressureCurve::validate)?;
+        self.touch.validate()
+    }
+
+    /// デバイスに使うカーブ（専用のプロファイルがなければ全体のカーブ）
+    pub fn curve_for(&self, device_id: Option<&str>) -> &PressureCurve {
+        device_id
+            .and_then(|id| self.profiles.get(id))
+            .unwrap_or(&self.pressure_curve)
+    }
+
+    /// カーブを設定（デバイスIDがなければ全体のカーブ）
+    pub fn set_curve(&mut self, device_id: Option<&str>, curve: PressureCurve) {
+        match device_id {
+            Some(id) => {
+                self.profiles.insert(id.to_string(), curve);
+            }
+            None => self.pressure_curve = curve,
+        }
+    }
+
+    /// カーブを初期状態に戻す（デバイスのプロファイルは削除して全体のカーブを使う）
+    pub fn reset_curve(&mut self, device_id: Option<&str>) {
+        match device_id {
+            Some(id) => {
+                self.profiles.remove(id);
+            }
+            None => self.pressure_curve = PressureCurve::default(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_curve_apply() {
+        let identity = PressureCurve::default();
+        assert_eq!(identity.apply(0.3), 0.3);
+
+        let curve = PressureCurve { input_min: 0.2, input_max: 0.6, gamma: 2.0 };
+        assert_eq!(curve.apply(0.1), 0.0);
+        assert!((curve.apply(0.4) - 0.25).abs() < 1e-6);
+        assert_eq!(curve.apply(0.9), 1.0);
+
+        assert!(PressureCurve { input_min: 0.5, input_max: 0.5, gamma: 1.0 }.validate().is_err());
+        assert!(PressureCurve { gamma: 0.0, ..identity }.validate().is_err());
+    }
+
+    #[test]
+    fn test_fit_light_pen() {
+        // 0.05〜0.45 に偏った弱い筆圧（中央値 0.15）
+        let samples: Vec<f32> = (0..200).map(|i| {
+            let t = i as f32 / 199.0;
+            0.05 + 0.4 * t * t
+        }).collect();
+
+        let mut calibration = PressureCalibration::new();
+        assert!(matches!(calibration.fit(), Err(TabletError::NotEnoughSamples { .. })));
+        calibration.record(&[0.0; 10]).unwrap();
+        assert_eq!(calibration.record(&samples).unwrap(), 200);
+        assert!(calibration.record(&[1.5]).is_err());
+
+        let curve = calibration.fit().unwrap();
+        assert!(curve.input_min < 0.06 && curve.input_max > 0.4);
+        assert!(curve.gamma < 1.0);
+        assert!((curve.apply(samples[100]) - 0.5).abs() < 0.02);
+    }
+
+    #[test]
+    fn test_device_profiles() {
+        let mut settings = TabletSettings::default();
+        let curve = PressureCurve { input_min: 0.1, input_max: 0.8, gamma: 0.7 };
+        settings.set_curve(Some("pen-1"), curve);
+
+        assert_eq!(settings.curve_for(Some("pen-1")), &curve);
+        assert_eq!(settings.curve_for(Some("pen-2")), &PressureCurve::default());
+        assert_eq!(settings.curve_for(None), &PressureCurve::default());
+
+        settings.reset_curve(Some("pen-1"));
+        assert!(settings.profiles.is_empty());
+    }
+}
@@ -81,8 +81,13 @@ async fn test_invalid_dimensions() {
     let result = engine.create_offscreen_renderer(0, 0);
     assert!(result.is_err(), "無効な寸法(0x0)が受け入れられました");
     
-    // 大きすぎる寸法（4K以上）
-    let result = engine.create_offscreen_renderer(4000, 3000);
+    // 大きすぎる寸法（デバイスから求めたキャンバスの最大辺を超える）
+    let max_dimension = engine.device_capabilities()
+        .expect("デバイス情報を取得できません")
+        .max_canvas_dimension;
+    let result = engine.create_offscreen_renderer(max_dimension + 1, 600);
+    assert!(result.is_err(), "大きすぎる寸法が受け入れられました");
+    let result = engine.create_offscreen_renderer(800, max_dimension + 1);
     assert!(result.is_err(), "大きすぎる寸法が受け入れられました");
     
     info!("[TEST] 無効な寸法テスト成功");
@@ -0,0 +1,374 @@
+use base64::{engine::general_purpose::STANDARD, Engine as _};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::error::Error;
+use std::fmt;
+use std::io::Write;
+use std::time::{Duration, Instant};
+use log::{info, debug};
+
+/// タイムラプス記録のエラー型
+#[derive(Debug)]
+pub enum TimelapseError {
+    InvalidConfig(String),
+    NoFrames,
+    EncodeFailed(String),
+}
+
+impl fmt::Display for TimelapseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimelapseError::InvalidConfig(msg) => write!(f, "タイムラプス設定が不正です: {}", msg),
+            TimelapseError::NoFrames => write!(f, "タイムラプスのフレームがありません"),
+            TimelapseError::EncodeFailed(msg) => write!(f, "タイムラプスのエンコードに失敗しました: {}", msg),
+        }
+    }
+}
+
+impl Error for TimelapseError {}
+
+/// タイムラプスの記録設定
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelapseConfig {
+    /// 指定した操作数ごとに記録
+    pub every_operations: Option<u32>,
+    /// 前回の記録から指定秒数が経過した後の操作で記録
+    pub every_seconds: Option<f32>,
+    /// 記録するフレームの長辺の最大ピクセル数
+    pub max_dimension: u32,
+    /// バッファの最大バイト数（超えたらフレームを間引く）
+    pub storage_budget_bytes: u64,
+}
+
+impl Default for TimelapseConfig {
+    fn default() -> Self {
+        Self {
+            every_operations: Some(10),
+            every_seconds: None,
+            max_dimension: 512,
+            storage_budget_bytes: 64 * 1024 * 1024,
+        }
+    }
+}
+
+impl TimelapseConfig {
+    pub fn validate(&self) -> Result<(), TimelapseError> {
+        if self.every_operations.is_none() && self.every_seconds.is_none() {
+            return Err(TimelapseError::InvalidConfig("記録間隔（操作数または秒数）を指定してください".to_string()));
+        }
+        if self.every_operations == Some(0) {
+            return Err(TimelapseError::InvalidConfig("操作数の間隔は1以上である必要があります".to_string()));
+        }
+        if self.every_seconds.is_some_and(|s| !(s > 0.0 && s.is_finite())) {
+            return Err(TimelapseError::InvalidConfig("秒数の間隔は0より大きい必要があります".to_string()));
+        }
+        if self.max_dimension == 0 {
+            return Err(TimelapseError::InvalidConfig("フレームサイズは1以上である必要があります".to_string()));
+        }
+        if self.storage_budget_bytes == 0 {
+            return Err(TimelapseError::InvalidConfig("容量上限は1以上である必要があります".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// 記録されたフレーム（縮小済みPNG）
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TimelapseFrame {
+    pub timestamp: i64,
+    pub width: u32,
+    pub height: u32,
+    #[serde(serialize_with = "serialize_base64", deserialize_with = "deserialize_base64")]
+    pub png: Vec<u8>,
+}
+
+/// プロジェクトと共に保存されるタイムラプスのバッファ
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct TimelapseBuffer {
+    pub frames: Vec<TimelapseFrame>,
+    /// 容量超過で間引いた回数
+    #[serde(default)]
+    pub thinned: u32,
+}
+
+impl TimelapseBuffer {
+    pub fn bytes_used(&self) -> u64 {
+        self.frames.iter().map(|f| f.png.len() as u64).sum()
+    }
+
+    /// 容量内に収まるまで1フレームおきに間引く（最新フレームは常に残す）
+    fn enforce_budget(&mut self, budget: u64) {
+        while self.frames.len() > 1 && self.bytes_used() > budget {
+            if self.frames.len() == 2 {
+                self.frames.remove(0);
+            } else {
+                let last = self.frames.len() - 1;
+                let mut index = 0;
+                self.frames.retain(|_| {
+                    let keep = index % 2 == 0 || index == last;
+                    index += 1;
+                    keep
+                });
+            }
+            self.thinned += 1;
+            debug!("[Timelapse] 容量超過のため間引き: {} フレーム", self.frames.len());
+        }
+    }
+}
+
+/// タイムラプス記録の状態
+#[derive(Debug, Clone, Serialize)]
+pub struct TimelapseStatus {
+    pub paused: bool,
+    pub frame_count: usize,
+    pub bytes_used: u64,
+    pub storage_budget_bytes: u64,
+    pub thinned: u32,
+}
+
+/// 描画操作に合わせて縮小フレームを記録するレコーダー
+#[derive(Debug)]
+pub struct TimelapseRecorder {
+    config: TimelapseConfig,
+    buffer: TimelapseBuffer,
+    paused: bool,
+    operations_since_capture: u32,
+    last_capture: Option<Instant>,
+    /// メモリ逼迫時にフレームサイズを縮める倍率
+    resolution_scale: f32,
+}
+
+impl TimelapseRecorder {
+    /// 既存のバッファ（プロジェクトに保存されていたもの）に追記する形で開始
+    pub fn new(config: TimelapseConfig, buffer: TimelapseBuffer) -> Result<Self, TimelapseError> {
+        config.validate()?;
+        info!("[Timelapse] 記録開始: 既存 {} フレーム", buffer.frames.len());
+        Ok(Self {
+            config,
+            buffer,
+            paused: false,
+            operations_since_capture: 0,
+            last_capture: None,
+            resolution_scale: 1.0,
+        })
+    }
+
+    pub fn pause(&mut self) {
+        self.paused = true;
+    }
+
+    pub fn resume(&mut self) {
+        self.paused = false;
+    }
+
+    /// フレームサイズの倍率を設定（メモリ逼迫時に下げる）
+    pub fn set_resolution_scale(&mut self, scale: f32) {
+        self.resolution_scale = scale.clamp(0.01, 1.0);
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn buffer(&self) -> &TimelapseBuffer {
+        &self.buffer
+    }
+
+    pub fn into_buffer(self) -> TimelapseBuffer {
+        self.buffer
+    }
+
+    /// 操作を通知し、フレームを記録すべきかを返す
+    pub fn note_operation(&mut self, now: Instant) -> bool {
+        if self.paused {
+            return false;
+        }
+        self.operations_since_capture += 1;
+
+        let by_operations = self.config.every_operations
+            .is_some_and(|n| self.operations_since_capture >= n);
+        let by_time = self.config.every_seconds.is_some_and(|seconds| {
+            self.last_capture.is_none_or(|last| now.duration_since(last) >= Duration::from_secs_f32(seconds))
+        });
+        by_operations || by_time
+    }
+
+    /// 合成済みの画像を縮小して記録
+    pub fn add_frame(&mut self, image: &RgbaImage, now: Instant) -> Result<(), TimelapseError> {
+        let max_dimension = ((self.config.max_dimension as f32 * self.resolution_scale) as u32).max(1);
+        let frame = downscale(image, max_dimension);
+        let mut png = std::io::Cursor::new(Vec::new());
+        frame.write_to(&mut png, image::ImageFormat::Png)
+            .map_err(|e| TimelapseError::EncodeFailed(e.to_string()))?;
+
+        self.buffer.frames.push(TimelapseFrame {
+            timestamp: chrono::Utc::now().timestamp_millis(),
+            width: frame.width(),
+            height: frame.height(),
+            png: png.into_inner(),
+        });
+        self.buffer.enforce_budget(self.config.storage_budget_bytes);
+        self.operations_since_capture = 0;
+        self.last_capture = Some(now);
+
+        debug!("[Timelapse] フレーム記録: {} フレーム / {} bytes",
+               self.buffer.frames.len(), self.buffer.bytes_used());
+        Ok(())
+    }
+
+    pub fn status(&self) -> TimelapseStatus {
+        TimelapseStatus {
+            paused: self.paused,
+            frame_count: self.buffer.frames.len(),
+            bytes_used: self.buffer.bytes_used(),
+            storage_budget_bytes: self.config.storage_budget_bytes,
+            thinned: self.buffer.thinned,
+        }
+    }
+}
+
+/// 長辺が max_dimension 以下になるよう縮小（拡大はしない）
+fn downscale(image: &RgbaImage, max_dimension: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let longest = width.max(height);
+    if longest <= max_dimension {
+        return image.clone();
+    }
+    let scale = max_dimension as f64 / longest as f64;
+    let new_width = ((width as f64 * scale).round() as u32).max(1);
+    let new_height = ((height as f64 * scale).round() as u32).max(1);
+    image::imageops::resize(image, new_width, new_height, image::imageops::FilterType::Triangle)
+}
+
+/// タイムラプスをループするGIFアニメーションとして書き出す
+///
+/// 途中でキャンバスサイズが変わった場合は先頭フレームのサイズに揃える。
+pub fn encode_gif<W: Write>(buffer: &TimelapseBuffer, fps: u32, writer: W) -> Result<(), TimelapseError> {
+    let first = buffer.frames.first().ok_or(TimelapseError::NoFrames)?;
+    let (width, height) = (first.width, first.height);
+    let delay = Delay::from_numer_denom_ms(1000, fps.max(1));
+
+    let mut encoder = GifEncoder::new_with_speed(writer, 10);
+    encoder.set_repeat(Repeat::Infinite)
+        .map_err(|e| TimelapseError::EncodeFailed(e.to_string()))?;
+
+    for frame in &buffer.frames {
+        let mut image = image::load_from_memory_with_format(&frame.png, image::ImageFormat::Png)
+            .map_err(|e| TimelapseError::EncodeFailed(e.to_string()))?
+            .to_rgba8();
+        if image.dimensions() != (width, height) {
+            image = image::imageops::resize(&image, width, height, image::imageops::FilterType::Triangle);
+        }
+        encoder.encode_frame(Frame::from_parts(image, 0, 0, delay))
+            .map_err(|e| TimelapseError::EncodeFailed(e.to_string()))?;
+    }
+
+    info!("[Timelapse] GIF書き出し完了: {} フレーム ({}x{}, {}fps)", buffer.frames.len(), width, height, fps);
+    Ok(())
+}
+
+fn serialize_base64<S: Serializer>(bytes: &[u8], serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_str(&STANDARD.encode(bytes))
+}
+
+fn deserialize_base64<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<u8>, D::Error> {
+    let encoded = String::deserialize(deserializer)?;
+    STANDARD.decode(encoded).map_err(serde::de::Error::custom)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(every_operations: Option<u32>, every_seconds: Option<f32>) -> TimelapseConfig {
+        TimelapseConfig { every_operations, every_seconds, ..TimelapseConfig::default() }
+    }
+
+    #[test]
+    fn test_capture_every_n_operations() {
+        let mut recorder = TimelapseRecorder::new(config(Some(3), None), TimelapseBuffer::default()).unwrap();
+        let now = Instant::now();
+        assert!(!recorder.note_operation(now));
+        assert!(!recorder.note_operation(now));
+        assert!(recorder.note_operation(now));
+
+        recorder.add_frame(&RgbaImage::new(4, 4), now).unwrap();
+        assert!(!recorder.note_operation(now));
+    }
+
+    #[test]
+    fn test_capture_every_n_seconds() {
+        let mut recorder = TimelapseRecorder::new(config(None, Some(2.0)), TimelapseBuffer::default()).unwrap();
+        let start = Instant::now();
+        assert!(recorder.note_operation(start));
+        recorder.add_frame(&RgbaImage::new(4, 4), start).unwrap();
+
+        assert!(!recorder.note_operation(start + Duration::from_secs(1)));
+        assert!(recorder.note_operation(start + Duration::from_secs(2)));
+    }
+
+    #[test]
+    fn test_pause_and_resume() {
+        let mut recorder = TimelapseRecorder::new(config(Some(1), None), TimelapseBuffer::default()).unwrap();
+        recorder.pause();
+        assert!(!recorder.note_operation(Instant::now()));
+        recorder.resume();
+        assert!(recorder.note_operation(Instant::now()));
+    }
+
+    #[test]
+    fn test_frames_are_downscaled() {
+        let mut recorder = TimelapseRecorder::new(
+            TimelapseConfig { max_dimension: 8, ..config(Some(1), None) },
+            TimelapseBuffer::default(),
+        ).unwrap();
+        recorder.add_frame(&RgbaImage::new(32, 16), Instant::now()).unwrap();
+
+        let frame = &recorder.buffer().frames[0];
+        assert_eq!((frame.width, frame.height), (8, 4));
+
+        recorder.set_resolution_scale(0.5);
+        recorder.add_frame(&RgbaImage::new(32, 16), Instant::now()).unwrap();
+        let frame = &recorder.buffer().frames[1];
+        assert_eq!((frame.width, frame.height), (4, 2));
+    }
+
+    #[test]
+    fn test_budget_thins_frames_and_keeps_latest() {
+        let mut buffer = TimelapseBuffer::default();
+        for i in 0..8 {
+            buffer.frames.push(TimelapseFrame { timestamp: i, width: 1, height: 1, png: vec![0; 10] });
+        }
+        buffer.enforce_budget(55);
+
+        let timestamps: Vec<i64> = buffer.frames.iter().map(|f| f.timestamp).collect();
+        assert_eq!(timestamps, vec![0, 2, 4, 6, 7]);
+        assert_eq!(buffer.thinned, 1);
+    }
+
+    #[test]
+    fn test_invalid_config() {
+        assert!(config(None, None).validate().is_err());
+        assert!(config(Some(0), None).validate().is_err());
+        assert!(config(None, Some(-1.0)).validate().is_err());
+        assert!(TimelapseConfig::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_encode_gif_and_buffer_json() {
+        let mut recorder = TimelapseRecorder::new(config(Some(1), None), TimelapseBuffer::default()).unwrap();
+        recorder.add_frame(&RgbaImage::from_pixel(4, 4, image::Rgba([255, 0, 0, 255])), Instant::now()).unwrap();
+        recorder.add_frame(&RgbaImage::from_pixel(8, 8, image::Rgba([0, 0, 255, 255])), Instant::now()).unwrap();
+
+        let mut gif = Vec::new();
+        encode_gif(recorder.buffer(), 12, &mut gif).unwrap();
+        assert_eq!(&gif[0..6], b"GIF89a");
+
+        let json = serde_json::to_string(recorder.buffer()).unwrap();
+        let restored: TimelapseBuffer = serde_json::from_str(&json).unwrap();
+        assert_eq!(&restored, recorder.buffer());
+
+        assert!(matches!(encode_gif(&TimelapseBuffer::default(), 12, Vec::new()), Err(TimelapseError::NoFrames)));
+    }
+}